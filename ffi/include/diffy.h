@@ -0,0 +1,160 @@
+#ifndef DIFFY_H
+#define DIFFY_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The outcome of a call
+ */
+typedef enum DiffyStatus {
+  /**
+   * The call succeeded
+   */
+  DIFFY_STATUS_OK = 0,
+  /**
+   * A buffer was `NULL` but its length wasn't 0
+   */
+  DIFFY_STATUS_INVALID_ARGUMENT = 1,
+  /**
+   * The patch couldn't be parsed
+   */
+  DIFFY_STATUS_PARSE_ERROR = 2,
+  /**
+   * The patch doesn't apply to the base buffer
+   */
+  DIFFY_STATUS_APPLY_ERROR = 3,
+  /**
+   * The merge has conflicts, the output holds the merged text with conflict markers
+   */
+  DIFFY_STATUS_CONFLICT = 4,
+  /**
+   * The call panicked
+   */
+  DIFFY_STATUS_PANIC = 5,
+} DiffyStatus;
+
+/**
+ * Options used when creating a patch
+ *
+ * Start from [`diffy_options_default`] so fields added later keep their defaults.
+ */
+typedef struct DiffyOptions {
+  /**
+   * The number of lines of context around each change
+   */
+  size_t context_len;
+  /**
+   * Whether lines are compared ignoring ASCII case
+   */
+  bool ignore_case;
+  /**
+   * Whether lines are compared ignoring their leading whitespace
+   */
+  bool ignore_indentation;
+} DiffyOptions;
+
+/**
+ * The result of a call, released with [`diffy_result_free`]
+ */
+typedef struct DiffyResult {
+  /**
+   * The outcome of the call
+   */
+  enum DiffyStatus status;
+  /**
+   * The output, or `NULL` if the call failed without producing any
+   *
+   * The output is followed by a NUL byte which isn't counted in `len`.
+   */
+  uint8_t *data;
+  /**
+   * The length of the output in bytes
+   */
+  size_t len;
+  /**
+   * A NUL terminated message describing why the call failed, or `NULL` on success
+   */
+  char *error;
+} DiffyResult;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Returns the default options
+ *
+ * ## Defaults
+ * * context_len = 3
+ * * ignore_case = false
+ * * ignore_indentation = false
+ */
+struct DiffyOptions diffy_options_default(void);
+
+/**
+ * Creates a unified patch between `original` and `modified`
+ *
+ * If `options` is `NULL` the defaults from [`diffy_options_default`] are used.
+ *
+ * # Safety
+ *
+ * `original` and `modified` have to be valid for reads of `original_len` and `modified_len`
+ * bytes, or be `NULL`. `options` has to point to a valid [`DiffyOptions`] or be `NULL`.
+ */
+struct DiffyResult diffy_create_patch(const uint8_t *original,
+                                      size_t original_len,
+                                      const uint8_t *modified,
+                                      size_t modified_len,
+                                      const struct DiffyOptions *options);
+
+/**
+ * Applies the unified patch in `patch` to `base`
+ *
+ * # Safety
+ *
+ * `base` and `patch` have to be valid for reads of `base_len` and `patch_len` bytes, or be
+ * `NULL`.
+ */
+struct DiffyResult diffy_apply(const uint8_t *base,
+                               size_t base_len,
+                               const uint8_t *patch,
+                               size_t patch_len);
+
+/**
+ * Performs a 3-way merge of `ours` and `theirs`, which both derive from `ancestor`
+ *
+ * If the merge has conflicts the status is [`DiffyStatus::Conflict`] and the output holds the
+ * merged text with conflict markers.
+ *
+ * # Safety
+ *
+ * `ancestor`, `ours` and `theirs` have to be valid for reads of `ancestor_len`, `ours_len`
+ * and `theirs_len` bytes, or be `NULL`.
+ */
+struct DiffyResult diffy_merge(const uint8_t *ancestor,
+                               size_t ancestor_len,
+                               const uint8_t *ours,
+                               size_t ours_len,
+                               const uint8_t *theirs,
+                               size_t theirs_len);
+
+/**
+ * Releases the output and error message of `result`
+ *
+ * The released pointers are set to `NULL`, so freeing the same result twice is harmless.
+ * `result` may be `NULL`.
+ *
+ * # Safety
+ *
+ * `result` has to point to a [`DiffyResult`] returned by this library, or be `NULL`.
+ */
+void diffy_result_free(struct DiffyResult *result);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* DIFFY_H */
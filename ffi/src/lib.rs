@@ -0,0 +1,295 @@
+//! C bindings for [`diffy`]
+//!
+//! ## Overview
+//!
+//! This crate exposes the core flows of `diffy` through a C API, with the matching header in
+//! `include/diffy.h`:
+//!
+//! - [`diffy_create_patch`] creates a unified patch between two buffers
+//! - [`diffy_apply`] applies a unified patch to a base buffer
+//! - [`diffy_merge`] performs a 3-way merge between three buffers
+//!
+//! Buffers are passed as a pointer and a length and don't have to be utf8. A `NULL` pointer
+//! with a length of 0 is treated as an empty buffer.
+//!
+//! ## Ownership
+//!
+//! Every function returns a [`DiffyResult`] which owns its output and error message, and which
+//! has to be released with [`diffy_result_free`] once it's no longer needed. On success the
+//! output is followed by a NUL byte which isn't counted in its length, so text can be used as a
+//! C string as is.
+//!
+//! No panic ever unwinds into the caller, a panic is reported as [`DiffyStatus::Panic`]
+//! instead.
+
+use diffy::{DiffOptions, MergeOptions, Patch};
+use std::{
+    any::Any,
+    ffi::CString,
+    os::raw::c_char,
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+};
+
+/// The outcome of a call
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffyStatus {
+    /// The call succeeded
+    Ok = 0,
+    /// A buffer was `NULL` but its length wasn't 0
+    InvalidArgument = 1,
+    /// The patch couldn't be parsed
+    ParseError = 2,
+    /// The patch doesn't apply to the base buffer
+    ApplyError = 3,
+    /// The merge has conflicts, the output holds the merged text with conflict markers
+    Conflict = 4,
+    /// The call panicked
+    Panic = 5,
+}
+
+/// Options used when creating a patch
+///
+/// Start from [`diffy_options_default`] so fields added later keep their defaults.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DiffyOptions {
+    /// The number of lines of context around each change
+    pub context_len: usize,
+    /// Whether lines are compared ignoring ASCII case
+    pub ignore_case: bool,
+    /// Whether lines are compared ignoring their leading whitespace
+    pub ignore_indentation: bool,
+}
+
+/// The result of a call, released with [`diffy_result_free`]
+#[repr(C)]
+#[derive(Debug)]
+pub struct DiffyResult {
+    /// The outcome of the call
+    pub status: DiffyStatus,
+    /// The output, or `NULL` if the call failed without producing any
+    ///
+    /// The output is followed by a NUL byte which isn't counted in `len`.
+    pub data: *mut u8,
+    /// The length of the output in bytes
+    pub len: usize,
+    /// A NUL terminated message describing why the call failed, or `NULL` on success
+    pub error: *mut c_char,
+}
+
+/// Returns the default options
+///
+/// ## Defaults
+/// * context_len = 3
+/// * ignore_case = false
+/// * ignore_indentation = false
+#[no_mangle]
+pub extern "C" fn diffy_options_default() -> DiffyOptions {
+    DiffyOptions {
+        context_len: 3,
+        ignore_case: false,
+        ignore_indentation: false,
+    }
+}
+
+/// Creates a unified patch between `original` and `modified`
+///
+/// If `options` is `NULL` the defaults from [`diffy_options_default`] are used.
+///
+/// # Safety
+///
+/// `original` and `modified` have to be valid for reads of `original_len` and `modified_len`
+/// bytes, or be `NULL`. `options` has to point to a valid [`DiffyOptions`] or be `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn diffy_create_patch(
+    original: *const u8,
+    original_len: usize,
+    modified: *const u8,
+    modified_len: usize,
+    options: *const DiffyOptions,
+) -> DiffyResult {
+    guard(|| {
+        let original = unsafe { buffer(original, original_len, "original") }?;
+        let modified = unsafe { buffer(modified, modified_len, "modified") }?;
+        let options = unsafe { options.as_ref() }
+            .copied()
+            .unwrap_or_else(|| diffy_options_default());
+
+        let patch = DiffOptions::new()
+            .set_context_len(options.context_len)
+            .set_ignore_case(options.ignore_case)
+            .set_ignore_indentation(options.ignore_indentation)
+            .create_patch_bytes(original, modified);
+        Ok(patch.to_bytes())
+    })
+}
+
+/// Applies the unified patch in `patch` to `base`
+///
+/// # Safety
+///
+/// `base` and `patch` have to be valid for reads of `base_len` and `patch_len` bytes, or be
+/// `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn diffy_apply(
+    base: *const u8,
+    base_len: usize,
+    patch: *const u8,
+    patch_len: usize,
+) -> DiffyResult {
+    guard(|| {
+        let base = unsafe { buffer(base, base_len, "base") }?;
+        let patch = unsafe { buffer(patch, patch_len, "patch") }?;
+
+        let patch = Patch::from_bytes(patch)
+            .map_err(|error| Failure::new(DiffyStatus::ParseError, error))?;
+        diffy::apply_bytes(base, &patch)
+            .map_err(|error| Failure::new(DiffyStatus::ApplyError, error))
+    })
+}
+
+/// Performs a 3-way merge of `ours` and `theirs`, which both derive from `ancestor`
+///
+/// If the merge has conflicts the status is [`DiffyStatus::Conflict`] and the output holds the
+/// merged text with conflict markers.
+///
+/// # Safety
+///
+/// `ancestor`, `ours` and `theirs` have to be valid for reads of `ancestor_len`, `ours_len`
+/// and `theirs_len` bytes, or be `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn diffy_merge(
+    ancestor: *const u8,
+    ancestor_len: usize,
+    ours: *const u8,
+    ours_len: usize,
+    theirs: *const u8,
+    theirs_len: usize,
+) -> DiffyResult {
+    guard(|| {
+        let ancestor = unsafe { buffer(ancestor, ancestor_len, "ancestor") }?;
+        let ours = unsafe { buffer(ours, ours_len, "ours") }?;
+        let theirs = unsafe { buffer(theirs, theirs_len, "theirs") }?;
+
+        MergeOptions::new()
+            .merge_bytes(ancestor, ours, theirs)
+            .map_err(|merged| Failure {
+                status: DiffyStatus::Conflict,
+                message: "merge conflict".into(),
+                output: Some(merged),
+            })
+    })
+}
+
+/// Releases the output and error message of `result`
+///
+/// The released pointers are set to `NULL`, so freeing the same result twice is harmless.
+/// `result` may be `NULL`.
+///
+/// # Safety
+///
+/// `result` has to point to a [`DiffyResult`] returned by this library, or be `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn diffy_result_free(result: *mut DiffyResult) {
+    let result = match unsafe { result.as_mut() } {
+        Some(result) => result,
+        None => return,
+    };
+
+    if !result.data.is_null() {
+        // The output was allocated with a trailing NUL byte
+        let data = ptr::slice_from_raw_parts_mut(result.data, result.len + 1);
+        drop(unsafe { Box::from_raw(data) });
+    }
+    if !result.error.is_null() {
+        drop(unsafe { CString::from_raw(result.error) });
+    }
+    result.data = ptr::null_mut();
+    result.len = 0;
+    result.error = ptr::null_mut();
+}
+
+// Why a call failed, along with any output it produced anyway
+struct Failure {
+    status: DiffyStatus,
+    message: String,
+    output: Option<Vec<u8>>,
+}
+
+impl Failure {
+    fn new(status: DiffyStatus, error: impl ToString) -> Self {
+        Self {
+            status,
+            message: error.to_string(),
+            output: None,
+        }
+    }
+}
+
+// Runs a call, catching any panic so it doesn't unwind across the C boundary
+fn guard(f: impl FnOnce() -> Result<Vec<u8>, Failure>) -> DiffyResult {
+    let outcome = panic::catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|payload| Err(Failure::new(DiffyStatus::Panic, panic_message(&*payload))));
+
+    match outcome {
+        Ok(output) => {
+            let (data, len) = into_raw_buffer(output);
+            DiffyResult {
+                status: DiffyStatus::Ok,
+                data,
+                len,
+                error: ptr::null_mut(),
+            }
+        }
+        Err(failure) => {
+            let (data, len) = match failure.output {
+                Some(output) => into_raw_buffer(output),
+                None => (ptr::null_mut(), 0),
+            };
+            // Error messages are built from patch contents, which may contain NUL bytes
+            let message = CString::new(failure.message.replace('\0', "\\0"))
+                .expect("NUL bytes have been escaped");
+            DiffyResult {
+                status: failure.status,
+                data,
+                len,
+                error: message.into_raw(),
+            }
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic");
+    format!("panicked: {}", message)
+}
+
+// Hands an output over to C, with a trailing NUL byte which isn't counted in its length
+fn into_raw_buffer(mut output: Vec<u8>) -> (*mut u8, usize) {
+    let len = output.len();
+    output.push(0);
+    let data = Box::into_raw(output.into_boxed_slice());
+    (data as *mut u8, len)
+}
+
+// Borrows a buffer passed in from C, treating `NULL` with a length of 0 as empty
+unsafe fn buffer<'a>(data: *const u8, len: usize, name: &str) -> Result<&'a [u8], Failure> {
+    if data.is_null() {
+        if len == 0 {
+            Ok(&[])
+        } else {
+            Err(Failure::new(
+                DiffyStatus::InvalidArgument,
+                format!("`{}` is NULL but its length is {}", name, len),
+            ))
+        }
+    } else {
+        Ok(unsafe { slice::from_raw_parts(data, len) })
+    }
+}
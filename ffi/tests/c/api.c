@@ -0,0 +1,132 @@
+#include "diffy.h"
+
+#include <stdio.h>
+#include <string.h>
+
+#define CHECK(cond)                                                          \
+  do {                                                                       \
+    if (!(cond)) {                                                           \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond); \
+      exit(1);                                                               \
+    }                                                                        \
+  } while (0)
+
+#define BUF(s) (const uint8_t *)(s), strlen(s)
+
+static void check_output(const DiffyResult *result, const char *expected) {
+  CHECK(result->data != NULL);
+  CHECK(result->len == strlen(expected));
+  CHECK(memcmp(result->data, expected, result->len) == 0);
+  /* The output is NUL terminated */
+  CHECK(strcmp((const char *)result->data, expected) == 0);
+}
+
+static void create_and_apply(void) {
+  const char *original = "a\nb\nc\n";
+  const char *modified = "a\nB\nc\n";
+
+  DiffyResult patch = diffy_create_patch(BUF(original), BUF(modified), NULL);
+  CHECK(patch.status == DIFFY_STATUS_OK);
+  CHECK(patch.error == NULL);
+  check_output(&patch,
+               "--- original\n"
+               "+++ modified\n"
+               "@@ -1,3 +1,3 @@\n"
+               " a\n"
+               "-b\n"
+               "+B\n"
+               " c\n");
+
+  DiffyResult applied = diffy_apply(BUF(original), patch.data, patch.len);
+  CHECK(applied.status == DIFFY_STATUS_OK);
+  check_output(&applied, modified);
+  diffy_result_free(&applied);
+
+  DiffyResult failed = diffy_apply(BUF("x\ny\nz\n"), patch.data, patch.len);
+  CHECK(failed.status == DIFFY_STATUS_APPLY_ERROR);
+  CHECK(failed.data == NULL);
+  CHECK(failed.error != NULL);
+  diffy_result_free(&failed);
+
+  diffy_result_free(&patch);
+  CHECK(patch.data == NULL && patch.len == 0 && patch.error == NULL);
+  /* Freeing twice is harmless */
+  diffy_result_free(&patch);
+  diffy_result_free(NULL);
+}
+
+static void options(void) {
+  DiffyOptions options = diffy_options_default();
+  CHECK(options.context_len == 3);
+  CHECK(!options.ignore_case);
+
+  options.ignore_case = true;
+  DiffyResult patch =
+      diffy_create_patch(BUF("Hello\nWorld\n"), BUF("hello\nworld\n"), &options);
+  CHECK(patch.status == DIFFY_STATUS_OK);
+  check_output(&patch, "--- original\n+++ modified\n");
+  diffy_result_free(&patch);
+
+  options.ignore_case = false;
+  options.context_len = 0;
+  patch = diffy_create_patch(BUF("a\nb\nc\n"), BUF("a\nB\nc\n"), &options);
+  CHECK(patch.status == DIFFY_STATUS_OK);
+  check_output(&patch, "--- original\n+++ modified\n@@ -2 +2 @@\n-b\n+B\n");
+  diffy_result_free(&patch);
+}
+
+static void parse_error(void) {
+  DiffyResult result =
+      diffy_apply(BUF("a\n"), BUF("--- a\n+++ b\n@@ -1 +1 @@\n-a\n-b\n+c\n"));
+  CHECK(result.status == DIFFY_STATUS_PARSE_ERROR);
+  CHECK(result.data == NULL);
+  CHECK(result.error != NULL && strlen(result.error) > 0);
+  diffy_result_free(&result);
+}
+
+static void merge(void) {
+  const char *ancestor = "one\ntwo\nthree\n";
+
+  DiffyResult merged =
+      diffy_merge(BUF(ancestor), BUF("ONE\ntwo\nthree\n"), BUF("one\ntwo\nTHREE\n"));
+  CHECK(merged.status == DIFFY_STATUS_OK);
+  CHECK(merged.error == NULL);
+  check_output(&merged, "ONE\ntwo\nTHREE\n");
+  diffy_result_free(&merged);
+
+  DiffyResult conflict =
+      diffy_merge(BUF(ancestor), BUF("one\nTWO\nthree\n"), BUF("one\n2\nthree\n"));
+  CHECK(conflict.status == DIFFY_STATUS_CONFLICT);
+  CHECK(conflict.error != NULL);
+  CHECK(conflict.data != NULL);
+  CHECK(strstr((const char *)conflict.data, "<<<<<<<") != NULL);
+  CHECK(strstr((const char *)conflict.data, ">>>>>>>") != NULL);
+  diffy_result_free(&conflict);
+}
+
+static void null_buffers(void) {
+  DiffyResult result = diffy_create_patch(NULL, 0, BUF("a\n"), NULL);
+  CHECK(result.status == DIFFY_STATUS_OK);
+  check_output(&result, "--- original\n+++ modified\n@@ -0,0 +1 @@\n+a\n");
+  diffy_result_free(&result);
+
+  result = diffy_merge(NULL, 0, NULL, 0, NULL, 0);
+  CHECK(result.status == DIFFY_STATUS_OK);
+  check_output(&result, "");
+  diffy_result_free(&result);
+
+  result = diffy_apply(NULL, 1, BUF(""));
+  CHECK(result.status == DIFFY_STATUS_INVALID_ARGUMENT);
+  CHECK(result.data == NULL);
+  CHECK(result.error != NULL);
+  diffy_result_free(&result);
+}
+
+int main(void) {
+  create_and_apply();
+  options();
+  parse_error();
+  merge();
+  null_buffers();
+  return 0;
+}
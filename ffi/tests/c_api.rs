@@ -0,0 +1,34 @@
+// Builds the C program in `tests/c` against `include/diffy.h` and the cdylib, then runs it
+#![cfg(unix)]
+
+use std::{env, path::Path, process::Command};
+
+#[test]
+fn c_program() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    // Test binaries live in `target/<profile>/deps`, next to the libraries they're built with
+    let exe = env::current_exe().unwrap();
+    let lib_dir = exe.parent().unwrap();
+    let program = lib_dir.join("diffy_ffi_c_api");
+
+    let cc = env::var("CC").unwrap_or_else(|_| "cc".to_owned());
+    let status = Command::new(&cc)
+        .arg(manifest_dir.join("tests/c/api.c"))
+        .arg("-std=c99")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg("-L")
+        .arg(lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-ldiffy_ffi")
+        .arg("-o")
+        .arg(&program)
+        .status()
+        .unwrap_or_else(|error| panic!("failed to run `{}`: {}", cc, error));
+    assert!(status.success(), "failed to compile the C test program");
+
+    let status = Command::new(&program).status().unwrap();
+    assert!(status.success(), "the C test program failed");
+}
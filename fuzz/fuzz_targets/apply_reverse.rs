@@ -0,0 +1,12 @@
+#![no_main]
+
+use diffy::{apply, ApplicablePatch};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|case: ApplicablePatch<'_>| {
+    let modified = apply(&case.base, &case.patch).expect("patch should apply to its base");
+    assert_eq!(modified, case.modified);
+
+    let base = apply(&modified, &case.patch.reverse()).expect("reverse patch should apply");
+    assert_eq!(base, case.base);
+});
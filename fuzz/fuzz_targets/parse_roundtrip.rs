@@ -0,0 +1,11 @@
+#![no_main]
+
+use diffy::Patch;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|patch: Patch<'_, str>| {
+    let s = patch.to_string();
+    let parsed = Patch::from_str(&s).expect("formatted patch should parse");
+    assert_eq!(parsed, patch);
+    assert_eq!(patch.to_bytes(), s.as_bytes());
+});
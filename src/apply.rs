@@ -1,43 +1,70 @@
 use crate::{
     patch::{Hunk, Line, Patch},
-    utils::LineIter,
+    utils::{LineIter, Text},
 };
-use std::{fmt, iter};
+use alloc::{
+    borrow::{Cow, ToOwned},
+    string::String,
+    vec::Vec,
+};
+use core::{fmt, iter, ops};
 
 /// An error returned when [`apply`]ing a `Patch` fails
 ///
 /// [`apply`]: fn.apply.html
-#[derive(Debug)]
-pub struct ApplyError(usize);
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ApplyError {
+    /// No position could be found in the base image where the lines of a hunk match
+    HunkMismatch {
+        /// The 1-based index of the hunk within the patch
+        hunk: usize,
+    },
+}
+
+impl ApplyError {
+    /// Returns the 1-based index of the hunk which failed to apply
+    pub fn hunk(&self) -> usize {
+        match self {
+            ApplyError::HunkMismatch { hunk } => *hunk,
+        }
+    }
+
+    /// Returns `true` if a hunk's lines couldn't be found in the base image
+    pub fn is_hunk_mismatch(&self) -> bool {
+        matches!(self, ApplyError::HunkMismatch { .. })
+    }
+}
 
 impl fmt::Display for ApplyError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "error applying hunk #{}", self.0)
+        match self {
+            ApplyError::HunkMismatch { hunk } => write!(f, "error applying hunk #{}", hunk),
+        }
     }
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for ApplyError {}
 
 #[derive(Debug)]
 enum ImageLine<'a, T: ?Sized> {
-    Unpatched(&'a T),
+    // A line which hasn't been touched by the patch being applied, along with its index in the
+    // image from before the patch was applied
+    Unpatched(&'a T, usize),
     Patched(&'a T),
 }
 
 impl<'a, T: ?Sized> ImageLine<'a, T> {
     fn inner(&self) -> &'a T {
         match self {
-            ImageLine::Unpatched(inner) | ImageLine::Patched(inner) => inner,
+            ImageLine::Unpatched(inner, _) | ImageLine::Patched(inner) => inner,
         }
     }
 
-    fn into_inner(self) -> &'a T {
-        self.inner()
-    }
-
     fn is_patched(&self) -> bool {
         match self {
-            ImageLine::Unpatched(_) => false,
+            ImageLine::Unpatched(..) => false,
             ImageLine::Patched(_) => true,
         }
     }
@@ -51,6 +78,296 @@ impl<T: ?Sized> Clone for ImageLine<'_, T> {
     }
 }
 
+// The lines of a base image which patches are applied to, borrowed from the base image and the
+// patches themselves
+pub(crate) struct Image<'a, T: ?Sized> {
+    lines: Vec<ImageLine<'a, T>>,
+    // Whether the image ends in a newline, when that differs from what its last line says
+    eof_newline: Option<bool>,
+}
+
+impl<'a, T: Text + ?Sized> Image<'a, T> {
+    pub(crate) fn new(base_image: &'a T) -> Self {
+        Self {
+            lines: LineIter::new(base_image)
+                .enumerate()
+                .map(|(idx, line)| ImageLine::Unpatched(line, idx))
+                .collect(),
+            eof_newline: None,
+        }
+    }
+
+    // Builds an image from the lines of a text which isn't stored contiguously
+    #[cfg(feature = "ropey")]
+    pub(crate) fn from_lines(lines: impl IntoIterator<Item = &'a T>) -> Self {
+        Self {
+            lines: lines
+                .into_iter()
+                .enumerate()
+                .map(|(idx, line)| ImageLine::Unpatched(line, idx))
+                .collect(),
+            eof_newline: None,
+        }
+    }
+
+    // Returns whether the patched text should end in a newline, if that differs from what its
+    // last line says
+    #[cfg(feature = "ropey")]
+    pub(crate) fn eof_newline(&self) -> Option<bool> {
+        self.eof_newline
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    // Returns the lines of the image. Unless a trailing newline mismatch was ignored while
+    // applying, these make up the whole patched text.
+    pub(crate) fn lines(&self) -> impl Iterator<Item = &'a T> + '_ {
+        self.lines.iter().map(ImageLine::inner)
+    }
+
+    // Applies each hunk of the patch in turn, returning the range of lines of the patched image
+    // each hunk wrote. On error the hunks before the failing one are left applied.
+    pub(crate) fn apply(
+        &mut self,
+        patch: &'a Patch<'a, T>,
+        options: &ApplyOptions,
+    ) -> Result<Vec<ops::Range<usize>>, ApplyError>
+    where
+        T: ToOwned,
+    {
+        let mut spans: Vec<ops::Range<usize>> = Vec::new();
+        for (i, hunk) in patch.hunks().iter().enumerate() {
+            let pos = self
+                .apply_hunk(hunk, options)
+                .map_err(|_| ApplyError::HunkMismatch { hunk: i + 1 })?;
+            let old_len = hunk.old_range().len();
+            let new_len = hunk.new_lines().count();
+            let end = pos + old_len;
+
+            // The spans of the earlier hunks after this one move by the number of lines it added.
+            // An empty span where this hunk starts stays before it. Lines written by an earlier
+            // hunk are never replaced, but a hunk found away from its header may cover the empty
+            // span of an earlier hunk which only deleted lines, so any part of a span inside the
+            // replaced lines is dropped.
+            for span in &mut spans {
+                if span.end <= pos {
+                    continue;
+                }
+                let start = if span.start < pos {
+                    span.start
+                } else if span.start >= end {
+                    span.start - old_len + new_len
+                } else {
+                    pos + new_len
+                };
+                let span_end = if span.end >= end {
+                    span.end - old_len + new_len
+                } else {
+                    pos
+                };
+                // A span entirely replaced by this hunk is left empty where this hunk starts
+                *span = if start <= span_end {
+                    start..span_end
+                } else {
+                    pos..pos
+                };
+            }
+            spans.push(pos..pos + new_len);
+        }
+        Ok(spans)
+    }
+
+    // Returns, for each line, its index from before the patch being applied or `None` if the
+    // line was written by the patch
+    pub(crate) fn origins(&self) -> impl Iterator<Item = Option<usize>> + '_ {
+        self.lines.iter().map(|line| match line {
+            ImageLine::Unpatched(_, idx) => Some(*idx),
+            ImageLine::Patched(_) => None,
+        })
+    }
+
+    // Marks every line as unpatched, so that another patch can be applied on top
+    pub(crate) fn reset(&mut self) {
+        for (idx, line) in self.lines.iter_mut().enumerate() {
+            *line = ImageLine::Unpatched(line.inner(), idx);
+        }
+    }
+
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut result: Vec<u8> = self
+            .lines
+            .iter()
+            .flat_map(|line| line.inner().as_bytes())
+            .copied()
+            .collect();
+        match self.eof_newline {
+            Some(true) if !result.ends_with(b"\n") => result.push(b'\n'),
+            Some(false) if result.ends_with(b"\n") => {
+                result.pop();
+            }
+            _ => {}
+        }
+        result
+    }
+
+    // Returns the pieces making up the patched text, and whether a newline has to be added
+    // after them. Lines which follow each other in the base image are joined into a single slice
+    // of it, so an unchanged stretch of the base image is a single piece however many lines it
+    // has.
+    fn pieces(&self, base_image: &'a T) -> (Vec<&'a T>, bool) {
+        let base = base_image.as_bytes();
+        let mut pieces = Vec::new();
+        let mut run: Option<(usize, usize)> = None;
+        let flush = |pieces: &mut Vec<&'a T>, run: Option<(usize, usize)>| {
+            if let Some((start, end)) = run {
+                pieces.push(base_image.split_at(end).0.split_at(start).1);
+            }
+        };
+
+        for line in self.lines.iter().map(ImageLine::inner) {
+            match offset_in(base, line.as_bytes()) {
+                Some(start) => match &mut run {
+                    Some((_, end)) if *end == start => *end += line.len(),
+                    _ => {
+                        flush(&mut pieces, run);
+                        run = Some((start, start + line.len()));
+                    }
+                },
+                None => {
+                    flush(&mut pieces, run.take());
+                    pieces.push(line);
+                }
+            }
+        }
+        flush(&mut pieces, run);
+
+        let ends_with_newline = pieces.last().map_or(false, |piece| piece.ends_with("\n"));
+        match self.eof_newline {
+            Some(true) if !ends_with_newline => return (pieces, true),
+            Some(false) if ends_with_newline => {
+                let last = pieces.pop().unwrap();
+                pieces.push(last.split_at(last.len() - 1).0);
+            }
+            _ => {}
+        }
+        (pieces, false)
+    }
+
+    // Turns the spans of lines written by each hunk into `HunkSpan`s, given the length of the
+    // rendered image
+    fn hunk_spans(&self, spans: Vec<ops::Range<usize>>, len: usize) -> Vec<HunkSpan> {
+        let mut offsets = Vec::with_capacity(self.lines.len() + 1);
+        offsets.push(0);
+        for line in &self.lines {
+            offsets.push(offsets.last().unwrap() + line.inner().len());
+        }
+        // The end of the rendered image may have gained or lost a newline
+        *offsets.last_mut().unwrap() = len;
+
+        spans
+            .into_iter()
+            .map(|lines| HunkSpan {
+                bytes: offsets[lines.start]..offsets[lines.end],
+                lines,
+            })
+            .collect()
+    }
+
+    // Applies a hunk, returning the position in the image it was applied at
+    fn apply_hunk(&mut self, hunk: &Hunk<'a, T>, options: &ApplyOptions) -> Result<usize, ()> {
+        // Find position
+        let pos = find_position(&self.lines, hunk, options.ignore_trailing_newline).ok_or(())?;
+        let end = pos + hunk.old_range().len();
+        let eof_newline = self
+            .eof_newline
+            .or_else(|| self.lines.last().map(|line| line.inner().ends_with("\n")));
+
+        // update image, keeping the image's own context lines so that they still point into the
+        // base image
+        let at_eof = end == self.lines.len();
+        let mut old_lines = self.lines[pos..end].iter().map(ImageLine::inner);
+        let new_lines: Vec<_> = hunk
+            .lines()
+            .iter()
+            .filter_map(|line| match *line {
+                Line::Context(line) => {
+                    let old = old_lines.next().unwrap();
+                    Some(if old == line { old } else { line })
+                }
+                Line::Delete(_) => {
+                    old_lines.next();
+                    None
+                }
+                Line::Insert(line) => Some(line),
+            })
+            .map(ImageLine::Patched)
+            .collect();
+        self.lines.splice(pos..end, new_lines);
+        if !at_eof {
+            return Ok(pos);
+        }
+
+        // When the image disagrees with the hunk about the newline at the end of the file, keep
+        // the image's convention unless the hunk changes it
+        let old_newline = hunk.old_lines().last().map(|line| line.ends_with("\n"));
+        let new_newline = hunk.new_lines().last().map(|line| line.ends_with("\n"));
+        self.eof_newline = match (old_newline, new_newline) {
+            (Some(old), Some(new)) if old == new && eof_newline != Some(new) => eof_newline,
+            _ => None,
+        };
+        Ok(pos)
+    }
+}
+
+impl Image<'_, str> {
+    pub(crate) fn render(&self) -> String {
+        let mut result: String = self.lines.iter().map(ImageLine::inner).collect();
+        match self.eof_newline {
+            Some(true) if !result.ends_with('\n') => result.push('\n'),
+            Some(false) if result.ends_with('\n') => {
+                result.pop();
+            }
+            _ => {}
+        }
+        result
+    }
+}
+
+// Returns the position of `part` within `whole`, if it's a slice of it
+fn offset_in(whole: &[u8], part: &[u8]) -> Option<usize> {
+    let start = (part.as_ptr() as usize).checked_sub(whole.as_ptr() as usize)?;
+    (start + part.len() <= whole.len()).then_some(start)
+}
+
+// Returns the patched text as a slice of the base image, if it's made up of a single piece of it
+fn base_slice<'b, T: Text + ?Sized>(
+    base_image: &'b T,
+    (pieces, add_newline): &(Vec<&T>, bool),
+) -> Option<&'b T> {
+    if *add_newline {
+        return None;
+    }
+    match pieces[..] {
+        [] => Some(base_image.split_at(0).0),
+        [piece] => {
+            let start = offset_in(base_image.as_bytes(), piece.as_bytes())?;
+            Some(base_image.split_at(start + piece.len()).0.split_at(start).1)
+        }
+        _ => None,
+    }
+}
+
+impl<T: ?Sized> Clone for Image<'_, T> {
+    fn clone(&self) -> Self {
+        Self {
+            lines: self.lines.clone(),
+            eof_newline: self.eof_newline,
+        }
+    }
+}
+
 /// Apply a `Patch` to a base image
 ///
 /// ```
@@ -89,48 +406,258 @@ impl<T: ?Sized> Clone for ImageLine<'_, T> {
 /// assert_eq!(apply(base_image, &patch).unwrap(), expected);
 /// ```
 pub fn apply(base_image: &str, patch: &Patch<'_, str>) -> Result<String, ApplyError> {
-    let mut image: Vec<_> = LineIter::new(base_image)
-        .map(ImageLine::Unpatched)
-        .collect();
-
-    for (i, hunk) in patch.hunks().iter().enumerate() {
-        apply_hunk(&mut image, hunk).map_err(|_| ApplyError(i + 1))?;
-    }
-
-    Ok(image.into_iter().map(ImageLine::into_inner).collect())
+    ApplyOptions::new().apply(base_image, patch)
 }
 
 /// Apply a non-utf8 `Patch` to a base image
 pub fn apply_bytes(base_image: &[u8], patch: &Patch<'_, [u8]>) -> Result<Vec<u8>, ApplyError> {
-    let mut image: Vec<_> = LineIter::new(base_image)
-        .map(ImageLine::Unpatched)
-        .collect();
+    ApplyOptions::new().apply_bytes(base_image, patch)
+}
 
-    for (i, hunk) in patch.hunks().iter().enumerate() {
-        apply_hunk(&mut image, hunk).map_err(|_| ApplyError(i + 1))?;
-    }
+/// Apply a `Patch` to a base image, borrowing the base image where possible
+///
+/// See [`ApplyOptions::apply_cow`](struct.ApplyOptions.html#method.apply_cow).
+pub fn apply_cow<'a>(
+    base_image: &'a str,
+    patch: &Patch<'_, str>,
+) -> Result<Cow<'a, str>, ApplyError> {
+    ApplyOptions::new().apply_cow(base_image, patch)
+}
 
-    Ok(image
-        .into_iter()
-        .flat_map(ImageLine::into_inner)
-        .copied()
-        .collect())
+/// Apply a non-utf8 `Patch` to a base image, borrowing the base image where possible
+///
+/// See [`ApplyOptions::apply_cow`](struct.ApplyOptions.html#method.apply_cow).
+pub fn apply_bytes_cow<'a>(
+    base_image: &'a [u8],
+    patch: &Patch<'_, [u8]>,
+) -> Result<Cow<'a, [u8]>, ApplyError> {
+    ApplyOptions::new().apply_bytes_cow(base_image, patch)
 }
 
-fn apply_hunk<'a, T: PartialEq + ?Sized>(
-    image: &mut Vec<ImageLine<'a, T>>,
-    hunk: &Hunk<'a, T>,
-) -> Result<(), ()> {
-    // Find position
-    let pos = find_position(image, hunk).ok_or(())?;
+/// Where the lines written by a hunk ended up in a patched text, see
+/// [`ApplyOptions::apply_with_spans`]
+///
+/// A hunk writes its context and inserted lines, so the span covers exactly those lines. It
+/// takes into account where the hunk was actually applied, which may differ from the position
+/// in its header, and the lines added or removed by the other hunks of the patch. A hunk which
+/// only deletes lines has an empty span, positioned where the lines were, or where a later hunk
+/// replacing the lines around that position starts.
+///
+/// [`ApplyOptions::apply_with_spans`]: struct.ApplyOptions.html#method.apply_with_spans
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct HunkSpan {
+    /// The range of 0-based indices of the lines in the patched text
+    pub lines: ops::Range<usize>,
+    /// The range of bytes in the patched text
+    pub bytes: ops::Range<usize>,
+}
+
+/// A collection of options for modifying the way a patch is applied
+#[derive(Debug)]
+pub struct ApplyOptions {
+    ignore_trailing_newline: bool,
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) force_deletions: bool,
+}
+
+impl ApplyOptions {
+    /// Constructs a new `ApplyOptions` with default settings
+    ///
+    /// ## Defaults
+    /// * ignore_trailing_newline = false
+    /// * force_deletions = false
+    pub fn new() -> Self {
+        Self {
+            ignore_trailing_newline: false,
+            force_deletions: false,
+        }
+    }
+
+    /// Set whether the last line of the base image may match a line of a hunk which only
+    /// differs from it by a trailing newline
+    ///
+    /// This lets a patch apply to a file which disagrees with it on whether there is a newline
+    /// at the end of the file. The result keeps the base image's convention, unless the patch
+    /// itself adds or removes the newline at the end of the file.
+    ///
+    /// ```
+    /// use diffy::{create_patch, ApplyOptions};
+    ///
+    /// let patch = create_patch("a\nb\n", "a\nB\n");
+    ///
+    /// let mut options = ApplyOptions::new();
+    /// assert!(options.apply("a\nb", &patch).is_err());
+    /// options.set_ignore_trailing_newline(true);
+    /// assert_eq!(options.apply("a\nb", &patch).unwrap(), "a\nB");
+    /// ```
+    pub fn set_ignore_trailing_newline(&mut self, ignore_trailing_newline: bool) -> &mut Self {
+        self.ignore_trailing_newline = ignore_trailing_newline;
+        self
+    }
+
+    /// Set whether [`PatchSet::apply_to_map`] deletes a file even if its contents don't match
+    /// the ones the patch expects to delete
+    ///
+    /// [`PatchSet::apply_to_map`]: struct.PatchSet.html#method.apply_to_map
+    pub fn set_force_deletions(&mut self, force_deletions: bool) -> &mut Self {
+        self.force_deletions = force_deletions;
+        self
+    }
+
+    /// Apply a `Patch` to a base image based on the configured options
+    pub fn apply(&self, base_image: &str, patch: &Patch<'_, str>) -> Result<String, ApplyError> {
+        let mut image = Image::new(base_image);
+        image.apply(patch, self)?;
+        Ok(image.render())
+    }
+
+    /// Apply a non-utf8 `Patch` to a base image based on the configured options
+    pub fn apply_bytes(
+        &self,
+        base_image: &[u8],
+        patch: &Patch<'_, [u8]>,
+    ) -> Result<Vec<u8>, ApplyError> {
+        let mut image = Image::new(base_image);
+        image.apply(patch, self)?;
+        Ok(image.to_bytes())
+    }
+
+    /// Apply a `Patch` to a base image based on the configured options, borrowing the base
+    /// image where possible
+    ///
+    /// The result is borrowed from the base image when the patch has no hunks, or when it only
+    /// removes lines from the start or end of the base image. Otherwise the patched text is
+    /// assembled from unchanged stretches of the base image and the lines written by the patch,
+    /// and allocated once at its final size, where [`apply`] reallocates its result while it
+    /// grows. Unless the patch has no hunks, the base image is still split into lines to find
+    /// where the hunks apply, which takes a few words of memory per line on top of the result.
+    ///
+    /// ```
+    /// use diffy::{create_patch, ApplyOptions, Patch};
+    /// use std::borrow::Cow;
+    ///
+    /// let base_image = "a\nb\nc\n";
+    /// let options = ApplyOptions::new();
+    ///
+    /// let patch = Patch::from_str("--- a\n+++ b\n").unwrap();
+    /// assert!(matches!(options.apply_cow(base_image, &patch), Ok(Cow::Borrowed("a\nb\nc\n"))));
+    ///
+    /// let patch = create_patch(base_image, "a\nb\n");
+    /// assert!(matches!(options.apply_cow(base_image, &patch), Ok(Cow::Borrowed("a\nb\n"))));
+    ///
+    /// let patch = create_patch(base_image, "a\nB\nc\n");
+    /// assert_eq!(options.apply_cow(base_image, &patch).unwrap(), "a\nB\nc\n");
+    /// ```
+    ///
+    /// [`apply`]: #method.apply
+    pub fn apply_cow<'a>(
+        &self,
+        base_image: &'a str,
+        patch: &Patch<'_, str>,
+    ) -> Result<Cow<'a, str>, ApplyError> {
+        if patch.hunks().is_empty() {
+            return Ok(Cow::Borrowed(base_image));
+        }
+        let mut image = Image::new(base_image);
+        image.apply(patch, self)?;
+        let pieces = image.pieces(base_image);
+        if let Some(text) = base_slice(base_image, &pieces) {
+            return Ok(Cow::Borrowed(text));
+        }
+        let (mut pieces, add_newline) = pieces;
+        if add_newline {
+            pieces.push("\n");
+        }
+        Ok(Cow::Owned(pieces.concat()))
+    }
+
+    /// Apply a non-utf8 `Patch` to a base image based on the configured options, borrowing the
+    /// base image where possible
+    ///
+    /// See [`apply_cow`](#method.apply_cow).
+    pub fn apply_bytes_cow<'a>(
+        &self,
+        base_image: &'a [u8],
+        patch: &Patch<'_, [u8]>,
+    ) -> Result<Cow<'a, [u8]>, ApplyError> {
+        if patch.hunks().is_empty() {
+            return Ok(Cow::Borrowed(base_image));
+        }
+        let mut image = Image::new(base_image);
+        image.apply(patch, self)?;
+        let pieces = image.pieces(base_image);
+        if let Some(text) = base_slice(base_image, &pieces) {
+            return Ok(Cow::Borrowed(text));
+        }
+        let (mut pieces, add_newline) = pieces;
+        if add_newline {
+            pieces.push(b"\n");
+        }
+        Ok(Cow::Owned(pieces.concat()))
+    }
+
+    /// Apply a `Patch` to a base image based on the configured options, also returning where
+    /// each hunk's lines ended up in the patched text
+    ///
+    /// The spans are in the same order as the hunks of the patch. This is useful for pointing
+    /// at the patched regions afterwards, like scrolling a viewer to them or highlighting them.
+    ///
+    /// ```
+    /// use diffy::{ApplyOptions, HunkSpan, Patch};
+    ///
+    /// let s = "\
+    /// --- a
+    /// +++ b
+    /// @@ -1,2 +1,3 @@
+    ///  a
+    /// +new
+    ///  b
+    /// @@ -4 +5 @@
+    /// -d
+    /// +D
+    /// ";
+    /// let patch = Patch::from_str(s).unwrap();
+    ///
+    /// let options = ApplyOptions::new();
+    /// let (patched, spans) = options.apply_with_spans("a\nb\nc\nd\n", &patch).unwrap();
+    /// assert_eq!(patched, "a\nnew\nb\nc\nD\n");
+    /// assert_eq!(spans[0], HunkSpan { lines: 0..3, bytes: 0..8 });
+    /// assert_eq!(spans[1], HunkSpan { lines: 4..5, bytes: 10..12 });
+    /// assert_eq!(&patched[spans[1].bytes.clone()], "D\n");
+    /// ```
+    pub fn apply_with_spans(
+        &self,
+        base_image: &str,
+        patch: &Patch<'_, str>,
+    ) -> Result<(String, Vec<HunkSpan>), ApplyError> {
+        let mut image = Image::new(base_image);
+        let spans = image.apply(patch, self)?;
+        let text = image.render();
+        let spans = image.hunk_spans(spans, text.len());
+        Ok((text, spans))
+    }
 
-    // update image
-    image.splice(
-        pos..pos + pre_image_line_count(hunk.lines()),
-        post_image(hunk.lines()).map(ImageLine::Patched),
-    );
+    /// Apply a non-utf8 `Patch` to a base image based on the configured options, also
+    /// returning where each hunk's lines ended up in the patched text
+    ///
+    /// See [`apply_with_spans`](#method.apply_with_spans).
+    pub fn apply_bytes_with_spans(
+        &self,
+        base_image: &[u8],
+        patch: &Patch<'_, [u8]>,
+    ) -> Result<(Vec<u8>, Vec<HunkSpan>), ApplyError> {
+        let mut image = Image::new(base_image);
+        let spans = image.apply(patch, self)?;
+        let text = image.to_bytes();
+        let spans = image.hunk_spans(spans, text.len());
+        Ok((text, spans))
+    }
+}
 
-    Ok(())
+impl Default for ApplyOptions {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 // Search in `image` for a palce to apply hunk.
@@ -139,13 +666,14 @@ fn apply_hunk<'a, T: PartialEq + ?Sized>(
 //
 // It might be worth looking into other possible positions to apply the hunk to as described here:
 // https://neil.fraser.name/writing/patch/
-fn find_position<T: PartialEq + ?Sized>(
+fn find_position<T: Text + ?Sized>(
     image: &[ImageLine<T>],
     hunk: &Hunk<'_, T>,
+    ignore_trailing_newline: bool,
 ) -> Option<usize> {
     // In order to avoid searching through positions which are out of bounds of the image,
-    // clamp the starting position based on the length of the image
-    let pos = std::cmp::min(hunk.new_range().start().saturating_sub(1), image.len());
+    // clamp the starting position based on the length of the image.
+    let pos = core::cmp::min(hunk.new_range().to_std_range().start, image.len());
 
     // Create an iterator that starts with 'pos' and then interleaves
     // moving pos backward/foward by one.
@@ -154,33 +682,17 @@ fn find_position<T: PartialEq + ?Sized>(
 
     iter::once(pos)
         .chain(interleave(backward, forward))
-        .find(|&pos| match_fragment(image, hunk.lines(), pos))
+        .find(|&pos| match_fragment(image, hunk, pos, ignore_trailing_newline))
 }
 
-fn pre_image_line_count<T: ?Sized>(lines: &[Line<'_, T>]) -> usize {
-    pre_image(lines).count()
-}
-
-fn post_image<'a, 'b, T: ?Sized>(lines: &'b [Line<'a, T>]) -> impl Iterator<Item = &'a T> + 'b {
-    lines.iter().filter_map(|line| match line {
-        Line::Context(l) | Line::Insert(l) => Some(*l),
-        Line::Delete(_) => None,
-    })
-}
-
-fn pre_image<'a, 'b, T: ?Sized>(lines: &'b [Line<'a, T>]) -> impl Iterator<Item = &'a T> + 'b {
-    lines.iter().filter_map(|line| match line {
-        Line::Context(l) | Line::Delete(l) => Some(*l),
-        Line::Insert(_) => None,
-    })
-}
-
-fn match_fragment<T: PartialEq + ?Sized>(
+fn match_fragment<T: Text + ?Sized>(
     image: &[ImageLine<T>],
-    lines: &[Line<'_, T>],
+    hunk: &Hunk<'_, T>,
     pos: usize,
+    ignore_trailing_newline: bool,
 ) -> bool {
-    let len = pre_image_line_count(lines);
+    let len = hunk.old_range().len();
+    let at_eof = pos + len == image.len();
 
     let image = if let Some(image) = image.get(pos..pos + len) {
         image
@@ -193,7 +705,18 @@ fn match_fragment<T: PartialEq + ?Sized>(
         return false;
     }
 
-    pre_image(lines).eq(image.iter().map(ImageLine::inner))
+    hunk.old_lines()
+        .zip(image.iter().map(ImageLine::inner))
+        .enumerate()
+        .all(|(idx, (old, line))| {
+            // The last line of the image may differ in its trailing newline
+            old == line
+                || (ignore_trailing_newline
+                    && at_eof
+                    && idx + 1 == len
+                    && old.strip_suffix("\n").unwrap_or(old)
+                        == line.strip_suffix("\n").unwrap_or(line))
+        })
 }
 
 #[derive(Debug)]
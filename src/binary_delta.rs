@@ -0,0 +1,416 @@
+//! Compact deltas between binary files, for shipping updates to large binaries like firmware
+//! images
+//!
+//! [`encode_delta`] describes how to build a new file by copying ranges of an old one and
+//! inserting new bytes, and [`apply_delta`] rebuilds the new file from the old one and the delta.
+//! Unlike the text deltas of [`diffy::encode_delta`], matches are found at any byte offset
+//! rather than along lines, and both files are checksummed so a delta applied to the wrong file,
+//! or a corrupted delta, is rejected instead of producing garbage.
+//!
+//! ```
+//! use diffy::binary_delta::{apply_delta, encode_delta};
+//!
+//! let old: Vec<u8> = (0..=255).cycle().take(4096).collect();
+//! let mut new = old.clone();
+//! new[1000..1004].copy_from_slice(b"\xde\xad\xbe\xef");
+//! new.extend_from_slice(b"appended");
+//!
+//! let delta = encode_delta(&old, &new);
+//! assert!(delta.len() < 64);
+//! assert_eq!(apply_delta(&old, &delta).unwrap(), new);
+//! ```
+//!
+//! For applying a delta without holding the whole new file in memory, e.g. to write it straight
+//! to flash, iterate over the chunks of the new file with [`DeltaChunks`] instead.
+//!
+//! # Format
+//!
+//! The format is versioned, so deltas can be stored and exchanged between versions of this
+//! crate. Numbers are unsigned LEB128 varints like in [`diffy::encode_delta`] unless noted
+//! otherwise. A delta consists of:
+//!
+//! 1. the magic bytes `DFYB` followed by the version byte `1`
+//! 2. the length in bytes of the old file
+//! 3. the length in bytes of the new file
+//! 4. the CRC-32 (as used by zlib and PNG) of the old file, as 4 little-endian bytes
+//! 5. the CRC-32 of the new file, as 4 little-endian bytes
+//! 6. any number of operations, each starting with a number `n`:
+//!    - if `n` is even, it's a copy of `n >> 1` bytes from the old file, followed by the offset
+//!      to copy from. The offset is relative to the end of the previous copy, or to the start of
+//!      the old file for the first copy, and is zigzag encoded: `0, -1, 1, -2, ...` are written
+//!      as `0, 1, 2, 3, ...`
+//!    - if `n` is odd, it's an insert of the `n >> 1` bytes which follow
+//!
+//! [`encode_delta`]: fn.encode_delta.html
+//! [`apply_delta`]: fn.apply_delta.html
+//! [`DeltaChunks`]: struct.DeltaChunks.html
+//! [`diffy::encode_delta`]: ../fn.encode_delta.html
+
+use crate::{
+    block_match::find_matches,
+    delta::{read_varint, write_varint},
+};
+use alloc::vec::Vec;
+
+pub use crate::DeltaError;
+
+const MAGIC: &[u8] = b"DFYB";
+const VERSION: u8 = 1;
+
+/// Encode the differences between two binary files as a compact delta
+///
+/// The old file is indexed by the hashes of its 16 byte blocks, then matches are found by
+/// hashing every 16 byte window of the new file and extending the matching windows in both
+/// directions, which finds moved and repeated content anywhere in the old file. Bytes which
+/// aren't covered by a match are inserted as they are.
+pub fn encode_delta(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    delta.extend_from_slice(MAGIC);
+    delta.push(VERSION);
+    write_varint(&mut delta, old.len());
+    write_varint(&mut delta, new.len());
+    delta.extend_from_slice(&crc32(old).to_le_bytes());
+    delta.extend_from_slice(&crc32(new).to_le_bytes());
+
+    let mut copy_end = 0;
+    let mut insert_start = 0;
+    for m in find_matches(old, new) {
+        write_insert(&mut delta, &new[insert_start..m.new]);
+        write_varint(&mut delta, m.len << 1);
+        write_varint(&mut delta, zigzag(m.old, copy_end));
+        copy_end = m.old + m.len;
+        insert_start = m.new + m.len;
+    }
+    write_insert(&mut delta, &new[insert_start..]);
+
+    delta
+}
+
+/// Apply a delta created by [`encode_delta`] to the old file it was created from, returning the
+/// new file
+///
+/// The delta is rejected if it isn't in a supported format, if `old` isn't the file it was
+/// created from, or if it doesn't produce the file it was created for.
+///
+/// ```
+/// use diffy::binary_delta::{apply_delta, encode_delta, DeltaError};
+///
+/// let delta = encode_delta(b"\x00\x01\x02\x03", b"\x00\x01\x02\x04");
+/// assert_eq!(apply_delta(b"\x00\x01\x02\x03", &delta).unwrap(), b"\x00\x01\x02\x04");
+/// assert_eq!(
+///     apply_delta(b"\x00\x01\x02\x05", &delta),
+///     Err(DeltaError::BaseChecksumMismatch)
+/// );
+/// ```
+///
+/// [`encode_delta`]: fn.encode_delta.html
+pub fn apply_delta(old: &[u8], delta: &[u8]) -> Result<Vec<u8>, DeltaError> {
+    let chunks = DeltaChunks::new(old, delta)?;
+    // The length comes from the input so it's only used as a hint, within reason
+    let capacity = chunks.new_len().min(old.len().saturating_add(delta.len()));
+    let mut new = Vec::with_capacity(capacity);
+    for chunk in chunks {
+        new.extend_from_slice(chunk?);
+    }
+    Ok(new)
+}
+
+/// An iterator over the chunks of the new file produced by applying a delta, created with
+/// [`DeltaChunks::new`]
+///
+/// Each chunk is borrowed from either the old file or the delta, so the new file can be written
+/// out as it's produced without ever being held in memory. The checksum of the new file can
+/// only be verified once all of it has been produced, so the last item is an error if it
+/// doesn't match: when writing the new file in place of the old one, only commit to it once the
+/// iterator has been exhausted without an error.
+///
+/// ```
+/// use diffy::binary_delta::{encode_delta, DeltaChunks};
+///
+/// let old = b"The quick brown fox jumps over the lazy dog".repeat(4);
+/// let new = b"The quick brown cat jumps over the lazy dog".repeat(4);
+/// let delta = encode_delta(&old, &new);
+///
+/// let mut written = Vec::new();
+/// for chunk in DeltaChunks::new(&old, &delta).unwrap() {
+///     written.extend_from_slice(chunk.unwrap());
+/// }
+/// assert_eq!(written, new);
+/// ```
+///
+/// [`DeltaChunks::new`]: struct.DeltaChunks.html#method.new
+#[derive(Clone, Debug)]
+pub struct DeltaChunks<'a> {
+    old: &'a [u8],
+    ops: &'a [u8],
+    new_len: usize,
+    new_crc: u32,
+    produced: usize,
+    crc: u32,
+    copy_end: usize,
+    done: bool,
+}
+
+impl<'a> DeltaChunks<'a> {
+    /// Read the header of `delta`, checking that it applies to `old`
+    pub fn new(old: &'a [u8], delta: &'a [u8]) -> Result<Self, DeltaError> {
+        let mut delta = delta
+            .strip_prefix(MAGIC)
+            .and_then(|rest| rest.strip_prefix(&[VERSION]))
+            .ok_or(DeltaError::UnsupportedFormat)?;
+        if read_varint(&mut delta)? != old.len() {
+            return Err(DeltaError::BaseLengthMismatch);
+        }
+        let new_len = read_varint(&mut delta)?;
+        let old_crc = read_u32(&mut delta)?;
+        let new_crc = read_u32(&mut delta)?;
+        if crc32(old) != old_crc {
+            return Err(DeltaError::BaseChecksumMismatch);
+        }
+
+        Ok(Self {
+            old,
+            ops: delta,
+            new_len,
+            new_crc,
+            produced: 0,
+            crc: CRC_INIT,
+            copy_end: 0,
+            done: false,
+        })
+    }
+
+    /// Returns the length in bytes of the new file, as recorded in the delta
+    pub fn new_len(&self) -> usize {
+        self.new_len
+    }
+
+    fn next_chunk(&mut self) -> Result<Option<&'a [u8]>, DeltaError> {
+        if self.ops.is_empty() {
+            if self.produced != self.new_len {
+                return Err(DeltaError::ResultLengthMismatch);
+            }
+            if !self.crc != self.new_crc {
+                return Err(DeltaError::ResultChecksumMismatch);
+            }
+            return Ok(None);
+        }
+
+        let n = read_varint(&mut self.ops)?;
+        let len = n >> 1;
+        let chunk = if n & 1 == 0 {
+            let start = unzigzag(read_varint(&mut self.ops)?, self.copy_end)
+                .ok_or(DeltaError::CopyOutOfBounds)?;
+            let chunk = start
+                .checked_add(len)
+                .and_then(|end| self.old.get(start..end))
+                .ok_or(DeltaError::CopyOutOfBounds)?;
+            self.copy_end = start + len;
+            chunk
+        } else {
+            if self.ops.len() < len {
+                return Err(DeltaError::UnexpectedEof);
+            }
+            let (chunk, rest) = self.ops.split_at(len);
+            self.ops = rest;
+            chunk
+        };
+
+        if self.new_len - self.produced < chunk.len() {
+            return Err(DeltaError::ResultLengthMismatch);
+        }
+        self.produced += chunk.len();
+        self.crc = update_crc32(self.crc, chunk);
+        Ok(Some(chunk))
+    }
+}
+
+impl<'a> Iterator for DeltaChunks<'a> {
+    type Item = Result<&'a [u8], DeltaError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let next = self.next_chunk().transpose();
+        if !matches!(next, Some(Ok(_))) {
+            self.done = true;
+        }
+        next
+    }
+}
+
+impl core::iter::FusedIterator for DeltaChunks<'_> {}
+
+// Encodes the signed distance from `base` to `offset`
+fn zigzag(offset: usize, base: usize) -> usize {
+    if offset >= base {
+        (offset - base) << 1
+    } else {
+        ((base - offset) << 1) - 1
+    }
+}
+
+fn unzigzag(n: usize, base: usize) -> Option<usize> {
+    if n & 1 == 0 {
+        base.checked_add(n >> 1)
+    } else {
+        base.checked_sub((n >> 1) + 1)
+    }
+}
+
+fn write_insert(delta: &mut Vec<u8>, bytes: &[u8]) {
+    if !bytes.is_empty() {
+        write_varint(delta, bytes.len() << 1 | 1);
+        delta.extend_from_slice(bytes);
+    }
+}
+
+fn read_u32(delta: &mut &[u8]) -> Result<u32, DeltaError> {
+    if delta.len() < 4 {
+        return Err(DeltaError::UnexpectedEof);
+    }
+    let (bytes, rest) = delta.split_at(4);
+    *delta = rest;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+const CRC_INIT: u32 = !0;
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+fn update_crc32(crc: u32, bytes: &[u8]) -> u32 {
+    bytes.iter().fold(crc, |crc, &byte| {
+        CRC_TABLE[usize::from(crc as u8 ^ byte)] ^ (crc >> 8)
+    })
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    !update_crc32(CRC_INIT, bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(
+            crc32(b"The quick brown fox jumps over the lazy dog"),
+            0x414f_a339
+        );
+
+        for (offset, base) in [(0, 0), (5, 5), (10, 3), (3, 10), (0, isize::MAX as usize)] {
+            assert_eq!(unzigzag(zigzag(offset, base), base), Some(offset));
+        }
+        assert_eq!(zigzag(4, 5), 1);
+        assert_eq!(zigzag(6, 5), 2);
+        assert_eq!(unzigzag(1, 0), None);
+    }
+
+    #[test]
+    fn encodes_matches() {
+        // Moving a large block only takes a couple of copies
+        let old: Vec<u8> = (0..4000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let new = [&old[2000..], &old[..2000]].concat();
+        let delta = encode_delta(&old, &new);
+        assert!(delta.len() < 32, "{}", delta.len());
+        assert_eq!(apply_delta(&old, &delta).unwrap(), new);
+
+        // Unrelated content is inserted as it is
+        let delta = encode_delta(b"abc", b"xyz");
+        let mut expected = b"DFYB\x01\x03\x03".to_vec();
+        expected.extend_from_slice(&crc32(b"abc").to_le_bytes());
+        expected.extend_from_slice(&crc32(b"xyz").to_le_bytes());
+        expected.extend_from_slice(b"\x07xyz");
+        assert_eq!(delta, expected);
+    }
+
+    #[test]
+    fn streams_chunks() {
+        let old = b"0123456789abcdef".repeat(8);
+        let mut new = old.clone();
+        new.splice(40..40, b"inserted".iter().copied());
+        let delta = encode_delta(&old, &new);
+
+        let chunks = DeltaChunks::new(&old, &delta).unwrap();
+        assert_eq!(chunks.new_len(), new.len());
+        let chunks: Vec<&[u8]> = chunks.collect::<Result<_, _>>().unwrap();
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.concat(), new);
+    }
+
+    #[test]
+    fn rejects_corrupted_deltas() {
+        let old: Vec<u8> = (0..2000u32).map(|i| (i * 13 % 256) as u8).collect();
+        let mut new = old.clone();
+        new[500..520].fill(0xaa);
+        new.extend_from_slice(b"tail");
+        let delta = encode_delta(&old, &new);
+        assert_eq!(apply_delta(&old, &delta).unwrap(), new);
+
+        // Any flipped bit either errors or still produces the right content, e.g. by copying
+        // from an identical part of the old file
+        for i in 0..delta.len() {
+            for bit in 0..8 {
+                let mut corrupted = delta.clone();
+                corrupted[i] ^= 1 << bit;
+                if let Ok(result) = apply_delta(&old, &corrupted) {
+                    assert_eq!(result, new, "byte {} bit {}", i, bit);
+                }
+            }
+        }
+        for len in 0..delta.len() {
+            assert!(apply_delta(&old, &delta[..len]).is_err());
+        }
+
+        assert_eq!(apply_delta(&old, b""), Err(DeltaError::UnsupportedFormat));
+        let mut future = delta.clone();
+        future[4] = 2;
+        assert_eq!(
+            apply_delta(&old, &future),
+            Err(DeltaError::UnsupportedFormat)
+        );
+        assert_eq!(
+            apply_delta(&old[1..], &delta),
+            Err(DeltaError::BaseLengthMismatch)
+        );
+        let mut other = old.clone();
+        other[0] ^= 1;
+        assert_eq!(
+            apply_delta(&other, &delta),
+            Err(DeltaError::BaseChecksumMismatch)
+        );
+
+        // A corrupted literal is only caught by the checksum, after its chunk was produced
+        let mut corrupted = delta.clone();
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 1;
+        let chunks: Vec<_> = DeltaChunks::new(&old, &corrupted).unwrap().collect();
+        assert_eq!(
+            chunks.last(),
+            Some(&Err(DeltaError::ResultChecksumMismatch))
+        );
+        assert!(chunks[..chunks.len() - 1].iter().all(Result::is_ok));
+    }
+}
@@ -0,0 +1,263 @@
+//! Finding the ranges of one byte string which also appear in another, for encoding deltas
+//! which copy them instead of spelling them out
+
+use alloc::{vec, vec::Vec};
+
+// The length of the blocks of the old file which are indexed to find matches. Shorter matches
+// aren't found, and wouldn't save much over inserting their bytes anyway.
+const BLOCK: usize = 16;
+// The number of blocks with the same hash which are indexed, and so compared when looking for a
+// match. Blocks past the first few with a hash are left out, which keeps repeated content from
+// building up long runs of probes.
+const MAX_CANDIDATES: usize = 8;
+// The number of slots of the index searched for a hash. Blocks which don't fit are left out,
+// which keeps a run of occupied slots from being scanned for every byte of the new file.
+const MAX_PROBES: usize = 32;
+// The multiplier of the rolling hash
+const BASE: u32 = 257;
+
+/// A range of the new bytes which is equal to a range of the old bytes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Match {
+    pub(crate) old: usize,
+    pub(crate) new: usize,
+    pub(crate) len: usize,
+}
+
+// Finds matches covering as much of `new` as possible, in order and without overlapping
+//
+// The old bytes are indexed by the hashes of their aligned blocks, then every window of the new
+// bytes is hashed as it rolls forward. A window which matches a block is extended in both
+// directions, which finds moved and repeated content anywhere in the old bytes.
+pub(crate) fn find_matches(old: &[u8], new: &[u8]) -> Vec<Match> {
+    let index = BlockIndex::new(old);
+    let mut matches = Vec::new();
+    let mut prev_end = 0;
+    let mut pos = 0;
+    let mut hash = new.get(..BLOCK).map_or(0, hash_block);
+    while pos + BLOCK <= new.len() {
+        let best = index
+            .candidates(hash)
+            .filter(|&start| old[start..start + BLOCK] == new[pos..pos + BLOCK])
+            .map(|start| {
+                (
+                    start,
+                    BLOCK + common_prefix(&old[start + BLOCK..], &new[pos + BLOCK..]),
+                )
+            })
+            .max_by_key(|&(start, len)| (len, core::cmp::Reverse(start)));
+
+        let (start, len) = match best {
+            Some(best) => best,
+            None => {
+                if let Some(&next) = new.get(pos + BLOCK) {
+                    hash = roll_hash(hash, new[pos], next);
+                }
+                pos += 1;
+                continue;
+            }
+        };
+
+        // The match may also cover bytes since the end of the previous one
+        let back = common_suffix(&old[..start], &new[prev_end..pos]);
+        matches.push(Match {
+            old: start - back,
+            new: pos - back,
+            len: back + len,
+        });
+
+        pos += len;
+        prev_end = pos;
+        if let Some(block) = new.get(pos..pos + BLOCK) {
+            hash = hash_block(block);
+        }
+    }
+    matches
+}
+
+// A hash table from the hashes of the old file's aligned blocks to the offsets of the first
+// `MAX_CANDIDATES` of them, using open addressing with at most `MAX_PROBES` linear probes
+struct BlockIndex {
+    slots: Vec<Option<(u32, usize)>>,
+}
+
+impl BlockIndex {
+    fn new(old: &[u8]) -> Self {
+        let blocks = old.len() / BLOCK;
+        let mut slots = vec![None; (blocks * 2).next_power_of_two()];
+        let mask = slots.len() - 1;
+        for start in (0..blocks).map(|block| block * BLOCK) {
+            let hash = hash_block(&old[start..start + BLOCK]);
+            let mut same_hash = 0;
+            let free = (0..MAX_PROBES.min(slots.len()))
+                .map(|probe| (mix(hash) as usize).wrapping_add(probe) & mask)
+                .find(|&slot| match slots[slot] {
+                    Some((slot_hash, _)) => {
+                        same_hash += usize::from(slot_hash == hash);
+                        false
+                    }
+                    None => true,
+                });
+            if let Some(slot) = free.filter(|_| same_hash < MAX_CANDIDATES) {
+                slots[slot] = Some((hash, start));
+            }
+        }
+        Self { slots }
+    }
+
+    // Returns the offsets of the first few blocks with the given hash
+    fn candidates(&self, hash: u32) -> impl Iterator<Item = usize> + '_ {
+        let mask = self.slots.len() - 1;
+        (0..MAX_PROBES.min(self.slots.len()))
+            .map(move |probe| self.slots[(mix(hash) as usize).wrapping_add(probe) & mask])
+            .take_while(Option::is_some)
+            .flatten()
+            .filter(move |&(slot_hash, _)| slot_hash == hash)
+            .map(|(_, start)| start)
+    }
+}
+
+// Spreads the bits of a hash over all of it, so that blocks with close hashes, e.g. ones which
+// only differ in their last byte, don't end up in neighbouring slots
+fn mix(hash: u32) -> u32 {
+    let hash = (hash ^ (hash >> 16)).wrapping_mul(0x85eb_ca6b);
+    let hash = (hash ^ (hash >> 13)).wrapping_mul(0xc2b2_ae35);
+    hash ^ (hash >> 16)
+}
+
+fn hash_block(block: &[u8]) -> u32 {
+    block.iter().fold(0u32, |hash, &byte| {
+        hash.wrapping_mul(BASE).wrapping_add(u32::from(byte))
+    })
+}
+
+// Moves the window of a block hash one byte forward, from starting with `out` to ending with
+// `next`
+fn roll_hash(hash: u32, out: u8, next: u8) -> u32 {
+    let out_weight = (1..BLOCK).fold(1u32, |weight, _| weight.wrapping_mul(BASE));
+    hash.wrapping_sub(u32::from(out).wrapping_mul(out_weight))
+        .wrapping_mul(BASE)
+        .wrapping_add(u32::from(next))
+}
+
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+fn common_suffix(a: &[u8], b: &[u8]) -> usize {
+    a.iter()
+        .rev()
+        .zip(b.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rolling_hash() {
+        let bytes: Vec<u8> = (0..64).collect();
+        let hash = hash_block(&bytes[..BLOCK]);
+        let rolled = (0..10).fold(hash, |hash, i| roll_hash(hash, bytes[i], bytes[i + BLOCK]));
+        assert_eq!(rolled, hash_block(&bytes[10..10 + BLOCK]));
+    }
+
+    #[test]
+    fn matches() {
+        let old: Vec<u8> = (0..1000u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+            .collect();
+        let mut new = [&old[500..], b"inserted", &old[..500]].concat();
+        new[100] ^= 0xff;
+
+        // The matches are extended up to the changed bytes, even off the block boundaries
+        assert_eq!(
+            find_matches(&old, &new),
+            [
+                Match {
+                    old: 500,
+                    new: 0,
+                    len: 100
+                },
+                Match {
+                    old: 601,
+                    new: 101,
+                    len: 399
+                },
+                Match {
+                    old: 0,
+                    new: 508,
+                    len: 500
+                },
+            ]
+        );
+        assert!(find_matches(&old, &old[..BLOCK - 1]).is_empty());
+        assert!(find_matches(&[], &old).is_empty());
+    }
+
+    #[test]
+    fn repeated_blocks() {
+        // Only the first few of many equal blocks are indexed
+        let old = b"0123456789abcdef".repeat(1000);
+        let index = BlockIndex::new(&old);
+        assert_eq!(index.slots.iter().flatten().count(), MAX_CANDIDATES);
+        let starts: Vec<usize> = index.candidates(hash_block(&old[..BLOCK])).collect();
+        assert_eq!(starts, [0, 16, 32, 48, 64, 80, 96, 112]);
+
+        // Which are still enough to match the blocks after a change
+        let mut new = old.clone();
+        new[8000] = b'x';
+        assert_eq!(
+            find_matches(&old, &new),
+            [
+                Match {
+                    old: 0,
+                    new: 0,
+                    len: 8000
+                },
+                Match {
+                    old: 0,
+                    new: 8016,
+                    len: 7984
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn clustered_hashes() {
+        // Numbered blocks only differ in their last few bytes, so their hashes are close to
+        // each other, and have to be spread over the index to keep lookups short
+        let old: Vec<u8> = (0..50_000)
+            .flat_map(|i: u32| alloc::format!("{:015}\n", i).into_bytes())
+            .collect();
+        let index = BlockIndex::new(&old);
+        assert_eq!(index.slots.iter().flatten().count(), 50_000);
+
+        let mut new = old.clone();
+        new.insert(16 * 25_000 + 5, b'x');
+        assert_eq!(
+            find_matches(&old, &new),
+            [
+                Match {
+                    old: 0,
+                    new: 0,
+                    len: 16 * 25_000 + 5
+                },
+                Match {
+                    old: 16 * 25_000 + 5,
+                    new: 16 * 25_000 + 6,
+                    len: 16 * 25_000 - 5
+                },
+            ]
+        );
+        let new: Vec<u8> = (0..50_000)
+            .flat_map(|i: u32| alloc::format!("{:016}\n", i * 7).into_bytes())
+            .collect();
+        for m in find_matches(&old, &new) {
+            assert_eq!(old[m.old..m.old + m.len], new[m.new..m.new + m.len]);
+        }
+    }
+}
@@ -0,0 +1,232 @@
+//! Cleanup passes for diffs which weren't produced by [`diff`]
+//!
+//! [`diff`] already runs these passes on its own output. They're exposed here so that callers who
+//! build their own `Vec<Diff>`, e.g. from the edits made to an editor buffer, get the same
+//! readable output.
+//!
+//! ```
+//! use diffy::{cleanup, Diff};
+//!
+//! let original = "abc";
+//! let modified = "abbc";
+//! let mut diffs = vec![
+//!     Diff::Equal("a"),
+//!     Diff::Insert("b"),
+//!     Diff::Equal("bc"),
+//! ];
+//!
+//! cleanup::compact(original, modified, &mut diffs).unwrap();
+//! assert_eq!(diffs, [Diff::Equal("ab"), Diff::Insert("b"), Diff::Equal("c")]);
+//! ```
+//!
+//! [`diff`]: ../fn.diff.html
+
+use crate::{
+    diff::{cleanup, Diff},
+    range::{DiffRange, Range, SliceLike},
+};
+use alloc::vec::Vec;
+use core::fmt;
+
+/// An error returned when a list of diffs doesn't describe the texts it was given with
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CleanupError {
+    /// The diff at `index` doesn't match the next segment of the text it should cover
+    Mismatch {
+        /// The index of the offending diff
+        index: usize,
+    },
+    /// The diffs end before covering the whole of both texts
+    Incomplete,
+}
+
+impl fmt::Display for CleanupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CleanupError::Mismatch { index } => {
+                write!(f, "diff #{} doesn't match the text it covers", index)
+            }
+            CleanupError::Incomplete => write!(f, "diffs don't cover the whole text"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CleanupError {}
+
+/// Merge adjacent diffs of the same kind and shift insertions and deletions into their most
+/// readable position
+///
+/// `diffs` must reconstruct `original` from its [`Equal`] and [`Delete`] segments and `modified`
+/// from its [`Equal`] and [`Insert`] segments, otherwise `diffs` is left untouched and an error
+/// is returned. The compacted diffs still reconstruct both texts, with their segments borrowed
+/// from `original` and `modified`. Empty segments are dropped.
+///
+/// [`Equal`]: ../enum.Diff.html#variant.Equal
+/// [`Delete`]: ../enum.Diff.html#variant.Delete
+/// [`Insert`]: ../enum.Diff.html#variant.Insert
+pub fn compact<'a>(
+    original: &'a str,
+    modified: &'a str,
+    diffs: &mut Vec<Diff<'a, str>>,
+) -> Result<(), CleanupError> {
+    compact_impl(original, modified, diffs)
+}
+
+/// Compact diffs between two slices, see [`compact`]
+///
+/// [`compact`]: fn.compact.html
+pub fn compact_slice<'a, T: PartialEq>(
+    original: &'a [T],
+    modified: &'a [T],
+    diffs: &mut Vec<Diff<'a, [T]>>,
+) -> Result<(), CleanupError> {
+    compact_impl(original, modified, diffs)
+}
+
+fn compact_impl<'a, T: ?Sized + SliceLike>(
+    original: &'a T,
+    modified: &'a T,
+    diffs: &mut Vec<Diff<'a, T>>,
+) -> Result<(), CleanupError> {
+    let mut ranges = to_ranges(original, modified, diffs)?;
+    // Unlike the output of the diff algorithm, user built diffs may contain empty or neighbouring
+    // ranges of the same kind which `cleanup::compact` doesn't expect
+    merge_adjacent(&mut ranges);
+    cleanup::compact(&mut ranges);
+    merge_adjacent(&mut ranges);
+    diffs.clear();
+    diffs.extend(ranges.into_iter().map(Diff::from));
+    Ok(())
+}
+
+// Drops empty ranges and merges neighbouring ranges of the same kind
+fn merge_adjacent<T: ?Sized + SliceLike>(ranges: &mut Vec<DiffRange<'_, '_, T>>) {
+    ranges.retain(|range| !range.is_empty());
+    ranges.dedup_by(|next, prev| match (*prev, *next) {
+        (DiffRange::Equal(..), DiffRange::Equal(..))
+        | (DiffRange::Delete(_), DiffRange::Delete(_))
+        | (DiffRange::Insert(_), DiffRange::Insert(_)) => {
+            prev.grow_down(next.len());
+            true
+        }
+        _ => false,
+    });
+}
+
+// Locates each diff within the two texts, checking that together they cover both texts exactly
+fn to_ranges<'a, T: ?Sized + SliceLike>(
+    original: &'a T,
+    modified: &'a T,
+    diffs: &[Diff<'a, T>],
+) -> Result<Vec<DiffRange<'a, 'a, T>>, CleanupError> {
+    // Returns the range of `text` starting at `*pos` which matches `segment`
+    fn locate<'a, T: ?Sized + SliceLike>(
+        text: &'a T,
+        pos: &mut usize,
+        segment: &T,
+    ) -> Option<Range<'a, T>> {
+        if !text.as_slice(*pos..text.len()).starts_with(segment) {
+            return None;
+        }
+        let range = Range::new(text, *pos..*pos + segment.len());
+        *pos += segment.len();
+        Some(range)
+    }
+
+    let mut old_pos = 0;
+    let mut new_pos = 0;
+    let ranges = diffs
+        .iter()
+        .enumerate()
+        .map(|(index, diff)| {
+            let range = match *diff {
+                Diff::Equal(segment) => locate(original, &mut old_pos, segment).and_then(|old| {
+                    locate(modified, &mut new_pos, segment).map(|new| DiffRange::Equal(old, new))
+                }),
+                Diff::Delete(segment) => {
+                    locate(original, &mut old_pos, segment).map(DiffRange::Delete)
+                }
+                Diff::Insert(segment) => {
+                    locate(modified, &mut new_pos, segment).map(DiffRange::Insert)
+                }
+            };
+            range.ok_or(CleanupError::Mismatch { index })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if old_pos != original.len() || new_pos != modified.len() {
+        return Err(CleanupError::Incomplete);
+    }
+    Ok(ranges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn compact_user_diffs() {
+        let original = "one two three";
+        let modified = "one two two three";
+        let mut diffs = vec![
+            Diff::Equal("one "),
+            Diff::Delete(""),
+            Diff::Insert("two"),
+            Diff::Insert(" "),
+            Diff::Equal("two three"),
+        ];
+        compact(original, modified, &mut diffs).unwrap();
+        assert_eq!(
+            diffs,
+            [
+                Diff::Equal("one two t"),
+                Diff::Insert("wo t"),
+                Diff::Equal("hree"),
+            ]
+        );
+        assert_eq!(diffs, crate::diff(original, modified));
+
+        let original = [1, 2, 3];
+        let modified = [1, 3];
+        let mut diffs = vec![
+            Diff::Equal(&original[..1]),
+            Diff::Delete(&original[1..2]),
+            Diff::Equal(&original[2..]),
+        ];
+        compact_slice(&original, &modified, &mut diffs).unwrap();
+        assert_eq!(
+            diffs,
+            [
+                Diff::Equal(&original[..1]),
+                Diff::Delete(&original[1..2]),
+                Diff::Equal(&original[2..]),
+            ]
+        );
+    }
+
+    #[test]
+    fn compact_rejects_invalid_diffs() {
+        let mut diffs = vec![Diff::Equal("a"), Diff::Insert("c"), Diff::Delete("b")];
+        assert_eq!(
+            compact("ab", "ac", &mut diffs),
+            Ok(()),
+            "order of deletions and insertions doesn't matter"
+        );
+
+        let mut diffs = vec![Diff::Equal("a"), Diff::Insert("x")];
+        assert_eq!(
+            compact("ab", "ax", &mut diffs),
+            Err(CleanupError::Incomplete)
+        );
+
+        let mut diffs = vec![Diff::Equal("a"), Diff::Delete("c")];
+        assert_eq!(
+            compact("ab", "a", &mut diffs),
+            Err(CleanupError::Mismatch { index: 1 })
+        );
+        assert_eq!(diffs, [Diff::Equal("a"), Diff::Delete("c")]);
+    }
+}
@@ -0,0 +1,496 @@
+//! A compact binary encoding of the differences between two texts
+
+use crate::{
+    diff::{char_starts, DiffOpKind, DiffOptions},
+    range::{DiffRange, Range},
+    utils::LineIter,
+};
+use alloc::{string::String, vec, vec::Vec};
+use core::{fmt, ops};
+
+/// An error returned when [`apply_delta`] fails
+///
+/// [`apply_delta`]: fn.apply_delta.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DeltaError {
+    /// The delta ended in the middle of a number or an insert operation
+    UnexpectedEof,
+    /// A number in the delta doesn't fit in a `usize`
+    Overflow,
+    /// The delta was encoded against a text of a different length than the one it was applied
+    /// to
+    BaseLengthMismatch,
+    /// A copy operation refers to bytes past the end of the original text
+    CopyOutOfBounds,
+    /// The operations of the delta don't produce a text of the length given in its header
+    ResultLengthMismatch,
+    /// The operations of the delta produce bytes which aren't valid UTF-8
+    InvalidUtf8,
+    /// The delta doesn't start with the header of a supported binary delta format
+    UnsupportedFormat,
+    /// The delta was encoded against different content than the one it was applied to
+    BaseChecksumMismatch,
+    /// The operations of the delta don't produce the content the delta was encoded for
+    ResultChecksumMismatch,
+    /// The delta contains an operation which isn't defined by its format
+    ReservedOpcode,
+}
+
+impl fmt::Display for DeltaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeltaError::UnexpectedEof => write!(f, "unexpected end of delta"),
+            DeltaError::Overflow => write!(f, "number in delta is too large"),
+            DeltaError::BaseLengthMismatch => {
+                write!(f, "delta doesn't apply to a text of this length")
+            }
+            DeltaError::CopyOutOfBounds => write!(f, "copy is out of bounds of the original text"),
+            DeltaError::ResultLengthMismatch => {
+                write!(f, "delta doesn't produce a text of the expected length")
+            }
+            DeltaError::InvalidUtf8 => write!(f, "delta doesn't produce valid utf8"),
+            DeltaError::UnsupportedFormat => write!(f, "unsupported binary delta format"),
+            DeltaError::BaseChecksumMismatch => {
+                write!(f, "delta doesn't apply to content with this checksum")
+            }
+            DeltaError::ResultChecksumMismatch => {
+                write!(
+                    f,
+                    "delta doesn't produce content with the expected checksum"
+                )
+            }
+            DeltaError::ReservedOpcode => write!(f, "delta contains a reserved opcode"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DeltaError {}
+
+/// Encode the differences between two texts as a compact binary delta
+///
+/// Unlike a [`Patch`], a delta doesn't contain any context or deleted text: it only describes
+/// how to build the modified text by copying ranges of the original text and inserting new
+/// bytes, making it much smaller for storing or sending changes to large texts. It's turned back
+/// into the modified text with [`apply_delta`].
+///
+/// The texts are diffed line by line, and only the changed lines are diffed char by char. Both
+/// steps give up past a fixed number of edits, so encoding stays fast on large texts, at the cost
+/// of inserting very different regions whole instead of copying the little they have in common.
+///
+/// # Format
+///
+/// The format is stable, so deltas can be stored and exchanged between versions of this crate.
+/// All numbers are unsigned LEB128 varints: 7 bits per byte, least significant group first,
+/// with the high bit set on every byte but the last. A delta consists of:
+///
+/// 1. the length in bytes of the original text
+/// 2. the length in bytes of the modified text
+/// 3. any number of operations, each starting with a number `n`:
+///    - if `n` is even, it's a copy of `n >> 1` bytes from the original text, starting at the
+///      byte offset given by the number which follows
+///    - if `n` is odd, it's an insert of the `n >> 1` bytes which follow
+///
+/// The modified text is the concatenation of the bytes produced by every operation in order.
+///
+/// ```
+/// use diffy::{apply_delta, encode_delta};
+///
+/// let original = "The Way of Kings\nWords of Radiance\nOathbringer\n";
+/// let modified = "The Way of Kings\nWords of Radiance\nRhythm of War\n";
+///
+/// let delta = encode_delta(original, modified);
+/// assert_eq!(apply_delta(original, &delta).unwrap(), modified);
+/// ```
+///
+/// [`Patch`]: struct.Patch.html
+/// [`apply_delta`]: fn.apply_delta.html
+pub fn encode_delta(old: &str, new: &str) -> Vec<u8> {
+    let mut delta = Vec::new();
+    write_varint(&mut delta, old.len());
+    write_varint(&mut delta, new.len());
+
+    // Short equal ranges are cheaper to insert than to copy, so they're gathered into inserts
+    // along with the surrounding inserted text
+    let mut insert = 0..0;
+    for piece in pieces(old, new) {
+        match piece.old {
+            Some(start) if piece.new.len() > copy_cost(start, piece.new.len()) => {
+                write_insert(&mut delta, &new.as_bytes()[insert.clone()]);
+                write_varint(&mut delta, piece.new.len() << 1);
+                write_varint(&mut delta, start);
+                insert = piece.new.end..piece.new.end;
+            }
+            _ => {
+                if insert.is_empty() {
+                    insert.start = piece.new.start;
+                }
+                insert.end = piece.new.end;
+            }
+        }
+    }
+    write_insert(&mut delta, &new.as_bytes()[insert]);
+
+    delta
+}
+
+// The most lines which are diffed line by line, and the most edits a changed region is diffed
+// char by char with, before falling back to cheaper ways of splitting the texts. This keeps
+// encoding linear in the length of the texts.
+const MAX_CHANGED_LINES: usize = 1024;
+const MAX_REFINE_COST: usize = 256;
+
+// A piece of the modified text, which is either copied from the original text starting at byte
+// `old` or inserted
+struct Piece {
+    old: Option<usize>,
+    new: ops::Range<usize>,
+}
+
+// Splits the modified text into pieces copied from the original text and inserted pieces. The
+// texts are diffed line by line, and only the regions of changed lines are diffed char by char.
+fn pieces(old: &str, new: &str) -> Vec<Piece> {
+    let offsets = |text: &str| -> Vec<usize> {
+        let mut offsets = vec![0];
+        for line in LineIter::new(text) {
+            offsets.push(offsets.last().unwrap() + line.len());
+        }
+        offsets
+    };
+    let old_offsets = offsets(old);
+    let new_offsets = offsets(new);
+    let old = (old, &old_offsets[..]);
+    let new = (new, &new_offsets[..]);
+
+    let mut pieces = Vec::new();
+    let mut options = DiffOptions::new();
+    options.set_max_changed_lines(Some(MAX_CHANGED_LINES));
+    let diff = match options.try_diff_lines(old.0, new.0) {
+        Ok(diff) => diff,
+        // Texts this different are a single changed region
+        Err(_) => {
+            refine_lines(
+                old,
+                0..old_offsets.len() - 1,
+                new,
+                0..new_offsets.len() - 1,
+                &mut pieces,
+            );
+            return pieces;
+        }
+    };
+
+    let mut deleted = None;
+    for op in diff.ops() {
+        match op.kind {
+            DiffOpKind::Equal => {
+                deleted = None;
+                pieces.push(Piece {
+                    old: Some(old_offsets[op.old.start]),
+                    new: new_offsets[op.new.start]..new_offsets[op.new.end],
+                });
+            }
+            // A deletion only matters if lines are inserted in its place
+            DiffOpKind::Delete => deleted = Some(op.old),
+            DiffOpKind::Insert => {
+                let old_lines = deleted.take().unwrap_or(op.old);
+                refine_lines(old, old_lines, new, op.new, &mut pieces);
+            }
+        }
+    }
+    pieces
+}
+
+// Diffs a region of changed lines char by char. If they differ too much as a whole but there are
+// as many old as new lines, each line is diffed against the one replacing it instead.
+fn refine_lines(
+    (old, old_offsets): (&str, &[usize]),
+    old_lines: ops::Range<usize>,
+    (new, new_offsets): (&str, &[usize]),
+    new_lines: ops::Range<usize>,
+    pieces: &mut Vec<Piece>,
+) {
+    let old_bytes = old_offsets[old_lines.start]..old_offsets[old_lines.end];
+    let new_bytes = new_offsets[new_lines.start]..new_offsets[new_lines.end];
+    if refine(old, old_bytes, new, new_bytes.clone(), pieces) {
+        return;
+    }
+    if old_lines.len() == new_lines.len() {
+        for (old_line, new_line) in old_lines.zip(new_lines) {
+            let old_bytes = old_offsets[old_line]..old_offsets[old_line + 1];
+            let new_bytes = new_offsets[new_line]..new_offsets[new_line + 1];
+            if !refine(old, old_bytes, new, new_bytes.clone(), pieces) {
+                pieces.push(Piece {
+                    old: None,
+                    new: new_bytes,
+                });
+            }
+        }
+    } else {
+        pieces.push(Piece {
+            old: None,
+            new: new_bytes,
+        });
+    }
+}
+
+// Diffs two ranges of the texts char by char, returning `false` without adding any pieces if
+// that takes more than `MAX_REFINE_COST` edits
+fn refine(
+    old: &str,
+    old_bytes: ops::Range<usize>,
+    new: &str,
+    new_bytes: ops::Range<usize>,
+    pieces: &mut Vec<Piece>,
+) -> bool {
+    let (old_chars, old_starts) = char_starts(&old[old_bytes.clone()]);
+    let (new_chars, new_starts) = char_starts(&new[new_bytes.clone()]);
+    let solution =
+        match DiffOptions::default().diff_slice_bounded(&old_chars, &new_chars, MAX_REFINE_COST) {
+            Some(solution) => solution,
+            None => return false,
+        };
+    let new_range = |range: Range<'_, [char]>| {
+        new_bytes.start + new_starts[range.offset()]
+            ..new_bytes.start + new_starts[range.offset() + range.len()]
+    };
+    for diff_range in solution {
+        match diff_range {
+            DiffRange::Equal(old_range, new) => pieces.push(Piece {
+                old: Some(old_bytes.start + old_starts[old_range.offset()]),
+                new: new_range(new),
+            }),
+            DiffRange::Delete(_) => {}
+            DiffRange::Insert(new) => pieces.push(Piece {
+                old: None,
+                new: new_range(new),
+            }),
+        }
+    }
+    true
+}
+
+/// Apply a delta created by [`encode_delta`] to the original text it was created from,
+/// returning the modified text
+///
+/// ```
+/// use diffy::{apply_delta, encode_delta, DeltaError};
+///
+/// let delta = encode_delta("abc\n", "abd\n");
+/// assert_eq!(apply_delta("abc\n", &delta).unwrap(), "abd\n");
+/// assert_eq!(apply_delta("a\n", &delta), Err(DeltaError::BaseLengthMismatch));
+/// ```
+///
+/// [`encode_delta`]: fn.encode_delta.html
+pub fn apply_delta(old: &str, delta: &[u8]) -> Result<String, DeltaError> {
+    let mut delta = delta;
+    if read_varint(&mut delta)? != old.len() {
+        return Err(DeltaError::BaseLengthMismatch);
+    }
+    let new_len = read_varint(&mut delta)?;
+
+    // The length comes from the input so it's only used as a hint, within reason
+    let mut new = Vec::with_capacity(new_len.min(old.len().saturating_add(delta.len())));
+    while !delta.is_empty() {
+        let n = read_varint(&mut delta)?;
+        let len = n >> 1;
+        let bytes = if n & 1 == 0 {
+            let start = read_varint(&mut delta)?;
+            start
+                .checked_add(len)
+                .and_then(|end| old.as_bytes().get(start..end))
+                .ok_or(DeltaError::CopyOutOfBounds)?
+        } else {
+            if delta.len() < len {
+                return Err(DeltaError::UnexpectedEof);
+            }
+            let (bytes, rest) = delta.split_at(len);
+            delta = rest;
+            bytes
+        };
+
+        if new.len() + bytes.len() > new_len {
+            return Err(DeltaError::ResultLengthMismatch);
+        }
+        new.extend_from_slice(bytes);
+    }
+
+    if new.len() != new_len {
+        return Err(DeltaError::ResultLengthMismatch);
+    }
+    String::from_utf8(new).map_err(|_| DeltaError::InvalidUtf8)
+}
+
+// The number of bytes taken up by a copy operation
+fn copy_cost(start: usize, len: usize) -> usize {
+    varint_len(len << 1) + varint_len(start)
+}
+
+fn write_insert(delta: &mut Vec<u8>, bytes: &[u8]) {
+    if !bytes.is_empty() {
+        write_varint(delta, bytes.len() << 1 | 1);
+        delta.extend_from_slice(bytes);
+    }
+}
+
+fn varint_len(mut n: usize) -> usize {
+    let mut len = 1;
+    while n >= 0x80 {
+        n >>= 7;
+        len += 1;
+    }
+    len
+}
+
+pub(crate) fn write_varint(delta: &mut Vec<u8>, mut n: usize) {
+    while n >= 0x80 {
+        delta.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    delta.push(n as u8);
+}
+
+pub(crate) fn read_varint(delta: &mut &[u8]) -> Result<usize, DeltaError> {
+    let mut n: usize = 0;
+    let mut shift = 0;
+    loop {
+        let (&byte, rest) = delta.split_first().ok_or(DeltaError::UnexpectedEof)?;
+        *delta = rest;
+
+        let bits = usize::from(byte & 0x7f);
+        if shift >= usize::BITS || (bits << shift) >> shift != bits {
+            return Err(DeltaError::Overflow);
+        }
+        n |= bits << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_delta, encode_delta, read_varint, write_varint, DeltaError};
+    use crate::create_patch;
+    use alloc::{string::String, vec, vec::Vec};
+
+    #[test]
+    fn varints() {
+        for n in [0, 1, 127, 128, 300, 16383, 16384, usize::MAX] {
+            let mut bytes = Vec::new();
+            write_varint(&mut bytes, n);
+            assert_eq!(bytes.len(), super::varint_len(n));
+            let mut slice = &bytes[..];
+            assert_eq!(read_varint(&mut slice), Ok(n));
+            assert!(slice.is_empty());
+        }
+
+        let mut bytes = Vec::new();
+        write_varint(&mut bytes, 300);
+        assert_eq!(bytes, [0xac, 0x02]);
+        assert_eq!(
+            read_varint(&mut &[0x80][..]),
+            Err(DeltaError::UnexpectedEof)
+        );
+        assert_eq!(read_varint(&mut &[0xff; 11][..]), Err(DeltaError::Overflow));
+    }
+
+    #[test]
+    fn wire_format() {
+        let delta = encode_delta("hello world\n", "hello there world\n");
+        let mut expected = vec![12, 18];
+        // Copy "hello " from offset 0
+        expected.extend([6 << 1, 0]);
+        // Insert "there "
+        expected.push(6 << 1 | 1);
+        expected.extend(b"there ");
+        // Copy "world\n" from offset 6
+        expected.extend([6 << 1, 6]);
+        assert_eq!(delta, expected);
+    }
+
+    #[test]
+    fn round_trips() {
+        let cases = [
+            ("", ""),
+            ("", "new\n"),
+            ("old\n", ""),
+            ("same\n", "same\n"),
+            ("a\nb\nc\n", "a\nB\nc"),
+            ("Gruß\n", "Grüße\n"),
+            ("c", "\u{e9}"),
+            ("ab", "\u{e9}b"),
+            ("\u{2603}\u{2603}\n", "\u{2604}\u{2603}\n"),
+            ("日本語のテキスト\nです\n", "日本のテキスト!\nです\n"),
+        ];
+        for (old, new) in cases {
+            assert_eq!(apply_delta(old, &encode_delta(old, new)).unwrap(), new);
+        }
+
+        let old: String = (0..2000).map(|i| alloc::format!("line {}\n", i)).collect();
+        let new = old
+            .replace("line 1000\n", "changed\n")
+            .replace("line 5\n", "");
+        let delta = encode_delta(&old, &new);
+        assert_eq!(apply_delta(&old, &delta).unwrap(), new);
+        assert!(delta.len() < create_patch(&old, &new).to_bytes().len());
+        assert!(delta.len() < 40);
+    }
+
+    #[test]
+    fn large_texts() {
+        // Long lines which have nothing in common are inserted whole
+        let old: String = (0..100_000)
+            .map(|i| char::from(b'a' + (i * 7 % 13) as u8))
+            .collect();
+        let new: String = (0..100_000)
+            .map(|i| char::from(b'a' + (i * 5 % 11) as u8))
+            .collect();
+        let delta = encode_delta(&old, &new);
+        assert_eq!(apply_delta(&old, &delta).unwrap(), new);
+        assert!(delta.len() <= new.len() + 10);
+
+        // Every line changing a little is still diffed char by char, line by line
+        let old: String = (0..20_000)
+            .map(|i| alloc::format!("let x{} = {};\n", i, i))
+            .collect();
+        let new = old.replace("let", "const");
+        let delta = encode_delta(&old, &new);
+        assert_eq!(apply_delta(&old, &delta).unwrap(), new);
+        assert!(delta.len() < new.len() / 2);
+    }
+
+    #[test]
+    fn invalid_deltas() {
+        let old = "abc";
+        assert_eq!(apply_delta(old, &[]), Err(DeltaError::UnexpectedEof));
+        assert_eq!(
+            apply_delta(old, &[2, 0]),
+            Err(DeltaError::BaseLengthMismatch)
+        );
+        assert_eq!(
+            apply_delta(old, &[3, 2, 4, 2]),
+            Err(DeltaError::CopyOutOfBounds)
+        );
+        assert_eq!(
+            apply_delta(old, &[3, 2, 5, b'x']),
+            Err(DeltaError::UnexpectedEof)
+        );
+        assert_eq!(
+            apply_delta(old, &[3, 2, 2, 0]),
+            Err(DeltaError::ResultLengthMismatch)
+        );
+        assert_eq!(
+            apply_delta(old, &[3, 1, 6, 0]),
+            Err(DeltaError::ResultLengthMismatch)
+        );
+        assert_eq!(
+            apply_delta(old, &[3, 1, 3, 0xff]),
+            Err(DeltaError::InvalidUtf8)
+        );
+        assert_eq!(apply_delta(old, &[3, 2, 4, 0]).unwrap(), "ab");
+    }
+}
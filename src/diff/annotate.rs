@@ -0,0 +1,89 @@
+//! Attribute each line of a text to the version of it which introduced the line
+
+use super::{DiffOpKind, DiffOptions};
+use alloc::vec::Vec;
+
+/// Where a line of the last version passed to [`annotate`] comes from
+///
+/// [`annotate`]: fn.annotate.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LineOrigin {
+    /// The index of the version which introduced the line
+    pub version: usize,
+    /// The 1-based line number of the line in that version
+    pub lineno: usize,
+}
+
+impl DiffOptions {
+    /// Find the version which introduced each line of the last of `versions`, based on the
+    /// configured options
+    ///
+    /// See [`annotate`] for details. Lines of a block which was moved keep their origin only
+    /// when move detection is enabled with [`set_detect_moves`].
+    ///
+    /// [`annotate`]: fn.annotate.html
+    /// [`set_detect_moves`]: #method.set_detect_moves
+    pub fn annotate<S: AsRef<str>>(&self, versions: &[S]) -> Vec<LineOrigin> {
+        let first = match versions.first() {
+            Some(first) => first.as_ref(),
+            None => return Vec::new(),
+        };
+        let mut origins: Vec<LineOrigin> = (1..=first.split_inclusive('\n').count())
+            .map(|lineno| LineOrigin { version: 0, lineno })
+            .collect();
+
+        for (index, pair) in versions.windows(2).enumerate() {
+            let version = index + 1;
+            let diff = self.diff_lines(pair[0].as_ref(), pair[1].as_ref());
+
+            // Every line starts out as introduced by this version, then the lines which are
+            // unchanged or moved from the previous version inherit their origin from it
+            let mut next: Vec<LineOrigin> = (1..=diff.new_lines().len())
+                .map(|lineno| LineOrigin { version, lineno })
+                .collect();
+            let carried = diff
+                .ops()
+                .into_iter()
+                .filter(|op| op.kind == DiffOpKind::Equal)
+                .map(|op| (op.old, op.new))
+                .chain(diff.moves().iter().map(|m| (m.old.clone(), m.new.clone())));
+            for (old, new) in carried {
+                next[new].copy_from_slice(&origins[old]);
+            }
+            origins = next;
+        }
+
+        origins
+    }
+}
+
+/// Find the version which introduced each line of the last of `versions`
+///
+/// `versions` is the history of a text, from oldest to newest. One [`LineOrigin`] is returned
+/// for each line of the last version, giving the index of the version in which the line was
+/// added or last modified along with its line number there. The origins are found by diffing
+/// each version against the previous one, so the lines which are equal in the two versions
+/// carry their origin over, while every other line of the newer version is attributed to it.
+/// Move detection is enabled, so a block of lines moved elsewhere keeps its origin too. When
+/// a line appears several times, each occurrence is matched up by the diff separately.
+///
+/// The versions can be anything which can be borrowed as a `str`, like `&str`s or `String`s.
+///
+/// ```
+/// use diffy::annotate;
+///
+/// let versions = ["a\nb\n", "a\nB\nc\n", "z\na\nB\nc\n"];
+/// let versions_and_linenos: Vec<_> = annotate(&versions)
+///     .into_iter()
+///     .map(|origin| (origin.version, origin.lineno))
+///     .collect();
+/// assert_eq!(versions_and_linenos, [(2, 1), (0, 1), (1, 2), (1, 3)]);
+///
+/// let owned: Vec<String> = versions.iter().map(|version| version.to_string()).collect();
+/// assert_eq!(annotate(&owned), annotate(&versions));
+/// ```
+///
+/// [`LineOrigin`]: struct.LineOrigin.html
+pub fn annotate<S: AsRef<str>>(versions: &[S]) -> Vec<LineOrigin> {
+    DiffOptions::new().set_detect_moves(true).annotate(versions)
+}
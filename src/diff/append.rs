@@ -0,0 +1,261 @@
+//! Keep a diff up to date as text is appended to the modified text
+
+use super::{build_edit_script, DiffLines, DiffOptions, EditRange};
+use crate::{patch::Patch, utils::LineIter};
+use alloc::{string::String, vec, vec::Vec};
+
+// How many lines of the original text past those appended are searched for lines to match up
+const LOOKAHEAD: usize = 64;
+
+/// The line-by-line differences between two texts, kept up to date as text is appended to the
+/// modified one, e.g. to follow a growing log file
+///
+/// Appending text doesn't change the lines before it, so the part of the diff up to the last
+/// lines both texts have in common stays valid and only the lines after them are diffed again.
+/// Appended lines which don't occur anywhere in the original text can't be matched up with it, so
+/// they simply extend the lines inserted at the end without diffing anything, e.g. once the
+/// modified text grows past the end of the original one.
+///
+/// Lines which were matched up stay matched up, and appended lines are only matched up with
+/// lines of the original text not too far ahead of them, so the result may differ from diffing
+/// both texts from scratch, but it's always a valid diff between them.
+///
+/// ```
+/// use diffy::AppendDiff;
+///
+/// let original = "start\nsteady\nstop\n";
+/// let mut diff = AppendDiff::new(original, "start\n");
+///
+/// diff.append_new("steady\n");
+/// diff.append_new("halt\n");
+/// assert_eq!(diff.modified(), "start\nsteady\nhalt\n");
+///
+/// let expected = "\
+/// --- original
+/// +++ modified
+/// @@ -1,3 +1,3 @@
+///  start
+///  steady
+/// -stop
+/// +halt
+/// ";
+/// assert_eq!(diff.to_patch(3).to_string(), expected);
+/// ```
+#[derive(Clone, Debug)]
+pub struct AppendDiff {
+    original: String,
+    modified: String,
+    // The offset each line of both texts starts at
+    old_starts: Vec<usize>,
+    new_starts: Vec<usize>,
+    // The lines of the original text ordered by their contents, to look up appended lines
+    old_sorted: Vec<usize>,
+    // The id of each line of both texts: equal lines of the original text share the index of
+    // the first of them, and lines of the modified text missing from it get an id past its end
+    old_ids: Vec<usize>,
+    new_ids: Vec<usize>,
+    // The lines of the modified text which also occur in the original text after the last lines
+    // both texts had in common when they were appended, in order
+    new_matched: Vec<usize>,
+    edit_script: Vec<EditRange>,
+}
+
+impl AppendDiff {
+    /// Compute the line-by-line differences between `original` and `modified`
+    pub fn new(original: &str, modified: &str) -> Self {
+        let old_starts = line_starts(original, 0);
+        let line = |index: usize| line_at(original, &old_starts, index);
+        let mut old_sorted: Vec<usize> = (0..old_starts.len()).collect();
+        // The sort is stable, so equal lines stay ordered by their index
+        old_sorted.sort_by(|&a, &b| line(a).cmp(line(b)));
+        let mut old_ids = vec![0; old_starts.len()];
+        for (i, &index) in old_sorted.iter().enumerate() {
+            old_ids[index] = match i.checked_sub(1).map(|prev| old_sorted[prev]) {
+                Some(prev) if line(prev) == line(index) => old_ids[prev],
+                _ => index,
+            };
+        }
+
+        let mut diff = Self {
+            original: original.into(),
+            modified: String::new(),
+            old_starts,
+            new_starts: Vec::new(),
+            old_sorted,
+            old_ids,
+            new_ids: Vec::new(),
+            new_matched: Vec::new(),
+            edit_script: Vec::new(),
+        };
+        // Until lines are appended every line of the original text is deleted
+        if !diff.old_starts.is_empty() {
+            let old_len = diff.old_starts.len();
+            diff.edit_script.push(EditRange::new(0..old_len, 0..0));
+        }
+        diff.append_new(modified);
+        diff
+    }
+
+    /// Returns the original text
+    pub fn original(&self) -> &str {
+        &self.original
+    }
+
+    /// Returns the modified text, including everything appended to it
+    pub fn modified(&self) -> &str {
+        &self.modified
+    }
+
+    /// Append `extra` to the modified text, diffing only the lines after the last lines both
+    /// texts have in common
+    ///
+    /// If the modified text doesn't end with a newline, `extra` continues its last line.
+    pub fn append_new(&mut self, extra: &str) {
+        if extra.is_empty() {
+            return;
+        }
+
+        // A last line without a line ending is continued by the appended text
+        let mut kept_lines = self.new_starts.len();
+        if !self.modified.is_empty() && !self.modified.ends_with('\n') {
+            kept_lines -= 1;
+        }
+        let (old_start, new_start) = self.truncate_edit_script(kept_lines);
+
+        let offset = line_offset(&self.modified, &self.new_starts, kept_lines);
+        self.new_starts.truncate(kept_lines);
+        self.new_ids.truncate(kept_lines);
+        while self
+            .new_matched
+            .last()
+            .map_or(false, |&line| line >= kept_lines)
+        {
+            self.new_matched.pop();
+        }
+        self.modified.push_str(extra);
+        let appended = line_starts(&self.modified[offset..], offset);
+        self.new_starts.extend(appended);
+        for index in kept_lines..self.new_starts.len() {
+            let found = self.find_original(line_at(&self.modified, &self.new_starts, index));
+            // A line only occurring before the lines still to be diffed can't be matched up, e.g.
+            // a line repeated over and over, so it doesn't need diffing either
+            if found.map_or(false, |(_, last)| last >= old_start) {
+                self.new_matched.push(index);
+            }
+            self.new_ids
+                .push(found.map_or(self.old_starts.len() + index, |(id, _)| id));
+        }
+
+        // Without any line to match up, the rest of the original text is replaced entirely
+        let (old_len, new_len) = (self.old_starts.len(), self.new_starts.len());
+        if self
+            .new_matched
+            .last()
+            .map_or(true, |&line| line < new_start)
+        {
+            if old_start < old_len || new_start < new_len {
+                self.edit_script
+                    .push(EditRange::new(old_start..old_len, new_start..new_len));
+            }
+            return;
+        }
+
+        // Only the lines of the original text not too far ahead of the modified one are diffed,
+        // the ones after them are deleted until enough lines are appended to reach them
+        let old_end = old_len.min(old_start + 2 * (new_len - new_start) + LOOKAHEAD);
+        let solution = DiffOptions::default().diff_slice(
+            &self.old_ids[old_start..old_end],
+            &self.new_ids[new_start..],
+        );
+        self.edit_script
+            .extend(build_edit_script(&solution).into_iter().map(|edit| {
+                EditRange::new(
+                    old_start + edit.old.start..old_start + edit.old.end,
+                    new_start + edit.new.start..new_start + edit.new.end,
+                )
+            }));
+        if old_end < old_len {
+            match self.edit_script.last_mut() {
+                Some(edit) if edit.old.end == old_end && edit.new.end == new_len => {
+                    edit.old.end = old_len;
+                }
+                _ => self
+                    .edit_script
+                    .push(EditRange::new(old_end..old_len, new_len..new_len)),
+            }
+        }
+    }
+
+    /// Returns the differences as a [`DiffLines`] borrowing both texts
+    ///
+    /// [`DiffLines`]: struct.DiffLines.html
+    pub fn diff_lines(&self) -> DiffLines<'_, str> {
+        DiffLines::new(
+            LineIter::new(self.original.as_str()).collect(),
+            LineIter::new(self.modified.as_str()).collect(),
+            self.edit_script.clone(),
+        )
+    }
+
+    /// Produce a Patch using `context_len` lines of context around each change
+    pub fn to_patch(&self, context_len: usize) -> Patch<'_, str> {
+        self.diff_lines().to_patch(context_len)
+    }
+
+    // Returns the id of the lines of the original text equal to `line` along with the index of
+    // the last of them, if there are any
+    fn find_original(&self, line: &str) -> Option<(usize, usize)> {
+        let end = self
+            .old_sorted
+            .partition_point(|&index| line_at(&self.original, &self.old_starts, index) <= line);
+        let last = self.old_sorted[..end].last().copied()?;
+        if line_at(&self.original, &self.old_starts, last) == line {
+            Some((self.old_ids[last], last))
+        } else {
+            None
+        }
+    }
+
+    // Drops the changes which may depend on lines after the first `kept_lines` lines of the
+    // modified text, returning the line of each text up to which the rest of the edit script
+    // holds. That's always right after a line both texts have in common, or the start of both.
+    fn truncate_edit_script(&mut self, kept_lines: usize) -> (usize, usize) {
+        let mut new_end = self.new_starts.len();
+        loop {
+            let (old_start, new_start) = self
+                .edit_script
+                .last()
+                .map_or((0, 0), |edit| (edit.old.end, edit.new.end));
+            // The lines after the last change are equal in both texts
+            let equal_end = new_end.min(kept_lines);
+            if new_start < equal_end || self.edit_script.is_empty() {
+                let len = equal_end - new_start;
+                return (old_start + len, new_start + len);
+            }
+            let edit = self.edit_script.pop().unwrap();
+            new_end = edit.new.start;
+        }
+    }
+}
+
+// Returns the offset each line of `text` starts at, shifted by `offset`
+fn line_starts(text: &str, offset: usize) -> Vec<usize> {
+    let mut start = offset;
+    LineIter::new(text)
+        .map(|line| {
+            let line_start = start;
+            start += line.len();
+            line_start
+        })
+        .collect()
+}
+
+// Returns line `line` of `text`, including its line ending
+fn line_at<'a>(text: &'a str, starts: &[usize], line: usize) -> &'a str {
+    &text[starts[line]..line_offset(text, starts, line + 1)]
+}
+
+// Returns the offset line `line` of `text` starts at, or the length of `text` past its last line
+fn line_offset(text: &str, starts: &[usize], line: usize) -> usize {
+    starts.get(line).copied().unwrap_or(text.len())
+}
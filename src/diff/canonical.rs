@@ -0,0 +1,91 @@
+use super::DiffOptions;
+use crate::{
+    apply::{ApplyError, ApplyOptions, Image},
+    patch::Patch,
+    utils::{LineIter, Text},
+};
+use alloc::{
+    borrow::{Cow, ToOwned},
+    vec::Vec,
+};
+
+impl<'a> Patch<'a, str> {
+    /// Rewrite the patch into the form diffy itself would produce for the same change
+    ///
+    /// The patch is applied to `original` and the result is diffed against it again using
+    /// `options`, so the returned patch makes exactly the same change as this one but with the
+    /// context length, hunk boundaries and line alignment chosen by `options`. This gives
+    /// patches coming from different tools a canonical form, e.g. for storing or deduplicating
+    /// them. The file names are kept as they are.
+    ///
+    /// If the patch doesn't apply to `original` the error from applying it is returned.
+    ///
+    /// ```
+    /// use diffy::{apply, DiffOptions, Patch};
+    ///
+    /// let original = "a\nb\nc\nd\ne\n";
+    /// let s = "\
+    /// --- a/file
+    /// +++ b/file
+    /// @@ -2 +2 @@
+    /// -b
+    /// +B
+    /// ";
+    /// let patch = Patch::from_str(s).unwrap();
+    ///
+    /// let normalized = patch.normalize(original, &DiffOptions::new()).unwrap();
+    /// let expected = "\
+    /// --- a/file
+    /// +++ b/file
+    /// @@ -1,5 +1,5 @@
+    ///  a
+    /// -b
+    /// +B
+    ///  c
+    ///  d
+    ///  e
+    /// ";
+    /// assert_eq!(normalized.to_string(), expected);
+    /// assert_eq!(apply(original, &normalized), apply(original, &patch));
+    /// ```
+    pub fn normalize<'b>(
+        &'b self,
+        original: &'b str,
+        options: &DiffOptions,
+    ) -> Result<Patch<'b, str>, ApplyError> {
+        normalize(self, original, options)
+    }
+}
+
+impl<'a> Patch<'a, [u8]> {
+    /// Rewrite a potentially non-utf8 patch into the form diffy itself would produce for the
+    /// same change, see [`Patch::normalize`](struct.Patch.html#method.normalize)
+    pub fn normalize<'b>(
+        &'b self,
+        original: &'b [u8],
+        options: &DiffOptions,
+    ) -> Result<Patch<'b, [u8]>, ApplyError> {
+        normalize(self, original, options)
+    }
+}
+
+fn normalize<'b, T: Text + ToOwned + ?Sized>(
+    patch: &'b Patch<'_, T>,
+    original: &'b T,
+    options: &DiffOptions,
+) -> Result<Patch<'b, T>, ApplyError> {
+    // The patched text is made up of lines borrowed from the original and the patch, so it
+    // never needs to be built
+    let mut image = Image::new(original);
+    image.apply(patch, &ApplyOptions::new())?;
+    let new_lines: Vec<&T> = image.lines().collect();
+    let old_lines: Vec<&T> = LineIter::new(original).collect();
+
+    let diff = options.diff_line_slices(old_lines, new_lines);
+    let (pre, post) = options.context_lens();
+    Ok(Patch::new(
+        patch.original().map(Cow::Borrowed),
+        patch.modified().map(Cow::Borrowed),
+        diff.hunks(pre, post),
+    ))
+}
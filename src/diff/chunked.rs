@@ -0,0 +1,245 @@
+use super::{
+    myers::{self, Deadline, Progress},
+    DiffOp, DiffOpKind,
+};
+use crate::{range::DiffRange, utils::Classifier};
+use alloc::vec::Vec;
+use core::cmp;
+
+// The bounds on the average chunk length, which keep the longest chunks representable
+const MIN_CHUNK_LEN: usize = 64;
+const MAX_CHUNK_LEN: usize = 1 << 28;
+// The most bytes the byte-by-byte diff of a differing region can insert and delete, which keeps
+// refining regions whose bytes mostly differ from taking quadratic time
+const MAX_REFINE_COST: usize = 1024;
+
+/// A collection of options for modifying the way [`diff_chunked`] compares two inputs
+///
+/// [`diff_chunked`]: fn.diff_chunked.html
+#[derive(Clone, Debug)]
+pub struct ChunkOptions {
+    chunk_len: usize,
+    max_refine_len: usize,
+}
+
+impl ChunkOptions {
+    /// Construct a new `ChunkOptions` with default settings
+    ///
+    /// ## Defaults
+    /// * chunk_len = 8192
+    /// * max_refine_len = 65536
+    pub fn new() -> Self {
+        Self {
+            chunk_len: 8192,
+            max_refine_len: 65536,
+        }
+    }
+
+    /// Set the average length of the chunks the inputs are split into, which is rounded up to a
+    /// power of two of at least 64
+    ///
+    /// Chunks are between a quarter and four times as long as this. Shorter chunks narrow down
+    /// changed regions more closely, but leave more chunks to compare.
+    pub fn set_chunk_len(&mut self, chunk_len: usize) -> &mut Self {
+        self.chunk_len = chunk_len
+            .clamp(MIN_CHUNK_LEN, MAX_CHUNK_LEN)
+            .next_power_of_two();
+        self
+    }
+
+    /// Set the maximum combined length of the original and modified bytes of a differing region
+    /// for it to be refined with a byte-by-byte diff
+    ///
+    /// Regions which are longer, or all regions if this is `0`, are reported as a deletion of
+    /// their original bytes followed by an insertion of their modified bytes. So are regions
+    /// which would take more than 1024 inserted and deleted bytes to describe.
+    pub fn set_max_refine_len(&mut self, max_refine_len: usize) -> &mut Self {
+        self.max_refine_len = max_refine_len;
+        self
+    }
+}
+
+impl Default for ChunkOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Compute the differences between two potentially very large binary inputs as byte ranges
+///
+/// Both inputs are split into chunks whose boundaries depend only on the bytes around them, so
+/// that an insertion or deletion only changes the chunks it touches. The sequences of chunks are
+/// then diffed instead of the bytes, which takes roughly linear time in the length of the
+/// inputs as long as few chunks differ. Differing regions no longer than
+/// [`set_max_refine_len`] are refined with a byte-by-byte diff.
+///
+/// Adjacent ops of the same kind are merged, and the ops tile both inputs like the ones returned
+/// by [`diff_slice_ops`].
+///
+/// ```
+/// use diffy::{diff_chunked, ChunkOptions, DiffOpKind};
+///
+/// let old: Vec<u8> = (0..100_000u32).map(|i| (i * 7919 % 65_521) as u8).collect();
+/// let mut new = old.clone();
+/// new[50_000..50_004].copy_from_slice(b"diff");
+///
+/// let ops = diff_chunked(&old, &new, &ChunkOptions::new());
+/// let changed: Vec<_> = ops
+///     .iter()
+///     .filter(|op| op.kind != DiffOpKind::Equal)
+///     .map(|op| (op.old.clone(), op.new.clone()))
+///     .collect();
+/// assert!(changed.iter().all(|(old, _)| old.start >= 50_000 && old.end <= 50_004));
+/// ```
+///
+/// [`set_max_refine_len`]: struct.ChunkOptions.html#method.set_max_refine_len
+/// [`diff_slice_ops`]: fn.diff_slice_ops.html
+pub fn diff_chunked(old: &[u8], new: &[u8], options: &ChunkOptions) -> Vec<DiffOp> {
+    let old_bounds = chunk_bounds(old, options.chunk_len);
+    let new_bounds = chunk_bounds(new, options.chunk_len);
+
+    let mut classifier = Classifier::<[u8]>::default();
+    let old_ids: Vec<_> = old_bounds
+        .windows(2)
+        .map(|w| classifier.classify(&old[w[0]..w[1]]))
+        .collect();
+    let new_ids: Vec<_> = new_bounds
+        .windows(2)
+        .map(|w| classifier.classify(&new[w[0]..w[1]]))
+        .collect();
+
+    let mut ops = Vec::new();
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for diff_range in myers::diff(&old_ids, &new_ids, Deadline::none(), &mut Progress::none()) {
+        if let DiffRange::Equal(old_chunks, new_chunks) = diff_range {
+            let old_start = old_bounds[old_chunks.offset()];
+            let new_start = new_bounds[new_chunks.offset()];
+            push_changes(
+                &mut ops,
+                &old[old_pos..old_start],
+                &new[new_pos..new_start],
+                options.max_refine_len,
+            );
+
+            old_pos = old_bounds[old_chunks.range().end];
+            new_pos = new_bounds[new_chunks.range().end];
+            push_op(
+                &mut ops,
+                DiffOpKind::Equal,
+                old_pos - old_start,
+                new_pos - new_start,
+            );
+        }
+    }
+    push_changes(
+        &mut ops,
+        &old[old_pos..],
+        &new[new_pos..],
+        options.max_refine_len,
+    );
+
+    ops
+}
+
+// Appends the ops describing a region in which no chunks matched
+fn push_changes(ops: &mut Vec<DiffOp>, old: &[u8], new: &[u8], max_refine_len: usize) {
+    let refined = if old.len() + new.len() <= max_refine_len {
+        myers::diff_bounded(
+            old,
+            new,
+            MAX_REFINE_COST,
+            Deadline::none(),
+            &mut Progress::none(),
+        )
+    } else {
+        None
+    };
+    let refined = match refined {
+        Some(refined) => refined,
+        None => {
+            push_op(ops, DiffOpKind::Delete, old.len(), 0);
+            push_op(ops, DiffOpKind::Insert, 0, new.len());
+            return;
+        }
+    };
+
+    for diff_range in refined {
+        match diff_range {
+            DiffRange::Equal(range, _) => push_op(ops, DiffOpKind::Equal, range.len(), range.len()),
+            DiffRange::Delete(range) => push_op(ops, DiffOpKind::Delete, range.len(), 0),
+            DiffRange::Insert(range) => push_op(ops, DiffOpKind::Insert, 0, range.len()),
+        }
+    }
+}
+
+// Appends an op covering the given number of bytes of each input after the previous op, merging
+// it into the previous op if they're of the same kind
+fn push_op(ops: &mut Vec<DiffOp>, kind: DiffOpKind, old_len: usize, new_len: usize) {
+    if old_len == 0 && new_len == 0 {
+        return;
+    }
+    match ops.last_mut() {
+        Some(last) if last.kind == kind => {
+            last.old.end += old_len;
+            last.new.end += new_len;
+        }
+        last => {
+            let (old_pos, new_pos) = last.map_or((0, 0), |last| (last.old.end, last.new.end));
+            ops.push(DiffOp {
+                kind,
+                old: old_pos..old_pos + old_len,
+                new: new_pos..new_pos + new_len,
+            });
+        }
+    }
+}
+
+// Splits `data` into content-defined chunks, returning the offsets they start at followed by
+// the length of `data`
+//
+// A chunk ends after a byte where the gear hash of the bytes before it has its top bits clear.
+// Each byte is shifted out of the hash after 64 more bytes, so boundaries only depend on the
+// bytes just before them and line up again shortly after an insertion or deletion. Like
+// FastCDC, the bytes which can't affect the hash at the minimum chunk length aren't hashed.
+fn chunk_bounds(data: &[u8], chunk_len: usize) -> Vec<usize> {
+    let min_len = chunk_len / 4;
+    let max_len = chunk_len * 4;
+    let mask = !(u64::MAX >> chunk_len.trailing_zeros());
+
+    let mut bounds = Vec::with_capacity(data.len() / chunk_len + 2);
+    bounds.push(0);
+    let mut start = 0;
+    while start < data.len() {
+        let min = cmp::min(start + min_len, data.len());
+        let end = cmp::min(start + max_len, data.len());
+
+        let mut hash = 0u64;
+        let mut boundary = end;
+        for pos in min.saturating_sub(64).max(start)..end {
+            hash = (hash << 1).wrapping_add(GEAR[usize::from(data[pos])]);
+            if pos >= min && hash & mask == 0 {
+                boundary = pos + 1;
+                break;
+            }
+        }
+        bounds.push(boundary);
+        start = boundary;
+    }
+    bounds
+}
+
+// Random values for each byte, generated with SplitMix64
+const GEAR: [u64; 256] = {
+    let mut table = [0; 256];
+    let mut state: u64 = 0;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+};
@@ -1,4 +1,6 @@
-use crate::range::{DiffRange, SliceLike};
+use super::myers::{self, Deadline};
+use crate::range::{DiffRange, Range, SliceLike};
+use alloc::vec::Vec;
 
 // Walks through all edits and shifts them up and then down, trying to see if they run into similar
 // edits which can be merged
@@ -24,6 +26,56 @@ pub fn compact<'a, 'b, T: ?Sized + SliceLike>(diffs: &mut Vec<DiffRange<'a, 'b,
         }
         pointer += 1;
     }
+
+    // Finally merge any Equalities which ended up next to each other, either from shifting edits
+    // out from between them or because they were already adjacent
+    diffs.dedup_by(|next, prev| match (*prev, *next) {
+        (DiffRange::Equal(..), DiffRange::Equal(..)) => {
+            prev.grow_down(next.len());
+            true
+        }
+        _ => false,
+    });
+}
+
+// Diffs the deleted and inserted elements of every change again, looking for a shortest edit
+// script between them. This turns elements which were both deleted and inserted, e.g. because
+// they were junk or the diff ran out of time, back into equalities.
+pub fn minimize<'a, 'b, T: PartialEq>(diffs: &mut Vec<DiffRange<'a, 'b, [T]>>, deadline: Deadline) {
+    let mut minimized = Vec::with_capacity(diffs.len());
+    let mut idx = 0;
+    while idx < diffs.len() {
+        if let DiffRange::Equal(..) = diffs[idx] {
+            minimized.push(diffs[idx]);
+            idx += 1;
+            continue;
+        }
+
+        // The deletions and insertions between two equalities each cover a contiguous range
+        let start = idx;
+        let (mut old, mut new) = (None, None);
+        while let Some(&diff) = diffs.get(idx) {
+            match diff {
+                DiffRange::Equal(..) => break,
+                DiffRange::Delete(range) => extend(&mut old, range),
+                DiffRange::Insert(range) => extend(&mut new, range),
+            }
+            idx += 1;
+        }
+        match (old, new) {
+            (Some(old), Some(new)) => minimized.extend(myers::diff_ranges(old, new, deadline)),
+            _ => minimized.extend_from_slice(&diffs[start..idx]),
+        }
+    }
+    *diffs = minimized;
+}
+
+// Grows the range covered by the edits of a change by the next edit
+fn extend<'a, T: ?Sized>(covered: &mut Option<Range<'a, T>>, range: Range<'a, T>) {
+    match covered {
+        Some(covered) => covered.grow_down(range.len()),
+        None => *covered = Some(range),
+    }
 }
 
 // Attempts to shift the Insertion or Deletion at location `pointer` as far upwards as possible.
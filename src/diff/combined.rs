@@ -0,0 +1,305 @@
+use super::{DiffLines, DiffOptions};
+use crate::patch::{HunkRange, NO_NEWLINE_AT_EOF};
+use alloc::{vec, vec::Vec};
+use core::{cmp, fmt};
+
+/// How a line in a [`CombinedPatch`] relates to one of the two parents
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ParentChange {
+    /// The line is unaffected with respect to this parent, rendered as `' '`
+    Unchanged,
+    /// The line is in the merged text but not in this parent, rendered as `'+'`
+    Added,
+    /// The line is in this parent but was removed from the merged text, rendered as `'-'`
+    Removed,
+}
+
+impl ParentChange {
+    fn as_char(self) -> char {
+        match self {
+            ParentChange::Unchanged => ' ',
+            ParentChange::Added => '+',
+            ParentChange::Removed => '-',
+        }
+    }
+}
+
+/// A line in a [`CombinedHunk`] along with how it relates to each parent
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CombinedLine<'a> {
+    changes: [ParentChange; 2],
+    content: &'a str,
+}
+
+impl<'a> CombinedLine<'a> {
+    /// Returns how this line relates to the first and second parent respectively
+    pub fn changes(&self) -> [ParentChange; 2] {
+        self.changes
+    }
+
+    /// Returns the content of the line, including its terminating newline if it has one
+    pub fn content(&self) -> &'a str {
+        self.content
+    }
+
+    fn is_context(&self) -> bool {
+        self.changes == [ParentChange::Unchanged; 2]
+    }
+
+    // Whether this line exists in the first parent, the second parent and the merged text
+    fn presence(&self) -> [bool; 3] {
+        match self.changes {
+            [ParentChange::Removed, _] => [true, false, false],
+            [_, ParentChange::Removed] => [false, true, false],
+            [a, b] => [
+                a == ParentChange::Unchanged,
+                b == ParentChange::Unchanged,
+                true,
+            ],
+        }
+    }
+}
+
+/// A group of differing lines between two parents and a merged text
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CombinedHunk<'a> {
+    parent_ranges: [HunkRange; 2],
+    merged_range: HunkRange,
+    lines: Vec<CombinedLine<'a>>,
+}
+
+impl<'a> CombinedHunk<'a> {
+    /// Returns the ranges of the first and second parent covered by this hunk
+    pub fn parent_ranges(&self) -> [HunkRange; 2] {
+        self.parent_ranges
+    }
+
+    /// Returns the range of the merged text covered by this hunk
+    pub fn merged_range(&self) -> HunkRange {
+        self.merged_range
+    }
+
+    /// Returns the lines in the hunk
+    pub fn lines(&self) -> &[CombinedLine<'a>] {
+        &self.lines
+    }
+}
+
+/// The differences between a merged text and both of its parents
+///
+/// A `CombinedPatch` is rendered in the style of git's combined diff format. Instead of a single
+/// `+`/`-` marker each line is prefixed by two columns, one per parent:
+///
+/// * The first column describes the line relative to the first parent and the second column
+///   relative to the second parent.
+/// * `+` in a column means the line is in the merged text but not in that parent.
+/// * `-` in a column means the line was in that parent but isn't in the merged text. Lines
+///   removed from both parents are listed once per parent, so a removed line always has a `-`
+///   in exactly one column.
+/// * A space means the line is unchanged with respect to that parent.
+///
+/// A line prefixed by two spaces is context. Hunk headers list the range of the first parent,
+/// the second parent and the merged text, e.g. `@@@ -1,3 -1,2 +1,4 @@@`. No filename headers
+/// are emitted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CombinedPatch<'a> {
+    hunks: Vec<CombinedHunk<'a>>,
+}
+
+impl<'a> CombinedPatch<'a> {
+    /// Returns the hunks in the patch
+    pub fn hunks(&self) -> &[CombinedHunk<'a>] {
+        &self.hunks
+    }
+}
+
+impl fmt::Display for CombinedPatch<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for hunk in &self.hunks {
+            writeln!(
+                f,
+                "@@@ -{} -{} +{} @@@",
+                hunk.parent_ranges[0], hunk.parent_ranges[1], hunk.merged_range
+            )?;
+            for line in &hunk.lines {
+                write!(
+                    f,
+                    "{}{}{}",
+                    line.changes[0].as_char(),
+                    line.changes[1].as_char(),
+                    line.content
+                )?;
+                if !line.content.ends_with('\n') {
+                    writeln!(f)?;
+                    writeln!(f, "{}", NO_NEWLINE_AT_EOF)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DiffOptions {
+    /// Produce a combined diff showing how `merged` differs from both of its parents
+    pub fn combined_diff<'a>(
+        &self,
+        parent_a: &'a str,
+        parent_b: &'a str,
+        merged: &'a str,
+    ) -> CombinedPatch<'a> {
+        let a = self.diff_lines(parent_a, merged);
+        let b = self.diff_lines(parent_b, merged);
+        let merged_lines = a.new_lines();
+
+        let a = Alignment::new(&a);
+        let b = Alignment::new(&b);
+
+        let mut lines = Vec::new();
+        for (m, merged_line) in merged_lines
+            .iter()
+            .map(Some)
+            .chain(core::iter::once(None))
+            .enumerate()
+        {
+            for content in &a.removed_before[m] {
+                lines.push(CombinedLine {
+                    changes: [ParentChange::Removed, ParentChange::Unchanged],
+                    content,
+                });
+            }
+            for content in &b.removed_before[m] {
+                lines.push(CombinedLine {
+                    changes: [ParentChange::Unchanged, ParentChange::Removed],
+                    content,
+                });
+            }
+            if let Some(content) = merged_line {
+                lines.push(CombinedLine {
+                    changes: [a.changes[m], b.changes[m]],
+                    content,
+                });
+            }
+        }
+
+        CombinedPatch {
+            hunks: to_combined_hunks(&lines, self.context_lens()),
+        }
+    }
+}
+
+/// Produce a combined diff showing how `merged` differs from both of its parents
+///
+/// See [`CombinedPatch`] for a description of the output format.
+///
+/// ```
+/// use diffy::combined_diff;
+///
+/// let ours = "a\nb\nc\n";
+/// let theirs = "a\nB\nc\n";
+/// let merged = "a\nB\nc\nd\n";
+///
+/// let expected = "\
+/// @@@ -1,3 -1,3 +1,4 @@@
+///   a
+/// - b
+/// + B
+///   c
+/// ++d
+/// ";
+/// assert_eq!(combined_diff(ours, theirs, merged).to_string(), expected);
+/// ```
+///
+/// [`CombinedPatch`]: struct.CombinedPatch.html
+pub fn combined_diff<'a>(
+    parent_a: &'a str,
+    parent_b: &'a str,
+    merged: &'a str,
+) -> CombinedPatch<'a> {
+    DiffOptions::default().combined_diff(parent_a, parent_b, merged)
+}
+
+// The lines of a single parent aligned against the merged text
+struct Alignment<'a> {
+    // How each line of the merged text relates to the parent
+    changes: Vec<ParentChange>,
+    // Lines of the parent which were removed immediately before each line of the merged text,
+    // with an extra entry for lines removed at the end
+    removed_before: Vec<Vec<&'a str>>,
+}
+
+impl<'a> Alignment<'a> {
+    fn new(diff: &DiffLines<'a, str>) -> Self {
+        let mut changes = vec![ParentChange::Unchanged; diff.new_lines().len()];
+        let mut removed_before = vec![Vec::new(); diff.new_lines().len() + 1];
+
+        for edit in &diff.edit_script {
+            for change in &mut changes[edit.new.clone()] {
+                *change = ParentChange::Added;
+            }
+            removed_before[edit.new.start].extend_from_slice(&diff.old_lines()[edit.old.clone()]);
+        }
+
+        Self {
+            changes,
+            removed_before,
+        }
+    }
+}
+
+fn to_combined_hunks<'a>(
+    lines: &[CombinedLine<'a>],
+    (pre_context_len, post_context_len): (usize, usize),
+) -> Vec<CombinedHunk<'a>> {
+    let mut hunks = Vec::new();
+
+    // Number of lines of each of the three texts preceding each line
+    let mut positions = Vec::with_capacity(lines.len());
+    let mut counts = [0; 3];
+    for line in lines {
+        positions.push(counts);
+        for (count, present) in counts.iter_mut().zip(line.presence()) {
+            *count += usize::from(present);
+        }
+    }
+
+    let mut idx = 0;
+    while let Some(first_change) = lines[idx..].iter().position(|l| !l.is_context()) {
+        let start = (idx + first_change)
+            .saturating_sub(pre_context_len)
+            .max(idx);
+
+        // Extend the hunk until the gap to the next change is too large to share context
+        let mut last_change = idx + first_change;
+        for (i, line) in lines.iter().enumerate().skip(last_change + 1) {
+            if i - last_change > pre_context_len + post_context_len {
+                break;
+            }
+            if !line.is_context() {
+                last_change = i;
+            }
+        }
+        let end = cmp::min(last_change + 1 + post_context_len, lines.len());
+
+        let hunk_lines = lines[start..end].to_vec();
+        let mut lens = [0; 3];
+        for line in &hunk_lines {
+            for (len, present) in lens.iter_mut().zip(line.presence()) {
+                *len += usize::from(present);
+            }
+        }
+        let ranges: Vec<HunkRange> = positions[start]
+            .iter()
+            .zip(lens)
+            .map(|(&before, len)| HunkRange::from(before..before + len))
+            .collect();
+
+        hunks.push(CombinedHunk {
+            parent_ranges: [ranges[0], ranges[1]],
+            merged_range: ranges[2],
+            lines: hunk_lines,
+        });
+        idx = end;
+    }
+
+    hunks
+}
@@ -0,0 +1,159 @@
+use super::{to_hunks, EditRange};
+use crate::{patch::Patch, utils::LineIter};
+use alloc::{string::String, vec::Vec};
+use core::{fmt, ops};
+
+/// A replacement of a range of lines in a base text, used to build a patch with
+/// [`Patch::from_edits`]
+///
+/// Lines are indexed from `0`. Each of the `new_lines` is used verbatim and should include its
+/// line terminator, unless it's meant to be the last line of a text without a trailing newline.
+///
+/// [`Patch::from_edits`]: struct.Patch.html#method.from_edits
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Edit {
+    /// The range of lines of the base text to replace
+    pub old: ops::Range<usize>,
+    /// The lines to replace them with
+    pub new_lines: Vec<String>,
+}
+
+/// An error returned when [`Patch::from_edits`] is given an invalid list of edits
+///
+/// Edits are identified by their 0-based index in the list.
+///
+/// [`Patch::from_edits`]: struct.Patch.html#method.from_edits
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EditError {
+    /// The edit's range starts after it ends
+    InvalidRange {
+        /// The index of the offending edit
+        edit: usize,
+    },
+    /// The edit's range extends past the end of the base text
+    OutOfBounds {
+        /// The index of the offending edit
+        edit: usize,
+    },
+    /// The edit starts before the end of the edit preceding it
+    Overlapping {
+        /// The index of the offending edit
+        edit: usize,
+    },
+}
+
+impl EditError {
+    /// Returns the 0-based index of the offending edit
+    pub fn edit(&self) -> usize {
+        match self {
+            EditError::InvalidRange { edit }
+            | EditError::OutOfBounds { edit }
+            | EditError::Overlapping { edit } => *edit,
+        }
+    }
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EditError::InvalidRange { edit } => write!(f, "edit #{} has an invalid range", edit),
+            EditError::OutOfBounds { edit } => {
+                write!(f, "edit #{} is out of bounds of the base text", edit)
+            }
+            EditError::Overlapping { edit } => {
+                write!(f, "edit #{} overlaps the edit preceding it", edit)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EditError {}
+
+impl<'a> Patch<'a, str> {
+    /// Build a `Patch` which applies a list of edits to `base`, without computing a diff
+    ///
+    /// This lets tools which work out their edits by other means reuse diffy's patch
+    /// formatting and [`apply`]. Edits must be ordered by their position in `base` and must not
+    /// overlap. Context lines are taken from `base` and edits whose context overlaps or touches
+    /// are merged into a single hunk.
+    ///
+    /// ```
+    /// use diffy::{apply, Edit, Patch};
+    ///
+    /// let base = "a\nb\nc\nd\n";
+    /// let edits = [
+    ///     Edit { old: 1..2, new_lines: vec!["B\n".into()] },
+    ///     Edit { old: 4..4, new_lines: vec!["e\n".into()] },
+    /// ];
+    ///
+    /// let expected = "\
+    /// --- original
+    /// +++ modified
+    /// @@ -1,4 +1,5 @@
+    ///  a
+    /// -b
+    /// +B
+    ///  c
+    ///  d
+    /// +e
+    /// ";
+    ///
+    /// let patch = Patch::from_edits(base, &edits, 3).unwrap();
+    /// assert_eq!(patch.to_string(), expected);
+    /// assert_eq!(apply(base, &patch).unwrap(), "a\nB\nc\nd\ne\n");
+    /// ```
+    ///
+    /// [`apply`]: fn.apply.html
+    pub fn from_edits(
+        base: &'a str,
+        edits: &'a [Edit],
+        context_len: usize,
+    ) -> Result<Patch<'a, str>, EditError> {
+        let old_lines: Vec<&'a str> = LineIter::new(base).collect();
+        let mut new_lines = Vec::with_capacity(old_lines.len());
+        let mut edit_script: Vec<EditRange> = Vec::with_capacity(edits.len());
+
+        let mut old_pos = 0;
+        for (idx, edit) in edits.iter().enumerate() {
+            if edit.old.start > edit.old.end {
+                return Err(EditError::InvalidRange { edit: idx });
+            }
+            if edit.old.end > old_lines.len() {
+                return Err(EditError::OutOfBounds { edit: idx });
+            }
+            if edit.old.start < old_pos {
+                return Err(EditError::Overlapping { edit: idx });
+            }
+            if edit.old.is_empty() && edit.new_lines.is_empty() {
+                continue;
+            }
+
+            new_lines.extend_from_slice(&old_lines[old_pos..edit.old.start]);
+            let new_start = new_lines.len();
+            new_lines.extend(edit.new_lines.iter().map(String::as_str));
+            let new = new_start..new_lines.len();
+
+            // Edits which touch each other form a single change
+            match edit_script.last_mut() {
+                Some(last) if last.old.end == edit.old.start => {
+                    last.old.end = edit.old.end;
+                    last.new.end = new.end;
+                }
+                _ => edit_script.push(EditRange::new(edit.old.clone(), new)),
+            }
+            old_pos = edit.old.end;
+        }
+        new_lines.extend_from_slice(&old_lines[old_pos..]);
+
+        let hunks = to_hunks(
+            &old_lines,
+            &new_lines,
+            &edit_script,
+            context_len,
+            context_len,
+        );
+        Ok(Patch::new(Some("original"), Some("modified"), hunks))
+    }
+}
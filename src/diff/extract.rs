@@ -0,0 +1,198 @@
+use super::DiffOptions;
+use crate::{range::DiffRange, utils::Text};
+use alloc::vec::Vec;
+
+impl DiffOptions {
+    /// Returns the lines inserted into `modified`, in order
+    ///
+    /// The lines are taken straight from the diff without building a [`DiffLines`] or a
+    /// [`Patch`], which makes this a cheap way to list what's new, e.g. for a changelog. Lines
+    /// are compared the same way as by [`diff_lines`] and keep their line endings. A changed line
+    /// is both removed and added, so it's returned by [`removed_lines`] as well.
+    ///
+    /// ```
+    /// use diffy::DiffOptions;
+    ///
+    /// let original = "a\nb\nc\n";
+    /// let modified = "a\nB\nc\nd\n";
+    ///
+    /// let options = DiffOptions::new();
+    /// assert_eq!(options.added_lines(original, modified), ["B\n", "d\n"]);
+    /// assert_eq!(options.removed_lines(original, modified), ["b\n"]);
+    /// ```
+    ///
+    /// [`DiffLines`]: struct.DiffLines.html
+    /// [`Patch`]: struct.Patch.html
+    /// [`diff_lines`]: #method.diff_lines
+    /// [`removed_lines`]: #method.removed_lines
+    pub fn added_lines<'a>(&self, original: &str, modified: &'a str) -> Vec<&'a str> {
+        let (_, added) = self.changed_lines(original, modified, Side::New);
+        added.into_iter().map(|(_, line)| line).collect()
+    }
+
+    /// Returns the lines deleted from `original`, in order, see [`added_lines`]
+    ///
+    /// [`added_lines`]: #method.added_lines
+    pub fn removed_lines<'a>(&self, original: &'a str, modified: &str) -> Vec<&'a str> {
+        let (removed, _) = self.changed_lines(original, modified, Side::Old);
+        removed.into_iter().map(|(_, line)| line).collect()
+    }
+
+    /// Returns the lines inserted into `modified` along with their 1-based line numbers in
+    /// `modified`, see [`added_lines`]
+    ///
+    /// Lines are numbered the same way with [`set_squeeze_blank_lines`] enabled, it only leaves
+    /// out the blank lines which just change the length of a run of blank lines.
+    ///
+    /// ```
+    /// use diffy::DiffOptions;
+    ///
+    /// let original = "a\nb\nc\n";
+    /// let modified = "a\nB\nc\nd\n";
+    ///
+    /// let options = DiffOptions::new();
+    /// assert_eq!(
+    ///     options.added_lines_numbered(original, modified),
+    ///     [(2, "B\n"), (4, "d\n")]
+    /// );
+    /// assert_eq!(
+    ///     options.removed_lines_numbered(original, modified),
+    ///     [(2, "b\n")]
+    /// );
+    /// ```
+    ///
+    /// [`added_lines`]: #method.added_lines
+    /// [`set_squeeze_blank_lines`]: #method.set_squeeze_blank_lines
+    pub fn added_lines_numbered<'a>(
+        &self,
+        original: &str,
+        modified: &'a str,
+    ) -> Vec<(usize, &'a str)> {
+        self.changed_lines(original, modified, Side::New).1
+    }
+
+    /// Returns the lines deleted from `original` along with their 1-based line numbers in
+    /// `original`, see [`added_lines_numbered`]
+    ///
+    /// [`added_lines_numbered`]: #method.added_lines_numbered
+    pub fn removed_lines_numbered<'a>(
+        &self,
+        original: &'a str,
+        modified: &str,
+    ) -> Vec<(usize, &'a str)> {
+        self.changed_lines(original, modified, Side::Old).0
+    }
+
+    /// Returns the elements inserted into `new`, in order, see [`added_lines`]
+    ///
+    /// This works on any slice, like lines which have already been split or tokens.
+    ///
+    /// ```
+    /// use diffy::DiffOptions;
+    ///
+    /// let old = ["fn", "main", "(", ")"];
+    /// let new = ["pub", "fn", "main", "(", "args", ")"];
+    ///
+    /// let options = DiffOptions::new();
+    /// assert_eq!(options.added_items(&old, &new), [&"pub", &"args"]);
+    /// assert!(options.removed_items(&old, &new).is_empty());
+    /// ```
+    ///
+    /// [`added_lines`]: #method.added_lines
+    pub fn added_items<'a, T: PartialEq>(&self, old: &[T], new: &'a [T]) -> Vec<&'a T> {
+        let mut added = Vec::new();
+        for diff in self.diff_slice(old, new) {
+            if let DiffRange::Insert(range) = diff {
+                added.extend(&new[range.range()]);
+            }
+        }
+        added
+    }
+
+    /// Returns the elements deleted from `old`, in order, see [`added_items`]
+    ///
+    /// [`added_items`]: #method.added_items
+    pub fn removed_items<'a, T: PartialEq>(&self, old: &'a [T], new: &[T]) -> Vec<&'a T> {
+        let mut removed = Vec::new();
+        for diff in self.diff_slice(old, new) {
+            if let DiffRange::Delete(range) = diff {
+                removed.extend(&old[range.range()]);
+            }
+        }
+        removed
+    }
+
+    // Collects the numbered lines which were deleted from `original` or inserted into
+    // `modified`, only looking at the side that's asked for
+    #[allow(clippy::type_complexity)]
+    fn changed_lines<'o, 'm, T: ?Sized + Text>(
+        &self,
+        original: &'o T,
+        modified: &'m T,
+        side: Side,
+    ) -> (Vec<(usize, &'o T)>, Vec<(usize, &'m T)>) {
+        let (mut removed, mut added) = (Vec::new(), Vec::new());
+        if original.as_bytes() == modified.as_bytes() {
+            return (removed, added);
+        }
+
+        let old_lines = self.split_lines(original);
+        let new_lines = self.split_lines(modified);
+        let (old_ids, new_ids) = self.classify_line_slices(&old_lines, &new_lines);
+        let (edit_script, squeezed_edits) = self
+            .line_edit_script(&old_lines, &new_lines, &old_ids, &new_ids, usize::MAX)
+            .expect("unbounded diff can't exceed its bound");
+
+        for (idx, edit) in edit_script.into_iter().enumerate() {
+            if squeezed_edits.get(idx).copied().unwrap_or(false) {
+                continue;
+            }
+            match side {
+                Side::Old => {
+                    let range = edit.old;
+                    removed.extend((range.start + 1..).zip(old_lines[range].iter().copied()));
+                }
+                Side::New => {
+                    let range = edit.new;
+                    added.extend((range.start + 1..).zip(new_lines[range].iter().copied()));
+                }
+            }
+        }
+        (removed, added)
+    }
+}
+
+// Which of the texts being diffed to collect changed lines from
+#[derive(Clone, Copy)]
+enum Side {
+    Old,
+    New,
+}
+
+/// Returns the lines inserted into `modified`, in order, see [`DiffOptions::added_lines`]
+///
+/// [`DiffOptions::added_lines`]: struct.DiffOptions.html#method.added_lines
+pub fn added_lines<'a>(original: &str, modified: &'a str) -> Vec<&'a str> {
+    DiffOptions::default().added_lines(original, modified)
+}
+
+/// Returns the lines deleted from `original`, in order, see [`DiffOptions::removed_lines`]
+///
+/// [`DiffOptions::removed_lines`]: struct.DiffOptions.html#method.removed_lines
+pub fn removed_lines<'a>(original: &'a str, modified: &str) -> Vec<&'a str> {
+    DiffOptions::default().removed_lines(original, modified)
+}
+
+/// Returns the elements inserted into `new`, in order, see [`DiffOptions::added_items`]
+///
+/// [`DiffOptions::added_items`]: struct.DiffOptions.html#method.added_items
+pub fn added_items<'a, T: PartialEq>(old: &[T], new: &'a [T]) -> Vec<&'a T> {
+    DiffOptions::default().added_items(old, new)
+}
+
+/// Returns the elements deleted from `old`, in order, see [`DiffOptions::removed_items`]
+///
+/// [`DiffOptions::removed_items`]: struct.DiffOptions.html#method.removed_items
+pub fn removed_items<'a, T: PartialEq>(old: &'a [T], new: &[T]) -> Vec<&'a T> {
+    DiffOptions::default().removed_items(old, new)
+}
@@ -0,0 +1,325 @@
+use super::{DiffLines, DiffOptions, EditRange, IgnoredEdit, RecordSep};
+use crate::patch::{Hunk, Patch, DEV_NULL};
+use std::{
+    fs, io,
+    path::Path,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+// The number of bytes inspected when deciding whether a file is binary, matching git
+const BINARY_SNIFF_LEN: usize = 8000;
+
+/// The differences between two files, as returned by [`create_patch_from_files`]
+///
+/// A `FilePatch` owns the contents of both files along with the diff between them, so it
+/// doesn't borrow from anything. The [`Patch`] itself is built on demand by [`patch`], without
+/// recomputing the diff.
+///
+/// [`create_patch_from_files`]: fn.create_patch_from_files.html
+/// [`Patch`]: struct.Patch.html
+/// [`patch`]: #method.patch
+#[derive(Clone, Debug)]
+pub struct FilePatch {
+    original_name: String,
+    modified_name: String,
+    original: Vec<u8>,
+    modified: Vec<u8>,
+    binary: bool,
+    edit_script: Vec<EditRange>,
+    ignored_edits: Vec<IgnoredEdit>,
+    pre_context_len: usize,
+    post_context_len: usize,
+    merge_hunks: bool,
+    record_separator: RecordSep,
+}
+
+impl FilePatch {
+    /// Returns the name of the old file, or `/dev/null` if it didn't exist
+    pub fn original_name(&self) -> &str {
+        &self.original_name
+    }
+
+    /// Returns the name of the new file, or `/dev/null` if it didn't exist
+    pub fn modified_name(&self) -> &str {
+        &self.modified_name
+    }
+
+    /// Returns `true` if the patch creates the new file, i.e. the old file didn't exist
+    pub fn is_creation(&self) -> bool {
+        self.original_name == DEV_NULL
+    }
+
+    /// Returns `true` if the patch deletes the old file, i.e. the new file doesn't exist
+    pub fn is_deletion(&self) -> bool {
+        self.modified_name == DEV_NULL
+    }
+
+    /// Returns the contents of the old file
+    pub fn original(&self) -> &[u8] {
+        &self.original
+    }
+
+    /// Returns the contents of the new file
+    pub fn modified(&self) -> &[u8] {
+        &self.modified
+    }
+
+    /// Returns `true` if either file looks like binary content
+    ///
+    /// A file is considered binary if it contains a NUL byte within its first 8000 bytes. Binary
+    /// files aren't diffed line by line, so their patch doesn't contain any hunks.
+    pub fn is_binary(&self) -> bool {
+        self.binary
+    }
+
+    /// Returns the patch between the two files, with their names filled in
+    pub fn patch(&self) -> Patch<'_, [u8]> {
+        Patch::new(
+            Some(self.original_name.as_bytes()),
+            Some(self.modified_name.as_bytes()),
+            self.hunks(),
+        )
+    }
+
+    // Splits a file into records the same way its edit script was computed
+    fn split_lines<'a>(&self, text: &'a [u8]) -> Vec<&'a [u8]> {
+        super::split_records(text, self.record_separator)
+    }
+
+    // Builds the hunks of the patch between the two files
+    pub(crate) fn hunks(&self) -> Vec<Hunk<'_, [u8]>> {
+        let mut diff = DiffLines::new(
+            self.split_lines(&self.original),
+            self.split_lines(&self.modified),
+            self.edit_script.clone(),
+        );
+        diff.merge_hunks = self.merge_hunks;
+        diff.ignored_edits = self.ignored_edits.clone();
+        diff.hunks(self.pre_context_len, self.post_context_len)
+    }
+
+    /// Render the patch into its textual form
+    ///
+    /// Binary files are rendered as a single `Binary files ... differ` line instead, like GNU
+    /// diff does, or as nothing at all if they're identical.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        if self.binary {
+            if self.original == self.modified {
+                return Vec::new();
+            }
+            return std::format!(
+                "Binary files {} and {} differ\n",
+                self.original_name,
+                self.modified_name
+            )
+            .into_bytes();
+        }
+        self.patch().to_bytes()
+    }
+}
+
+impl DiffOptions {
+    /// Set whether [`create_patch_from_files`] should treat a file which doesn't exist as an
+    /// empty file named `/dev/null`, producing a patch which creates or deletes the other file
+    ///
+    /// When disabled, the default, a missing file is reported as an error.
+    ///
+    /// [`create_patch_from_files`]: fn.create_patch_from_files.html
+    pub fn set_missing_files_as_empty(&mut self, missing_as_empty: bool) -> &mut Self {
+        self.missing_files_as_empty = missing_as_empty;
+        self
+    }
+}
+
+/// Create a patch between the contents of two files
+///
+/// Both files are read into memory and diffed line by line with `options`. The paths are used
+/// as the names of the files in the patch. Files which look like binary content aren't diffed,
+/// see [`FilePatch::is_binary`].
+///
+/// If one of the files doesn't exist an error with [`io::ErrorKind::NotFound`] is returned,
+/// unless [`DiffOptions::set_missing_files_as_empty`] is enabled. It's always an error for
+/// both files to be missing.
+///
+/// ```no_run
+/// use diffy::{create_patch_from_files, DiffOptions};
+/// use std::path::Path;
+///
+/// let patch = create_patch_from_files(
+///     Path::new("old/README.md"),
+///     Path::new("new/README.md"),
+///     &DiffOptions::new(),
+/// )?;
+/// std::io::Write::write_all(&mut std::io::stdout(), &patch.to_bytes())?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// [`FilePatch::is_binary`]: struct.FilePatch.html#method.is_binary
+/// [`io::ErrorKind::NotFound`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.NotFound
+/// [`DiffOptions::set_missing_files_as_empty`]: struct.DiffOptions.html#method.set_missing_files_as_empty
+pub fn create_patch_from_files(
+    old: &Path,
+    new: &Path,
+    options: &DiffOptions,
+) -> io::Result<FilePatch> {
+    let (original_name, original) = read_file(old, options.missing_files_as_empty)?;
+    let (modified_name, modified) = read_file(new, options.missing_files_as_empty)?;
+    if original.is_none() && modified.is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "neither file to diff exists",
+        ));
+    }
+    Ok(FilePatch::new(
+        original_name,
+        original,
+        modified_name,
+        modified,
+        options,
+    ))
+}
+
+impl FilePatch {
+    // Diffs the contents of two files, where a missing file is treated as an empty file named
+    // `/dev/null`
+    pub(crate) fn new(
+        original_name: String,
+        original: Option<Vec<u8>>,
+        modified_name: String,
+        modified: Option<Vec<u8>>,
+        options: &DiffOptions,
+    ) -> Self {
+        let (original_name, original) = match original {
+            Some(original) => (original_name, original),
+            None => (DEV_NULL.to_string(), Vec::new()),
+        };
+        let (modified_name, modified) = match modified {
+            Some(modified) => (modified_name, modified),
+            None => (DEV_NULL.to_string(), Vec::new()),
+        };
+
+        let binary = is_binary(&original) || is_binary(&modified);
+        let (edit_script, ignored_edits) = if binary {
+            (Vec::new(), Vec::new())
+        } else {
+            let diff = options.diff_lines_bytes(&original, &modified);
+            (diff.edit_script, diff.ignored_edits)
+        };
+
+        Self {
+            original_name,
+            modified_name,
+            original,
+            modified,
+            binary,
+            edit_script,
+            ignored_edits,
+            pre_context_len: options.context_lens().0,
+            post_context_len: options.context_lens().1,
+            merge_hunks: options.merge_hunks,
+            record_separator: options.record_separator,
+        }
+    }
+}
+
+// Reads a file, returning `None` if it doesn't exist and missing files are allowed
+fn read_file(path: &Path, allow_missing: bool) -> io::Result<(String, Option<Vec<u8>>)> {
+    let name = path.display().to_string();
+    match fs::read(path) {
+        Ok(contents) => Ok((name, Some(contents))),
+        Err(e) if allow_missing && e.kind() == io::ErrorKind::NotFound => Ok((name, None)),
+        Err(e) => Err(e),
+    }
+}
+
+// Returns `true` if the contents look like binary content, i.e. there's a NUL byte near the start
+pub(crate) fn is_binary(contents: &[u8]) -> bool {
+    contents[..contents.len().min(BINARY_SNIFF_LEN)].contains(&0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apply_bytes;
+    use std::{env, path::PathBuf};
+
+    // A scratch directory which is removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = env::temp_dir().join(std::format!("diffy-{}-{}", name, std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn file(&self, name: &str, contents: &[u8]) -> PathBuf {
+            let path = self.0.join(name);
+            fs::write(&path, contents).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn patch_from_files() {
+        let dir = TempDir::new("files");
+        let old = dir.file("old.txt", b"a\nb\nc\n");
+        let new = dir.file("new.txt", b"a\nB\nc\n");
+
+        let patch = create_patch_from_files(&old, &new, &DiffOptions::new()).unwrap();
+        assert!(!patch.is_binary());
+        assert_eq!(patch.original_name(), old.display().to_string());
+        let expected = std::format!(
+            "--- {}\n+++ {}\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n",
+            old.display(),
+            new.display()
+        );
+        assert_eq!(patch.to_bytes(), expected.as_bytes());
+        assert_eq!(
+            apply_bytes(patch.original(), &patch.patch()).unwrap(),
+            b"a\nB\nc\n"
+        );
+
+        let bin_old = dir.file("old.bin", b"\x00\x01\x02");
+        let bin_new = dir.file("new.bin", b"\x00\x01\x03");
+        let patch = create_patch_from_files(&bin_old, &bin_new, &DiffOptions::new()).unwrap();
+        assert!(patch.is_binary());
+        assert!(patch.patch().hunks().is_empty());
+        let expected = std::format!(
+            "Binary files {} and {} differ\n",
+            bin_old.display(),
+            bin_new.display()
+        );
+        assert_eq!(patch.to_bytes(), expected.as_bytes());
+    }
+
+    #[test]
+    fn patch_from_missing_files() {
+        let dir = TempDir::new("missing");
+        let missing = dir.0.join("missing.txt");
+        let new = dir.file("new.txt", b"a\n");
+
+        let mut opts = DiffOptions::new();
+        let err = create_patch_from_files(&missing, &new, &opts).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        opts.set_missing_files_as_empty(true);
+        let patch = create_patch_from_files(&missing, &new, &opts).unwrap();
+        let expected = std::format!("--- /dev/null\n+++ {}\n@@ -0,0 +1 @@\n+a\n", new.display());
+        assert_eq!(patch.to_bytes(), expected.as_bytes());
+
+        let patch = create_patch_from_files(&new, &missing, &opts).unwrap();
+        assert_eq!(patch.modified_name(), "/dev/null");
+        assert_eq!(apply_bytes(b"a\n", &patch.patch()).unwrap(), b"");
+
+        let err = create_patch_from_files(&missing, &missing, &opts).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,167 @@
+use crate::utils::{split_line_ending, LineIter, Text};
+
+/// A high-level summary of how two texts differ
+///
+/// Each kind is checked in order and the first one which holds is returned, so a more specific
+/// kind always takes priority over a more general one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DiffKind {
+    /// The texts are byte-for-byte identical
+    Identical,
+
+    /// The texts have the same number of lines and the content of every line is the same, only
+    /// the line terminators differ (`\n` vs `\r\n`, or a missing newline at the end of the
+    /// text)
+    LineEndingsOnly,
+
+    /// The texts are the same once all whitespace, including line terminators and blank
+    /// lines, is removed
+    WhitespaceOnly,
+
+    /// The texts differ in their content
+    Content,
+}
+
+impl DiffKind {
+    /// Returns `true` if the texts are identical
+    pub fn is_identical(&self) -> bool {
+        matches!(self, DiffKind::Identical)
+    }
+
+    /// Returns `true` if the texts differ in more than just line endings or whitespace
+    pub fn is_content(&self) -> bool {
+        matches!(self, DiffKind::Content)
+    }
+}
+
+/// Summarize how two texts differ without computing a full diff
+///
+/// ```
+/// use diffy::{classify_difference, DiffKind};
+///
+/// assert_eq!(classify_difference("a\nb\n", "a\nb\n"), DiffKind::Identical);
+/// assert_eq!(classify_difference("a\nb\n", "a\r\nb\r\n"), DiffKind::LineEndingsOnly);
+/// assert_eq!(classify_difference("a b\n", "a  b\n"), DiffKind::WhitespaceOnly);
+/// assert_eq!(classify_difference("a\nb\n", "a\nc\n"), DiffKind::Content);
+/// ```
+pub fn classify_difference(original: &str, modified: &str) -> DiffKind {
+    classify(original, modified, |a, b| {
+        a.chars()
+            .filter(|c| !c.is_whitespace())
+            .eq(b.chars().filter(|c| !c.is_whitespace()))
+    })
+}
+
+/// Summarize how two potentially non-utf8 texts differ without computing a full diff
+///
+/// Only ASCII whitespace is considered when checking for [`DiffKind::WhitespaceOnly`].
+///
+/// [`DiffKind::WhitespaceOnly`]: enum.DiffKind.html#variant.WhitespaceOnly
+pub fn classify_difference_bytes(original: &[u8], modified: &[u8]) -> DiffKind {
+    classify(original, modified, |a, b| {
+        a.iter()
+            .filter(|b| !b.is_ascii_whitespace())
+            .eq(b.iter().filter(|b| !b.is_ascii_whitespace()))
+    })
+}
+
+fn classify<T: Text + ?Sized>(
+    original: &T,
+    modified: &T,
+    same_ignoring_whitespace: impl Fn(&T, &T) -> bool,
+) -> DiffKind {
+    if original == modified {
+        DiffKind::Identical
+    } else if same_ignoring_line_endings(original, modified) {
+        DiffKind::LineEndingsOnly
+    } else if same_ignoring_whitespace(original, modified) {
+        DiffKind::WhitespaceOnly
+    } else {
+        DiffKind::Content
+    }
+}
+
+fn same_ignoring_line_endings<T: Text + ?Sized>(original: &T, modified: &T) -> bool {
+    let mut original = LineIter::new(original);
+    let mut modified = LineIter::new(modified);
+
+    loop {
+        match (original.next(), modified.next()) {
+            (Some(a), Some(b)) => {
+                if split_line_ending(a).0 != split_line_ending(b).0 {
+                    return false;
+                }
+            }
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! assert_kind {
+        ($original:expr, $modified:expr, $kind:ident $(,)?) => {
+            assert_eq!(
+                classify_difference($original, $modified),
+                DiffKind::$kind,
+                "{:?} vs {:?}",
+                $original,
+                $modified
+            );
+            assert_eq!(
+                classify_difference_bytes($original.as_bytes(), $modified.as_bytes()),
+                DiffKind::$kind,
+                "{:?} vs {:?}",
+                $original,
+                $modified
+            );
+        };
+    }
+
+    #[test]
+    fn identical() {
+        assert_kind!("", "", Identical);
+        assert_kind!("a\nb\n", "a\nb\n", Identical);
+        assert_kind!("a\r\nb", "a\r\nb", Identical);
+    }
+
+    #[test]
+    fn line_endings_only() {
+        assert_kind!("a\nb\n", "a\r\nb\r\n", LineEndingsOnly);
+        assert_kind!("a\r\nb\n", "a\nb\r\n", LineEndingsOnly);
+        assert_kind!("a\nb\n", "a\nb", LineEndingsOnly);
+        assert_kind!("a\nb", "a\r\nb\r\n", LineEndingsOnly);
+        // A lone carriage return isn't a line terminator
+        assert_kind!("a\rb\n", "a\nb\n", WhitespaceOnly);
+        // Adding an empty line changes the number of lines
+        assert_kind!("a\n", "a\n\n", WhitespaceOnly);
+    }
+
+    #[test]
+    fn whitespace_only() {
+        assert_kind!("a b\n", "a  b\n", WhitespaceOnly);
+        assert_kind!("a\tb\n", "ab\n", WhitespaceOnly);
+        assert_kind!("a\nb\n", "a\n\n  b\n", WhitespaceOnly);
+        assert_kind!("  a\r\n", "a\n", WhitespaceOnly);
+        // Non-ASCII whitespace only counts for utf8 text
+        assert_eq!(
+            classify_difference("a\u{a0}b", "ab"),
+            DiffKind::WhitespaceOnly
+        );
+        assert_eq!(
+            classify_difference_bytes("a\u{a0}b".as_bytes(), b"ab"),
+            DiffKind::Content
+        );
+    }
+
+    #[test]
+    fn content() {
+        assert_kind!("a\n", "b\n", Content);
+        assert_kind!("", "a", Content);
+        assert_kind!("a\nb\n", "b\na\n", Content);
+        assert_kind!("a b\n", "a c\n", Content);
+    }
+}
@@ -0,0 +1,132 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::cmp::Ordering;
+
+/// The differences between two maps, as computed by [`diff_map`]
+///
+/// Entries are compared by key rather than by position, so the order entries were inserted in,
+/// or would be serialized in, never shows up as a difference. Each list is sorted by key.
+///
+/// [`diff_map`]: fn.diff_map.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MapDiff<'a, K, V> {
+    added: Vec<(&'a K, &'a V)>,
+    removed: Vec<(&'a K, &'a V)>,
+    changed: Vec<(&'a K, &'a V, &'a V)>,
+}
+
+impl<'a, K, V> MapDiff<'a, K, V> {
+    /// Returns the entries whose keys are only in the new map
+    pub fn added(&self) -> &[(&'a K, &'a V)] {
+        &self.added
+    }
+
+    /// Returns the entries whose keys are only in the old map
+    pub fn removed(&self) -> &[(&'a K, &'a V)] {
+        &self.removed
+    }
+
+    /// Returns the keys in both maps whose values differ, along with their old and new values
+    pub fn changed(&self) -> &[(&'a K, &'a V, &'a V)] {
+        &self.changed
+    }
+
+    /// Returns `true` if both maps have the same entries
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Compute the keys which were added, removed or changed between two maps
+///
+/// This is a structured alternative to diffing map-like data serialized to lines, where
+/// differences in the order entries happen to be written in would otherwise show up as
+/// spurious changes.
+///
+/// ```
+/// use diffy::diff_map;
+/// use std::collections::BTreeMap;
+///
+/// let old = BTreeMap::from([("a", 1), ("b", 2), ("c", 3)]);
+/// let new = BTreeMap::from([("b", 2), ("c", 4), ("d", 5)]);
+///
+/// let diff = diff_map(&old, &new);
+/// assert_eq!(diff.added(), [(&"d", &5)]);
+/// assert_eq!(diff.removed(), [(&"a", &1)]);
+/// assert_eq!(diff.changed(), [(&"c", &3, &4)]);
+/// ```
+pub fn diff_map<'a, K: Ord, V: PartialEq>(
+    old: &'a BTreeMap<K, V>,
+    new: &'a BTreeMap<K, V>,
+) -> MapDiff<'a, K, V> {
+    let mut diff = MapDiff {
+        added: Vec::new(),
+        removed: Vec::new(),
+        changed: Vec::new(),
+    };
+
+    // Both maps iterate in key order, so they can be walked side by side
+    let mut old = old.iter().peekable();
+    let mut new = new.iter().peekable();
+    loop {
+        let order = match (old.peek(), new.peek()) {
+            (Some((old_key, _)), Some((new_key, _))) => old_key.cmp(new_key),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => break,
+        };
+        match order {
+            Ordering::Less => diff.removed.extend(old.next()),
+            Ordering::Greater => diff.added.extend(new.next()),
+            Ordering::Equal => {
+                let (key, old_value) = old.next().unwrap();
+                let (_, new_value) = new.next().unwrap();
+                if old_value != new_value {
+                    diff.changed.push((key, old_value, new_value));
+                }
+            }
+        }
+    }
+
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_maps() {
+        let empty = BTreeMap::<u32, u32>::new();
+        let map = BTreeMap::from([(1, 1), (2, 2)]);
+
+        assert!(diff_map(&empty, &empty).is_empty());
+        assert!(diff_map(&map, &map.clone()).is_empty());
+
+        let diff = diff_map(&empty, &map);
+        assert_eq!(diff.added(), [(&1, &1), (&2, &2)]);
+        assert!(diff.removed().is_empty() && diff.changed().is_empty());
+
+        let diff = diff_map(&map, &empty);
+        assert_eq!(diff.removed(), [(&1, &1), (&2, &2)]);
+        assert!(diff.added().is_empty() && diff.changed().is_empty());
+    }
+
+    #[test]
+    fn insertion_order() {
+        let old: BTreeMap<_, _> = [("z", 1), ("a", 2), ("m", 3)].into_iter().collect();
+        let new: BTreeMap<_, _> = [("m", 3), ("z", 1), ("a", 2)].into_iter().collect();
+        assert!(diff_map(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn interleaved_keys() {
+        let old = BTreeMap::from([(1, "a"), (3, "c"), (5, "e"), (7, "g")]);
+        let new = BTreeMap::from([(2, "b"), (3, "C"), (5, "e"), (6, "f"), (7, "G"), (8, "h")]);
+
+        let diff = diff_map(&old, &new);
+        assert_eq!(diff.added(), [(&2, &"b"), (&6, &"f"), (&8, &"h")]);
+        assert_eq!(diff.removed(), [(&1, &"a")]);
+        assert_eq!(diff.changed(), [(&3, &"c", &"C"), (&7, &"g", &"G")]);
+        assert!(!diff.is_empty());
+    }
+}
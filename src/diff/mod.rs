@@ -1,22 +1,75 @@
 use crate::{
     patch::{Hunk, HunkRange, Line, Patch},
     range::{DiffRange, SliceLike},
-    utils::Classifier,
+    utils::{split_line_ending, strip_indentation, Classifier, LineIter, Text},
 };
-use std::{cmp, ops};
+use alloc::{
+    borrow::{Cow, ToOwned},
+    string::String,
+    vec::Vec,
+};
+use core::{cmp, fmt, ops};
+
+pub use annotate::{annotate, LineOrigin};
+pub use append::AppendDiff;
+pub use chunked::{diff_chunked, ChunkOptions};
+pub use combined::{combined_diff, CombinedHunk, CombinedLine, CombinedPatch, ParentChange};
+pub use edits::{Edit, EditError};
+pub use extract::{added_items, added_lines, removed_items, removed_lines};
+#[cfg(feature = "std")]
+pub use files::{create_patch_from_files, FilePatch};
+pub use kind::{classify_difference, classify_difference_bytes, DiffKind};
+pub use map::{diff_map, MapDiff};
+pub use moves::MovedBlock;
+#[cfg(feature = "unicode-normalization")]
+pub use normalize::NormalizationForm;
+pub use range_ops::{diff_slice_ops, diff_text_ops, DiffOp, DiffOpKind};
+#[cfg(feature = "std")]
+pub use readers::{diff_readers, OwnedDiffLines};
+pub use similarity::{DiffMetrics, Reference};
+pub use subrange::{diff_lines_range, LineRangeError};
 
-mod cleanup;
-mod myers;
+mod annotate;
+mod append;
+mod canonical;
+mod chunked;
+pub(crate) mod cleanup;
+mod combined;
+mod edits;
+mod extract;
+#[cfg(feature = "std")]
+pub(crate) mod files;
+mod kind;
+mod map;
+mod moves;
+pub(crate) mod myers;
+#[cfg(feature = "unicode-normalization")]
+mod normalize;
+mod range_ops;
+#[cfg(feature = "std")]
+mod readers;
+mod similarity;
+mod subrange;
 
 #[cfg(test)]
 mod tests;
 
-// TODO determine if this should be exposed in the public API
-#[allow(dead_code)]
+/// A segment of the differences between two texts
+///
+/// With the `serde` feature enabled a `Diff` serializes as an adjacently tagged enum, e.g.
+/// `{"kind":"insert","value":"text"}`.
 #[derive(Debug, PartialEq, Eq)]
-enum Diff<'a, T: ?Sized> {
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(tag = "kind", content = "value", rename_all = "lowercase")
+)]
+pub enum Diff<'a, T: ?Sized> {
+    /// A segment present in both texts
     Equal(&'a T),
+    /// A segment only present in the original text
     Delete(&'a T),
+    /// A segment only present in the modified text
     Insert(&'a T),
 }
 
@@ -28,6 +81,99 @@ impl<T: ?Sized> Clone for Diff<'_, T> {
     }
 }
 
+impl<T: ?Sized + ToOwned> Diff<'_, T> {
+    /// Returns an owned copy of this segment, which can also be deserialized
+    pub fn into_owned(self) -> OwnedDiff<T::Owned> {
+        match self {
+            Diff::Equal(text) => OwnedDiff::Equal(text.to_owned()),
+            Diff::Delete(text) => OwnedDiff::Delete(text.to_owned()),
+            Diff::Insert(text) => OwnedDiff::Insert(text.to_owned()),
+        }
+    }
+}
+
+/// An owned segment of the differences between two texts, see [`Diff::into_owned`]
+///
+/// With the `serde` feature enabled an `OwnedDiff` has the same wire format as a [`Diff`], so
+/// serialized diffs can be read back as `Vec<OwnedDiff<String>>`.
+///
+/// ```
+/// use diffy::{diff, OwnedDiff};
+///
+/// let solution: Vec<OwnedDiff<String>> = diff("abc", "aXc")
+///     .into_iter()
+///     .map(|diff| diff.into_owned())
+///     .collect();
+/// assert_eq!(solution[1], OwnedDiff::Delete("b".to_owned()));
+/// assert_eq!(solution[2].as_diff(), diffy::Diff::Insert("X"));
+/// ```
+///
+/// [`Diff`]: enum.Diff.html
+/// [`Diff::into_owned`]: enum.Diff.html#method.into_owned
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "kind", content = "value", rename_all = "lowercase")
+)]
+pub enum OwnedDiff<T> {
+    /// A segment present in both texts
+    Equal(T),
+    /// A segment only present in the original text
+    Delete(T),
+    /// A segment only present in the modified text
+    Insert(T),
+}
+
+impl<T> OwnedDiff<T> {
+    /// Returns a [`Diff`] borrowing this segment's text
+    ///
+    /// [`Diff`]: enum.Diff.html
+    pub fn as_diff<U: ?Sized>(&self) -> Diff<'_, U>
+    where
+        T: core::borrow::Borrow<U>,
+    {
+        match self {
+            OwnedDiff::Equal(text) => Diff::Equal(text.borrow()),
+            OwnedDiff::Delete(text) => Diff::Delete(text.borrow()),
+            OwnedDiff::Insert(text) => Diff::Insert(text.borrow()),
+        }
+    }
+}
+
+/// A segment of the differences between two texts, where long runs of equal text may be elided,
+/// as returned by [`DiffOptions::diff_elided`]
+///
+/// A `Skip` only records the length of the text present in both texts, so a diff holding mostly
+/// `Skip`s doesn't keep either text borrowed for its content. The lengths of all of the segments
+/// are still enough to work out where each one starts in both texts.
+///
+/// [`DiffOptions::diff_elided`]: struct.DiffOptions.html#method.diff_elided
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(tag = "kind", content = "value", rename_all = "lowercase")
+)]
+pub enum ElidedDiff<'a, T: ?Sized> {
+    /// A segment present in both texts
+    Equal(&'a T),
+    /// A segment only present in the original text
+    Delete(&'a T),
+    /// A segment only present in the modified text
+    Insert(&'a T),
+    /// A segment of the given length present in both texts, which has been elided
+    Skip(usize),
+}
+
+impl<T: ?Sized> Copy for ElidedDiff<'_, T> {}
+
+impl<T: ?Sized> Clone for ElidedDiff<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
 impl<'a, T> From<DiffRange<'a, 'a, T>> for Diff<'a, T>
 where
     T: ?Sized + SliceLike,
@@ -41,11 +187,184 @@ where
     }
 }
 
+/// An index-level operation for transforming one slice into another, as returned by
+/// [`diff_ops`]
+///
+/// Applying the operations in order, while walking both slices from the front, turns the old
+/// slice into the new one.
+///
+/// [`diff_ops`]: fn.diff_ops.html
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Op {
+    /// Keep the element at this index of the old slice
+    Keep(usize),
+    /// Remove the element at this index of the old slice
+    Remove(usize),
+    /// Insert the element at this index of the new slice
+    Insert(usize),
+}
+
+/// Which side of each change patches show context lines on, see
+/// [`DiffOptions::set_context_mode`]
+///
+/// [`DiffOptions::set_context_mode`]: struct.DiffOptions.html#method.set_context_mode
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ContextMode {
+    /// Context lines before and after each change
+    #[default]
+    Both,
+    /// Context lines only before each change
+    LeadingOnly,
+    /// Context lines only after each change
+    TrailingOnly,
+}
+
+/// What separates the records of a text which are compared against each other, see
+/// [`DiffOptions::set_record_separator`]
+///
+/// Every record keeps the separator which ends it, so joining the records gives back the text.
+/// The last record of a text may not have one.
+///
+/// [`DiffOptions::set_record_separator`]: struct.DiffOptions.html#method.set_record_separator
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum RecordSep {
+    /// Records are lines ending with `\n`
+    #[default]
+    Line,
+    /// Records end with this byte, like the `\0` separating the paths printed by
+    /// `find -print0`
+    ///
+    /// In a `str` a byte only separates records where it ends a char, so non-ASCII bytes can't
+    /// split a char in two.
+    Byte(u8),
+    /// Records end with this string. An empty string never matches, so the whole text is a
+    /// single record.
+    Str(&'static str),
+    /// Records are paragraphs: a run of non-blank lines along with the blank lines following
+    /// it. Blank lines at the start of a text form a record of their own.
+    ///
+    /// A line is blank when it is empty apart from its line ending.
+    Paragraph,
+}
+
+/// The prefix of a line which is ignored when comparing lines, see
+/// [`DiffOptions::set_strip_prefix_for_matching`]
+///
+/// Prefixes are measured in bytes and never include the line ending. A prefix longer than a
+/// line's content covers all of it, and in a `str` a prefix ending inside a char is extended to
+/// the end of that char.
+///
+/// [`DiffOptions::set_strip_prefix_for_matching`]: struct.DiffOptions.html#method.set_strip_prefix_for_matching
+#[derive(Clone, Copy)]
+pub enum LinePrefix {
+    /// A prefix of the same length on every line, like fixed-width timestamps
+    Len(usize),
+    /// A function returning the length of the prefix of each line, which is given including
+    /// its line ending
+    Fn(fn(&[u8]) -> usize),
+}
+
+// Written by hand since a derived impl doesn't compile for the function pointer on every
+// supported compiler
+impl fmt::Debug for LinePrefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinePrefix::Len(len) => f.debug_tuple("Len").field(len).finish(),
+            LinePrefix::Fn(_) => f.write_str("Fn(..)"),
+        }
+    }
+}
+
 /// A collection of options for modifying the way a diff is performed
-#[derive(Debug)]
 pub struct DiffOptions {
     compact: bool,
+    minimal: bool,
     context_len: usize,
+    context_mode: ContextMode,
+    max_changed_lines: Option<usize>,
+    merge_hunks: bool,
+    detect_moves: bool,
+    ignore_indentation: bool,
+    strip_prefix: Option<LinePrefix>,
+    trailing_chars: String,
+    ignore_case: bool,
+    #[cfg(feature = "unicode-case")]
+    ignore_case_unicode: bool,
+    #[cfg(feature = "unicode-normalization")]
+    normalization: Option<NormalizationForm>,
+    squeeze_blank_lines: bool,
+    comment_prefixes: Vec<String>,
+    ignore_comment_changes: bool,
+    record_separator: RecordSep,
+    is_junk: Option<fn(&[u8]) -> bool>,
+    #[cfg(feature = "std")]
+    pub(crate) missing_files_as_empty: bool,
+    #[cfg(feature = "std")]
+    hash_lines_only: bool,
+    #[cfg(feature = "std")]
+    timeout: Option<std::time::Duration>,
+    #[cfg(feature = "std")]
+    progress: Option<ProgressCallback>,
+}
+
+// A user provided callback receiving the progress of a diff, see `DiffOptions::on_progress`. It
+// sits behind a mutex so the options can still be shared between threads.
+#[cfg(feature = "std")]
+struct ProgressCallback(std::sync::Mutex<alloc::boxed::Box<dyn FnMut(f32) + Send>>);
+
+#[cfg(feature = "std")]
+impl ProgressCallback {
+    fn report(&self, fraction: f32) {
+        let mut callback = self
+            .0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        callback(fraction);
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
+
+// Written by hand since a derived impl doesn't compile for `is_junk` on every supported
+// compiler, and there's nothing useful to show about a function pointer anyway
+impl fmt::Debug for DiffOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("DiffOptions");
+        debug
+            .field("compact", &self.compact)
+            .field("minimal", &self.minimal)
+            .field("context_len", &self.context_len)
+            .field("context_mode", &self.context_mode)
+            .field("max_changed_lines", &self.max_changed_lines)
+            .field("merge_hunks", &self.merge_hunks)
+            .field("detect_moves", &self.detect_moves)
+            .field("ignore_indentation", &self.ignore_indentation)
+            .field("strip_prefix", &self.strip_prefix)
+            .field("trailing_chars", &self.trailing_chars)
+            .field("ignore_case", &self.ignore_case);
+        #[cfg(feature = "unicode-case")]
+        debug.field("ignore_case_unicode", &self.ignore_case_unicode);
+        #[cfg(feature = "unicode-normalization")]
+        debug.field("normalization", &self.normalization);
+        debug
+            .field("squeeze_blank_lines", &self.squeeze_blank_lines)
+            .field("comment_prefixes", &self.comment_prefixes)
+            .field("ignore_comment_changes", &self.ignore_comment_changes)
+            .field("record_separator", &self.record_separator)
+            .field("is_junk", &self.is_junk.is_some());
+        #[cfg(feature = "std")]
+        debug
+            .field("missing_files_as_empty", &self.missing_files_as_empty)
+            .field("hash_lines_only", &self.hash_lines_only)
+            .field("timeout", &self.timeout)
+            .field("progress", &self.progress);
+        debug.finish()
+    }
 }
 
 impl DiffOptions {
@@ -53,10 +372,56 @@ impl DiffOptions {
     ///
     /// ## Defaults
     /// * context_len = 3
+    /// * context_mode = ContextMode::Both
+    /// * max_changed_lines = None
+    /// * minimal = false
+    /// * merge_hunks = true
+    /// * detect_moves = false
+    /// * ignore_indentation = false
+    /// * strip_prefix_for_matching = None
+    /// * ignore_trailing_chars = ""
+    /// * ignore_case = false
+    /// * ignore_case_unicode = false
+    /// * normalize_unicode = None
+    /// * squeeze_blank_lines = false
+    /// * comment_prefixes = ["#", "//", ";"]
+    /// * ignore_comment_changes = false
+    /// * record_separator = RecordSep::Line
+    /// * is_junk = None
+    /// * missing_files_as_empty = false
+    /// * hash_lines_only = false
+    /// * timeout = None
+    /// * on_progress = None
     pub fn new() -> Self {
         Self {
             compact: true,
+            minimal: false,
             context_len: 3,
+            context_mode: ContextMode::Both,
+            max_changed_lines: None,
+            merge_hunks: true,
+            detect_moves: false,
+            ignore_indentation: false,
+            strip_prefix: None,
+            trailing_chars: String::new(),
+            ignore_case: false,
+            #[cfg(feature = "unicode-case")]
+            ignore_case_unicode: false,
+            #[cfg(feature = "unicode-normalization")]
+            normalization: None,
+            squeeze_blank_lines: false,
+            comment_prefixes: ["#", "//", ";"].iter().map(|&p| p.into()).collect(),
+            ignore_comment_changes: false,
+            record_separator: RecordSep::Line,
+            is_junk: None,
+            #[cfg(feature = "std")]
+            missing_files_as_empty: false,
+            #[cfg(feature = "std")]
+            hash_lines_only: false,
+            #[cfg(feature = "std")]
+            timeout: None,
+            #[cfg(feature = "std")]
+            progress: None,
         }
     }
 
@@ -66,6 +431,521 @@ impl DiffOptions {
         self
     }
 
+    /// Set whether patches show the context lines set with [`set_context_len`] before each
+    /// change, after it, or on both sides
+    ///
+    /// Two changes end up in the same hunk when the context shown before the second one
+    /// reaches the context shown after the first one, or the first change itself if there is
+    /// none. With [`ContextMode::LeadingOnly`] or [`ContextMode::TrailingOnly`] that's when at
+    /// most `context_len` unchanged lines separate them.
+    ///
+    /// ```
+    /// use diffy::{ContextMode, DiffOptions};
+    ///
+    /// let original = "a\nb\nc\nd\ne\n";
+    /// let modified = "a\nb\nC\nd\ne\n";
+    ///
+    /// let mut options = DiffOptions::new();
+    /// options.set_context_len(1).set_context_mode(ContextMode::LeadingOnly);
+    /// let expected = "\
+    /// --- original
+    /// +++ modified
+    /// @@ -2,2 +2,2 @@
+    ///  b
+    /// -c
+    /// +C
+    /// ";
+    /// assert_eq!(options.create_patch(original, modified).to_string(), expected);
+    /// ```
+    ///
+    /// [`set_context_len`]: #method.set_context_len
+    /// [`ContextMode::LeadingOnly`]: enum.ContextMode.html#variant.LeadingOnly
+    /// [`ContextMode::TrailingOnly`]: enum.ContextMode.html#variant.TrailingOnly
+    pub fn set_context_mode(&mut self, context_mode: ContextMode) -> &mut Self {
+        self.context_mode = context_mode;
+        self
+    }
+
+    // Returns the number of context lines shown before and after each change
+    fn context_lens(&self) -> (usize, usize) {
+        match self.context_mode {
+            ContextMode::Both => (self.context_len, self.context_len),
+            ContextMode::LeadingOnly => (self.context_len, 0),
+            ContextMode::TrailingOnly => (0, self.context_len),
+        }
+    }
+
+    /// Set the maximum number of inserted plus deleted lines that the `try_*` family of
+    /// functions (e.g. [`try_create_patch`]) will accept before giving up with a
+    /// [`TooDifferentError`]. `None` means there is no limit.
+    ///
+    /// The limit is enforced while the diff is being computed: the search is aborted as soon as
+    /// it's known that the limit will be exceeded, so guarding against diffing two unrelated
+    /// files is cheap. The non-`try` functions ignore this limit.
+    ///
+    /// [`try_create_patch`]: #method.try_create_patch
+    /// [`TooDifferentError`]: struct.TooDifferentError.html
+    pub fn set_max_changed_lines(&mut self, max_changed_lines: Option<usize>) -> &mut Self {
+        self.max_changed_lines = max_changed_lines;
+        self
+    }
+
+    /// Set how long computing a diff may take before giving up on finding a minimal one, or
+    /// `None` to always find a minimal diff
+    ///
+    /// When the timeout expires, the parts of the texts which haven't been diffed yet are
+    /// described as deleted and re-inserted, with only their common prefix and suffix kept. The
+    /// result is still a valid diff, so patches produced from it apply as usual, but it may be
+    /// much larger than necessary. This keeps interactive tools responsive on pathological
+    /// inputs. Since it depends on how fast the diff is computed, the result is
+    /// non-deterministic for inputs which take about as long to diff as the timeout.
+    ///
+    /// ```
+    /// use diffy::{apply, DiffOptions};
+    /// use std::time::Duration;
+    ///
+    /// let original = "a\nb\nc\nd\n";
+    /// let modified = "a\nx\nc\ny\n";
+    ///
+    /// let mut options = DiffOptions::new();
+    /// options.set_timeout(Some(Duration::ZERO));
+    /// let patch = options.create_patch(original, modified);
+    /// assert_eq!(apply(original, &patch).unwrap(), modified);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn set_timeout(&mut self, timeout: Option<std::time::Duration>) -> &mut Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // Returns the deadline for a diff started now
+    fn deadline(&self) -> myers::Deadline {
+        #[cfg(feature = "std")]
+        return myers::Deadline::after(self.timeout);
+        #[cfg(not(feature = "std"))]
+        myers::Deadline::none()
+    }
+
+    /// Set a callback which is called periodically while a diff is computed, with an estimate
+    /// of how much of it is done as a fraction between `0.0` and `1.0`
+    ///
+    /// The estimate is approximate: it's based on how much of both inputs the diff covers so
+    /// far and on how many edits the search for it has already ruled out, so it may advance
+    /// unevenly, e.g. stall while a large change is searched and then jump ahead. It never goes
+    /// backwards though. To keep the overhead low the callback is only called when the estimate
+    /// advanced by at least a percent, and with `1.0` once a diff is complete. Each diff computed
+    /// with these options starts over from `0.0`.
+    ///
+    /// ```
+    /// use diffy::DiffOptions;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let reported = Arc::new(Mutex::new(Vec::new()));
+    /// let sink = Arc::clone(&reported);
+    ///
+    /// let mut options = DiffOptions::new();
+    /// options.on_progress(move |fraction| sink.lock().unwrap().push(fraction));
+    /// options.create_patch("a\nb\nc\n", "a\nB\nc\n");
+    ///
+    /// let reported = reported.lock().unwrap();
+    /// assert_eq!(reported.last(), Some(&1.0));
+    /// assert!(reported.windows(2).all(|pair| pair[0] < pair[1]));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn on_progress(&mut self, f: impl FnMut(f32) + Send + 'static) -> &mut Self {
+        self.progress = Some(ProgressCallback(std::sync::Mutex::new(
+            alloc::boxed::Box::new(f),
+        )));
+        self
+    }
+
+    // Runs `f` with a tracker reporting the progress of a diff to the callback, if there is one
+    fn with_progress<R>(&self, f: impl FnOnce(&mut myers::Progress<'_>) -> R) -> R {
+        #[cfg(feature = "std")]
+        if let Some(callback) = &self.progress {
+            let mut report = |fraction| callback.report(fraction);
+            return f(&mut myers::Progress::new(&mut report));
+        }
+        f(&mut myers::Progress::none())
+    }
+
+    /// Set whether changes whose context overlaps or touches should be merged into a single hunk
+    ///
+    /// When disabled every change gets a hunk of its own, which is useful for tools which let
+    /// users pick individual changes to apply. The unchanged lines between two nearby changes
+    /// are then split between the two hunks so that their context never overlaps.
+    pub fn set_merge_hunks(&mut self, merge_hunks: bool) -> &mut Self {
+        self.merge_hunks = merge_hunks;
+        self
+    }
+
+    /// Set whether blocks of lines which were moved should be detected
+    ///
+    /// A block of at least three lines which was deleted in one place and inserted unchanged in
+    /// another is reported by [`DiffLines::moves`]. Candidates are found by indexing windows of
+    /// inserted lines, so detection stays cheap even for large, heavily reordered inputs. The
+    /// diff itself, and any patch created from it, is unaffected.
+    ///
+    /// [`DiffLines::moves`]: struct.DiffLines.html#method.moves
+    pub fn set_detect_moves(&mut self, detect_moves: bool) -> &mut Self {
+        self.detect_moves = detect_moves;
+        self
+    }
+
+    /// Set whether lines which only differ in their leading spaces and tabs should be
+    /// considered equal
+    ///
+    /// This is useful for reviewing changes which re-indent a block of code, where the lines
+    /// whose content actually changed would otherwise be lost among the re-indented ones.
+    /// Unchanged lines are taken from the modified text, so a patch produced with this option
+    /// enabled won't necessarily apply to the original text.
+    ///
+    /// To keep indentation-only changes in a patch but display them differently see
+    /// [`PatchFormatter::mark_indentation_changes`].
+    ///
+    /// ```
+    /// use diffy::DiffOptions;
+    ///
+    /// let original = "fn main() {\n    foo();\n}\n";
+    /// let modified = "fn main() {\n\tfoo();\n\tbar();\n}\n";
+    ///
+    /// let expected = "\
+    /// --- original
+    /// +++ modified
+    /// @@ -2,0 +3 @@
+    /// +\tbar();
+    /// ";
+    ///
+    /// let mut options = DiffOptions::new();
+    /// options.set_ignore_indentation(true).set_context_len(0);
+    /// assert_eq!(options.create_patch(original, modified).to_string(), expected);
+    /// ```
+    ///
+    /// [`PatchFormatter::mark_indentation_changes`]: struct.PatchFormatter.html#method.mark_indentation_changes
+    pub fn set_ignore_indentation(&mut self, ignore_indentation: bool) -> &mut Self {
+        self.ignore_indentation = ignore_indentation;
+        self
+    }
+
+    /// Set a prefix of each line to leave out when comparing lines, or `None` to compare whole
+    /// lines
+    ///
+    /// This is meant for annotated texts, like logs whose lines start with a timestamp, where
+    /// lines should match by their content even though their annotations differ. Only the
+    /// comparison is affected: lines in a patch still include their prefix, and unchanged lines
+    /// are taken from the modified text, so a patch produced with this option set won't
+    /// necessarily apply to the original text. Other options comparing lines, like
+    /// [`set_ignore_indentation`], apply to what's left after the prefix.
+    ///
+    /// ```
+    /// use diffy::{DiffOptions, LinePrefix};
+    ///
+    /// let original = "12:00:01 start\n12:00:02 load config\n12:00:05 done\n";
+    /// let modified = "13:30:00 start\n13:30:01 load config\n13:30:01 warn: slow\n13:30:09 done\n";
+    ///
+    /// let expected = "\
+    /// --- original
+    /// +++ modified
+    /// @@ -2,0 +3 @@
+    /// +13:30:01 warn: slow
+    /// ";
+    ///
+    /// let mut options = DiffOptions::new();
+    /// options
+    ///     .set_strip_prefix_for_matching(Some(LinePrefix::Len(9)))
+    ///     .set_context_len(0);
+    /// assert_eq!(options.create_patch(original, modified).to_string(), expected);
+    /// ```
+    ///
+    /// [`set_ignore_indentation`]: #method.set_ignore_indentation
+    pub fn set_strip_prefix_for_matching(&mut self, prefix: Option<LinePrefix>) -> &mut Self {
+        self.strip_prefix = prefix;
+        self
+    }
+
+    /// Set the characters to leave out from the end of each line when comparing lines, or an
+    /// empty string to compare whole lines
+    ///
+    /// Lines which only differ in trailing characters from `chars`, like the commas or
+    /// semicolons added or removed when reformatting JSON or JavaScript, are considered equal.
+    /// Any number of these characters are trimmed in any order, but line endings are still
+    /// compared. Only the comparison is affected: the lines in a patch are always the original
+    /// text, with unchanged lines taken from the modified text.
+    ///
+    /// ```
+    /// use diffy::DiffOptions;
+    ///
+    /// let original = "[\n  1,\n  2\n]\n";
+    /// let modified = "[\n  1,\n  2,\n  3\n]\n";
+    ///
+    /// let expected = "\
+    /// --- original
+    /// +++ modified
+    /// @@ -3,0 +4 @@
+    /// +  3
+    /// ";
+    ///
+    /// let mut options = DiffOptions::new();
+    /// options.set_ignore_trailing_chars(",;").set_context_len(0);
+    /// assert_eq!(options.create_patch(original, modified).to_string(), expected);
+    /// ```
+    pub fn set_ignore_trailing_chars(&mut self, chars: &str) -> &mut Self {
+        self.trailing_chars = chars.into();
+        self
+    }
+
+    /// Set whether lines which only differ in the case of their ASCII letters should be
+    /// considered equal
+    ///
+    /// Only the comparison is affected: the lines in a patch are always the original text, with
+    /// unchanged lines taken from the modified text. For folding the case of non-ASCII letters
+    /// see [`set_ignore_case_unicode`].
+    ///
+    /// ```
+    /// use diffy::DiffOptions;
+    ///
+    /// let mut options = DiffOptions::new();
+    /// options.set_ignore_case(true);
+    /// assert!(options.create_patch("Hello\n", "HELLO\n").hunks().is_empty());
+    /// assert_eq!(options.create_patch("\u{c9}t\u{e9}\n", "\u{e9}t\u{e9}\n").hunks().len(), 1);
+    /// ```
+    ///
+    /// [`set_ignore_case_unicode`]: #method.set_ignore_case_unicode
+    pub fn set_ignore_case(&mut self, ignore_case: bool) -> &mut Self {
+        self.ignore_case = ignore_case;
+        self
+    }
+
+    /// Set whether lines which are equal after full Unicode case folding should be considered
+    /// equal
+    ///
+    /// Unlike [`set_ignore_case`] this folds every cased letter, including ones which fold to
+    /// several characters like `ß` to `ss`. It's slower since every line containing non-ASCII
+    /// characters has to be folded into a new string before it's compared. Lines which aren't
+    /// valid utf8 only have their ASCII letters folded.
+    ///
+    /// ```
+    /// use diffy::DiffOptions;
+    ///
+    /// let mut options = DiffOptions::new();
+    /// options.set_ignore_case_unicode(true);
+    /// assert!(options.create_patch("Stra\u{df}e\n", "STRASSE\n").hunks().is_empty());
+    /// ```
+    ///
+    /// [`set_ignore_case`]: #method.set_ignore_case
+    #[cfg(feature = "unicode-case")]
+    pub fn set_ignore_case_unicode(&mut self, ignore_case_unicode: bool) -> &mut Self {
+        self.ignore_case_unicode = ignore_case_unicode;
+        self
+    }
+
+    /// Set the Unicode normalization form lines are converted to before they're compared, or
+    /// `None` to compare lines as they are
+    ///
+    /// Text from different sources may encode the same characters differently, e.g. `é` as a
+    /// single precomposed code point or as an `e` followed by a combining accent. Normalizing
+    /// makes such lines compare equal. Only the comparison is affected: the lines in a patch are
+    /// always the original text. Lines which aren't valid utf8 are compared as they are.
+    ///
+    /// ```
+    /// use diffy::{DiffOptions, NormalizationForm};
+    ///
+    /// let composed = "caf\u{e9}\n";
+    /// let decomposed = "cafe\u{301}\n";
+    ///
+    /// let mut options = DiffOptions::new();
+    /// assert_eq!(options.create_patch(composed, decomposed).hunks().len(), 1);
+    ///
+    /// options.set_normalize_unicode(Some(NormalizationForm::Nfc));
+    /// assert!(options.create_patch(composed, decomposed).hunks().is_empty());
+    /// ```
+    #[cfg(feature = "unicode-normalization")]
+    pub fn set_normalize_unicode(&mut self, form: Option<NormalizationForm>) -> &mut Self {
+        self.normalization = form;
+        self
+    }
+
+    /// Set whether runs of consecutive blank lines should be squeezed into a single blank line
+    /// before diffing, like `cat -s`
+    ///
+    /// This hides changes which only add or remove blank lines next to other blank lines, which
+    /// is useful for inputs whose spacing is inconsistent. A blank line is one with nothing
+    /// before its line ending. Such changes get no hunk of their own, like with GNU diff's
+    /// `--ignore-blank-lines`, but when they're close enough to another change to share its
+    /// hunk they're shown in it. Hunks are numbered against the texts themselves, so a patch
+    /// created with this option enabled still applies to the original text.
+    ///
+    /// ```
+    /// use diffy::DiffOptions;
+    ///
+    /// let original = "a\n\n\nb\nc\nd\ne\n";
+    /// let modified = "a\n\nb\nc\nd\nE\n\n\n";
+    ///
+    /// let expected = "\
+    /// --- original
+    /// +++ modified
+    /// @@ -6,2 +5,4 @@
+    ///  d
+    /// -e
+    /// +E
+    /// +
+    /// +
+    /// ";
+    ///
+    /// let mut options = DiffOptions::new();
+    /// options.set_squeeze_blank_lines(true).set_context_len(1);
+    /// assert_eq!(options.create_patch(original, modified).to_string(), expected);
+    /// ```
+    pub fn set_squeeze_blank_lines(&mut self, squeeze_blank_lines: bool) -> &mut Self {
+        self.squeeze_blank_lines = squeeze_blank_lines;
+        self
+    }
+
+    /// Set the prefixes which mark a line as a comment, see [`set_ignore_comment_changes`]
+    ///
+    /// [`set_ignore_comment_changes`]: #method.set_ignore_comment_changes
+    pub fn set_comment_prefixes(&mut self, prefixes: &[&str]) -> &mut Self {
+        self.comment_prefixes = prefixes.iter().map(|&prefix| prefix.into()).collect();
+        self
+    }
+
+    /// Set whether hunks which only delete and insert comments should be left out of patches
+    ///
+    /// A line is a comment if it starts with one of the [comment prefixes] once its leading
+    /// spaces and tabs are trimmed. A hunk is left out if all of the lines it deletes and
+    /// inserts are comments or blank lines, as long as at least one of them is a comment, while
+    /// a hunk which also changes other lines is kept whole, comments included. Comments are
+    /// still compared like any other line, so this only affects which hunks are produced, e.g.
+    /// a patch leaving out changes to comments won't turn the original text into the modified
+    /// one.
+    ///
+    /// Combined with [`set_squeeze_blank_lines`], hunks which only change the length of runs of
+    /// blank lines are left out as well, whether or not they change any comments.
+    ///
+    /// ```
+    /// use diffy::DiffOptions;
+    ///
+    /// let original = "# Copyright 2023\nport = 80\n";
+    /// let modified = "# Copyright 2024\nport = 80\n";
+    ///
+    /// let mut options = DiffOptions::new();
+    /// options.set_ignore_comment_changes(true);
+    /// assert!(options.create_patch(original, modified).hunks().is_empty());
+    /// ```
+    ///
+    /// [comment prefixes]: #method.set_comment_prefixes
+    /// [`set_squeeze_blank_lines`]: #method.set_squeeze_blank_lines
+    pub fn set_ignore_comment_changes(&mut self, ignore_comment_changes: bool) -> &mut Self {
+        self.ignore_comment_changes = ignore_comment_changes;
+        self
+    }
+
+    /// Set what separates the records which line diffs compare, instead of lines
+    ///
+    /// Texts which aren't a list of lines, like NUL-separated paths or prose made of
+    /// paragraphs, can then be diffed record by record with [`diff_lines`] and
+    /// [`diff_lines_bytes`]: every "line" of the resulting [`DiffLines`] is a record, ending with
+    /// its separator. All the other options apply to records just like they would to lines.
+    ///
+    /// Patches can only describe lines, so patches of records other than [`RecordSep::Line`]
+    /// can't be parsed back or applied. Use [`DiffLines::ops`] or [`DiffLines::edit_script`]
+    /// to consume their differences instead.
+    ///
+    /// ```
+    /// use diffy::{DiffOpKind, DiffOptions, RecordSep};
+    ///
+    /// let original = "a.txt\0b.txt\0c.txt\0";
+    /// let modified = "a.txt\0c.txt\0d.txt\0";
+    ///
+    /// let mut options = DiffOptions::new();
+    /// options.set_record_separator(RecordSep::Byte(b'\0'));
+    /// let diff = options.diff_lines(original, modified);
+    /// assert_eq!(diff.old_lines(), ["a.txt\0", "b.txt\0", "c.txt\0"]);
+    ///
+    /// let removed: Vec<_> = diff
+    ///     .ops()
+    ///     .into_iter()
+    ///     .filter(|op| op.kind == DiffOpKind::Delete)
+    ///     .flat_map(|op| &diff.old_lines()[op.old])
+    ///     .collect();
+    /// assert_eq!(removed, [&"b.txt\0"]);
+    /// ```
+    ///
+    /// [`diff_lines`]: #method.diff_lines
+    /// [`diff_lines_bytes`]: #method.diff_lines_bytes
+    /// [`DiffLines`]: struct.DiffLines.html
+    /// [`DiffLines::ops`]: struct.DiffLines.html#method.ops
+    /// [`DiffLines::edit_script`]: struct.DiffLines.html#method.edit_script
+    /// [`RecordSep::Line`]: enum.RecordSep.html#variant.Line
+    pub fn set_record_separator(&mut self, separator: RecordSep) -> &mut Self {
+        self.record_separator = separator;
+        self
+    }
+
+    /// Set a predicate marking lines as junk, which keeps them from anchoring line diffs, like
+    /// the `isjunk` parameter of Python's `difflib`
+    ///
+    /// Lines which occur all over a text, like blank lines or lone closing braces, can line up
+    /// unrelated parts of two texts. Junk lines still show up in diffs and can still be unchanged,
+    /// but only the other lines are matched up at first, and junk lines are then only matched
+    /// between those. The predicate is given each line including its line ending.
+    ///
+    /// ```
+    /// use diffy::DiffOptions;
+    ///
+    /// let original = "a\n}\nb\n";
+    /// let modified = "c\n}\nd\n";
+    ///
+    /// let expected = "\
+    /// --- original
+    /// +++ modified
+    /// @@ -1,3 +1,3 @@
+    /// -a
+    /// -}
+    /// -b
+    /// +c
+    /// +}
+    /// +d
+    /// ";
+    ///
+    /// let mut options = DiffOptions::new();
+    /// options.set_is_junk(Some(|line| line == b"}\n"));
+    /// assert_eq!(options.create_patch(original, modified).to_string(), expected);
+    /// ```
+    pub fn set_is_junk(&mut self, is_junk: Option<fn(&[u8]) -> bool>) -> &mut Self {
+        self.is_junk = is_junk;
+        self
+    }
+
+    /// Set whether to spend extra effort on making diffs as small as possible, like git's
+    /// `--minimal`
+    ///
+    /// Diffs are usually minimal already, but not when [junk lines] keep lines from being
+    /// matched up or when the [timeout] ran out. With this enabled the deleted and inserted lines
+    /// of every change are diffed again once the diff is computed, matching up any lines they
+    /// share. This takes precedence over junk lines, which are then matched within a change like
+    /// any other line. The second pass is bounded by the same timeout as the diff itself.
+    ///
+    /// ```
+    /// use diffy::DiffOptions;
+    ///
+    /// let original = "a\n}\nb\n";
+    /// let modified = "c\n}\nd\n";
+    ///
+    /// let mut options = DiffOptions::new();
+    /// options.set_is_junk(Some(|line| line == b"}\n"));
+    /// assert_eq!(options.diff_counts(original, modified), (0, 3, 3));
+    /// options.set_minimal(true);
+    /// assert_eq!(options.diff_counts(original, modified), (1, 2, 2));
+    /// ```
+    ///
+    /// [junk lines]: #method.set_is_junk
+    /// [timeout]: #method.set_timeout
+    pub fn set_minimal(&mut self, minimal: bool) -> &mut Self {
+        self.minimal = minimal;
+        self
+    }
+
     /// Enable/Disable diff compaction. Compaction is a post-processing step which attempts to
     /// produce a prettier diff by reducing the number of edited blocks by shifting and merging
     /// edit blocks.
@@ -76,49 +956,766 @@ impl DiffOptions {
         self
     }
 
-    // TODO determine if this should be exposed in the public API
-    #[allow(dead_code)]
-    fn diff<'a>(&self, original: &'a str, modified: &'a str) -> Vec<Diff<'a, str>> {
-        let solution = myers::diff(original.as_bytes(), modified.as_bytes());
+    /// Compute the character-level differences between two texts based on the configured options
+    pub fn diff<'a>(&self, original: &'a str, modified: &'a str) -> Vec<Diff<'a, str>> {
+        self.diff_str(original, modified)
+            .into_iter()
+            .map(Diff::from)
+            .collect()
+    }
 
-        let mut solution = solution
+    /// Compute the character-level differences between two texts like [`diff`], replacing every
+    /// equal segment at least `min_len` bytes long with an [`ElidedDiff::Skip`] of its length
+    ///
+    /// ```
+    /// use diffy::{DiffOptions, ElidedDiff};
+    ///
+    /// let original = "A long unchanged prefix, then x.";
+    /// let modified = "A long unchanged prefix, then y.";
+    ///
+    /// let diff = DiffOptions::new().diff_elided(original, modified, 10);
+    /// assert_eq!(
+    ///     diff,
+    ///     [
+    ///         ElidedDiff::Skip(30),
+    ///         ElidedDiff::Delete("x"),
+    ///         ElidedDiff::Insert("y"),
+    ///         ElidedDiff::Equal("."),
+    ///     ]
+    /// );
+    /// ```
+    ///
+    /// [`diff`]: #method.diff
+    /// [`ElidedDiff::Skip`]: enum.ElidedDiff.html#variant.Skip
+    pub fn diff_elided<'a>(
+        &self,
+        original: &'a str,
+        modified: &'a str,
+        min_len: usize,
+    ) -> Vec<ElidedDiff<'a, str>> {
+        self.diff_str(original, modified)
             .into_iter()
-            .map(|diff_range| diff_range.to_str(original, modified))
-            .collect();
+            .map(|diff| match diff {
+                DiffRange::Equal(range, _) if range.len() >= min_len => {
+                    ElidedDiff::Skip(range.len())
+                }
+                DiffRange::Equal(range, _) => ElidedDiff::Equal(range.as_slice()),
+                DiffRange::Delete(range) => ElidedDiff::Delete(range.as_slice()),
+                DiffRange::Insert(range) => ElidedDiff::Insert(range.as_slice()),
+            })
+            .collect()
+    }
+
+    fn diff_str<'a>(&self, original: &'a str, modified: &'a str) -> Vec<DiffRange<'a, 'a, str>> {
+        let mut solution = if original.is_ascii() && modified.is_ascii() {
+            // Every byte is a whole char, so the bytes can be diffed directly
+            self.diff_chars(original.as_bytes(), modified.as_bytes())
+                .into_iter()
+                .map(|diff_range| diff_range.to_str(original, |i| i, modified, |i| i))
+                .collect()
+        } else {
+            let (old_chars, old_starts) = char_starts(original);
+            let (new_chars, new_starts) = char_starts(modified);
+            self.diff_chars(&old_chars, &new_chars)
+                .into_iter()
+                .map(|diff_range| {
+                    diff_range.to_str(original, |i| old_starts[i], modified, |i| new_starts[i])
+                })
+                .collect()
+        };
 
         if self.compact {
             cleanup::compact(&mut solution);
         }
 
-        solution.into_iter().map(Diff::from).collect()
+        solution
+    }
+
+    // Diffs the chars of two texts, without compaction which needs the texts themselves
+    fn diff_chars<'a, 'b, T: PartialEq>(
+        &self,
+        old: &'a [T],
+        new: &'b [T],
+    ) -> Vec<DiffRange<'a, 'b, [T]>> {
+        let deadline = self.deadline();
+        let mut solution = self.with_progress(|progress| myers::diff(old, new, deadline, progress));
+        if self.minimal {
+            cleanup::minimize(&mut solution, deadline);
+        }
+        solution
+    }
+
+    /// Compute the index-level operations which transform `old` into `new` based on the
+    /// configured options
+    pub fn diff_ops<T: PartialEq>(&self, old: &[T], new: &[T]) -> Vec<Op> {
+        let mut ops = Vec::with_capacity(cmp::max(old.len(), new.len()));
+        for diff_range in self.diff_slice(old, new) {
+            match diff_range {
+                DiffRange::Equal(range, _) => ops.extend(range.range().map(Op::Keep)),
+                DiffRange::Delete(range) => ops.extend(range.range().map(Op::Remove)),
+                DiffRange::Insert(range) => ops.extend(range.range().map(Op::Insert)),
+            }
+        }
+        ops
     }
 
     /// Produce a Patch between two texts based on the configured options
     pub fn create_patch<'a>(&self, original: &'a str, modified: &'a str) -> Patch<'a, str> {
+        let (pre, post) = self.context_lens();
+        self.diff_lines(original, modified).to_patch_asym(pre, post)
+    }
+
+    /// Create a patch between two potentially non-utf8 texts
+    pub fn create_patch_bytes<'a>(
+        &self,
+        original: &'a [u8],
+        modified: &'a [u8],
+    ) -> Patch<'a, [u8]> {
+        let (pre, post) = self.context_lens();
+        self.diff_lines_bytes(original, modified)
+            .to_patch_asym(pre, post)
+    }
+
+    /// Produce a Patch between two texts, failing if more lines were changed than allowed by
+    /// [`set_max_changed_lines`]
+    ///
+    /// ```
+    /// use diffy::DiffOptions;
+    ///
+    /// let mut options = DiffOptions::new();
+    /// options.set_max_changed_lines(Some(2));
+    ///
+    /// assert!(options.try_create_patch("a\nb\nc\n", "a\nB\nc\n").is_ok());
+    /// assert!(options.try_create_patch("a\nb\nc\n", "A\nB\nC\n").is_err());
+    /// ```
+    ///
+    /// [`set_max_changed_lines`]: #method.set_max_changed_lines
+    pub fn try_create_patch<'a>(
+        &self,
+        original: &'a str,
+        modified: &'a str,
+    ) -> Result<Patch<'a, str>, TooDifferentError> {
+        let (pre, post) = self.context_lens();
+        self.try_diff_lines(original, modified)
+            .map(|diff| diff.to_patch_asym(pre, post))
+    }
+
+    /// Create a patch between two potentially non-utf8 texts, failing if more lines were changed
+    /// than allowed by [`set_max_changed_lines`]
+    ///
+    /// [`set_max_changed_lines`]: #method.set_max_changed_lines
+    pub fn try_create_patch_bytes<'a>(
+        &self,
+        original: &'a [u8],
+        modified: &'a [u8],
+    ) -> Result<Patch<'a, [u8]>, TooDifferentError> {
+        let (pre, post) = self.context_lens();
+        self.try_diff_lines_bytes(original, modified)
+            .map(|diff| diff.to_patch_asym(pre, post))
+    }
+
+    /// Compute the line-by-line differences between two texts based on the configured options
+    pub fn diff_lines<'a>(&self, original: &'a str, modified: &'a str) -> DiffLines<'a, str> {
+        self.diff_line_slices(self.split_lines(original), self.split_lines(modified))
+    }
+
+    /// Compute the line-by-line differences between two potentially non-utf8 texts
+    pub fn diff_lines_bytes<'a>(
+        &self,
+        original: &'a [u8],
+        modified: &'a [u8],
+    ) -> DiffLines<'a, [u8]> {
+        self.diff_line_slices(self.split_lines(original), self.split_lines(modified))
+    }
+
+    /// Compute the line-by-line differences between two texts which have already been split
+    /// into lines
+    ///
+    /// Each slice is compared and reported as a single line, even if it contains a `\n`, so
+    /// logical lines spanning several physical lines stay whole. Lines should end with their
+    /// line ending, like the lines of [`DiffLines`] do: a line without one is rendered in a
+    /// patch as missing the newline at the end of the file.
+    ///
+    /// The returned `DiffLines` borrows the lines themselves rather than the slices holding
+    /// them. See [`diff_lines_from_owned_slices`] for lines which aren't `&str`s.
+    ///
+    /// ```
+    /// use diffy::DiffOptions;
+    ///
+    /// let original = ["fn main() {\n", "    call(a,\n         b);\n", "}\n"];
+    /// let diff = DiffOptions::new().diff_lines_from_slices(&original, &["fn main() {}\n"]);
+    /// let edit = &diff.edit_script().edits[0];
+    /// assert_eq!((edit.old.clone(), edit.new.clone()), (0..3, 0..1));
+    /// ```
+    ///
+    /// [`DiffLines`]: struct.DiffLines.html
+    /// [`diff_lines_from_owned_slices`]: #method.diff_lines_from_owned_slices
+    pub fn diff_lines_from_slices<'a>(
+        &self,
+        original: &[&'a str],
+        modified: &[&'a str],
+    ) -> DiffLines<'a, str> {
+        self.diff_line_slices(original.to_vec(), modified.to_vec())
+    }
+
+    /// Compute the line-by-line differences between two texts which have already been split
+    /// into lines of any type which can be borrowed as a `str`, like `String`s
+    ///
+    /// This works like [`diff_lines_from_slices`], except that the returned `DiffLines` borrows
+    /// from the slices holding the lines.
+    ///
+    /// ```
+    /// use diffy::DiffOptions;
+    ///
+    /// let original = ["fn main() {\n", "    call(a,\n         b);\n", "}\n"];
+    /// let modified: Vec<String> = original.iter().map(|line| line.replace('b', "c")).collect();
+    ///
+    /// let diff = DiffOptions::new().diff_lines_from_owned_slices(&original, &modified);
+    /// let edit = &diff.edit_script().edits[0];
+    /// assert_eq!((edit.old.clone(), edit.new.clone()), (1..2, 1..2));
+    /// assert_eq!(diff.new_lines()[1], modified[1]);
+    /// ```
+    ///
+    /// [`diff_lines_from_slices`]: #method.diff_lines_from_slices
+    pub fn diff_lines_from_owned_slices<'a, O, M>(
+        &self,
+        original: &'a [O],
+        modified: &'a [M],
+    ) -> DiffLines<'a, str>
+    where
+        O: AsRef<str>,
+        M: AsRef<str>,
+    {
+        self.diff_line_slices(
+            original.iter().map(AsRef::as_ref).collect(),
+            modified.iter().map(AsRef::as_ref).collect(),
+        )
+    }
+
+    /// Compute the line-by-line differences between two texts, failing if more lines were
+    /// changed than allowed by [`set_max_changed_lines`]
+    ///
+    /// [`set_max_changed_lines`]: #method.set_max_changed_lines
+    pub fn try_diff_lines<'a>(
+        &self,
+        original: &'a str,
+        modified: &'a str,
+    ) -> Result<DiffLines<'a, str>, TooDifferentError> {
+        let max = self.max_changed_lines.unwrap_or(usize::MAX);
+        self.diff_lines_bounded(original, modified, max)
+            .ok_or(TooDifferentError(max))
+    }
+
+    /// Compute the line-by-line differences between two potentially non-utf8 texts, failing if
+    /// more lines were changed than allowed by [`set_max_changed_lines`]
+    ///
+    /// [`set_max_changed_lines`]: #method.set_max_changed_lines
+    pub fn try_diff_lines_bytes<'a>(
+        &self,
+        original: &'a [u8],
+        modified: &'a [u8],
+    ) -> Result<DiffLines<'a, [u8]>, TooDifferentError> {
+        let max = self.max_changed_lines.unwrap_or(usize::MAX);
+        self.diff_lines_bounded(original, modified, max)
+            .ok_or(TooDifferentError(max))
+    }
+
+    /// Returns `true` if more than `max_changed_lines` lines would need to be inserted or
+    /// deleted to turn one text into the other
+    ///
+    /// This is much cheaper than computing the diff and counting its changes: no diff is built,
+    /// and the search stops as soon as it's known that the texts differ by more than
+    /// `max_changed_lines`, so unrelated texts are rejected early. Lines are compared the same
+    /// way as by [`diff_lines`], and a changed line counts as one deleted and one inserted line.
+    ///
+    /// ```
+    /// use diffy::DiffOptions;
+    ///
+    /// let original = "a\nb\nc\nd\n";
+    /// let modified = "a\nB\nc\nd\ne\n";
+    ///
+    /// let options = DiffOptions::new();
+    /// assert!(options.differs_by_more_than(original, modified, 2));
+    /// assert!(!options.differs_by_more_than(original, modified, 3));
+    /// ```
+    ///
+    /// [`diff_lines`]: #method.diff_lines
+    pub fn differs_by_more_than(
+        &self,
+        original: &str,
+        modified: &str,
+        max_changed_lines: usize,
+    ) -> bool {
+        self.lines_differ_by_more_than(original, modified, max_changed_lines)
+    }
+
+    /// Returns `true` if more than `max_changed_lines` lines would need to be inserted or
+    /// deleted to turn one potentially non-utf8 text into the other, see
+    /// [`differs_by_more_than`]
+    ///
+    /// [`differs_by_more_than`]: #method.differs_by_more_than
+    pub fn differs_by_more_than_bytes(
+        &self,
+        original: &[u8],
+        modified: &[u8],
+        max_changed_lines: usize,
+    ) -> bool {
+        self.lines_differ_by_more_than(original, modified, max_changed_lines)
+    }
+
+    /// Returns `true` if more than `max_changes` elements would need to be inserted or deleted
+    /// to turn `old` into `new`, see [`differs_by_more_than`]
+    ///
+    /// This works on any slice, like lines which have already been split.
+    ///
+    /// [`differs_by_more_than`]: #method.differs_by_more_than
+    pub fn differs_by_more_than_slices<T: PartialEq>(
+        &self,
+        old: &[T],
+        new: &[T],
+        max_changes: usize,
+    ) -> bool {
+        myers::exceeds_cost(old, new, max_changes)
+    }
+
+    /// Count the lines which are unchanged, inserted and deleted between two texts, returned as
+    /// `(equal, inserted, deleted)`
+    ///
+    /// The counts are taken straight from the diff without building a [`DiffLines`] or a
+    /// [`Patch`], which makes this the cheapest way to measure line-level churn. Lines are
+    /// compared the same way as by [`diff_lines`], and a changed line counts as one deleted and
+    /// one inserted line, so `equal + deleted` is the number of lines in `original` and `equal +
+    /// inserted` is the number of lines in `modified`. Identical texts aren't diffed at all.
+    ///
+    /// ```
+    /// use diffy::DiffOptions;
+    ///
+    /// let original = "a\nb\nc\nd\n";
+    /// let modified = "a\nB\nc\nd\ne\n";
+    ///
+    /// assert_eq!(DiffOptions::new().diff_counts(original, modified), (3, 2, 1));
+    /// ```
+    ///
+    /// [`DiffLines`]: struct.DiffLines.html
+    /// [`Patch`]: struct.Patch.html
+    /// [`diff_lines`]: #method.diff_lines
+    pub fn diff_counts(&self, original: &str, modified: &str) -> (usize, usize, usize) {
+        self.count_line_changes(original, modified)
+    }
+
+    /// Count the lines which are unchanged, inserted and deleted between two potentially
+    /// non-utf8 texts, see [`diff_counts`]
+    ///
+    /// [`diff_counts`]: #method.diff_counts
+    pub fn diff_counts_bytes(&self, original: &[u8], modified: &[u8]) -> (usize, usize, usize) {
+        self.count_line_changes(original, modified)
+    }
+
+    fn count_line_changes<T: ?Sized + Text>(
+        &self,
+        original: &T,
+        modified: &T,
+    ) -> (usize, usize, usize) {
+        if original.as_bytes() == modified.as_bytes() {
+            return (self.squeeze_lines(self.split_lines(original)).len(), 0, 0);
+        }
+
+        let old_lines = self.squeeze_lines(self.split_lines(original));
+        let new_lines = self.squeeze_lines(self.split_lines(modified));
+        let (old_ids, new_ids) = self.classify_line_slices(&old_lines, &new_lines);
+        let junk = self.junk_lines(&old_lines, &new_lines);
+        let solution = self
+            .diff_line_ids(&old_ids, &new_ids, junk, usize::MAX)
+            .expect("unbounded diff can't exceed its bound");
+
+        let mut counts = (0, 0, 0);
+        for diff in &solution {
+            match diff {
+                DiffRange::Equal(range, _) => counts.0 += range.len(),
+                DiffRange::Insert(range) => counts.1 += range.len(),
+                DiffRange::Delete(range) => counts.2 += range.len(),
+            }
+        }
+        counts
+    }
+
+    fn lines_differ_by_more_than<T: ?Sized + Text>(
+        &self,
+        original: &T,
+        modified: &T,
+        max_changed_lines: usize,
+    ) -> bool {
+        let old_lines = self.squeeze_lines(self.split_lines(original));
+        let new_lines = self.squeeze_lines(self.split_lines(modified));
+        let (old_ids, new_ids) = self.classify_line_slices(&old_lines, &new_lines);
+        myers::exceeds_cost(&old_ids, &new_ids, max_changed_lines)
+    }
+
+    fn diff_lines_bounded<'a, T: ?Sized + Text>(
+        &self,
+        original: &'a T,
+        modified: &'a T,
+        max_changed_lines: usize,
+    ) -> Option<DiffLines<'a, T>> {
+        let (old_lines, old_ids, new_lines, new_ids) = self.classify_lines(original, modified);
+        let (edit_script, squeezed_edits) = self.line_edit_script(
+            &old_lines,
+            &new_lines,
+            &old_ids,
+            &new_ids,
+            max_changed_lines,
+        )?;
+
+        let mut diff = DiffLines::new(old_lines, new_lines, edit_script);
+        diff.merge_hunks = self.merge_hunks;
+        diff.ignored_edits = self.ignored_edits(
+            &diff.old_lines,
+            &diff.new_lines,
+            &diff.edit_script,
+            squeezed_edits,
+        );
+        if self.detect_moves {
+            diff.moves = moves::detect(&old_ids, &new_ids, &diff.edit_script);
+        }
+        Some(diff)
+    }
+
+    // Splits both texts into lines and assigns each line an id, where lines which should be
+    // considered equal share the same id
+    fn classify_lines<'a, T: ?Sized + Text>(
+        &self,
+        original: &'a T,
+        modified: &'a T,
+    ) -> (Vec<&'a T>, Vec<u64>, Vec<&'a T>, Vec<u64>) {
+        let old_lines = self.split_lines(original);
+        let new_lines = self.split_lines(modified);
+        let (old_ids, new_ids) = self.classify_line_slices(&old_lines, &new_lines);
+        (old_lines, old_ids, new_lines, new_ids)
+    }
+
+    fn split_lines<'a, T: ?Sized + Text>(&self, text: &'a T) -> Vec<&'a T> {
+        split_records(text, self.record_separator)
+    }
+
+    // Marks the lines which are left out when squeezing runs of blank lines, or returns `None`
+    // if blank lines aren't squeezed
+    fn squeezed_lines<T: ?Sized + Text>(&self, lines: &[&T]) -> Option<Vec<bool>> {
+        if !self.squeeze_blank_lines {
+            return None;
+        }
+        Some(squeezed_blank_lines(
+            lines.iter().map(|&line| is_blank(line)),
+        ))
+    }
+
+    fn squeeze_lines<'a, T: ?Sized + Text>(&self, lines: Vec<&'a T>) -> Vec<&'a T> {
+        match self.squeezed_lines(&lines) {
+            Some(squeezed) => lines
+                .into_iter()
+                .zip(squeezed)
+                .filter_map(|(line, squeezed)| if squeezed { None } else { Some(line) })
+                .collect(),
+            None => lines,
+        }
+    }
+
+    fn classify_line_slices<'a, T: ?Sized + Text>(
+        &self,
+        old_lines: &[&'a T],
+        new_lines: &[&'a T],
+    ) -> (Vec<u64>, Vec<u64>) {
+        if self.has_owned_keys() {
+            let old_keys: Vec<_> = old_lines.iter().map(|&line| self.line_key(line)).collect();
+            let new_keys: Vec<_> = new_lines.iter().map(|&line| self.line_key(line)).collect();
+
+            let mut classifier = Classifier::<[u8]>::default();
+            let old_ids = old_keys.iter().map(|k| classifier.classify(k)).collect();
+            let new_ids = new_keys.iter().map(|k| classifier.classify(k)).collect();
+            return (old_ids, new_ids);
+        }
+
         let mut classifier = Classifier::default();
-        let (old_lines, old_ids) = classifier.classify_lines(original);
-        let (new_lines, new_ids) = classifier.classify_lines(modified);
+        let old_ids = old_lines
+            .iter()
+            .map(|&line| classifier.classify(self.match_key(line)))
+            .collect();
+        let new_ids = new_lines
+            .iter()
+            .map(|&line| classifier.classify(self.match_key(line)))
+            .collect();
+        (old_ids, new_ids)
+    }
+
+    // Computes the line-by-line differences between two texts which have already been split
+    // into lines
+    fn diff_line_slices<'a, T: ?Sized + Text>(
+        &self,
+        old_lines: Vec<&'a T>,
+        new_lines: Vec<&'a T>,
+    ) -> DiffLines<'a, T> {
+        let (old_ids, new_ids) = self.classify_line_slices(&old_lines, &new_lines);
+        let (edit_script, squeezed_edits) = self
+            .line_edit_script(&old_lines, &new_lines, &old_ids, &new_ids, usize::MAX)
+            .expect("unbounded diff can't exceed its bound");
+
+        let mut diff = DiffLines::new(old_lines, new_lines, edit_script);
+        diff.merge_hunks = self.merge_hunks;
+        diff.ignored_edits = self.ignored_edits(
+            &diff.old_lines,
+            &diff.new_lines,
+            &diff.edit_script,
+            squeezed_edits,
+        );
+        if self.detect_moves {
+            diff.moves = moves::detect(&old_ids, &new_ids, &diff.edit_script);
+        }
+        diff
+    }
+
+    // Diffs two texts' lines into an edit script over all of their lines. When runs of blank
+    // lines are squeezed, the returned marks tell which changes only change the length of such
+    // a run, see `diff_squeezed_line_ids`.
+    fn line_edit_script<T: ?Sized + Text, I: PartialEq + Clone>(
+        &self,
+        old_lines: &[&T],
+        new_lines: &[&T],
+        old_ids: &[I],
+        new_ids: &[I],
+        max_changed_lines: usize,
+    ) -> Option<(Vec<EditRange>, Vec<bool>)> {
+        let squeezed = self
+            .squeezed_lines(old_lines)
+            .zip(self.squeezed_lines(new_lines));
+        let junk = self.junk_lines(old_lines, new_lines);
+        self.diff_squeezed_line_ids(old_ids, new_ids, squeezed, junk, max_changed_lines)
+    }
+
+    // Diffs the ids of two texts' lines like `diff_line_ids`, leaving out the lines marked in
+    // `squeezed`. The edit script is mapped back to all of the lines, so that the hunks built
+    // from it apply to the texts themselves, and where the runs of blank lines between two
+    // matching lines differ in length a change is added, which is marked in the returned
+    // `Vec`. It's empty if no lines are squeezed.
+    fn diff_squeezed_line_ids<I: PartialEq + Clone>(
+        &self,
+        old_ids: &[I],
+        new_ids: &[I],
+        squeezed: Option<(Vec<bool>, Vec<bool>)>,
+        junk: Option<(Vec<bool>, Vec<bool>)>,
+        max_changed_lines: usize,
+    ) -> Option<(Vec<EditRange>, Vec<bool>)> {
+        let (old_squeezed, new_squeezed) = match squeezed {
+            Some(squeezed) => squeezed,
+            None => {
+                let solution = self.diff_line_ids(old_ids, new_ids, junk, max_changed_lines)?;
+                return Some((build_edit_script(&solution), Vec::new()));
+            }
+        };
+
+        let kept = |squeezed: &[bool]| -> Vec<usize> {
+            (0..squeezed.len()).filter(|&idx| !squeezed[idx]).collect()
+        };
+        let (old_kept, new_kept) = (kept(&old_squeezed), kept(&new_squeezed));
+        let junk = junk.map(|(old, new)| (select(&old, &old_kept), select(&new, &new_kept)));
+        let (old_ids_kept, new_ids_kept) = (select(old_ids, &old_kept), select(new_ids, &new_kept));
+        let solution = self.diff_line_ids(&old_ids_kept, &new_ids_kept, junk, max_changed_lines)?;
+        Some(unsqueeze_edit_script(
+            &build_edit_script(&solution),
+            (&old_kept, old_ids.len()),
+            (&new_kept, new_ids.len()),
+        ))
+    }
+
+    // Marks which changes of an edit script are left out of a patch unless they share a hunk
+    // with another change: the changes to the length of runs of blank lines marked in
+    // `squeezed_edits`, and if changes to comments are ignored, changes which only delete and
+    // insert comments and blank lines. Empty if no changes are ignored.
+    fn ignored_edits<T: ?Sized + Text>(
+        &self,
+        old_lines: &[&T],
+        new_lines: &[&T],
+        edit_script: &[EditRange],
+        squeezed_edits: Vec<bool>,
+    ) -> Vec<IgnoredEdit> {
+        if !self.ignore_comment_changes {
+            return IgnoredEdit::squeezed(squeezed_edits);
+        }
+        edit_script
+            .iter()
+            .enumerate()
+            .map(|(idx, edit)| {
+                let mut lines = old_lines[edit.old.clone()]
+                    .iter()
+                    .chain(&new_lines[edit.new.clone()]);
+                if squeezed_edits.get(idx).copied().unwrap_or(false) {
+                    IgnoredEdit::Squeezed
+                } else if !lines
+                    .clone()
+                    .all(|&line| is_blank(line) || self.is_comment(line))
+                {
+                    IgnoredEdit::No
+                } else if lines.any(|&line| self.is_comment(line)) {
+                    IgnoredEdit::Comments
+                } else {
+                    IgnoredEdit::Blank
+                }
+            })
+            .collect()
+    }
+
+    fn is_comment<T: ?Sized + Text>(&self, line: &T) -> bool {
+        let content = strip_indentation(line).as_bytes();
+        self.comment_prefixes
+            .iter()
+            .any(|prefix| !prefix.is_empty() && content.starts_with(prefix.as_bytes()))
+    }
+
+    // Marks which lines of two texts are junk, if a junk predicate was set
+    fn junk_lines<T: ?Sized + Text>(
+        &self,
+        old_lines: &[&T],
+        new_lines: &[&T],
+    ) -> Option<(Vec<bool>, Vec<bool>)> {
+        let is_junk = self.is_junk?;
+        Some((
+            old_lines.iter().map(|l| is_junk(l.as_bytes())).collect(),
+            new_lines.iter().map(|l| is_junk(l.as_bytes())).collect(),
+        ))
+    }
+
+    // Diffs the ids of two texts' lines, keeping the lines marked as junk from anchoring the
+    // diff
+    fn diff_line_ids<'a, I: PartialEq>(
+        &self,
+        old_ids: &'a [I],
+        new_ids: &'a [I],
+        junk: Option<(Vec<bool>, Vec<bool>)>,
+        max_changed_lines: usize,
+    ) -> Option<Vec<DiffRange<'a, 'a, [I]>>> {
+        let (old_junk, new_junk) = match junk {
+            Some(junk) => junk,
+            None => return self.diff_slice_bounded(old_ids, new_ids, max_changed_lines),
+        };
+
+        let deadline = self.deadline();
+        let mut solution = self.with_progress(|progress| {
+            myers::diff_bounded_with_junk(
+                old_ids,
+                &old_junk,
+                new_ids,
+                &new_junk,
+                max_changed_lines,
+                deadline,
+                progress,
+            )
+        })?;
+
+        if self.minimal {
+            cleanup::minimize(&mut solution, deadline);
+        }
+        if self.compact {
+            cleanup::compact(&mut solution);
+        }
+
+        Some(solution)
+    }
+
+    // Returns `true` if lines may need to be copied into a new key before they're classified
+    fn has_owned_keys(&self) -> bool {
+        #[cfg(feature = "unicode-normalization")]
+        if self.normalization.is_some() {
+            return true;
+        }
+        self.ignores_case() || !self.trailing_chars.is_empty()
+    }
+
+    fn ignores_case(&self) -> bool {
+        #[cfg(feature = "unicode-case")]
+        if self.ignore_case_unicode {
+            return true;
+        }
+        self.ignore_case
+    }
+
+    // Returns the part of a line which is compared, before any transformations which need to
+    // copy it
+    fn match_key<'a, T: ?Sized + Text>(&self, line: &'a T) -> &'a T {
+        let line = match self.strip_prefix {
+            Some(prefix) => {
+                let content_len = split_line_ending(line).0.len();
+                let mut len = match prefix {
+                    LinePrefix::Len(len) => len,
+                    LinePrefix::Fn(prefix_len) => prefix_len(line.as_bytes()),
+                };
+                len = cmp::min(len, content_len);
+                while !line.is_char_boundary(len) {
+                    len += 1;
+                }
+                line.split_at(len).1
+            }
+            None => line,
+        };
+        if self.ignore_indentation {
+            strip_indentation(line)
+        } else {
+            line
+        }
+    }
+
+    // Returns `true` if lines are compared as they are, without leaving out or transforming
+    // any part of them
+    #[cfg(feature = "std")]
+    fn compares_whole_lines(&self) -> bool {
+        !self.ignore_indentation && self.strip_prefix.is_none() && !self.has_owned_keys()
+    }
+
+    // Returns the key a line is compared by. Lines which aren't valid utf8 are only ever
+    // transformed at the byte level.
+    fn line_key<'a, T: ?Sized + Text>(&self, line: &'a T) -> Cow<'a, [u8]> {
+        let line = self.match_key(line);
+        let mut key = Cow::Borrowed(line.as_bytes());
+
+        if !self.trailing_chars.is_empty() {
+            let (content, ending) = split_line_ending(line.as_bytes());
+            let trimmed = trim_trailing_chars(content, &self.trailing_chars);
+            if trimmed.len() < content.len() {
+                key = Cow::Owned([trimmed, ending].concat());
+            }
+        }
+
+        #[cfg(feature = "unicode-case")]
+        if self.ignore_case_unicode {
+            if let Some(s) = core::str::from_utf8(&key).ok().filter(|s| !s.is_ascii()) {
+                key = Cow::Owned(caseless::default_case_fold_str(s).into_bytes());
+            }
+        }
+
+        if self.ignores_case() && key.iter().any(u8::is_ascii_uppercase) {
+            key.to_mut().make_ascii_lowercase();
+        }
 
-        let solution = self.diff_slice(&old_ids, &new_ids);
+        // ASCII text is the same in every normalization form
+        #[cfg(feature = "unicode-normalization")]
+        if let Some(form) = self.normalization {
+            if let Some(s) = core::str::from_utf8(&key).ok().filter(|s| !s.is_ascii()) {
+                key = Cow::Owned(form.normalize(s).into_bytes());
+            }
+        }
 
-        let hunks = to_hunks(&old_lines, &new_lines, &solution, self.context_len);
-        Patch::new(Some("original"), Some("modified"), hunks)
+        key
     }
 
-    /// Create a patch between two potentially non-utf8 texts
-    pub fn create_patch_bytes<'a>(
+    /// Diff two texts which have already been split into lines
+    #[cfg(feature = "arbitrary")]
+    pub(crate) fn diff_split_lines<'a, T: ?Sized + PartialEq>(
         &self,
-        original: &'a [u8],
-        modified: &'a [u8],
-    ) -> Patch<'a, [u8]> {
-        let mut classifier = Classifier::default();
-        let (old_lines, old_ids) = classifier.classify_lines(original);
-        let (new_lines, new_ids) = classifier.classify_lines(modified);
-
-        let solution = self.diff_slice(&old_ids, &new_ids);
-
-        let hunks = to_hunks(&old_lines, &new_lines, &solution, self.context_len);
-        Patch::new(Some(&b"original"[..]), Some(&b"modified"[..]), hunks)
+        old_lines: Vec<&'a T>,
+        new_lines: Vec<&'a T>,
+    ) -> DiffLines<'a, T> {
+        let solution = self.diff_slice(&old_lines, &new_lines);
+        let edit_script = build_edit_script(&solution);
+        let mut diff = DiffLines::new(old_lines, new_lines, edit_script);
+        diff.merge_hunks = self.merge_hunks;
+        diff
     }
 
     pub(crate) fn diff_slice<'a, T: PartialEq>(
@@ -126,14 +1723,39 @@ impl DiffOptions {
         old: &'a [T],
         new: &'a [T],
     ) -> Vec<DiffRange<'a, 'a, [T]>> {
-        let mut solution = myers::diff(old, new);
+        let deadline = self.deadline();
+        let mut solution = self.with_progress(|progress| myers::diff(old, new, deadline, progress));
 
+        if self.minimal {
+            cleanup::minimize(&mut solution, deadline);
+        }
         if self.compact {
             cleanup::compact(&mut solution);
         }
 
         solution
     }
+
+    pub(crate) fn diff_slice_bounded<'a, T: PartialEq>(
+        &self,
+        old: &'a [T],
+        new: &'a [T],
+        max_cost: usize,
+    ) -> Option<Vec<DiffRange<'a, 'a, [T]>>> {
+        let deadline = self.deadline();
+        let mut solution = self.with_progress(|progress| {
+            myers::diff_bounded(old, new, max_cost, deadline, progress)
+        })?;
+
+        if self.minimal {
+            cleanup::minimize(&mut solution, deadline);
+        }
+        if self.compact {
+            cleanup::compact(&mut solution);
+        }
+
+        Some(solution)
+    }
 }
 
 impl Default for DiffOptions {
@@ -142,12 +1764,50 @@ impl Default for DiffOptions {
     }
 }
 
-// TODO determine if this should be exposed in the public API
-#[allow(dead_code)]
-fn diff<'a>(original: &'a str, modified: &'a str) -> Vec<Diff<'a, str>> {
+/// Compute the character-level differences between two texts
+///
+/// ```
+/// use diffy::{diff, Diff};
+///
+/// let solution = diff("Tress of the Emerald Sea", "Tress of the Crimson Sea");
+/// assert_eq!(solution.first(), Some(&Diff::Equal("Tress of the ")));
+/// assert_eq!(solution.last(), Some(&Diff::Equal(" Sea")));
+/// ```
+pub fn diff<'a>(original: &'a str, modified: &'a str) -> Vec<Diff<'a, str>> {
     DiffOptions::default().diff(original, modified)
 }
 
+/// Compute the index-level operations which transform `old` into `new`
+///
+/// This is useful for keeping a parallel data structure, such as a list of UI elements, in sync
+/// with a list of items without rebuilding it from scratch.
+///
+/// ```
+/// use diffy::{diff_ops, Op};
+///
+/// let old = ["a", "b", "c"];
+/// let new = ["a", "c", "d"];
+///
+/// let ops = diff_ops(&old, &new);
+/// assert_eq!(
+///     ops,
+///     [Op::Keep(0), Op::Remove(1), Op::Keep(2), Op::Insert(2)]
+/// );
+///
+/// let mut elements = Vec::new();
+/// for op in ops {
+///     match op {
+///         Op::Keep(idx) => elements.push(old[idx]),
+///         Op::Remove(_) => {}
+///         Op::Insert(idx) => elements.push(new[idx]),
+///     }
+/// }
+/// assert_eq!(elements, new);
+/// ```
+pub fn diff_ops<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Op> {
+    DiffOptions::default().diff_ops(old, new)
+}
+
 /// Create a patch between two texts.
 ///
 /// ```
@@ -190,96 +1850,729 @@ pub fn create_patch_bytes<'a>(original: &'a [u8], modified: &'a [u8]) -> Patch<'
     DiffOptions::default().create_patch_bytes(original, modified)
 }
 
+/// An error returned when more lines were changed than allowed by
+/// [`DiffOptions::set_max_changed_lines`]
+///
+/// [`DiffOptions::set_max_changed_lines`]: struct.DiffOptions.html#method.set_max_changed_lines
+#[derive(Debug)]
+pub struct TooDifferentError(usize);
+
+impl TooDifferentError {
+    /// Returns the maximum number of changed lines which was exceeded
+    pub fn max_changed_lines(&self) -> usize {
+        self.0
+    }
+}
+
+impl fmt::Display for TooDifferentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "more than {} lines changed", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TooDifferentError {}
+
+/// Compute the line-by-line differences between two texts
+///
+/// ```
+/// # use diffy::diff_lines;
+/// let original = "one\ntwo\nthree\nfour\nfive\n";
+/// let modified = "one\ntwo\nthree\nfour\n5\n";
+///
+/// let expected = "\
+/// --- original
+/// +++ modified
+/// @@ -3,3 +3,3 @@
+///  three
+///  four
+/// -five
+/// +5
+/// ";
+///
+/// let diff = diff_lines(original, modified);
+/// assert_eq!(diff.to_patch_asym(2, 0).to_string(), expected);
+/// ```
+pub fn diff_lines<'a>(original: &'a str, modified: &'a str) -> DiffLines<'a, str> {
+    DiffOptions::default().diff_lines(original, modified)
+}
+
+/// Compute the line-by-line differences between two texts which have already been split into
+/// lines, see [`DiffOptions::diff_lines_from_slices`]
+///
+/// [`DiffOptions::diff_lines_from_slices`]: struct.DiffOptions.html#method.diff_lines_from_slices
+pub fn diff_lines_from_slices<'a>(
+    original: &[&'a str],
+    modified: &[&'a str],
+) -> DiffLines<'a, str> {
+    DiffOptions::default().diff_lines_from_slices(original, modified)
+}
+
+/// Compute the line-by-line differences between two texts which have already been split into
+/// lines of any type which can be borrowed as a `str`, see
+/// [`DiffOptions::diff_lines_from_owned_slices`]
+///
+/// [`DiffOptions::diff_lines_from_owned_slices`]: struct.DiffOptions.html#method.diff_lines_from_owned_slices
+pub fn diff_lines_from_owned_slices<'a, O, M>(
+    original: &'a [O],
+    modified: &'a [M],
+) -> DiffLines<'a, str>
+where
+    O: AsRef<str>,
+    M: AsRef<str>,
+{
+    DiffOptions::default().diff_lines_from_owned_slices(original, modified)
+}
+
+/// Compute the line-by-line differences between two potentially non-utf8 texts
+pub fn diff_lines_bytes<'a>(original: &'a [u8], modified: &'a [u8]) -> DiffLines<'a, [u8]> {
+    DiffOptions::default().diff_lines_bytes(original, modified)
+}
+
+/// Returns `true` if more than `max_changed_lines` lines would need to be inserted or deleted
+/// to turn one text into the other, see [`DiffOptions::differs_by_more_than`]
+///
+/// ```
+/// use diffy::differs_by_more_than;
+///
+/// assert!(differs_by_more_than("a\nb\n", "a\nc\n", 1));
+/// assert!(!differs_by_more_than("a\nb\n", "a\nc\n", 2));
+/// ```
+///
+/// [`DiffOptions::differs_by_more_than`]: struct.DiffOptions.html#method.differs_by_more_than
+pub fn differs_by_more_than(original: &str, modified: &str, max_changed_lines: usize) -> bool {
+    DiffOptions::default().differs_by_more_than(original, modified, max_changed_lines)
+}
+
+/// Returns `true` if more than `max_changed_lines` lines would need to be inserted or deleted
+/// to turn one potentially non-utf8 text into the other, see
+/// [`DiffOptions::differs_by_more_than`]
+///
+/// [`DiffOptions::differs_by_more_than`]: struct.DiffOptions.html#method.differs_by_more_than
+pub fn differs_by_more_than_bytes(
+    original: &[u8],
+    modified: &[u8],
+    max_changed_lines: usize,
+) -> bool {
+    DiffOptions::default().differs_by_more_than_bytes(original, modified, max_changed_lines)
+}
+
+/// Returns `true` if more than `max_changes` elements would need to be inserted or deleted to
+/// turn `old` into `new`, see [`DiffOptions::differs_by_more_than`]
+///
+/// [`DiffOptions::differs_by_more_than`]: struct.DiffOptions.html#method.differs_by_more_than
+pub fn differs_by_more_than_slices<T: PartialEq>(old: &[T], new: &[T], max_changes: usize) -> bool {
+    DiffOptions::default().differs_by_more_than_slices(old, new, max_changes)
+}
+
+/// Count the lines which are unchanged, inserted and deleted between two texts, returned as
+/// `(equal, inserted, deleted)`, see [`DiffOptions::diff_counts`]
+///
+/// ```
+/// use diffy::diff_counts;
+///
+/// assert_eq!(diff_counts("a\nb\n", "a\nc\nd\n"), (1, 2, 1));
+/// ```
+///
+/// [`DiffOptions::diff_counts`]: struct.DiffOptions.html#method.diff_counts
+pub fn diff_counts(original: &str, modified: &str) -> (usize, usize, usize) {
+    DiffOptions::default().diff_counts(original, modified)
+}
+
+/// Count the lines which are unchanged, inserted and deleted between two potentially non-utf8
+/// texts, see [`DiffOptions::diff_counts`]
+///
+/// [`DiffOptions::diff_counts`]: struct.DiffOptions.html#method.diff_counts
+pub fn diff_counts_bytes(original: &[u8], modified: &[u8]) -> (usize, usize, usize) {
+    DiffOptions::default().diff_counts_bytes(original, modified)
+}
+
+/// The line-by-line differences between two texts
+///
+/// A `DiffLines` holds the lines of both texts along with the edit script needed to turn
+/// one into the other, which allows a [`Patch`] to be produced with any amount of context
+/// without having to recompute the diff.
+///
+/// [`Patch`]: struct.Patch.html
+pub struct DiffLines<'a, T: ?Sized> {
+    old_lines: Vec<&'a T>,
+    new_lines: Vec<&'a T>,
+    edit_script: Vec<EditRange>,
+    merge_hunks: bool,
+    // Which changes get no hunk of their own, see `DiffOptions::ignored_edits`
+    ignored_edits: Vec<IgnoredEdit>,
+    moves: Vec<moves::MovedBlock>,
+}
+
+impl<'a, T: ?Sized> DiffLines<'a, T> {
+    fn new(old_lines: Vec<&'a T>, new_lines: Vec<&'a T>, edit_script: Vec<EditRange>) -> Self {
+        Self {
+            old_lines,
+            new_lines,
+            edit_script,
+            merge_hunks: true,
+            ignored_edits: Vec::new(),
+            moves: Vec::new(),
+        }
+    }
+
+    /// Returns the lines of the original text
+    pub fn old_lines(&self) -> &[&'a T] {
+        &self.old_lines
+    }
+
+    /// Returns the lines of the modified text
+    pub fn new_lines(&self) -> &[&'a T] {
+        &self.new_lines
+    }
+
+    /// Returns an owned view of the edit script, suitable for serialization
+    pub fn edit_script(&self) -> EditScript<T::Owned>
+    where
+        T: ToOwned,
+    {
+        EditScript {
+            old_len: self.old_lines.len(),
+            new_len: self.new_lines.len(),
+            edits: self.line_edits(),
+            old_lines: None,
+            new_lines: None,
+        }
+    }
+
+    /// Returns an owned view of the edit script which also includes the lines of both texts
+    pub fn edit_script_with_lines(&self) -> EditScript<T::Owned>
+    where
+        T: ToOwned,
+    {
+        EditScript {
+            old_lines: Some(self.old_lines.iter().map(|l| (*l).to_owned()).collect()),
+            new_lines: Some(self.new_lines.iter().map(|l| (*l).to_owned()).collect()),
+            ..self.edit_script()
+        }
+    }
+
+    /// Returns how similar the two texts are as a number between `0.0` and `1.0`
+    ///
+    /// The score is twice the number of unchanged lines divided by the total number of lines in
+    /// both texts, so identical texts score `1.0` and texts without any lines in common score
+    /// `0.0`. Two empty texts are considered identical.
+    ///
+    /// ```
+    /// use diffy::diff_lines;
+    ///
+    /// assert_eq!(diff_lines("a\nb\n", "a\nc\n").similarity(), 0.5);
+    /// ```
+    pub fn similarity(&self) -> f64 {
+        self.metrics().ratio()
+    }
+
+    /// Returns measures of how closely the two texts match, computed from the edit script
+    ///
+    /// Lines are counted by their position in the texts, and a line is matched when the diff
+    /// left it unchanged, which depends on the options lines were compared with: lines which
+    /// only compare equal, e.g. with [`set_ignore_case`], count as matched.
+    ///
+    /// ```
+    /// use diffy::diff_lines;
+    ///
+    /// let metrics = diff_lines("a\nb\nc\nd\n", "a\nb\nx\nd\ne\n").metrics();
+    /// assert_eq!(metrics.matched, 3);
+    /// assert_eq!((metrics.old_len, metrics.new_len), (4, 5));
+    /// assert_eq!(metrics.longest_match, 2);
+    /// assert_eq!(metrics.ratio(), 6.0 / 9.0);
+    /// ```
+    ///
+    /// [`set_ignore_case`]: struct.DiffOptions.html#method.set_ignore_case
+    pub fn metrics(&self) -> DiffMetrics {
+        DiffMetrics::from_edit_script(
+            self.old_lines.len(),
+            self.new_lines.len(),
+            &self.edit_script,
+        )
+    }
+
+    fn line_edits(&self) -> Vec<LineEdit> {
+        self.edit_script
+            .iter()
+            .map(|edit| LineEdit {
+                old: edit.old.clone(),
+                new: edit.new.clone(),
+            })
+            .collect()
+    }
+
+    /// Returns the number of hunks a patch with `context_len` lines of context would contain
+    ///
+    /// This is cheaper than building the patch with `to_patch` and counting its hunks, as none
+    /// of the hunks' lines are collected.
+    ///
+    /// ```
+    /// use diffy::diff_lines;
+    ///
+    /// let original = "a\nb\nc\nd\ne\nf\ng\n";
+    /// let modified = "A\nb\nc\nd\ne\nf\nG\n";
+    /// let diff = diff_lines(original, modified);
+    /// assert_eq!(diff.hunk_count(1), 2);
+    /// assert_eq!(diff.hunk_count(3), 1);
+    /// assert_eq!(diff.hunk_count(3), diff.to_patch(3).hunks().len());
+    /// ```
+    pub fn hunk_count(&self, context_len: usize) -> usize {
+        count_hunks(
+            self.old_lines.len(),
+            self.new_lines.len(),
+            &self.edit_script,
+            &self.ignored_edits,
+            self.merge_hunks,
+            context_len,
+        )
+    }
+
+    /// Returns the hunks of a patch with `context_len` lines of context, building them one at a
+    /// time
+    ///
+    /// These are the same hunks [`to_patch`] collects, but only the hunk currently being looked
+    /// at is held in memory, so hunks can be written out and dropped one by one when diffing
+    /// huge texts.
+    ///
+    /// ```
+    /// use diffy::{diff_lines, PatchFormatter};
+    ///
+    /// let diff = diff_lines("a\nb\nc\nd\ne\n", "A\nb\nc\nd\nE\n");
+    /// let f = PatchFormatter::new();
+    /// let mut output = String::new();
+    /// for hunk in diff.iter_hunks(1) {
+    ///     output += &f.fmt_hunk(&hunk).to_string();
+    /// }
+    /// assert_eq!(output, "@@ -1,2 +1,2 @@\n-a\n+A\n b\n@@ -4,2 +4,2 @@\n d\n-e\n+E\n");
+    /// ```
+    ///
+    /// [`to_patch`]: #method.to_patch
+    pub fn iter_hunks(&self, context_len: usize) -> impl Iterator<Item = Hunk<'a, T>> + '_ {
+        self.hunk_iter(context_len, context_len)
+    }
+
+    fn hunk_iter(
+        &self,
+        pre_context_len: usize,
+        post_context_len: usize,
+    ) -> impl Iterator<Item = Hunk<'a, T>> + '_ {
+        self.hunk_groups(pre_context_len, post_context_len)
+            .map(move |group| to_hunk(&self.old_lines, &self.new_lines, &self.edit_script, group))
+    }
+
+    // Groups the changes into hunks, leaving out the hunks which only contain ignored changes
+    fn hunk_groups(
+        &self,
+        pre_context_len: usize,
+        post_context_len: usize,
+    ) -> impl Iterator<Item = HunkGroup> + '_ {
+        hunk_groups(
+            self.old_lines.len(),
+            self.new_lines.len(),
+            &self.edit_script,
+            &self.ignored_edits,
+            self.merge_hunks,
+            (pre_context_len, post_context_len),
+        )
+    }
+
+    fn hunks(&self, pre_context_len: usize, post_context_len: usize) -> Vec<Hunk<'a, T>> {
+        self.hunk_iter(pre_context_len, post_context_len).collect()
+    }
+}
+
+impl<'a> DiffLines<'a, str> {
+    /// Produce a Patch using `context_len` lines of context around each change
+    pub fn to_patch(&self, context_len: usize) -> Patch<'a, str> {
+        self.to_patch_asym(context_len, context_len)
+    }
+
+    /// Produce a Patch using `pre` lines of context before, and `post` lines of context after,
+    /// each change
+    ///
+    /// Two changes end up in the same hunk when the number of unchanged lines between them is
+    /// at most `pre + post`, like GNU diff does, unless merging was disabled with
+    /// [`set_merge_hunks`].
+    ///
+    /// [`set_merge_hunks`]: struct.DiffOptions.html#method.set_merge_hunks
+    pub fn to_patch_asym(&self, pre: usize, post: usize) -> Patch<'a, str> {
+        Patch::new(Some("original"), Some("modified"), self.hunks(pre, post))
+    }
+}
+
+impl<'a> DiffLines<'a, [u8]> {
+    /// Produce a Patch using `context_len` lines of context around each change
+    pub fn to_patch(&self, context_len: usize) -> Patch<'a, [u8]> {
+        self.to_patch_asym(context_len, context_len)
+    }
+
+    /// Produce a Patch using `pre` lines of context before, and `post` lines of context after,
+    /// each change
+    ///
+    /// Two changes end up in the same hunk when the number of unchanged lines between them is
+    /// at most `pre + post`, like GNU diff does, unless merging was disabled with
+    /// [`set_merge_hunks`].
+    ///
+    /// [`set_merge_hunks`]: struct.DiffOptions.html#method.set_merge_hunks
+    pub fn to_patch_asym(&self, pre: usize, post: usize) -> Patch<'a, [u8]> {
+        Patch::new(
+            Some(&b"original"[..]),
+            Some(&b"modified"[..]),
+            self.hunks(pre, post),
+        )
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for DiffLines<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DiffLines")
+            .field("old_lines", &self.old_lines)
+            .field("new_lines", &self.new_lines)
+            .field("edit_script", &self.edit_script)
+            .field("merge_hunks", &self.merge_hunks)
+            .field("ignored_edits", &self.ignored_edits)
+            .field("moves", &self.moves)
+            .finish()
+    }
+}
+
+/// An owned view of the edit script of a [`DiffLines`]
+///
+/// Lines are indexed from `0` and every line which isn't covered by an edit is unchanged. With
+/// the `serde` feature enabled this can be serialized and deserialized, e.g. to be handed off to
+/// a diff renderer running in a browser:
+///
+/// ```json
+/// {
+///   "old_len": 3,
+///   "new_len": 3,
+///   "edits": [{ "old": { "start": 1, "end": 2 }, "new": { "start": 1, "end": 2 } }],
+///   "old_lines": ["a\n", "b\n", "c\n"],
+///   "new_lines": ["a\n", "B\n", "c\n"]
+/// }
+/// ```
+///
+/// The `old_lines` and `new_lines` fields are only present when the script was produced by
+/// [`DiffLines::edit_script_with_lines`].
+///
+/// [`DiffLines`]: struct.DiffLines.html
+/// [`DiffLines::edit_script_with_lines`]: struct.DiffLines.html#method.edit_script_with_lines
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EditScript<T> {
+    /// The number of lines in the original text
+    pub old_len: usize,
+    /// The number of lines in the modified text
+    pub new_len: usize,
+    /// The changed regions, in order
+    pub edits: Vec<LineEdit>,
+    /// The lines of the original text, if requested
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub old_lines: Option<Vec<T>>,
+    /// The lines of the modified text, if requested
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub new_lines: Option<Vec<T>>,
+}
+
+/// A changed region of an [`EditScript`]: the lines in `old` are replaced by the lines in `new`
+///
+/// One of the ranges may be empty for a pure insertion or deletion.
+///
+/// [`EditScript`]: struct.EditScript.html
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LineEdit {
+    /// The range of lines from the original text
+    pub old: ops::Range<usize>,
+    /// The range of lines from the modified text
+    pub new: ops::Range<usize>,
+}
+
 fn to_hunks<'a, T: ?Sized>(
     lines1: &[&'a T],
     lines2: &[&'a T],
-    solution: &[DiffRange<[u64]>],
-    context_len: usize,
+    edit_script: &[EditRange],
+    pre_context_len: usize,
+    post_context_len: usize,
 ) -> Vec<Hunk<'a, T>> {
-    let edit_script = build_edit_script(solution);
+    HunkGroups::new(
+        lines1.len(),
+        lines2.len(),
+        edit_script,
+        pre_context_len,
+        post_context_len,
+    )
+    .map(|group| to_hunk(lines1, lines2, edit_script, group))
+    .collect()
+}
 
-    let mut hunks = Vec::new();
+// Returns the chars of a text along with the byte offset each one starts at, followed by the
+// length of the text
+pub(crate) fn char_starts(text: &str) -> (Vec<char>, Vec<usize>) {
+    let (chars, mut starts): (Vec<_>, Vec<_>) =
+        text.char_indices().map(|(start, c)| (c, start)).unzip();
+    starts.push(text.len());
+    (chars, starts)
+}
 
-    let mut idx = 0;
-    while let Some(mut script) = edit_script.get(idx) {
-        let start1 = script.old.start.saturating_sub(context_len);
-        let start2 = script.new.start.saturating_sub(context_len);
+// Groups the changes of an edit script into hunks with the given lengths of context before and
+// after each change, leaving out the hunks which only contain ignored changes
+fn hunk_groups<'s>(
+    len1: usize,
+    len2: usize,
+    edit_script: &'s [EditRange],
+    ignored_edits: &'s [IgnoredEdit],
+    merge_hunks: bool,
+    (pre_context_len, post_context_len): (usize, usize),
+) -> impl Iterator<Item = HunkGroup> + 's {
+    let mut groups = HunkGroups::new(len1, len2, edit_script, pre_context_len, post_context_len);
+    groups.merge = merge_hunks;
+    groups.filter(move |group| !only_ignored(ignored_edits, group))
+}
 
-        let (mut end1, mut end2) = calc_end(
-            context_len,
-            lines1.len(),
-            lines2.len(),
-            script.old.end,
-            script.new.end,
-        );
+// Counts the hunks `hunk_groups` would return with `context_len` lines of context
+fn count_hunks(
+    len1: usize,
+    len2: usize,
+    edit_script: &[EditRange],
+    ignored_edits: &[IgnoredEdit],
+    merge_hunks: bool,
+    context_len: usize,
+) -> usize {
+    if !merge_hunks && ignored_edits.is_empty() {
+        // Every change gets a hunk of its own
+        return edit_script.len();
+    }
+    hunk_groups(
+        len1,
+        len2,
+        edit_script,
+        ignored_edits,
+        merge_hunks,
+        (context_len, context_len),
+    )
+    .count()
+}
 
-        let mut lines = Vec::new();
+// Whether a change is left out of a patch unless it shares a hunk with other changes, see
+// `DiffOptions::ignored_edits`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum IgnoredEdit {
+    No,
+    // Only changes the length of a run of blank lines
+    Squeezed,
+    // Only deletes and inserts comments and blank lines, at least one of them a comment
+    Comments,
+    // Only deletes and inserts blank lines, which are only ignored along with comments
+    Blank,
+}
 
-        // Pre-context
-        for line in lines2.get(start2..script.new.start).into_iter().flatten() {
-            lines.push(Line::Context(*line));
-        }
+impl IgnoredEdit {
+    // Ignores the changes to the length of runs of blank lines marked in `squeezed_edits`
+    fn squeezed(squeezed_edits: Vec<bool>) -> Vec<Self> {
+        squeezed_edits
+            .into_iter()
+            .map(|squeezed| if squeezed { Self::Squeezed } else { Self::No })
+            .collect()
+    }
+}
 
-        loop {
-            // Delete lines from text1
-            for line in lines1.get(script.old.clone()).into_iter().flatten() {
-                lines.push(Line::Delete(*line));
-            }
+// Returns `true` if every change of a group is ignored, blank lines only counting as ignored if
+// the group changes a comment as well
+fn only_ignored(ignored_edits: &[IgnoredEdit], group: &HunkGroup) -> bool {
+    let ignored = match ignored_edits.get(group.edits.clone()) {
+        Some(ignored) => ignored,
+        None => return false,
+    };
+    ignored.iter().all(|&edit| edit != IgnoredEdit::No)
+        && (ignored.iter().all(|&edit| edit != IgnoredEdit::Blank)
+            || ignored.contains(&IgnoredEdit::Comments))
+}
 
-            // Insert lines from text2
-            for line in lines2.get(script.new.clone()).into_iter().flatten() {
-                lines.push(Line::Insert(*line));
-            }
+// Builds the hunk spanning a group of changes
+fn to_hunk<'a, T: ?Sized>(
+    lines1: &[&'a T],
+    lines2: &[&'a T],
+    edit_script: &[EditRange],
+    group: HunkGroup,
+) -> Hunk<'a, T> {
+    let edits = &edit_script[group.edits];
+    let mut lines = Vec::new();
 
-            if let Some(s) = edit_script.get(idx + 1) {
-                // Check to see if we can merge the hunks
-                let start1_next =
-                    cmp::min(s.old.start, lines1.len() - 1).saturating_sub(context_len);
-                if start1_next < end1 {
-                    // Context lines between hunks
-                    for (_i1, i2) in (script.old.end..s.old.start).zip(script.new.end..s.new.start)
-                    {
-                        if let Some(line) = lines2.get(i2) {
-                            lines.push(Line::Context(*line));
-                        }
-                    }
+    // Pre-context
+    for line in lines2
+        .get(group.new.start..edits[0].new.start)
+        .into_iter()
+        .flatten()
+    {
+        lines.push(Line::Context(*line));
+    }
 
-                    // Calc the new end
-                    let (e1, e2) = calc_end(
-                        context_len,
-                        lines1.len(),
-                        lines2.len(),
-                        s.old.end,
-                        s.new.end,
-                    );
-
-                    end1 = e1;
-                    end2 = e2;
-                    script = s;
-                    idx += 1;
-                    continue;
+    for (idx, script) in edits.iter().enumerate() {
+        // Context lines between the merged changes
+        if let Some(prev) = idx.checked_sub(1).map(|idx| &edits[idx]) {
+            for (_i1, i2) in (prev.old.end..script.old.start).zip(prev.new.end..script.new.start) {
+                if let Some(line) = lines2.get(i2) {
+                    lines.push(Line::Context(*line));
                 }
             }
+        }
+
+        // Delete lines from text1
+        for line in lines1.get(script.old.clone()).into_iter().flatten() {
+            lines.push(Line::Delete(*line));
+        }
 
-            break;
+        // Insert lines from text2
+        for line in lines2.get(script.new.clone()).into_iter().flatten() {
+            lines.push(Line::Insert(*line));
         }
+    }
+
+    // Post-context
+    let last = &edits[edits.len() - 1];
+    for line in lines2
+        .get(last.new.end..group.new.end)
+        .into_iter()
+        .flatten()
+    {
+        lines.push(Line::Context(*line));
+    }
+
+    let old_range = HunkRange::from(group.old);
+    let new_range = HunkRange::from(group.new);
+
+    Hunk::new(old_range, new_range, None, lines)
+}
+
+// The changes which make up a single hunk, along with the lines the hunk spans in both texts
+struct HunkGroup {
+    edits: ops::Range<usize>,
+    old: ops::Range<usize>,
+    new: ops::Range<usize>,
+}
+
+// Groups the changes of an edit script into hunks, merging changes whose context would overlap
+// or be adjacent unless merging is disabled
+struct HunkGroups<'s> {
+    len1: usize,
+    len2: usize,
+    edit_script: &'s [EditRange],
+    pre_context_len: usize,
+    post_context_len: usize,
+    merge: bool,
+    idx: usize,
+}
 
-        // Post-context
-        for line in lines2.get(script.new.end..end2).into_iter().flatten() {
-            lines.push(Line::Context(*line));
+impl<'s> HunkGroups<'s> {
+    fn new(
+        len1: usize,
+        len2: usize,
+        edit_script: &'s [EditRange],
+        pre_context_len: usize,
+        post_context_len: usize,
+    ) -> Self {
+        Self {
+            len1,
+            len2,
+            edit_script,
+            pre_context_len,
+            post_context_len,
+            merge: true,
+            idx: 0,
         }
+    }
+
+    // Returns the context lines after a change and before the next one when they're kept in
+    // separate hunks, splitting the `gap` unchanged lines between them so that neither hunk's
+    // context overlaps the other's
+    fn split_gap(&self, gap: usize) -> (usize, usize) {
+        let post = cmp::min(
+            self.post_context_len,
+            gap - cmp::min(self.pre_context_len, gap / 2),
+        );
+        (post, cmp::min(self.pre_context_len, gap - post))
+    }
 
-        let len1 = end1 - start1;
-        let old_range = HunkRange::new(if len1 > 0 { start1 + 1 } else { start1 }, len1);
+    // Returns the group of a single change, when merging is disabled
+    fn next_unmerged(&mut self) -> Option<HunkGroup> {
+        let idx = self.idx;
+        let script = self.edit_script.get(idx)?;
+        let pre = match idx.checked_sub(1).map(|prev| &self.edit_script[prev]) {
+            Some(prev) => self.split_gap(script.old.start - prev.old.end).1,
+            None => self.pre_context_len,
+        };
+        let post = match self.edit_script.get(idx + 1) {
+            Some(next) => self.split_gap(next.old.start - script.old.end).0,
+            None => self.post_context_len,
+        };
 
-        let len2 = end2 - start2;
-        let new_range = HunkRange::new(if len2 > 0 { start2 + 1 } else { start2 }, len2);
+        let (end1, end2) = calc_end(post, self.len1, self.len2, script.old.end, script.new.end);
 
-        hunks.push(Hunk::new(old_range, new_range, None, lines));
-        idx += 1;
+        self.idx += 1;
+        Some(HunkGroup {
+            edits: idx..self.idx,
+            old: script.old.start.saturating_sub(pre)..end1,
+            new: script.new.start.saturating_sub(pre)..end2,
+        })
     }
+}
+
+impl Iterator for HunkGroups<'_> {
+    type Item = HunkGroup;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.merge {
+            return self.next_unmerged();
+        }
+
+        let first = self.idx;
+        let script = self.edit_script.get(first)?;
+        let start1 = script.old.start.saturating_sub(self.pre_context_len);
+        let start2 = script.new.start.saturating_sub(self.pre_context_len);
+
+        let (mut end1, mut end2) = calc_end(
+            self.post_context_len,
+            self.len1,
+            self.len2,
+            script.old.end,
+            script.new.end,
+        );
 
-    hunks
+        // Merge the following changes for as long as their context overlaps or touches, so
+        // that two hunks are never adjacent
+        while let Some(s) = self.edit_script.get(self.idx + 1) {
+            let start1_next = s.old.start.saturating_sub(self.pre_context_len);
+            if start1_next > end1 {
+                break;
+            }
+
+            let (e1, e2) = calc_end(
+                self.post_context_len,
+                self.len1,
+                self.len2,
+                s.old.end,
+                s.new.end,
+            );
+            end1 = e1;
+            end2 = e2;
+            self.idx += 1;
+        }
+
+        self.idx += 1;
+        Some(HunkGroup {
+            edits: first..self.idx,
+            old: start1..end1,
+            new: start2..end2,
+        })
+    }
 }
 
 fn calc_end(
@@ -303,7 +2596,7 @@ fn calc_end(
     (end1, end2)
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 struct EditRange {
     old: ops::Range<usize>,
     new: ops::Range<usize>,
@@ -315,6 +2608,144 @@ impl EditRange {
     }
 }
 
+// Returns `true` if a line is empty apart from its line ending
+fn is_blank<T: ?Sized + Text>(line: &T) -> bool {
+    matches!(line.as_bytes(), b"\n" | b"\r\n")
+}
+
+// Marks every blank line which directly follows another blank line, given whether each line is
+// blank
+fn squeezed_blank_lines(blank: impl IntoIterator<Item = bool>) -> Vec<bool> {
+    let mut prev_blank = false;
+    blank
+        .into_iter()
+        .map(|blank| {
+            let squeezed = blank && prev_blank;
+            prev_blank = blank;
+            squeezed
+        })
+        .collect()
+}
+
+fn select<T: Clone>(items: &[T], indices: &[usize]) -> Vec<T> {
+    indices.iter().map(|&idx| items[idx].clone()).collect()
+}
+
+// Maps an edit script over the lines of two texts which were kept when squeezing their runs of
+// blank lines back to all of their lines, given the index of each kept line and the number of
+// lines of each text. Returns the edit script along with which of its changes only make two
+// runs of blank lines the same length; those are added wherever the lines squeezed after two
+// matching lines differ in number. A change touching another one is merged into it.
+fn unsqueeze_edit_script(
+    edit_script: &[EditRange],
+    (old_kept, old_len): (&[usize], usize),
+    (new_kept, new_len): (&[usize], usize),
+) -> (Vec<EditRange>, Vec<bool>) {
+    let old_pos = |idx: usize| old_kept.get(idx).copied().unwrap_or(old_len);
+    let new_pos = |idx: usize| new_kept.get(idx).copied().unwrap_or(new_len);
+    let mut edits: Vec<EditRange> = Vec::new();
+    let mut squeezed: Vec<bool> = Vec::new();
+    let mut push = |edit: EditRange, is_squeezed: bool| match edits.last_mut() {
+        Some(last) if last.old.end == edit.old.start && last.new.end == edit.new.start => {
+            last.old.end = edit.old.end;
+            last.new.end = edit.new.end;
+            *squeezed.last_mut().unwrap() &= is_squeezed;
+        }
+        _ => {
+            edits.push(edit);
+            squeezed.push(is_squeezed);
+        }
+    };
+
+    let (mut old_idx, mut new_idx) = (0, 0);
+    let end = EditRange::new(
+        old_kept.len()..old_kept.len(),
+        new_kept.len()..new_kept.len(),
+    );
+    for edit in edit_script.iter().chain(Some(&end)) {
+        // Each pair of matching lines is followed by the lines squeezed after it
+        for (old, new) in (old_idx..edit.old.start).zip(new_idx..edit.new.start) {
+            let old_run = old_pos(old + 1) - old_pos(old);
+            let new_run = new_pos(new + 1) - new_pos(new);
+            let common = cmp::min(old_run, new_run);
+            if old_run != new_run {
+                push(
+                    EditRange::new(
+                        old_pos(old) + common..old_pos(old + 1),
+                        new_pos(new) + common..new_pos(new + 1),
+                    ),
+                    true,
+                );
+            }
+        }
+        if !edit.old.is_empty() || !edit.new.is_empty() {
+            push(
+                EditRange::new(
+                    old_pos(edit.old.start)..old_pos(edit.old.end),
+                    new_pos(edit.new.start)..new_pos(edit.new.end),
+                ),
+                false,
+            );
+        }
+        old_idx = edit.old.end;
+        new_idx = edit.new.end;
+    }
+    (edits, squeezed)
+}
+
+// Trims every trailing char of a line's content which is in `chars`
+fn trim_trailing_chars<'a>(mut content: &'a [u8], chars: &str) -> &'a [u8] {
+    let mut buf = [0; 4];
+    while let Some(c) = chars
+        .chars()
+        .find(|c| content.ends_with(c.encode_utf8(&mut buf).as_bytes()))
+    {
+        content = &content[..content.len() - c.len_utf8()];
+    }
+    content
+}
+
+fn split_records<T: ?Sized + Text>(text: &T, separator: RecordSep) -> Vec<&T> {
+    let record_len = |rest: &T| -> usize {
+        let len = match separator {
+            RecordSep::Line => rest.find("\n").map(|idx| idx + 1),
+            RecordSep::Byte(byte) => rest
+                .as_bytes()
+                .iter()
+                .enumerate()
+                .filter(|&(_, &b)| b == byte)
+                .map(|(idx, _)| idx + 1)
+                .find(|&end| rest.is_char_boundary(end)),
+            RecordSep::Str("") => None,
+            RecordSep::Str(separator) => rest.find(separator).map(|idx| idx + separator.len()),
+            RecordSep::Paragraph => {
+                // A record ends before the first non-blank line following a blank line
+                let mut len = 0;
+                let mut after_blank = false;
+                for line in LineIter::new(rest) {
+                    let blank = split_line_ending(line).0.is_empty();
+                    if after_blank && !blank {
+                        break;
+                    }
+                    after_blank |= blank;
+                    len += line.len();
+                }
+                Some(len)
+            }
+        };
+        len.unwrap_or_else(|| rest.len())
+    };
+
+    let mut records = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let (record, remaining) = rest.split_at(record_len(rest));
+        records.push(record);
+        rest = remaining;
+    }
+    records
+}
+
 fn build_edit_script<T>(solution: &[DiffRange<[T]>]) -> Vec<EditRange> {
     let mut idx_a = 0;
     let mut idx_b = 0;
@@ -0,0 +1,130 @@
+//! Detect blocks of lines which were moved rather than deleted and inserted
+
+use super::{DiffLines, EditRange};
+use alloc::{vec, vec::Vec};
+use core::ops;
+
+#[cfg(feature = "std")]
+use std::collections::HashMap as Map;
+
+// Without `std` there's no randomly seeded hasher available so fall back to an ordered map
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap as Map;
+
+// The number of lines in the windows used to find moved blocks, which is also the minimum size
+// of a moved block. Shorter blocks are too likely to be unrelated lines which happen to match,
+// like a lone closing brace.
+const WINDOW: usize = 3;
+
+/// A block of lines which was deleted from one place in the original text and inserted
+/// unchanged at another place in the modified text, as returned by [`DiffLines::moves`]
+///
+/// [`DiffLines::moves`]: struct.DiffLines.html#method.moves
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MovedBlock {
+    /// The range of deleted lines in the original text
+    pub old: ops::Range<usize>,
+    /// The range of inserted lines in the modified text
+    pub new: ops::Range<usize>,
+}
+
+impl<T: ?Sized> DiffLines<'_, T> {
+    /// Returns the blocks of lines which were moved, if move detection was enabled with
+    /// [`DiffOptions::set_detect_moves`]
+    ///
+    /// The blocks are ordered by their position in the original text. Each line of the edit
+    /// script is part of at most one moved block.
+    ///
+    /// ```
+    /// use diffy::DiffOptions;
+    ///
+    /// let original = "a\nb\nc\nd\ne\nf\n";
+    /// let modified = "d\ne\nf\na\nb\nc\n";
+    ///
+    /// let diff = DiffOptions::new().set_detect_moves(true).diff_lines(original, modified);
+    /// // "d", "e" and "f" were moved in front of "a", "b" and "c"
+    /// assert_eq!(diff.moves().len(), 1);
+    /// assert_eq!(diff.moves()[0].old, 3..6);
+    /// assert_eq!(diff.moves()[0].new, 0..3);
+    /// ```
+    ///
+    /// [`DiffOptions::set_detect_moves`]: struct.DiffOptions.html#method.set_detect_moves
+    pub fn moves(&self) -> &[MovedBlock] {
+        &self.moves
+    }
+}
+
+// Finds the blocks of deleted lines which were inserted unchanged elsewhere.
+//
+// Rather than comparing every deleted block against every inserted block, every window of
+// `WINDOW` consecutive inserted lines is indexed by its line ids. Each window of deleted lines
+// then only has to look up the inserted windows with exactly the same lines, after which a
+// match is greedily extended for as long as both sides keep agreeing.
+pub(super) fn detect(
+    old_ids: &[u64],
+    new_ids: &[u64],
+    edit_script: &[EditRange],
+) -> Vec<MovedBlock> {
+    let mut inserted = vec![false; new_ids.len()];
+    for edit in edit_script {
+        inserted[edit.new.clone()]
+            .iter_mut()
+            .for_each(|i| *i = true);
+    }
+
+    let mut windows: Map<&[u64], Vec<usize>> = Map::default();
+    for edit in edit_script {
+        for start in edit.new.start..edit.new.end.saturating_sub(WINDOW - 1) {
+            windows
+                .entry(&new_ids[start..start + WINDOW])
+                .or_default()
+                .push(start);
+        }
+    }
+
+    let mut claimed = vec![false; new_ids.len()];
+    let mut moves = Vec::new();
+    for edit in edit_script {
+        let mut start = edit.old.start;
+        while start + WINDOW <= edit.old.end {
+            let candidates = windows
+                .get(&old_ids[start..start + WINDOW])
+                .map_or(&[][..], Vec::as_slice);
+
+            // The longest unclaimed run of inserted lines agreeing with the deleted lines
+            let best = candidates
+                .iter()
+                .map(|&new_start| {
+                    let len = (0..)
+                        .take_while(|&offset| {
+                            let (old, new) = (start + offset, new_start + offset);
+                            old < edit.old.end
+                                && new < new_ids.len()
+                                && inserted[new]
+                                && !claimed[new]
+                                && old_ids[old] == new_ids[new]
+                        })
+                        .count();
+                    (new_start, len)
+                })
+                .filter(|&(_, len)| len >= WINDOW)
+                .max_by_key(|&(new_start, len)| (len, core::cmp::Reverse(new_start)));
+
+            match best {
+                Some((new_start, len)) => {
+                    claimed[new_start..new_start + len]
+                        .iter_mut()
+                        .for_each(|c| *c = true);
+                    moves.push(MovedBlock {
+                        old: start..start + len,
+                        new: new_start..new_start + len,
+                    });
+                    start += len;
+                }
+                None => start += 1,
+            }
+        }
+    }
+
+    moves
+}
@@ -1,5 +1,108 @@
 use crate::range::{DiffRange, Range};
-use std::ops::{Index, IndexMut};
+use alloc::{vec, vec::Vec};
+use core::ops::{self, Index, IndexMut};
+
+/// A point in time after which the search stops looking for a minimal diff
+///
+/// Without the `std` feature there's no clock, so a deadline never passes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Deadline {
+    #[cfg(feature = "std")]
+    at: Option<std::time::Instant>,
+}
+
+impl Deadline {
+    /// A deadline which never passes
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// A deadline `timeout` from now, or no deadline if `timeout` is `None`
+    #[cfg(feature = "std")]
+    pub fn after(timeout: Option<std::time::Duration>) -> Self {
+        let now = std::time::Instant::now();
+        Self {
+            // A timeout too large to represent is as good as no timeout
+            at: timeout.and_then(|timeout| now.checked_add(timeout)),
+        }
+    }
+
+    fn has_passed(&self) -> bool {
+        #[cfg(feature = "std")]
+        if let Some(at) = self.at {
+            return std::time::Instant::now() >= at;
+        }
+        false
+    }
+}
+
+/// Estimates how far along a diff is and reports it to a callback
+///
+/// The estimate is the number of elements of both inputs covered by the diff so far, plus the
+/// edit distance reached by the search for the current middle snake, relative to the length of
+/// both inputs. It's reported at most once per percent and never goes backwards.
+pub struct Progress<'a> {
+    report: Option<&'a mut dyn FnMut(f32)>,
+    total: usize,
+    done: usize,
+    reported: f32,
+}
+
+impl<'a> Progress<'a> {
+    /// Progress which isn't reported anywhere
+    pub fn none() -> Self {
+        Self {
+            report: None,
+            total: 0,
+            done: 0,
+            reported: 0.0,
+        }
+    }
+
+    /// Progress reported to `report`
+    #[cfg(feature = "std")]
+    pub fn new(report: &'a mut dyn FnMut(f32)) -> Self {
+        Self {
+            report: Some(report),
+            ..Self::none()
+        }
+    }
+
+    fn start(&mut self, total: usize) {
+        self.total = total;
+        self.done = 0;
+    }
+
+    // Marks `len` more elements of both inputs as covered by the diff
+    fn advance(&mut self, len: usize) {
+        self.done += len;
+        self.update(0);
+    }
+
+    // Reports the progress of a search which has reached an edit distance of `d`
+    fn update(&mut self, d: usize) {
+        if self.report.is_none() || self.total == 0 {
+            return;
+        }
+        let fraction = ((self.done + d) as f32 / self.total as f32).min(1.0);
+        if fraction >= self.reported + 0.01 {
+            self.report(fraction);
+        }
+    }
+
+    fn finish(&mut self) {
+        if self.reported < 1.0 {
+            self.report(1.0);
+        }
+    }
+
+    fn report(&mut self, fraction: f32) {
+        if let Some(report) = &mut self.report {
+            self.reported = fraction;
+            report(fraction);
+        }
+    }
+}
 
 // A D-path is a path which starts at (0,0) that has exactly D non-diagonal edges. All D-paths
 // consist of a (D - 1)-path followed by a non-diagonal edge and then a possibly empty sequence of
@@ -57,8 +160,8 @@ struct Snake {
     y_end: usize,
 }
 
-impl ::std::fmt::Display for Snake {
-    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+impl ::core::fmt::Display for Snake {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
         write!(
             f,
             "({}, {}) -> ({}, {})",
@@ -72,6 +175,16 @@ fn max_d(len1: usize, len2: usize) -> usize {
     (len1 + len2 + 1) / 2 + 1
 }
 
+// The outcome of searching for the middle snake
+enum MiddleSnake {
+    // The middle snake and the length of the shortest edit script passing through it
+    Found(isize, Snake),
+    // The shortest edit script would be longer than the maximum allowed cost
+    TooCostly,
+    // The deadline passed before the middle snake was found
+    TimedOut,
+}
+
 // The divide part of a divide-and-conquer strategy. A D-path has D+1 snakes some of which may
 // be empty. The divide step requires finding the ceil(D/2) + 1 or middle snake of an optimal
 // D-path. The idea for doing so is to simultaneously run the basic algorithm in both the
@@ -82,7 +195,10 @@ fn find_middle_snake<T: PartialEq>(
     new: Range<'_, [T]>,
     vf: &mut V,
     vb: &mut V,
-) -> (isize, Snake) {
+    max_cost: usize,
+    deadline: Deadline,
+    progress: &mut Progress<'_>,
+) -> MiddleSnake {
     let n = old.len();
     let m = new.len();
 
@@ -102,6 +218,15 @@ fn find_middle_snake<T: PartialEq>(
     assert!(vb.len() >= d_max);
 
     for d in 0..d_max as isize {
+        // Any snake found from here on would have an edit distance of at least `2 * d - 1`
+        if d > 0 && (2 * d - 1) as usize > max_cost {
+            return MiddleSnake::TooCostly;
+        }
+        if deadline.has_passed() {
+            return MiddleSnake::TimedOut;
+        }
+        progress.update(d as usize);
+
         // Forward path
         for k in (-d..=d).rev().step_by(2) {
             let mut x = if k == -d || (k != d && vf[k - 1] < vf[k + 1]) {
@@ -135,7 +260,7 @@ fn find_middle_snake<T: PartialEq>(
                         y_end: y,
                     };
                     // Edit distance to this snake is `2 * d - 1`
-                    return (2 * d - 1, snake);
+                    return MiddleSnake::Found(2 * d - 1, snake);
                 }
             }
         }
@@ -171,7 +296,10 @@ fn find_middle_snake<T: PartialEq>(
                         y_end: m - y0,
                     };
                     // Edit distance to this snake is `2 * d`
-                    return (2 * d, snake);
+                    if 2 * d as usize > max_cost {
+                        return MiddleSnake::TooCostly;
+                    }
+                    return MiddleSnake::Found(2 * d, snake);
                 }
             }
         }
@@ -182,13 +310,24 @@ fn find_middle_snake<T: PartialEq>(
     unreachable!("unable to find a middle snake");
 }
 
+// Returns `false` if more than `max_cost` inserted and deleted elements would be required.
+//
+// The length of the shortest edit script for both halves of a split sums up to the edit distance
+// found by the middle snake, so `max_cost` only ever needs to be checked once before recursing.
+//
+// Once `deadline` has passed, the remaining differences are described by deleting and inserting
+// everything that isn't part of a common prefix or suffix, which is valid but not minimal.
+#[allow(clippy::too_many_arguments)]
 fn conquer<'a, 'b, T: PartialEq>(
     mut old: Range<'a, [T]>,
     mut new: Range<'b, [T]>,
     vf: &mut V,
     vb: &mut V,
+    max_cost: usize,
+    deadline: Deadline,
+    progress: &mut Progress<'_>,
     solution: &mut Vec<DiffRange<'a, 'b, [T]>>,
-) {
+) -> bool {
     // Check for common prefix
     let common_prefix_len = old.common_prefix_len(new);
     if common_prefix_len > 0 {
@@ -197,6 +336,7 @@ fn conquer<'a, 'b, T: PartialEq>(
             new.slice(..common_prefix_len),
         );
         solution.push(common_prefix);
+        progress.advance(2 * common_prefix_len);
     }
 
     old = old.slice(common_prefix_len..old.len());
@@ -215,27 +355,127 @@ fn conquer<'a, 'b, T: PartialEq>(
         // Do nothing
     } else if old.is_empty() {
         // Inserts
+        if new.len() > max_cost {
+            return false;
+        }
         solution.push(DiffRange::Insert(new));
+        progress.advance(new.len());
     } else if new.is_empty() {
         // Deletes
+        if old.len() > max_cost {
+            return false;
+        }
         solution.push(DiffRange::Delete(old));
+        progress.advance(old.len());
     } else {
         // Divide & Conquer
-        let (_shortest_edit_script_len, snake) = find_middle_snake(old, new, vf, vb);
-
-        let (old_a, old_b) = old.split_at(snake.x_start);
-        let (new_a, new_b) = new.split_at(snake.y_start);
-
-        conquer(old_a, new_a, vf, vb, solution);
-        conquer(old_b, new_b, vf, vb, solution);
+        match find_middle_snake(old, new, vf, vb, max_cost, deadline, progress) {
+            MiddleSnake::Found(_shortest_edit_script_len, snake) => {
+                let (old_a, old_b) = old.split_at(snake.x_start);
+                let (new_a, new_b) = new.split_at(snake.y_start);
+
+                conquer(
+                    old_a,
+                    new_a,
+                    vf,
+                    vb,
+                    usize::MAX,
+                    deadline,
+                    progress,
+                    solution,
+                );
+                conquer(
+                    old_b,
+                    new_b,
+                    vf,
+                    vb,
+                    usize::MAX,
+                    deadline,
+                    progress,
+                    solution,
+                );
+            }
+            MiddleSnake::TooCostly => return false,
+            MiddleSnake::TimedOut => {
+                if old.len() + new.len() > max_cost {
+                    return false;
+                }
+                solution.push(DiffRange::Delete(old));
+                solution.push(DiffRange::Insert(new));
+                progress.advance(old.len() + new.len());
+            }
+        }
     }
 
     if common_suffix_len > 0 {
         solution.push(common_suffix);
+        progress.advance(2 * common_suffix_len);
     }
+
+    true
 }
 
-pub fn diff<'a, 'b, T: PartialEq>(old: &'a [T], new: &'b [T]) -> Vec<DiffRange<'a, 'b, [T]>> {
+// Finds the middle snake of a shortest edit script between `old` and `new`, returning the
+// length of the edit script along with the ranges of both inputs covered by the snake
+pub fn middle_snake<T: PartialEq>(
+    old: &[T],
+    new: &[T],
+) -> (usize, ops::Range<usize>, ops::Range<usize>) {
+    // `find_middle_snake` expects both inputs to have elements, otherwise the only edit script
+    // inserts or deletes everything and any point along it splits it in half
+    if old.is_empty() || new.is_empty() {
+        let (x, y) = (old.len() / 2, new.len() / 2);
+        return (old.len() + new.len(), x..x, y..y);
+    }
+
+    let max_d = max_d(old.len(), new.len());
+    let mut vf = V::new(max_d);
+    let mut vb = V::new(max_d);
+    match find_middle_snake(
+        Range::new(old, ..),
+        Range::new(new, ..),
+        &mut vf,
+        &mut vb,
+        usize::MAX,
+        Deadline::none(),
+        &mut Progress::none(),
+    ) {
+        MiddleSnake::Found(d, snake) => (
+            d as usize,
+            snake.x_start..snake.x_end,
+            snake.y_start..snake.y_end,
+        ),
+        MiddleSnake::TooCostly | MiddleSnake::TimedOut => {
+            unreachable!("the search has no bound or deadline")
+        }
+    }
+}
+
+pub fn diff<'a, 'b, T: PartialEq>(
+    old: &'a [T],
+    new: &'b [T],
+    deadline: Deadline,
+    progress: &mut Progress<'_>,
+) -> Vec<DiffRange<'a, 'b, [T]>> {
+    diff_bounded(old, new, usize::MAX, deadline, progress)
+        .expect("unbounded diff can't exceed its bound")
+}
+
+// Like `diff` but gives up, returning `None`, as soon as it's known that more than `max_cost`
+// elements would need to be inserted or deleted.
+pub fn diff_bounded<'a, 'b, T: PartialEq>(
+    old: &'a [T],
+    new: &'b [T],
+    max_cost: usize,
+    deadline: Deadline,
+    progress: &mut Progress<'_>,
+) -> Option<Vec<DiffRange<'a, 'b, [T]>>> {
+    // The difference in length is a lower bound on the number of edits
+    if old.len().abs_diff(new.len()) > max_cost {
+        return None;
+    }
+    progress.start(old.len() + new.len());
+
     let old_recs = Range::new(old, ..);
     let new_recs = Range::new(new, ..);
 
@@ -248,11 +488,201 @@ pub fn diff<'a, 'b, T: PartialEq>(old: &'a [T], new: &'b [T]) -> Vec<DiffRange<'
     let mut vf = V::new(max_d);
     let mut vb = V::new(max_d);
 
-    conquer(old_recs, new_recs, &mut vf, &mut vb, &mut solution);
+    if conquer(
+        old_recs,
+        new_recs,
+        &mut vf,
+        &mut vb,
+        max_cost,
+        deadline,
+        progress,
+        &mut solution,
+    ) {
+        progress.finish();
+        Some(solution)
+    } else {
+        None
+    }
+}
 
+// Finds a shortest edit script between two ranges of larger inputs, keeping their offsets into
+// those inputs, or a larger one once `deadline` has passed
+pub fn diff_ranges<'a, 'b, T: PartialEq>(
+    old: Range<'a, [T]>,
+    new: Range<'b, [T]>,
+    deadline: Deadline,
+) -> Vec<DiffRange<'a, 'b, [T]>> {
+    let mut solution = Vec::new();
+    let max_d = max_d(old.len(), new.len());
+    let mut vf = V::new(max_d);
+    let mut vb = V::new(max_d);
+    conquer(
+        old,
+        new,
+        &mut vf,
+        &mut vb,
+        usize::MAX,
+        deadline,
+        &mut Progress::none(),
+        &mut solution,
+    );
     solution
 }
 
+// Returns `true` if more than `max_cost` elements would need to be inserted or deleted, without
+// computing the diff itself. The search for the middle snake of the whole inputs already finds
+// the length of their shortest edit script, so there's no need to recurse into either half.
+pub fn exceeds_cost<T: PartialEq>(old: &[T], new: &[T], max_cost: usize) -> bool {
+    // The difference in length is a lower bound on the number of edits
+    if old.len().abs_diff(new.len()) > max_cost {
+        return true;
+    }
+
+    let mut old = Range::new(old, ..);
+    let mut new = Range::new(new, ..);
+    let common_prefix_len = old.common_prefix_len(new);
+    old = old.slice(common_prefix_len..);
+    new = new.slice(common_prefix_len..);
+    let common_suffix_len = old.common_suffix_len(new);
+    old = old.slice(..old.len() - common_suffix_len);
+    new = new.slice(..new.len() - common_suffix_len);
+
+    if old.is_empty() || new.is_empty() {
+        return old.len() + new.len() > max_cost;
+    }
+
+    let max_d = max_d(old.len(), new.len());
+    let mut vf = V::new(max_d);
+    let mut vb = V::new(max_d);
+    let progress = &mut Progress::none();
+    match find_middle_snake(
+        old,
+        new,
+        &mut vf,
+        &mut vb,
+        max_cost,
+        Deadline::none(),
+        progress,
+    ) {
+        MiddleSnake::Found(..) => false,
+        MiddleSnake::TooCostly => true,
+        MiddleSnake::TimedOut => unreachable!("the search has no deadline"),
+    }
+}
+
+// An element which only compares equal to another if neither of them is junk
+struct Anchor<'a, T> {
+    item: &'a T,
+    junk: bool,
+}
+
+impl<T: PartialEq> PartialEq for Anchor<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        !self.junk && !other.junk && self.item == other.item
+    }
+}
+
+fn anchors<'a, T>(items: &'a [T], junk: &[bool]) -> Vec<Anchor<'a, T>> {
+    items
+        .iter()
+        .zip(junk)
+        .map(|(item, &junk)| Anchor { item, junk })
+        .collect()
+}
+
+// Like `diff_bounded` but elements marked as junk can't anchor the diff, similarly to the junk
+// heuristic of Python's difflib. Only the elements which aren't junk are matched up, and junk
+// elements are then only kept where they extend those matches or the start or end of the
+// slices, instead of being aligned with junk in the middle of an unrelated change.
+pub fn diff_bounded_with_junk<'a, 'b, T: PartialEq>(
+    old: &'a [T],
+    old_junk: &[bool],
+    new: &'b [T],
+    new_junk: &[bool],
+    max_cost: usize,
+    deadline: Deadline,
+    progress: &mut Progress<'_>,
+) -> Option<Vec<DiffRange<'a, 'b, [T]>>> {
+    if old.len().abs_diff(new.len()) > max_cost {
+        return None;
+    }
+
+    // Only junk elements can be matched up besides the ones matched by the search for anchors,
+    // so it can give up once more than the junk elements on top of `max_cost` are left over
+    let junk_len = old_junk
+        .iter()
+        .chain(new_junk)
+        .filter(|&&junk| junk)
+        .count();
+    let (old_anchors, new_anchors) = (anchors(old, old_junk), anchors(new, new_junk));
+    let anchor_cost = max_cost.saturating_add(junk_len);
+    let mut matches: Vec<_> =
+        diff_bounded(&old_anchors, &new_anchors, anchor_cost, deadline, progress)?
+            .into_iter()
+            .filter_map(|diff_range| match diff_range {
+                DiffRange::Equal(old_match, new_match) => {
+                    Some((old_match.range(), new_match.range()))
+                }
+                DiffRange::Delete(_) | DiffRange::Insert(_) => None,
+            })
+            .collect();
+    matches.push((old.len()..old.len(), new.len()..new.len()));
+
+    let old_recs = Range::new(old, ..);
+    let new_recs = Range::new(new, ..);
+
+    let mut solution = Vec::new();
+    let mut cost = 0;
+    let (mut old_start, mut new_start) = (0, 0);
+    for (old_match, new_match) in matches {
+        let mut old_gap = old_recs.slice(old_start..old_match.start);
+        let mut new_gap = new_recs.slice(new_start..new_match.start);
+
+        let common_prefix_len = old_gap.common_prefix_len(new_gap);
+        if common_prefix_len > 0 {
+            solution.push(DiffRange::Equal(
+                old_gap.slice(..common_prefix_len),
+                new_gap.slice(..common_prefix_len),
+            ));
+        }
+        old_gap = old_gap.slice(common_prefix_len..);
+        new_gap = new_gap.slice(common_prefix_len..);
+
+        let common_suffix_len = old_gap.common_suffix_len(new_gap);
+        let common_suffix = DiffRange::Equal(
+            old_gap.slice(old_gap.len() - common_suffix_len..),
+            new_gap.slice(new_gap.len() - common_suffix_len..),
+        );
+        old_gap = old_gap.slice(..old_gap.len() - common_suffix_len);
+        new_gap = new_gap.slice(..new_gap.len() - common_suffix_len);
+
+        cost += old_gap.len() + new_gap.len();
+        if cost > max_cost {
+            return None;
+        }
+        if !old_gap.is_empty() {
+            solution.push(DiffRange::Delete(old_gap));
+        }
+        if !new_gap.is_empty() {
+            solution.push(DiffRange::Insert(new_gap));
+        }
+        if common_suffix_len > 0 {
+            solution.push(common_suffix);
+        }
+
+        old_start = old_match.end;
+        new_start = new_match.end;
+        if !old_match.is_empty() {
+            solution.push(DiffRange::Equal(
+                old_recs.slice(old_match),
+                new_recs.slice(new_match),
+            ));
+        }
+    }
+
+    Some(solution)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -264,6 +694,146 @@ mod tests {
         let max_d = max_d(a.len(), b.len());
         let mut vf = V::new(max_d);
         let mut vb = V::new(max_d);
-        find_middle_snake(a, b, &mut vf, &mut vb);
+        assert!(matches!(
+            find_middle_snake(
+                a,
+                b,
+                &mut vf,
+                &mut vb,
+                usize::MAX,
+                Deadline::none(),
+                &mut Progress::none()
+            ),
+            MiddleSnake::Found(5, _)
+        ));
+    }
+
+    #[test]
+    fn test_diff_bounded() {
+        let a = &b"ABCABBA"[..];
+        let b = &b"CBABAC"[..];
+        // The shortest edit script is 5 edits long
+        assert!(diff_bounded(a, b, 4, Deadline::none(), &mut Progress::none()).is_none());
+        assert!(diff_bounded(a, b, 5, Deadline::none(), &mut Progress::none()).is_some());
+        assert!(diff_bounded(a, b, usize::MAX, Deadline::none(), &mut Progress::none()).is_some());
+
+        assert!(diff_bounded(
+            &b"ABC"[..],
+            &b"ABC"[..],
+            0,
+            Deadline::none(),
+            &mut Progress::none()
+        )
+        .is_some());
+        assert!(diff_bounded(
+            &b""[..],
+            &b"ABC"[..],
+            2,
+            Deadline::none(),
+            &mut Progress::none()
+        )
+        .is_none());
+        assert!(diff_bounded(
+            &b"ABC"[..],
+            &b""[..],
+            3,
+            Deadline::none(),
+            &mut Progress::none()
+        )
+        .is_some());
+    }
+
+    #[test]
+    fn test_exceeds_cost() {
+        let a = &b"ABCABBA"[..];
+        let b = &b"CBABAC"[..];
+        assert!(exceeds_cost(a, b, 4));
+        assert!(!exceeds_cost(a, b, 5));
+        assert!(!exceeds_cost(a, b, usize::MAX));
+
+        // Even and odd edit distances, with common prefixes and suffixes
+        assert!(exceeds_cost(&b"xxABCyy"[..], &b"xxDEFyy"[..], 5));
+        assert!(!exceeds_cost(&b"xxABCyy"[..], &b"xxDEFyy"[..], 6));
+        assert!(exceeds_cost(&b"xxABCyy"[..], &b"xxDEyy"[..], 4));
+        assert!(!exceeds_cost(&b"xxABCyy"[..], &b"xxDEyy"[..], 5));
+
+        assert!(!exceeds_cost(&b"ABC"[..], &b"ABC"[..], 0));
+        assert!(exceeds_cost(&b""[..], &b"ABC"[..], 2));
+        assert!(!exceeds_cost(&b"ABC"[..], &b""[..], 3));
+        assert!(exceeds_cost(&b"ABC"[..], &b"AC"[..], 0));
+        assert!(!exceeds_cost(&b"ABC"[..], &b"AC"[..], 1));
+
+        // Agrees with the length of the edit script found by `diff`
+        let a = &b"the quick brown fox jumps over the lazy dog"[..];
+        let b = &b"a quick brown dog jumped over the lazy fox"[..];
+        let cost: usize = diff(a, b, Deadline::none(), &mut Progress::none())
+            .iter()
+            .map(|range| match range {
+                DiffRange::Equal(..) => 0,
+                DiffRange::Delete(range) | DiffRange::Insert(range) => range.len(),
+            })
+            .sum();
+        assert!(exceeds_cost(a, b, cost - 1));
+        assert!(!exceeds_cost(a, b, cost));
+    }
+
+    #[test]
+    fn test_diff_bounded_with_junk() {
+        let a = &b"x-y-z"[..];
+        let b = &b"u-v-z"[..];
+        let junk = |s: &[u8]| s.iter().map(|&c| c == b'-').collect::<Vec<_>>();
+        let cost = |solution: &[DiffRange<[u8]>]| {
+            solution
+                .iter()
+                .filter(|d| !matches!(d, DiffRange::Equal(..)))
+                .map(DiffRange::len)
+                .sum::<usize>()
+        };
+
+        // The `-` next to the matching `z` is kept, the one between changes isn't
+        let solution = diff_bounded_with_junk(
+            a,
+            &junk(a),
+            b,
+            &junk(b),
+            6,
+            Deadline::none(),
+            &mut Progress::none(),
+        );
+        assert_eq!(cost(&solution.unwrap()), 6);
+        assert!(diff_bounded_with_junk(
+            a,
+            &junk(a),
+            b,
+            &junk(b),
+            5,
+            Deadline::none(),
+            &mut Progress::none()
+        )
+        .is_none());
+        assert_eq!(
+            cost(&diff(a, b, Deadline::none(), &mut Progress::none())),
+            4
+        );
+
+        // The search for anchors gives up as soon as the bound is exceeded
+        #[cfg(feature = "std")]
+        {
+            let a: Vec<u32> = (0..2000).collect();
+            let b: Vec<u32> = (2000..4000).collect();
+            let mut reported = 0.0;
+            let mut report = |fraction| reported = fraction;
+            let solution = diff_bounded_with_junk(
+                &a,
+                &[false; 2000],
+                &b,
+                &[false; 2000],
+                10,
+                Deadline::none(),
+                &mut Progress::new(&mut report),
+            );
+            assert!(solution.is_none());
+            assert!(reported < 0.5);
+        }
     }
 }
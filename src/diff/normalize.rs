@@ -0,0 +1,28 @@
+use alloc::string::String;
+use unicode_normalization::UnicodeNormalization;
+
+/// A Unicode normalization form, see [`DiffOptions::set_normalize_unicode`]
+///
+/// [`DiffOptions::set_normalize_unicode`]: struct.DiffOptions.html#method.set_normalize_unicode
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum NormalizationForm {
+    /// Canonical decomposition followed by canonical composition
+    Nfc,
+    /// Canonical decomposition
+    Nfd,
+    /// Compatibility decomposition followed by canonical composition
+    Nfkc,
+    /// Compatibility decomposition
+    Nfkd,
+}
+
+impl NormalizationForm {
+    pub(super) fn normalize(self, s: &str) -> String {
+        match self {
+            NormalizationForm::Nfc => s.nfc().collect(),
+            NormalizationForm::Nfd => s.nfd().collect(),
+            NormalizationForm::Nfkc => s.nfkc().collect(),
+            NormalizationForm::Nfkd => s.nfkd().collect(),
+        }
+    }
+}
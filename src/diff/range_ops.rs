@@ -0,0 +1,174 @@
+use super::{DiffLines, DiffOptions};
+use crate::range::{DiffRange, SliceLike};
+use alloc::vec::Vec;
+use core::ops;
+
+/// The kind of a [`DiffOp`]
+///
+/// [`DiffOp`]: struct.DiffOp.html
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum DiffOpKind {
+    /// The ranges are equal in both inputs
+    Equal,
+    /// The `old` range was removed
+    Delete,
+    /// The `new` range was inserted
+    Insert,
+}
+
+/// A segment of the differences between two inputs, described by index ranges instead of slices
+///
+/// Both ranges are always present. The `new` range of a [`Delete`] and the `old` range of an
+/// [`Insert`] are empty and sit at the position in that input where the change happens, so the
+/// ops of a diff tile both inputs exactly, in order.
+///
+/// Indexes are byte offsets on char boundaries for [`diff_text_ops`], element indexes for
+/// [`diff_slice_ops`] and line indexes for [`DiffLines::ops`].
+///
+/// [`Delete`]: enum.DiffOpKind.html#variant.Delete
+/// [`Insert`]: enum.DiffOpKind.html#variant.Insert
+/// [`diff_text_ops`]: fn.diff_text_ops.html
+/// [`diff_slice_ops`]: fn.diff_slice_ops.html
+/// [`DiffLines::ops`]: struct.DiffLines.html#method.ops
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DiffOp {
+    /// The kind of the op
+    pub kind: DiffOpKind,
+    /// The range of the original input
+    pub old: ops::Range<usize>,
+    /// The range of the modified input
+    pub new: ops::Range<usize>,
+}
+
+impl DiffOp {
+    fn equal(old: ops::Range<usize>, new: ops::Range<usize>) -> Self {
+        Self {
+            kind: DiffOpKind::Equal,
+            old,
+            new,
+        }
+    }
+
+    fn delete(old: ops::Range<usize>, new_pos: usize) -> Self {
+        Self {
+            kind: DiffOpKind::Delete,
+            old,
+            new: new_pos..new_pos,
+        }
+    }
+
+    fn insert(old_pos: usize, new: ops::Range<usize>) -> Self {
+        Self {
+            kind: DiffOpKind::Insert,
+            old: old_pos..old_pos,
+            new,
+        }
+    }
+}
+
+fn to_ops<T: ?Sized + SliceLike>(solution: &[DiffRange<'_, '_, T>]) -> Vec<DiffOp> {
+    let mut old_pos = 0;
+    let mut new_pos = 0;
+    solution
+        .iter()
+        .map(|diff_range| {
+            let op = match *diff_range {
+                DiffRange::Equal(old, new) => DiffOp::equal(old.range(), new.range()),
+                DiffRange::Delete(old) => DiffOp::delete(old.range(), new_pos),
+                DiffRange::Insert(new) => DiffOp::insert(old_pos, new.range()),
+            };
+            old_pos = op.old.end;
+            new_pos = op.new.end;
+            op
+        })
+        .collect()
+}
+
+impl DiffOptions {
+    /// Compute the character-level differences between two texts as byte ranges, based on the
+    /// configured options
+    pub fn diff_text_ops(&self, original: &str, modified: &str) -> Vec<DiffOp> {
+        to_ops(&self.diff_str(original, modified))
+    }
+
+    /// Compute the differences between two slices as index ranges, based on the configured
+    /// options
+    pub fn diff_slice_ops<T: PartialEq>(&self, old: &[T], new: &[T]) -> Vec<DiffOp> {
+        to_ops(&self.diff_slice(old, new))
+    }
+}
+
+/// Compute the character-level differences between two texts as byte ranges
+///
+/// The ops describe the same diff as [`diff`]:
+///
+/// ```
+/// use diffy::{diff, diff_text_ops, Diff, DiffOpKind};
+///
+/// let original = "Tress of the Emerald Sea";
+/// let modified = "Tress of the Crimson Sea";
+///
+/// let diffs: Vec<_> = diff_text_ops(original, modified)
+///     .into_iter()
+///     .map(|op| match op.kind {
+///         DiffOpKind::Equal => Diff::Equal(&original[op.old]),
+///         DiffOpKind::Delete => Diff::Delete(&original[op.old]),
+///         DiffOpKind::Insert => Diff::Insert(&modified[op.new]),
+///     })
+///     .collect();
+/// assert_eq!(diffs, diff(original, modified));
+/// ```
+///
+/// [`diff`]: fn.diff.html
+pub fn diff_text_ops(original: &str, modified: &str) -> Vec<DiffOp> {
+    DiffOptions::default().diff_text_ops(original, modified)
+}
+
+/// Compute the differences between two slices as index ranges
+pub fn diff_slice_ops<T: PartialEq>(old: &[T], new: &[T]) -> Vec<DiffOp> {
+    DiffOptions::default().diff_slice_ops(old, new)
+}
+
+impl<T: ?Sized> DiffLines<'_, T> {
+    /// Returns the differences between the two texts as ranges of line indexes
+    ///
+    /// A changed region which both removes and adds lines is split into a [`Delete`] followed
+    /// by an [`Insert`].
+    ///
+    /// [`Delete`]: enum.DiffOpKind.html#variant.Delete
+    /// [`Insert`]: enum.DiffOpKind.html#variant.Insert
+    pub fn ops(&self) -> Vec<DiffOp> {
+        let mut ops = Vec::with_capacity(self.edit_script.len() * 3 + 1);
+        let mut old_pos = 0;
+        let mut new_pos = 0;
+        for edit in &self.edit_script {
+            if old_pos < edit.old.start {
+                ops.push(DiffOp::equal(
+                    old_pos..edit.old.start,
+                    new_pos..edit.new.start,
+                ));
+            }
+            if !edit.old.is_empty() {
+                ops.push(DiffOp::delete(edit.old.clone(), edit.new.start));
+            }
+            if !edit.new.is_empty() {
+                ops.push(DiffOp::insert(edit.old.end, edit.new.clone()));
+            }
+            old_pos = edit.old.end;
+            new_pos = edit.new.end;
+        }
+        if old_pos < self.old_lines.len() {
+            ops.push(DiffOp::equal(
+                old_pos..self.old_lines.len(),
+                new_pos..self.new_lines.len(),
+            ));
+        }
+        ops
+    }
+}
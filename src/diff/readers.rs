@@ -0,0 +1,677 @@
+use super::{
+    count_hunks, moves, DiffLines, DiffMetrics, DiffOptions, EditRange, EditScript, IgnoredEdit,
+    LineEdit, MovedBlock, RecordSep,
+};
+use crate::{
+    patch::Patch,
+    utils::{split_line_ending, Classifier},
+};
+use std::{
+    borrow::Cow,
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    io::{self, BufRead},
+    vec::Vec,
+};
+
+/// The line-by-line differences between two texts read by [`diff_readers`]
+///
+/// Unlike [`DiffLines`] this doesn't borrow the texts. Instead each distinct line is stored once,
+/// and both texts are kept as the ids of their lines. A [`DiffLines`] borrowing those lines can
+/// be built on demand with [`diff_lines`], unless only the hashes of the lines were kept.
+///
+/// [`diff_readers`]: fn.diff_readers.html
+/// [`DiffLines`]: struct.DiffLines.html
+/// [`diff_lines`]: #method.diff_lines
+#[derive(Clone, Debug)]
+pub struct OwnedDiffLines {
+    lines: Option<DistinctLines>,
+    old_ids: Vec<u32>,
+    new_ids: Vec<u32>,
+    old_len: usize,
+    new_len: usize,
+    edit_script: Vec<EditRange>,
+    merge_hunks: bool,
+    ignored_edits: Vec<IgnoredEdit>,
+    moves: Vec<MovedBlock>,
+}
+
+impl OwnedDiffLines {
+    /// Returns the number of lines in the original text
+    pub fn old_len(&self) -> usize {
+        self.old_len
+    }
+
+    /// Returns the number of lines in the modified text
+    pub fn new_len(&self) -> usize {
+        self.new_len
+    }
+
+    /// Returns `true` if the contents of the lines were kept, so that a patch can be produced
+    ///
+    /// See [`DiffOptions::set_hash_lines_only`].
+    ///
+    /// [`DiffOptions::set_hash_lines_only`]: struct.DiffOptions.html#method.set_hash_lines_only
+    pub fn has_lines(&self) -> bool {
+        self.lines.is_some()
+    }
+
+    /// Returns a [`DiffLines`] borrowing the stored lines, or `None` if only their hashes were
+    /// kept
+    ///
+    /// [`DiffLines`]: struct.DiffLines.html
+    pub fn diff_lines(&self) -> Option<DiffLines<'_, [u8]>> {
+        let lines = self.lines.as_ref()?;
+        let mut diff = DiffLines::new(
+            self.old_ids.iter().map(|&id| lines.get(id)).collect(),
+            self.new_ids.iter().map(|&id| lines.get(id)).collect(),
+            self.edit_script.clone(),
+        );
+        diff.merge_hunks = self.merge_hunks;
+        diff.ignored_edits = self.ignored_edits.clone();
+        diff.moves = self.moves.clone();
+        Some(diff)
+    }
+
+    /// Produce a Patch using `context_len` lines of context around each change, or `None` if
+    /// only the hashes of the lines were kept
+    pub fn to_patch(&self, context_len: usize) -> Option<Patch<'_, [u8]>> {
+        Some(self.diff_lines()?.to_patch(context_len))
+    }
+
+    /// Returns an owned view of the edit script, suitable for serialization
+    pub fn edit_script(&self) -> EditScript<Vec<u8>> {
+        EditScript {
+            old_len: self.old_len,
+            new_len: self.new_len,
+            edits: self
+                .edit_script
+                .iter()
+                .map(|edit| LineEdit {
+                    old: edit.old.clone(),
+                    new: edit.new.clone(),
+                })
+                .collect(),
+            old_lines: None,
+            new_lines: None,
+        }
+    }
+
+    /// Returns how similar the two texts are as a number between `0.0` and `1.0`
+    ///
+    /// See [`DiffLines::similarity`].
+    ///
+    /// [`DiffLines::similarity`]: struct.DiffLines.html#method.similarity
+    pub fn similarity(&self) -> f64 {
+        self.metrics().ratio()
+    }
+
+    /// Returns measures of how closely the two streams match
+    ///
+    /// See [`DiffLines::metrics`].
+    ///
+    /// [`DiffLines::metrics`]: struct.DiffLines.html#method.metrics
+    pub fn metrics(&self) -> DiffMetrics {
+        DiffMetrics::from_edit_script(self.old_len, self.new_len, &self.edit_script)
+    }
+
+    /// Returns the number of hunks a patch with `context_len` lines of context would contain
+    pub fn hunk_count(&self, context_len: usize) -> usize {
+        count_hunks(
+            self.old_len,
+            self.new_len,
+            &self.edit_script,
+            &self.ignored_edits,
+            self.merge_hunks,
+            context_len,
+        )
+    }
+
+    /// Returns the blocks of lines which were moved, if move detection was enabled
+    ///
+    /// See [`DiffLines::moves`].
+    ///
+    /// [`DiffLines::moves`]: struct.DiffLines.html#method.moves
+    pub fn moves(&self) -> &[MovedBlock] {
+        &self.moves
+    }
+}
+
+impl DiffOptions {
+    /// Set whether [`diff_readers`] should only keep a hash of each distinct line instead of its
+    /// contents
+    ///
+    /// This is enough to compute the differences and statistics about them, like
+    /// [`OwnedDiffLines::similarity`], but not to produce a patch. Lines are then considered
+    /// equal when their 64 bit hashes are, so there is a tiny chance that two different lines
+    /// are mistaken for each other.
+    ///
+    /// [`diff_readers`]: fn.diff_readers.html
+    /// [`OwnedDiffLines::similarity`]: struct.OwnedDiffLines.html#method.similarity
+    pub fn set_hash_lines_only(&mut self, hash_lines_only: bool) -> &mut Self {
+        self.hash_lines_only = hash_lines_only;
+        self
+    }
+}
+
+/// Compute the line-by-line differences between the texts read from two readers
+///
+/// Both readers are read line by line without holding either text in memory. Each distinct line
+/// is stored once, and the texts are diffed as sequences of line ids, so memory use grows with
+/// the length of the distinct lines plus a few bytes per line rather than with the length of both
+/// texts. With [`DiffOptions::set_hash_lines_only`] only a hash of each distinct line is kept.
+///
+/// The result is the same as the one of [`DiffOptions::diff_lines_bytes`] on the full texts,
+/// including the records chosen with [`DiffOptions::set_record_separator`].
+///
+/// ```
+/// use diffy::{diff_readers, DiffOptions};
+///
+/// let original = "one\ntwo\nthree\n";
+/// let modified = "one\n2\nthree\n";
+///
+/// let diff = diff_readers(original.as_bytes(), modified.as_bytes(), &DiffOptions::new())?;
+/// let expected = "\
+/// --- original
+/// +++ modified
+/// @@ -1,3 +1,3 @@
+///  one
+/// -two
+/// +2
+///  three
+/// ";
+/// assert_eq!(diff.to_patch(3).unwrap().to_bytes(), expected.as_bytes());
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// [`DiffOptions::set_hash_lines_only`]: struct.DiffOptions.html#method.set_hash_lines_only
+/// [`DiffOptions::set_record_separator`]: struct.DiffOptions.html#method.set_record_separator
+/// [`DiffOptions::diff_lines_bytes`]: struct.DiffOptions.html#method.diff_lines_bytes
+pub fn diff_readers(
+    old: impl BufRead,
+    new: impl BufRead,
+    options: &DiffOptions,
+) -> io::Result<OwnedDiffLines> {
+    let mut interner = Interner::new(!options.hash_lines_only);
+    let mut junk = Vec::new();
+    let (old_ids, old_squeezed) = read_lines(old, options, &mut interner, &mut junk)?;
+    let (new_ids, new_squeezed) = read_lines(new, options, &mut interner, &mut junk)?;
+
+    // Lines are stored by their contents, so lines which are only equal by their keys need to
+    // share an id before they're diffed
+    let (old_keys, new_keys) = match &interner.lines {
+        Some(lines) if !options.compares_whole_lines() => {
+            let keys: Vec<_> = (0..lines.len())
+                .map(|id| options.line_key(lines.get(id as u32)))
+                .collect();
+            let mut classifier = Classifier::<[u8]>::default();
+            let keys: Vec<_> = keys
+                .iter()
+                .map(|key| classifier.classify(key) as u32)
+                .collect();
+            (
+                Cow::Owned(old_ids.iter().map(|&id| keys[id as usize]).collect()),
+                Cow::Owned(new_ids.iter().map(|&id| keys[id as usize]).collect()),
+            )
+        }
+        _ => (Cow::Borrowed(&old_ids[..]), Cow::Borrowed(&new_ids[..])),
+    };
+
+    let junk = options.is_junk.map(|_| {
+        (
+            old_ids.iter().map(|&id| junk[id as usize]).collect(),
+            new_ids.iter().map(|&id| junk[id as usize]).collect(),
+        )
+    });
+    let squeezed = old_squeezed.zip(new_squeezed);
+    let (edit_script, squeezed_edits) = options
+        .diff_squeezed_line_ids(&old_keys, &new_keys, squeezed, junk, usize::MAX)
+        .expect("unbounded diff can't exceed its bound");
+    let moves = if options.detect_moves {
+        let widen = |keys: &[u32]| -> Vec<u64> { keys.iter().map(|&key| key.into()).collect() };
+        moves::detect(&widen(&old_keys), &widen(&new_keys), &edit_script)
+    } else {
+        Vec::new()
+    };
+    drop((old_keys, new_keys));
+
+    let (old_len, new_len) = (old_ids.len(), new_ids.len());
+    let lines = interner.lines;
+    let (old_ids, new_ids) = if lines.is_some() {
+        (old_ids, new_ids)
+    } else {
+        (Vec::new(), Vec::new())
+    };
+    // Telling comments apart needs the contents of the lines, so changes to comments are only
+    // ignored when they're kept
+    let ignored_edits = match &lines {
+        Some(lines) => {
+            let get = |ids: &[u32]| -> Vec<&[u8]> { ids.iter().map(|&id| lines.get(id)).collect() };
+            options.ignored_edits(&get(&old_ids), &get(&new_ids), &edit_script, squeezed_edits)
+        }
+        None => IgnoredEdit::squeezed(squeezed_edits),
+    };
+    Ok(OwnedDiffLines {
+        lines,
+        old_ids,
+        new_ids,
+        old_len,
+        new_len,
+        edit_script,
+        merge_hunks: options.merge_hunks,
+        ignored_edits,
+        moves,
+    })
+}
+
+// Reads the lines of a text, returning the ids they were interned as along with which lines are
+// left out when squeezing runs of blank lines, if they're squeezed. `junk` is extended with
+// whether each newly seen line is junk.
+fn read_lines(
+    reader: impl BufRead,
+    options: &DiffOptions,
+    interner: &mut Interner,
+    junk: &mut Vec<bool>,
+) -> io::Result<(Vec<u32>, Option<Vec<bool>>)> {
+    let mut reader = RecordReader::new(reader, options.record_separator);
+    let mut ids = Vec::new();
+    let mut blank = Vec::new();
+    let mut line = Vec::new();
+    loop {
+        if !reader.read(&mut line)? {
+            let squeezed = options
+                .squeeze_blank_lines
+                .then(|| super::squeezed_blank_lines(blank));
+            return Ok((ids, squeezed));
+        }
+
+        if options.squeeze_blank_lines {
+            blank.push(super::is_blank(&line[..]));
+        }
+
+        // Without their contents lines can only be told apart by the hash of their key
+        let id = if interner.lines.is_some() {
+            interner.intern(&line)?
+        } else {
+            interner.intern(&options.line_key(&line[..]))?
+        };
+        if let Some(is_junk) = options.is_junk {
+            if junk.len() < interner.len() {
+                junk.push(is_junk(&line));
+            }
+        }
+        ids.push(id);
+    }
+}
+
+// Reads the records of a text one at a time, splitting them like `split_records` does
+struct RecordReader<R> {
+    reader: R,
+    separator: RecordSep,
+    // The line read past the end of the previous paragraph, which starts the next one
+    pending: Vec<u8>,
+}
+
+impl<R: BufRead> RecordReader<R> {
+    fn new(reader: R, separator: RecordSep) -> Self {
+        Self {
+            reader,
+            separator,
+            pending: Vec::new(),
+        }
+    }
+
+    // Reads the next record into `record`, returning `false` once the text is exhausted
+    fn read(&mut self, record: &mut Vec<u8>) -> io::Result<bool> {
+        record.clear();
+        match self.separator {
+            RecordSep::Line => {
+                self.reader.read_until(b'\n', record)?;
+            }
+            RecordSep::Byte(byte) => {
+                self.reader.read_until(byte, record)?;
+            }
+            RecordSep::Str(separator) => match separator.as_bytes().last() {
+                // An empty separator never matches, so the whole text is a single record
+                None => {
+                    self.reader.read_to_end(record)?;
+                }
+                Some(&last) => {
+                    while !record.ends_with(separator.as_bytes()) {
+                        if self.reader.read_until(last, record)? == 0 {
+                            break;
+                        }
+                    }
+                }
+            },
+            RecordSep::Paragraph => {
+                // A record ends before the first non-blank line following a blank line
+                record.append(&mut self.pending);
+                let mut after_blank = false;
+                let mut line = Vec::new();
+                while self.reader.read_until(b'\n', &mut line)? != 0 {
+                    let blank = split_line_ending(&line[..]).0.is_empty();
+                    if after_blank && !blank {
+                        self.pending = line;
+                        break;
+                    }
+                    after_blank |= blank;
+                    record.append(&mut line);
+                }
+            }
+        }
+        Ok(!record.is_empty())
+    }
+}
+
+// The contents of distinct lines, stored one after the other
+#[derive(Clone, Debug, Default)]
+struct DistinctLines {
+    bytes: Vec<u8>,
+    // The offset each line ends at in `bytes`
+    ends: Vec<usize>,
+}
+
+impl DistinctLines {
+    fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    fn get(&self, id: u32) -> &[u8] {
+        let id = id as usize;
+        let start = id.checked_sub(1).map_or(0, |prev| self.ends[prev]);
+        &self.bytes[start..self.ends[id]]
+    }
+
+    fn push(&mut self, line: &[u8]) {
+        self.bytes.extend_from_slice(line);
+        self.ends.push(self.bytes.len());
+    }
+}
+
+// Assigns each distinct line an id, in the order they're first seen
+struct Interner {
+    hasher: RandomState,
+    // An open addressing hash table of `id + 1` for each line, where `0` is an empty slot
+    slots: Vec<u32>,
+    hashes: Vec<u64>,
+    // `None` if lines are only told apart by their hashes
+    lines: Option<DistinctLines>,
+}
+
+impl Interner {
+    fn new(keep_lines: bool) -> Self {
+        Self {
+            hasher: RandomState::new(),
+            slots: Vec::new(),
+            hashes: Vec::new(),
+            lines: keep_lines.then(DistinctLines::default),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    fn intern(&mut self, line: &[u8]) -> io::Result<u32> {
+        let mut hasher = self.hasher.build_hasher();
+        hasher.write(line);
+        let hash = hasher.finish();
+
+        // Keep the table at most half full
+        if self.hashes.len() >= self.slots.len() / 2 {
+            self.grow();
+        }
+        let mask = self.slots.len() - 1;
+        let mut slot = hash as usize & mask;
+        while let Some(id) = self.slots[slot].checked_sub(1) {
+            if self.hashes[id as usize] == hash
+                && self
+                    .lines
+                    .as_ref()
+                    .map_or(true, |lines| lines.get(id) == line)
+            {
+                return Ok(id);
+            }
+            slot = (slot + 1) & mask;
+        }
+
+        let id = u32::try_from(self.hashes.len())
+            .ok()
+            .filter(|&id| id != u32::MAX)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "too many distinct lines"))?;
+        self.slots[slot] = id + 1;
+        self.hashes.push(hash);
+        if let Some(lines) = &mut self.lines {
+            lines.push(line);
+        }
+        Ok(id)
+    }
+
+    fn grow(&mut self) {
+        let len = (self.slots.len() * 2).max(64);
+        let mask = len - 1;
+        self.slots = vec![0; len];
+        for (id, &hash) in self.hashes.iter().enumerate() {
+            let mut slot = hash as usize & mask;
+            while self.slots[slot] != 0 {
+                slot = (slot + 1) & mask;
+            }
+            self.slots[slot] = id as u32 + 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{format, io::Read, string::String};
+
+    // Checks that diffing two texts through readers gives the same result as diffing them
+    // directly
+    fn check(old: &str, new: &str, options: &DiffOptions) {
+        let expected = options.diff_lines_bytes(old.as_bytes(), new.as_bytes());
+        let diff = diff_readers(old.as_bytes(), new.as_bytes(), options).unwrap();
+        let lines = diff.diff_lines().unwrap();
+        assert_eq!(lines.old_lines(), expected.old_lines());
+        assert_eq!(lines.new_lines(), expected.new_lines());
+        assert_eq!(diff.edit_script(), expected.edit_script());
+        assert_eq!(diff.moves(), expected.moves());
+        assert_eq!(diff.similarity(), expected.similarity());
+        for context_len in [0, 1, 3] {
+            assert_eq!(
+                diff.to_patch(context_len).unwrap().to_bytes(),
+                expected.to_patch(context_len).to_bytes()
+            );
+            assert_eq!(
+                diff.hunk_count(context_len),
+                expected.hunk_count(context_len)
+            );
+        }
+    }
+
+    #[test]
+    fn matches_diff_lines() {
+        let cases = [
+            ("", ""),
+            ("", "a\n"),
+            ("a\nb\nc\n", ""),
+            ("a\nb\nc\n", "a\nb\nc\n"),
+            ("a\nb\nc\n", "a\nB\nc\n"),
+            ("a\nb\nc", "a\nb\nc\n"),
+            ("a\nb\nc\nd\ne\nf\ng\nh\n", "x\nb\nc\nd\ne\nf\ng\nH\n"),
+            ("a\n\n\n\nb\n  c\n", "a\n\nB\n\n\nb\nC\n"),
+            ("a\nb\nc\nd\ne\nf\ng\n", "e\nf\ng\na\nb\nc\nd\n"),
+            ("{\na\n}\n{\nb\n}\n", "{\na\n}\n{\nc\n}\n{\nb\n}\n"),
+            ("# x\na\nb\nc\n# y\n", "# X\nA\nb\nC\n# z\n"),
+            ("# 2023\nk\n\nv\n", "# 2024\nk\nv\n"),
+        ];
+
+        let mut options = [
+            DiffOptions::new(),
+            DiffOptions::new(),
+            DiffOptions::new(),
+            DiffOptions::new(),
+        ];
+        options[1]
+            .set_ignore_case(true)
+            .set_ignore_indentation(true)
+            .set_squeeze_blank_lines(true)
+            .set_merge_hunks(false);
+        options[2]
+            .set_detect_moves(true)
+            .set_is_junk(Some(|line: &[u8]| {
+                line.iter().filter(|b| !b.is_ascii_whitespace()).count() <= 1
+            }));
+        options[3]
+            .set_ignore_comment_changes(true)
+            .set_merge_hunks(false);
+        for options in &options {
+            for (old, new) in cases {
+                check(old, new, options);
+                check(new, old, options);
+            }
+        }
+    }
+
+    #[test]
+    fn record_separators() {
+        let cases = [
+            ("a;b;c;", "a;B;c;d"),
+            ("a\0b\0c", "a\0c\0"),
+            ("a::b::c:", "a::c::b::"),
+            ("\n\na\nb\n\nc\n\n\nd\n", "a\nb\n\nC\nd\n\n\n"),
+        ];
+        for separator in [
+            RecordSep::Str(";"),
+            RecordSep::Byte(0),
+            RecordSep::Str("::"),
+            RecordSep::Str(""),
+            RecordSep::Paragraph,
+        ] {
+            let mut options = DiffOptions::new();
+            options.set_record_separator(separator);
+            for (old, new) in cases {
+                check(old, new, &options);
+                check(new, old, &options);
+            }
+        }
+
+        // Separators may straddle the reader's buffer
+        let text = "a::b:c::::d";
+        let mut reader = RecordReader::new(
+            io::BufReader::with_capacity(1, text.as_bytes()),
+            RecordSep::Str("::"),
+        );
+        let mut records = Vec::new();
+        let mut record = Vec::new();
+        while reader.read(&mut record).unwrap() {
+            records.push(String::from_utf8(record.clone()).unwrap());
+        }
+        assert_eq!(records, ["a::", "b:c::", "::", "d"]);
+    }
+
+    #[test]
+    fn hash_lines_only() {
+        let old = "a\nb\nc\nd\n";
+        let new = "a\nB\nc\nd\ne\n";
+        let mut options = DiffOptions::new();
+        options.set_hash_lines_only(true).set_ignore_case(true);
+
+        let diff = diff_readers(old.as_bytes(), new.as_bytes(), &options).unwrap();
+        let expected = options.diff_lines_bytes(old.as_bytes(), new.as_bytes());
+        assert!(!diff.has_lines() && diff.diff_lines().is_none());
+        assert_eq!((diff.old_len(), diff.new_len()), (4, 5));
+        assert_eq!(diff.edit_script(), expected.edit_script());
+        assert_eq!(diff.similarity(), expected.similarity());
+    }
+
+    #[test]
+    fn interns_lines() {
+        let mut interner = Interner::new(true);
+        let lines: Vec<_> = (0..1000).map(|i| format!("line {}\n", i % 300)).collect();
+        let ids: Vec<_> = lines
+            .iter()
+            .map(|line| interner.intern(line.as_bytes()).unwrap())
+            .collect();
+        assert_eq!(interner.len(), 300);
+        let stored = interner.lines.as_ref().unwrap();
+        for (line, &id) in lines.iter().zip(&ids) {
+            assert_eq!(id as usize, lines.iter().position(|l| l == line).unwrap());
+            assert_eq!(stored.get(id), line.as_bytes());
+        }
+    }
+
+    // A stream of about `len` bytes of lines picked from a small vocabulary, where every
+    // `every`th line is swapped for a line which only appears in this stream
+    struct Lines {
+        state: u64,
+        line: usize,
+        every: usize,
+        tag: &'static str,
+        remaining: usize,
+        buf: Vec<u8>,
+        pos: usize,
+    }
+
+    impl Lines {
+        fn new(len: usize, every: usize, tag: &'static str) -> Self {
+            Self {
+                state: 0x2545_f491_4f6c_dd1d,
+                line: 0,
+                every,
+                tag,
+                remaining: len,
+                buf: Vec::new(),
+                pos: 0,
+            }
+        }
+    }
+
+    impl Read for Lines {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.pos == self.buf.len() {
+                if self.remaining == 0 {
+                    return Ok(0);
+                }
+                self.state ^= self.state << 13;
+                self.state ^= self.state >> 7;
+                self.state ^= self.state << 17;
+                self.line += 1;
+                let line = if self.line % self.every == 0 {
+                    format!("{} {}\n", self.tag, self.line)
+                } else {
+                    let word = self.state % 50_000;
+                    format!("{:>8} {:016x} {:04}\n", word, word * 0x9e37_79b9, word % 97)
+                };
+                self.remaining = self.remaining.saturating_sub(line.len());
+                self.buf = line.into_bytes();
+                self.pos = 0;
+            }
+            let len = buf.len().min(self.buf.len() - self.pos);
+            buf[..len].copy_from_slice(&self.buf[self.pos..self.pos + len]);
+            self.pos += len;
+            Ok(len)
+        }
+    }
+
+    #[test]
+    #[ignore = "reads about 200MB"]
+    fn large_streams() {
+        let len = 100 << 20;
+        let old = io::BufReader::new(Lines::new(len, 50_000, "old"));
+        let new = io::BufReader::new(Lines::new(len, 40_000, "new"));
+        let diff = diff_readers(old, new, &DiffOptions::new()).unwrap();
+
+        let distinct = diff.lines.as_ref().unwrap();
+        assert!(distinct.bytes.len() < 2 << 20, "{}", distinct.bytes.len());
+
+        // Each tagged line changes one line of the other stream, apart from the few lines at the
+        // end of the longer stream
+        let changed: usize = diff.edit_script.iter().map(|edit| edit.old.len()).sum();
+        let tagged = diff.old_len() / 50_000 + diff.new_len() / 40_000;
+        assert!(changed <= tagged + 4, "{} > {}", changed, tagged);
+        let patch = diff.to_patch(0).unwrap();
+        assert!(patch.hunks().len() <= tagged + 1);
+    }
+}
@@ -0,0 +1,339 @@
+use super::{myers, DiffLines, DiffOp, DiffOpKind, EditRange};
+use crate::{
+    range::DiffRange,
+    utils::{Classifier, LineIter, Text},
+};
+use alloc::{vec, vec::Vec};
+
+// The most pairs of lines whose similarity is computed to find the best pairing in a change
+// which deletes and inserts a different number of lines
+const MAX_PAIRING_CANDIDATES: usize = 1024;
+
+// The most chars inserted or deleted to turn one line into another when computing their
+// similarity exactly
+const MAX_LINE_SIMILARITY_COST: usize = 256;
+
+/// A text whose lines have been classified once so it can be compared against many candidates
+///
+/// Comparing a single text against a large number of others with [`DiffLines::similarity`]
+/// would classify the reference text's lines over and over again. A `Reference` does that work
+/// up front and only needs to classify the lines of each candidate.
+///
+/// ```
+/// use diffy::Reference;
+///
+/// let reference = Reference::new("a\nb\nc\nd\n");
+/// let candidates = ["a\nb\nc\nd\n", "a\nx\nc\ny\n", "w\nx\ny\nz\n"];
+/// let scores: Vec<f64> = candidates.iter().map(|c| reference.similarity_to(c)).collect();
+/// assert_eq!(scores, [1.0, 0.5, 0.0]);
+/// ```
+///
+/// [`DiffLines::similarity`]: struct.DiffLines.html#method.similarity
+pub struct Reference<'a, T: ?Sized> {
+    classifier: Classifier<'a, T>,
+    ids: Vec<u64>,
+}
+
+impl<'a> Reference<'a, str> {
+    /// Classify the lines of `text` for later comparisons
+    pub fn new(text: &'a str) -> Self {
+        Self::classify(text)
+    }
+
+    /// Returns the similarity between the reference text and `candidate`
+    ///
+    /// See [`DiffLines::similarity`] for how the score is computed.
+    ///
+    /// [`DiffLines::similarity`]: struct.DiffLines.html#method.similarity
+    pub fn similarity_to(&self, candidate: &str) -> f64 {
+        self.similarity(candidate)
+    }
+}
+
+impl<'a> Reference<'a, [u8]> {
+    /// Classify the lines of potentially non-utf8 `text` for later comparisons
+    pub fn new_bytes(text: &'a [u8]) -> Self {
+        Self::classify(text)
+    }
+
+    /// Returns the similarity between the reference text and a potentially non-utf8 `candidate`
+    ///
+    /// See [`DiffLines::similarity`] for how the score is computed.
+    ///
+    /// [`DiffLines::similarity`]: struct.DiffLines.html#method.similarity
+    pub fn similarity_to(&self, candidate: &[u8]) -> f64 {
+        self.similarity(candidate)
+    }
+}
+
+impl<'a, T: ?Sized + Text> Reference<'a, T> {
+    fn classify(text: &'a T) -> Self {
+        let mut classifier = Classifier::default();
+        let (_, ids) = classifier.classify_lines(text);
+        Self { classifier, ids }
+    }
+
+    fn similarity(&self, candidate: &T) -> f64 {
+        // Lines which don't appear in the reference can never be matched so they can all share
+        // an id which isn't used by any of the reference's lines
+        let unmatched = self.ids.len() as u64;
+        let candidate_ids: Vec<u64> = LineIter::new(candidate)
+            .map(|line| self.classifier.get(line).unwrap_or(unmatched))
+            .collect();
+
+        let matching = myers::diff(
+            &self.ids,
+            &candidate_ids,
+            myers::Deadline::none(),
+            &mut myers::Progress::none(),
+        )
+        .iter()
+        .map(|range| match range {
+            DiffRange::Equal(range, _) => range.len(),
+            DiffRange::Delete(_) | DiffRange::Insert(_) => 0,
+        })
+        .sum();
+
+        ratio(matching, self.ids.len(), candidate_ids.len())
+    }
+}
+
+impl<T: ?Sized> core::fmt::Debug for Reference<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Reference")
+            .field("lines", &self.ids.len())
+            .finish()
+    }
+}
+
+impl<'a> DiffLines<'a, str> {
+    /// Returns each changed line paired with the line which replaced it, along with how similar
+    /// the two are as a number between `0.0` and `1.0`
+    ///
+    /// Within each change the deleted lines are paired with the inserted lines in order. When
+    /// a change deletes as many lines as it inserts they're paired up one by one. Otherwise
+    /// each line is paired with at most one line of the other side so that the total
+    /// similarity of the pairs is as high as possible, and lines which aren't similar to any
+    /// line left to pair them with aren't returned. Changes which would take more than 1024
+    /// comparisons to pair this way are paired up one by one as well, leaving the extra lines
+    /// of the longer side unpaired.
+    ///
+    /// The similarity of two lines is computed like [`similarity`] but over their chars
+    /// instead of their lines, ignoring line endings. Lines which would take more than 256
+    /// inserted or deleted chars to turn into each other only count the chars of their common
+    /// prefix and suffix as matching, so comparing long lines stays fast. A renderer can use it
+    /// to decide whether to highlight the changes within a line or show it as replaced entirely.
+    ///
+    /// ```
+    /// use diffy::diff_lines;
+    ///
+    /// let original = "let x = 1;\nunchanged\n";
+    /// let modified = "completely different\nlet x = 2;\nunchanged\n";
+    ///
+    /// let pairs = diff_lines(original, modified).changed_line_pairs();
+    /// assert_eq!(pairs.len(), 1);
+    /// let (old, new, similarity) = pairs[0];
+    /// assert_eq!((old, new), ("let x = 1;\n", "let x = 2;\n"));
+    /// assert_eq!(similarity, 0.9);
+    /// ```
+    ///
+    /// [`similarity`]: #method.similarity
+    pub fn changed_line_pairs(&self) -> Vec<(&'a str, &'a str, f64)> {
+        let mut pairs = Vec::new();
+        for edit in &self.edit_script {
+            let old = &self.old_lines[edit.old.clone()];
+            let new = &self.new_lines[edit.new.clone()];
+            if old.len() == new.len() || old.len() * new.len() > MAX_PAIRING_CANDIDATES {
+                pairs.extend(
+                    old.iter()
+                        .zip(new)
+                        .map(|(&old, &new)| (old, new, line_similarity(old, new))),
+                );
+            } else {
+                pairs.extend(best_pairs(old, new));
+            }
+        }
+        pairs
+    }
+}
+
+// Pairs up the lines of both sides of a change in order, maximizing the total similarity of the
+// pairs
+fn best_pairs<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(&'a str, &'a str, f64)> {
+    let similarities: Vec<Vec<f64>> = old
+        .iter()
+        .map(|old| new.iter().map(|new| line_similarity(old, new)).collect())
+        .collect();
+
+    // `best[i][j]` is the highest total similarity of pairing the first `i` old lines with the
+    // first `j` new lines
+    let mut best = vec![vec![0.0; new.len() + 1]; old.len() + 1];
+    for i in 1..=old.len() {
+        for j in 1..=new.len() {
+            let paired = best[i - 1][j - 1] + similarities[i - 1][j - 1];
+            best[i][j] = paired.max(best[i - 1][j]).max(best[i][j - 1]);
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (old.len(), new.len());
+    while i > 0 && j > 0 {
+        let similarity = similarities[i - 1][j - 1];
+        if best[i][j] == best[i - 1][j] {
+            i -= 1;
+        } else if best[i][j] == best[i][j - 1] {
+            j -= 1;
+        } else {
+            pairs.push((old[i - 1], new[j - 1], similarity));
+            i -= 1;
+            j -= 1;
+        }
+    }
+    pairs.reverse();
+    pairs
+}
+
+// Computes the similarity of two lines by the chars they have in common, or only those of their
+// common prefix and suffix when they differ in too many chars to diff them quickly
+fn line_similarity(old: &str, new: &str) -> f64 {
+    let trim = |line: &str| -> Vec<char> {
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        line.strip_suffix('\r').unwrap_or(line).chars().collect()
+    };
+    let (old, new) = (trim(old), trim(new));
+
+    let matching = match myers::diff_bounded(
+        &old,
+        &new,
+        MAX_LINE_SIMILARITY_COST,
+        myers::Deadline::none(),
+        &mut myers::Progress::none(),
+    ) {
+        Some(solution) => solution
+            .iter()
+            .map(|range| match range {
+                DiffRange::Equal(range, _) => range.len(),
+                DiffRange::Delete(_) | DiffRange::Insert(_) => 0,
+            })
+            .sum(),
+        None => {
+            let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+            let suffix = old[prefix..]
+                .iter()
+                .rev()
+                .zip(new[prefix..].iter().rev())
+                .take_while(|(a, b)| a == b)
+                .count();
+            prefix + suffix
+        }
+    };
+    ratio(matching, old.len(), new.len())
+}
+
+/// Measures of how closely two inputs match, see [`DiffLines::metrics`]
+///
+/// Every count is in the units of the diff it was computed from: lines for [`DiffLines`], slice
+/// elements for [`diff_slice_ops`] and bytes for [`diff_text_ops`]. Lines are counted by their
+/// position, so a line which occurs several times counts once per unchanged occurrence, not
+/// once per distinct line.
+///
+/// [`DiffLines`]: struct.DiffLines.html
+/// [`DiffLines::metrics`]: struct.DiffLines.html#method.metrics
+/// [`diff_slice_ops`]: fn.diff_slice_ops.html
+/// [`diff_text_ops`]: fn.diff_text_ops.html
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DiffMetrics {
+    /// The number of unchanged elements, which is the same in both inputs
+    pub matched: usize,
+    /// The length of the original input
+    pub old_len: usize,
+    /// The length of the modified input
+    pub new_len: usize,
+    /// The length of the longest run of consecutive unchanged elements
+    pub longest_match: usize,
+}
+
+impl DiffMetrics {
+    /// Compute the metrics of the diff described by `ops`
+    ///
+    /// The ops can come from [`diff_slice_ops`], [`diff_text_ops`] or [`DiffLines::ops`], and
+    /// must tile both inputs in order like theirs do. Neighbouring [`Equal`] ops count as a
+    /// single run of unchanged elements.
+    ///
+    /// ```
+    /// use diffy::{diff_slice_ops, DiffMetrics};
+    ///
+    /// let metrics = DiffMetrics::from_ops(&diff_slice_ops(&[1, 2, 3, 4, 5], &[1, 2, 9, 4]));
+    /// assert_eq!(metrics.matched, 3);
+    /// assert_eq!((metrics.old_len, metrics.new_len), (5, 4));
+    /// assert_eq!(metrics.longest_match, 2);
+    /// ```
+    ///
+    /// [`diff_slice_ops`]: fn.diff_slice_ops.html
+    /// [`diff_text_ops`]: fn.diff_text_ops.html
+    /// [`DiffLines::ops`]: struct.DiffLines.html#method.ops
+    /// [`Equal`]: enum.DiffOpKind.html#variant.Equal
+    pub fn from_ops(ops: &[DiffOp]) -> Self {
+        let mut metrics = Self::default();
+        let mut run = 0;
+        for op in ops {
+            metrics.old_len = op.old.end;
+            metrics.new_len = op.new.end;
+            match op.kind {
+                DiffOpKind::Equal => {
+                    metrics.matched += op.old.len();
+                    run += op.old.len();
+                    metrics.longest_match = metrics.longest_match.max(run);
+                }
+                // Empty ops don't separate their neighbours
+                _ if op.old.is_empty() && op.new.is_empty() => {}
+                DiffOpKind::Delete | DiffOpKind::Insert => run = 0,
+            }
+        }
+        metrics
+    }
+
+    // Computes the metrics of a line diff from its edit script
+    pub(super) fn from_edit_script(
+        old_len: usize,
+        new_len: usize,
+        edit_script: &[EditRange],
+    ) -> Self {
+        let mut metrics = Self {
+            matched: old_len,
+            old_len,
+            new_len,
+            longest_match: 0,
+        };
+        let mut old_pos = 0;
+        for edit in edit_script {
+            metrics.matched -= edit.old.len();
+            metrics.longest_match = metrics.longest_match.max(edit.old.start - old_pos);
+            old_pos = edit.old.end;
+        }
+        metrics.longest_match = metrics.longest_match.max(old_len - old_pos);
+        metrics
+    }
+
+    /// Returns how similar the inputs are as a number between `0.0` and `1.0`
+    ///
+    /// This is twice the number of matched elements divided by the total length of both
+    /// inputs, the same score as [`DiffLines::similarity`]. Two empty inputs are considered
+    /// identical.
+    ///
+    /// [`DiffLines::similarity`]: struct.DiffLines.html#method.similarity
+    pub fn ratio(&self) -> f64 {
+        ratio(self.matched, self.old_len, self.new_len)
+    }
+}
+
+/// Computes `2 * matching / (old_len + new_len)`, treating two empty texts as identical
+pub(super) fn ratio(matching: usize, old_len: usize, new_len: usize) -> f64 {
+    let total = old_len + new_len;
+    if total == 0 {
+        1.0
+    } else {
+        (2 * matching) as f64 / total as f64
+    }
+}
@@ -0,0 +1,150 @@
+//! Diff a region of each text rather than the whole texts
+
+use super::{DiffLines, DiffOptions};
+use crate::patch::{Hunk, Patch};
+use core::{fmt, ops};
+
+/// An error returned when a line range passed to [`DiffOptions::diff_lines_range`] doesn't fit
+/// within its text
+///
+/// [`DiffOptions::diff_lines_range`]: struct.DiffOptions.html#method.diff_lines_range
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineRangeError {
+    range: ops::Range<usize>,
+    line_count: usize,
+}
+
+impl LineRangeError {
+    /// Returns the invalid range
+    pub fn range(&self) -> ops::Range<usize> {
+        self.range.clone()
+    }
+
+    /// Returns the number of lines in the text the range was meant for
+    pub fn line_count(&self) -> usize {
+        self.line_count
+    }
+}
+
+impl fmt::Display for LineRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line range {}..{} is out of bounds for a text of {} lines",
+            self.range.start, self.range.end, self.line_count
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LineRangeError {}
+
+impl DiffOptions {
+    /// Produce a Patch between the lines `old_range` of `original` and the lines `new_range` of
+    /// `modified`, based on the configured options
+    ///
+    /// The ranges hold 0-based line indexes. Only the lines within them are compared, which
+    /// avoids diffing two huge texts when only one region of them matters, but the hunks of the
+    /// patch are numbered by their lines' positions in the whole texts. Context is only taken
+    /// from the lines within the ranges.
+    ///
+    /// ```
+    /// use diffy::DiffOptions;
+    ///
+    /// let original = "a\nb\nc\nd\ne\nf\n";
+    /// let modified = "a\nb\nc\nD\ne\nF\n";
+    ///
+    /// // Only the change to `d` is within the ranges
+    /// let patch = DiffOptions::new()
+    ///     .set_context_len(1)
+    ///     .diff_lines_range(original, modified, 2..5, 2..5)
+    ///     .unwrap();
+    /// let expected = "\
+    /// --- original
+    /// +++ modified
+    /// @@ -3,3 +3,3 @@
+    ///  c
+    /// -d
+    /// +D
+    ///  e
+    /// ";
+    /// assert_eq!(patch.to_string(), expected);
+    ///
+    /// assert!(DiffOptions::new()
+    ///     .diff_lines_range(original, modified, 2..7, 2..5)
+    ///     .is_err());
+    /// ```
+    pub fn diff_lines_range<'a>(
+        &self,
+        original: &'a str,
+        modified: &'a str,
+        old_range: ops::Range<usize>,
+        new_range: ops::Range<usize>,
+    ) -> Result<Patch<'a, str>, LineRangeError> {
+        let (old_lines, old_ids, new_lines, new_ids) = self.classify_lines(original, modified);
+        check_range(&old_range, old_lines.len())?;
+        check_range(&new_range, new_lines.len())?;
+
+        let (old_lines, new_lines) = (&old_lines[old_range.clone()], &new_lines[new_range.clone()]);
+        let (edit_script, squeezed_edits) = self
+            .line_edit_script(
+                old_lines,
+                new_lines,
+                &old_ids[old_range.clone()],
+                &new_ids[new_range.clone()],
+                usize::MAX,
+            )
+            .expect("unbounded diff can't exceed its bound");
+        let mut diff = DiffLines::new(old_lines.to_vec(), new_lines.to_vec(), edit_script);
+        diff.merge_hunks = self.merge_hunks;
+        diff.ignored_edits = self.ignored_edits(
+            &diff.old_lines,
+            &diff.new_lines,
+            &diff.edit_script,
+            squeezed_edits,
+        );
+
+        // The hunks are numbered relative to the start of the ranges, so move them back to
+        // where the ranges are in the whole texts
+        let (pre_context_len, post_context_len) = self.context_lens();
+        let hunks = diff
+            .hunks(pre_context_len, post_context_len)
+            .into_iter()
+            .map(|hunk| {
+                Hunk::new(
+                    hunk.old_range().shift(old_range.start as isize),
+                    hunk.new_range().shift(new_range.start as isize),
+                    None,
+                    hunk.lines().to_vec(),
+                )
+            })
+            .collect();
+        Ok(Patch::new(Some("original"), Some("modified"), hunks))
+    }
+}
+
+fn check_range(range: &ops::Range<usize>, line_count: usize) -> Result<(), LineRangeError> {
+    if range.start <= range.end && range.end <= line_count {
+        Ok(())
+    } else {
+        Err(LineRangeError {
+            range: range.clone(),
+            line_count,
+        })
+    }
+}
+
+/// Produce a Patch between the lines `old_range` of `original` and the lines `new_range` of
+/// `modified`
+///
+/// See [`DiffOptions::diff_lines_range`] for details.
+///
+/// [`DiffOptions::diff_lines_range`]: struct.DiffOptions.html#method.diff_lines_range
+pub fn diff_lines_range<'a>(
+    original: &'a str,
+    modified: &'a str,
+    old_range: ops::Range<usize>,
+    new_range: ops::Range<usize>,
+) -> Result<Patch<'a, str>, LineRangeError> {
+    DiffOptions::default().diff_lines_range(original, modified, old_range, new_range)
+}
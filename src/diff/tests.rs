@@ -2,8 +2,9 @@ use super::*;
 use crate::{
     apply::apply,
     diff::{Diff, DiffRange},
-    patch::Patch,
+    patch::{HunkRange, Patch},
     range::Range,
+    utils::LineIter,
 };
 
 // Helper macros are based off of the ones used in [dissimilar](https://docs.rs/dissimilar)
@@ -196,6 +197,34 @@ fn test_unicode() {
 
     let d = diff(snowman, comet);
     assert_eq!(d, vec![Diff::Delete(snowman), Diff::Insert(comet)]);
+
+    // Chars of different widths are diffed as whole chars
+    assert_eq!(diff("c", "é"), [Diff::Delete("c"), Diff::Insert("é")]);
+    assert_eq!(
+        diff("ab", "éb"),
+        [Diff::Delete("a"), Diff::Insert("é"), Diff::Equal("b")]
+    );
+    assert_eq!(
+        diff("a\u{2603}b", "a\u{1f600}b\u{2603}"),
+        [
+            Diff::Equal("a"),
+            Diff::Delete("\u{2603}"),
+            Diff::Insert("\u{1f600}"),
+            Diff::Equal("b"),
+            Diff::Insert("\u{2603}"),
+        ]
+    );
+    let mut options = DiffOptions::new();
+    options.set_minimal(true);
+    assert_eq!(
+        options.diff("日本語のテキスト", "日本のテキスト!"),
+        [
+            Diff::Equal("日本"),
+            Diff::Delete("語"),
+            Diff::Equal("のテキスト"),
+            Diff::Insert("!"),
+        ]
+    );
 }
 
 #[test]
@@ -212,10 +241,17 @@ fn test_compact() {
         "No change case",
     );
 
-    // TODO implement equality compaction
-    // let mut solution = diff_range_list![Equal("a"), Equal("b"), Equal("c")];
-    // cleanup::compact(&mut solution);
-    // assert_diff_range!([Equal("abc")], solution, "Compact equalities");
+    let mut solution = diff_range_list![Equal("a"), Equal("b"), Equal("c")];
+    cleanup::compact(&mut solution);
+    assert_diff_range!([Equal("abc")], solution, "Compact equalities");
+
+    let mut solution = diff_range_list![Equal("a"), Delete("b"), Equal("c"), Equal("d")];
+    cleanup::compact(&mut solution);
+    assert_diff_range!(
+        [Equal("a"), Delete("b"), Equal("cd")],
+        solution,
+        "Compact trailing equalities"
+    );
 
     let mut solution = diff_range_list![Delete("a"), Delete("b"), Delete("c")];
     cleanup::compact(&mut solution);
@@ -323,6 +359,25 @@ fn test_compact() {
     );
 }
 
+#[test]
+fn no_adjacent_equalities() {
+    // Myers can split a run of equal elements across both halves of its divide-and-conquer
+    for (old, new) in [
+        ("ababbba", "baabaaa"),
+        ("ababbab", "baabaaa"),
+        ("abababb", "baabaaa"),
+    ] {
+        let solution = DiffOptions::default().diff_slice(old.as_bytes(), new.as_bytes());
+        assert!(
+            !solution
+                .windows(2)
+                .any(|w| matches!((w[0], w[1]), (DiffRange::Equal(..), DiffRange::Equal(..)))),
+            "{:?}",
+            solution
+        );
+    }
+}
+
 macro_rules! assert_patch {
     ($diff_options:expr, $old:ident, $new:ident, $expected:ident $(,)?) => {
         let patch = $diff_options.create_patch($old, $new);
@@ -341,6 +396,11 @@ macro_rules! assert_patch {
             crate::apply_bytes($old.as_bytes(), &bpatch).unwrap(),
             $new.as_bytes()
         );
+        assert_eq!(crate::apply_cow($old, &patch).unwrap(), $new);
+        assert_eq!(
+            crate::apply_bytes_cow($old.as_bytes(), &bpatch).unwrap(),
+            $new.as_bytes()
+        );
     };
     ($old:ident, $new:ident, $expected:ident $(,)?) => {
         assert_patch!(DiffOptions::default(), $old, $new, $expected);
@@ -706,3 +766,3092 @@ Kupluh, Indeed
     let re_reverse = apply(&apply(original, &p).unwrap(), &reverse).unwrap();
     assert_eq!(re_reverse, original);
 }
+
+#[test]
+fn asymmetric_context() {
+    let original = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+    let modified = "1\ntwo\n3\n4\n5\n6\n7\n8\nnine\n10\n";
+
+    let diff = diff_lines(original, modified);
+    let diff_bytes = diff_lines_bytes(original.as_bytes(), modified.as_bytes());
+
+    macro_rules! assert_asym {
+        ($pre:expr, $post:expr, $expected:expr $(,)?) => {
+            let patch = diff.to_patch_asym($pre, $post);
+            assert_eq!(patch.to_string(), $expected);
+            assert_eq!(
+                diff_bytes.to_patch_asym($pre, $post).to_bytes(),
+                $expected.as_bytes()
+            );
+            assert_eq!(apply(original, &patch).unwrap(), modified);
+        };
+    }
+
+    // Leading context is clamped at the start of the file
+    assert_asym!(
+        3,
+        1,
+        "\
+--- original
++++ modified
+@@ -1,3 +1,3 @@
+ 1
+-2
++two
+ 3
+@@ -6,5 +6,5 @@
+ 6
+ 7
+ 8
+-9
++nine
+ 10
+",
+    );
+
+    // Trailing context is clamped at the end of the file
+    assert_asym!(
+        1,
+        3,
+        "\
+--- original
++++ modified
+@@ -1,5 +1,5 @@
+ 1
+-2
++two
+ 3
+ 4
+ 5
+@@ -8,3 +8,3 @@
+ 8
+-9
++nine
+ 10
+",
+    );
+
+    // No context at all on one side
+    assert_asym!(
+        0,
+        2,
+        "\
+--- original
++++ modified
+@@ -2,3 +2,3 @@
+-2
++two
+ 3
+ 4
+@@ -9,2 +9,2 @@
+-9
++nine
+ 10
+",
+    );
+
+    // Hunks merge once the gap between changes is at most `pre + post`
+    assert_eq!(diff.to_patch_asym(2, 3).hunks().len(), 2);
+    assert_eq!(diff.to_patch_asym(3, 2).hunks().len(), 2);
+    assert_eq!(diff.to_patch_asym(3, 3).hunks().len(), 1);
+    assert_eq!(diff.to_patch_asym(4, 3).hunks().len(), 1);
+
+    // A symmetric window is the same as `to_patch`
+    assert_eq!(diff.to_patch_asym(2, 2), diff.to_patch(2));
+    assert_eq!(
+        diff.to_patch(3),
+        DiffOptions::new().create_patch(original, modified)
+    );
+}
+
+#[test]
+fn edit_script() {
+    let original = "a\nb\nc\nd\n";
+    let modified = "a\nB\nc\nd\ne\n";
+
+    let script = diff_lines(original, modified).edit_script();
+    assert_eq!(script.old_len, 4);
+    assert_eq!(script.new_len, 5);
+    assert_eq!(
+        script.edits,
+        [
+            LineEdit {
+                old: 1..2,
+                new: 1..2
+            },
+            LineEdit {
+                old: 4..4,
+                new: 4..5
+            },
+        ]
+    );
+    assert_eq!(script.old_lines, None);
+
+    let script =
+        diff_lines_bytes(original.as_bytes(), modified.as_bytes()).edit_script_with_lines();
+    assert_eq!(script.new_lines.unwrap()[4], b"e\n");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serialize_diff() {
+    let solution = diff("abc", "aXc");
+    assert_eq!(
+        serde_json::to_string(&solution).unwrap(),
+        r#"[{"kind":"equal","value":"a"},{"kind":"delete","value":"b"},{"kind":"insert","value":"X"},{"kind":"equal","value":"c"}]"#
+    );
+    let json = serde_json::to_string(&solution).unwrap();
+    let owned: Vec<OwnedDiff<String>> = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+        owned,
+        solution
+            .iter()
+            .map(|diff| diff.into_owned())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(serde_json::to_string(&owned).unwrap(), json);
+
+    let solution = diff("naïve", "naive!");
+    let json = serde_json::to_string(&solution).unwrap();
+    assert_eq!(
+        json,
+        r#"[{"kind":"equal","value":"na"},{"kind":"delete","value":"ï"},{"kind":"insert","value":"i"},{"kind":"equal","value":"ve"},{"kind":"insert","value":"!"}]"#
+    );
+    let owned: Vec<OwnedDiff<String>> = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+        owned.iter().map(OwnedDiff::as_diff).collect::<Vec<_>>(),
+        solution
+    );
+
+    let original = "a\nb\nc\n";
+    let modified = "a\nB\nc\n";
+    let diff = diff_lines(original, modified);
+
+    let json = serde_json::to_string(&diff.edit_script()).unwrap();
+    assert_eq!(
+        json,
+        r#"{"old_len":3,"new_len":3,"edits":[{"old":{"start":1,"end":2},"new":{"start":1,"end":2}}]}"#
+    );
+
+    let script = diff.edit_script_with_lines();
+    let json = serde_json::to_string(&script).unwrap();
+    assert_eq!(
+        json,
+        r#"{"old_len":3,"new_len":3,"edits":[{"old":{"start":1,"end":2},"new":{"start":1,"end":2}}],"old_lines":["a\n","b\n","c\n"],"new_lines":["a\n","B\n","c\n"]}"#
+    );
+    assert_eq!(
+        serde_json::from_str::<EditScript<String>>(&json).unwrap(),
+        script
+    );
+}
+
+#[test]
+fn max_changed_lines() {
+    let original = "1\n2\n3\n4\n5\n6\n7\n8\n";
+    let modified = "1\ntwo\n3\n4\nfive\n6\n7\n8\nnine\n";
+
+    // Two lines are replaced and one is appended: 5 inserted + deleted lines
+    let mut opts = DiffOptions::new();
+    assert_eq!(
+        opts.try_create_patch(original, modified).unwrap(),
+        opts.create_patch(original, modified)
+    );
+
+    opts.set_max_changed_lines(Some(5));
+    assert_eq!(
+        opts.try_create_patch(original, modified).unwrap(),
+        opts.create_patch(original, modified)
+    );
+    assert!(opts
+        .try_create_patch_bytes(original.as_bytes(), modified.as_bytes())
+        .is_ok());
+
+    opts.set_max_changed_lines(Some(4));
+    let err = opts.try_create_patch(original, modified).unwrap_err();
+    assert_eq!(err.max_changed_lines(), 4);
+    assert_eq!(err.to_string(), "more than 4 lines changed");
+    assert!(opts
+        .try_diff_lines_bytes(original.as_bytes(), modified.as_bytes())
+        .is_err());
+
+    // The limit only applies to the `try_*` functions
+    assert_eq!(opts.create_patch(original, modified).hunks().len(), 1);
+
+    // Identical texts never exceed the limit
+    opts.set_max_changed_lines(Some(0));
+    assert!(opts.try_create_patch(original, original).is_ok());
+    assert!(opts.try_create_patch("", "a\n").is_err());
+}
+
+#[test]
+fn apply_deletion_without_context() {
+    let original = "x\nb\ny\nb\n";
+    let modified = "x\nb\ny\n";
+    let expected = "\
+--- original
++++ modified
+@@ -4 +3,0 @@
+-b
+";
+    let mut opts = DiffOptions::new();
+    opts.set_context_len(0);
+    assert_patch!(opts, original, modified, expected);
+}
+
+#[test]
+fn metrics() {
+    let metrics = |matched, old_len, new_len, longest_match| DiffMetrics {
+        matched,
+        old_len,
+        new_len,
+        longest_match,
+    };
+    let cases = [
+        ("a\nb\nc\nd\ne\n", "a\nb\nX\nd\ne\nf\n", metrics(4, 5, 6, 2)),
+        ("a\nb\nc\n", "a\nb\nc\n", metrics(3, 3, 3, 3)),
+        // Repeated lines count once per occurrence
+        ("a\na\na\n", "a\na\n", metrics(2, 3, 2, 2)),
+        ("x\na\na\ny\na\n", "a\na\nz\na\n", metrics(3, 5, 4, 2)),
+        ("", "", metrics(0, 0, 0, 0)),
+        ("", "a\n", metrics(0, 0, 1, 0)),
+        ("a\nb\n", "c\nd\n", metrics(0, 2, 2, 0)),
+    ];
+    for (original, modified, expected) in cases {
+        let diff = diff_lines(original, modified);
+        assert_eq!(diff.metrics(), expected, "{:?} -> {:?}", original, modified);
+        assert_eq!(DiffMetrics::from_ops(&diff.ops()), expected);
+        assert_eq!(diff.metrics().ratio(), diff.similarity());
+    }
+    assert_eq!(metrics(0, 0, 0, 0).ratio(), 1.0);
+    assert_eq!(metrics(4, 5, 6, 2).ratio(), 8.0 / 11.0);
+
+    // Lines which are only equal under the options count as matched
+    let mut options = DiffOptions::new();
+    options.set_ignore_case(true);
+    assert_eq!(
+        options.diff_lines("A\nb\nC\n", "a\nb\nc\n").metrics(),
+        metrics(3, 3, 3, 3)
+    );
+
+    assert_eq!(
+        DiffMetrics::from_ops(&diff_text_ops("abcdef", "abXdef")),
+        metrics(5, 6, 6, 3)
+    );
+    assert_eq!(
+        DiffMetrics::from_ops(&diff_slice_ops(&[1, 2, 3], &[0, 1, 2, 3, 4])),
+        metrics(3, 3, 5, 3)
+    );
+
+    // Neighbouring and empty ops don't split runs
+    let op = |kind, old: ops::Range<usize>, new: ops::Range<usize>| DiffOp { kind, old, new };
+    let ops = [
+        op(DiffOpKind::Equal, 0..2, 0..2),
+        op(DiffOpKind::Insert, 2..2, 2..2),
+        op(DiffOpKind::Equal, 2..3, 2..3),
+        op(DiffOpKind::Delete, 3..4, 3..3),
+        op(DiffOpKind::Equal, 4..5, 3..4),
+    ];
+    assert_eq!(DiffMetrics::from_ops(&ops), metrics(4, 5, 4, 3));
+    assert_eq!(DiffMetrics::from_ops(&[]), metrics(0, 0, 0, 0));
+}
+
+#[test]
+fn similarity() {
+    let reference_text = "a\nb\na\nc\n";
+    let reference = Reference::new(reference_text);
+    let reference_bytes = Reference::new_bytes(reference_text.as_bytes());
+
+    for candidate in [
+        "a\nb\na\nc\n",
+        "a\na\nb\n",
+        "c\nb\na\n",
+        "x\ny\n",
+        "a\nb\na\nc\nd\n",
+        "",
+    ] {
+        let expected = diff_lines(reference_text, candidate).similarity();
+        assert_eq!(
+            reference.similarity_to(candidate),
+            expected,
+            "{candidate:?}"
+        );
+        assert_eq!(
+            reference_bytes.similarity_to(candidate.as_bytes()),
+            expected,
+            "{candidate:?}"
+        );
+    }
+
+    assert_eq!(Reference::new("a\n").similarity_to("a\n"), 1.0);
+    assert_eq!(Reference::new("").similarity_to(""), 1.0);
+    assert_eq!(Reference::new("a\n").similarity_to(""), 0.0);
+}
+
+#[test]
+fn combined() {
+    let ours = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\nours\n";
+    let theirs = "1\ntwo\n3\n4\n5\n6\n7\n8\n9\n10\ntheirs\n";
+    let merged = "1\ntwo\n3\n4\n5\n6\n7\n8\n9\n10\nmerged";
+    let expected = "\
+@@@ -1,3 -1,3 +1,3 @@@
+  1
+- 2
++ two
+  3
+@@@ -10,2 -10,2 +10,2 @@@
+  10
+- ours
+ -theirs
+++merged
+\\ No newline at end of file
+";
+    let mut opts = DiffOptions::new();
+    opts.set_context_len(1);
+    let patch = opts.combined_diff(ours, theirs, merged);
+    assert_eq!(patch.to_string(), expected);
+
+    let hunk = &patch.hunks()[1];
+    assert_eq!(hunk.parent_ranges()[1].range(), 10..12);
+    assert_eq!(
+        hunk.lines()[2].changes(),
+        [ParentChange::Unchanged, ParentChange::Removed]
+    );
+
+    assert_eq!(combined_diff(ours, ours, ours).hunks(), &[]);
+}
+
+#[test]
+fn apply_error() {
+    fn assert_send_sync<T: Send + Sync + 'static>() {}
+    assert_send_sync::<crate::ApplyError>();
+
+    let patch = create_patch("a\nb\nc\n", "a\nB\nc\n");
+    let err = apply("x\ny\nz\n", &patch).unwrap_err();
+    assert_eq!(err, crate::ApplyError::HunkMismatch { hunk: 1 });
+    assert!(err.is_hunk_mismatch());
+    assert_eq!(err.hunk(), 1);
+    assert_eq!(err.to_string(), "error applying hunk #1");
+}
+
+#[test]
+fn apply_cow() {
+    use crate::{apply_bytes_cow, apply_cow, ApplyOptions};
+    use alloc::borrow::Cow;
+
+    let base = "a\nb\nc\nd\ne\n";
+    let is_borrowed = |text: &Cow<'_, str>| matches!(text, Cow::Borrowed(_));
+
+    // A patch without hunks, or which only removes lines at either end, borrows the base
+    let cases = [
+        (base, true),
+        ("b\nc\nd\ne\n", true),
+        ("a\nb\nc\n", true),
+        ("b\nc\nd\n", true),
+        ("a\nb\nc\nd\ne", false),
+        ("", true),
+        ("a\nb\nd\ne\n", false),
+        ("a\nb\nc\nd\ne\nf\n", false),
+        ("x\n", false),
+    ];
+    for (modified, borrowed) in cases {
+        let patch = create_patch(base, modified);
+        let patched = apply_cow(base, &patch).unwrap();
+        assert_eq!(patched, modified);
+        assert_eq!(is_borrowed(&patched), borrowed, "{:?}", modified);
+        assert_eq!(patched, apply(base, &patch).unwrap());
+
+        let bpatch = create_patch_bytes(base.as_bytes(), modified.as_bytes());
+        let patched = apply_bytes_cow(base.as_bytes(), &bpatch).unwrap();
+        assert_eq!(patched, modified.as_bytes());
+        assert_eq!(matches!(patched, Cow::Borrowed(_)), borrowed);
+    }
+
+    // The image's own newline convention is kept at the end of the file
+    let mut options = ApplyOptions::new();
+    options.set_ignore_trailing_newline(true);
+    for (base, original, modified, expected) in [
+        ("a\nb", "a\nb\n", "a\nB\n", "a\nB"),
+        ("a\nb\n", "a\nb", "a\nB", "a\nB\n"),
+        ("a\nb\n", "a\nb", "a", "a\n"),
+    ] {
+        let patch = create_patch(original, modified);
+        let patched = options.apply_cow(base, &patch).unwrap();
+        assert_eq!(patched, expected);
+        assert_eq!(patched, options.apply(base, &patch).unwrap());
+    }
+    let patch = create_patch("a\nb\n", "a\n");
+    let patched = options.apply_cow("a\nb", &patch).unwrap();
+    assert_eq!(patched, "a");
+    assert!(is_borrowed(&patched));
+
+    // Errors are the same as when applying into a new string
+    let patch = create_patch("a\nb\nc\n", "a\nB\nc\n");
+    assert_eq!(
+        apply_cow("x\ny\nz\n", &patch).unwrap_err(),
+        apply("x\ny\nz\n", &patch).unwrap_err()
+    );
+}
+
+#[test]
+fn apply_with_spans() {
+    use crate::{ApplyOptions, HunkSpan};
+
+    let original = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+    let modified = "1\nzero\n2\n3\n4\n5\n6\nseven\n8\n9\n10\n11\n";
+    let mut options = DiffOptions::new();
+    options.set_context_len(1);
+    let patch = options.create_patch(original, modified);
+    assert_eq!(patch.hunks().len(), 3);
+
+    // Each span covers the context and inserted lines of its hunk, after the lines added by the
+    // first hunk
+    let check = |base: &str, expected: &str, lines: [ops::Range<usize>; 3]| {
+        let options = ApplyOptions::new();
+        let (patched, spans) = options.apply_with_spans(base, &patch).unwrap();
+        assert_eq!(patched, expected);
+        assert_eq!(
+            spans.iter().map(|s| s.lines.clone()).collect::<Vec<_>>(),
+            lines
+        );
+        for (span, hunk) in spans.iter().zip(patch.hunks()) {
+            assert_eq!(
+                patched[span.bytes.clone()],
+                hunk.new_lines().collect::<String>()
+            );
+            let lines: Vec<_> = LineIter::new(patched.as_str()).collect();
+            assert_eq!(
+                lines[span.lines.clone()].concat(),
+                patched[span.bytes.clone()]
+            );
+        }
+
+        let (bytes, byte_spans) = options
+            .apply_bytes_with_spans(base.as_bytes(), &Patch::from(patch.clone()))
+            .unwrap();
+        assert_eq!(bytes, patched.as_bytes());
+        assert_eq!(byte_spans, spans);
+    };
+    check(original, modified, [0..3, 6..9, 11..12]);
+
+    // Hunks applied away from the position in their header
+    let shifted = format!("a\nb\n{}", original);
+    check(
+        &shifted,
+        &format!("a\nb\n{}", modified),
+        [2..5, 8..11, 13..14],
+    );
+
+    // A hunk which only deletes lines has an empty span where they were
+    let patch = create_patch("a\nb\nc\n", "a\nc\nd\n");
+    let patch = patch.strip_context();
+    let (patched, spans) = ApplyOptions::new()
+        .apply_with_spans("a\nb\nc\n", &patch)
+        .unwrap();
+    assert_eq!(patched, "a\nc\nd\n");
+    assert_eq!(
+        spans,
+        [
+            HunkSpan {
+                lines: 1..1,
+                bytes: 2..2
+            },
+            HunkSpan {
+                lines: 2..3,
+                bytes: 4..6
+            },
+        ]
+    );
+
+    // A later hunk may be applied over the place an earlier one deleted lines at
+    let patch =
+        Patch::from_str("--- a\n+++ b\n@@ -3 +2,0 @@\n-X\n@@ -4,4 +3,0 @@\n-a\n-b\n-c\n-d\n")
+            .unwrap();
+    let base = "a\nb\nX\nc\nd\n";
+    assert_eq!(apply(base, &patch).unwrap(), "");
+    let (patched, spans) = ApplyOptions::new().apply_with_spans(base, &patch).unwrap();
+    assert_eq!(patched, "");
+    assert_eq!(
+        spans.iter().map(|s| s.lines.clone()).collect::<Vec<_>>(),
+        [0..0, 0..0]
+    );
+    let (_, byte_spans) = ApplyOptions::new()
+        .apply_bytes_with_spans(base.as_bytes(), &Patch::from(patch.clone()))
+        .unwrap();
+    assert_eq!(byte_spans, spans);
+
+    // The newline kept or dropped at the end of the text is part of the last hunk's span
+    let mut options = ApplyOptions::new();
+    options.set_ignore_trailing_newline(true);
+    let patch = create_patch("a\nb\n", "a\nB\n");
+    let (patched, spans) = options.apply_with_spans("a\nb", &patch).unwrap();
+    assert_eq!(patched, "a\nB");
+    assert_eq!(spans[0].bytes, 0..3);
+    let patch = create_patch("a\nb", "a\nB");
+    let (patched, spans) = options.apply_with_spans("a\nb\n", &patch).unwrap();
+    assert_eq!(patched, "a\nB\n");
+    assert_eq!(spans[0].bytes, 0..4);
+}
+
+#[cfg(feature = "std")]
+#[test]
+#[ignore = "applies patches to a 100MB text"]
+fn apply_cow_large_base() {
+    use crate::{apply_cow, Patch};
+    use alloc::borrow::Cow;
+    use std::time::Instant;
+
+    let base: String = (0..5_300_000)
+        .map(|i| format!("line {:>14}\n", i))
+        .collect();
+    assert!(base.len() >= 100 << 20);
+
+    let line = "line        2500000\n";
+    let pos = base.find(line).unwrap();
+    let before = base[..pos].lines().count();
+    let patch = format!(
+        "--- a\n+++ b\n@@ -{0},3 +{0},3 @@\n {1}-{2}+changed\n {3}",
+        before,
+        &base[pos - line.len()..pos],
+        line,
+        &base[pos + line.len()..pos + 2 * line.len()],
+    );
+    let patch = Patch::from_str(&patch).unwrap();
+    let empty = Patch::from_str("--- a\n+++ b\n").unwrap();
+
+    let start = Instant::now();
+    let patched = apply_cow(&base, &patch).unwrap();
+    let cow_time = start.elapsed();
+    assert_eq!(patched, apply(&base, &patch).unwrap());
+    assert_eq!(patched.len(), base.len() - line.len() + "changed\n".len());
+
+    // Without hunks the base image isn't even split into lines
+    let start = Instant::now();
+    let unchanged = apply_cow(&base, &empty).unwrap();
+    let borrow_time = start.elapsed();
+    assert!(matches!(unchanged, Cow::Borrowed(_)));
+    assert!(
+        borrow_time < cow_time,
+        "apply_cow: {:?}, apply_cow without hunks: {:?}",
+        cow_time,
+        borrow_time
+    );
+}
+
+#[test]
+fn unmerged_hunks() {
+    let original = "1\n2\n3\n4\n5\n6\n7\n8\n";
+    let modified = "1\ntwo\n3\n4\n5\nsix\n7\n8\n";
+
+    let merged = "\
+--- original
++++ modified
+@@ -1,8 +1,8 @@
+ 1
+-2
++two
+ 3
+ 4
+ 5
+-6
++six
+ 7
+ 8
+";
+    let mut opts = DiffOptions::new();
+    assert_patch!(opts, original, modified, merged);
+
+    // The three unchanged lines between the changes are split between the two hunks
+    let unmerged = "\
+--- original
++++ modified
+@@ -1,4 +1,4 @@
+ 1
+-2
++two
+ 3
+ 4
+@@ -5,4 +5,4 @@
+ 5
+-6
++six
+ 7
+ 8
+";
+    opts.set_merge_hunks(false);
+    assert_patch!(opts, original, modified, unmerged);
+
+    // Changes far enough apart get their full context either way
+    let original = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+    let modified = "one\n2\n3\n4\n5\n6\n7\n8\n9\nten\n";
+    let mut opts = DiffOptions::new();
+    opts.set_context_len(2);
+    let merged = opts.create_patch(original, modified);
+    opts.set_merge_hunks(false);
+    assert_eq!(opts.create_patch(original, modified), merged);
+    assert_eq!(merged.hunks().len(), 2);
+}
+
+#[test]
+fn display_hunks() {
+    let original = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+    let modified = "one\n2\n3\n4\n5\n6\n7\n8\n9\nten";
+    let mut opts = DiffOptions::new();
+    opts.set_context_len(1);
+
+    let patch = opts.create_patch(original, modified);
+    assert_eq!(patch.hunks().len(), 2);
+    let hunks: String = patch.hunks().iter().map(ToString::to_string).collect();
+    assert_eq!(
+        format!("--- original\n+++ modified\n{}", hunks),
+        patch.to_string()
+    );
+    assert_eq!(
+        patch.hunks()[1].to_string(),
+        "@@ -9,2 +9,2 @@\n 9\n-10\n+ten\n\\ No newline at end of file\n"
+    );
+
+    let bpatch = opts.create_patch_bytes(original.as_bytes(), modified.as_bytes());
+    let mut bytes = b"--- original\n+++ modified\n".to_vec();
+    for hunk in bpatch.hunks() {
+        bytes.extend(hunk.to_bytes());
+        assert_eq!(hunk.display_lossy().to_string().as_bytes(), hunk.to_bytes());
+    }
+    assert_eq!(bytes, bpatch.to_bytes());
+
+    #[cfg(feature = "std")]
+    {
+        let mut written = Vec::new();
+        crate::PatchFormatter::new()
+            .write_hunk_into(&bpatch.hunks()[0], &mut written)
+            .unwrap();
+        assert_eq!(written, patch.hunks()[0].to_string().as_bytes());
+    }
+}
+
+#[test]
+fn strip_context() {
+    let original = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10";
+    let cases = [
+        "0\n1\nB\n3\n4\n6\n7\n8\n9\n10\n",
+        "2\n3\n4\n5\n6\nseven\n8\n9\n10",
+        "1\n2\n3\n4\n5\n6\n7\n8\n",
+    ];
+
+    for modified in cases {
+        let patch = create_patch(original, modified);
+        let stripped = patch.strip_context();
+        assert!(stripped
+            .hunks()
+            .iter()
+            .flat_map(Hunk::lines)
+            .all(|line| !matches!(line, Line::Context(_))));
+
+        let mut opts = DiffOptions::new();
+        opts.set_context_len(0);
+        assert_eq!(stripped, opts.create_patch(original, modified));
+        assert_eq!(apply(original, &stripped).unwrap(), modified);
+        assert_eq!(apply(modified, &stripped.reverse()).unwrap(), original);
+    }
+
+    // A single hunk with four separate groups of changes is split into four hunks
+    let patch = create_patch(original, cases[0]);
+    assert_eq!(patch.hunks().len(), 1);
+    assert_eq!(patch.strip_context().hunks().len(), 4);
+}
+
+#[test]
+fn hunk_old_and_new_lines() {
+    let original = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+    let modified = "1\ntwo\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n13\n";
+
+    let old_lines: Vec<_> = LineIter::new(original).collect();
+    let new_lines: Vec<_> = LineIter::new(modified).collect();
+    let old_lines_bytes: Vec<_> = LineIter::new(original.as_bytes()).collect();
+    let new_lines_bytes: Vec<_> = LineIter::new(modified.as_bytes()).collect();
+
+    let patch = create_patch(original, modified);
+    let bpatch = create_patch_bytes(original.as_bytes(), modified.as_bytes());
+    assert_eq!(patch.hunks().len(), 2);
+
+    for (hunk, bhunk) in patch.hunks().iter().zip(bpatch.hunks()) {
+        let old: Vec<_> = hunk.old_lines().collect();
+        let new: Vec<_> = hunk.new_lines().collect();
+        assert_eq!(old, old_lines[hunk.old_range().to_std_range()]);
+        assert_eq!(new, new_lines[hunk.new_range().to_std_range()]);
+        assert_eq!(old.len(), hunk.old_range().len());
+        assert_eq!(new.len(), hunk.new_range().len());
+
+        let old: Vec<_> = bhunk.old_lines().collect();
+        let new: Vec<_> = bhunk.new_lines().collect();
+        assert_eq!(old, old_lines_bytes[bhunk.old_range().to_std_range()]);
+        assert_eq!(new, new_lines_bytes[bhunk.new_range().to_std_range()]);
+    }
+}
+
+#[test]
+fn ignore_indentation() {
+    // The body of the loop is nested one level deeper and switches from spaces to tabs
+    let original = "\
+fn main() {
+    for x in xs {
+        foo(x);
+        bar(x);
+    }
+}
+";
+    let modified = "\
+fn main() {
+\tif cond {
+\t\tfor x in xs {
+\t\t\tfoo(x);
+\t\t\tbaz(x);
+\t\t}
+\t}
+}
+";
+
+    let mut opts = DiffOptions::new();
+    opts.set_ignore_indentation(true);
+    let expected = "\
+--- original
++++ modified
+@@ -1,6 +1,8 @@
+ fn main() {
++\tif cond {
+ \t\tfor x in xs {
+ \t\t\tfoo(x);
+-        bar(x);
++\t\t\tbaz(x);
++\t\t}
+ \t}
+ }
+";
+    let patch = opts.create_patch(original, modified);
+    assert_eq!(patch.to_string(), expected);
+    let bpatch = opts.create_patch_bytes(original.as_bytes(), modified.as_bytes());
+    assert_eq!(bpatch.to_bytes(), expected.as_bytes());
+
+    // Every line is re-indented so nothing survives without the option
+    let patch = DiffOptions::new().create_patch(original, modified);
+    assert_eq!(patch.hunks()[0].lines().len(), 12);
+    assert_eq!(apply(original, &patch).unwrap(), modified);
+
+    // Tab/space swaps alone produce no hunks at all
+    let original = "a\n    b\n\tc\n  \t d\n";
+    let modified = "a\n\tb\n    c\n d\n";
+    let patch = opts.create_patch(original, modified);
+    assert!(patch.hunks().is_empty());
+
+    // Whitespace within a line is still significant
+    let patch = opts.create_patch("a b\n", "a  b\n");
+    assert_eq!(patch.hunks().len(), 1);
+}
+
+#[test]
+fn pre_and_post_images() {
+    let original = "\
+The Way of Kings
+Words of Radiance
+Edgedancer
+Oathbringer
+Dawnshard
+Rhythm of War
+";
+    let modified = "\
+The Way of Kings
+Words of Radiance
+Oathbringer
+Dawnshard
+Rhythm of War
+Wind and Truth
+";
+
+    let mut opts = DiffOptions::new();
+    opts.set_context_len(usize::MAX);
+    let patch = opts.create_patch(original, modified);
+    assert_eq!(patch.pre_image(), [(HunkRange::new(1, 6), original.into())]);
+    assert_eq!(
+        patch.post_image(),
+        [(HunkRange::new(1, 6), modified.into())]
+    );
+    assert_eq!(patch.whole_pre_image().as_deref(), Some(original));
+    assert_eq!(patch.whole_post_image().as_deref(), Some(modified));
+
+    let bpatch = opts.create_patch_bytes(original.as_bytes(), modified.as_bytes());
+    assert_eq!(
+        bpatch.whole_pre_image().as_deref(),
+        Some(original.as_bytes())
+    );
+    assert_eq!(
+        bpatch.whole_post_image().as_deref(),
+        Some(modified.as_bytes())
+    );
+
+    // Hunks which don't share context are returned as separate fragments
+    let patch = opts.set_context_len(1).create_patch(original, modified);
+    let expected = [
+        (
+            HunkRange::new(2, 3),
+            "Words of Radiance\nEdgedancer\nOathbringer\n".into(),
+        ),
+        (HunkRange::new(6, 1), "Rhythm of War\n".into()),
+    ];
+    assert_eq!(patch.pre_image(), expected);
+    let expected = [
+        (
+            HunkRange::new(2, 2),
+            "Words of Radiance\nOathbringer\n".into(),
+        ),
+        (
+            HunkRange::new(5, 2),
+            "Rhythm of War\nWind and Truth\n".into(),
+        ),
+    ];
+    assert_eq!(patch.post_image(), expected);
+    assert_eq!(patch.whole_pre_image(), None);
+    assert_eq!(patch.whole_post_image(), None);
+
+    // Contiguous hunks still cover the whole file
+    let patch = opts
+        .set_context_len(1)
+        .set_merge_hunks(false)
+        .create_patch("a\nb\nc\nd\n", "A\nb\nc\nD\n");
+    assert_eq!(patch.hunks().len(), 2);
+    assert_eq!(patch.whole_pre_image().as_deref(), Some("a\nb\nc\nd\n"));
+    assert_eq!(patch.whole_post_image().as_deref(), Some("A\nb\nc\nD\n"));
+
+    // Creating a file has an empty pre-image
+    let patch = create_patch("", modified);
+    assert_eq!(patch.pre_image(), [(HunkRange::new(0, 0), String::new())]);
+    assert_eq!(patch.whole_pre_image().as_deref(), Some(""));
+    assert_eq!(patch.whole_post_image().as_deref(), Some(modified));
+
+    // Nothing can be reconstructed from a patch without any hunks
+    let patch = create_patch(original, original);
+    assert_eq!(patch.whole_pre_image(), None);
+}
+
+#[test]
+fn patch_from_edits() {
+    let base = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+    let modified = "0\n1\n2\nthree\n5\n6\n7\n8\n9\n10\n11\n";
+    let edits = [
+        Edit {
+            old: 0..0,
+            new_lines: vec!["0\n".into()],
+        },
+        // A no-op edit is skipped
+        Edit {
+            old: 1..1,
+            new_lines: vec![],
+        },
+        Edit {
+            old: 2..3,
+            new_lines: vec!["three\n".into()],
+        },
+        // Touches the previous edit, so both form a single change
+        Edit {
+            old: 3..4,
+            new_lines: vec![],
+        },
+        Edit {
+            old: 11..12,
+            new_lines: vec![],
+        },
+    ];
+
+    for context_len in [0, 1, 3, 10] {
+        let patch = Patch::from_edits(base, &edits, context_len).unwrap();
+        let expected = DiffOptions::new()
+            .set_context_len(context_len)
+            .create_patch(base, modified);
+        assert_eq!(patch, expected);
+        assert_eq!(apply(base, &patch).unwrap(), modified);
+    }
+
+    let patch = Patch::from_edits(base, &[], 3).unwrap();
+    assert!(patch.hunks().is_empty());
+
+    let edits = [Edit {
+        old: 0..0,
+        new_lines: vec!["a\n".into()],
+    }];
+    let patch = Patch::from_edits("", &edits, 3).unwrap();
+    assert_eq!(patch, create_patch("", "a\n"));
+
+    let edit = |old| Edit {
+        old,
+        new_lines: vec![],
+    };
+    #[allow(clippy::reversed_empty_ranges)]
+    let err = Patch::from_edits(base, &[edit(0..1), edit(5..4)], 3).unwrap_err();
+    assert_eq!(err, EditError::InvalidRange { edit: 1 });
+    let err = Patch::from_edits(base, &[edit(11..13)], 3).unwrap_err();
+    assert_eq!(err, EditError::OutOfBounds { edit: 0 });
+    assert!(Patch::from_edits(base, &[edit(12..12)], 3).is_ok());
+    let err = Patch::from_edits(base, &[edit(2..5), edit(4..6)], 3).unwrap_err();
+    assert_eq!(err, EditError::Overlapping { edit: 1 });
+    assert_eq!(err.edit(), 1);
+    assert_eq!(err.to_string(), "edit #1 overlaps the edit preceding it");
+}
+
+#[test]
+fn annotated_lines() {
+    let numbers = |patch: &Patch<'_, str>| -> Vec<(usize, Option<usize>, Option<usize>)> {
+        patch
+            .annotated_lines()
+            .map(|l| (l.hunk_index, l.old_lineno, l.new_lineno))
+            .collect()
+    };
+
+    // Two changes close enough to be merged into a single hunk
+    let original = "1\n2\n3\n4\n5\n6\n7\n8\n";
+    let modified = "1\n2\ntwo and a half\n3\n4\n5\n7\n8\n";
+    let patch = create_patch(original, modified);
+    assert_eq!(patch.hunks().len(), 1);
+    assert_eq!(
+        numbers(&patch),
+        [
+            (0, Some(1), Some(1)),
+            (0, Some(2), Some(2)),
+            (0, None, Some(3)),
+            (0, Some(3), Some(4)),
+            (0, Some(4), Some(5)),
+            (0, Some(5), Some(6)),
+            (0, Some(6), None),
+            (0, Some(7), Some(7)),
+            (0, Some(8), Some(8)),
+        ]
+    );
+    let lines: Vec<_> = patch.annotated_lines().map(|l| *l.line).collect();
+    assert_eq!(lines, patch.hunks()[0].lines());
+
+    // Without context, pure insertions and deletions have an empty range on one side
+    let original = "a\nb\nc\nd\ne\n";
+    let modified = "x\na\nb\nd\ne\ny\n";
+    let patch = DiffOptions::new()
+        .set_context_len(0)
+        .create_patch(original, modified);
+    assert_eq!(
+        patch.to_string(),
+        "\
+--- original
++++ modified
+@@ -0,0 +1 @@
++x
+@@ -3 +3,0 @@
+-c
+@@ -5,0 +6 @@
++y
+"
+    );
+    assert_eq!(
+        numbers(&patch),
+        [(0, None, Some(1)), (1, Some(3), None), (2, None, Some(6))]
+    );
+
+    // Creating and deleting a whole file
+    let patch = create_patch("", "a\nb\n");
+    assert_eq!(numbers(&patch), [(0, None, Some(1)), (0, None, Some(2))]);
+    let patch = create_patch("a\nb\n", "");
+    assert_eq!(numbers(&patch), [(0, Some(1), None), (0, Some(2), None)]);
+}
+
+#[cfg(feature = "unicode-normalization")]
+#[test]
+fn normalize_unicode() {
+    use crate::NormalizationForm;
+
+    let composed = "r\u{e9}sum\u{e9}\nna\u{ef}ve\nsame\n";
+    let decomposed = "re\u{301}sume\u{301}\nnai\u{308}ve\nsame\nnew\n";
+
+    let mut opts = DiffOptions::new();
+    let patch = opts.create_patch(composed, decomposed);
+    assert_eq!(patch.hunks()[0].lines().len(), 6);
+
+    for form in [NormalizationForm::Nfc, NormalizationForm::Nfd] {
+        opts.set_normalize_unicode(Some(form));
+        let expected = "\
+--- original
++++ modified
+@@ -1,3 +1,4 @@
+ re\u{301}sume\u{301}
+ nai\u{308}ve
+ same
++new
+";
+        // Unchanged lines are output as they appear in the modified text
+        let patch = opts.create_patch(composed, decomposed);
+        assert_eq!(patch.to_string(), expected);
+        let patch = opts.create_patch_bytes(composed.as_bytes(), decomposed.as_bytes());
+        assert_eq!(patch.to_bytes(), expected.as_bytes());
+    }
+
+    // Compatibility forms also equate characters like ligatures with their components
+    let ligature = "\u{fb01}le\n";
+    let plain = "file\n";
+    opts.set_normalize_unicode(Some(NormalizationForm::Nfc));
+    assert_eq!(opts.create_patch(ligature, plain).hunks().len(), 1);
+    for form in [NormalizationForm::Nfkc, NormalizationForm::Nfkd] {
+        opts.set_normalize_unicode(Some(form));
+        assert!(opts.create_patch(ligature, plain).hunks().is_empty());
+    }
+
+    // Normalization composes with ignoring indentation
+    opts.set_ignore_indentation(true);
+    assert!(opts
+        .create_patch("  caf\u{e9}\n", "\tcafe\u{301}\n")
+        .hunks()
+        .is_empty());
+
+    // Lines which aren't valid utf8 are compared as they are
+    let patch = opts.create_patch_bytes(b"\xff\ncaf\xc3\xa9\n", b"\xff\ncafe\xcc\x81\n");
+    assert!(patch.hunks().is_empty());
+    let patch = opts.create_patch_bytes(b"\xff\n", b"\xfe\n");
+    assert_eq!(patch.hunks().len(), 1);
+}
+
+#[test]
+fn diff_ops_transform_old_into_new() {
+    let cases: &[(&[u32], &[u32])] = &[
+        (&[], &[]),
+        (&[], &[1, 2]),
+        (&[1, 2], &[]),
+        (&[1, 2, 3], &[1, 2, 3]),
+        (&[1, 2, 3, 4, 5], &[0, 1, 3, 4, 6, 5]),
+        (&[1, 1, 2, 1, 1], &[1, 2, 2, 1]),
+    ];
+
+    for (old, new) in cases {
+        let ops = diff_ops(old, new);
+
+        let mut result = Vec::new();
+        let (mut next_old, mut next_new) = (0, 0);
+        for op in &ops {
+            match *op {
+                Op::Keep(idx) => {
+                    assert_eq!(idx, next_old);
+                    result.push(old[idx]);
+                    next_old += 1;
+                    next_new += 1;
+                }
+                Op::Remove(idx) => {
+                    assert_eq!(idx, next_old);
+                    next_old += 1;
+                }
+                Op::Insert(idx) => {
+                    assert_eq!(idx, next_new);
+                    result.push(new[idx]);
+                    next_new += 1;
+                }
+            }
+        }
+        assert_eq!((next_old, next_new), (old.len(), new.len()));
+        assert_eq!(&result, new);
+    }
+
+    assert_eq!(
+        diff_ops(&[1, 2, 3], &[1, 4, 3]),
+        [Op::Keep(0), Op::Remove(1), Op::Insert(1), Op::Keep(2)]
+    );
+}
+
+#[test]
+fn ignore_case() {
+    let original = "#include <STDIO.H>\nint Main(void);\nstatic int x;\n";
+    let modified = "#include <stdio.h>\nint main(void);\nstatic int y;\n";
+
+    let mut opts = DiffOptions::new();
+    opts.set_ignore_case(true).set_context_len(1);
+    let expected = "\
+--- original
++++ modified
+@@ -2,2 +2,2 @@
+ int main(void);
+-static int x;
++static int y;
+";
+    // Unchanged lines are output as they appear in the modified text
+    let patch = opts.create_patch(original, modified);
+    assert_eq!(patch.to_string(), expected);
+    let patch = opts.create_patch_bytes(original.as_bytes(), modified.as_bytes());
+    assert_eq!(patch.to_bytes(), expected.as_bytes());
+
+    // Only ASCII letters are folded, even in lines which aren't valid utf8
+    assert_eq!(
+        opts.create_patch("\u{c9}T\u{c9}\n", "\u{e9}t\u{e9}\n")
+            .hunks()
+            .len(),
+        1
+    );
+    let patch = opts.create_patch_bytes(b"\xffA\n", b"\xffa\n");
+    assert!(patch.hunks().is_empty());
+
+    opts.set_ignore_case(false);
+    assert_eq!(opts.create_patch(original, modified).hunks().len(), 1);
+    assert_eq!(
+        opts.create_patch(original, modified).hunks()[0]
+            .lines()
+            .len(),
+        6
+    );
+}
+
+#[cfg(feature = "unicode-case")]
+#[test]
+fn ignore_case_unicode() {
+    let pairs = [
+        ("Stra\u{df}e\n", "STRASSE\n"),
+        (
+            "\u{3a3}\u{399}\u{3a3}\u{3a5}\u{3a6}\u{39f}\u{3a3}\n",
+            "\u{3c3}\u{3b9}\u{3c3}\u{3c5}\u{3c6}\u{3bf}\u{3c2}\n",
+        ),
+        ("\u{130}stanbul\n", "i\u{307}stanbul\n"),
+        ("\u{fb03}\n", "FFI\n"),
+    ];
+
+    let mut opts = DiffOptions::new();
+    opts.set_ignore_case(true);
+    for (a, b) in pairs {
+        assert_eq!(opts.create_patch(a, b).hunks().len(), 1, "{} {}", a, b);
+    }
+
+    // Unicode folding doesn't need ASCII folding to be enabled separately
+    opts.set_ignore_case(false).set_ignore_case_unicode(true);
+    for (a, b) in pairs {
+        assert!(opts.create_patch(a, b).hunks().is_empty(), "{} {}", a, b);
+        let patch = opts.create_patch_bytes(a.as_bytes(), b.as_bytes());
+        assert!(patch.hunks().is_empty());
+    }
+    assert_eq!(
+        opts.create_patch("Stra\u{df}e\n", "STRAE\n").hunks().len(),
+        1
+    );
+
+    // Lines which aren't valid utf8 still have their ASCII letters folded
+    let patch = opts.create_patch_bytes(b"\xffSTRASSE\n", b"\xffstrasse\n");
+    assert!(patch.hunks().is_empty());
+    let patch = opts.create_patch_bytes(b"\xff\xc3\x9f\n", b"\xffSS\n");
+    assert_eq!(patch.hunks().len(), 1);
+}
+
+// Asserts that `ops` cover `0..old_len` and `0..new_len` exactly and in order
+fn assert_ops_tile(ops: &[DiffOp], old_len: usize, new_len: usize) {
+    let mut old_pos = 0;
+    let mut new_pos = 0;
+    for op in ops {
+        assert_eq!(op.old.start, old_pos, "{:?}", ops);
+        assert_eq!(op.new.start, new_pos, "{:?}", ops);
+        match op.kind {
+            DiffOpKind::Equal => assert_eq!(op.old.len(), op.new.len()),
+            DiffOpKind::Delete => assert!(op.new.is_empty() && !op.old.is_empty()),
+            DiffOpKind::Insert => assert!(op.old.is_empty() && !op.new.is_empty()),
+        }
+        old_pos = op.old.end;
+        new_pos = op.new.end;
+    }
+    assert_eq!((old_pos, new_pos), (old_len, new_len));
+}
+
+#[test]
+fn diff_range_ops() {
+    let pairs = [
+        ("", ""),
+        ("abc", ""),
+        ("", "abc"),
+        ("kitten", "sitting"),
+        ("caf\u{e9} \u{1f600}", "cafe \u{1f601}!"),
+        ("c", "\u{e9}"),
+        ("ab", "\u{e9}b"),
+        ("\u{2603}\u{2603}", "\u{2604}\u{2603}"),
+    ];
+    for (original, modified) in pairs {
+        let ops = diff_text_ops(original, modified);
+        assert_ops_tile(&ops, original.len(), modified.len());
+        for op in &ops {
+            assert!(
+                original.is_char_boundary(op.old.start) && original.is_char_boundary(op.old.end)
+            );
+            assert!(
+                modified.is_char_boundary(op.new.start) && modified.is_char_boundary(op.new.end)
+            );
+        }
+
+        let diffs: Vec<_> = ops
+            .iter()
+            .map(|op| match op.kind {
+                DiffOpKind::Equal => {
+                    assert_eq!(original[op.old.clone()], modified[op.new.clone()]);
+                    Diff::Equal(&original[op.old.clone()])
+                }
+                DiffOpKind::Delete => Diff::Delete(&original[op.old.clone()]),
+                DiffOpKind::Insert => Diff::Insert(&modified[op.new.clone()]),
+            })
+            .collect();
+        assert_eq!(diffs, diff(original, modified));
+
+        let old: Vec<char> = original.chars().collect();
+        let new: Vec<char> = modified.chars().collect();
+        let ops = diff_slice_ops(&old, &new);
+        assert_ops_tile(&ops, old.len(), new.len());
+        let elementwise: Vec<_> = ops
+            .iter()
+            .flat_map(|op| match op.kind {
+                DiffOpKind::Equal => op.old.clone().map(Op::Keep).collect::<Vec<_>>(),
+                DiffOpKind::Delete => op.old.clone().map(Op::Remove).collect(),
+                DiffOpKind::Insert => op.new.clone().map(Op::Insert).collect(),
+            })
+            .collect();
+        assert_eq!(elementwise, diff_ops(&old, &new));
+    }
+
+    // Byte ranges cover whole chars
+    assert_eq!(
+        diff_text_ops("ab", "\u{e9}b"),
+        [
+            DiffOp {
+                kind: DiffOpKind::Delete,
+                old: 0..1,
+                new: 0..0
+            },
+            DiffOp {
+                kind: DiffOpKind::Insert,
+                old: 1..1,
+                new: 0..2
+            },
+            DiffOp {
+                kind: DiffOpKind::Equal,
+                old: 1..2,
+                new: 2..3
+            },
+        ]
+    );
+
+    let original = "a\nb\nc\nd\ne\n";
+    let modified = "a\nB\nc\ne\nf\n";
+    let diff = diff_lines(original, modified);
+    let ops = diff.ops();
+    assert_ops_tile(&ops, diff.old_lines().len(), diff.new_lines().len());
+    assert_eq!(
+        ops,
+        [
+            DiffOp {
+                kind: DiffOpKind::Equal,
+                old: 0..1,
+                new: 0..1
+            },
+            DiffOp {
+                kind: DiffOpKind::Delete,
+                old: 1..2,
+                new: 1..1
+            },
+            DiffOp {
+                kind: DiffOpKind::Insert,
+                old: 2..2,
+                new: 1..2
+            },
+            DiffOp {
+                kind: DiffOpKind::Equal,
+                old: 2..3,
+                new: 2..3
+            },
+            DiffOp {
+                kind: DiffOpKind::Delete,
+                old: 3..4,
+                new: 3..3
+            },
+            DiffOp {
+                kind: DiffOpKind::Equal,
+                old: 4..5,
+                new: 3..4
+            },
+            DiffOp {
+                kind: DiffOpKind::Insert,
+                old: 5..5,
+                new: 4..5
+            },
+        ]
+    );
+    assert!(diff_lines(original, original)
+        .ops()
+        .iter()
+        .all(|op| op.kind == DiffOpKind::Equal));
+}
+
+#[test]
+fn chunked_diff() {
+    // Pseudo-random bytes, so that chunk boundaries fall like they would in real binary files
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let old: Vec<u8> = (0..4 << 20)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect();
+
+    // Overwrite some bytes near the start and insert some near the end
+    let mut new = old.clone();
+    new[300_000..300_010].fill(0);
+    new.splice(3_900_000..3_900_000, b"inserted".iter().copied());
+
+    let changes = |ops: &[DiffOp]| -> Vec<DiffOp> {
+        assert_ops_tile(ops, old.len(), new.len());
+        for op in ops.iter().filter(|op| op.kind == DiffOpKind::Equal) {
+            assert!(old[op.old.clone()] == new[op.new.clone()]);
+        }
+        ops.iter()
+            .filter(|op| op.kind != DiffOpKind::Equal)
+            .cloned()
+            .collect()
+    };
+
+    // Small differing regions are refined down to the changed bytes
+    let mut opts = ChunkOptions::new();
+    let changed = changes(&diff_chunked(&old, &new, &opts));
+    let (first, second): (Vec<_>, Vec<_>) = changed.iter().partition(|op| op.old.start < 1 << 20);
+    assert!(first
+        .iter()
+        .all(|op| op.old.start >= 300_000 && op.old.end <= 300_010));
+    assert!(first
+        .iter()
+        .all(|op| op.new.start >= 300_000 && op.new.end <= 300_010));
+    assert!(second.iter().all(|op| op.kind == DiffOpKind::Insert));
+    assert_eq!(second.iter().map(|op| op.new.len()).sum::<usize>(), 8);
+    assert!(second[0].new.start.abs_diff(3_900_000) <= 8);
+
+    // Otherwise they're made up of the chunks around the changes
+    opts.set_max_refine_len(0);
+    let changed = changes(&diff_chunked(&old, &new, &opts));
+    assert_eq!(changed.len(), 4);
+    for (delete, insert) in [(&changed[0], &changed[1]), (&changed[2], &changed[3])] {
+        assert_eq!(delete.kind, DiffOpKind::Delete);
+        assert_eq!(insert.kind, DiffOpKind::Insert);
+        assert!(delete.old.len() <= 2 * 32 * 1024);
+    }
+    assert!(changed[0].old.start <= 300_000 && changed[0].old.end >= 300_010);
+    assert!(changed[3].new.start <= 3_900_000 && changed[3].new.end >= 3_900_008);
+
+    // Smaller chunks narrow the changes down further
+    opts.set_chunk_len(256);
+    let changed = changes(&diff_chunked(&old, &new, &opts));
+    assert!(changed.iter().all(|op| op.old.len() <= 4 * 1024));
+
+    assert_eq!(
+        diff_chunked(&old, &old, &opts),
+        [DiffOp {
+            kind: DiffOpKind::Equal,
+            old: 0..old.len(),
+            new: 0..old.len()
+        }]
+    );
+    assert_eq!(diff_chunked(b"", b"", &opts), []);
+    assert_eq!(
+        diff_chunked(b"", b"abc", &opts),
+        [DiffOp {
+            kind: DiffOpKind::Insert,
+            old: 0..0,
+            new: 0..3
+        }]
+    );
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn timeout() {
+    use std::time::Duration;
+
+    let original = "a\nb\nc\nd\ne\nf\ng\nh\n";
+    let modified = "a\nb\nC\nd\ne\nF\ng\nh\n";
+
+    let mut opts = DiffOptions::new();
+    opts.set_context_len(0);
+    assert_eq!(opts.create_patch(original, modified).hunks().len(), 2);
+
+    // Without any time to search, everything between the common prefix and suffix is replaced
+    opts.set_timeout(Some(Duration::ZERO));
+    let expected = "\
+--- original
++++ modified
+@@ -3,4 +3,4 @@
+-c
+-d
+-e
+-f
++C
++d
++e
++F
+";
+    let patch = opts.create_patch(original, modified);
+    assert_eq!(patch.to_string(), expected);
+    assert_eq!(apply(original, &patch).unwrap(), modified);
+
+    let solution = opts.diff("kitten", "sitting");
+    assert_eq!(solution, [Diff::Delete("kitten"), Diff::Insert("sitting")]);
+
+    // A generous timeout doesn't change the result
+    opts.set_timeout(Some(Duration::from_secs(3600)));
+    assert_eq!(opts.create_patch(original, modified).hunks().len(), 2);
+    opts.set_timeout(Some(Duration::MAX));
+    assert_eq!(opts.create_patch(original, modified).hunks().len(), 2);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn progress() {
+    use std::sync::{Arc, Mutex};
+
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<DiffOptions>();
+
+    let original: String = (0..600).map(|i| format!("{}\n", i * 7 % 300)).collect();
+    let modified: String = (0..600).map(|i| format!("{}\n", i * 11 % 300)).collect();
+
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&reported);
+    let mut opts = DiffOptions::new();
+    opts.on_progress(move |fraction| sink.lock().unwrap().push(fraction));
+
+    // Reporting progress doesn't change the diff
+    let patch = opts.create_patch(&original, &modified);
+    assert_eq!(patch, create_patch(&original, &modified));
+
+    // The estimate only ever grows, at least a percent at a time, and ends with the full diff
+    let fractions = std::mem::take(&mut *reported.lock().unwrap());
+    assert!(fractions.len() > 10, "{:?}", fractions);
+    assert!(fractions.len() <= 101, "{:?}", fractions);
+    let (last, steps) = fractions.split_last().unwrap();
+    assert!(steps.windows(2).all(|pair| pair[1] >= pair[0] + 0.01));
+    assert!(steps[0] > 0.0 && steps[steps.len() - 1] < 1.0);
+    assert_eq!(*last, 1.0);
+
+    // Identical inputs are done right away, and a diff which gives up isn't complete
+    opts.create_patch(&original, &original);
+    assert_eq!(*reported.lock().unwrap(), [1.0]);
+    reported.lock().unwrap().clear();
+
+    opts.set_max_changed_lines(Some(10));
+    assert!(opts.try_create_patch(&original, &modified).is_err());
+    assert!(!reported.lock().unwrap().contains(&1.0));
+}
+
+#[test]
+fn apply_ignoring_trailing_newline() {
+    use crate::apply::ApplyOptions;
+
+    let eol = |newline: bool| if newline { "\n" } else { "" };
+    let mut options = ApplyOptions::new();
+    options.set_ignore_trailing_newline(true);
+
+    for base_newline in [false, true] {
+        for old_newline in [false, true] {
+            for new_newline in [false, true] {
+                let base = format!("a\nb\nc{}", eol(base_newline));
+                let old = format!("a\nb\nc{}", eol(old_newline));
+                let new = format!("a\nb\nC{}", eol(new_newline));
+                let patch = create_patch(&old, &new);
+
+                // The base image's convention is kept unless the patch changes it
+                let expected_newline = if old_newline == new_newline {
+                    base_newline
+                } else {
+                    new_newline
+                };
+                let expected = format!("a\nb\nC{}", eol(expected_newline));
+                let case = (base_newline, old_newline, new_newline);
+
+                assert_eq!(
+                    options.apply(&base, &patch).unwrap(),
+                    expected,
+                    "{:?}",
+                    case
+                );
+                let bytes = create_patch_bytes(old.as_bytes(), new.as_bytes());
+                assert_eq!(
+                    options.apply_bytes(base.as_bytes(), &bytes).unwrap(),
+                    expected.as_bytes(),
+                    "{:?}",
+                    case
+                );
+
+                // Without the option the patch only applies if the base image agrees with it
+                let strict = apply(&base, &patch);
+                if base_newline == old_newline {
+                    assert_eq!(strict.unwrap(), new, "{:?}", case);
+                } else {
+                    assert!(strict.is_err(), "{:?}", case);
+                }
+            }
+        }
+    }
+
+    // Only the last line of the base image may differ in its trailing newline
+    let patch = create_patch("a\nb\n", "A\nb\n");
+    assert!(options.apply("a\nb\nc", &patch).is_ok());
+    let patch = Patch::from_str(
+        "--- a\n+++ b\n@@ -1,2 +1,2 @@\n-a\n\\ No newline at end of file\n+A\n b\n",
+    )
+    .unwrap();
+    assert!(options.apply("a\nb\n", &patch).is_err());
+}
+
+#[test]
+fn diff_elided() {
+    let mut original = String::new();
+    let mut modified = String::new();
+    for i in 0..100 {
+        original.push_str(&format!("line {}\n", i));
+        modified.push_str(&format!("line {}\n", if i == 50 { 5000 } else { i }));
+    }
+
+    let opts = DiffOptions::new();
+    let elided = opts.diff_elided(&original, &modified, 16);
+    let full = opts.diff(&original, &modified);
+    assert_eq!(elided.len(), full.len());
+    let prefix_len = original.find("line 50").unwrap() + "line 50".len();
+    assert_eq!(elided[0], ElidedDiff::Skip(prefix_len));
+    assert_eq!(elided[1], ElidedDiff::Insert("00"));
+    assert!(matches!(elided.last(), Some(ElidedDiff::Skip(_))));
+
+    // The lengths of the segments are enough to find each one in both texts
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for diff in &elided {
+        match *diff {
+            ElidedDiff::Equal(text) => {
+                assert_eq!(&original[old_pos..old_pos + text.len()], text);
+                assert_eq!(&modified[new_pos..new_pos + text.len()], text);
+                old_pos += text.len();
+                new_pos += text.len();
+            }
+            ElidedDiff::Delete(text) => {
+                assert_eq!(&original[old_pos..old_pos + text.len()], text);
+                old_pos += text.len();
+            }
+            ElidedDiff::Insert(text) => {
+                assert_eq!(&modified[new_pos..new_pos + text.len()], text);
+                new_pos += text.len();
+            }
+            ElidedDiff::Skip(len) => {
+                assert_eq!(
+                    original[old_pos..old_pos + len],
+                    modified[new_pos..new_pos + len]
+                );
+                old_pos += len;
+                new_pos += len;
+            }
+        }
+    }
+    assert_eq!((old_pos, new_pos), (original.len(), modified.len()));
+
+    // Nothing is elided below the minimum length
+    let elided = opts.diff_elided(&original, &modified, original.len() + 1);
+    assert!(!elided
+        .iter()
+        .any(|diff| matches!(diff, ElidedDiff::Skip(_))));
+}
+
+#[test]
+fn normalize_patch() {
+    let original = "a\nb\nc\nd\ne\nf\ng\n";
+    // Zero context hunks, as produced by `diff -U0`, for changes close enough to share a hunk
+    let s = "\
+--- a/file
++++ b/file
+@@ -2 +2 @@
+-b
++B
+@@ -4 +4 @@
+-d
++D
+";
+    let patch = Patch::from_str(s).unwrap();
+    let normalized = patch.normalize(original, &DiffOptions::new()).unwrap();
+    let expected = "\
+--- a/file
++++ b/file
+@@ -1,7 +1,7 @@
+ a
+-b
++B
+ c
+-d
++D
+ e
+ f
+ g
+";
+    assert_eq!(normalized.to_string(), expected);
+
+    let mut opts = DiffOptions::new();
+    opts.set_context_len(0);
+    assert_eq!(patch.normalize(original, &opts).unwrap(), patch);
+
+    // A patch which doesn't apply isn't normalized at all
+    let err = patch.normalize("a\nx\nc\nd\n", &opts).unwrap_err();
+    assert_eq!(err.hunk(), 1);
+}
+
+#[test]
+fn detect_moves() {
+    let original = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+    let modified = "a\nb\nc\nh\ni\nj\nd\ne\nf\ng\n";
+
+    // Moves aren't detected by default
+    assert!(diff_lines(original, modified).moves().is_empty());
+
+    let mut opts = DiffOptions::new();
+    opts.set_detect_moves(true);
+    let diff = opts.diff_lines(original, modified);
+    assert_eq!(
+        diff.moves(),
+        [MovedBlock {
+            old: 7..10,
+            new: 3..6
+        }]
+    );
+    // The diff itself is unchanged
+    assert_eq!(diff.to_patch(3), create_patch(original, modified));
+
+    // Blocks shorter than the window and blocks which changed on the way aren't moves
+    let diff = opts.diff_lines("a\nb\nc\nd\ne\n", "d\ne\nc\na\nB\n");
+    assert!(diff.moves().is_empty());
+
+    // A large file whose sections were shuffled around
+    let section = |name: &str| -> String {
+        (0..1000)
+            .map(|i| format!("{} line {}\n", name, i))
+            .collect()
+    };
+    let (a, b, c, d) = (section("a"), section("b"), section("c"), section("d"));
+    let original = [&a[..], &b, &c, &d].concat();
+    let modified = [&c[..], &a, &d, &b].concat();
+    let diff = opts.diff_lines(&original, &modified);
+
+    let moved: usize = diff.moves().iter().map(|m| m.old.len()).sum();
+    assert_eq!(moved, 2000);
+    for m in diff.moves() {
+        assert_eq!(m.old.len(), m.new.len());
+        assert_eq!(
+            diff.old_lines()[m.old.clone()],
+            diff.new_lines()[m.new.clone()]
+        );
+    }
+}
+
+#[test]
+fn annotate_history() {
+    let v0 = "fn main() {\n    setup();\n    run();\n}\n\nfn a() {}\nfn b() {}\nfn c() {}\n";
+    // Inserts a line and modifies another
+    let v1 = "fn main() {\n    setup();\n    log();\n    run(true);\n}\n\nfn a() {}\nfn b() {}\nfn c() {}\n";
+    // Moves `a`, `b` and `c` in front of `main`
+    let v2 = "fn a() {}\nfn b() {}\nfn c() {}\nfn main() {\n    setup();\n    log();\n    run(true);\n}\n\n";
+
+    let origin = |version, lineno| LineOrigin { version, lineno };
+    assert_eq!(
+        annotate(&[v0, v1, v2]),
+        [
+            origin(0, 6),
+            origin(0, 7),
+            origin(0, 8),
+            origin(0, 1),
+            origin(0, 2),
+            origin(1, 3),
+            origin(1, 4),
+            origin(0, 4),
+            origin(0, 5),
+        ]
+    );
+
+    // Without move detection the moved lines count as introduced by the version moving them
+    let origins = DiffOptions::new().annotate(&[v0, v1, v2]);
+    assert!(origins[..3].iter().all(|o| o.version == 2));
+
+    // Duplicate lines are attributed one by one
+    assert_eq!(
+        annotate(&["}\n}\n", "}\nx\n}\n}\n"]),
+        [origin(0, 1), origin(1, 2), origin(1, 3), origin(0, 2)]
+    );
+
+    assert!(annotate::<&str>(&[]).is_empty());
+    assert_eq!(annotate(&["a\n"]), [origin(0, 1)]);
+    assert!(annotate(&["a\n", ""]).is_empty());
+}
+
+#[test]
+fn diff_lines_in_range() {
+    let original: String = (0..20).map(|i| format!("line {}\n", i)).collect();
+    let modified = original
+        .replace("line 3\n", "three\n")
+        .replace("line 10\n", "ten\n11.5\n")
+        .replace("line 17\n", "");
+
+    // Only the change around line 10 is in the ranges, and the line inserted after it shifts
+    // the end of the new range by one
+    let patch = DiffOptions::new()
+        .set_context_len(2)
+        .diff_lines_range(&original, &modified, 6..14, 6..15)
+        .unwrap();
+    let expected = "\
+--- original
++++ modified
+@@ -9,5 +9,6 @@
+ line 8
+ line 9
+-line 10
++ten
++11.5
+ line 11
+ line 12
+";
+    assert_eq!(patch.to_string(), expected);
+
+    // The context mode is honored like when diffing the whole texts
+    let patch = DiffOptions::new()
+        .set_context_len(2)
+        .set_context_mode(ContextMode::LeadingOnly)
+        .diff_lines_range(&original, &modified, 6..14, 6..15)
+        .unwrap();
+    let expected = "\
+--- original
++++ modified
+@@ -9,3 +9,4 @@
+ line 8
+ line 9
+-line 10
++ten
++11.5
+";
+    assert_eq!(patch.to_string(), expected);
+
+    // The ranges don't need to start at the same line, and the patch is numbered against the
+    // whole texts even when the ranges cover a single change
+    let patch = diff_lines_range(&original, &modified, 17..18, 18..18).unwrap();
+    assert_eq!(patch.hunks().len(), 1);
+    assert_eq!(patch.hunks()[0].old_range(), HunkRange::new(18, 1));
+    assert_eq!(patch.hunks()[0].new_range(), HunkRange::new(18, 0));
+    let patch = diff_lines_range(&original, &modified, 0..0, 0..1).unwrap();
+    assert_eq!(patch.hunks()[0].old_range(), HunkRange::new(0, 0));
+    assert_eq!(patch.hunks()[0].new_range(), HunkRange::new(1, 1));
+
+    // Diffing the whole texts gives the same patch as `create_patch`
+    assert_eq!(
+        diff_lines_range(&original, &modified, 0..20, 0..20).unwrap(),
+        create_patch(&original, &modified)
+    );
+    assert!(diff_lines_range(&original, &modified, 5..5, 5..5)
+        .unwrap()
+        .hunks()
+        .is_empty());
+
+    let error = diff_lines_range(&original, &modified, 0..5, 10..21).unwrap_err();
+    assert_eq!(error.range(), 10..21);
+    assert_eq!(error.line_count(), 20);
+    #[allow(clippy::reversed_empty_ranges)]
+    let reversed = 5..4;
+    assert!(diff_lines_range(&original, &modified, reversed, 0..1).is_err());
+}
+
+#[test]
+fn squeeze_blank_lines() {
+    let mut opts = DiffOptions::new();
+    opts.set_squeeze_blank_lines(true);
+
+    // Runs of any length between identical content compare equal
+    let contents = ["a\n", "b\n", "c\n"];
+    for (old_run, new_run) in [(1, 2), (3, 1), (2, 5), (4, 4)] {
+        let original = contents.join(&"\n".repeat(old_run));
+        let modified = contents.join(&"\n".repeat(new_run));
+        assert!(opts.create_patch(&original, &modified).hunks().is_empty());
+        assert!(opts
+            .create_patch_bytes(original.as_bytes(), modified.as_bytes())
+            .hunks()
+            .is_empty());
+    }
+
+    // Adding or removing a blank line between two non-blank lines still shows, as does a line
+    // ending with "\r\n" next to one ending with "\n"
+    assert_eq!(opts.create_patch("a\nb\n", "a\n\n\nb\n").hunks().len(), 1);
+    assert_eq!(opts.create_patch("a\n\n\nb\n", "a\nb\n").hunks().len(), 1);
+    assert!(opts
+        .create_patch("a\r\n\r\n\r\nb\r\n", "a\r\n\r\nb\r\n")
+        .hunks()
+        .is_empty());
+    assert_eq!(
+        opts.create_patch("a\n\n\nb\n", "a\r\n\nb\n").hunks().len(),
+        1
+    );
+
+    // Hunks are numbered against the texts themselves and apply to the original. The runs of
+    // blank lines sharing a hunk with the change are changed along with it.
+    let original = "a\n\n\n\nb\nc\n\n\nd\n";
+    let modified = "a\n\nb\nC\n\nd\n";
+    let expected = "\
+--- original
++++ modified
+@@ -2,8 +2,5 @@
+
+-
+-
+ b
+-c
++C
+
+-
+ d
+";
+    opts.set_context_len(1);
+    let patch = opts.create_patch(original, modified);
+    assert_eq!(patch.to_string(), expected);
+    assert_eq!(apply(original, &patch).unwrap(), modified);
+
+    // Without squeezing, the removed blank lines are part of the patch
+    opts.set_squeeze_blank_lines(false);
+    let patch = opts.create_patch(original, modified);
+    assert_eq!(patch.hunks()[0].old_range(), HunkRange::new(2, 7));
+
+    // Runs of blank lines away from any change keep their length
+    opts.set_squeeze_blank_lines(true);
+    let original = "a\n\n\n\nb\nc\nx\ny\nd\ne\nf\n\n\ng\n";
+    let modified = "a\n\nb\nc\nx\ny\nD\ne\nf\n\n\n\ng\n";
+    for context_len in [0, 1] {
+        opts.set_context_len(context_len);
+        let patch = opts.create_patch(original, modified);
+        assert_eq!(patch.hunks().len(), 1);
+        assert_eq!(patch.hunks()[0].old_range().start(), 9 - context_len);
+        assert_eq!(patch.hunks()[0].new_range().start(), 7 - context_len);
+        assert_eq!(
+            apply(original, &patch).unwrap(),
+            "a\n\n\n\nb\nc\nx\ny\nD\ne\nf\n\n\ng\n"
+        );
+        let bytes = opts.create_patch_bytes(original.as_bytes(), modified.as_bytes());
+        assert_eq!(bytes.to_bytes(), patch.to_string().into_bytes());
+    }
+    let changed = DiffOptions::new()
+        .set_squeeze_blank_lines(true)
+        .added_lines_numbered(original, modified);
+    assert_eq!(changed, [(7, "D\n")]);
+}
+
+#[test]
+fn junk_lines() {
+    fn is_junk(line: &[u8]) -> bool {
+        line.iter().all(|b| b" \t\r\n{}".contains(b))
+    }
+
+    let original = "\
+fn main() {
+    start();
+}
+
+fn one() {
+    if a {
+        b();
+    }
+    c();
+}
+
+fn end() {}
+";
+    let modified = "\
+fn main() {
+    start();
+}
+
+fn two() {
+    if x {
+        y();
+    }
+    z();
+}
+
+fn end() {}
+";
+
+    // Without a junk predicate the closing brace in the middle of the rewritten function
+    // splits it up
+    let mut opts = DiffOptions::new();
+    opts.set_context_len(1);
+    let expected = "\
+--- original
++++ modified
+@@ -4,7 +4,7 @@
+
+-fn one() {
+-    if a {
+-        b();
++fn two() {
++    if x {
++        y();
+     }
+-    c();
++    z();
+ }
+";
+    assert_eq!(opts.create_patch(original, modified).to_string(), expected);
+
+    // Junk lines are only kept unchanged next to lines which match
+    opts.set_is_junk(Some(is_junk));
+    let expected = "\
+--- original
++++ modified
+@@ -4,7 +4,7 @@
+
+-fn one() {
+-    if a {
+-        b();
+-    }
+-    c();
++fn two() {
++    if x {
++        y();
++    }
++    z();
+ }
+";
+    assert_eq!(opts.create_patch(original, modified).to_string(), expected);
+    assert_eq!(
+        opts.create_patch_bytes(original.as_bytes(), modified.as_bytes())
+            .to_bytes(),
+        expected.as_bytes()
+    );
+
+    // Junk lines can still be inserted, deleted or unchanged between other lines
+    let patch = opts.create_patch("a\n\nb\n}\n", "a\n\n\nb\n");
+    assert_eq!(
+        patch.to_string(),
+        "--- original\n+++ modified\n@@ -2,3 +2,3 @@\n\n+\n b\n-}\n"
+    );
+    assert!(opts.create_patch(original, original).hunks().is_empty());
+
+    // The limit on changed lines counts junk lines which no longer match
+    opts.set_max_changed_lines(Some(9));
+    assert!(opts.try_create_patch(original, modified).is_err());
+    opts.set_max_changed_lines(Some(10));
+    assert!(opts.try_create_patch(original, modified).is_ok());
+}
+
+#[test]
+fn minimal() {
+    fn is_junk(line: &[u8]) -> bool {
+        line.iter().all(|b| b" \t\r\n{}".contains(b))
+    }
+
+    let original = "\
+fn one() {
+    if a {
+        b();
+    }
+    c();
+}
+";
+    let modified = "\
+fn two() {
+    if x {
+        y();
+    }
+    z();
+}
+";
+
+    // Junk lines keep the closing brace from matching, which deletes and inserts it
+    let mut opts = DiffOptions::new();
+    assert!(format!("{:?}", opts).contains("is_junk: false"));
+    opts.set_is_junk(Some(is_junk));
+    assert!(format!("{:?}", opts).contains("is_junk: true"));
+    assert_eq!(opts.diff_counts(original, modified), (1, 5, 5));
+
+    // A minimal diff matches it up within the change again
+    opts.set_minimal(true);
+    assert_eq!(opts.diff_counts(original, modified), (2, 4, 4));
+    let expected = "\
+--- original
++++ modified
+@@ -1,6 +1,6 @@
+-fn one() {
+-    if a {
+-        b();
++fn two() {
++    if x {
++        y();
+     }
+-    c();
++    z();
+ }
+";
+    let patch = opts.create_patch(original, modified);
+    assert_eq!(patch.to_string(), expected);
+    assert_eq!(apply(original, &patch).unwrap(), modified);
+
+    // Several lines shared by the deleted and inserted lines are matched up, in order
+    opts.set_is_junk(Some(|line| line.len() < 3));
+    let original = "a\nx\nb\ny\nc\n";
+    let modified = "d\nx\ne\ny\nf\n";
+    opts.set_minimal(false);
+    assert_eq!(opts.diff_counts(original, modified), (0, 5, 5));
+    opts.set_minimal(true);
+    assert_eq!(opts.diff_counts(original, modified), (2, 3, 3));
+
+    // Diffs which are minimal already don't change
+    let mut opts = DiffOptions::new();
+    let original = "a\nb\nc\nd\n";
+    let modified = "a\nc\nB\nd\ne\n";
+    let patch = opts.create_patch(original, modified);
+    opts.set_minimal(true);
+    assert_eq!(opts.create_patch(original, modified), patch);
+    assert_eq!(opts.diff("kitten", "sitting"), diff("kitten", "sitting"));
+}
+
+#[test]
+fn diff_lines_from_slices() {
+    // Splitting the joined texts would match up the lines inside of the logical lines
+    let original = ["let a = [\n    1,\n    2,\n];\n", "let b = 3;\n", "end\n"];
+    let modified = ["let a = [\n    1,\n    4,\n];\n", "let b = 3;\n", "end\n"];
+
+    let diff = super::diff_lines_from_slices(&original, &modified);
+    assert_eq!(diff.old_lines(), original);
+    assert_eq!(diff.new_lines(), modified);
+    let edits = diff.edit_script().edits;
+    assert_eq!(edits.len(), 1);
+    assert_eq!((edits[0].old.clone(), edits[0].new.clone()), (0..1, 0..1));
+
+    let patch = diff.to_patch(1);
+    assert_eq!(patch.hunks().len(), 1);
+    assert_eq!(
+        patch.hunks()[0].lines(),
+        [
+            Line::Delete(original[0]),
+            Line::Insert(modified[0]),
+            Line::Context("let b = 3;\n"),
+        ]
+    );
+    let (original_text, modified_text) = (original.concat(), modified.concat());
+    let joined = diff_lines(&original_text, &modified_text);
+    assert_eq!(joined.edit_script().edits[0].old, 2..3);
+
+    // The lines are borrowed rather than the slices holding them, while owned lines are
+    // borrowed from their slices
+    let diff = {
+        let original = original.to_vec();
+        super::diff_lines_from_slices(&original, &modified[1..])
+    };
+    assert_eq!(diff.new_lines(), &modified[1..]);
+    let owned: Vec<String> = modified.iter().map(|&line| line.to_owned()).collect();
+    let owned_diff = super::diff_lines_from_owned_slices(&original, &owned);
+    assert_eq!(
+        owned_diff.edit_script().edits,
+        super::diff_lines_from_slices(&original, &modified)
+            .edit_script()
+            .edits
+    );
+
+    // Slices which are real lines give the same result as splitting their joined texts
+    let original = ["a\n", "b\n", "\n", "\n", "c\n", "d"];
+    let modified = ["a\n", "B\n", "\n", "c\n", "d\n"];
+    let mut opts = DiffOptions::new();
+    for squeeze in [false, true] {
+        opts.set_squeeze_blank_lines(squeeze);
+        assert_eq!(
+            opts.diff_lines_from_slices(&original, &modified)
+                .to_patch(3)
+                .to_string(),
+            opts.create_patch(&original.concat(), &modified.concat())
+                .to_string()
+        );
+    }
+}
+
+#[test]
+fn strip_prefix_for_matching() {
+    let original = "\
+2024-01-01T10:00:00 server started
+2024-01-01T10:00:01 listening on :80
+2024-01-01T10:00:07 request /index
+2024-01-01T10:00:09 shutting down
+";
+    let modified = "\
+2024-03-05T08:12:44 server started
+2024-03-05T08:12:45 listening on :80
+2024-03-05T08:12:46 request /about
+2024-03-05T08:12:50 shutting down
+";
+    let expected = "\
+--- original
++++ modified
+@@ -2,3 +2,3 @@
+ 2024-03-05T08:12:45 listening on :80
+-2024-01-01T10:00:07 request /index
++2024-03-05T08:12:46 request /about
+ 2024-03-05T08:12:50 shutting down
+";
+
+    let mut options = DiffOptions::new();
+    options.set_context_len(1);
+    assert_eq!(options.create_patch(original, modified).hunks().len(), 1);
+    assert_eq!(diff_lines(original, modified).edit_script.len(), 1);
+    assert_eq!(diff_lines(original, modified).edit_script[0].old, 0..4);
+
+    // Timestamps of a fixed width, or up to the first space
+    options.set_strip_prefix_for_matching(Some(LinePrefix::Len(20)));
+    assert_eq!(
+        options.create_patch(original, modified).to_string(),
+        expected
+    );
+    assert_eq!(format!("{:?}", LinePrefix::Len(20)), "Len(20)");
+    let prefix = LinePrefix::Fn(|line| {
+        line.iter()
+            .position(|&b| b == b' ')
+            .map_or(0, |idx| idx + 1)
+    });
+    assert_eq!(format!("{:?}", prefix), "Fn(..)");
+    options.set_strip_prefix_for_matching(Some(prefix));
+    assert_eq!(
+        options.create_patch(original, modified).to_string(),
+        expected
+    );
+    assert_eq!(
+        options
+            .create_patch_bytes(original.as_bytes(), modified.as_bytes())
+            .to_bytes(),
+        expected.as_bytes()
+    );
+    #[cfg(feature = "std")]
+    {
+        let diff = diff_readers(original.as_bytes(), modified.as_bytes(), &options).unwrap();
+        assert_eq!(diff.to_patch(1).unwrap().to_bytes(), expected.as_bytes());
+    }
+
+    // Prefixes don't include line endings and don't split chars
+    options.set_strip_prefix_for_matching(Some(LinePrefix::Len(3)));
+    assert!(options.create_patch("ab\n", "cd\n").hunks().is_empty());
+    assert_eq!(options.create_patch("ab\n", "cd").hunks().len(), 1);
+    assert!(options
+        .create_patch("a\u{e9}x\n", "b\u{e8}x\n")
+        .hunks()
+        .is_empty());
+    assert_eq!(
+        options
+            .create_patch("a\u{e9}x\n", "b\u{e8}y\n")
+            .hunks()
+            .len(),
+        1
+    );
+
+    // Other options apply to the rest of the line
+    options.set_ignore_indentation(true).set_ignore_case(true);
+    assert!(options
+        .create_patch("01 \tFoo\n", "02   fOO\n")
+        .hunks()
+        .is_empty());
+}
+
+#[test]
+fn ignore_trailing_chars() {
+    let original = "\
+{
+  \"name\": \"diffy\",
+  \"keywords\": [\"diff\", \"patch\"]
+}
+";
+    let modified = "\
+{
+  \"name\": \"diffy\",
+  \"keywords\": [\"diff\", \"patch\"],
+  \"license\": \"MIT\"
+}
+";
+    let expected = "\
+--- original
++++ modified
+@@ -3,0 +4 @@
++  \"license\": \"MIT\"
+";
+
+    let mut options = DiffOptions::new();
+    options.set_context_len(0);
+    assert_eq!(
+        options.create_patch(original, modified).hunks()[0]
+            .lines()
+            .len(),
+        3
+    );
+
+    // Unchanged lines are output as they appear in the modified text
+    options.set_ignore_trailing_chars(",");
+    assert_eq!(
+        options.create_patch(original, modified).to_string(),
+        expected
+    );
+    assert_eq!(
+        options
+            .create_patch_bytes(original.as_bytes(), modified.as_bytes())
+            .to_bytes(),
+        expected.as_bytes()
+    );
+    #[cfg(feature = "std")]
+    {
+        let diff = diff_readers(original.as_bytes(), modified.as_bytes(), &options).unwrap();
+        assert_eq!(diff.to_patch(0).unwrap().to_bytes(), expected.as_bytes());
+    }
+
+    // Any number of chars from the set are trimmed in any order, but nothing else
+    options.set_ignore_trailing_chars(";,\u{3002}");
+    assert!(options.create_patch("f(a);\n", "f(a)\n").hunks().is_empty());
+    assert!(options.create_patch("x,;,\n", "x\n").hunks().is_empty());
+    assert!(options
+        .create_patch("\u{6587}\u{3002}\n", "\u{6587}\n")
+        .hunks()
+        .is_empty());
+    assert_eq!(options.create_patch("x, \n", "x\n").hunks().len(), 1);
+    assert_eq!(options.create_patch(",x\n", "x\n").hunks().len(), 1);
+    assert!(options
+        .create_patch_bytes(b"\xff;\r\n", b"\xff\r\n")
+        .hunks()
+        .is_empty());
+
+    // Line endings are still compared
+    assert_eq!(options.create_patch("x;\n", "x").hunks().len(), 1);
+    assert_eq!(options.create_patch("x;\n", "x\r\n").hunks().len(), 1);
+
+    // Other options apply as well
+    options.set_ignore_indentation(true).set_ignore_case(true);
+    assert!(options
+        .create_patch("  Foo,\n", "\tfoo\n")
+        .hunks()
+        .is_empty());
+
+    options.set_ignore_trailing_chars("");
+    assert_eq!(options.create_patch("  Foo,\n", "\tfoo\n").hunks().len(), 1);
+}
+
+#[test]
+fn differs_by_more_than() {
+    let original = "a\nb\nc\nd\ne\nf\n";
+    let cases = [
+        ("a\nb\nc\nd\ne\nf\n", 0),
+        ("a\nb\nc\nd\ne\n", 1),
+        ("a\nB\nc\nd\ne\nf\n", 2),
+        ("x\na\nb\nc\nD\ne\n", 4),
+        ("f\ne\nd\nc\nb\na\n", 10),
+        ("", 6),
+    ];
+    for (modified, changed_lines) in cases {
+        let diff = diff_lines(original, modified);
+        let counted: usize = diff
+            .edit_script
+            .iter()
+            .map(|edit| edit.old.len() + edit.new.len())
+            .sum();
+        assert_eq!(counted, changed_lines, "{:?}", modified);
+
+        assert!(!super::differs_by_more_than(
+            original,
+            modified,
+            changed_lines
+        ));
+        assert!(!super::differs_by_more_than(
+            modified,
+            original,
+            changed_lines
+        ));
+        assert!(!differs_by_more_than_bytes(
+            original.as_bytes(),
+            modified.as_bytes(),
+            changed_lines
+        ));
+        if let Some(fewer) = changed_lines.checked_sub(1) {
+            assert!(super::differs_by_more_than(original, modified, fewer));
+            assert!(super::differs_by_more_than(modified, original, fewer));
+            assert!(differs_by_more_than_bytes(
+                original.as_bytes(),
+                modified.as_bytes(),
+                fewer
+            ));
+        }
+    }
+
+    // Lines are compared with the configured options
+    let mut options = DiffOptions::new();
+    assert!(options.differs_by_more_than("a\nb\n", "A\nB\n", 3));
+    options.set_ignore_case(true);
+    assert!(!options.differs_by_more_than("a\nb\n", "A\nB\n", 0));
+
+    let old = [1, 2, 3, 4, 5];
+    assert!(!differs_by_more_than_slices(&old, &[1, 3, 4, 6], 3));
+    assert!(differs_by_more_than_slices(&old, &[1, 3, 4, 6], 2));
+    assert!(!differs_by_more_than_slices::<u8>(&[], &[], 0));
+}
+
+#[test]
+fn diff_counts() {
+    let original = "a\nb\nc\nd\ne\nf\n";
+    let cases = [
+        ("a\nb\nc\nd\ne\nf\n", (6, 0, 0)),
+        ("a\nb\nc\nd\ne\n", (5, 0, 1)),
+        ("a\nB\nc\nd\ne\nf\n", (5, 1, 1)),
+        ("x\na\nb\nc\nD\ne\n", (4, 2, 2)),
+        ("a\nb\nc\nd\ne\nf", (5, 1, 1)),
+        ("", (0, 0, 6)),
+    ];
+    for (modified, counts) in cases {
+        assert_eq!(
+            super::diff_counts(original, modified),
+            counts,
+            "{:?}",
+            modified
+        );
+        assert_eq!(
+            diff_counts_bytes(original.as_bytes(), modified.as_bytes()),
+            counts
+        );
+        let (equal, inserted, deleted) = counts;
+        assert_eq!(
+            super::diff_counts(modified, original),
+            (equal, deleted, inserted)
+        );
+
+        let diff = diff_lines(original, modified);
+        assert_eq!(diff.metrics().matched, equal);
+        assert_eq!(equal + inserted, diff.new_lines().len());
+        assert_eq!(equal + deleted, diff.old_lines().len());
+    }
+    assert_eq!(super::diff_counts("", ""), (0, 0, 0));
+
+    // Lines are compared with the configured options, including for identical texts
+    let mut options = DiffOptions::new();
+    assert_eq!(options.diff_counts("a\nb\n", "A\nb\n"), (1, 1, 1));
+    options.set_ignore_case(true);
+    assert_eq!(options.diff_counts("a\nb\n", "A\nb\n"), (2, 0, 0));
+    options.set_squeeze_blank_lines(true);
+    assert_eq!(
+        options.diff_counts("a\n\n\n\nb\n", "a\n\n\n\nb\n"),
+        (3, 0, 0)
+    );
+}
+
+#[test]
+fn changed_lines() {
+    let original = "a\nb\nc\nd\ne\nf\n";
+    let modified = "x\na\nb\nC\nd\nf\ny";
+
+    assert_eq!(added_lines(original, modified), ["x\n", "C\n", "y"]);
+    assert_eq!(removed_lines(original, modified), ["c\n", "e\n"]);
+    let options = DiffOptions::new();
+    assert_eq!(
+        options.added_lines_numbered(original, modified),
+        [(1, "x\n"), (4, "C\n"), (7, "y")]
+    );
+    assert_eq!(
+        options.removed_lines_numbered(original, modified),
+        [(3, "c\n"), (5, "e\n")]
+    );
+
+    // The lines are the ones a patch inserts and deletes
+    let patch = create_patch(original, modified);
+    let lines = || patch.hunks().iter().flat_map(|hunk| hunk.lines());
+    let inserted: Vec<_> = lines()
+        .filter(|l| l.is_insert())
+        .map(|l| l.value())
+        .collect();
+    let deleted: Vec<_> = lines()
+        .filter(|l| l.is_delete())
+        .map(|l| l.value())
+        .collect();
+    assert_eq!(added_lines(original, modified), inserted);
+    assert_eq!(removed_lines(original, modified), deleted);
+
+    assert!(added_lines(original, original).is_empty());
+    assert!(removed_lines(original, original).is_empty());
+    assert_eq!(added_lines("", "a\n"), ["a\n"]);
+    assert_eq!(removed_lines("a\n", ""), ["a\n"]);
+
+    // Lines are compared with the configured options, but returned as they are
+    let mut options = DiffOptions::new();
+    options.set_ignore_case(true);
+    assert_eq!(options.added_lines("a\nb\n", "A\nb\nC\n"), ["C\n"]);
+    assert_eq!(options.removed_lines("A\nb\nc\n", "a\nB\n"), ["c\n"]);
+
+    // Any slice works
+    let old = [1, 2, 3, 4, 5];
+    let new = [0, 1, 2, 4, 5, 6];
+    assert_eq!(added_items(&old, &new), [&0, &6]);
+    assert_eq!(removed_items(&old, &new), [&3]);
+    assert!(added_items(&old, &old).is_empty());
+    let new: Vec<String> = ["b", "x"].iter().map(|s| s.to_string()).collect();
+    let old: Vec<String> = ["a", "b"].iter().map(|s| s.to_string()).collect();
+    assert_eq!(added_items(&old, &new), [&new[1]]);
+    assert_eq!(removed_items(&old, &new), [&old[0]]);
+}
+
+#[test]
+fn record_separator() {
+    type Edits = Vec<(ops::Range<usize>, ops::Range<usize>)>;
+
+    fn records<'a, T: ?Sized + Text>(
+        options: &DiffOptions,
+        original: &'a T,
+        modified: &'a T,
+    ) -> (Vec<&'a T>, Edits) {
+        let diff =
+            options.diff_line_slices(options.split_lines(original), options.split_lines(modified));
+        let edits = diff
+            .edit_script
+            .iter()
+            .map(|edit| (edit.old.clone(), edit.new.clone()))
+            .collect();
+        (diff.old_lines().to_vec(), edits)
+    }
+
+    let mut options = DiffOptions::new();
+
+    // NUL-separated records, where the last one has no separator
+    options.set_record_separator(RecordSep::Byte(b'\0'));
+    let original = "x\0y\nz\0w";
+    assert_eq!(
+        records(&options, original, "x\0y\nz\0w\0"),
+        (vec!["x\0", "y\nz\0", "w"], vec![(2..3, 2..3)])
+    );
+    assert_eq!(
+        records(&options, original.as_bytes(), b"v\0x\0y\nz\0w"),
+        (vec![&b"x\0"[..], b"y\nz\0", b"w"], vec![(0..0, 0..1)])
+    );
+    assert_eq!(records(&options, "", "").0, Vec::<&str>::new());
+
+    // Bytes in the middle of a char don't separate records of a `str`
+    options.set_record_separator(RecordSep::Byte(0x82));
+    assert_eq!(records(&options, "a\u{20ac}b", "").0, ["a\u{20ac}b"]);
+    assert_eq!(
+        records(&options, "a\u{20ac}b".as_bytes(), b"").0,
+        [&b"a\xe2\x82"[..], b"\xacb"]
+    );
+
+    options.set_record_separator(RecordSep::Str("--"));
+    assert_eq!(records(&options, "a--b---c", "").0, ["a--", "b--", "-c"]);
+    options.set_record_separator(RecordSep::Str(""));
+    assert_eq!(records(&options, "a\nb\n", "").0, ["a\nb\n"]);
+
+    // Paragraphs keep the blank lines following them, and only empty lines are blank
+    options.set_record_separator(RecordSep::Paragraph);
+    let original = "\n\nFirst\nparagraph\n\n\nSecond\n \nparagraph\r\n\r\nLast";
+    let modified = "\n\nFirst\nparagraph\n\n\nSecond\n \nparagraph\r\n\r\nLast\n";
+    let (lines, edits) = records(&options, original, modified);
+    assert_eq!(
+        lines,
+        [
+            "\n\n",
+            "First\nparagraph\n\n\n",
+            "Second\n \nparagraph\r\n\r\n",
+            "Last"
+        ]
+    );
+    assert_eq!(lines.concat(), original);
+    assert_eq!(edits, [(3..4, 3..4)]);
+
+    let original = "One\n\nTwo\n\nThree\n";
+    let modified = "One\n\nTwo, edited\n\nThree\n\nFour\n";
+    let diff = options.diff_lines(original, modified);
+    assert_eq!(diff.new_lines().concat(), modified);
+    assert_eq!(
+        diff.ops(),
+        [
+            DiffOp {
+                kind: DiffOpKind::Equal,
+                old: 0..1,
+                new: 0..1,
+            },
+            DiffOp {
+                kind: DiffOpKind::Delete,
+                old: 1..3,
+                new: 1..1,
+            },
+            DiffOp {
+                kind: DiffOpKind::Insert,
+                old: 3..3,
+                new: 1..4,
+            },
+        ]
+    );
+}
+
+#[test]
+fn changed_line_pairs() {
+    assert!(diff_lines("a\nb\n", "a\nb\n")
+        .changed_line_pairs()
+        .is_empty());
+    assert!(diff_lines("a\n", "a\nb\n").changed_line_pairs().is_empty());
+
+    // Changes with as many deleted as inserted lines are paired up in order, however
+    // dissimilar the lines are
+    let pairs = diff_lines("a\nabcd\nwxyz\nz\n", "a\nabce\r\nqrst\nz\n").changed_line_pairs();
+    assert_eq!(
+        pairs,
+        [("abcd\n", "abce\r\n", 0.75), ("wxyz\n", "qrst\n", 0.0)]
+    );
+    let pairs = diff_lines("line\r\n", "line").changed_line_pairs();
+    assert_eq!(pairs, [("line\r\n", "line", 1.0)]);
+
+    // Otherwise lines are paired with the most similar lines which keep the pairs in order
+    let original = "{\nfoo(1);\nbar(2);\n}\n";
+    let modified = "{\nnew();\nfoo(10);\nother();\nbaz(2);\n}\n";
+    let pairs = diff_lines(original, modified).changed_line_pairs();
+    let lines: Vec<_> = pairs.iter().map(|&(old, new, _)| (old, new)).collect();
+    assert_eq!(
+        lines,
+        [("foo(1);\n", "foo(10);\n"), ("bar(2);\n", "baz(2);\n")]
+    );
+    assert!(pairs.iter().all(|&(_, _, similarity)| similarity > 0.7));
+    let pairs = diff_lines("x\nabc\ny\n", "x\nd\nab\ne\ny\n").changed_line_pairs();
+    assert_eq!(pairs, [("abc\n", "ab\n", 0.8)]);
+    let pairs = diff_lines("x\nabc\ny\n", "x\nd\ne\ny\n").changed_line_pairs();
+    assert!(pairs.is_empty());
+
+    // Large changes are paired up in order
+    let modified: String = (0..2000).map(|i| format!("{}\n", i)).collect();
+    let pairs = diff_lines("x\ny\n", &modified).changed_line_pairs();
+    assert_eq!(pairs, [("x\n", "0\n", 0.0), ("y\n", "1\n", 0.0)]);
+
+    // Long lines with few changes are compared exactly, and ones with many changes only by
+    // their common prefix and suffix
+    let old_line = format!("<{}>\n", "ab".repeat(50_000));
+    let new_line = format!("<{}>\n", "ab".repeat(50_000).replacen("ab", "cd", 10));
+    let pairs = diff_lines(&old_line, &new_line).changed_line_pairs();
+    assert_eq!(pairs[0].2, 1.0 - 20.0 / 100_002.0);
+    let new_line = format!("<{}>\n", "cd".repeat(50_000));
+    let pairs = diff_lines(&old_line, &new_line).changed_line_pairs();
+    assert_eq!(pairs[0].2, 2.0 / 100_002.0);
+}
+
+#[test]
+fn context_mode() {
+    // Changes to the first and last lines, with changes separated by 3 and 2 unchanged lines
+    let original = "a\nb\nc\nd\ne\nf\ng\nh\n";
+    let modified = "A\nb\nc\nd\nE\nf\ng\nH\n";
+    let mut opts = DiffOptions::new();
+    opts.set_context_len(2);
+
+    let cases = [
+        (
+            ContextMode::Both,
+            "\
+--- original
++++ modified
+@@ -1,8 +1,8 @@
+-a
++A
+ b
+ c
+ d
+-e
++E
+ f
+ g
+-h
++H
+",
+        ),
+        (
+            ContextMode::LeadingOnly,
+            "\
+--- original
++++ modified
+@@ -1 +1 @@
+-a
++A
+@@ -3,6 +3,6 @@
+ c
+ d
+-e
++E
+ f
+ g
+-h
++H
+",
+        ),
+        (
+            ContextMode::TrailingOnly,
+            "\
+--- original
++++ modified
+@@ -1,3 +1,3 @@
+-a
++A
+ b
+ c
+@@ -5,4 +5,4 @@
+-e
++E
+ f
+ g
+-h
++H
+",
+        ),
+    ];
+    for (mode, expected) in cases {
+        opts.set_context_mode(mode);
+        let patch = opts.create_patch(original, modified);
+        assert_eq!(patch.to_string(), expected, "{:?}", mode);
+        assert_eq!(apply(original, &patch).unwrap(), modified);
+        assert_eq!(
+            opts.try_create_patch_bytes(original.as_bytes(), modified.as_bytes())
+                .unwrap()
+                .to_bytes(),
+            expected.as_bytes()
+        );
+        assert_eq!(
+            patch.normalize(original, &opts).unwrap().to_string(),
+            expected
+        );
+    }
+
+    // Without any context, only changes next to each other share a hunk
+    opts.set_context_len(0);
+    for mode in [ContextMode::LeadingOnly, ContextMode::TrailingOnly] {
+        opts.set_context_mode(mode);
+        let patch = opts.create_patch(original, "A\nB\nc\nd\ne\nf\nG\nH\n");
+        let headers: Vec<_> = patch.hunks().iter().map(|h| h.to_string()).collect();
+        assert_eq!(patch.hunks().len(), 2);
+        assert!(headers[0].starts_with("@@ -1,2 +1,2 @@"), "{:?}", headers);
+        assert!(headers[1].starts_with("@@ -7,2 +7,2 @@"), "{:?}", headers);
+    }
+
+    // Combined diffs show context on the same sides
+    let ours = "A\nb\nc\nd\ne\nf\ng\nh\n";
+    opts.set_context_len(1)
+        .set_context_mode(ContextMode::LeadingOnly);
+    let expected = "\
+@@@ -1 -1 +1 @@@
+ -a
+ +A
+@@@ -4,2 -4,2 +4,2 @@@
+  d
+- e
+ -e
+++E
+@@@ -7,2 -7,2 +7,2 @@@
+  g
+- h
+ -h
+++H
+";
+    assert_eq!(
+        opts.combined_diff(ours, original, modified).to_string(),
+        expected
+    );
+}
+
+// Runs `diff -U<context_len>` from GNU diffutils on two texts, returning its output without the
+// `---`/`+++` header lines, which contain the files' paths and modification times
+#[cfg(feature = "gnu-diff-tests")]
+fn gnu_diff(original: &str, modified: &str, context_len: usize) -> String {
+    use std::{fs, process::Command, sync::atomic};
+
+    static COUNTER: atomic::AtomicUsize = atomic::AtomicUsize::new(0);
+    let id = COUNTER.fetch_add(1, atomic::Ordering::Relaxed);
+    let dir = std::env::temp_dir().join(format!("diffy-gnu-diff-{}-{}", std::process::id(), id));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("original"), original).unwrap();
+    fs::write(dir.join("modified"), modified).unwrap();
+    let output = Command::new("diff")
+        .arg(format!("-U{}", context_len))
+        .arg(dir.join("original"))
+        .arg(dir.join("modified"))
+        .output()
+        .expect("the `diff` binary from GNU diffutils is required");
+    fs::remove_dir_all(&dir).unwrap();
+
+    let output = String::from_utf8(output.stdout).unwrap();
+    output.splitn(3, '\n').nth(2).unwrap_or("").to_owned()
+}
+
+// Pairs of texts covering the edge cases of grouping changes into hunks
+#[cfg(feature = "gnu-diff-tests")]
+fn hunk_corpus() -> Vec<(String, String)> {
+    let lines = |range: std::ops::Range<usize>| -> String {
+        range.map(|i| format!("line {}\n", i)).collect()
+    };
+    let base = lines(0..20);
+    vec![
+        // Empty files, and adding or removing every line
+        (String::new(), String::new()),
+        (String::new(), "a\n".to_owned()),
+        ("a\n".to_owned(), String::new()),
+        (String::new(), base.clone()),
+        (base.clone(), String::new()),
+        // Single line changes, where the length is left out of the header
+        ("a\n".to_owned(), "b\n".to_owned()),
+        (base.clone(), base.replace("line 0\n", "zero\n")),
+        (base.clone(), base.replace("line 19\n", "nineteen\n")),
+        // Pure insertions and deletions, which have an empty range on one side
+        (base.clone(), format!("new\n{}", base)),
+        (base.clone(), format!("{}new\n", base)),
+        (base.clone(), base.replace("line 10\n", "line 10\nnew\n")),
+        (base.clone(), base.replace("line 0\n", "")),
+        (base.clone(), base.replace("line 19\n", "")),
+        (base.clone(), base.replace("line 7\nline 8\n", "")),
+        // Changes separated by gaps around twice the context length, which decide whether
+        // hunks are merged
+        (
+            base.clone(),
+            base.replace("line 2\n", "2\n").replace("line 8\n", "8\n"),
+        ),
+        (
+            base.clone(),
+            base.replace("line 2\n", "2\n").replace("line 9\n", "9\n"),
+        ),
+        (
+            base.clone(),
+            base.replace("line 2\n", "2\n").replace("line 10\n", "10\n"),
+        ),
+        (
+            base.clone(),
+            base.replace("line 4\n", "").replace("line 6\n", ""),
+        ),
+        (
+            base.clone(),
+            base.replace("line 4\n", "").replace("line 5\n", "5\n"),
+        ),
+        // Missing newlines at the end of the file
+        ("a\nb".to_owned(), "a\nb\n".to_owned()),
+        ("a\nb\n".to_owned(), "a\nc".to_owned()),
+        ("a".to_owned(), "b".to_owned()),
+        ("a".to_owned(), String::new()),
+        (
+            format!("{}end", base),
+            format!("{}end", base.replace("line 17\n", "")),
+        ),
+        // Changes at both ends and in the middle
+        (
+            base.clone(),
+            format!(
+                "first\n{}{}last\n",
+                lines(1..9),
+                lines(12..19).replace("line 14\n", "")
+            ),
+        ),
+    ]
+}
+
+#[test]
+fn iter_hunks() {
+    let original = "a\nb\nc\nd\ne\nf\ng\nh\n";
+    let modified = "A\nb\nc\nd\ne\nf\ng\nH\n";
+    let ranges = |hunks: &[Hunk<'_, str>]| {
+        hunks
+            .iter()
+            .map(|hunk| (hunk.old_range(), hunk.new_range()))
+            .collect::<Vec<_>>()
+    };
+
+    let diff = diff_lines(original, modified);
+    let hunks: Vec<_> = diff.iter_hunks(1).collect();
+    assert_eq!(
+        ranges(&hunks),
+        [
+            (HunkRange::new(1, 2), HunkRange::new(1, 2)),
+            (HunkRange::new(7, 2), HunkRange::new(7, 2)),
+        ]
+    );
+    assert_eq!(
+        hunks[0].lines(),
+        [
+            Line::Delete("a\n"),
+            Line::Insert("A\n"),
+            Line::Context("b\n")
+        ]
+    );
+    assert_eq!(
+        hunks[1].lines(),
+        [
+            Line::Context("g\n"),
+            Line::Delete("h\n"),
+            Line::Insert("H\n")
+        ]
+    );
+
+    // The context of the two changes overlaps
+    let hunks: Vec<_> = diff.iter_hunks(3).collect();
+    assert_eq!(
+        ranges(&hunks),
+        [(HunkRange::new(1, 8), HunkRange::new(1, 8))]
+    );
+    assert_eq!(hunks[0].lines().len(), 10);
+    assert_eq!(
+        hunks[0].lines()[..2],
+        [Line::Delete("a\n"), Line::Insert("A\n")]
+    );
+    assert_eq!(
+        hunks[0].lines()[8..],
+        [Line::Delete("h\n"), Line::Insert("H\n")]
+    );
+
+    // Without merging, the unchanged lines between the changes are split between their hunks
+    let diff = DiffOptions::new()
+        .set_merge_hunks(false)
+        .diff_lines(original, modified);
+    let hunks: Vec<_> = diff.iter_hunks(3).collect();
+    assert_eq!(
+        ranges(&hunks),
+        [
+            (HunkRange::new(1, 4), HunkRange::new(1, 4)),
+            (HunkRange::new(5, 4), HunkRange::new(5, 4)),
+        ]
+    );
+    assert_eq!(
+        hunks[1].lines(),
+        [
+            Line::Context("e\n"),
+            Line::Context("f\n"),
+            Line::Context("g\n"),
+            Line::Delete("h\n"),
+            Line::Insert("H\n"),
+        ]
+    );
+
+    assert_eq!(diff_lines(original, original).iter_hunks(3).count(), 0);
+}
+
+#[cfg(feature = "gnu-diff-tests")]
+#[test]
+fn hunk_headers_match_gnu_diff() {
+    for (original, modified) in &hunk_corpus() {
+        for context_len in [0, 1, 2, 3, 5] {
+            let patch = DiffOptions::new()
+                .set_context_len(context_len)
+                .create_patch(original, modified);
+            let patch = patch.to_string();
+            let hunks = patch.splitn(3, '\n').nth(2).unwrap();
+            assert_eq!(
+                hunks,
+                gnu_diff(original, modified, context_len),
+                "context {} for {:?} -> {:?}",
+                context_len,
+                original,
+                modified
+            );
+        }
+    }
+}
+
+#[test]
+fn append_diff() {
+    let original = "\
+starting up
+listening on :8080
+request /
+request /about
+shutting down
+";
+
+    // Lines are appended one piece at a time, like a log being written to
+    let mut diff = AppendDiff::new(original, "");
+    assert_eq!(
+        diff.to_patch(3).to_string(),
+        create_patch(original, "").to_string()
+    );
+    for piece in [
+        "starting up\nlisten",
+        "ing on :8080\n",
+        "request /\n",
+        "request /contact\nre",
+        "quest /about\n",
+    ] {
+        diff.append_new(piece);
+        let modified = diff.modified();
+        assert_eq!(
+            diff.to_patch(3).to_string(),
+            create_patch(original, modified).to_string(),
+            "after appending {:?}",
+            piece,
+        );
+    }
+
+    // Lines past the end of the original text are only ever inserted
+    diff.append_new("shutting down\nstopped\n");
+    let expected = "\
+--- original
++++ modified
+@@ -2,4 +2,6 @@
+ listening on :8080
+ request /
++request /contact
+ request /about
+ shutting down
++stopped
+";
+    assert_eq!(diff.to_patch(2).to_string(), expected);
+    assert_eq!(diff.original(), original);
+    assert_eq!(apply(original, &diff.to_patch(0)).unwrap(), diff.modified());
+
+    // Appending nothing doesn't change anything
+    diff.append_new("");
+    assert_eq!(diff.to_patch(2).to_string(), expected);
+    let lines = diff.diff_lines();
+    assert_eq!(lines.old_lines().len(), 5);
+    assert_eq!(lines.new_lines().len(), 7);
+}
+
+#[test]
+fn append_diff_long_texts() {
+    let original: String = (0..500).map(|i| format!("line {}\n", i)).collect();
+
+    // Lines missing from the original text extend the lines inserted at its end
+    let mut diff = AppendDiff::new(&original, "");
+    let mut modified = String::new();
+    for i in 0..500 {
+        let line = format!("other {}\n", i);
+        diff.append_new(&line);
+        modified.push_str(&line);
+    }
+    assert_eq!(diff.modified(), modified);
+    assert_eq!(
+        diff.to_patch(3).to_string(),
+        create_patch(&original, &modified).to_string()
+    );
+
+    // Lines far ahead in the original text are matched up once enough lines are appended
+    let mut diff = AppendDiff::new(&original, "");
+    let mut modified = String::new();
+    for i in (0..100).chain(300..500) {
+        if i % 7 == 3 {
+            diff.append_new("extra\n");
+            modified.push_str("extra\n");
+        }
+        let line = format!("line {}\n", i);
+        diff.append_new(&line);
+        modified.push_str(&line);
+    }
+    assert_eq!(diff.modified(), modified);
+    assert_eq!(
+        diff.to_patch(3).to_string(),
+        create_patch(&original, &modified).to_string()
+    );
+}
+
+#[test]
+fn append_diff_repeated_lines() {
+    // Lines only occurring before the ones left to diff are inserted without diffing them again
+    let mut diff = AppendDiff::new("heartbeat\nstart\nend\n", "heartbeat\nstart\n");
+    let mut modified = String::from("heartbeat\nstart\n");
+    for _ in 0..5000 {
+        diff.append_new("heartbeat\n");
+        modified.push_str("heartbeat\n");
+    }
+    assert_eq!(diff.modified(), modified);
+    let patch = diff.to_patch(0);
+    assert_eq!(patch.hunks().len(), 1);
+    assert_eq!(patch.hunks()[0].old_range(), HunkRange::new(3, 1));
+    assert_eq!(patch.hunks()[0].new_range(), HunkRange::new(3, 5000));
+
+    // They're still matched up once a line ahead of them is appended
+    diff.append_new("end\n");
+    modified.push_str("end\n");
+    assert_eq!(
+        diff.to_patch(3).to_string(),
+        create_patch("heartbeat\nstart\nend\n", &modified).to_string()
+    );
+}
+
+#[test]
+fn ignore_comment_changes() {
+    let original = "\
+# Copyright 2023 Example
+# Licensed under MIT
+
+[server]
+host = localhost
+port = 80
+workers = 4
+timeout = 30
+retries = 3
+log = info
+";
+    let bumped = original.replace("2023", "2024");
+
+    let mut opts = DiffOptions::new();
+    opts.set_ignore_comment_changes(true);
+    assert!(opts.create_patch(original, &bumped).hunks().is_empty());
+    assert_eq!(opts.diff_lines(original, &bumped).hunk_count(3), 0);
+    #[cfg(feature = "std")]
+    {
+        let diff = diff_readers(original.as_bytes(), bumped.as_bytes(), &opts).unwrap();
+        assert_eq!(diff.hunk_count(3), 0);
+        assert!(diff.to_patch(3).unwrap().hunks().is_empty());
+    }
+
+    // A change far enough from the header keeps its hunk, the header's is left out
+    let modified = bumped.replace("log = info", "log = debug");
+    let expected = "\
+--- original
++++ modified
+@@ -7,4 +7,4 @@
+ workers = 4
+ timeout = 30
+ retries = 3
+-log = info
++log = debug
+";
+    assert_eq!(opts.create_patch(original, &modified).to_string(), expected);
+    assert_eq!(opts.diff_lines(original, &modified).hunk_count(3), 1);
+    assert_eq!(
+        opts.diff_lines_range(original, &modified, 0..10, 0..10)
+            .unwrap()
+            .to_string(),
+        expected
+    );
+
+    // A hunk which also changes other lines is kept whole, comments included
+    let modified = bumped.replace("[server]", "[http]");
+    let patch = opts.create_patch(original, &modified);
+    assert_eq!(patch, create_patch(original, &modified));
+
+    // Comments are recognized after indentation, and hunks which only insert comments are
+    // left out as well
+    let original = "fn main() {\n    run();\n}\n";
+    let modified = "// Entry point\nfn main() {\n    // Start\n    run();\n}\n";
+    assert!(opts.create_patch(original, modified).hunks().is_empty());
+    opts.set_comment_prefixes(&["--"]);
+    assert_eq!(
+        opts.create_patch(original, modified),
+        create_patch(original, modified)
+    );
+    assert!(opts
+        .create_patch("a\n-- one\nb\n", "a\n-- two\nb\n")
+        .hunks()
+        .is_empty());
+
+    // Blank lines deleted or inserted along with comments are left out with them, while
+    // hunks which only change blank lines are kept unless blank lines are squeezed
+    let mut opts = DiffOptions::new();
+    opts.set_ignore_comment_changes(true);
+    let changes = [
+        ("a\n# x\nb\n", "a\n# y\n\nb\n", 0, 0),
+        ("a\n# one\n\nb\n", "a\n# two\n\n\n\nb\n", 0, 0),
+        ("a\n\n# x\nb\n", "a\nb\n", 0, 0),
+        ("a\n\nb\n", "a\n\n\nb\n", 1, 0),
+        ("a\nb\n", "a\n\nb\n", 1, 1),
+        ("a\n# x\nb\n", "a\n# y\n\nB\n", 1, 1),
+        // A blank line changed apart from the comments is still left out with them
+        ("# 2023\nk\n\nv\n", "# 2024\nk\nv\n", 0, 0),
+        ("# 2023\nk\n\nv\n", "# 2023\nk\nv\n", 1, 1),
+    ];
+    for (original, modified, hunks, squeezed_hunks) in changes {
+        opts.set_squeeze_blank_lines(false);
+        assert_eq!(opts.create_patch(original, modified).hunks().len(), hunks);
+        opts.set_squeeze_blank_lines(true);
+        assert_eq!(
+            opts.create_patch(original, modified).hunks().len(),
+            squeezed_hunks
+        );
+    }
+
+    // Without merging, each change is judged on its own
+    let original = "# one\na\nb\n";
+    let modified = "# two\na\nB\n";
+    opts.set_merge_hunks(false).set_context_len(0);
+    let diff = opts.diff_lines(original, modified);
+    assert_eq!(diff.hunk_count(0), 1);
+    assert_eq!(diff.to_patch(0).hunks()[0].old_range().start(), 3);
+}
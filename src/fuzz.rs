@@ -0,0 +1,206 @@
+//! Structure-aware [`Arbitrary`] implementations for fuzzing
+
+use crate::{
+    diff::DiffOptions,
+    patch::{Hunk, HunkRange, Line, LineKind, Patch},
+};
+use alloc::{string::String, vec, vec::Vec};
+use arbitrary::{Arbitrary, Result, Unstructured};
+use core::ops::ControlFlow;
+
+// Used when the fuzzer's input doesn't provide a complete line
+const FALLBACK_LINES: &[&str] = &["\n", "a\n", "b\n", "c\n", "foo\n", "bar\n"];
+
+// Produces a single line, including its terminating newline, borrowed from the fuzzer's input
+fn arbitrary_line<'a>(u: &mut Unstructured<'a>) -> Result<&'a str> {
+    let s = <&'a str>::arbitrary(u)?;
+    match s.find('\n') {
+        Some(idx) => Ok(&s[..=idx]),
+        None => u.choose(FALLBACK_LINES).copied(),
+    }
+}
+
+// Produces a filename which survives a round trip through the patch parser
+fn arbitrary_filename<'a>(u: &mut Unstructured<'a>, fallback: &'static str) -> Result<&'a str> {
+    let s = <&'a str>::arbitrary(u)?;
+    if !s.is_empty() && s.bytes().all(|b| b.is_ascii_graphic()) {
+        Ok(s)
+    } else {
+        Ok(fallback)
+    }
+}
+
+/// Generates a `Line` whose contents end in a newline
+impl<'a> Arbitrary<'a> for Line<'a, str> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let line = arbitrary_line(u)?;
+        let kind = *u.choose(&[LineKind::Context, LineKind::Delete, LineKind::Insert])?;
+        Ok(Line::new(kind, line))
+    }
+}
+
+/// Generates a non-empty `HunkRange` starting at line 1 or later
+impl<'a> Arbitrary<'a> for HunkRange {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let start = u.int_in_range(1..=u16::MAX as usize)?;
+        let len = u.int_in_range(1..=u16::MAX as usize)?;
+        Ok(HunkRange::new(start, len))
+    }
+}
+
+/// Generates a `Hunk` whose ranges agree with its lines
+///
+/// Every generated hunk starts with a context line so that neither of its ranges is empty.
+impl<'a> Arbitrary<'a> for Hunk<'a, str> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let old_start = u.int_in_range(1..=u16::MAX as usize)?;
+        let new_start = u.int_in_range(1..=u16::MAX as usize)?;
+        arbitrary_hunk(u, old_start, new_start)
+    }
+}
+
+fn arbitrary_hunk<'a>(
+    u: &mut Unstructured<'a>,
+    old_start: usize,
+    new_start: usize,
+) -> Result<Hunk<'a, str>> {
+    let mut lines = vec![Line::Context(arbitrary_line(u)?)];
+    u.arbitrary_loop(None, Some(16), |u| {
+        lines.push(Line::arbitrary(u)?);
+        Ok(ControlFlow::Continue(()))
+    })?;
+
+    let old_len = lines.iter().filter(|l| !l.is_insert()).count();
+    let new_len = lines.iter().filter(|l| !l.is_delete()).count();
+    Ok(Hunk::new(
+        HunkRange::new(old_start, old_len),
+        HunkRange::new(new_start, new_len),
+        None,
+        lines,
+    ))
+}
+
+/// Generates a `Patch` with increasing, non-overlapping hunks
+impl<'a> Arbitrary<'a> for Patch<'a, str> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let original = arbitrary_filename(u, "original")?;
+        let modified = arbitrary_filename(u, "modified")?;
+
+        let mut hunks: Vec<Hunk<'a, str>> = Vec::new();
+        let mut old_pos = 1;
+        let mut new_pos = 1;
+        u.arbitrary_loop(Some(1), Some(8), |u| {
+            // Lines between hunks are unchanged so both sides advance by the same amount
+            let gap = u.int_in_range(0..=64)?;
+            let hunk = arbitrary_hunk(u, old_pos + gap, new_pos + gap)?;
+            old_pos = hunk.old_range().end() + 1;
+            new_pos = hunk.new_range().end() + 1;
+            hunks.push(hunk);
+            Ok(ControlFlow::Continue(()))
+        })?;
+
+        Ok(Patch::new(Some(original), Some(modified), hunks))
+    }
+}
+
+/// A base text along with a `Patch` which is guaranteed to apply to it cleanly
+///
+/// ```
+/// use arbitrary::{Arbitrary, Unstructured};
+/// use diffy::{apply, ApplicablePatch};
+///
+/// let data = [7u8; 256];
+/// let case = ApplicablePatch::arbitrary(&mut Unstructured::new(&data)).unwrap();
+/// assert_eq!(apply(&case.base, &case.patch).unwrap(), case.modified);
+/// ```
+#[derive(Debug)]
+pub struct ApplicablePatch<'a> {
+    /// The text the patch applies to
+    pub base: String,
+    /// The result of applying `patch` to `base`
+    pub modified: String,
+    /// The patch between `base` and `modified`
+    pub patch: Patch<'a, str>,
+}
+
+impl<'a> Arbitrary<'a> for ApplicablePatch<'a> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut old_lines = Vec::new();
+        let mut new_lines = Vec::new();
+        u.arbitrary_loop(None, Some(64), |u| {
+            match u.int_in_range(0..=3)? {
+                0 => old_lines.push(arbitrary_line(u)?),
+                1 => new_lines.push(arbitrary_line(u)?),
+                _ => {
+                    let line = arbitrary_line(u)?;
+                    old_lines.push(line);
+                    new_lines.push(line);
+                }
+            }
+            Ok(ControlFlow::Continue(()))
+        })?;
+
+        let base = old_lines.concat();
+        let modified = new_lines.concat();
+        let context_len = u.int_in_range(0..=5)?;
+        let patch = DiffOptions::new()
+            .diff_split_lines(old_lines, new_lines)
+            .to_patch(context_len);
+
+        Ok(Self {
+            base,
+            modified,
+            patch,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apply;
+
+    // A tiny xorshift generator so the tests don't need a source of randomness
+    fn inputs() -> impl Iterator<Item = Vec<u8>> {
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        (0..500).map(move |i| {
+            (0..(i * 7) % 2048)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    state as u8
+                })
+                .collect()
+        })
+    }
+
+    #[test]
+    fn patch_round_trips() {
+        for data in inputs() {
+            let patch = Patch::arbitrary(&mut Unstructured::new(&data)).unwrap();
+            for hunk in patch.hunks() {
+                assert!(!hunk.old_range().is_empty() && !hunk.new_range().is_empty());
+            }
+            for pair in patch.hunks().windows(2) {
+                assert!(pair[0].old_range().end() < pair[1].old_range().start());
+                assert!(pair[0].new_range().end() < pair[1].new_range().start());
+            }
+
+            let s = patch.to_string();
+            assert_eq!(Patch::from_str(&s).unwrap(), patch);
+        }
+    }
+
+    #[test]
+    fn applicable_patch_applies() {
+        for data in inputs() {
+            let case = ApplicablePatch::arbitrary(&mut Unstructured::new(&data)).unwrap();
+            assert_eq!(apply(&case.base, &case.patch).unwrap(), case.modified);
+            assert_eq!(
+                apply(&case.modified, &case.patch.reverse()).unwrap(),
+                case.base
+            );
+        }
+    }
+}
@@ -0,0 +1,1560 @@
+//! Character based patches which still apply after the text they were made against has changed,
+//! in the style of [diff-match-patch]
+//!
+//! A [`FuzzyPatch`] is made of small hunks, each anchored by a character offset and a few
+//! characters of context on either side of its changes. When it's applied the context and
+//! deleted text of each hunk are searched for near its expected position with the Bitap fuzzy
+//! matching algorithm, so a hunk still applies when the text has been edited elsewhere, or even
+//! slightly edited where the hunk goes. Each hunk succeeds or fails on its own, which makes
+//! these patches a good fit for syncing the edits of several people working on the same text.
+//!
+//! ```
+//! use diffy::fuzzy::FuzzyPatch;
+//!
+//! let patch = FuzzyPatch::make(
+//!     "The quick brown fox jumps over the lazy dog.",
+//!     "That quick brown fox jumped over a lazy dog.",
+//! );
+//!
+//! // Someone else changed the text in the meantime
+//! let (text, applied) = patch.apply("The quick red rabbit jumps over the tired tiger.");
+//! assert_eq!(text, "That quick red rabbit jumped over a tired tiger.");
+//! assert_eq!(applied, [true, true]);
+//! ```
+//!
+//! Patches are converted to and from text in the format used by every implementation of
+//! diff-match-patch, so they can be exchanged with them:
+//!
+//! ```
+//! use diffy::fuzzy::FuzzyPatch;
+//!
+//! let patch = FuzzyPatch::make("Hello world", "Hello, world!");
+//! let text = patch.to_string();
+//! assert_eq!(text, "@@ -2,10 +2,12 @@\n ello\n+,\n  world\n+!\n");
+//! assert_eq!(text.parse::<FuzzyPatch>().unwrap(), patch);
+//! ```
+//!
+//! Offsets and lengths count Unicode scalar values, like the Python implementation of
+//! diff-match-patch. The JavaScript and Java implementations count UTF-16 code units instead,
+//! which only differ for text outside of the Basic Multilingual Plane, e.g. emoji.
+//!
+//! [diff-match-patch]: https://github.com/google/diff-match-patch
+
+use crate::{
+    diff::{DiffOpKind, DiffOptions},
+    range::DiffRange,
+};
+use alloc::{collections::BTreeMap, string::String, vec, vec::Vec};
+use core::{cmp, fmt, ops, str::FromStr};
+
+type Diffs = Vec<(DiffOpKind, Vec<char>)>;
+
+// The number of characters of context added on each side of a hunk
+const MARGIN: usize = 4;
+// The longest pattern Bitap matching can search for, which also bounds the size of a hunk
+const MAX_BITS: usize = 32;
+// The cost of an edit in characters, when deciding whether to merge edits around an equality
+const EDIT_COST: usize = 4;
+
+/// A collection of options for applying a [`FuzzyPatch`]
+///
+/// [`FuzzyPatch`]: struct.FuzzyPatch.html
+#[derive(Clone, Debug)]
+pub struct FuzzyOptions {
+    match_threshold: f64,
+    match_distance: usize,
+    delete_threshold: f64,
+}
+
+impl FuzzyOptions {
+    /// Construct a new `FuzzyOptions` with default settings
+    ///
+    /// ## Defaults
+    /// * match_threshold = 0.5
+    /// * match_distance = 1000
+    /// * delete_threshold = 0.5
+    pub fn new() -> Self {
+        Self {
+            match_threshold: 0.5,
+            match_distance: 1000,
+            delete_threshold: 0.5,
+        }
+    }
+
+    /// Set how closely the text has to match a hunk for it to apply, from `0.0` for an exact
+    /// match to `1.0` for anything at all
+    ///
+    /// The score of a match is the fraction of characters which differ, plus its distance
+    /// from the hunk's expected position divided by the [match distance].
+    ///
+    /// [match distance]: #method.set_match_distance
+    pub fn set_match_threshold(&mut self, match_threshold: f64) -> &mut Self {
+        self.match_threshold = match_threshold;
+        self
+    }
+
+    /// Set how far from its expected position a hunk can be found
+    ///
+    /// A match this many characters away scores a whole `1.0` on top of its differences, so
+    /// with the default threshold of `0.5` an exact match has to be within half as many
+    /// characters. A distance of `0` only allows matches at the expected position.
+    pub fn set_match_distance(&mut self, match_distance: usize) -> &mut Self {
+        self.match_distance = match_distance;
+        self
+    }
+
+    /// Set how much the text deleted by a large hunk may differ from the text being deleted,
+    /// from `0.0` for not at all to `1.0` for entirely
+    ///
+    /// This only applies to hunks longer than the 32 characters which can be matched at once,
+    /// which are located by matching their start and end separately.
+    pub fn set_delete_threshold(&mut self, delete_threshold: f64) -> &mut Self {
+        self.delete_threshold = delete_threshold;
+        self
+    }
+
+    /// Apply `patch` to `text`, returning the patched text along with whether each of the
+    /// patch's hunks could be applied
+    pub fn apply(&self, patch: &FuzzyPatch, text: &str) -> (String, Vec<bool>) {
+        if patch.hunks.is_empty() {
+            return (text.into(), Vec::new());
+        }
+
+        let mut hunks = patch.hunks.clone();
+        let padding = add_padding(&mut hunks);
+        let mut text: Vec<char> = padding
+            .iter()
+            .copied()
+            .chain(text.chars())
+            .chain(padding.iter().copied())
+            .collect();
+        split_max(&mut hunks);
+
+        // The offset between where hunks are expected and where they've been found
+        let mut delta: isize = 0;
+        let mut results = Vec::with_capacity(hunks.len());
+        for hunk in &hunks {
+            let expected = hunk.start2 as isize + delta;
+            let text1 = diff_text1(&hunk.diffs);
+
+            // A hunk too long for Bitap is located by matching its start and end separately
+            let (start, end) = if text1.len() > MAX_BITS {
+                let start = self.match_main(&text, &text1[..MAX_BITS], expected);
+                let end = start.and_then(|start| {
+                    let expected_end = expected + (text1.len() - MAX_BITS) as isize;
+                    self.match_main(&text, &text1[text1.len() - MAX_BITS..], expected_end)
+                        .filter(|&end| start < end)
+                });
+                match end {
+                    Some(end) => (start, Some(end)),
+                    None => (None, None),
+                }
+            } else {
+                (self.match_main(&text, &text1, expected), None)
+            };
+
+            let start = match start {
+                Some(start) => start,
+                None => {
+                    results.push(false);
+                    delta -= hunk.length2 as isize - hunk.length1 as isize;
+                    continue;
+                }
+            };
+            delta = start as isize - expected;
+            let start = cmp::min(start, text.len());
+
+            let found_end = match end {
+                Some(end) => end + MAX_BITS,
+                None => start + text1.len(),
+            };
+            let text2 = &text[start..cmp::min(found_end, text.len())];
+            if text1 == text2 {
+                text.splice(start..start + text1.len(), diff_text2(&hunk.diffs));
+                results.push(true);
+                continue;
+            }
+
+            // The text changed where the hunk goes, so its edits are mapped onto the text
+            let mut diffs = char_diff(&text1, text2);
+            if text1.len() > MAX_BITS
+                && levenshtein(&diffs) as f64 / text1.len() as f64 > self.delete_threshold
+            {
+                results.push(false);
+                continue;
+            }
+            cleanup_semantic_lossless(&mut diffs);
+            let mut index1 = 0;
+            for (kind, data) in &hunk.diffs {
+                match kind {
+                    DiffOpKind::Equal => {}
+                    DiffOpKind::Insert => {
+                        let index2 = cmp::min(start + x_index(&diffs, index1), text.len());
+                        text.splice(index2..index2, data.iter().copied());
+                    }
+                    DiffOpKind::Delete => {
+                        let index2 = cmp::min(start + x_index(&diffs, index1), text.len());
+                        let end = start + x_index(&diffs, index1 + data.len());
+                        text.drain(index2..cmp::max(index2, cmp::min(end, text.len())));
+                    }
+                }
+                if *kind != DiffOpKind::Delete {
+                    index1 += data.len();
+                }
+            }
+            results.push(true);
+        }
+
+        let text = text[padding.len()..text.len() - padding.len()]
+            .iter()
+            .collect();
+        (text, results)
+    }
+
+    // Finds the best match for `pattern` in `text` near `loc`
+    fn match_main(&self, text: &[char], pattern: &[char], loc: isize) -> Option<usize> {
+        let loc = cmp::min(cmp::max(loc, 0) as usize, text.len());
+        if text == pattern {
+            Some(0)
+        } else if text.is_empty() {
+            None
+        } else if text[loc..].starts_with(pattern) {
+            Some(loc)
+        } else {
+            self.match_bitap(text, pattern, loc)
+        }
+    }
+
+    // Finds the best fuzzy match for `pattern` in `text` near `loc` with the Bitap algorithm,
+    // where a match's score combines its errors and its distance from `loc`
+    fn match_bitap(&self, text: &[char], pattern: &[char], loc: usize) -> Option<usize> {
+        let mut alphabet = BTreeMap::new();
+        for (i, &c) in pattern.iter().enumerate() {
+            *alphabet.entry(c).or_insert(0u64) |= 1 << (pattern.len() - i - 1);
+        }
+
+        let score = |errors: usize, x: usize| {
+            let accuracy = errors as f64 / pattern.len() as f64;
+            let proximity = loc.abs_diff(x);
+            if self.match_distance == 0 {
+                if proximity == 0 {
+                    accuracy
+                } else {
+                    1.0
+                }
+            } else {
+                accuracy + proximity as f64 / self.match_distance as f64
+            }
+        };
+
+        // An exact match near `loc` gives a first bound on the score
+        let mut threshold = self.match_threshold;
+        if let Some(exact) = find(text, pattern, loc) {
+            threshold = threshold.min(score(0, exact));
+            if let Some(exact) = rfind(text, pattern, loc + pattern.len()) {
+                threshold = threshold.min(score(0, exact));
+            }
+        }
+
+        let match_mask = 1u64 << (pattern.len() - 1);
+        let mut best = None;
+        let mut bin_max = pattern.len() + text.len();
+        let mut last_rd: Vec<u64> = Vec::new();
+        for errors in 0..pattern.len() {
+            // Binary search for how far from `loc` a match with this many errors can be
+            let (mut bin_min, mut bin_mid) = (0, bin_max);
+            while bin_min < bin_mid {
+                if score(errors, loc + bin_mid) <= threshold {
+                    bin_min = bin_mid;
+                } else {
+                    bin_max = bin_mid;
+                }
+                bin_mid = (bin_max - bin_min) / 2 + bin_min;
+            }
+            bin_max = bin_mid;
+
+            let mut start = cmp::max(1, loc as isize - bin_mid as isize + 1) as usize;
+            let finish = cmp::min(loc + bin_mid, text.len()) + pattern.len();
+            let mut rd = vec![0u64; finish + 2];
+            rd[finish + 1] = (1 << errors) - 1;
+            let mut j = finish;
+            while j >= start {
+                let char_match = text
+                    .get(j - 1)
+                    .and_then(|c| alphabet.get(c))
+                    .copied()
+                    .unwrap_or(0);
+                rd[j] = ((rd[j + 1] << 1) | 1) & char_match;
+                if errors > 0 {
+                    let last = |i: usize| last_rd.get(i).copied().unwrap_or(0);
+                    rd[j] |= (((last(j + 1) | last(j)) << 1) | 1) | last(j + 1);
+                }
+
+                if rd[j] & match_mask != 0 {
+                    let score = score(errors, j - 1);
+                    if score <= threshold {
+                        threshold = score;
+                        best = Some(j - 1);
+                        if j - 1 > loc {
+                            // Keep looking on the other side of `loc`, no further away
+                            start = cmp::max(1, 2 * loc as isize - (j - 1) as isize) as usize;
+                        } else {
+                            break;
+                        }
+                    }
+                }
+                j -= 1;
+            }
+
+            // No match with more errors can beat the one found
+            if score(errors + 1, loc) > threshold {
+                break;
+            }
+            last_rd = rd;
+        }
+        best
+    }
+}
+
+impl Default for FuzzyOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A patch made of hunks anchored by character offsets, which can be applied to a text that
+/// has drifted from the one the patch was made against
+///
+/// See the [module documentation](index.html) for an overview.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FuzzyPatch {
+    hunks: Vec<FuzzyHunk>,
+}
+
+/// A single hunk of a [`FuzzyPatch`]
+///
+/// [`FuzzyPatch`]: struct.FuzzyPatch.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FuzzyHunk {
+    diffs: Diffs,
+    start1: usize,
+    start2: usize,
+    length1: usize,
+    length2: usize,
+}
+
+impl FuzzyHunk {
+    /// Returns the range of characters the hunk covers in the original text
+    pub fn old_range(&self) -> ops::Range<usize> {
+        self.start1..self.start1 + self.length1
+    }
+
+    /// Returns the range of characters the hunk covers in the modified text
+    pub fn new_range(&self) -> ops::Range<usize> {
+        self.start2..self.start2 + self.length2
+    }
+
+    // Surrounds the hunk with enough context to make its text unique within `text`, plus a
+    // margin, without growing it past what Bitap can match
+    fn add_context(&mut self, text: &[char]) {
+        if text.is_empty() {
+            return;
+        }
+
+        let slice = |start: usize, end: usize| {
+            &text[cmp::min(start, text.len())..cmp::min(end, text.len())]
+        };
+        let mut pattern = slice(self.start2, self.start2 + self.length1);
+        let mut padding = 0;
+        while find(text, pattern, 0) != rfind(text, pattern, text.len())
+            && pattern.len() < MAX_BITS - MARGIN - MARGIN
+        {
+            padding += MARGIN;
+            pattern = slice(
+                self.start2.saturating_sub(padding),
+                self.start2 + self.length1 + padding,
+            );
+        }
+        padding += MARGIN;
+
+        let prefix = slice(self.start2.saturating_sub(padding), self.start2);
+        if !prefix.is_empty() {
+            self.diffs.insert(0, (DiffOpKind::Equal, prefix.to_vec()));
+        }
+        let suffix = slice(
+            self.start2 + self.length1,
+            self.start2 + self.length1 + padding,
+        );
+        if !suffix.is_empty() {
+            self.diffs.push((DiffOpKind::Equal, suffix.to_vec()));
+        }
+
+        self.start1 -= prefix.len();
+        self.start2 -= prefix.len();
+        self.length1 += prefix.len() + suffix.len();
+        self.length2 += prefix.len() + suffix.len();
+    }
+}
+
+impl FuzzyPatch {
+    /// Make a patch which turns `original` into `modified`
+    pub fn make(original: &str, modified: &str) -> Self {
+        let old: Vec<char> = original.chars().collect();
+        let new: Vec<char> = modified.chars().collect();
+        let mut diffs = char_diff(&old, &new);
+        if diffs.len() > 2 {
+            cleanup_semantic(&mut diffs);
+            cleanup_efficiency(&mut diffs);
+        }
+        Self::from_diffs(&old, &diffs)
+    }
+
+    // Groups the diffs into hunks, splitting them wherever there's enough unchanged text
+    fn from_diffs(text1: &[char], diffs: &Diffs) -> Self {
+        let mut hunks = Vec::new();
+        let mut hunk = FuzzyHunk {
+            diffs: Vec::new(),
+            start1: 0,
+            start2: 0,
+            length1: 0,
+            length2: 0,
+        };
+        let (mut count1, mut count2) = (0, 0);
+        // The context of a hunk comes from the text as it is with the previous hunks applied
+        let mut prepatch = text1.to_vec();
+        let mut postpatch = text1.to_vec();
+
+        for (index, (kind, data)) in diffs.iter().enumerate() {
+            if hunk.diffs.is_empty() && *kind != DiffOpKind::Equal {
+                hunk.start1 = count1;
+                hunk.start2 = count2;
+            }
+
+            match kind {
+                DiffOpKind::Insert => {
+                    hunk.diffs.push((*kind, data.clone()));
+                    hunk.length2 += data.len();
+                    postpatch.splice(count2..count2, data.iter().copied());
+                }
+                DiffOpKind::Delete => {
+                    hunk.length1 += data.len();
+                    hunk.diffs.push((*kind, data.clone()));
+                    postpatch.drain(count2..count2 + data.len());
+                }
+                DiffOpKind::Equal => {
+                    if data.len() <= 2 * MARGIN
+                        && !hunk.diffs.is_empty()
+                        && index + 1 != diffs.len()
+                    {
+                        hunk.diffs.push((*kind, data.clone()));
+                        hunk.length1 += data.len();
+                        hunk.length2 += data.len();
+                    } else if data.len() >= 2 * MARGIN && !hunk.diffs.is_empty() {
+                        hunk.add_context(&prepatch);
+                        let next = FuzzyHunk {
+                            diffs: Vec::new(),
+                            start1: 0,
+                            start2: 0,
+                            length1: 0,
+                            length2: 0,
+                        };
+                        hunks.push(core::mem::replace(&mut hunk, next));
+                        prepatch = postpatch.clone();
+                        count1 = count2;
+                    }
+                }
+            }
+
+            if *kind != DiffOpKind::Insert {
+                count1 += data.len();
+            }
+            if *kind != DiffOpKind::Delete {
+                count2 += data.len();
+            }
+        }
+
+        if !hunk.diffs.is_empty() {
+            hunk.add_context(&prepatch);
+            hunks.push(hunk);
+        }
+        Self { hunks }
+    }
+
+    /// Apply the patch to `text` with the default [`FuzzyOptions`], returning the patched text
+    /// along with whether each of the patch's hunks could be applied
+    ///
+    /// [`FuzzyOptions`]: struct.FuzzyOptions.html
+    pub fn apply(&self, text: &str) -> (String, Vec<bool>) {
+        FuzzyOptions::default().apply(self, text)
+    }
+
+    /// Returns the hunks in the patch
+    pub fn hunks(&self) -> &[FuzzyHunk] {
+        &self.hunks
+    }
+}
+
+impl fmt::Display for FuzzyPatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let coords = |f: &mut fmt::Formatter<'_>, start: usize, len: usize| match len {
+            0 => write!(f, "{},0", start),
+            1 => write!(f, "{}", start + 1),
+            _ => write!(f, "{},{}", start + 1, len),
+        };
+
+        for hunk in &self.hunks {
+            write!(f, "@@ -")?;
+            coords(f, hunk.start1, hunk.length1)?;
+            write!(f, " +")?;
+            coords(f, hunk.start2, hunk.length2)?;
+            writeln!(f, " @@")?;
+
+            for (kind, data) in &hunk.diffs {
+                let sign = match kind {
+                    DiffOpKind::Equal => ' ',
+                    DiffOpKind::Delete => '-',
+                    DiffOpKind::Insert => '+',
+                };
+                write!(f, "{}", sign)?;
+                let mut buf = [0; 4];
+                for c in data {
+                    for &byte in c.encode_utf8(&mut buf).as_bytes() {
+                        if byte.is_ascii_alphanumeric() || b"-_.!~*'();/?:@&=+$,# ".contains(&byte)
+                        {
+                            write!(f, "{}", byte as char)?;
+                        } else {
+                            write!(f, "%{:02X}", byte)?;
+                        }
+                    }
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An error returned when parsing a [`FuzzyPatch`] from text fails
+///
+/// [`FuzzyPatch`]: struct.FuzzyPatch.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseFuzzyPatchError {
+    line: usize,
+    reason: &'static str,
+}
+
+impl ParseFuzzyPatchError {
+    /// Returns the 1-based number of the malformed line
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl fmt::Display for ParseFuzzyPatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid fuzzy patch on line {}: {}",
+            self.line, self.reason
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseFuzzyPatchError {}
+
+impl FromStr for FuzzyPatch {
+    type Err = ParseFuzzyPatchError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut hunks: Vec<FuzzyHunk> = Vec::new();
+        if s.is_empty() {
+            return Ok(Self { hunks });
+        }
+
+        for (index, line) in s.split('\n').enumerate() {
+            let error = |reason| ParseFuzzyPatchError {
+                line: index + 1,
+                reason,
+            };
+            let mut chars = line.chars();
+            let kind = match chars.next() {
+                Some('@') => {
+                    let (start1, length1, start2, length2) =
+                        parse_header(line).ok_or_else(|| error("invalid hunk header"))?;
+                    hunks.push(FuzzyHunk {
+                        diffs: Vec::new(),
+                        start1,
+                        start2,
+                        length1,
+                        length2,
+                    });
+                    continue;
+                }
+                Some(' ') => DiffOpKind::Equal,
+                Some('-') => DiffOpKind::Delete,
+                Some('+') => DiffOpKind::Insert,
+                None => continue,
+                Some(_) => return Err(error("line doesn't start with ' ', '-', '+' or '@'")),
+            };
+            let data = percent_decode(chars.as_str()).ok_or_else(|| error("invalid escape"))?;
+            hunks
+                .last_mut()
+                .ok_or_else(|| error("line outside of a hunk"))?
+                .diffs
+                .push((kind, data.chars().collect()));
+        }
+
+        Ok(Self { hunks })
+    }
+}
+
+// Parses a `@@ -a,b +c,d @@` header into 0-based starts and lengths, where a missing length
+// is 1 and a length of 0 places the start after the line it refers to
+fn parse_header(line: &str) -> Option<(usize, usize, usize, usize)> {
+    let ranges = line.strip_prefix("@@ -")?.strip_suffix(" @@")?;
+    let (old, new) = ranges.split_once(" +")?;
+    let coords = |coords: &str| -> Option<(usize, usize)> {
+        let (start, len) = coords.split_once(',').unwrap_or((coords, ""));
+        let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+        if start.is_empty() || !all_digits(start) || !all_digits(len) {
+            return None;
+        }
+        let start: usize = start.parse().ok()?;
+        match len {
+            "" => Some((start.checked_sub(1)?, 1)),
+            "0" => Some((start, 0)),
+            len => Some((start.checked_sub(1)?, len.parse().ok()?)),
+        }
+    };
+    let (start1, length1) = coords(old)?;
+    let (start2, length2) = coords(new)?;
+    Some((start1, length1, start2, length2))
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        if byte == b'%' {
+            let hex = rest.get(..2)?;
+            let hex = core::str::from_utf8(hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &rest[2..];
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+// Computes the character level diff between two texts, merged the way the cleanups expect
+// Diffs two texts as slices of chars, so that every segment is made of whole chars
+fn char_diff(old: &[char], new: &[char]) -> Diffs {
+    let mut diffs = DiffOptions::default()
+        .diff_slice(old, new)
+        .into_iter()
+        .map(|d| match d {
+            DiffRange::Equal(range, _) => (DiffOpKind::Equal, range.as_slice().to_vec()),
+            DiffRange::Delete(range) => (DiffOpKind::Delete, range.as_slice().to_vec()),
+            DiffRange::Insert(range) => (DiffOpKind::Insert, range.as_slice().to_vec()),
+        })
+        .collect();
+    cleanup_merge(&mut diffs);
+    diffs
+}
+
+fn diff_text1(diffs: &Diffs) -> Vec<char> {
+    diffs
+        .iter()
+        .filter(|(kind, _)| *kind != DiffOpKind::Insert)
+        .flat_map(|(_, data)| data.iter().copied())
+        .collect()
+}
+
+fn diff_text2(diffs: &Diffs) -> Vec<char> {
+    diffs
+        .iter()
+        .filter(|(kind, _)| *kind != DiffOpKind::Delete)
+        .flat_map(|(_, data)| data.iter().copied())
+        .collect()
+}
+
+// The number of inserted, deleted or substituted characters
+fn levenshtein(diffs: &Diffs) -> usize {
+    let (mut levenshtein, mut insertions, mut deletions) = (0, 0, 0);
+    for (kind, data) in diffs {
+        match kind {
+            DiffOpKind::Insert => insertions += data.len(),
+            DiffOpKind::Delete => deletions += data.len(),
+            DiffOpKind::Equal => {
+                levenshtein += cmp::max(insertions, deletions);
+                insertions = 0;
+                deletions = 0;
+            }
+        }
+    }
+    levenshtein + cmp::max(insertions, deletions)
+}
+
+// Maps a position in the first text of `diffs` to the equivalent position in the second
+fn x_index(diffs: &Diffs, loc: usize) -> usize {
+    let (mut chars1, mut chars2) = (0, 0);
+    let (mut last_chars1, mut last_chars2) = (0, 0);
+    let mut last_kind = None;
+    for (kind, data) in diffs {
+        if *kind != DiffOpKind::Insert {
+            chars1 += data.len();
+        }
+        if *kind != DiffOpKind::Delete {
+            chars2 += data.len();
+        }
+        if chars1 > loc {
+            last_kind = Some(*kind);
+            break;
+        }
+        last_chars1 = chars1;
+        last_chars2 = chars2;
+    }
+    // A position within a deletion maps to where the deletion was
+    if last_kind == Some(DiffOpKind::Delete) {
+        return last_chars2;
+    }
+    last_chars2 + (loc - last_chars1)
+}
+
+fn find(text: &[char], pattern: &[char], from: usize) -> Option<usize> {
+    if pattern.is_empty() {
+        return Some(cmp::min(from, text.len()));
+    }
+    text.get(from..)?
+        .windows(pattern.len())
+        .position(|w| w == pattern)
+        .map(|i| i + from)
+}
+
+// The last occurrence of `pattern` starting at or before `from`
+fn rfind(text: &[char], pattern: &[char], from: usize) -> Option<usize> {
+    let last = text.len().checked_sub(pattern.len())?;
+    (0..=cmp::min(from, last))
+        .rev()
+        .find(|&i| text[i..].starts_with(pattern))
+}
+
+fn common_prefix(a: &[char], b: &[char]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+fn common_suffix(a: &[char], b: &[char]) -> usize {
+    a.iter()
+        .rev()
+        .zip(b.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count()
+}
+
+// The length of the longest suffix of `a` which is a prefix of `b`
+fn common_overlap(a: &[char], b: &[char]) -> usize {
+    let len = cmp::min(a.len(), b.len());
+    let (a, b) = (&a[a.len() - len..], &b[..len]);
+    if a == b {
+        return len;
+    }
+
+    // Look for ever longer suffixes of `a` which occur in `b`
+    let mut best = 0;
+    let mut length = 1;
+    loop {
+        let found = match find(b, &a[len - length..], 0) {
+            Some(found) => found,
+            None => return best,
+        };
+        length += found;
+        if found == 0 || a[len - length..] == b[..length] {
+            best = length;
+            length += 1;
+        }
+        if length > len {
+            return best;
+        }
+    }
+}
+
+// Merges neighbouring edits, factoring out their common prefixes and suffixes, and shifts
+// single edits sideways to merge away the equalities around them where possible
+fn cleanup_merge(diffs: &mut Diffs) {
+    diffs.push((DiffOpKind::Equal, Vec::new()));
+    let mut pointer = 0;
+    let (mut count_delete, mut count_insert) = (0, 0);
+    let (mut text_delete, mut text_insert) = (Vec::new(), Vec::new());
+    while pointer < diffs.len() {
+        match diffs[pointer].0 {
+            DiffOpKind::Insert => {
+                count_insert += 1;
+                text_insert.extend_from_slice(&diffs[pointer].1);
+                pointer += 1;
+            }
+            DiffOpKind::Delete => {
+                count_delete += 1;
+                text_delete.extend_from_slice(&diffs[pointer].1);
+                pointer += 1;
+            }
+            DiffOpKind::Equal => {
+                if count_delete + count_insert > 1 {
+                    if count_delete != 0 && count_insert != 0 {
+                        let common = common_prefix(&text_insert, &text_delete);
+                        if common != 0 {
+                            let x = pointer.checked_sub(count_delete + count_insert + 1);
+                            match x.filter(|&x| diffs[x].0 == DiffOpKind::Equal) {
+                                Some(x) => diffs[x].1.extend_from_slice(&text_insert[..common]),
+                                None => {
+                                    diffs.insert(
+                                        0,
+                                        (DiffOpKind::Equal, text_insert[..common].to_vec()),
+                                    );
+                                    pointer += 1;
+                                }
+                            }
+                            text_insert.drain(..common);
+                            text_delete.drain(..common);
+                        }
+                        let common = common_suffix(&text_insert, &text_delete);
+                        if common != 0 {
+                            let suffix = text_insert.split_off(text_insert.len() - common);
+                            diffs[pointer].1.splice(0..0, suffix);
+                            text_delete.truncate(text_delete.len() - common);
+                        }
+                    }
+
+                    let mut merged = Vec::new();
+                    if !text_delete.is_empty() {
+                        merged.push((DiffOpKind::Delete, core::mem::take(&mut text_delete)));
+                    }
+                    if !text_insert.is_empty() {
+                        merged.push((DiffOpKind::Insert, core::mem::take(&mut text_insert)));
+                    }
+                    pointer -= count_delete + count_insert;
+                    let merged_len = merged.len();
+                    diffs.splice(pointer..pointer + count_delete + count_insert, merged);
+                    pointer += merged_len + 1;
+                } else if pointer != 0 && diffs[pointer - 1].0 == DiffOpKind::Equal {
+                    let (_, data) = diffs.remove(pointer);
+                    diffs[pointer - 1].1.extend(data);
+                } else {
+                    pointer += 1;
+                }
+                count_insert = 0;
+                count_delete = 0;
+                text_delete.clear();
+                text_insert.clear();
+            }
+        }
+    }
+    if diffs.last().map_or(false, |(_, data)| data.is_empty()) {
+        diffs.pop();
+    }
+
+    // Shift single edits surrounded by equalities sideways, e.g. `A<ins>BA</ins>C` becomes
+    // `<ins>AB</ins>AC`
+    let mut changes = false;
+    let mut pointer = 1;
+    while pointer + 1 < diffs.len() {
+        if diffs[pointer - 1].0 == DiffOpKind::Equal && diffs[pointer + 1].0 == DiffOpKind::Equal {
+            let prev = diffs[pointer - 1].1.clone();
+            let next = diffs[pointer + 1].1.clone();
+            if diffs[pointer].1.ends_with(&prev) {
+                let edit = &mut diffs[pointer].1;
+                edit.truncate(edit.len() - prev.len());
+                edit.splice(0..0, prev.iter().copied());
+                diffs[pointer + 1].1.splice(0..0, prev.iter().copied());
+                diffs.remove(pointer - 1);
+                changes = true;
+            } else if diffs[pointer].1.starts_with(&next) {
+                diffs[pointer - 1].1.extend_from_slice(&next);
+                let edit = &mut diffs[pointer].1;
+                edit.drain(..next.len());
+                edit.extend_from_slice(&next);
+                diffs.remove(pointer + 1);
+                changes = true;
+            }
+        }
+        pointer += 1;
+    }
+    if changes {
+        cleanup_merge(diffs);
+    }
+}
+
+// Turns short equalities between larger edits into edits, so the diff reads as a few
+// meaningful changes rather than many small coincidental ones
+fn cleanup_semantic(diffs: &mut Diffs) {
+    let mut changes = false;
+    let mut equalities: Vec<usize> = Vec::new();
+    let mut last_equality: Option<usize> = None;
+    let mut pointer = 0;
+    // The lengths of the edits before and after the last equality
+    let (mut insertions1, mut deletions1) = (0, 0);
+    let (mut insertions2, mut deletions2) = (0, 0);
+    while pointer < diffs.len() {
+        if diffs[pointer].0 == DiffOpKind::Equal {
+            equalities.push(pointer);
+            insertions1 = core::mem::take(&mut insertions2);
+            deletions1 = core::mem::take(&mut deletions2);
+            last_equality = Some(pointer);
+            pointer += 1;
+            continue;
+        }
+
+        if diffs[pointer].0 == DiffOpKind::Insert {
+            insertions2 += diffs[pointer].1.len();
+        } else {
+            deletions2 += diffs[pointer].1.len();
+        }
+
+        let eliminate = last_equality.map_or(false, |eq| {
+            let len = diffs[eq].1.len();
+            len != 0
+                && len <= cmp::max(insertions1, deletions1)
+                && len <= cmp::max(insertions2, deletions2)
+        });
+        if eliminate {
+            let eq = *equalities.last().unwrap();
+            let data = diffs[eq].1.clone();
+            diffs.insert(eq, (DiffOpKind::Delete, data));
+            diffs[eq + 1].0 = DiffOpKind::Insert;
+            equalities.pop();
+            equalities.pop();
+            insertions1 = 0;
+            deletions1 = 0;
+            insertions2 = 0;
+            deletions2 = 0;
+            last_equality = None;
+            changes = true;
+            // Go back to the equality before the one eliminated, which may now be eliminated
+            // as well
+            match equalities.last() {
+                Some(&prev) => pointer = prev,
+                None => {
+                    pointer = 0;
+                    // Reprocess from the start, with no equality seen yet
+                    equalities.clear();
+                    continue;
+                }
+            }
+        }
+        pointer += 1;
+    }
+
+    if changes {
+        cleanup_merge(diffs);
+    }
+    cleanup_semantic_lossless(diffs);
+
+    // Factor out overlaps between deletions and insertions, e.g. `<del>abcxxx</del><ins>xxxdef</ins>`
+    // becomes `<del>abc</del>xxx<ins>def</ins>`, when the overlap is as big as half of either
+    let mut pointer = 1;
+    while pointer < diffs.len() {
+        if diffs[pointer - 1].0 == DiffOpKind::Delete && diffs[pointer].0 == DiffOpKind::Insert {
+            let deletion = diffs[pointer - 1].1.clone();
+            let insertion = diffs[pointer].1.clone();
+            let overlap1 = common_overlap(&deletion, &insertion);
+            let overlap2 = common_overlap(&insertion, &deletion);
+            if overlap1 >= overlap2 {
+                if 2 * overlap1 >= deletion.len() || 2 * overlap1 >= insertion.len() {
+                    diffs.insert(pointer, (DiffOpKind::Equal, insertion[..overlap1].to_vec()));
+                    diffs[pointer - 1].1 = deletion[..deletion.len() - overlap1].to_vec();
+                    diffs[pointer + 1].1 = insertion[overlap1..].to_vec();
+                    pointer += 1;
+                }
+            } else if 2 * overlap2 >= deletion.len() || 2 * overlap2 >= insertion.len() {
+                diffs.insert(pointer, (DiffOpKind::Equal, deletion[..overlap2].to_vec()));
+                diffs[pointer - 1] = (
+                    DiffOpKind::Insert,
+                    insertion[..insertion.len() - overlap2].to_vec(),
+                );
+                diffs[pointer + 1] = (DiffOpKind::Delete, deletion[overlap2..].to_vec());
+                pointer += 1;
+            }
+            pointer += 1;
+        }
+        pointer += 1;
+    }
+}
+
+// Scores how natural a boundary between `one` and `two` is, from 6 for the edge of the text
+// down to 0 for the middle of a word
+fn semantic_score(one: &[char], two: &[char]) -> u8 {
+    let (c1, c2) = match (one.last(), two.first()) {
+        (Some(&c1), Some(&c2)) => (c1, c2),
+        _ => return 6,
+    };
+
+    let non_alphanumeric1 = !c1.is_ascii_alphanumeric();
+    let non_alphanumeric2 = !c2.is_ascii_alphanumeric();
+    let whitespace1 = non_alphanumeric1 && c1.is_whitespace();
+    let whitespace2 = non_alphanumeric2 && c2.is_whitespace();
+    let line_break1 = whitespace1 && (c1 == '\r' || c1 == '\n');
+    let line_break2 = whitespace2 && (c2 == '\r' || c2 == '\n');
+    let blank_line1 =
+        line_break1 && (one.ends_with(&['\n', '\n']) || one.ends_with(&['\n', '\r', '\n']));
+    let blank_line2 = line_break2
+        && [
+            &['\n', '\n'][..],
+            &['\n', '\r', '\n'],
+            &['\r', '\n', '\n'],
+            &['\r', '\n', '\r', '\n'],
+        ]
+        .iter()
+        .any(|start| two.starts_with(start));
+
+    if blank_line1 || blank_line2 {
+        5
+    } else if line_break1 || line_break2 {
+        4
+    } else if non_alphanumeric1 && !whitespace1 && whitespace2 {
+        3
+    } else if whitespace1 || whitespace2 {
+        2
+    } else if non_alphanumeric1 || non_alphanumeric2 {
+        1
+    } else {
+        0
+    }
+}
+
+// Shifts single edits surrounded by equalities to the most natural boundaries, e.g.
+// `The c<ins>at c</ins>ame.` becomes `The <ins>cat </ins>came.`
+fn cleanup_semantic_lossless(diffs: &mut Diffs) {
+    let mut pointer = 1;
+    while pointer + 1 < diffs.len() {
+        if diffs[pointer - 1].0 != DiffOpKind::Equal || diffs[pointer + 1].0 != DiffOpKind::Equal {
+            pointer += 1;
+            continue;
+        }
+
+        let mut equality1 = diffs[pointer - 1].1.clone();
+        let mut edit = diffs[pointer].1.clone();
+        let mut equality2 = diffs[pointer + 1].1.clone();
+
+        // Shift the edit as far left as possible
+        let common = common_suffix(&equality1, &edit);
+        if common != 0 {
+            let common_text = edit[edit.len() - common..].to_vec();
+            equality1.truncate(equality1.len() - common);
+            edit.truncate(edit.len() - common);
+            edit.splice(0..0, common_text.iter().copied());
+            equality2.splice(0..0, common_text);
+        }
+
+        // Then step right one character at a time, keeping the best scoring position
+        let mut best = (equality1.clone(), edit.clone(), equality2.clone());
+        let mut best_score = semantic_score(&equality1, &edit) + semantic_score(&edit, &equality2);
+        while !edit.is_empty() && !equality2.is_empty() && edit[0] == equality2[0] {
+            equality1.push(edit.remove(0));
+            edit.push(equality2.remove(0));
+            let score = semantic_score(&equality1, &edit) + semantic_score(&edit, &equality2);
+            // `>=` favours the rightmost of equally good positions
+            if score >= best_score {
+                best_score = score;
+                best = (equality1.clone(), edit.clone(), equality2.clone());
+            }
+        }
+
+        let (best_equality1, best_edit, best_equality2) = best;
+        if diffs[pointer - 1].1 != best_equality1 {
+            if best_equality1.is_empty() {
+                diffs.remove(pointer - 1);
+                pointer -= 1;
+            } else {
+                diffs[pointer - 1].1 = best_equality1;
+            }
+            diffs[pointer].1 = best_edit;
+            if best_equality2.is_empty() {
+                diffs.remove(pointer + 1);
+                pointer -= 1;
+            } else {
+                diffs[pointer + 1].1 = best_equality2;
+            }
+        }
+        pointer += 1;
+    }
+}
+
+// Turns equalities which are too short to be worth keeping between edits into edits, reducing
+// the number of edits at the cost of a few more changed characters
+fn cleanup_efficiency(diffs: &mut Diffs) {
+    let mut changes = false;
+    let mut equalities: Vec<usize> = Vec::new();
+    let mut last_equality: Option<Vec<char>> = None;
+    let mut pointer: isize = 0;
+    // Whether there's an insertion or deletion before or after the last equality
+    let (mut pre_ins, mut pre_del, mut post_ins, mut post_del) = (false, false, false, false);
+    while (pointer as usize) < diffs.len() {
+        let p = pointer as usize;
+        if diffs[p].0 == DiffOpKind::Equal {
+            if diffs[p].1.len() < EDIT_COST && (post_ins || post_del) {
+                equalities.push(p);
+                pre_ins = post_ins;
+                pre_del = post_del;
+                last_equality = Some(diffs[p].1.clone());
+            } else {
+                equalities.clear();
+                last_equality = None;
+            }
+            post_ins = false;
+            post_del = false;
+        } else {
+            if diffs[p].0 == DiffOpKind::Delete {
+                post_del = true;
+            } else {
+                post_ins = true;
+            }
+
+            let edits = [pre_ins, pre_del, post_ins, post_del]
+                .iter()
+                .filter(|&&b| b)
+                .count();
+            let eliminate = last_equality.as_ref().map_or(false, |eq| {
+                !eq.is_empty() && (edits == 4 || (2 * eq.len() < EDIT_COST && edits == 3))
+            });
+            if eliminate {
+                let eq = *equalities.last().unwrap();
+                diffs.insert(eq, (DiffOpKind::Delete, last_equality.take().unwrap()));
+                diffs[eq + 1].0 = DiffOpKind::Insert;
+                equalities.pop();
+                if pre_ins && pre_del {
+                    // No changes made which could affect previous entries
+                    post_ins = true;
+                    post_del = true;
+                    equalities.clear();
+                } else {
+                    equalities.pop();
+                    pointer = equalities.last().map_or(-1, |&eq| eq as isize);
+                    post_ins = false;
+                    post_del = false;
+                }
+                changes = true;
+            }
+        }
+        pointer += 1;
+    }
+
+    if changes {
+        cleanup_merge(diffs);
+    }
+}
+
+// Pads the start and end of the patch with characters which are unlikely to be in the text,
+// so hunks at the edges of the text have some context to be matched by
+fn add_padding(hunks: &mut [FuzzyHunk]) -> Vec<char> {
+    let padding: Vec<char> = (1..=MARGIN as u32).filter_map(char::from_u32).collect();
+    for hunk in hunks.iter_mut() {
+        hunk.start1 += MARGIN;
+        hunk.start2 += MARGIN;
+    }
+
+    if let Some(hunk) = hunks.first_mut() {
+        match hunk.diffs.first_mut() {
+            Some((DiffOpKind::Equal, data)) => {
+                if data.len() < MARGIN {
+                    let extra = MARGIN - data.len();
+                    data.splice(0..0, padding[data.len()..].iter().copied());
+                    hunk.start1 -= extra;
+                    hunk.start2 -= extra;
+                    hunk.length1 += extra;
+                    hunk.length2 += extra;
+                }
+            }
+            _ => {
+                hunk.diffs.insert(0, (DiffOpKind::Equal, padding.clone()));
+                hunk.start1 -= MARGIN;
+                hunk.start2 -= MARGIN;
+                hunk.length1 += MARGIN;
+                hunk.length2 += MARGIN;
+            }
+        }
+    }
+
+    if let Some(hunk) = hunks.last_mut() {
+        match hunk.diffs.last_mut() {
+            Some((DiffOpKind::Equal, data)) => {
+                if data.len() < MARGIN {
+                    let extra = MARGIN - data.len();
+                    data.extend_from_slice(&padding[..extra]);
+                    hunk.length1 += extra;
+                    hunk.length2 += extra;
+                }
+            }
+            _ => {
+                hunk.diffs.push((DiffOpKind::Equal, padding.clone()));
+                hunk.length1 += MARGIN;
+                hunk.length2 += MARGIN;
+            }
+        }
+    }
+
+    padding
+}
+
+// Splits hunks which are too long to be matched with Bitap into smaller ones
+fn split_max(hunks: &mut Vec<FuzzyHunk>) {
+    let mut x = 0;
+    while x < hunks.len() {
+        if hunks[x].length1 <= MAX_BITS {
+            x += 1;
+            continue;
+        }
+
+        let mut big = hunks.remove(x);
+        let (mut start1, mut start2) = (big.start1, big.start2);
+        let mut precontext: Vec<char> = Vec::new();
+        while !big.diffs.is_empty() {
+            let mut hunk = FuzzyHunk {
+                diffs: Vec::new(),
+                start1: start1.saturating_sub(precontext.len()),
+                start2: start2.saturating_sub(precontext.len()),
+                length1: 0,
+                length2: 0,
+            };
+            let mut empty = true;
+            if !precontext.is_empty() {
+                hunk.length1 = precontext.len();
+                hunk.length2 = precontext.len();
+                hunk.diffs.push((DiffOpKind::Equal, precontext));
+            }
+
+            while !big.diffs.is_empty() && hunk.length1 < MAX_BITS - MARGIN {
+                let (kind, len) = (big.diffs[0].0, big.diffs[0].1.len());
+                if kind == DiffOpKind::Insert {
+                    // Insertions are harmless
+                    hunk.length2 += len;
+                    start2 += len;
+                    hunk.diffs.push(big.diffs.remove(0));
+                    empty = false;
+                } else if kind == DiffOpKind::Delete
+                    && hunk.diffs.len() == 1
+                    && hunk.diffs[0].0 == DiffOpKind::Equal
+                    && len > 2 * MAX_BITS
+                {
+                    // A large deletion, let it pass in one chunk
+                    hunk.length1 += len;
+                    start1 += len;
+                    empty = false;
+                    hunk.diffs.push(big.diffs.remove(0));
+                } else {
+                    // Deletion or equality, only take as much as fits
+                    let take = cmp::min(len, MAX_BITS - hunk.length1 - MARGIN);
+                    hunk.length1 += take;
+                    start1 += take;
+                    if kind == DiffOpKind::Equal {
+                        hunk.length2 += take;
+                        start2 += take;
+                    } else {
+                        empty = false;
+                    }
+                    let data = if take == len {
+                        big.diffs.remove(0).1
+                    } else {
+                        big.diffs[0].1.drain(..take).collect()
+                    };
+                    hunk.diffs.push((kind, data));
+                }
+            }
+
+            // The end of this hunk becomes the context at the start of the next
+            let text2 = diff_text2(&hunk.diffs);
+            precontext = text2[text2.len().saturating_sub(MARGIN)..].to_vec();
+            let mut postcontext = diff_text1(&big.diffs);
+            postcontext.truncate(MARGIN);
+            if !postcontext.is_empty() {
+                hunk.length1 += postcontext.len();
+                hunk.length2 += postcontext.len();
+                match hunk.diffs.last_mut() {
+                    Some((DiffOpKind::Equal, data)) => data.extend(postcontext),
+                    _ => hunk.diffs.push((DiffOpKind::Equal, postcontext)),
+                }
+            }
+            if !empty {
+                hunks.insert(x, hunk);
+                x += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+
+    fn diffs(list: &[(DiffOpKind, &str)]) -> Diffs {
+        list.iter()
+            .map(|&(kind, s)| (kind, s.chars().collect()))
+            .collect()
+    }
+
+    #[test]
+    fn cleanups() {
+        use DiffOpKind::{Delete, Equal, Insert};
+
+        let mut d = diffs(&[
+            (Equal, "a"),
+            (Delete, "b"),
+            (Insert, "c"),
+            (Delete, "ac"),
+            (Insert, "ba"),
+            (Equal, "c"),
+        ]);
+        cleanup_merge(&mut d);
+        assert_eq!(
+            d,
+            diffs(&[(Equal, "a"), (Delete, "bac"), (Insert, "cba"), (Equal, "c")])
+        );
+        let mut d = diffs(&[(Equal, "a"), (Insert, "ba"), (Equal, "c")]);
+        cleanup_merge(&mut d);
+        assert_eq!(d, diffs(&[(Insert, "ab"), (Equal, "ac")]));
+
+        let mut d = diffs(&[(Equal, "The c"), (Insert, "ow and the c"), (Equal, "at.")]);
+        cleanup_semantic_lossless(&mut d);
+        assert_eq!(
+            d,
+            diffs(&[(Equal, "The "), (Insert, "cow and the "), (Equal, "cat.")])
+        );
+
+        let mut d = diffs(&[(Delete, "a"), (Equal, "b"), (Delete, "c")]);
+        cleanup_semantic(&mut d);
+        assert_eq!(d, diffs(&[(Delete, "abc"), (Insert, "b")]));
+        let mut d = diffs(&[(Delete, "xxxabc"), (Insert, "defxxx")]);
+        cleanup_semantic(&mut d);
+        assert_eq!(
+            d,
+            diffs(&[(Insert, "def"), (Equal, "xxx"), (Delete, "abc")])
+        );
+
+        let mut d = diffs(&[
+            (Delete, "ab"),
+            (Insert, "12"),
+            (Equal, "xyz"),
+            (Delete, "cd"),
+            (Insert, "34"),
+        ]);
+        cleanup_efficiency(&mut d);
+        assert_eq!(d, diffs(&[(Delete, "abxyzcd"), (Insert, "12xyz34")]));
+
+        assert_eq!(common_overlap(&['a', 'b', 'c'], &['a', 'b', 'c', 'd']), 3);
+        assert_eq!(
+            common_overlap(&['1', '2', '3', '4', '5', '6'], &['x', 'x', 'x']),
+            0
+        );
+        let x = diffs(&[(Delete, "a"), (Insert, "1234"), (Equal, "xyz")]);
+        assert_eq!(x_index(&x, 2), 5);
+        let x = diffs(&[(Equal, "a"), (Delete, "1234"), (Equal, "xyz")]);
+        assert_eq!(x_index(&x, 3), 1);
+    }
+
+    #[test]
+    fn matching() {
+        let chars = |s: &str| s.chars().collect::<Vec<_>>();
+        let mut options = FuzzyOptions::new();
+        options.set_match_distance(100).set_match_threshold(0.5);
+        let bitap = |o: &FuzzyOptions, text: &str, pattern: &str, loc| {
+            o.match_bitap(&chars(text), &chars(pattern), loc)
+        };
+        assert_eq!(bitap(&options, "abcdefghijk", "fgh", 5), Some(5));
+        assert_eq!(bitap(&options, "abcdefghijk", "efxhi", 0), Some(4));
+        assert_eq!(bitap(&options, "abcdefghijk", "cdefxyhijk", 5), Some(2));
+        assert_eq!(bitap(&options, "abcdefghijk", "bxy", 1), None);
+        assert_eq!(bitap(&options, "123456789xx0", "3456789x0", 2), Some(2));
+        assert_eq!(bitap(&options, "abcdexyzabcde", "abccde", 3), Some(0));
+        assert_eq!(bitap(&options, "abcdexyzabcde", "abccde", 5), Some(8));
+        options.set_match_threshold(0.4);
+        assert_eq!(bitap(&options, "abcdefghijk", "efxyhi", 1), Some(4));
+        options.set_match_threshold(0.3);
+        assert_eq!(bitap(&options, "abcdefghijk", "efxyhi", 1), None);
+        options.set_match_threshold(0.5).set_match_distance(10);
+        assert_eq!(
+            bitap(&options, "abcdefghijklmnopqrstuvwxyz", "abcdefg", 24),
+            None
+        );
+        assert_eq!(
+            bitap(&options, "abcdefghijklmnopqrstuvwxyz", "abcdxxefg", 1),
+            Some(0)
+        );
+        options.set_match_distance(1000);
+        assert_eq!(
+            bitap(&options, "abcdefghijklmnopqrstuvwxyz", "abcdefg", 24),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn make() {
+        let text1 = "The quick brown fox jumps over the lazy dog.";
+        let text2 = "That quick brown fox jumped over a lazy dog.";
+        assert_eq!(FuzzyPatch::make("", "").to_string(), "");
+        assert_eq!(
+            FuzzyPatch::make(text2, text1).to_string(),
+            "@@ -1,8 +1,7 @@\n Th\n-at\n+e\n  qui\n@@ -21,17 +21,18 @@\n jump\n-ed\n+s\n  over \n-a\n+the\n  laz\n"
+        );
+        assert_eq!(
+            FuzzyPatch::make(text1, text2).to_string(),
+            "@@ -1,11 +1,12 @@\n Th\n-e\n+at\n  quick b\n@@ -22,18 +22,17 @@\n jump\n-s\n+ed\n  over \n-the\n+a\n  laz\n"
+        );
+        assert_eq!(
+            FuzzyPatch::make("`1234567890-=[]\\;',./", "~!@#$%^&*()_+{}|:\"<>?").to_string(),
+            "@@ -1,21 +1,21 @@\n-%601234567890-=%5B%5D%5C;',./\n+~!@#$%25%5E&*()_+%7B%7D%7C:%22%3C%3E?\n"
+        );
+        let text1 = "abcdef".repeat(100);
+        let text2 = format!("{}123", text1);
+        assert_eq!(
+            FuzzyPatch::make(&text1, &text2).to_string(),
+            "@@ -573,28 +573,31 @@\n cdefabcdefabcdefabcdefabcdef\n+123\n"
+        );
+
+        let patch = FuzzyPatch::make("Grüße", "Grüßen");
+        assert_eq!(patch.to_string(), "@@ -1,5 +1,6 @@\n Gr%C3%BC%C3%9Fe\n+n\n");
+        assert_eq!(patch.hunks()[0].old_range(), 0..5);
+        assert_eq!(patch.hunks()[0].new_range(), 0..6);
+
+        // Chars of different widths are never split
+        for (text1, text2) in [
+            ("c", "\u{e9}"),
+            ("ab", "\u{e9}b"),
+            ("\u{2603}\u{2603}x", "\u{2604}\u{2603}"),
+            ("日本語のテキスト", "日本のテキスト!"),
+        ] {
+            let patch = FuzzyPatch::make(text1, text2);
+            assert_eq!(patch.apply(text1).0, text2);
+            assert_eq!(patch.to_string().parse::<FuzzyPatch>().unwrap(), patch);
+        }
+        assert_eq!(
+            FuzzyPatch::make("c", "\u{e9}").to_string(),
+            "@@ -1 +1 @@\n-c\n+%C3%A9\n"
+        );
+    }
+
+    #[test]
+    fn text_format() {
+        for text in [
+            "",
+            "@@ -21,18 +22,17 @@\n jump\n-s\n+ed\n  over \n-the\n+a\n %0Alaz\n",
+            "@@ -1 +1 @@\n-a\n+b\n",
+            "@@ -1,3 +0,0 @@\n-abc\n",
+            "@@ -0,0 +1,3 @@\n+abc\n",
+            "@@ -1,2 +1,2 @@\n-%F0%9F%98%80\n+~\n",
+        ] {
+            assert_eq!(text.parse::<FuzzyPatch>().unwrap().to_string(), text);
+        }
+
+        let patch: FuzzyPatch = "@@ -1 +1 @@\n-a%2Bb\n+a+b %3F\n".parse().unwrap();
+        assert_eq!(
+            patch.hunks()[0].diffs,
+            diffs(&[(DiffOpKind::Delete, "a+b"), (DiffOpKind::Insert, "a+b ?")])
+        );
+
+        for (text, line) in [
+            ("Bad\nPatch\n", 1),
+            ("@@ -1 +1 @@\n?a\n", 2),
+            ("@@ -x +1 @@\n", 1),
+            ("@@ -0 +1 @@\n", 1),
+            ("+a\n", 1),
+            ("@@ -1 +1 @@\n-%E\n", 2),
+            ("@@ -1 +1 @@\n-%FF\n", 2),
+        ] {
+            assert_eq!(
+                text.parse::<FuzzyPatch>().unwrap_err().line(),
+                line,
+                "{:?}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn apply() {
+        let patch = FuzzyPatch::make("", "");
+        assert_eq!(patch.apply("Hello world."), ("Hello world.".into(), vec![]));
+
+        let patch = FuzzyPatch::make(
+            "The quick brown fox jumps over the lazy dog.",
+            "That quick brown fox jumped over a lazy dog.",
+        );
+        assert_eq!(
+            patch.apply("The quick brown fox jumps over the lazy dog."),
+            (
+                "That quick brown fox jumped over a lazy dog.".into(),
+                vec![true, true]
+            )
+        );
+        assert_eq!(
+            patch.apply("The quick red rabbit jumps over the tired tiger."),
+            (
+                "That quick red rabbit jumped over a tired tiger.".into(),
+                vec![true, true]
+            )
+        );
+        assert_eq!(
+            patch.apply("I am the very model of a modern major general."),
+            (
+                "I am the very model of a modern major general.".into(),
+                vec![false, false]
+            )
+        );
+
+        // Big deletions are located by their start and end
+        let patch = FuzzyPatch::make(
+            "x1234567890123456789012345678901234567890123456789012345678901234567890y",
+            "xabcy",
+        );
+        assert_eq!(
+            patch.apply("x123456789012345678901234567890-----++++++++++-----123456789012345678901234567890y"),
+            ("xabcy".into(), vec![true, true])
+        );
+        assert_eq!(
+            patch.apply("x12345678901234567890---------------++++++++++---------------12345678901234567890y"),
+            (
+                "xabc12345678901234567890---------------++++++++++---------------12345678901234567890y".into(),
+                vec![false, true]
+            )
+        );
+        let mut options = FuzzyOptions::new();
+        options.set_delete_threshold(0.6);
+        assert_eq!(
+            options.apply(&patch, "x12345678901234567890---------------++++++++++---------------12345678901234567890y"),
+            ("xabcy".into(), vec![true, true])
+        );
+
+        // A strict match still finds the end of a big deletion which drifted
+        let mut options = FuzzyOptions::new();
+        options.set_match_threshold(0.0).set_match_distance(0);
+        let patch = FuzzyPatch::make(
+            "abcdefghijklmnopqrstuvwxyz--------------------1234567890",
+            "abcXXXXXXXXXXdefghijklmnopqrstuvwxyz--------------------1234567YYYYYYYYYY890",
+        );
+        assert_eq!(
+            options.apply(
+                &patch,
+                "ABCDEFGHIJKLMNOPQRSTUVWXYZ--------------------1234567890"
+            ),
+            (
+                "ABCDEFGHIJKLMNOPQRSTUVWXYZ--------------------1234567YYYYYYYYYY890".into(),
+                vec![false, true]
+            )
+        );
+
+        // Hunks at the edges of the text
+        let patch = FuzzyPatch::make("", "test");
+        assert_eq!(patch.apply(""), ("test".into(), vec![true]));
+        let patch = FuzzyPatch::make("XY", "XtestY");
+        assert_eq!(patch.apply("XY"), ("XtestY".into(), vec![true]));
+        let patch = FuzzyPatch::make("y", "y123");
+        assert_eq!(patch.apply("x"), ("x123".into(), vec![true]));
+
+        // Applying a patch leaves it untouched
+        let patch = FuzzyPatch::make("", "test");
+        let text = patch.to_string();
+        patch.apply("");
+        assert_eq!(patch.to_string(), text);
+    }
+}
@@ -0,0 +1,329 @@
+//! Deltas in the format git uses to store objects in packfiles
+//!
+//! Git stores most objects in its packfiles as deltas against other objects, which describe how
+//! to build the target object by copying ranges of the base object and inserting new bytes.
+//! [`encode`] and [`decode`] read and write this format, so blobs can be materialized from
+//! deltas received from git, and deltas can be produced for git to consume.
+//!
+//! ```
+//! use diffy::git_delta;
+//!
+//! let base = b"The Way of Kings\nWords of Radiance\nOathbringer\n".repeat(4);
+//! let target = b"The Way of Kings\nWords of Radiance\nRhythm of War\n".repeat(4);
+//!
+//! let delta = git_delta::encode(&base, &target);
+//! assert_eq!(git_delta::decode(&base, &delta).unwrap(), target);
+//! ```
+//!
+//! # Format
+//!
+//! A delta starts with the sizes of the base and target objects, as unsigned LEB128 varints,
+//! followed by any number of instructions, each starting with an opcode byte:
+//!
+//! - if the high bit of the opcode is set, it's a copy from the base object. Bits `0` to `3`
+//!   say which bytes of the 32 bit offset to copy from follow, least significant first, and bits
+//!   `4` to `6` which bytes of the 24 bit size do. Bytes which don't follow are zero, and a size
+//!   of zero means `0x10000`.
+//! - otherwise if the opcode isn't zero, it's an insert of the `opcode` bytes which follow.
+//! - an opcode of zero is reserved.
+//!
+//! Objects are only compressed around deltas in packfiles, so the deltas read and written here
+//! are uncompressed. Unlike [`binary_delta`] deltas, these don't contain checksums: git
+//! verifies the object id of the result instead.
+//!
+//! [`encode`]: fn.encode.html
+//! [`decode`]: fn.decode.html
+//! [`binary_delta`]: ../binary_delta/index.html
+
+use crate::{
+    block_match::find_matches,
+    delta::{read_varint, write_varint},
+};
+use alloc::vec::Vec;
+
+pub use crate::DeltaError;
+
+// The largest copy git emits in a single instruction, which every version of git can read
+const MAX_COPY: usize = 0x10000;
+// The largest insert a single instruction can hold
+const MAX_INSERT: usize = 0x7f;
+
+/// Encode a git delta which builds `target` from `base`
+///
+/// Copies are at most `0x10000` bytes long, like the ones git produces, and only copy from the
+/// first 4 GiB of `base` since their offsets have 32 bits.
+pub fn encode(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    write_varint(&mut delta, base.len());
+    write_varint(&mut delta, target.len());
+
+    let mut insert_start = 0;
+    for m in find_matches(base, target) {
+        if u32::try_from(m.old + m.len).is_err() {
+            continue;
+        }
+        write_inserts(&mut delta, &target[insert_start..m.new]);
+        let mut offset = m.old;
+        while offset < m.old + m.len {
+            let len = (m.old + m.len - offset).min(MAX_COPY);
+            write_copy(&mut delta, offset, len);
+            offset += len;
+        }
+        insert_start = m.new + m.len;
+    }
+    write_inserts(&mut delta, &target[insert_start..]);
+
+    delta
+}
+
+/// Decode a git delta against its `base`, returning the target object
+///
+/// Every instruction is checked against the sizes in the delta's header and the bounds of
+/// `base`, so a malformed delta or the wrong base is reported as an error.
+///
+/// ```
+/// use diffy::{git_delta, DeltaError};
+///
+/// // Copy "Hello" from the base, then insert " git"
+/// let delta = b"\x0b\x09\x90\x05\x04 git";
+/// assert_eq!(git_delta::decode(b"Hello world", delta).unwrap(), b"Hello git");
+/// assert_eq!(
+///     git_delta::decode(b"Hello", delta),
+///     Err(DeltaError::BaseLengthMismatch)
+/// );
+/// ```
+pub fn decode(base: &[u8], delta: &[u8]) -> Result<Vec<u8>, DeltaError> {
+    let mut delta = delta;
+    if read_varint(&mut delta)? != base.len() {
+        return Err(DeltaError::BaseLengthMismatch);
+    }
+    let target_len = read_varint(&mut delta)?;
+
+    // The length comes from the input so it's only used as a hint, within reason
+    let capacity = target_len.min(base.len().saturating_add(delta.len()));
+    let mut target = Vec::with_capacity(capacity);
+    while let Some((&opcode, rest)) = delta.split_first() {
+        delta = rest;
+        let bytes = if opcode & 0x80 != 0 {
+            let mut offset = 0;
+            for i in 0..4 {
+                if opcode & 1 << i != 0 {
+                    offset |= usize::from(read_byte(&mut delta)?) << (8 * i);
+                }
+            }
+            let mut len = 0;
+            for i in 0..3 {
+                if opcode & 0x10 << i != 0 {
+                    len |= usize::from(read_byte(&mut delta)?) << (8 * i);
+                }
+            }
+            if len == 0 {
+                len = 0x10000;
+            }
+            offset
+                .checked_add(len)
+                .and_then(|end| base.get(offset..end))
+                .ok_or(DeltaError::CopyOutOfBounds)?
+        } else if opcode != 0 {
+            let len = usize::from(opcode);
+            if delta.len() < len {
+                return Err(DeltaError::UnexpectedEof);
+            }
+            let (bytes, rest) = delta.split_at(len);
+            delta = rest;
+            bytes
+        } else {
+            return Err(DeltaError::ReservedOpcode);
+        };
+
+        if target_len - target.len() < bytes.len() {
+            return Err(DeltaError::ResultLengthMismatch);
+        }
+        target.extend_from_slice(bytes);
+    }
+
+    if target.len() != target_len {
+        return Err(DeltaError::ResultLengthMismatch);
+    }
+    Ok(target)
+}
+
+// Writes a copy of at most `MAX_COPY` bytes, leaving out the bytes of its offset and size which
+// are zero
+fn write_copy(delta: &mut Vec<u8>, offset: usize, len: usize) {
+    let opcode_idx = delta.len();
+    delta.push(0x80);
+    for i in 0..4 {
+        let byte = (offset >> (8 * i)) as u8;
+        if byte != 0 {
+            delta[opcode_idx] |= 1 << i;
+            delta.push(byte);
+        }
+    }
+    // A size of `0x10000` is written as zero
+    let len = len % MAX_COPY;
+    for i in 0..3 {
+        let byte = (len >> (8 * i)) as u8;
+        if byte != 0 {
+            delta[opcode_idx] |= 0x10 << i;
+            delta.push(byte);
+        }
+    }
+}
+
+fn write_inserts(delta: &mut Vec<u8>, bytes: &[u8]) {
+    for chunk in bytes.chunks(MAX_INSERT) {
+        delta.push(chunk.len() as u8);
+        delta.extend_from_slice(chunk);
+    }
+}
+
+fn read_byte(delta: &mut &[u8]) -> Result<u8, DeltaError> {
+    let (&byte, rest) = delta.split_first().ok_or(DeltaError::UnexpectedEof)?;
+    *delta = rest;
+    Ok(byte)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{format, vec};
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    // Pairs of blobs along with the delta between them produced by `git pack-objects`
+    fn git_fixtures() -> Vec<(Vec<u8>, Vec<u8>, Vec<u8>)> {
+        let lines = |range: core::ops::Range<usize>| -> Vec<u8> {
+            range
+                .flat_map(|i| format!("line {}\n", i).into_bytes())
+                .collect()
+        };
+        let text_base = lines(0..200);
+        let text_target = [
+            &lines(0..50)[..],
+            b"changed\n",
+            &lines(51..120),
+            &lines(126..200),
+            b"tail\n",
+        ]
+        .concat();
+
+        let binary_target: Vec<u8> = (0..4000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let mut binary_base = binary_target.clone();
+        binary_base[1000..1010].fill(0xff);
+        binary_base.splice(3000..3000, b"appended".iter().copied());
+
+        vec![
+            (
+                text_base,
+                text_target,
+                hex("9a0de90cb08601076368616e676564b38d014402b307049302057461696c0a"),
+            ),
+            (
+                binary_base,
+                binary_target,
+                hex("a81fa01fb0e803b3cf08e90293e00308b3cf08e90293e00308b3cf08e90293e00308b3cf08e502"),
+            ),
+        ]
+    }
+
+    #[test]
+    fn decodes_git_deltas() {
+        for (base, target, delta) in git_fixtures() {
+            assert_eq!(decode(&base, &delta).unwrap(), target);
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        for (base, target, git) in git_fixtures() {
+            let delta = encode(&base, &target);
+            assert_eq!(decode(&base, &delta).unwrap(), target);
+            assert!(
+                delta.len() <= git.len() + 16,
+                "{} > {}",
+                delta.len(),
+                git.len()
+            );
+            let delta = encode(&target, &base);
+            assert_eq!(decode(&target, &delta).unwrap(), base);
+        }
+
+        // Long copies and inserts are split across instructions
+        let base: Vec<u8> = (0..200_000u32).map(|i| (i * 31 / 7) as u8).collect();
+        let target = [&base[..], &[0xab; 300][..]].concat();
+        let delta = encode(&base, &target);
+        assert_eq!(decode(&base, &delta).unwrap(), target);
+        assert_eq!(encode(&[], &[]), [0, 0]);
+        assert_eq!(decode(&[], &[0, 0]).unwrap(), b"");
+    }
+
+    #[test]
+    fn instructions() {
+        let base: Vec<u8> = (0..0x20000u32).map(|i| (i >> 8) as u8).collect();
+
+        let mut delta = Vec::new();
+        write_copy(&mut delta, 0x10203, 0x10000);
+        write_copy(&mut delta, 0, 0x100);
+        write_copy(&mut delta, 0x1000000, 0x1234);
+        assert_eq!(
+            delta,
+            [0x87, 0x03, 0x02, 0x01, 0xa0, 0x01, 0xb8, 0x01, 0x34, 0x12]
+        );
+
+        let mut delta = Vec::new();
+        write_varint(&mut delta, 0x20000);
+        write_varint(&mut delta, 0x10002);
+        // Copy 0x10000 bytes from 0x10000, then 2 bytes from 0x10
+        delta.extend([0x84, 0x01]);
+        delta.extend([0x91, 0x10, 0x02]);
+        let target = decode(&base, &delta).unwrap();
+        assert_eq!(&target[..0x10000], &base[0x10000..]);
+        assert_eq!(&target[0x10000..], &base[0x10..0x12]);
+    }
+
+    #[test]
+    fn rejects_invalid_deltas() {
+        let base = b"0123456789";
+        assert_eq!(decode(base, &[]), Err(DeltaError::UnexpectedEof));
+        assert_eq!(decode(base, &[9, 0]), Err(DeltaError::BaseLengthMismatch));
+        assert_eq!(decode(base, &[10, 1, 0]), Err(DeltaError::ReservedOpcode));
+        // Copies past the end of the base, or whose offset or size is cut off
+        assert_eq!(
+            decode(base, &[10, 5, 0x91, 8, 5]),
+            Err(DeltaError::CopyOutOfBounds)
+        );
+        assert_eq!(
+            decode(base, &[10, 5, 0x90, 11]),
+            Err(DeltaError::CopyOutOfBounds)
+        );
+        assert_eq!(
+            decode(base, &[10, 5, 0x9f, 0xff, 0xff, 0xff, 0xff, 1]),
+            Err(DeltaError::CopyOutOfBounds)
+        );
+        assert_eq!(
+            decode(base, &[10, 5, 0x91, 1]),
+            Err(DeltaError::UnexpectedEof)
+        );
+        // Inserts which are cut off
+        assert_eq!(
+            decode(base, &[10, 5, 3, b'a']),
+            Err(DeltaError::UnexpectedEof)
+        );
+        // Results of the wrong size
+        assert_eq!(
+            decode(base, &[10, 1, 2, b'a', b'b']),
+            Err(DeltaError::ResultLengthMismatch)
+        );
+        assert_eq!(
+            decode(base, &[10, 3, 2, b'a', b'b']),
+            Err(DeltaError::ResultLengthMismatch)
+        );
+        assert_eq!(decode(base, &[10, 2, 2, b'a', b'b']).unwrap(), b"ab");
+    }
+}
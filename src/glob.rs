@@ -0,0 +1,170 @@
+use alloc::{string::String, vec::Vec};
+
+/// A glob pattern for matching paths
+///
+/// Paths are matched with `/` as their separator. The following wildcards are supported:
+///
+/// * `?` matches any single character other than `/`
+/// * `*` matches any sequence of characters other than `/`
+/// * `**` matches any sequence of characters, including `/`. When it makes up a whole path
+///   component it also matches no directories at all, so `src/**/*.rs` matches `src/lib.rs`.
+///
+/// Like in a `.gitignore`, a pattern which doesn't contain a `/` is matched against the last
+/// component of a path, while any other pattern is matched against the whole path. A leading
+/// `/` is ignored.
+///
+/// ```
+/// use diffy::Pattern;
+///
+/// assert!(Pattern::new("*.rs").matches("src/diff/mod.rs"));
+/// assert!(Pattern::new("src/*.rs").matches("src/lib.rs"));
+/// assert!(!Pattern::new("src/*.rs").matches("src/diff/mod.rs"));
+/// assert!(Pattern::new("src/**/*.rs").matches("src/diff/mod.rs"));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Pattern {
+    pattern: String,
+    chars: Vec<char>,
+    anchored: bool,
+}
+
+impl Pattern {
+    /// Construct a new `Pattern`
+    pub fn new(pattern: &str) -> Self {
+        let anchored = pattern.contains('/');
+        let stripped = pattern.strip_prefix('/').unwrap_or(pattern);
+        Self {
+            pattern: pattern.into(),
+            chars: stripped.chars().collect(),
+            anchored,
+        }
+    }
+
+    /// Returns the pattern as it was given to [`Pattern::new`]
+    ///
+    /// [`Pattern::new`]: #method.new
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Returns `true` if `path`, using `/` as its separator, matches the pattern
+    pub fn matches(&self, path: &str) -> bool {
+        let path = path.strip_prefix('/').unwrap_or(path);
+        let path = if self.anchored {
+            path
+        } else {
+            path.rsplit('/').next().unwrap_or(path)
+        };
+        let path: Vec<char> = path.chars().collect();
+        glob_match(&self.chars, &path)
+    }
+}
+
+// Matches by backtracking to the last wildcard only: a `*` resumes one character further
+// unless that would cross a `/`, in which case the last `**` resumes instead. Since a later
+// wildcard can always take over what an earlier one would have matched, this never has to
+// revisit an earlier wildcard and stays quadratic at worst.
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    // The pattern index to resume at, along with the text index to resume at next time
+    let mut star: Option<(usize, usize)> = None;
+    let mut globstar: Option<(usize, usize)> = None;
+    let (mut p, mut t) = (0, 0);
+    loop {
+        match pattern.get(p) {
+            Some('*') if pattern.get(p + 1) == Some(&'*') => {
+                star = None;
+                if pattern.get(p + 2) == Some(&'/') && (p == 0 || pattern[p - 1] == '/') {
+                    // Try matching no directories at all first
+                    globstar = Some((p + 2, t));
+                    p += 3;
+                } else {
+                    globstar = Some((p + 2, t + 1));
+                    p += 2;
+                }
+                continue;
+            }
+            Some('*') => {
+                star = Some((p + 1, t + 1));
+                p += 1;
+                continue;
+            }
+            Some('?') if t < text.len() && text[t] != '/' => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            Some(c) if c != &'?' && text.get(t) == Some(c) => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            None if t == text.len() => return true,
+            _ => {}
+        }
+
+        match star {
+            Some((star_p, star_t)) if star_t <= text.len() && text[star_t - 1] != '/' => {
+                star = Some((star_p, star_t + 1));
+                p = star_p;
+                t = star_t;
+                continue;
+            }
+            _ => star = None,
+        }
+        match globstar {
+            Some((globstar_p, globstar_t)) if globstar_t <= text.len() => {
+                globstar = Some((globstar_p, globstar_t + 1));
+                p = globstar_p;
+                t = globstar_t;
+            }
+            _ => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_patterns() {
+        let cases = [
+            ("*.rs", "lib.rs", true),
+            ("*.rs", "src/lib.rs", true),
+            ("*.rs", "lib.rs.orig", false),
+            ("target", "target", true),
+            ("target", "crates/foo/target", true),
+            ("/target", "crates/foo/target", false),
+            ("/target", "target", true),
+            ("src/?.rs", "src/a.rs", true),
+            ("src/?.rs", "src/ab.rs", false),
+            ("src/*", "src/diff", true),
+            ("src/*", "src/diff/mod.rs", false),
+            ("src/**", "src/diff/mod.rs", true),
+            ("**/mod.rs", "mod.rs", true),
+            ("**/mod.rs", "src/diff/mod.rs", true),
+            ("src/**/mod.rs", "src/mod.rs", true),
+            ("src/**/mod.rs", "src/patch/mod.rs.bak", false),
+            ("src**/mod.rs", "src/mod.rs", true),
+            ("src**/mod.rs", "srcmod.rs", false),
+            ("d\u{e9}j\u{e0}/?", "d\u{e9}j\u{e0}/\u{e9}", true),
+        ];
+        for (pattern, path, expected) in cases {
+            assert_eq!(
+                Pattern::new(pattern).matches(path),
+                expected,
+                "{} {}",
+                pattern,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn glob_backtracking_is_not_exponential() {
+        let path = "a".repeat(50);
+        assert!(!Pattern::new("*a*a*a*a*a*a*a*a*b").matches(&path));
+        assert!(!Pattern::new("**a**a**a**a**a**a**a**a**b").matches(&path));
+        assert!(Pattern::new("*a*a*a*a*a*a*a*a*").matches(&path));
+    }
+}
@@ -198,6 +198,35 @@
 //! assert_eq!(merge(original, a, b).unwrap_err(), expected);
 //! ```
 //!
+//! ## Feature Flags
+//!
+//! - `std` (enabled by default): implements `std::error::Error` for the crate's error types and
+//!   provides [`PatchFormatter::write_patch_into`] for writing into an `io::Write` and
+//!   [`create_patch_from_files`] and [`create_patchset_from_dirs`] for diffing files and
+//!   directories on disk, along with [`diff_readers`] for diffing large streams.
+//!   [`ColorChoice::Auto`] only detects whether to use color with it.
+//!   Without it the crate is `no_std` and only requires `alloc`.
+//! - `arbitrary`: implements [`Arbitrary`] for [`Patch`] and its components, generating
+//!   internally consistent patches for structure-aware fuzzing, along with
+//!   `ApplicablePatch` for generating a base text and a patch which applies to it. Implies
+//!   `std`.
+//! - `binary-delta`: provides the [`binary_delta`](binary_delta/index.html) module for encoding
+//!   compact, checksummed deltas between binary files and applying them.
+//! - `proptest`: provides the [`proptest`](proptest/index.html) module with strategies for
+//!   generating pairs and triples of related texts for property testing. Implies `std`.
+//! - `rayon`: diffs the files of a [`PatchSet`] in parallel. Implies `std`.
+//! - `ropey`: provides [`apply_to_rope`] for applying patches to a [`Rope`] in place. Implies
+//!   `std`.
+//! - `serde`: implements `Serialize` for [`Diff`] and `Serialize`/`Deserialize` for its owned
+//!   form [`OwnedDiff`] and for [`EditScript`].
+//! - `unicode-normalization`: provides [`DiffOptions::set_normalize_unicode`] for comparing
+//!   lines after normalizing them to one of the Unicode normalization forms.
+//! - `unicode-case`: provides [`DiffOptions::set_ignore_case_unicode`] for comparing lines using
+//!   full Unicode case folding. Implies `std`.
+//!
+//! [`Arbitrary`]: https://docs.rs/arbitrary/1/arbitrary/trait.Arbitrary.html
+//! [`Rope`]: https://docs.rs/ropey/1/ropey/struct.Rope.html
+//!
 //! [LibXDiff]: http://www.xmailserver.org/xdiff-lib.html
 //! [Myers' diff algorithm]: http://www.xmailserver.org/diff2.pdf
 //! [GNU Diffutils]: https://www.gnu.org/software/diffutils/
@@ -208,18 +237,88 @@
 //!
 //! [`Display`]: https://doc.rust-lang.org/stable/std/fmt/trait.Display.html
 //! [`Patch`]: struct.Patch.html
+//! [`PatchSet`]: struct.PatchSet.html
 //! [`PatchFormatter`]: struct.PatchFormatter.html
+//! [`PatchFormatter::write_patch_into`]: struct.PatchFormatter.html#method.write_patch_into
+//! [`DiffOptions::set_normalize_unicode`]: struct.DiffOptions.html#method.set_normalize_unicode
+//! [`DiffOptions::set_ignore_case_unicode`]: struct.DiffOptions.html#method.set_ignore_case_unicode
+//! [`ColorChoice::Auto`]: enum.ColorChoice.html#variant.Auto
+//! [`apply_to_rope`]: fn.apply_to_rope.html
 //! [`create_patch`]: fn.create_patch.html
 //! [`create_patch_bytes`]: fn.create_patch_bytes.html
+//! [`create_patch_from_files`]: fn.create_patch_from_files.html
+//! [`create_patchset_from_dirs`]: fn.create_patchset_from_dirs.html
+//! [`diff_readers`]: fn.diff_readers.html
+
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+
+extern crate alloc;
 
 mod apply;
+#[cfg(feature = "binary-delta")]
+pub mod binary_delta;
+mod block_match;
+pub mod cleanup;
+mod delta;
 mod diff;
+#[cfg(feature = "arbitrary")]
+mod fuzz;
+pub mod fuzzy;
+pub mod git_delta;
+mod glob;
 mod merge;
+pub mod myers;
 mod patch;
+#[cfg(feature = "std")]
+mod patch_set;
+#[cfg(feature = "proptest")]
+pub mod proptest;
 mod range;
+#[cfg(feature = "ropey")]
+mod rope;
+mod series;
 mod utils;
 
-pub use apply::{apply, apply_bytes, ApplyError};
-pub use diff::{create_patch, create_patch_bytes, DiffOptions};
-pub use merge::{merge, merge_bytes, ConflictStyle, MergeOptions};
-pub use patch::{Hunk, HunkRange, Line, ParsePatchError, Patch, PatchFormatter};
+pub use apply::{
+    apply, apply_bytes, apply_bytes_cow, apply_cow, ApplyError, ApplyOptions, HunkSpan,
+};
+pub use delta::{apply_delta, encode_delta, DeltaError};
+#[cfg(feature = "unicode-normalization")]
+pub use diff::NormalizationForm;
+pub use diff::{
+    added_items, added_lines, annotate, classify_difference, classify_difference_bytes,
+    combined_diff, create_patch, create_patch_bytes, diff, diff_chunked, diff_counts,
+    diff_counts_bytes, diff_lines, diff_lines_bytes, diff_lines_from_owned_slices,
+    diff_lines_from_slices, diff_lines_range, diff_map, diff_ops, diff_slice_ops, diff_text_ops,
+    differs_by_more_than, differs_by_more_than_bytes, differs_by_more_than_slices, removed_items,
+    removed_lines, AppendDiff, ChunkOptions, CombinedHunk, CombinedLine, CombinedPatch,
+    ContextMode, Diff, DiffKind, DiffLines, DiffMetrics, DiffOp, DiffOpKind, DiffOptions, Edit,
+    EditError, EditScript, ElidedDiff, LineEdit, LineOrigin, LinePrefix, LineRangeError, MapDiff,
+    MovedBlock, Op, OwnedDiff, ParentChange, RecordSep, Reference, TooDifferentError,
+};
+#[cfg(feature = "std")]
+pub use diff::{create_patch_from_files, diff_readers, FilePatch, OwnedDiffLines};
+#[cfg(feature = "arbitrary")]
+pub use fuzz::ApplicablePatch;
+pub use glob::Pattern;
+pub use merge::{
+    merge, merge_bytes, merge_conflict_count, merge_is_clean, ConflictRegion, ConflictStyle,
+    MergeConflict, MergeOptions, MergeReport, Resolution,
+};
+#[cfg(feature = "std")]
+pub use merge::{merge_files, BinaryFileError, MergeFileResult, MergedContent};
+pub use patch::{
+    diff_patches, AnnotatedLine, Attributes, ColorChoice, EdScriptFormatter, FileStatus, Hunk,
+    HunkDiff, HunkRange, Line, LineKind, LintCategory, LintOptions, LintWarning, MergeHunksError,
+    ParsePatchError, ParsePorcelainError, Patch, PatchFormatter, PatchUtf8Error,
+    PorcelainFormatter, PorcelainPatch, PrefixPolicy, SortKey, SpanStyle, SplitRow, StyleConfig,
+    StyledRow, StyledSpan, Theme,
+};
+#[cfg(feature = "std")]
+pub use patch_set::{
+    create_patchset_from_dirs, FileOutcome, PatchSet, PatchSetError, PatchSetFormatter,
+    PatchSetReport,
+};
+#[cfg(feature = "ropey")]
+pub use rope::{apply_to_rope, ApplyReport, RopeEdit};
+pub use series::{apply_series, check_series, PatchSeries, SeriesError};
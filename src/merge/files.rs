@@ -0,0 +1,308 @@
+use super::{MergeConflict, MergeOptions};
+use crate::diff::files::is_binary;
+use std::{
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+/// The merged contents of a file, see [`merge_files`]
+///
+/// [`merge_files`]: fn.merge_files.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MergedContent {
+    /// The merge of three valid utf8 files
+    Text(String),
+    /// The merge of three files of which at least one isn't valid utf8
+    Bytes(Vec<u8>),
+}
+
+impl MergedContent {
+    /// Returns the merged contents as bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            MergedContent::Text(text) => text.as_bytes(),
+            MergedContent::Bytes(bytes) => bytes,
+        }
+    }
+}
+
+/// The outcome of merging three files with [`merge_files`]
+///
+/// [`merge_files`]: fn.merge_files.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MergeFileResult {
+    content: MergedContent,
+    conflicts: Vec<MergeConflict>,
+    resolved: usize,
+}
+
+impl MergeFileResult {
+    /// Returns the merged contents, including conflict markers if there are conflicts
+    pub fn content(&self) -> &MergedContent {
+        &self.content
+    }
+
+    /// Consumes the result, returning the merged contents
+    pub fn into_content(self) -> MergedContent {
+        self.content
+    }
+
+    /// Returns `true` if the merge has conflicts
+    pub fn has_conflicts(&self) -> bool {
+        !self.conflicts.is_empty()
+    }
+
+    /// Returns the conflicting regions of the merge, in order
+    pub fn conflicts(&self) -> &[MergeConflict] {
+        &self.conflicts
+    }
+
+    /// Returns the number of conflicts resolved by the resolver, see
+    /// [`MergeOptions::set_resolver`]
+    ///
+    /// [`MergeOptions::set_resolver`]: struct.MergeOptions.html#method.set_resolver
+    pub fn resolved_conflicts(&self) -> usize {
+        self.resolved
+    }
+}
+
+/// The error [`merge_files`] fails with when a file looks like binary content, wrapped in an
+/// [`io::Error`] of kind [`io::ErrorKind::InvalidData`]
+///
+/// A file is considered binary if it contains a NUL byte within its first 8000 bytes.
+///
+/// [`merge_files`]: fn.merge_files.html
+/// [`io::Error`]: https://doc.rust-lang.org/std/io/struct.Error.html
+/// [`io::ErrorKind::InvalidData`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.InvalidData
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BinaryFileError {
+    path: PathBuf,
+}
+
+impl BinaryFileError {
+    /// Returns the path of the binary file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl fmt::Display for BinaryFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cannot merge binary file {}", self.path.display())
+    }
+}
+
+impl Error for BinaryFileError {}
+
+impl MergeOptions {
+    /// Set whether [`merge_files`] writes the merged contents back to our file, like
+    /// `git merge-file` does
+    ///
+    /// The contents are written even if the merge has conflicts, in which case the file is left
+    /// with conflict markers for resolving them.
+    ///
+    /// [`merge_files`]: fn.merge_files.html
+    pub fn set_in_place(&mut self, in_place: bool) -> &mut Self {
+        self.in_place = in_place;
+        self
+    }
+
+    /// Set whether [`merge_files`] merges files which look like binary content line by line,
+    /// instead of failing with a [`BinaryFileError`]
+    ///
+    /// [`merge_files`]: fn.merge_files.html
+    /// [`BinaryFileError`]: struct.BinaryFileError.html
+    pub fn set_merge_binary(&mut self, merge_binary: bool) -> &mut Self {
+        self.merge_binary = merge_binary;
+        self
+    }
+}
+
+/// Merge the changes made to `ancestor` in `ours` and in `theirs`, reading all three files from
+/// disk
+///
+/// The files are merged as text if all of them are valid utf8 and as bytes otherwise. The
+/// conflict markers are labeled with the paths of the files. If [`MergeOptions::set_in_place`]
+/// is enabled the merged contents are written to `ours`.
+///
+/// A file which looks like binary content makes the merge fail with an error of kind
+/// [`io::ErrorKind::InvalidData`] wrapping a [`BinaryFileError`], unless
+/// [`MergeOptions::set_merge_binary`] is enabled.
+///
+/// ```no_run
+/// use diffy::{merge_files, MergeOptions};
+/// use std::path::Path;
+///
+/// let result = merge_files(
+///     Path::new("base.txt"),
+///     Path::new("ours.txt"),
+///     Path::new("theirs.txt"),
+///     MergeOptions::new().set_in_place(true),
+/// )?;
+/// std::process::exit(result.conflicts().len().min(127) as i32);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// [`MergeOptions::set_in_place`]: struct.MergeOptions.html#method.set_in_place
+/// [`MergeOptions::set_merge_binary`]: struct.MergeOptions.html#method.set_merge_binary
+/// [`io::ErrorKind::InvalidData`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.InvalidData
+/// [`BinaryFileError`]: struct.BinaryFileError.html
+pub fn merge_files(
+    ancestor: &Path,
+    ours: &Path,
+    theirs: &Path,
+    options: &MergeOptions,
+) -> io::Result<MergeFileResult> {
+    let paths = [ancestor, ours, theirs];
+    let contents = [fs::read(ancestor)?, fs::read(ours)?, fs::read(theirs)?];
+    if !options.merge_binary {
+        if let Some((path, _)) = paths
+            .iter()
+            .zip(&contents)
+            .find(|(_, contents)| is_binary(contents))
+        {
+            let error = BinaryFileError {
+                path: path.to_path_buf(),
+            };
+            return Err(io::Error::new(io::ErrorKind::InvalidData, error));
+        }
+    }
+
+    let labels = [ours, ancestor, theirs].map(|path| path.display().to_string());
+    let labels = [&*labels[0], &*labels[1], &*labels[2]];
+    let [ancestor, ours_contents, theirs] = &contents;
+    let texts = (
+        str::from_utf8(ancestor),
+        str::from_utf8(ours_contents),
+        str::from_utf8(theirs),
+    );
+    let (content, conflicts, resolved) = match texts {
+        (Ok(ancestor), Ok(ours), Ok(theirs)) => {
+            let report = options.merge_labeled(ancestor, ours, theirs, labels);
+            let content = MergedContent::Text(report.output);
+            (content, report.conflicts, report.resolved)
+        }
+        _ => {
+            let report = options.merge_bytes_labeled(ancestor, ours_contents, theirs, labels);
+            let content = MergedContent::Bytes(report.output);
+            (content, report.conflicts, report.resolved)
+        }
+    };
+
+    if options.in_place {
+        fs::write(ours, content.as_bytes())?;
+    }
+    Ok(MergeFileResult {
+        content,
+        conflicts,
+        resolved,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, format};
+
+    // A scratch directory which is removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = env::temp_dir().join(format!("diffy-merge-{}-{}", name, std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn file(&self, name: &str, contents: &[u8]) -> PathBuf {
+            let path = self.0.join(name);
+            fs::write(&path, contents).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn merge_text_files() {
+        let dir = TempDir::new("text");
+        let ancestor = dir.file("base", b"a\nb\nc\nd\n");
+        let ours = dir.file("ours", b"A\nb\nc\nd\n");
+        let theirs = dir.file("theirs", b"a\nb\nc\nD\n");
+
+        let result = merge_files(&ancestor, &ours, &theirs, &MergeOptions::new()).unwrap();
+        assert!(!result.has_conflicts());
+        assert_eq!(
+            result.content(),
+            &MergedContent::Text("A\nb\nc\nD\n".to_string())
+        );
+        assert_eq!(fs::read(&ours).unwrap(), b"A\nb\nc\nd\n");
+
+        // Conflicts are labeled with the paths of the files
+        let theirs = dir.file("theirs", b"a2\nb\nc\nd\n");
+        let result = merge_files(&ancestor, &ours, &theirs, &MergeOptions::new()).unwrap();
+        assert!(result.has_conflicts());
+        let expected = format!(
+            "<<<<<<< {}\nA\n||||||| {}\na\n=======\na2\n>>>>>>> {}\nb\nc\nd\n",
+            ours.display(),
+            ancestor.display(),
+            theirs.display()
+        );
+        assert_eq!(result.content().as_bytes(), expected.as_bytes());
+        assert_eq!(
+            result.conflicts(),
+            [MergeConflict {
+                ancestor: 0..1,
+                ours: 0..1,
+                theirs: 0..1,
+                merged: 0..7,
+            }]
+        );
+
+        // In place merges write to our file, conflicts included
+        let mut options = MergeOptions::new();
+        options.set_in_place(true);
+        let result = merge_files(&ancestor, &ours, &theirs, &options).unwrap();
+        assert_eq!(fs::read(&ours).unwrap(), expected.as_bytes());
+        assert_eq!(result.into_content().as_bytes(), expected.as_bytes());
+
+        let missing = dir.0.join("missing");
+        let err = merge_files(&missing, &ours, &theirs, &options).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn merge_binary_files() {
+        let dir = TempDir::new("binary");
+        let ancestor = dir.file("base.bin", b"\x00\na\nb\nc\n");
+        let ours = dir.file("ours.bin", b"\x00\nA\nb\nc\n");
+        let theirs = dir.file("theirs.bin", b"\x00\na\nb\n\xff\n");
+
+        let err = merge_files(&ancestor, &ours, &theirs, &MergeOptions::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let binary = err
+            .get_ref()
+            .and_then(|err| err.downcast_ref::<BinaryFileError>())
+            .unwrap();
+        assert_eq!(binary.path(), ancestor);
+        assert_eq!(fs::read(&ours).unwrap(), b"\x00\nA\nb\nc\n");
+
+        let mut options = MergeOptions::new();
+        options.set_merge_binary(true).set_in_place(true);
+        let result = merge_files(&ancestor, &ours, &theirs, &options).unwrap();
+        assert!(!result.has_conflicts());
+        assert_eq!(
+            result.content(),
+            &MergedContent::Bytes(b"\x00\nA\nb\n\xff\n".to_vec())
+        );
+        assert_eq!(fs::read(&ours).unwrap(), b"\x00\nA\nb\n\xff\n");
+    }
+}
@@ -1,14 +1,22 @@
 use crate::{
     diff::DiffOptions,
     range::{DiffRange, Range, SliceLike},
-    utils::Classifier,
+    utils::{Classifier, LineIter},
 };
-use std::{cmp, fmt};
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::{cmp, fmt, ops, str};
 
+#[cfg(feature = "std")]
+mod files;
 #[cfg(test)]
 mod tests;
 
+#[cfg(feature = "std")]
+pub use files::{merge_files, BinaryFileError, MergeFileResult, MergedContent};
+
 const DEFAULT_CONFLICT_MARKER_LENGTH: usize = 7;
+// The labels of our side, the ancestor and their side put after the conflict markers
+const DEFAULT_LABELS: [&str; 3] = ["ours", "original", "theirs"];
 
 enum Diff3Range<'ancestor, 'ours, 'theirs, T: ?Sized> {
     Equal(Range<'ancestor, T>, Range<'ours, T>, Range<'theirs, T>),
@@ -112,11 +120,133 @@ pub enum ConflictStyle {
     Diff3,
 }
 
+/// A conflicting region of a merge, see [`MergeFileResult::conflicts`]
+///
+/// Every range is a range of 0-based line indices.
+///
+/// [`MergeFileResult::conflicts`]: struct.MergeFileResult.html#method.conflicts
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MergeConflict {
+    /// The lines of the ancestor both sides changed
+    pub ancestor: ops::Range<usize>,
+    /// The lines our side changed them to
+    pub ours: ops::Range<usize>,
+    /// The lines their side changed them to
+    pub theirs: ops::Range<usize>,
+    /// The lines of the merged text making up the conflict, from the line of the opening
+    /// conflict marker up to and including the line of the closing one
+    pub merged: ops::Range<usize>,
+}
+
+/// A conflicting region handed to a resolver, see [`MergeOptions::set_resolver`]
+///
+/// Every line includes its line ending, except for the final line of a file which lacks one.
+///
+/// [`MergeOptions::set_resolver`]: struct.MergeOptions.html#method.set_resolver
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConflictRegion<'a> {
+    ancestor: &'a [&'a str],
+    ours: &'a [&'a str],
+    theirs: &'a [&'a str],
+}
+
+impl<'a> ConflictRegion<'a> {
+    /// Returns the lines of the ancestor both sides changed
+    pub fn ancestor(&self) -> &'a [&'a str] {
+        self.ancestor
+    }
+
+    /// Returns the lines our side changed them to
+    pub fn ours(&self) -> &'a [&'a str] {
+        self.ours
+    }
+
+    /// Returns the lines their side changed them to
+    pub fn theirs(&self) -> &'a [&'a str] {
+        self.theirs
+    }
+}
+
+/// How a resolver resolves a conflict, see [`MergeOptions::set_resolver`]
+///
+/// [`MergeOptions::set_resolver`]: struct.MergeOptions.html#method.set_resolver
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Resolution {
+    /// Take the lines of our side
+    Ours,
+    /// Take the lines of their side
+    Theirs,
+    /// Replace the conflict with these lines, which are written as is and so should include
+    /// their line endings
+    Replace(Vec<String>),
+    /// Leave the conflict marked with conflict markers
+    Unresolved,
+}
+
+/// The outcome of a merge, see [`MergeOptions::merge_report`]
+///
+/// [`MergeOptions::merge_report`]: struct.MergeOptions.html#method.merge_report
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MergeReport<T> {
+    output: T,
+    conflicts: Vec<MergeConflict>,
+    resolved: usize,
+}
+
+impl<T> MergeReport<T> {
+    /// Returns the merged text, including conflict markers if there are conflicts left
+    pub fn output(&self) -> &T {
+        &self.output
+    }
+
+    /// Consumes the report, returning the merged text
+    pub fn into_output(self) -> T {
+        self.output
+    }
+
+    /// Returns `true` if the merge has conflicts left marked with conflict markers
+    pub fn has_conflicts(&self) -> bool {
+        !self.conflicts.is_empty()
+    }
+
+    /// Returns the conflicting regions left marked with conflict markers, in order
+    pub fn conflicts(&self) -> &[MergeConflict] {
+        &self.conflicts
+    }
+
+    /// Returns the number of conflicts resolved by the resolver
+    pub fn resolved_conflicts(&self) -> usize {
+        self.resolved
+    }
+}
+
 /// A collection of options for modifying the way a merge is performed
-#[derive(Debug)]
 pub struct MergeOptions {
     conflict_marker_length: usize,
     style: ConflictStyle,
+    resolver: Option<Box<ResolveFn>>,
+    #[cfg(feature = "std")]
+    in_place: bool,
+    #[cfg(feature = "std")]
+    merge_binary: bool,
+}
+
+// A user provided function resolving conflicts, see `MergeOptions::set_resolver`
+type ResolveFn = dyn Fn(&ConflictRegion<'_>) -> Resolution + Send + Sync;
+
+impl fmt::Debug for MergeOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("MergeOptions");
+        debug
+            .field("conflict_marker_length", &self.conflict_marker_length)
+            .field("style", &self.style)
+            .field("resolver", &self.resolver.is_some());
+        #[cfg(feature = "std")]
+        debug
+            .field("in_place", &self.in_place)
+            .field("merge_binary", &self.merge_binary);
+        debug.finish()
+    }
 }
 
 impl MergeOptions {
@@ -125,10 +255,18 @@ impl MergeOptions {
     /// ## Defaults
     /// * conflict_marker_length = 7
     /// * style = ConflictStyle::Diff3
+    /// * resolver = None
+    /// * in_place = false
+    /// * merge_binary = false
     pub fn new() -> Self {
         Self {
             conflict_marker_length: DEFAULT_CONFLICT_MARKER_LENGTH,
             style: ConflictStyle::Diff3,
+            resolver: None,
+            #[cfg(feature = "std")]
+            in_place: false,
+            #[cfg(feature = "std")]
+            merge_binary: false,
         }
     }
 
@@ -144,6 +282,48 @@ impl MergeOptions {
         self
     }
 
+    /// Set a resolver which is given every conflict and decides how to resolve it, e.g. by
+    /// domain rules like taking the union of both sides of a sorted list
+    ///
+    /// The resolver runs on the conflicts as they would be marked, after conflicts where both
+    /// sides made the same change or only one side changed anything have been resolved. A
+    /// conflict the resolver returns [`Resolution::Unresolved`] for is marked with conflict
+    /// markers as usual. [`MergeOptions::merge_report`] reports how many conflicts were resolved.
+    ///
+    /// When merging bytes the resolver is only given conflicts whose lines are all valid utf8,
+    /// the other ones are left unresolved.
+    ///
+    /// ```
+    /// use diffy::{MergeOptions, Resolution};
+    ///
+    /// let ancestor = "apple\ncherry\n";
+    /// let ours = "apple\nbanana\ncherry\n";
+    /// let theirs = "apple\nblueberry\ncherry\n";
+    ///
+    /// let mut options = MergeOptions::new();
+    /// options.set_resolver(|region| {
+    ///     let both = region.ours().iter().chain(region.theirs());
+    ///     let mut lines: Vec<String> = both.map(|line| line.to_string()).collect();
+    ///     lines.sort();
+    ///     lines.dedup();
+    ///     Resolution::Replace(lines)
+    /// });
+    /// assert_eq!(
+    ///     options.merge(ancestor, ours, theirs).unwrap(),
+    ///     "apple\nbanana\nblueberry\ncherry\n",
+    /// );
+    /// ```
+    ///
+    /// [`Resolution::Unresolved`]: enum.Resolution.html#variant.Unresolved
+    /// [`MergeOptions::merge_report`]: #method.merge_report
+    pub fn set_resolver(
+        &mut self,
+        resolver: impl Fn(&ConflictRegion<'_>) -> Resolution + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.resolver = Some(Box::new(resolver));
+        self
+    }
+
     /// Merge two files, given a common ancestor, based on the configured options
     pub fn merge<'a>(
         &self,
@@ -151,6 +331,59 @@ impl MergeOptions {
         ours: &'a str,
         theirs: &'a str,
     ) -> Result<String, String> {
+        let report = self.merge_report(ancestor, ours, theirs);
+        if report.has_conflicts() {
+            Err(report.output)
+        } else {
+            Ok(report.output)
+        }
+    }
+
+    /// Perform a 3-way merge between potentially non-utf8 texts
+    pub fn merge_bytes<'a>(
+        &self,
+        ancestor: &'a [u8],
+        ours: &'a [u8],
+        theirs: &'a [u8],
+    ) -> Result<Vec<u8>, Vec<u8>> {
+        let report = self.merge_bytes_report(ancestor, ours, theirs);
+        if report.has_conflicts() {
+            Err(report.output)
+        } else {
+            Ok(report.output)
+        }
+    }
+
+    /// Merge two files, given a common ancestor, reporting the conflicts left and how many
+    /// conflicts the resolver resolved, see [`MergeOptions::set_resolver`]
+    ///
+    /// [`MergeOptions::set_resolver`]: #method.set_resolver
+    pub fn merge_report(&self, ancestor: &str, ours: &str, theirs: &str) -> MergeReport<String> {
+        self.merge_labeled(ancestor, ours, theirs, DEFAULT_LABELS)
+    }
+
+    /// Perform a 3-way merge between potentially non-utf8 texts, reporting the conflicts left
+    /// and how many conflicts the resolver resolved, see [`MergeOptions::set_resolver`]
+    ///
+    /// [`MergeOptions::set_resolver`]: #method.set_resolver
+    pub fn merge_bytes_report(
+        &self,
+        ancestor: &[u8],
+        ours: &[u8],
+        theirs: &[u8],
+    ) -> MergeReport<Vec<u8>> {
+        self.merge_bytes_labeled(ancestor, ours, theirs, DEFAULT_LABELS)
+    }
+
+    // Merges two texts, putting `labels` after the markers of our side, the ancestor and their
+    // side of each conflict
+    fn merge_labeled(
+        &self,
+        ancestor: &str,
+        ours: &str,
+        theirs: &str,
+        labels: [&str; 3],
+    ) -> MergeReport<String> {
         let mut classifier = Classifier::default();
         let (ancestor_lines, ancestor_ids) = classifier.classify_lines(ancestor);
         let (our_lines, our_ids) = classifier.classify_lines(ours);
@@ -170,18 +403,52 @@ impl MergeOptions {
             &our_lines,
             &their_lines,
             &merge,
-            self.conflict_marker_length,
-            self.style,
+            self,
+            labels,
         )
     }
 
-    /// Perform a 3-way merge between potentially non-utf8 texts
-    pub fn merge_bytes<'a>(
+    // Counts the conflicts a merge would leave marked with conflict markers, up to `limit` of
+    // them, without building the merged text
+    fn count_conflicts(&self, ancestor: &str, ours: &str, theirs: &str, limit: usize) -> usize {
+        let mut classifier = Classifier::default();
+        let (ancestor_lines, ancestor_ids) = classifier.classify_lines(ancestor);
+        let (our_lines, our_ids) =
+            classify_lines_against(&mut classifier, ours, &ancestor_lines, &ancestor_ids);
+        let (their_lines, their_ids) =
+            classify_lines_against(&mut classifier, theirs, &ancestor_lines, &ancestor_ids);
+
+        let opts = DiffOptions::default();
+        let our_solution = opts.diff_slice(&ancestor_ids, &our_ids);
+        let their_solution = opts.diff_slice(&ancestor_ids, &their_ids);
+
+        // The ranges are only walked up to the last conflict which is counted
+        MergeRanges::new(Diff3Ranges::new(&our_solution, &their_solution))
+            .map(cleanup_conflict)
+            .filter(|merge_range| match merge_range {
+                MergeRange::Conflict(ancestor_range, ours_range, theirs_range) => {
+                    let region = ConflictRegion {
+                        ancestor: &ancestor_lines[ancestor_range.range()],
+                        ours: &our_lines[ours_range.range()],
+                        theirs: &their_lines[theirs_range.range()],
+                    };
+                    self.resolver.as_ref().map_or(true, |resolve| {
+                        matches!(resolve(&region), Resolution::Unresolved)
+                    })
+                }
+                _ => false,
+            })
+            .take(limit)
+            .count()
+    }
+
+    fn merge_bytes_labeled(
         &self,
-        ancestor: &'a [u8],
-        ours: &'a [u8],
-        theirs: &'a [u8],
-    ) -> Result<Vec<u8>, Vec<u8>> {
+        ancestor: &[u8],
+        ours: &[u8],
+        theirs: &[u8],
+        labels: [&str; 3],
+    ) -> MergeReport<Vec<u8>> {
         let mut classifier = Classifier::default();
         let (ancestor_lines, ancestor_ids) = classifier.classify_lines(ancestor);
         let (our_lines, our_ids) = classifier.classify_lines(ours);
@@ -201,8 +468,8 @@ impl MergeOptions {
             &our_lines,
             &their_lines,
             &merge,
-            self.conflict_marker_length,
-            self.style,
+            self,
+            labels,
         )
     }
 }
@@ -219,6 +486,12 @@ impl Default for MergeOptions {
 /// Returns `Err(String)` if there were conflicts, with the conflicting
 /// regions marked with conflict markers.
 ///
+/// Conflict markers always start on their own line. When a conflicting side ends with the final
+/// line of its file and that line lacks a trailing newline, a newline is added before the next
+/// marker; it isn't part of either side. Outside of conflicts each line is emitted exactly as it
+/// appears in the side it was taken from, so the trailing newline state of a clean merge follows
+/// whichever side last changed the end of the file.
+///
 /// ## Merging two files without conflicts
 /// ```
 /// # use diffy::merge;
@@ -277,28 +550,130 @@ pub fn merge_bytes<'a>(
     MergeOptions::default().merge_bytes(ancestor, ours, theirs)
 }
 
+/// Returns `true` if merging `ours` and `theirs` with [`merge`] and `options` wouldn't leave any
+/// conflicts, without building the merged text
+///
+/// The texts are aligned exactly as [`merge`] does, so conflicts the resolver of `options`
+/// resolves don't count. The check stops at the first conflict which is left.
+///
+/// ```
+/// use diffy::{merge_is_clean, MergeOptions};
+///
+/// let ancestor = "a\nb\nc\n";
+/// let options = MergeOptions::new();
+/// assert!(merge_is_clean(ancestor, "A\nb\nc\n", "a\nb\nC\n", &options));
+/// assert!(!merge_is_clean(ancestor, "A\nb\nc\n", "a2\nb\nc\n", &options));
+/// ```
+///
+/// [`merge`]: fn.merge.html
+pub fn merge_is_clean(ancestor: &str, ours: &str, theirs: &str, options: &MergeOptions) -> bool {
+    options.count_conflicts(ancestor, ours, theirs, 1) == 0
+}
+
+/// Returns the number of conflicts merging `ours` and `theirs` with [`merge`] and `options`
+/// would leave marked with conflict markers, without building the merged text
+///
+/// See [`merge_is_clean`].
+///
+/// [`merge`]: fn.merge.html
+/// [`merge_is_clean`]: fn.merge_is_clean.html
+pub fn merge_conflict_count(
+    ancestor: &str,
+    ours: &str,
+    theirs: &str,
+    options: &MergeOptions,
+) -> usize {
+    options.count_conflicts(ancestor, ours, theirs, usize::MAX)
+}
+
+// Classifies the lines of `text` like `Classifier::classify_lines`, but takes the ids of the lines
+// it shares with the start and the end of `base` from `base_ids` rather than hashing them again.
+// Both sides of a merge usually share most of their lines with the ancestor, so this skips most
+// of the work of classifying them.
+fn classify_lines_against<'a>(
+    classifier: &mut Classifier<'a, str>,
+    text: &'a str,
+    base_lines: &[&'a str],
+    base_ids: &[u64],
+) -> (Vec<&'a str>, Vec<u64>) {
+    let lines: Vec<&str> = LineIter::new(text).collect();
+    let prefix_len = lines
+        .iter()
+        .zip(base_lines)
+        .take_while(|(line, base_line)| line == base_line)
+        .count();
+    let suffix_len = lines[prefix_len..]
+        .iter()
+        .rev()
+        .zip(base_lines[prefix_len..].iter().rev())
+        .take_while(|(line, base_line)| line == base_line)
+        .count();
+
+    let mut ids = Vec::with_capacity(lines.len());
+    ids.extend_from_slice(&base_ids[..prefix_len]);
+    ids.extend(
+        lines[prefix_len..lines.len() - suffix_len]
+            .iter()
+            .map(|line| classifier.classify(line)),
+    );
+    ids.extend_from_slice(&base_ids[base_ids.len() - suffix_len..]);
+    (lines, ids)
+}
+
 fn merge_solutions<'ancestor, 'ours, 'theirs, T: ?Sized + SliceLike>(
     our_solution: &[DiffRange<'ancestor, 'ours, T>],
     their_solution: &[DiffRange<'ancestor, 'theirs, T>],
 ) -> Vec<Diff3Range<'ancestor, 'ours, 'theirs, T>> {
-    let mut our_solution = our_solution.iter().copied();
-    let mut their_solution = their_solution.iter().copied();
-    let mut ours = our_solution.next();
-    let mut theirs = their_solution.next();
+    Diff3Ranges::new(our_solution, their_solution).collect()
+}
+
+// Walks the diffs of both sides against the ancestor together, yielding the ranges of the
+// ancestor which are equal, deleted or changed on either side
+struct Diff3Ranges<'s, 'ancestor, 'ours, 'theirs, T: ?Sized> {
+    our_solution: core::slice::Iter<'s, DiffRange<'ancestor, 'ours, T>>,
+    their_solution: core::slice::Iter<'s, DiffRange<'ancestor, 'theirs, T>>,
+    ours: Option<DiffRange<'ancestor, 'ours, T>>,
+    theirs: Option<DiffRange<'ancestor, 'theirs, T>>,
+}
+
+impl<'s, 'ancestor, 'ours, 'theirs, T: ?Sized + SliceLike>
+    Diff3Ranges<'s, 'ancestor, 'ours, 'theirs, T>
+{
+    fn new(
+        our_solution: &'s [DiffRange<'ancestor, 'ours, T>],
+        their_solution: &'s [DiffRange<'ancestor, 'theirs, T>],
+    ) -> Self {
+        let mut our_solution = our_solution.iter();
+        let mut their_solution = their_solution.iter();
+        Self {
+            ours: our_solution.next().copied(),
+            theirs: their_solution.next().copied(),
+            our_solution,
+            their_solution,
+        }
+    }
+}
+
+impl<'ancestor, 'ours, 'theirs, T: ?Sized + SliceLike> Iterator
+    for Diff3Ranges<'_, 'ancestor, 'ours, 'theirs, T>
+{
+    type Item = Diff3Range<'ancestor, 'ours, 'theirs, T>;
 
-    let mut solution = Vec::new();
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.ours.is_none() && self.theirs.is_none() {
+            return None;
+        }
 
-    while ours.is_some() || theirs.is_some() {
-        let merge_range = match (ours, theirs) {
+        let merge_range = match (self.ours, self.theirs) {
             //
             // Inserts can't easily be checked to see if they match each other
             //
             (Some(DiffRange::Insert(range)), _) => {
-                ours.take();
+                self.ours.take();
                 Diff3Range::Ours(range)
             }
             (_, Some(DiffRange::Insert(range))) => {
-                theirs.take();
+                self.theirs.take();
                 Diff3Range::Theirs(range)
             }
 
@@ -309,8 +684,8 @@ fn merge_solutions<'ancestor, 'ours, 'theirs, T: ?Sized + SliceLike>(
                 assert_eq!(ancestor1.offset(), ancestor2.offset());
                 let len = cmp::min(ancestor1.len(), ancestor2.len());
 
-                shrink_front(&mut ours, len);
-                shrink_front(&mut theirs, len);
+                shrink_front(&mut self.ours, len);
+                shrink_front(&mut self.theirs, len);
 
                 Diff3Range::Equal(
                     ancestor1.slice(..len),
@@ -323,8 +698,8 @@ fn merge_solutions<'ancestor, 'ours, 'theirs, T: ?Sized + SliceLike>(
                 assert_eq!(ancestor1.offset(), ancestor2.offset());
                 let len = cmp::min(ancestor1.len(), ancestor2.len());
 
-                shrink_front(&mut ours, len);
-                shrink_front(&mut theirs, len);
+                shrink_front(&mut self.ours, len);
+                shrink_front(&mut self.theirs, len);
 
                 Diff3Range::AncestorOurs(ancestor1.slice(..len), our_range.slice(..len))
             }
@@ -336,8 +711,8 @@ fn merge_solutions<'ancestor, 'ours, 'theirs, T: ?Sized + SliceLike>(
                 assert_eq!(ancestor1.offset(), ancestor2.offset());
                 let len = cmp::min(ancestor1.len(), ancestor2.len());
 
-                shrink_front(&mut ours, len);
-                shrink_front(&mut theirs, len);
+                shrink_front(&mut self.ours, len);
+                shrink_front(&mut self.theirs, len);
 
                 Diff3Range::AncestorTheirs(ancestor2.slice(..len), their_range.slice(..len))
             }
@@ -346,8 +721,8 @@ fn merge_solutions<'ancestor, 'ours, 'theirs, T: ?Sized + SliceLike>(
                 assert_eq!(ancestor1.offset(), ancestor2.offset());
                 let len = cmp::min(ancestor1.len(), ancestor2.len());
 
-                shrink_front(&mut ours, len);
-                shrink_front(&mut theirs, len);
+                shrink_front(&mut self.ours, len);
+                shrink_front(&mut self.theirs, len);
 
                 Diff3Range::Ancestor(ancestor1.slice(..len))
             }
@@ -362,17 +737,15 @@ fn merge_solutions<'ancestor, 'ours, 'theirs, T: ?Sized + SliceLike>(
             | (None, None) => unreachable!("Equal/Delete should match up"),
         };
 
-        solution.push(merge_range);
-
-        if ours.map_or(true, |range| range.is_empty()) {
-            ours = our_solution.next();
+        if self.ours.map_or(true, |range| range.is_empty()) {
+            self.ours = self.our_solution.next().copied();
         }
-        if theirs.map_or(true, |range| range.is_empty()) {
-            theirs = their_solution.next();
+        if self.theirs.map_or(true, |range| range.is_empty()) {
+            self.theirs = self.their_solution.next().copied();
         }
-    }
 
-    solution
+        Some(merge_range)
+    }
 }
 
 fn shrink_front<T: ?Sized + SliceLike>(maybe_range: &mut Option<DiffRange<T>>, len: usize) {
@@ -384,45 +757,80 @@ fn shrink_front<T: ?Sized + SliceLike>(maybe_range: &mut Option<DiffRange<T>>, l
 fn diff3_range_to_merge_range<'ancestor, 'ours, 'theirs, T: ?Sized + SliceLike>(
     solution: &[Diff3Range<'ancestor, 'ours, 'theirs, T>],
 ) -> Vec<MergeRange<'ancestor, 'ours, 'theirs, T>> {
-    let mut ancestor: Option<Range<'ancestor, T>> = None;
-    let mut ours: Option<Range<'ours, T>> = None;
-    let mut theirs: Option<Range<'theirs, T>> = None;
+    MergeRanges::new(solution.iter().copied()).collect()
+}
 
-    let mut merge = Vec::new();
+// Groups the ranges between the equal ranges of a 3-way diff into the changes of one side or
+// conflicts
+struct MergeRanges<'ancestor, 'ours, 'theirs, T: ?Sized, I> {
+    solution: I,
+    ancestor: Option<Range<'ancestor, T>>,
+    ours: Option<Range<'ours, T>>,
+    theirs: Option<Range<'theirs, T>>,
+    // An equal range following a change which was just returned
+    equal: Option<MergeRange<'ancestor, 'ours, 'theirs, T>>,
+}
 
-    for &diff3 in solution {
-        match diff3 {
-            Diff3Range::Equal(ancestor_range, our_range, their_range) => {
-                if let Some(merge_range) =
-                    create_merge_range(ancestor.take(), ours.take(), theirs.take())
-                {
-                    merge.push(merge_range);
+impl<'ancestor, 'ours, 'theirs, T: ?Sized, I> MergeRanges<'ancestor, 'ours, 'theirs, T, I> {
+    fn new(solution: I) -> Self {
+        Self {
+            solution,
+            ancestor: None,
+            ours: None,
+            theirs: None,
+            equal: None,
+        }
+    }
+}
+
+impl<'ancestor, 'ours, 'theirs, T, I> Iterator for MergeRanges<'ancestor, 'ours, 'theirs, T, I>
+where
+    T: ?Sized + SliceLike,
+    I: Iterator<Item = Diff3Range<'ancestor, 'ours, 'theirs, T>>,
+{
+    type Item = MergeRange<'ancestor, 'ours, 'theirs, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(equal) = self.equal.take() {
+            return Some(equal);
+        }
+
+        for diff3 in &mut self.solution {
+            match diff3 {
+                Diff3Range::Equal(ancestor_range, our_range, their_range) => {
+                    let equal = MergeRange::Equal(ancestor_range, our_range, their_range);
+                    match create_merge_range(
+                        self.ancestor.take(),
+                        self.ours.take(),
+                        self.theirs.take(),
+                    ) {
+                        Some(merge_range) => {
+                            self.equal = Some(equal);
+                            return Some(merge_range);
+                        }
+                        None => return Some(equal),
+                    }
                 }
-                merge.push(MergeRange::Equal(ancestor_range, our_range, their_range));
-            }
-            Diff3Range::Ancestor(range) => {
-                set_or_merge_range(&mut ancestor, range);
-                set_or_merge_range(&mut ours, Range::empty());
-                set_or_merge_range(&mut theirs, Range::empty());
-            }
-            Diff3Range::AncestorOurs(ancestor_range, our_range) => {
-                set_or_merge_range(&mut ancestor, ancestor_range);
-                set_or_merge_range(&mut ours, our_range);
-            }
-            Diff3Range::AncestorTheirs(ancestor_range, their_range) => {
-                set_or_merge_range(&mut ancestor, ancestor_range);
-                set_or_merge_range(&mut theirs, their_range);
+                Diff3Range::Ancestor(range) => {
+                    set_or_merge_range(&mut self.ancestor, range);
+                    set_or_merge_range(&mut self.ours, Range::empty());
+                    set_or_merge_range(&mut self.theirs, Range::empty());
+                }
+                Diff3Range::AncestorOurs(ancestor_range, our_range) => {
+                    set_or_merge_range(&mut self.ancestor, ancestor_range);
+                    set_or_merge_range(&mut self.ours, our_range);
+                }
+                Diff3Range::AncestorTheirs(ancestor_range, their_range) => {
+                    set_or_merge_range(&mut self.ancestor, ancestor_range);
+                    set_or_merge_range(&mut self.theirs, their_range);
+                }
+                Diff3Range::Ours(range) => set_or_merge_range(&mut self.ours, range),
+                Diff3Range::Theirs(range) => set_or_merge_range(&mut self.theirs, range),
             }
-            Diff3Range::Ours(range) => set_or_merge_range(&mut ours, range),
-            Diff3Range::Theirs(range) => set_or_merge_range(&mut theirs, range),
         }
-    }
 
-    if let Some(merge_range) = create_merge_range(ancestor.take(), ours.take(), theirs.take()) {
-        merge.push(merge_range);
+        create_merge_range(self.ancestor.take(), self.ours.take(), self.theirs.take())
     }
-
-    merge
 }
 
 fn set_or_merge_range<'a, T: ?Sized>(range1: &mut Option<Range<'a, T>>, range2: Range<'a, T>) {
@@ -464,29 +872,36 @@ fn create_merge_range<'ancestor, 'ours, 'theirs, T: ?Sized + SliceLike>(
     }
 }
 
-#[allow(clippy::needless_lifetimes)]
-fn cleanup_conflicts<'ancestor, 'ours, 'theirs, T: ?Sized + SliceLike + PartialEq>(
-    solution: &mut [MergeRange<'ancestor, 'ours, 'theirs, T>],
+fn cleanup_conflicts<T: ?Sized + SliceLike + PartialEq>(
+    solution: &mut [MergeRange<'_, '_, '_, T>],
 ) {
-    let mut pointer = 0;
-
-    // TODO this could probably be more sophisticated:
-    // e.g. run the diff algorithm on the conflict area
-    while let Some(&merge) = solution.get(pointer) {
-        if let MergeRange::Conflict(ancestor, ours, theirs) = merge {
-            // If the ranges in the conflict end up being the same on both sides then we can
-            // eliminate the conflict
-            if ours.as_slice() == theirs.as_slice() {
-                solution[pointer] = MergeRange::Both(ours, theirs);
-            // If either ours or theirs exactly matches ancestor then we can also eliminate the
-            // conflict
-            } else if ancestor.as_slice() == ours.as_slice() {
-                solution[pointer] = MergeRange::Theirs(theirs);
-            } else if ancestor.as_slice() == theirs.as_slice() {
-                solution[pointer] = MergeRange::Ours(ours);
-            }
+    for merge in solution {
+        *merge = cleanup_conflict(*merge);
+    }
+}
+
+// TODO this could probably be more sophisticated:
+// e.g. run the diff algorithm on the conflict area
+fn cleanup_conflict<'ancestor, 'ours, 'theirs, T: ?Sized + SliceLike + PartialEq>(
+    merge: MergeRange<'ancestor, 'ours, 'theirs, T>,
+) -> MergeRange<'ancestor, 'ours, 'theirs, T> {
+    match merge {
+        // If the ranges in the conflict end up being the same on both sides then we can
+        // eliminate the conflict
+        MergeRange::Conflict(_, ours, theirs) if ours.as_slice() == theirs.as_slice() => {
+            MergeRange::Both(ours, theirs)
+        }
+        // If either ours or theirs exactly matches ancestor then we can also eliminate the
+        // conflict
+        MergeRange::Conflict(ancestor, ours, theirs) if ancestor.as_slice() == ours.as_slice() => {
+            MergeRange::Theirs(theirs)
         }
-        pointer += 1;
+        MergeRange::Conflict(ancestor, ours, theirs)
+            if ancestor.as_slice() == theirs.as_slice() =>
+        {
+            MergeRange::Ours(ours)
+        }
+        merge => merge,
     }
 }
 
@@ -495,11 +910,14 @@ fn output_result<'a, T: ?Sized>(
     ours: &[&'a str],
     theirs: &[&'a str],
     merge: &[MergeRange<T>],
-    marker_len: usize,
-    style: ConflictStyle,
-) -> Result<String, String> {
-    let mut conflicts = 0;
+    options: &MergeOptions,
+    [our_label, ancestor_label, their_label]: [&str; 3],
+) -> MergeReport<String> {
+    let mut conflicts = Vec::new();
+    let mut resolved = 0;
     let mut output = String::new();
+    let mut lines = LineCounter::default();
+    let (marker_len, style) = (options.conflict_marker_length, options.style);
 
     for merge_range in merge {
         match merge_range {
@@ -507,18 +925,48 @@ fn output_result<'a, T: ?Sized>(
                 output.extend(ancestor[range.range()].iter().copied());
             }
             MergeRange::Conflict(ancestor_range, ours_range, theirs_range) => {
-                add_conflict_marker(&mut output, '<', marker_len, Some("ours"));
-                output.extend(ours[ours_range.range()].iter().copied());
-
-                if let ConflictStyle::Diff3 = style {
-                    add_conflict_marker(&mut output, '|', marker_len, Some("original"));
-                    output.extend(ancestor[ancestor_range.range()].iter().copied());
+                let region = ConflictRegion {
+                    ancestor: &ancestor[ancestor_range.range()],
+                    ours: &ours[ours_range.range()],
+                    theirs: &theirs[theirs_range.range()],
+                };
+                match options
+                    .resolver
+                    .as_ref()
+                    .map_or(Resolution::Unresolved, |resolve| resolve(&region))
+                {
+                    Resolution::Ours => output.extend(region.ours.iter().copied()),
+                    Resolution::Theirs => output.extend(region.theirs.iter().copied()),
+                    Resolution::Replace(lines) => output.extend(lines),
+                    Resolution::Unresolved => {
+                        add_conflict_marker(&mut output, '<', marker_len, Some(our_label));
+                        let start = lines.count(output.as_bytes()) - 1;
+                        output.extend(region.ours.iter().copied());
+
+                        if let ConflictStyle::Diff3 = style {
+                            add_conflict_marker(&mut output, '|', marker_len, Some(ancestor_label));
+                            output.extend(region.ancestor.iter().copied());
+                        }
+
+                        add_conflict_marker(&mut output, '=', marker_len, None);
+                        output.extend(region.theirs.iter().copied());
+                        add_conflict_marker(&mut output, '>', marker_len, Some(their_label));
+                        let missing_newline =
+                            lacks_newline(region.ours) && lacks_newline(region.theirs);
+                        if missing_newline {
+                            output.pop();
+                        }
+                        conflicts.push(MergeConflict {
+                            ancestor: ancestor_range.range(),
+                            ours: ours_range.range(),
+                            theirs: theirs_range.range(),
+                            merged: start
+                                ..lines.count(output.as_bytes()) + usize::from(missing_newline),
+                        });
+                        continue;
+                    }
                 }
-
-                add_conflict_marker(&mut output, '=', marker_len, None);
-                output.extend(theirs[theirs_range.range()].iter().copied());
-                add_conflict_marker(&mut output, '>', marker_len, Some("theirs"));
-                conflicts += 1;
+                resolved += 1;
             }
             MergeRange::Ours(range) => {
                 output.extend(ours[range.range()].iter().copied());
@@ -532,19 +980,27 @@ fn output_result<'a, T: ?Sized>(
         }
     }
 
-    if conflicts != 0 {
-        Err(output)
-    } else {
-        Ok(output)
+    MergeReport {
+        output,
+        conflicts,
+        resolved,
     }
 }
 
+// Conflict markers always start on their own line. If the content preceding a marker is the final
+// line of one of the inputs and lacks a trailing newline, one is added so that the marker isn't
+// glued onto the end of that line. When both sides of a conflict lack a trailing newline the
+// closing marker lacks one as well, see `lacks_newline`.
 fn add_conflict_marker(
     output: &mut String,
     marker: char,
     marker_len: usize,
     filename: Option<&str>,
 ) {
+    if !output.is_empty() && !output.ends_with('\n') {
+        output.push('\n');
+    }
+
     for _ in 0..marker_len {
         output.push(marker);
     }
@@ -561,11 +1017,14 @@ fn output_result_bytes<'a, T: ?Sized>(
     ours: &[&'a [u8]],
     theirs: &[&'a [u8]],
     merge: &[MergeRange<T>],
-    marker_len: usize,
-    style: ConflictStyle,
-) -> Result<Vec<u8>, Vec<u8>> {
-    let mut conflicts = 0;
+    options: &MergeOptions,
+    [our_label, ancestor_label, their_label]: [&str; 3],
+) -> MergeReport<Vec<u8>> {
+    let mut conflicts = Vec::new();
+    let mut resolved = 0;
     let mut output: Vec<u8> = Vec::new();
+    let mut lines = LineCounter::default();
+    let (marker_len, style) = (options.conflict_marker_length, options.style);
 
     for merge_range in merge {
         match merge_range {
@@ -575,24 +1034,73 @@ fn output_result_bytes<'a, T: ?Sized>(
                     .for_each(|line| output.extend_from_slice(line));
             }
             MergeRange::Conflict(ancestor_range, ours_range, theirs_range) => {
-                add_conflict_marker_bytes(&mut output, b'<', marker_len, Some(b"ours"));
-                ours[ours_range.range()]
-                    .iter()
-                    .for_each(|line| output.extend_from_slice(line));
-
-                if let ConflictStyle::Diff3 = style {
-                    add_conflict_marker_bytes(&mut output, b'|', marker_len, Some(b"original"));
-                    ancestor[ancestor_range.range()]
+                let ancestor_lines = &ancestor[ancestor_range.range()];
+                let our_lines = &ours[ours_range.range()];
+                let their_lines = &theirs[theirs_range.range()];
+                match resolve_bytes(
+                    options.resolver.as_deref(),
+                    ancestor_lines,
+                    our_lines,
+                    their_lines,
+                ) {
+                    Resolution::Ours => our_lines
+                        .iter()
+                        .for_each(|line| output.extend_from_slice(line)),
+                    Resolution::Theirs => their_lines
                         .iter()
-                        .for_each(|line| output.extend_from_slice(line));
+                        .for_each(|line| output.extend_from_slice(line)),
+                    Resolution::Replace(lines) => lines
+                        .iter()
+                        .for_each(|line| output.extend_from_slice(line.as_bytes())),
+                    Resolution::Unresolved => {
+                        add_conflict_marker_bytes(
+                            &mut output,
+                            b'<',
+                            marker_len,
+                            Some(our_label.as_bytes()),
+                        );
+                        let start = lines.count(&output) - 1;
+                        our_lines
+                            .iter()
+                            .for_each(|line| output.extend_from_slice(line));
+
+                        if let ConflictStyle::Diff3 = style {
+                            add_conflict_marker_bytes(
+                                &mut output,
+                                b'|',
+                                marker_len,
+                                Some(ancestor_label.as_bytes()),
+                            );
+                            ancestor_lines
+                                .iter()
+                                .for_each(|line| output.extend_from_slice(line));
+                        }
+
+                        add_conflict_marker_bytes(&mut output, b'=', marker_len, None);
+                        their_lines
+                            .iter()
+                            .for_each(|line| output.extend_from_slice(line));
+                        add_conflict_marker_bytes(
+                            &mut output,
+                            b'>',
+                            marker_len,
+                            Some(their_label.as_bytes()),
+                        );
+                        let missing_newline =
+                            lacks_newline(our_lines) && lacks_newline(their_lines);
+                        if missing_newline {
+                            output.pop();
+                        }
+                        conflicts.push(MergeConflict {
+                            ancestor: ancestor_range.range(),
+                            ours: ours_range.range(),
+                            theirs: theirs_range.range(),
+                            merged: start..lines.count(&output) + usize::from(missing_newline),
+                        });
+                        continue;
+                    }
                 }
-
-                add_conflict_marker_bytes(&mut output, b'=', marker_len, None);
-                theirs[theirs_range.range()]
-                    .iter()
-                    .for_each(|line| output.extend_from_slice(line));
-                add_conflict_marker_bytes(&mut output, b'>', marker_len, Some(b"theirs"));
-                conflicts += 1;
+                resolved += 1;
             }
             MergeRange::Ours(range) => {
                 ours[range.range()]
@@ -612,10 +1120,35 @@ fn output_result_bytes<'a, T: ?Sized>(
         }
     }
 
-    if conflicts != 0 {
-        Err(output)
-    } else {
-        Ok(output)
+    MergeReport {
+        output,
+        conflicts,
+        resolved,
+    }
+}
+
+// Asks the resolver how to resolve a conflict of a merge of bytes, leaving the conflict unresolved
+// if any of its lines isn't valid utf8
+fn resolve_bytes(
+    resolver: Option<&ResolveFn>,
+    ancestor: &[&[u8]],
+    ours: &[&[u8]],
+    theirs: &[&[u8]],
+) -> Resolution {
+    let resolve = match resolver {
+        Some(resolve) => resolve,
+        None => return Resolution::Unresolved,
+    };
+    fn text<'a>(lines: &[&'a [u8]]) -> Result<Vec<&'a str>, str::Utf8Error> {
+        lines.iter().map(|line| str::from_utf8(line)).collect()
+    }
+    match (text(ancestor), text(ours), text(theirs)) {
+        (Ok(ancestor), Ok(ours), Ok(theirs)) => resolve(&ConflictRegion {
+            ancestor: &ancestor,
+            ours: &ours,
+            theirs: &theirs,
+        }),
+        _ => Resolution::Unresolved,
     }
 }
 
@@ -625,6 +1158,10 @@ fn add_conflict_marker_bytes(
     marker_len: usize,
     filename: Option<&[u8]>,
 ) {
+    if !output.is_empty() && !output.ends_with(b"\n") {
+        output.push(b'\n');
+    }
+
     for _ in 0..marker_len {
         output.push(marker);
     }
@@ -635,3 +1172,29 @@ fn add_conflict_marker_bytes(
     }
     output.push(b'\n');
 }
+
+// Returns `true` if the last of `lines` lacks a trailing newline. When that's the case for both
+// sides of a conflict the merged text ends without one whichever side is picked, so the newline
+// after the closing conflict marker is dropped to preserve that.
+fn lacks_newline<T: AsRef<[u8]>>(lines: &[T]) -> bool {
+    lines
+        .last()
+        .map_or(false, |line| !line.as_ref().ends_with(b"\n"))
+}
+
+// Counts the lines of a growing output, only looking at what was added since the last count
+#[derive(Default)]
+struct LineCounter {
+    counted_len: usize,
+    lines: usize,
+}
+
+impl LineCounter {
+    // Returns the number of lines ending in a newline
+    fn count(&mut self, output: &[u8]) -> usize {
+        let added = &output[self.counted_len..];
+        self.lines += added.iter().filter(|&&b| b == b'\n').count();
+        self.counted_len = output.len();
+        self.lines
+    }
+}
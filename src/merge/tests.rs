@@ -404,3 +404,306 @@ fn delete_and_insert_conflict() {
         "MergeRange (Theirs::delete, Ours::insert) conflict"
     );
 }
+
+#[test]
+fn conflict_markers_without_trailing_newlines() {
+    // Every combination of trailing newline presence across the three inputs. The markers always
+    // start on their own line regardless of which sides are missing a trailing newline, and
+    // when both sides are missing it so is the closing marker.
+    for ancestor_nl in ["", "\n"] {
+        for ours_nl in ["", "\n"] {
+            for theirs_nl in ["", "\n"] {
+                let ancestor = &*format!("a\nb{}", ancestor_nl);
+                let ours = &*format!("a\nB1{}", ours_nl);
+                let theirs = &*format!("a\nB2{}", theirs_nl);
+                let expected = &*format!(
+                    "a\n<<<<<<< ours\nB1\n||||||| original\nb\n=======\nB2\n>>>>>>> theirs{}",
+                    if ours_nl.is_empty() && theirs_nl.is_empty() {
+                        ""
+                    } else {
+                        "\n"
+                    }
+                );
+                assert_merge!(
+                    ancestor,
+                    ours,
+                    theirs,
+                    Err(expected),
+                    "conflict on final line"
+                );
+            }
+        }
+    }
+
+    // Conflicting insertions at the end of a file without a trailing newline
+    let ancestor = "a";
+    let ours = "a\nb";
+    let theirs = "a\nc";
+    let expected = "\
+<<<<<<< ours
+a
+b
+||||||| original
+a
+=======
+a
+c
+>>>>>>> theirs";
+    assert_merge!(ancestor, ours, theirs, Err(expected), "conflicting appends");
+    let report = MergeOptions::new().merge_report(ancestor, ours, theirs);
+    assert_eq!(report.conflicts()[0].merged, 0..9);
+    let report = MergeOptions::new().merge_bytes_report(
+        ancestor.as_bytes(),
+        ours.as_bytes(),
+        theirs.as_bytes(),
+    );
+    assert_eq!(report.conflicts()[0].merged, 0..9);
+
+    // Picking either side of a conflict the newline is missing from keeps it missing
+    let mut opts = MergeOptions::new();
+    opts.set_resolver(|_| Resolution::Theirs);
+    assert_eq!(opts.merge(ancestor, ours, theirs).unwrap(), theirs);
+    opts.set_resolver(|_| Resolution::Ours);
+    assert_eq!(
+        opts.merge_bytes(ancestor.as_bytes(), ours.as_bytes(), theirs.as_bytes())
+            .unwrap(),
+        ours.as_bytes()
+    );
+
+    // Only the closing marker follows a side that lacks a trailing newline
+    let ancestor = "a\nb\n";
+    let ours = "a\nB1\n";
+    let theirs = "a\nB2";
+    let mut opts = MergeOptions::new();
+    opts.set_conflict_style(ConflictStyle::Merge);
+    let expected = "a\n<<<<<<< ours\nB1\n=======\nB2\n>>>>>>> theirs\n";
+    assert_eq!(opts.merge(ancestor, ours, theirs).unwrap_err(), expected);
+    assert_eq!(
+        opts.merge_bytes(ancestor.as_bytes(), ours.as_bytes(), theirs.as_bytes())
+            .unwrap_err(),
+        expected.as_bytes()
+    );
+}
+
+#[test]
+fn clean_merge_trailing_newline_follows_last_change() {
+    for ancestor_nl in ["", "\n"] {
+        for changed_nl in ["", "\n"] {
+            let ancestor = &*format!("a\nb\nc{}", ancestor_nl);
+            // One side changes the start of the file and leaves the end untouched
+            let start = &*format!("A\nb\nc{}", ancestor_nl);
+            // The other side changes the final line
+            let end = &*format!("a\nb\nC{}", changed_nl);
+            let expected = &*format!("A\nb\nC{}", changed_nl);
+
+            assert_merge!(ancestor, start, end, Ok(expected), "theirs touched EOF");
+            assert_merge!(ancestor, end, start, Ok(expected), "ours touched EOF");
+        }
+    }
+
+    // One side only changes the trailing newline of the final line, the other the first line
+    let ancestor = "a\nx\nb\n";
+    let ours = "a\nx\nb";
+    let theirs = "A\nx\nb\n";
+    let expected = "A\nx\nb";
+    assert_merge!(ancestor, ours, theirs, Ok(expected), "ours removed newline");
+    assert_merge!(
+        ancestor,
+        theirs,
+        ours,
+        Ok(expected),
+        "theirs removed newline"
+    );
+
+    let ancestor = "a\nx\nb";
+    let ours = "a\nx\nb\n";
+    let theirs = "A\nx\nb";
+    let expected = "A\nx\nb\n";
+    assert_merge!(ancestor, ours, theirs, Ok(expected), "ours added newline");
+    assert_merge!(ancestor, theirs, ours, Ok(expected), "theirs added newline");
+
+    // Both sides make the same change to the end of the file
+    let ancestor = "a\nx\nb\n";
+    let ours = "A\nx\nb";
+    let theirs = "a\nx\nb";
+    let expected = "A\nx\nb";
+    assert_merge!(ancestor, ours, theirs, Ok(expected), "both removed newline");
+}
+
+// Resolves conflicts in a sorted list by taking the lines of both sides, sorted
+fn union_sorted(region: &ConflictRegion<'_>) -> Resolution {
+    let both = region.ours().iter().chain(region.theirs());
+    let mut lines: Vec<String> = both.map(|line| line.to_string()).collect();
+    lines.sort();
+    lines.dedup();
+    Resolution::Replace(lines)
+}
+
+#[test]
+fn resolver() {
+    let ancestor = "apple\ncherry\nfig\nkiwi\n";
+    let ours = "apple\nbanana\ncherry\nfig\nkiwi\nlime\n";
+    let theirs = "apple\nblueberry\ncherry\nfig\nkiwi\nmango\n";
+
+    let mut options = MergeOptions::new();
+    let report = options.merge_report(ancestor, ours, theirs);
+    assert_eq!(report.conflicts().len(), 2);
+    assert_eq!(report.resolved_conflicts(), 0);
+
+    options.set_resolver(union_sorted);
+    let expected = "apple\nbanana\nblueberry\ncherry\nfig\nkiwi\nlime\nmango\n";
+    let report = options.merge_report(ancestor, ours, theirs);
+    assert!(!report.has_conflicts());
+    assert_eq!(report.resolved_conflicts(), 2);
+    assert_eq!(report.output(), expected);
+    assert_eq!(options.merge(ancestor, ours, theirs).unwrap(), expected);
+
+    let report =
+        options.merge_bytes_report(ancestor.as_bytes(), ours.as_bytes(), theirs.as_bytes());
+    assert_eq!(report.resolved_conflicts(), 2);
+    assert_eq!(report.into_output(), expected.as_bytes());
+
+    // Regions which aren't valid utf8 aren't handed to the resolver
+    let report = options.merge_bytes_report(
+        ancestor.as_bytes(),
+        b"apple\nbanana\ncherry\nfig\nkiwi\n\xff\n",
+        theirs.as_bytes(),
+    );
+    assert_eq!(report.resolved_conflicts(), 1);
+    assert_eq!(report.conflicts().len(), 1);
+    assert_eq!(report.conflicts()[0].ours, 5..6);
+
+    // The resolver only sees conflicts left after both sides making the same change
+    options.set_resolver(|region| {
+        assert_ne!(region.ours(), region.theirs());
+        match region.ancestor() {
+            [] => Resolution::Theirs,
+            _ => Resolution::Ours,
+        }
+    });
+    let ours = "apple\ncherry\nFIG\nkiwi\nlime\n";
+    let theirs = "apple\ncherry\nfig2\nkiwi\nmango\n";
+    let report = options.merge_report(ancestor, ours, theirs);
+    assert_eq!(report.resolved_conflicts(), 2);
+    assert_eq!(report.output(), "apple\ncherry\nFIG\nkiwi\nmango\n");
+
+    // Resolvers may capture state
+    let preferred = String::from("mango\n");
+    options.set_resolver(move |region| {
+        if region.theirs().contains(&preferred.as_str()) {
+            Resolution::Theirs
+        } else {
+            Resolution::Unresolved
+        }
+    });
+    let report = options.merge_report(ancestor, ours, theirs);
+    assert_eq!(report.resolved_conflicts(), 1);
+    assert_eq!(report.conflicts().len(), 1);
+    assert!(format!("{:?}", options).contains("resolver: true"));
+    assert!(format!("{:?}", MergeOptions::new()).contains("resolver: false"));
+}
+
+#[test]
+fn unresolved_resolver() {
+    let ancestor = "a\nb\nc\n";
+    let ours = "a\nB\nc\n";
+    let theirs = "a\nb2\nc\n";
+
+    let mut options = MergeOptions::new();
+    options.set_resolver(|region| {
+        assert_eq!(region.ancestor(), ["b\n"]);
+        assert_eq!(region.ours(), ["B\n"]);
+        assert_eq!(region.theirs(), ["b2\n"]);
+        Resolution::Unresolved
+    });
+
+    let report = options.merge_report(ancestor, ours, theirs);
+    assert!(report.has_conflicts());
+    assert_eq!(report.resolved_conflicts(), 0);
+    assert_eq!(
+        report.conflicts(),
+        [MergeConflict {
+            ancestor: 1..2,
+            ours: 1..2,
+            theirs: 1..2,
+            merged: 1..8,
+        }]
+    );
+    assert_eq!(
+        Err(report.into_output()),
+        MergeOptions::new().merge(ancestor, ours, theirs)
+    );
+}
+
+#[test]
+fn clean_merge_predicate() {
+    let ancestor = "apple\ncherry\nfig\nkiwi\n";
+    let ours = "apple\nbanana\ncherry\nfig\nkiwi\nlime\n";
+    let theirs = "apple\nblueberry\ncherry\nfig\nkiwi\nmango\n";
+
+    let mut options = MergeOptions::new();
+    assert!(merge_is_clean(ancestor, ours, ancestor, &options));
+    assert!(merge_is_clean(ancestor, ours, ours, &options));
+    assert_eq!(merge_conflict_count(ancestor, ours, ours, &options), 0);
+    assert!(!merge_is_clean(ancestor, ours, theirs, &options));
+    assert_eq!(merge_conflict_count(ancestor, ours, theirs, &options), 2);
+
+    // Conflicts resolved by the resolver don't count
+    options.set_resolver(|region| match region.ours() {
+        ["lime\n"] => Resolution::Ours,
+        _ => Resolution::Unresolved,
+    });
+    assert_eq!(merge_conflict_count(ancestor, ours, theirs, &options), 1);
+    options.set_resolver(union_sorted);
+    assert!(merge_is_clean(ancestor, ours, theirs, &options));
+}
+
+#[cfg(feature = "std")]
+#[test]
+#[ignore = "merges texts of a million lines"]
+fn clean_merge_predicate_large_merge() {
+    use std::{format, time::Instant};
+
+    let ancestor: String = (0..1_000_000).map(|i| format!("line {}\n", i)).collect();
+    let ours = ancestor.replace("line 1000\n", "ours\n");
+    let theirs = ancestor.replace("line 900000\n", "theirs\n");
+
+    let options = MergeOptions::new();
+    let start = Instant::now();
+    assert!(options.merge(&ancestor, &ours, &theirs).is_ok());
+    let merge_time = start.elapsed();
+    let start = Instant::now();
+    assert!(merge_is_clean(&ancestor, &ours, &theirs, &options));
+    let clean_time = start.elapsed();
+    println!(
+        "clean merge: {:?}, merge_is_clean: {:?}",
+        merge_time, clean_time
+    );
+
+    // Both sides rewrite every hundredth line, so each of those is a conflict, and only the first
+    // one is walked to when checking whether the merge is clean
+    let side = |name: &str| -> String {
+        (0..1_000_000)
+            .map(|i| {
+                if i % 100 == 0 {
+                    format!("{} {}\n", name, i)
+                } else {
+                    format!("line {}\n", i)
+                }
+            })
+            .collect()
+    };
+    let ours = side("ours");
+    let theirs = side("theirs");
+
+    let start = Instant::now();
+    assert!(options.merge(&ancestor, &ours, &theirs).is_err());
+    let merge_time = start.elapsed();
+    let start = Instant::now();
+    assert!(!merge_is_clean(&ancestor, &ours, &theirs, &options));
+    let clean_time = start.elapsed();
+    println!(
+        "conflicting merge: {:?}, merge_is_clean: {:?}",
+        merge_time, clean_time
+    );
+}
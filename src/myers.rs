@@ -0,0 +1,116 @@
+//! Low-level access to the Myers diff algorithm
+//!
+//! The diffs computed by this crate use the linear space variant of [Myers' diff algorithm],
+//! which finds the middle snake of a shortest edit script between two inputs, splits both
+//! inputs around it and recurses into the halves before and after it. [`middle_snake`] exposes
+//! that first step, for building specialized recursions, e.g. ones which split inputs at
+//! domain-specific anchors first.
+//!
+//! ## Stability
+//!
+//! This module is a building block for experimenting with custom diff strategies rather than
+//! a way to get at any particular diff. The returned snake is always the middle snake of a
+//! shortest edit script, but when several shortest edit scripts exist, which one it belongs to
+//! may change between releases, just like the diffs produced by the rest of the crate may.
+//!
+//! [Myers' diff algorithm]: http://www.xmailserver.org/diff2.pdf
+
+use crate::diff::myers;
+use core::ops;
+
+/// The middle snake of a shortest edit script between two inputs, see [`middle_snake`]
+///
+/// A snake is a run of elements which are equal in both inputs, so `old[old]` equals
+/// `new[new]` and both ranges have the same length. It may be empty, in which case it only
+/// marks the point at which to split both inputs.
+///
+/// [`middle_snake`]: fn.middle_snake.html
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Snake {
+    /// The range of the old input covered by the snake
+    pub old: ops::Range<usize>,
+    /// The range of the new input covered by the snake
+    pub new: ops::Range<usize>,
+    /// The number of elements inserted plus deleted by a shortest edit script
+    pub edit_distance: usize,
+}
+
+/// Find the middle snake of a shortest edit script between `old` and `new`
+///
+/// A shortest edit script between `old` and `new` is made up of one between the elements before
+/// the snake, `old[..snake.old.start]` and `new[..snake.new.start]`, the snake itself, and one
+/// between the elements after it. Roughly half of its edits fall on each side of the snake, so
+/// recursing into both sides finds a whole edit script in `O((N + M) D)` time and linear space.
+///
+/// Common prefixes and suffixes aren't treated specially, so stripping them before looking for
+/// the snake is usually much faster.
+///
+/// ```
+/// use diffy::myers::middle_snake;
+///
+/// let old = b"ABCABBA";
+/// let new = b"CBABAC";
+///
+/// let snake = middle_snake(old, new);
+/// assert_eq!(snake.edit_distance, 5);
+/// assert_eq!(old[snake.old.clone()], new[snake.new.clone()]);
+/// ```
+pub fn middle_snake<T: PartialEq>(old: &[T], new: &[T]) -> Snake {
+    let (edit_distance, old, new) = myers::middle_snake(old, new);
+    Snake {
+        old,
+        new,
+        edit_distance,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{vec, vec::Vec};
+
+    // The number of elements inserted plus deleted by a shortest edit script, computed the slow
+    // way through the longest common subsequence
+    fn edit_distance(old: &[u8], new: &[u8]) -> usize {
+        let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+        for (i, a) in old.iter().enumerate() {
+            for (j, b) in new.iter().enumerate() {
+                lcs[i + 1][j + 1] = if a == b {
+                    lcs[i][j] + 1
+                } else {
+                    lcs[i][j + 1].max(lcs[i + 1][j])
+                };
+            }
+        }
+        old.len() + new.len() - 2 * lcs[old.len()][new.len()]
+    }
+
+    #[test]
+    fn splits_shortest_edit_scripts() {
+        // Every pair of inputs of up to 5 elements out of 2 distinct ones
+        let inputs: Vec<Vec<u8>> = (0..=5)
+            .flat_map(|len| {
+                (0..1 << len)
+                    .map(move |bits: u32| (0..len).map(|i| (bits >> i) as u8 & 1).collect())
+            })
+            .collect();
+
+        for old in &inputs {
+            for new in &inputs {
+                let snake = middle_snake(old, new);
+                assert_eq!(
+                    snake.edit_distance,
+                    edit_distance(old, new),
+                    "{:?} {:?}",
+                    old,
+                    new
+                );
+                assert_eq!(old[snake.old.clone()], new[snake.new.clone()]);
+
+                let before = edit_distance(&old[..snake.old.start], &new[..snake.new.start]);
+                let after = edit_distance(&old[snake.old.end..], &new[snake.new.end..]);
+                assert_eq!(before + after, snake.edit_distance, "{:?} {:?}", old, new);
+            }
+        }
+    }
+}
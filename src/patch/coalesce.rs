@@ -0,0 +1,219 @@
+use super::{hunk_lines_count, Hunk, HunkRange, Line, LineKind, Patch};
+use crate::utils::{LineIter, Text};
+use alloc::{borrow::ToOwned, vec::Vec};
+use core::fmt;
+
+/// An error returned when [`Patch::merge_hunks`] can't merge two hunks
+///
+/// Hunks are identified by their 1-based index in the patch being merged.
+///
+/// [`Patch::merge_hunks`]: struct.Patch.html#method.merge_hunks
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MergeHunksError {
+    /// The lines between a hunk and the one preceding it aren't covered by either hunk, and
+    /// either no original text was supplied or it ends before the gap does
+    MissingContext {
+        /// The 1-based index of the hunk following the gap
+        hunk: usize,
+    },
+    /// A hunk overlaps the one preceding it by more than their shared context, or the context
+    /// lines they share differ
+    Overlap {
+        /// The 1-based index of the overlapping hunk
+        hunk: usize,
+    },
+}
+
+impl MergeHunksError {
+    /// Returns the 1-based index of the hunk which couldn't be merged into the one preceding it
+    pub fn hunk(&self) -> usize {
+        match self {
+            MergeHunksError::MissingContext { hunk } | MergeHunksError::Overlap { hunk } => *hunk,
+        }
+    }
+}
+
+impl fmt::Display for MergeHunksError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeHunksError::MissingContext { hunk } => {
+                write!(f, "no context to fill the gap before hunk #{}", hunk)
+            }
+            MergeHunksError::Overlap { hunk } => {
+                write!(f, "hunk #{} overlaps the preceding hunk", hunk)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MergeHunksError {}
+
+impl<'a> Patch<'a, str> {
+    /// Merge hunks which are at most `max_gap` lines of the old file apart
+    ///
+    /// This is the inverse of splitting a patch into small hunks, e.g. for turning a generated
+    /// patch with many neighbouring one-line hunks into fewer chunks which are easier to review.
+    /// The lines between two merged hunks become context lines, which are taken from `original`.
+    /// Hunks which touch, or which overlap only by the context lines they share, are merged
+    /// without needing `original`. The ranges of merged hunks are recomputed from their lines,
+    /// so the merged patch applies exactly like this one.
+    ///
+    /// Fails if a gap needs to be filled but `original` is `None` or too short, or if hunks
+    /// overlap by more than their context.
+    ///
+    /// ```
+    /// use diffy::{apply, Patch};
+    ///
+    /// let original = "a\nb\nc\nd\ne\n";
+    /// let s = "\
+    /// --- original
+    /// +++ modified
+    /// @@ -1 +1 @@
+    /// -a
+    /// +A
+    /// @@ -3 +3 @@
+    /// -c
+    /// +C
+    /// @@ -5 +5 @@
+    /// -e
+    /// +E
+    /// ";
+    /// let patch = Patch::from_str(s).unwrap();
+    ///
+    /// let merged = patch.merge_hunks(1, Some(original)).unwrap();
+    /// let expected = "\
+    /// --- original
+    /// +++ modified
+    /// @@ -1,5 +1,5 @@
+    /// -a
+    /// +A
+    ///  b
+    /// -c
+    /// +C
+    ///  d
+    /// -e
+    /// +E
+    /// ";
+    /// assert_eq!(merged.to_string(), expected);
+    /// assert_eq!(apply(original, &merged), apply(original, &patch));
+    ///
+    /// // The gaps can't be filled without the original text
+    /// assert!(patch.merge_hunks(1, None).is_err());
+    /// ```
+    pub fn merge_hunks<'b>(
+        &'b self,
+        max_gap: usize,
+        original: Option<&'b str>,
+    ) -> Result<Patch<'b, str>, MergeHunksError> {
+        merge_hunks(self, max_gap, original)
+    }
+}
+
+impl<'a> Patch<'a, [u8]> {
+    /// Merge hunks of a potentially non-utf8 patch which are at most `max_gap` lines of the old
+    /// file apart, see [`Patch::merge_hunks`](struct.Patch.html#method.merge_hunks)
+    pub fn merge_hunks<'b>(
+        &'b self,
+        max_gap: usize,
+        original: Option<&'b [u8]>,
+    ) -> Result<Patch<'b, [u8]>, MergeHunksError> {
+        merge_hunks(self, max_gap, original)
+    }
+}
+
+// A run of hunks merged so far, as the 0-based start of its old and new ranges and its lines
+struct Merged<'a, T: ?Sized> {
+    old_start: usize,
+    new_start: usize,
+    function_context: Option<&'a T>,
+    lines: Vec<Line<'a, T>>,
+}
+
+impl<'a, T: ?Sized> Merged<'a, T> {
+    fn new(hunk: &Hunk<'a, T>) -> Self {
+        Self {
+            old_start: hunk.old_range.to_std_range().start,
+            new_start: hunk.new_range.to_std_range().start,
+            function_context: hunk.function_context,
+            lines: hunk.lines.clone(),
+        }
+    }
+
+    fn old_end(&self) -> usize {
+        self.old_start + hunk_lines_count(&self.lines).0
+    }
+
+    fn into_hunk(self) -> Hunk<'a, T> {
+        let (old_len, new_len) = hunk_lines_count(&self.lines);
+        Hunk::new(
+            HunkRange::from(self.old_start..self.old_start + old_len),
+            HunkRange::from(self.new_start..self.new_start + new_len),
+            self.function_context,
+            self.lines,
+        )
+    }
+}
+
+fn merge_hunks<'a, T: ?Sized + Text + ToOwned + PartialEq>(
+    patch: &Patch<'a, T>,
+    max_gap: usize,
+    original: Option<&'a T>,
+) -> Result<Patch<'a, T>, MergeHunksError> {
+    let original_lines: Option<Vec<&T>> = original.map(|text| LineIter::new(text).collect());
+
+    let mut hunks = Vec::new();
+    let mut merged: Option<Merged<'a, T>> = None;
+    for (idx, hunk) in patch.hunks.iter().enumerate() {
+        let current = match &mut merged {
+            Some(current) => current,
+            None => {
+                merged = Some(Merged::new(hunk));
+                continue;
+            }
+        };
+
+        let start = hunk.old_range.to_std_range().start;
+        let end = current.old_end();
+        let mut lines = &hunk.lines[..];
+        if start < end {
+            // The overlapping lines have to be trailing context of the run and leading context
+            // of the hunk, and be the same in both
+            let overlap = end - start;
+            let trailing = current.lines.iter().rev().take_while(|l| l.is_context());
+            let leading = lines.iter().take_while(|l| l.is_context());
+            if trailing.count() < overlap
+                || leading.count() < overlap
+                || current.lines[current.lines.len() - overlap..] != lines[..overlap]
+            {
+                return Err(MergeHunksError::Overlap { hunk: idx + 1 });
+            }
+            lines = &lines[overlap..];
+        } else if start == end {
+            // The hunks touch, so there's no gap to fill
+        } else if start - end <= max_gap {
+            let gap = original_lines
+                .as_ref()
+                .and_then(|original| original.get(end..start))
+                .ok_or(MergeHunksError::MissingContext { hunk: idx + 1 })?;
+            current
+                .lines
+                .extend(gap.iter().map(|&line| Line::new(LineKind::Context, line)));
+        } else {
+            hunks.push(merged.take().unwrap().into_hunk());
+            merged = Some(Merged::new(hunk));
+            continue;
+        }
+        current.lines.extend_from_slice(lines);
+    }
+    hunks.extend(merged.map(Merged::into_hunk));
+
+    Ok(Patch {
+        original: patch.original.clone(),
+        modified: patch.modified.clone(),
+        hunks,
+        status: patch.status,
+        mode: patch.mode,
+    })
+}
@@ -0,0 +1,211 @@
+use super::{Filename, Hunk, Line, Patch};
+use alloc::{borrow::Cow, string::String, vec::Vec};
+use core::{fmt, str};
+
+/// An error returned when converting a patch of bytes into a [`Patch<str>`] fails because
+/// part of it isn't valid utf8
+///
+/// Hunks and the lines within them are identified by their 1-based index. A hunk converted on
+/// its own with [`Hunk::to_str_hunk`] is reported as hunk #1.
+///
+/// [`Patch<str>`]: struct.Patch.html
+/// [`Hunk::to_str_hunk`]: struct.Hunk.html#method.to_str_hunk
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PatchUtf8Error {
+    /// The name of the old file isn't valid utf8
+    OriginalFilename,
+    /// The name of the new file isn't valid utf8
+    ModifiedFilename,
+    /// The function context in the header of a hunk isn't valid utf8
+    FunctionContext {
+        /// The 1-based index of the hunk
+        hunk: usize,
+    },
+    /// A line of a hunk isn't valid utf8
+    Line {
+        /// The 1-based index of the hunk
+        hunk: usize,
+        /// The 1-based index of the line within the hunk
+        line: usize,
+    },
+}
+
+impl PatchUtf8Error {
+    /// Returns the 1-based index of the hunk which isn't valid utf8, if the error isn't in a
+    /// file name
+    pub fn hunk(&self) -> Option<usize> {
+        match self {
+            PatchUtf8Error::OriginalFilename | PatchUtf8Error::ModifiedFilename => None,
+            PatchUtf8Error::FunctionContext { hunk } | PatchUtf8Error::Line { hunk, .. } => {
+                Some(*hunk)
+            }
+        }
+    }
+
+    /// Returns the 1-based index within its hunk of the line which isn't valid utf8, if the
+    /// error is in a line
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            PatchUtf8Error::Line { line, .. } => Some(*line),
+            _ => None,
+        }
+    }
+
+    fn in_hunk(self, hunk: usize) -> Self {
+        match self {
+            PatchUtf8Error::FunctionContext { .. } => PatchUtf8Error::FunctionContext { hunk },
+            PatchUtf8Error::Line { line, .. } => PatchUtf8Error::Line { hunk, line },
+            error => error,
+        }
+    }
+}
+
+impl fmt::Display for PatchUtf8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchUtf8Error::OriginalFilename => write!(f, "invalid utf8 in the old file name"),
+            PatchUtf8Error::ModifiedFilename => write!(f, "invalid utf8 in the new file name"),
+            PatchUtf8Error::FunctionContext { hunk } => {
+                write!(f, "invalid utf8 in the function context of hunk #{}", hunk)
+            }
+            PatchUtf8Error::Line { hunk, line } => {
+                write!(f, "invalid utf8 in line {} of hunk #{}", line, hunk)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PatchUtf8Error {}
+
+impl<'a> Patch<'a, [u8]> {
+    /// Convert a potentially non-utf8 patch into a `Patch<str>`, if all of it is valid utf8
+    ///
+    /// The file names, hunks and status of the patch are kept as they are, so the converted
+    /// patch formats and applies exactly like this one. The contents of the lines are borrowed
+    /// from the same text as this patch's.
+    ///
+    /// ```
+    /// use diffy::{Patch, PatchUtf8Error};
+    ///
+    /// let s = b"--- a\n+++ b\n@@ -1 +1 @@\n-old\n+new\n";
+    /// let patch = Patch::from_bytes(s).unwrap();
+    /// let text = patch.to_str_patch().unwrap();
+    /// assert_eq!(text.to_string().as_bytes(), s);
+    ///
+    /// let s = b"--- a\n+++ b\n@@ -1 +1 @@\n-old\n+n\xffw\n";
+    /// let patch = Patch::from_bytes(s).unwrap();
+    /// let error = patch.to_str_patch().unwrap_err();
+    /// assert_eq!(error, PatchUtf8Error::Line { hunk: 1, line: 2 });
+    /// ```
+    pub fn to_str_patch(&self) -> Result<Patch<'a, str>, PatchUtf8Error> {
+        let original = self
+            .original
+            .as_ref()
+            .map(str_filename)
+            .transpose()
+            .map_err(|_| PatchUtf8Error::OriginalFilename)?;
+        let modified = self
+            .modified
+            .as_ref()
+            .map(str_filename)
+            .transpose()
+            .map_err(|_| PatchUtf8Error::ModifiedFilename)?;
+        let hunks = self
+            .hunks
+            .iter()
+            .enumerate()
+            .map(|(idx, hunk)| hunk.to_str_hunk().map_err(|error| error.in_hunk(idx + 1)))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Patch {
+            original,
+            modified,
+            hunks,
+            status: self.status,
+            mode: self.mode,
+        })
+    }
+}
+
+fn str_filename<'a>(name: &Filename<'a, [u8]>) -> Result<Filename<'a, str>, str::Utf8Error> {
+    let name = match &name.0 {
+        Cow::Borrowed(name) => Cow::Borrowed(str::from_utf8(name)?),
+        Cow::Owned(name) => Cow::Owned(String::from(str::from_utf8(name)?)),
+    };
+    Ok(Filename(name))
+}
+
+impl<'a> From<Patch<'a, str>> for Patch<'a, [u8]> {
+    fn from(patch: Patch<'a, str>) -> Self {
+        let bytes_filename = |name: Filename<'a, str>| match name.0 {
+            Cow::Borrowed(name) => Filename(Cow::Borrowed(name.as_bytes())),
+            Cow::Owned(name) => Filename(Cow::Owned(name.into_bytes())),
+        };
+
+        Patch {
+            original: patch.original.map(bytes_filename),
+            modified: patch.modified.map(bytes_filename),
+            hunks: patch.hunks.into_iter().map(Hunk::from).collect(),
+            status: patch.status,
+            mode: patch.mode,
+        }
+    }
+}
+
+impl<'a> Hunk<'a, [u8]> {
+    /// Convert a potentially non-utf8 hunk into a `Hunk<str>`, if all of it is valid utf8
+    ///
+    /// See [`Patch::to_str_patch`](struct.Patch.html#method.to_str_patch).
+    pub fn to_str_hunk(&self) -> Result<Hunk<'a, str>, PatchUtf8Error> {
+        let function_context = self
+            .function_context
+            .map(str::from_utf8)
+            .transpose()
+            .map_err(|_| PatchUtf8Error::FunctionContext { hunk: 1 })?;
+        let lines = self
+            .lines
+            .iter()
+            .enumerate()
+            .map(|(idx, line)| {
+                line.to_str_line().map_err(|_| PatchUtf8Error::Line {
+                    hunk: 1,
+                    line: idx + 1,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Hunk {
+            old_range: self.old_range,
+            new_range: self.new_range,
+            function_context,
+            lines,
+        })
+    }
+}
+
+impl<'a> From<Hunk<'a, str>> for Hunk<'a, [u8]> {
+    fn from(hunk: Hunk<'a, str>) -> Self {
+        Hunk {
+            old_range: hunk.old_range,
+            new_range: hunk.new_range,
+            function_context: hunk.function_context.map(str::as_bytes),
+            lines: hunk.lines.into_iter().map(Line::from).collect(),
+        }
+    }
+}
+
+impl<'a> Line<'a, [u8]> {
+    /// Convert a potentially non-utf8 line into a `Line<str>` of the same kind, if its
+    /// contents are valid utf8
+    pub fn to_str_line(&self) -> Result<Line<'a, str>, str::Utf8Error> {
+        Ok(Line::new(self.kind(), str::from_utf8(self.value())?))
+    }
+}
+
+impl<'a> From<Line<'a, str>> for Line<'a, [u8]> {
+    fn from(line: Line<'a, str>) -> Self {
+        line.map(str::as_bytes)
+    }
+}
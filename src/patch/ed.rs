@@ -0,0 +1,213 @@
+//! Format a Patch as an ed script
+
+#[cfg(feature = "std")]
+use super::format::IoWrite;
+use super::{format::WriteBytes, Hunk, Patch};
+use alloc::{borrow::ToOwned, vec::Vec};
+use core::fmt::{self, Display, Formatter};
+#[cfg(feature = "std")]
+use std::io;
+
+/// Struct used to output a `Patch` as an ed script, like `diff -e` does
+///
+/// An ed script is a series of `ed` commands which turn the original file into the modified
+/// one, e.g. for feeding to `patch -e` or `ed` itself. The commands are emitted from the end of
+/// the file to the start, so that applying one doesn't change the line numbers of the commands
+/// following it. Context lines and file names aren't part of an ed script, and a missing
+/// newline at the end of the file can't be represented, so one is always added.
+///
+/// ```
+/// use diffy::{create_patch, EdScriptFormatter};
+///
+/// let original = "a\nb\nc\nd\n";
+/// let modified = "a\nB\nc\n";
+/// let patch = create_patch(original, modified);
+///
+/// let expected = "\
+/// 4d
+/// 2c
+/// B
+/// .
+/// ";
+/// let f = EdScriptFormatter::new();
+/// assert_eq!(f.fmt_patch(&patch).to_string(), expected);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct EdScriptFormatter {
+    _private: (),
+}
+
+impl EdScriptFormatter {
+    /// Construct a new formatter
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a `Display` impl which can be used to print a Patch as an ed script
+    pub fn fmt_patch<'a>(&'a self, patch: &'a Patch<'a, str>) -> impl Display + 'a {
+        EdScriptDisplay { patch }
+    }
+
+    /// Write a `Patch` as an ed script into an `io::Write`r
+    #[cfg(feature = "std")]
+    pub fn write_patch_into<T: ToOwned + AsRef<[u8]> + ?Sized, W: io::Write>(
+        &self,
+        patch: &Patch<'_, T>,
+        w: W,
+    ) -> io::Result<()> {
+        EdScriptDisplay { patch }.write_into(IoWrite(w))
+    }
+
+    /// Returns the ed script for a `Patch` as bytes
+    pub fn to_bytes<T: ToOwned + AsRef<[u8]> + ?Sized>(&self, patch: &Patch<'_, T>) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let result = EdScriptDisplay { patch }.write_into(&mut bytes);
+        match result {
+            Ok(()) => {}
+            Err(never) => match never {},
+        }
+        bytes
+    }
+}
+
+struct EdScriptDisplay<'a, 'p, T: ToOwned + ?Sized> {
+    patch: &'a Patch<'p, T>,
+}
+
+impl<T: ToOwned + AsRef<[u8]> + ?Sized> EdScriptDisplay<'_, '_, T> {
+    fn write_into<W: WriteBytes>(&self, mut w: W) -> Result<(), W::Error> {
+        let changes: Vec<Hunk<'_, T>> = self
+            .patch
+            .hunks()
+            .iter()
+            .flat_map(Hunk::strip_context)
+            .collect();
+
+        for change in changes.iter().rev() {
+            let old = change.old_range();
+            let inserted: Vec<&[u8]> = change
+                .lines()
+                .iter()
+                .filter(|line| line.is_insert())
+                .map(|line| line.value().as_ref())
+                .collect();
+
+            let command = match (old.is_empty(), inserted.is_empty()) {
+                (true, _) => 'a',
+                (false, true) => 'd',
+                (false, false) => 'c',
+            };
+            match old.len() {
+                0 | 1 => write!(w, "{}", old.start())?,
+                len => write!(w, "{},{}", old.start(), old.start() + len - 1)?,
+            }
+            writeln!(w, "{}", command)?;
+            if command != 'd' {
+                write_text(&inserted, &mut w)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Writes the text of an append or change command along with the `.` ending it. As a line
+// consisting of a single `.` would end the text early it's written as `..`, the text is ended
+// and the extra `.` is removed with a substitution, as done by GNU diff.
+fn write_text<W: WriteBytes>(lines: &[&[u8]], mut w: W) -> Result<(), W::Error> {
+    let mut appending = true;
+    for &line in lines {
+        if !appending {
+            writeln!(w, "a")?;
+            appending = true;
+        }
+
+        let text = line.strip_suffix(b"\n").unwrap_or(line);
+        if text == b"." {
+            writeln!(w, "..")?;
+            writeln!(w, ".")?;
+            writeln!(w, "s/.//")?;
+            appending = false;
+        } else {
+            w.write_all(text)?;
+            writeln!(w)?;
+        }
+    }
+    if appending {
+        writeln!(w, ".")?;
+    }
+
+    Ok(())
+}
+
+impl Display for EdScriptDisplay<'_, '_, str> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.write_into(FmtWrite(f))
+    }
+}
+
+// Writes the utf8 output for a `Patch<str>` into a `Formatter`
+struct FmtWrite<'a, 'b>(&'a mut Formatter<'b>);
+
+impl WriteBytes for FmtWrite<'_, '_> {
+    type Error = fmt::Error;
+
+    fn write_all(&mut self, buf: &[u8]) -> fmt::Result {
+        let s = core::str::from_utf8(buf).map_err(|_| fmt::Error)?;
+        self.0.write_str(s)
+    }
+
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> fmt::Result {
+        self.0.write_fmt(args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EdScriptFormatter;
+    use crate::{create_patch, create_patch_bytes};
+    use alloc::string::ToString;
+
+    #[test]
+    fn matches_diff_e() {
+        let original = "a\nb\nc\nd\ne\nf\ng\nh\n";
+        let modified = "a\nB\nc\nd\nx\ny\ne\ng\n.\nh\n";
+        let patch = create_patch(original, modified);
+
+        // The output of `diff -e` from GNU diffutils 3.8
+        let expected = "\
+7a
+..
+.
+s/.//
+6d
+4a
+x
+y
+.
+2c
+B
+.
+";
+        let f = EdScriptFormatter::new();
+        assert_eq!(f.fmt_patch(&patch).to_string(), expected);
+
+        let bytes = create_patch_bytes(original.as_bytes(), modified.as_bytes());
+        assert_eq!(f.to_bytes(&bytes), expected.as_bytes());
+
+        // Text continues to be appended after a `.` line, and a missing newline is added
+        let patch = create_patch("a\nb\nc\n", "a\n.\nz\n");
+        let expected = "\
+2,3c
+..
+.
+s/.//
+a
+z
+.
+";
+        assert_eq!(f.fmt_patch(&patch).to_string(), expected);
+        let patch = create_patch("a\n", "a\nb");
+        assert_eq!(f.fmt_patch(&patch).to_string(), "1a\nb\n.\n");
+    }
+}
@@ -1,21 +1,42 @@
-use super::{Hunk, Line, Patch, NO_NEWLINE_AT_EOF};
-use nu_ansi_term::{Color, Style};
-use std::{
-    fmt::{Display, Formatter, Result},
-    io,
+use super::{
+    styled::{self, SplitRow, StyledRow},
+    theme::{ColorChoice, StyleConfig, Theme},
+    FileStatus, Hunk, Line, LineKind, Patch, DEV_NULL, NO_NEWLINE_AT_EOF,
 };
+use crate::{
+    diff::{diff_text_ops, DiffOpKind},
+    utils::{is_indentation_only, split_line_ending, strip_indentation},
+};
+use alloc::{
+    borrow::ToOwned,
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{
+    convert::Infallible,
+    fmt::{self, Display, Formatter, Result},
+    ops,
+};
+use nu_ansi_term::Style;
+#[cfg(feature = "std")]
+use std::io;
 
 /// Struct used to adjust the formatting of a `Patch`
 #[derive(Debug)]
 pub struct PatchFormatter {
     with_color: bool,
-
-    context: Style,
-    delete: Style,
-    insert: Style,
-    hunk_header: Style,
-    patch_header: Style,
-    function_context: Style,
+    mark_indentation: bool,
+    hunk_separator: String,
+    wrap_width: Option<usize>,
+    collapse_inner_context: Option<usize>,
+    intraline_highlight: bool,
+    split_indentation: bool,
+    theme: Theme,
+    style: StyleConfig,
+    line_renderer: Option<LineRenderer>,
 }
 
 impl PatchFormatter {
@@ -23,58 +44,849 @@ impl PatchFormatter {
     pub fn new() -> Self {
         Self {
             with_color: false,
-
-            context: Style::new(),
-            delete: Color::Red.normal(),
-            insert: Color::Green.normal(),
-            hunk_header: Color::Cyan.normal(),
-            patch_header: Style::new().bold(),
-            function_context: Style::new(),
+            mark_indentation: false,
+            hunk_separator: String::new(),
+            wrap_width: None,
+            collapse_inner_context: None,
+            intraline_highlight: false,
+            split_indentation: false,
+            theme: Theme::default(),
+            style: StyleConfig::new(),
+            line_renderer: None,
         }
     }
 
     /// Enable formatting a patch with color
+    ///
+    /// This is the same as [`color_choice`] with [`ColorChoice::Always`].
+    ///
+    /// [`color_choice`]: #method.color_choice
+    /// [`ColorChoice::Always`]: enum.ColorChoice.html#variant.Always
     pub fn with_color(mut self) -> Self {
         self.with_color = true;
         self
     }
 
+    /// Choose whether to format a patch with color
+    ///
+    /// [`ColorChoice::Auto`] looks at the environment once, when this is called, and only
+    /// enables color if the `NO_COLOR` environment variable isn't set and stdout is a terminal.
+    /// The default is [`ColorChoice::Never`].
+    ///
+    /// ```
+    /// use diffy::{create_patch, ColorChoice, PatchFormatter};
+    ///
+    /// let patch = create_patch("a\n", "b\n");
+    /// let f = PatchFormatter::new().color_choice(ColorChoice::Never);
+    /// assert_eq!(f.fmt_patch(&patch).to_string(), patch.to_string());
+    /// ```
+    ///
+    /// [`ColorChoice::Auto`]: enum.ColorChoice.html#variant.Auto
+    /// [`ColorChoice::Never`]: enum.ColorChoice.html#variant.Never
+    pub fn color_choice(mut self, choice: ColorChoice) -> Self {
+        self.with_color = choice.use_color();
+        self
+    }
+
+    /// Set the styles used when formatting with color
+    ///
+    /// The theme applies to every styled element of the output, including the patch and hunk
+    /// headers and the marks added by [`mark_indentation_changes`] and
+    /// [`with_intraline_highlight`]. The default is [`Theme::dark`].
+    ///
+    /// [`mark_indentation_changes`]: #method.mark_indentation_changes
+    /// [`with_intraline_highlight`]: #method.with_intraline_highlight
+    /// [`Theme::dark`]: struct.Theme.html#method.dark
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// Set the text attributes, like bold or dimmed, of each kind of line when formatting with
+    /// color
+    ///
+    /// The attributes are added on top of the colors of the [theme]. The default adds none,
+    /// see [`StyleConfig::new`].
+    ///
+    /// ```
+    /// use diffy::{create_patch, PatchFormatter, StyleConfig};
+    ///
+    /// let patch = create_patch("a\nb\n", "a\nB\n");
+    /// let f = PatchFormatter::new()
+    ///     .with_color()
+    ///     .style(StyleConfig::emphasize_changes());
+    /// assert!(f.fmt_patch(&patch).to_string().contains("\u{1b}[2m a\n"));
+    /// ```
+    ///
+    /// [theme]: #method.with_theme
+    /// [`StyleConfig::new`]: struct.StyleConfig.html#method.new
+    pub fn style(mut self, style: StyleConfig) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Mark changed lines which only differ from the line they're paired with in their
+    /// leading spaces and tabs
+    ///
+    /// Within a run of deleted lines followed by inserted lines, the n-th deleted line is
+    /// paired with the n-th inserted line. When formatting with color such pairs are rendered
+    /// in a separate color, and in [`to_styled_rows`] and [`to_split_rows`] they're styled
+    /// with [`SpanStyle::Indentation`]. The text of the patch itself is unaffected.
+    ///
+    /// ```
+    /// use diffy::{create_patch, PatchFormatter, SpanStyle};
+    ///
+    /// let patch = create_patch("if x {\nfoo();\n}\n", "if x {\n    foo();\n}\n");
+    /// let rows = PatchFormatter::new()
+    ///     .mark_indentation_changes()
+    ///     .to_styled_rows(&patch);
+    ///
+    /// assert_eq!(rows[4].spans[1].text, "foo();");
+    /// assert_eq!(rows[4].spans[1].style, SpanStyle::Indentation);
+    /// ```
+    ///
+    /// [`to_styled_rows`]: #method.to_styled_rows
+    /// [`to_split_rows`]: #method.to_split_rows
+    /// [`SpanStyle::Indentation`]: enum.SpanStyle.html#variant.Indentation
+    pub fn mark_indentation_changes(mut self) -> Self {
+        self.mark_indentation = true;
+        self
+    }
+
+    /// Highlight the characters which changed between a deleted line and the inserted line it's
+    /// paired with
+    ///
+    /// Within a run of deleted lines followed by inserted lines, the n-th deleted line is
+    /// paired with the n-th inserted line and the character-level differences between the two
+    /// are rendered in reverse video, while the rest of each line keeps its usual color. Pairs
+    /// which have nothing in common, as well as lines without a pair, are rendered as usual.
+    /// This only has an effect when formatting with color.
+    ///
+    /// ```
+    /// use diffy::{create_patch, PatchFormatter};
+    ///
+    /// let patch = create_patch("let x = 1;\n", "let x = 2;\n");
+    /// let f = PatchFormatter::new()
+    ///     .with_color()
+    ///     .with_intraline_highlight(true);
+    /// let output = f.fmt_patch(&patch).to_string();
+    /// assert!(output.contains("\u{1b}[7;31m1\u{1b}[0m"));
+    /// assert!(output.contains("\u{1b}[7;32m2\u{1b}[0m"));
+    /// ```
+    pub fn with_intraline_highlight(mut self, enable: bool) -> Self {
+        self.intraline_highlight = enable;
+        self
+    }
+
+    /// Highlight changes to the leading spaces and tabs of paired lines separately from changes
+    /// to the rest of the line
+    ///
+    /// This refines [`with_intraline_highlight`], which it implies: each line of a pair is split
+    /// into its leading whitespace and its content, and the two parts are compared on their
+    /// own. A changed leading run is highlighted as a whole in the indentation color, while the
+    /// content is compared character by character and its changes are highlighted as usual, so
+    /// a line which was reindented and edited shows both. Pairs whose content has nothing in
+    /// common are rendered as usual. The line diff itself is unaffected, and this only has an
+    /// effect when formatting with color.
+    ///
+    /// ```
+    /// use diffy::{create_patch, PatchFormatter};
+    ///
+    /// let patch = create_patch("foo(1);\n", "    foo(2);\n");
+    /// let f = PatchFormatter::new()
+    ///     .with_color()
+    ///     .with_indentation_split(true);
+    /// let output = f.fmt_patch(&patch).to_string();
+    /// assert!(output.contains("\u{1b}[7;33m    \u{1b}[0m"));
+    /// assert!(output.contains("\u{1b}[7;32m2\u{1b}[0m"));
+    /// ```
+    ///
+    /// [`with_intraline_highlight`]: #method.with_intraline_highlight
+    pub fn with_indentation_split(mut self, enable: bool) -> Self {
+        self.split_indentation = enable;
+        self
+    }
+
+    /// Render the text of every line of a hunk with `renderer`, e.g. to add syntax highlighting
+    ///
+    /// The renderer is called with the kind of each line and its text, without its prefix or
+    /// line ending, and writes the text as it should appear into the given `fmt::Write`. The
+    /// formatter still writes the prefixes, headers, line endings and any colors of its own
+    /// around the rendered text, so a renderer adding colors can use the kind of line to avoid
+    /// clashing with them. Lines which aren't valid utf8 are written as they are.
+    ///
+    /// The renderer is called once for every piece of a line the formatter writes on its own,
+    /// so it may see only part of a line: a line wrapped by [`wrap_width`] is rendered one row
+    /// at a time, and with [`with_intraline_highlight`] the changed and unchanged runs of a
+    /// line are rendered separately, so the highlight's colors can be written between them.
+    /// Joining the pieces of a line gives back its whole text.
+    ///
+    /// ```
+    /// use diffy::{create_patch, LineKind, PatchFormatter};
+    ///
+    /// let patch = create_patch("a\nb\n", "a\nc\n");
+    /// let f = PatchFormatter::new().with_line_renderer(|kind, text, w| match kind {
+    ///     LineKind::Context => write!(w, "{}", text),
+    ///     _ => write!(w, "<{}>", text),
+    /// });
+    ///
+    /// let expected = "\
+    /// --- original
+    /// +++ modified
+    /// @@ -1,2 +1,2 @@
+    ///  a
+    /// -<b>
+    /// +<c>
+    /// ";
+    /// assert_eq!(f.fmt_patch(&patch).to_string(), expected);
+    /// ```
+    ///
+    /// [`wrap_width`]: #method.wrap_width
+    /// [`with_intraline_highlight`]: #method.with_intraline_highlight
+    pub fn with_line_renderer<F>(mut self, renderer: F) -> Self
+    where
+        F: Fn(LineKind, &str, &mut dyn fmt::Write) -> Result + Send + Sync + 'static,
+    {
+        self.line_renderer = Some(LineRenderer(Box::new(renderer)));
+        self
+    }
+
+    // Writes the text of a line, passing it through the line renderer if there is one
+    fn fmt_content(&self, kind: LineKind, text: &str, f: &mut Formatter<'_>) -> Result {
+        match &self.line_renderer {
+            Some(renderer) => (renderer.0)(kind, text, f),
+            None => f.write_str(text),
+        }
+    }
+
+    fn write_content<W: WriteBytes>(
+        &self,
+        kind: LineKind,
+        text: &[u8],
+        mut w: W,
+    ) -> core::result::Result<(), W::Error> {
+        match (&self.line_renderer, core::str::from_utf8(text)) {
+            (Some(renderer), Ok(text)) => write!(
+                w,
+                "{}",
+                RenderedContent {
+                    renderer,
+                    kind,
+                    text
+                }
+            ),
+            _ => w.write_all(text),
+        }
+    }
+
+    /// Emit `separator` between two hunks of a patch which have unchanged lines between them
+    ///
+    /// The separator is written verbatim, so it should include its own line ending, and isn't
+    /// written before the first or after the last hunk. Hunks which are directly adjacent,
+    /// e.g. when hunk merging was disabled with [`DiffOptions::set_merge_hunks`], aren't
+    /// separated. The default is an empty separator, which keeps the output parseable.
+    ///
+    /// ```
+    /// use diffy::{create_patch, PatchFormatter};
+    ///
+    /// let original = "a\nb\nc\nd\ne\nf\ng\nh\ni\n";
+    /// let modified = "A\nb\nc\nd\ne\nf\ng\nh\nI\n";
+    /// let patch = create_patch(original, modified);
+    ///
+    /// let expected = "\
+    /// --- original
+    /// +++ modified
+    /// @@ -1,4 +1,4 @@
+    /// -a
+    /// +A
+    ///  b
+    ///  c
+    ///  d
+    /// ...
+    /// @@ -6,4 +6,4 @@
+    ///  f
+    ///  g
+    ///  h
+    /// -i
+    /// +I
+    /// ";
+    ///
+    /// let f = PatchFormatter::new().hunk_separator("...\n");
+    /// assert_eq!(f.fmt_patch(&patch).to_string(), expected);
+    /// ```
+    ///
+    /// [`DiffOptions::set_merge_hunks`]: struct.DiffOptions.html#method.set_merge_hunks
+    pub fn hunk_separator(mut self, separator: &str) -> Self {
+        self.hunk_separator = separator.to_string();
+        self
+    }
+
+    /// Wrap lines longer than `width` characters onto multiple output lines
+    ///
+    /// Each wrapped line carries the `+`, `-` or ` ` prefix of the line it was split from, so
+    /// long changed lines remain readable in a terminal without hiding any of their content.
+    /// The width doesn't include the prefix or the line ending and lines are only split
+    /// between characters. A width of `0` disables wrapping, which is the default.
+    ///
+    /// Wrapping only affects how a patch is displayed, the wrapped output can't be parsed back
+    /// into the same patch.
+    ///
+    /// ```
+    /// use diffy::{create_patch, PatchFormatter};
+    ///
+    /// let patch = create_patch("short\n", "a much longer line\n");
+    ///
+    /// let expected = "\
+    /// --- original
+    /// +++ modified
+    /// @@ -1 +1 @@
+    /// -short
+    /// +a much
+    /// + longe
+    /// +r line
+    /// ";
+    ///
+    /// let f = PatchFormatter::new().wrap_width(6);
+    /// assert_eq!(f.fmt_patch(&patch).to_string(), expected);
+    /// ```
+    pub fn wrap_width(mut self, width: usize) -> Self {
+        self.wrap_width = if width == 0 { None } else { Some(width) };
+        self
+    }
+
+    /// Collapse the context lines between two changes of the same hunk into a `…` line,
+    /// keeping `keep` lines next to each change
+    ///
+    /// Hunks can end up with long runs of unchanged lines between their changes when a large
+    /// context merges changes which are far apart. Only those runs are collapsed, the context
+    /// before the first and after the last change of a hunk is always shown, and so are runs
+    /// which would only hide a single line. Unlike [`hunk_separator`], which separates hunks,
+    /// this collapses lines inside a hunk.
+    ///
+    /// Collapsing only affects how a patch is displayed, the collapsed output can't be parsed
+    /// back into the same patch.
+    ///
+    /// ```
+    /// use diffy::{DiffOptions, PatchFormatter};
+    ///
+    /// let original = "a\nb\nc\nd\ne\nf\ng\nh\n";
+    /// let modified = "A\nb\nc\nd\ne\nf\ng\nH\n";
+    /// let patch = DiffOptions::new().set_context_len(10).create_patch(original, modified);
+    ///
+    /// let expected = "\
+    /// --- original
+    /// +++ modified
+    /// @@ -1,8 +1,8 @@
+    /// -a
+    /// +A
+    ///  b
+    /// …
+    ///  g
+    /// -h
+    /// +H
+    /// ";
+    ///
+    /// let f = PatchFormatter::new().collapse_inner_context(1);
+    /// assert_eq!(f.fmt_patch(&patch).to_string(), expected);
+    /// ```
+    ///
+    /// [`hunk_separator`]: #method.hunk_separator
+    pub fn collapse_inner_context(mut self, keep: usize) -> Self {
+        self.collapse_inner_context = Some(keep);
+        self
+    }
+
+    // Splits a line, without its line ending, into the ranges to output on separate lines
+    fn wrap_ranges<'a>(&self, line: &'a [u8]) -> WrapRanges<'a> {
+        WrapRanges {
+            line,
+            width: self.wrap_width.unwrap_or(usize::MAX),
+            start: Some(0),
+        }
+    }
+
+    // Returns the separator to write before `hunk`, given the hunk preceding it
+    fn separator_between<T: ?Sized>(&self, prev: &Hunk<'_, T>, hunk: &Hunk<'_, T>) -> &str {
+        // Empty ranges start after the line they follow, so compare them as indices instead
+        if prev.old_range.to_std_range().end < hunk.old_range.to_std_range().start {
+            &self.hunk_separator
+        } else {
+            ""
+        }
+    }
+
     /// Returns a `Display` impl which can be used to print a Patch
     pub fn fmt_patch<'a>(&'a self, patch: &'a Patch<'a, str>) -> impl Display + 'a {
         PatchDisplay { f: self, patch }
     }
 
+    /// Render a `Patch` into rows of [`StyledSpan`]s in the unified layout
+    ///
+    /// Rather than ANSI escape codes each span carries a [`SpanStyle`] which can be mapped onto
+    /// a renderer's own colors, e.g. when displaying a patch in a TUI. Lines are split into
+    /// spans highlighting the parts of a changed line which differ from the line it replaced.
+    /// Line endings and "No newline at end of file" markers are not included in the output.
+    ///
+    /// ```
+    /// use diffy::{create_patch, PatchFormatter, SpanStyle};
+    ///
+    /// let patch = create_patch("foo bar\n", "foo baz\n");
+    /// let rows = PatchFormatter::new().to_styled_rows(&patch);
+    ///
+    /// let deleted = &rows[3].spans;
+    /// assert_eq!(deleted.last().unwrap().text, "r");
+    /// assert_eq!(deleted.last().unwrap().style, SpanStyle::InlineDelete);
+    /// ```
+    ///
+    /// [`StyledSpan`]: struct.StyledSpan.html
+    /// [`SpanStyle`]: enum.SpanStyle.html
+    pub fn to_styled_rows<'a>(&self, patch: &'a Patch<'a, str>) -> Vec<StyledRow<'a>> {
+        styled::unified_rows(patch, self.mark_indentation)
+    }
+
+    /// Render a `Patch` into rows for a side-by-side layout
+    ///
+    /// Deleted lines are paired with the inserted lines that replaced them, with the old file
+    /// on the left and the new file on the right. See [`to_styled_rows`] for details on styling.
+    ///
+    /// [`to_styled_rows`]: #method.to_styled_rows
+    pub fn to_split_rows<'a>(&self, patch: &'a Patch<'a, str>) -> Vec<SplitRow<'a>> {
+        styled::split_rows(patch, self.mark_indentation)
+    }
+
+    /// Write a `Patch` into an `io::Write`r
+    #[cfg(feature = "std")]
     pub fn write_patch_into<T: ToOwned + AsRef<[u8]> + ?Sized, W: io::Write>(
         &self,
         patch: &Patch<'_, T>,
         w: W,
     ) -> io::Result<()> {
-        PatchDisplay { f: self, patch }.write_into(w)
+        PatchDisplay { f: self, patch }.write_into(IoWrite(w))
+    }
+
+    /// Render a `Patch` into a `Vec<u8>`
+    ///
+    /// This is the byte-level counterpart of [`fmt_patch`], which only accepts patches between
+    /// `str`s and produces a `String`. Patches between potentially non-utf8 texts, like the ones
+    /// from [`create_patch_bytes`], are written out with their lines passed through unchanged,
+    /// and patches between `str`s produce the same bytes as [`fmt_patch`]. Use
+    /// [`write_patch_into`] instead to write directly into an `io::Write`.
+    ///
+    /// ```
+    /// use diffy::{create_patch_bytes, PatchFormatter};
+    ///
+    /// let patch = create_patch_bytes(b"caf\xe9\n", b"cafe\n");
+    /// let bytes = PatchFormatter::new().patch_bytes(&patch);
+    /// assert!(bytes.ends_with(b"-caf\xe9\n+cafe\n"));
+    /// ```
+    ///
+    /// [`fmt_patch`]: #method.fmt_patch
+    /// [`write_patch_into`]: #method.write_patch_into
+    /// [`create_patch_bytes`]: fn.create_patch_bytes.html
+    pub fn patch_bytes<T: ToOwned + AsRef<[u8]> + ?Sized>(&self, patch: &Patch<'_, T>) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write_patch_into_vec(patch, &mut bytes);
+        bytes
+    }
+
+    pub(super) fn write_patch_into_vec<T: ToOwned + AsRef<[u8]> + ?Sized>(
+        &self,
+        patch: &Patch<'_, T>,
+        w: &mut Vec<u8>,
+    ) {
+        let result = PatchDisplay { f: self, patch }.write_into(w);
+        match result {
+            Ok(()) => {}
+            Err(never) => match never {},
+        }
+    }
+
+    pub(super) fn rendered_size<T: ToOwned + AsRef<[u8]> + ?Sized>(
+        &self,
+        patch: &Patch<'_, T>,
+    ) -> usize {
+        let mut counter = ByteCounter(0);
+        let result = PatchDisplay { f: self, patch }.write_into(&mut counter);
+        match result {
+            Ok(()) => counter.0,
+            Err(never) => match never {},
+        }
+    }
+
+    // Writes a patch framed the way `git apply` expects, i.e. preceded by a `diff --git` line
+    // naming both files and, if the file was renamed, `rename from` and `rename to` lines. The
+    // names are expected to carry git's `a/` and `b/` prefixes. A binary file is rendered as a
+    // `Binary files ... differ` line in place of its file headers and hunks.
+    #[cfg(feature = "std")]
+    pub(crate) fn write_git_patch_into_vec<T: ToOwned + AsRef<[u8]> + ?Sized>(
+        &self,
+        patch: &Patch<'_, T>,
+        binary: bool,
+        w: &mut Vec<u8>,
+    ) {
+        use super::{Filename, PrefixPolicy, Side};
+
+        // The path of a file without its `a/` or `b/` prefix
+        fn path<'a, T: ToOwned + AsRef<[u8]> + ?Sized>(
+            name: &'a Filename<'_, T>,
+            side: Side,
+        ) -> Filename<'a, [u8]> {
+            Filename(
+                PrefixPolicy::Git
+                    .strip(name.0.as_ref().as_ref(), side)
+                    .into(),
+            )
+        }
+
+        let display = PatchDisplay { f: self, patch };
+        let (original, modified) = match (&patch.original, &patch.modified) {
+            (Some(original), Some(modified)) => (original, modified),
+            _ => return self.write_patch_into_vec(patch, w),
+        };
+        let (from, to) = (path(original, Side::Old), path(modified, Side::New));
+
+        let result = (|| {
+            if self.with_color {
+                write!(w, "{}", self.theme.patch_header.prefix())?;
+            }
+            write!(w, "diff --git ")?;
+            original.write_into(&mut *w)?;
+            write!(w, " ")?;
+            modified.write_into(&mut *w)?;
+            writeln!(w)?;
+            if patch.status == FileStatus::Modified && from != to {
+                write!(w, "rename from ")?;
+                from.write_into(&mut *w)?;
+                write!(w, "\nrename to ")?;
+                to.write_into(&mut *w)?;
+                writeln!(w)?;
+            }
+            if binary {
+                if let Some(mode) = display.mode_header() {
+                    writeln!(w, "{}", mode)?;
+                }
+            }
+            if self.with_color {
+                write!(w, "{}", self.theme.patch_header.suffix())?;
+            }
+            if !binary {
+                return display.write_into(&mut *w);
+            }
+
+            write!(w, "Binary files ")?;
+            match patch.status {
+                FileStatus::Added => write!(w, "{}", DEV_NULL)?,
+                _ => original.write_into(&mut *w)?,
+            }
+            write!(w, " and ")?;
+            match patch.status {
+                FileStatus::Removed => write!(w, "{}", DEV_NULL)?,
+                _ => modified.write_into(&mut *w)?,
+            }
+            writeln!(w, " differ")
+        })();
+        match result {
+            Ok(()) => {}
+            Err(never) => match never {},
+        }
     }
 
-    fn fmt_hunk<'a>(&'a self, hunk: &'a Hunk<'a, str>) -> impl Display + 'a {
+    /// Returns a `Display` impl which can be used to print a single Hunk, including its
+    /// `@@ ... @@` header
+    pub fn fmt_hunk<'a>(&'a self, hunk: &'a Hunk<'a, str>) -> impl Display + 'a {
         HunkDisplay { f: self, hunk }
     }
 
-    fn write_hunk_into<T: AsRef<[u8]> + ?Sized, W: io::Write>(
+    /// Write a single `Hunk`, including its `@@ ... @@` header, into an `io::Write`r
+    #[cfg(feature = "std")]
+    pub fn write_hunk_into<T: AsRef<[u8]> + ?Sized, W: io::Write>(
         &self,
         hunk: &Hunk<'_, T>,
         w: W,
     ) -> io::Result<()> {
+        self.write_hunk_bytes(hunk, IoWrite(w))
+    }
+
+    pub(super) fn write_hunk_into_vec<T: AsRef<[u8]> + ?Sized>(
+        &self,
+        hunk: &Hunk<'_, T>,
+        w: &mut Vec<u8>,
+    ) {
+        let result = self.write_hunk_bytes(hunk, w);
+        match result {
+            Ok(()) => {}
+            Err(never) => match never {},
+        }
+    }
+
+    fn write_hunk_bytes<T: AsRef<[u8]> + ?Sized, W: WriteBytes>(
+        &self,
+        hunk: &Hunk<'_, T>,
+        w: W,
+    ) -> core::result::Result<(), W::Error> {
         HunkDisplay { f: self, hunk }.write_into(w)
     }
 
-    fn fmt_line<'a>(&'a self, line: &'a Line<'a, str>) -> impl Display + 'a {
-        LineDisplay { f: self, line }
+    fn fmt_line<'a>(&'a self, line: &'a Line<'a, str>, mark: &'a LineMark) -> impl Display + 'a {
+        LineDisplay {
+            f: self,
+            line,
+            mark,
+        }
     }
 
-    fn write_line_into<T: AsRef<[u8]> + ?Sized, W: io::Write>(
+    fn write_line_into<T: AsRef<[u8]> + ?Sized, W: WriteBytes>(
         &self,
         line: &Line<'_, T>,
+        mark: &LineMark,
         w: W,
-    ) -> io::Result<()> {
-        LineDisplay { f: self, line }.write_into(w)
+    ) -> core::result::Result<(), W::Error> {
+        LineDisplay {
+            f: self,
+            line,
+            mark,
+        }
+        .write_into(w)
+    }
+
+    // Returns the line standing in for a run of collapsed context lines
+    fn collapsed_marker(&self) -> String {
+        let style = if self.with_color {
+            self.line_style(LineKind::Context, false)
+        } else {
+            Style::new()
+        };
+        format!("{}…{}\n", style.prefix(), style.suffix())
+    }
+
+    fn line_style(&self, kind: LineKind, indentation_only: bool) -> Style {
+        let style = match kind {
+            _ if indentation_only => self.theme.indentation,
+            LineKind::Context => self.theme.context,
+            LineKind::Delete => self.theme.delete,
+            LineKind::Insert => self.theme.insert,
+        };
+        self.style.line_attributes(kind).apply(style)
+    }
+
+    fn highlight_style(&self, kind: LineKind, indentation: bool) -> Style {
+        let style = match kind {
+            _ if indentation => self.theme.inline_indentation,
+            LineKind::Delete => self.theme.inline_delete,
+            LineKind::Insert => self.theme.inline_insert,
+            LineKind::Context => self.theme.context,
+        };
+        self.style.line_attributes(kind).apply(style)
+    }
+
+    fn hunk_header_style(&self) -> Style {
+        self.style.hunk_header_style(self.theme.hunk_header)
+    }
+
+    // Marks the lines of a hunk which should be marked as indentation-only changes, have parts
+    // of them highlighted or be collapsed
+    fn line_marks<T: AsRef<[u8]> + ?Sized>(&self, hunk: &Hunk<'_, T>) -> Vec<LineMark> {
+        let lines = hunk.lines();
+        let mut marks = vec![LineMark::default(); lines.len()];
+        self.mark_collapsed_context(lines, &mut marks);
+        let highlight = self.intraline_highlight || self.split_indentation;
+        if !(self.with_color && (self.mark_indentation || highlight)) {
+            return marks;
+        }
+
+        let mut idx = 0;
+        while idx < lines.len() {
+            let deletes = idx;
+            while lines.get(idx).map_or(false, Line::is_delete) {
+                idx += 1;
+            }
+            let inserts = idx;
+            while lines.get(idx).map_or(false, Line::is_insert) {
+                idx += 1;
+            }
+
+            for (d, i) in (deletes..inserts).zip(inserts..idx) {
+                let (old, new) = (lines[d].value().as_ref(), lines[i].value().as_ref());
+                if self.mark_indentation && is_indentation_only(old, new) {
+                    marks[d].indentation_only = true;
+                    marks[i].indentation_only = true;
+                } else if self.split_indentation {
+                    if let Some([(old_indent, old), (new_indent, new)]) =
+                        split_intraline_changes(old, new)
+                    {
+                        marks[d].changed_indentation = old_indent;
+                        marks[d].highlights = old;
+                        marks[i].changed_indentation = new_indent;
+                        marks[i].highlights = new;
+                    }
+                } else if self.intraline_highlight {
+                    if let Some((old, new)) = intraline_changes(old, new) {
+                        marks[d].highlights = old;
+                        marks[i].highlights = new;
+                    }
+                }
+            }
+
+            if idx == deletes {
+                idx += 1;
+            }
+        }
+
+        marks
     }
+
+    fn mark_collapsed_context<T: ?Sized>(&self, lines: &[Line<'_, T>], marks: &mut [LineMark]) {
+        let keep = match self.collapse_inner_context {
+            Some(keep) => keep,
+            None => return,
+        };
+
+        let mut idx = 0;
+        while idx < lines.len() {
+            let start = idx;
+            while lines.get(idx).map_or(false, Line::is_context) {
+                idx += 1;
+            }
+
+            // Only runs with changes on both sides are inside the hunk
+            let inner = start > 0 && idx < lines.len();
+            if inner && idx - start > keep.saturating_mul(2).saturating_add(1) {
+                for mark in &mut marks[start + keep..idx - keep] {
+                    mark.collapsed = true;
+                }
+            }
+
+            if idx == start {
+                idx += 1;
+            }
+        }
+    }
+}
+
+// A user provided function rendering the text of a line, see
+// `PatchFormatter::with_line_renderer`
+struct LineRenderer(Box<RenderFn>);
+
+type RenderFn = dyn Fn(LineKind, &str, &mut dyn fmt::Write) -> Result + Send + Sync;
+
+impl fmt::Debug for LineRenderer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        f.write_str("LineRenderer")
+    }
+}
+
+// Adapts a `LineRenderer` to `Display` so it can be written into a `WriteBytes`
+struct RenderedContent<'a> {
+    renderer: &'a LineRenderer,
+    kind: LineKind,
+    text: &'a str,
+}
+
+impl Display for RenderedContent<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        (self.renderer.0)(self.kind, self.text, f)
+    }
+}
+
+// How a single line of a hunk is marked when formatting it
+#[derive(Clone, Default)]
+struct LineMark {
+    // Whether the line is replaced by a `…` line, along with the lines around it
+    collapsed: bool,
+    indentation_only: bool,
+    // The length of the leading whitespace of the line, when it's highlighted as a changed
+    // indentation. It's then also the first of the `highlights`.
+    changed_indentation: usize,
+    // The byte ranges of the line which differ from the line it's paired with
+    highlights: Vec<ops::Range<usize>>,
+}
+
+// Computes the byte ranges in which a deleted line and the inserted line paired with it differ.
+// Lines which aren't utf8, or don't have anything in common, aren't highlighted.
+#[allow(clippy::type_complexity)]
+fn intraline_changes(
+    old: &[u8],
+    new: &[u8],
+) -> Option<(Vec<ops::Range<usize>>, Vec<ops::Range<usize>>)> {
+    let old = core::str::from_utf8(split_line_ending(old).0).ok()?;
+    let new = core::str::from_utf8(split_line_ending(new).0).ok()?;
+    text_changes(old, new)
+}
+
+// Like `intraline_changes`, but compares the leading whitespace of the lines as a single token
+// and their content on its own. For each line, returns the length of its leading whitespace if
+// it changed, along with the highlighted ranges, which start with that whitespace.
+#[allow(clippy::type_complexity)]
+fn split_intraline_changes(old: &[u8], new: &[u8]) -> Option<[(usize, Vec<ops::Range<usize>>); 2]> {
+    let old = core::str::from_utf8(split_line_ending(old).0).ok()?;
+    let new = core::str::from_utf8(split_line_ending(new).0).ok()?;
+    let (old_content, new_content) = (strip_indentation(old), strip_indentation(new));
+    let (old_indent, new_indent) = (old.len() - old_content.len(), new.len() - new_content.len());
+
+    let (old_changes, new_changes) = if old_content == new_content {
+        (Vec::new(), Vec::new())
+    } else {
+        text_changes(old_content, new_content)?
+    };
+
+    let changed = old[..old_indent] != new[..new_indent];
+    let side = |indent: usize, changes: Vec<ops::Range<usize>>| {
+        let changed_indent = if changed { indent } else { 0 };
+        let leading = Some(0..changed_indent).filter(|range| !range.is_empty());
+        let content = changes
+            .into_iter()
+            .map(|range| range.start + indent..range.end + indent);
+        (changed_indent, leading.into_iter().chain(content).collect())
+    };
+    Some([side(old_indent, old_changes), side(new_indent, new_changes)])
+}
+
+// Computes the byte ranges in which two texts differ, or `None` if they have nothing in common
+#[allow(clippy::type_complexity)]
+fn text_changes(old: &str, new: &str) -> Option<(Vec<ops::Range<usize>>, Vec<ops::Range<usize>>)> {
+    let ops = diff_text_ops(old, new);
+    if !ops
+        .iter()
+        .any(|op| op.kind == DiffOpKind::Equal && !op.old.is_empty())
+    {
+        return None;
+    }
+
+    let mut deleted = Vec::new();
+    let mut inserted = Vec::new();
+    for op in ops {
+        match op.kind {
+            DiffOpKind::Equal => {}
+            DiffOpKind::Delete => deleted.push(op.old),
+            DiffOpKind::Insert => inserted.push(op.new),
+        }
+    }
+    Some((deleted, inserted))
+}
+
+// Splits `range` of a line into pieces which are either entirely inside or entirely outside of
+// the sorted `highlights`, flagging the highlighted ones
+fn highlight_pieces(
+    range: ops::Range<usize>,
+    highlights: &[ops::Range<usize>],
+) -> Vec<(ops::Range<usize>, bool)> {
+    let mut pieces = Vec::new();
+    let mut pos = range.start;
+    for highlight in highlights {
+        let start = highlight.start.clamp(pos, range.end);
+        let end = highlight.end.clamp(start, range.end);
+        if pos < start {
+            pieces.push((pos..start, false));
+        }
+        if start < end {
+            pieces.push((start..end, true));
+        }
+        pos = end;
+    }
+    if pos < range.end || pieces.is_empty() {
+        pieces.push((pos..range.end, false));
+    }
+    pieces
 }
 
 impl Default for PatchFormatter {
@@ -88,29 +900,71 @@ struct PatchDisplay<'a, T: ToOwned + ?Sized> {
     patch: &'a Patch<'a, T>,
 }
 
+impl<T: ToOwned + ?Sized> PatchDisplay<'_, T> {
+    fn has_header(&self) -> bool {
+        self.patch.original.is_some()
+            || self.patch.modified.is_some()
+            || self.patch.status != FileStatus::Modified
+    }
+
+    fn mode_header(&self) -> Option<ModeHeader> {
+        let kind = match self.patch.status {
+            FileStatus::Added => "new",
+            FileStatus::Removed => "deleted",
+            FileStatus::Modified => return None,
+        };
+        Some(ModeHeader {
+            kind,
+            mode: self.patch.mode,
+        })
+    }
+}
+
+// The git header line giving the mode of an added or removed file
+struct ModeHeader {
+    kind: &'static str,
+    mode: u32,
+}
+
+impl Display for ModeHeader {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{} file mode {:o}", self.kind, self.mode)
+    }
+}
+
 impl<T: ToOwned + AsRef<[u8]> + ?Sized> PatchDisplay<'_, T> {
-    fn write_into<W: io::Write>(&self, mut w: W) -> io::Result<()> {
-        if self.patch.original.is_some() || self.patch.modified.is_some() {
+    fn write_into<W: WriteBytes>(&self, mut w: W) -> core::result::Result<(), W::Error> {
+        if self.has_header() {
             if self.f.with_color {
-                write!(w, "{}", self.f.patch_header.prefix())?;
+                write!(w, "{}", self.f.theme.patch_header.prefix())?;
+            }
+            if let Some(mode) = self.mode_header() {
+                writeln!(w, "{}", mode)?;
             }
-            if let Some(original) = &self.patch.original {
+            if self.patch.status == FileStatus::Added {
+                writeln!(w, "--- {}", DEV_NULL)?;
+            } else if let Some(original) = &self.patch.original {
                 write!(w, "--- ")?;
                 original.write_into(&mut w)?;
                 writeln!(w)?;
             }
-            if let Some(modified) = &self.patch.modified {
+            if self.patch.status == FileStatus::Removed {
+                writeln!(w, "+++ {}", DEV_NULL)?;
+            } else if let Some(modified) = &self.patch.modified {
                 write!(w, "+++ ")?;
                 modified.write_into(&mut w)?;
                 writeln!(w)?;
             }
             if self.f.with_color {
-                write!(w, "{}", self.f.patch_header.suffix())?;
+                write!(w, "{}", self.f.theme.patch_header.suffix())?;
             }
         }
 
-        for hunk in &self.patch.hunks {
-            self.f.write_hunk_into(hunk, &mut w)?;
+        for (idx, hunk) in self.patch.hunks.iter().enumerate() {
+            if let Some(prev) = idx.checked_sub(1).map(|prev| &self.patch.hunks[prev]) {
+                w.write_all(self.f.separator_between(prev, hunk).as_bytes())?;
+            }
+            self.f.write_hunk_bytes(hunk, &mut w)?;
         }
 
         Ok(())
@@ -119,22 +973,32 @@ impl<T: ToOwned + AsRef<[u8]> + ?Sized> PatchDisplay<'_, T> {
 
 impl Display for PatchDisplay<'_, str> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        if self.patch.original.is_some() || self.patch.modified.is_some() {
+        if self.has_header() {
             if self.f.with_color {
-                write!(f, "{}", self.f.patch_header.prefix())?;
+                write!(f, "{}", self.f.theme.patch_header.prefix())?;
+            }
+            if let Some(mode) = self.mode_header() {
+                writeln!(f, "{}", mode)?;
             }
-            if let Some(original) = &self.patch.original {
+            if self.patch.status == FileStatus::Added {
+                writeln!(f, "--- {}", DEV_NULL)?;
+            } else if let Some(original) = &self.patch.original {
                 writeln!(f, "--- {}", original)?;
             }
-            if let Some(modified) = &self.patch.modified {
+            if self.patch.status == FileStatus::Removed {
+                writeln!(f, "+++ {}", DEV_NULL)?;
+            } else if let Some(modified) = &self.patch.modified {
                 writeln!(f, "+++ {}", modified)?;
             }
             if self.f.with_color {
-                write!(f, "{}", self.f.patch_header.suffix())?;
+                write!(f, "{}", self.f.theme.patch_header.suffix())?;
             }
         }
 
-        for hunk in &self.patch.hunks {
+        for (idx, hunk) in self.patch.hunks.iter().enumerate() {
+            if let Some(prev) = idx.checked_sub(1).map(|prev| &self.patch.hunks[prev]) {
+                f.write_str(self.f.separator_between(prev, hunk))?;
+            }
             write!(f, "{}", self.f.fmt_hunk(hunk))?;
         }
 
@@ -148,30 +1012,37 @@ struct HunkDisplay<'a, T: ?Sized> {
 }
 
 impl<T: AsRef<[u8]> + ?Sized> HunkDisplay<'_, T> {
-    fn write_into<W: io::Write>(&self, mut w: W) -> io::Result<()> {
+    fn write_into<W: WriteBytes>(&self, mut w: W) -> core::result::Result<(), W::Error> {
         if self.f.with_color {
-            write!(w, "{}", self.f.hunk_header.prefix())?;
+            write!(w, "{}", self.f.hunk_header_style().prefix())?;
         }
         write!(w, "@@ -{} +{} @@", self.hunk.old_range, self.hunk.new_range)?;
         if self.f.with_color {
-            write!(w, "{}", self.f.hunk_header.suffix())?;
+            write!(w, "{}", self.f.hunk_header_style().suffix())?;
         }
 
         if let Some(ctx) = self.hunk.function_context {
             write!(w, " ")?;
             if self.f.with_color {
-                write!(w, "{}", self.f.function_context.prefix())?;
+                write!(w, "{}", self.f.theme.function_context.prefix())?;
             }
             write!(w, " ")?;
             w.write_all(ctx.as_ref())?;
             if self.f.with_color {
-                write!(w, "{}", self.f.function_context.suffix())?;
+                write!(w, "{}", self.f.theme.function_context.suffix())?;
             }
         }
         writeln!(w)?;
 
-        for line in &self.hunk.lines {
-            self.f.write_line_into(line, &mut w)?;
+        let marks = self.f.line_marks(self.hunk);
+        let mut prev_collapsed = false;
+        for (line, mark) in self.hunk.lines.iter().zip(&marks) {
+            if !mark.collapsed {
+                self.f.write_line_into(line, mark, &mut w)?;
+            } else if !prev_collapsed {
+                write!(w, "{}", self.f.collapsed_marker())?;
+            }
+            prev_collapsed = mark.collapsed;
         }
 
         Ok(())
@@ -181,27 +1052,34 @@ impl<T: AsRef<[u8]> + ?Sized> HunkDisplay<'_, T> {
 impl Display for HunkDisplay<'_, str> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         if self.f.with_color {
-            write!(f, "{}", self.f.hunk_header.prefix())?;
+            write!(f, "{}", self.f.hunk_header_style().prefix())?;
         }
         write!(f, "@@ -{} +{} @@", self.hunk.old_range, self.hunk.new_range)?;
         if self.f.with_color {
-            write!(f, "{}", self.f.hunk_header.suffix())?;
+            write!(f, "{}", self.f.hunk_header_style().suffix())?;
         }
 
         if let Some(ctx) = self.hunk.function_context {
             write!(f, " ")?;
             if self.f.with_color {
-                write!(f, "{}", self.f.function_context.prefix())?;
+                write!(f, "{}", self.f.theme.function_context.prefix())?;
             }
             write!(f, " {}", ctx)?;
             if self.f.with_color {
-                write!(f, "{}", self.f.function_context.suffix())?;
+                write!(f, "{}", self.f.theme.function_context.suffix())?;
             }
         }
         writeln!(f)?;
 
-        for line in &self.hunk.lines {
-            write!(f, "{}", self.f.fmt_line(line))?;
+        let marks = self.f.line_marks(self.hunk);
+        let mut prev_collapsed = false;
+        for (line, mark) in self.hunk.lines.iter().zip(&marks) {
+            if !mark.collapsed {
+                write!(f, "{}", self.f.fmt_line(line, mark))?;
+            } else if !prev_collapsed {
+                write!(f, "{}", self.f.collapsed_marker())?;
+            }
+            prev_collapsed = mark.collapsed;
         }
 
         Ok(())
@@ -211,29 +1089,60 @@ impl Display for HunkDisplay<'_, str> {
 struct LineDisplay<'a, T: ?Sized> {
     f: &'a PatchFormatter,
     line: &'a Line<'a, T>,
+    mark: &'a LineMark,
 }
 
 impl<T: AsRef<[u8]> + ?Sized> LineDisplay<'_, T> {
-    fn write_into<W: io::Write>(&self, mut w: W) -> io::Result<()> {
-        let (sign, line, style) = match self.line {
-            Line::Context(line) => (' ', line.as_ref(), self.f.context),
-            Line::Delete(line) => ('-', line.as_ref(), self.f.delete),
-            Line::Insert(line) => ('+', line.as_ref(), self.f.insert),
+    fn write_into<W: WriteBytes>(&self, mut w: W) -> core::result::Result<(), W::Error> {
+        let sign = self.line.prefix_char();
+        let line = self.line.value().as_ref();
+        let style = self
+            .f
+            .line_style(self.line.kind(), self.mark.indentation_only);
+        let highlight = self.f.highlight_style(self.line.kind(), false);
+
+        let (content, ending) = match line.strip_suffix(b"\n") {
+            Some(content) => (content, &b"\n"[..]),
+            None => (line, &b""[..]),
         };
+        let mut ranges = self.f.wrap_ranges(content).peekable();
+        while let Some(range) = ranges.next() {
+            let ending = if ranges.peek().is_some() {
+                b"\n"
+            } else {
+                ending
+            };
 
-        if self.f.with_color {
-            write!(w, "{}", style.prefix())?;
-        }
+            if self.f.with_color {
+                write!(w, "{}", style.prefix())?;
+            }
 
-        if sign == ' ' && line == b"\n" {
-            w.write_all(line)?;
-        } else {
-            write!(w, "{}", sign)?;
-            w.write_all(line)?;
-        }
+            if sign == ' ' && line == b"\n" {
+                w.write_all(line)?;
+            } else {
+                write!(w, "{}", sign)?;
+                for (piece, highlighted) in highlight_pieces(range, &self.mark.highlights) {
+                    if highlighted {
+                        let highlight = if piece.end <= self.mark.changed_indentation {
+                            self.f.highlight_style(self.line.kind(), true)
+                        } else {
+                            highlight
+                        };
+                        write!(w, "{}{}", style.suffix(), highlight.prefix())?;
+                        self.f
+                            .write_content(self.line.kind(), &content[piece], &mut w)?;
+                        write!(w, "{}{}", highlight.suffix(), style.prefix())?;
+                    } else {
+                        self.f
+                            .write_content(self.line.kind(), &content[piece], &mut w)?;
+                    }
+                }
+                w.write_all(ending)?;
+            }
 
-        if self.f.with_color {
-            write!(w, "{}", style.suffix())?;
+            if self.f.with_color {
+                write!(w, "{}", style.suffix())?;
+            }
         }
 
         if !line.ends_with(b"\n") {
@@ -247,24 +1156,53 @@ impl<T: AsRef<[u8]> + ?Sized> LineDisplay<'_, T> {
 
 impl Display for LineDisplay<'_, str> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        let (sign, line, style) = match self.line {
-            Line::Context(line) => (' ', line, self.f.context),
-            Line::Delete(line) => ('-', line, self.f.delete),
-            Line::Insert(line) => ('+', line, self.f.insert),
+        let sign = self.line.prefix_char();
+        let line = self.line.value();
+        let style = self
+            .f
+            .line_style(self.line.kind(), self.mark.indentation_only);
+        let highlight = self.f.highlight_style(self.line.kind(), false);
+
+        let (content, ending) = match line.strip_suffix('\n') {
+            Some(content) => (content, "\n"),
+            None => (line, ""),
         };
+        let mut ranges = self.f.wrap_ranges(content.as_bytes()).peekable();
+        while let Some(range) = ranges.next() {
+            let ending = if ranges.peek().is_some() {
+                "\n"
+            } else {
+                ending
+            };
 
-        if self.f.with_color {
-            write!(f, "{}", style.prefix())?;
-        }
+            if self.f.with_color {
+                write!(f, "{}", style.prefix())?;
+            }
 
-        if sign == ' ' && *line == "\n" {
-            write!(f, "{}", line)?;
-        } else {
-            write!(f, "{}{}", sign, line)?;
-        }
+            if sign == ' ' && line == "\n" {
+                write!(f, "{}", line)?;
+            } else {
+                write!(f, "{}", sign)?;
+                for (piece, highlighted) in highlight_pieces(range, &self.mark.highlights) {
+                    if highlighted {
+                        let highlight = if piece.end <= self.mark.changed_indentation {
+                            self.f.highlight_style(self.line.kind(), true)
+                        } else {
+                            highlight
+                        };
+                        write!(f, "{}{}", style.suffix(), highlight.prefix())?;
+                        self.f.fmt_content(self.line.kind(), &content[piece], f)?;
+                        write!(f, "{}{}", highlight.suffix(), style.prefix())?;
+                    } else {
+                        self.f.fmt_content(self.line.kind(), &content[piece], f)?;
+                    }
+                }
+                f.write_str(ending)?;
+            }
 
-        if self.f.with_color {
-            write!(f, "{}", style.suffix())?;
+            if self.f.with_color {
+                write!(f, "{}", style.suffix())?;
+            }
         }
 
         if !line.ends_with('\n') {
@@ -275,3 +1213,117 @@ impl Display for LineDisplay<'_, str> {
         Ok(())
     }
 }
+
+// Iterator over the ranges of a line to output on separate lines when wrapping it. Ranges
+// only end at the start of a utf8 character, so they're valid slice boundaries for a `str`.
+struct WrapRanges<'a> {
+    line: &'a [u8],
+    width: usize,
+    start: Option<usize>,
+}
+
+impl Iterator for WrapRanges<'_> {
+    type Item = ops::Range<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.start?;
+        let end = self.line[start..]
+            .iter()
+            .enumerate()
+            .filter(|(_, &b)| b & 0xC0 != 0x80)
+            .nth(self.width)
+            .map_or(self.line.len(), |(offset, _)| start + offset);
+
+        self.start = if end < self.line.len() {
+            Some(end)
+        } else {
+            None
+        };
+        Some(start..end)
+    }
+}
+
+/// A minimal byte sink used to render patches without depending on `std::io`
+pub(super) trait WriteBytes {
+    type Error;
+
+    fn write_all(&mut self, buf: &[u8]) -> core::result::Result<(), Self::Error>;
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> core::result::Result<(), Self::Error>;
+}
+
+impl<W: WriteBytes + ?Sized> WriteBytes for &mut W {
+    type Error = W::Error;
+
+    fn write_all(&mut self, buf: &[u8]) -> core::result::Result<(), Self::Error> {
+        (**self).write_all(buf)
+    }
+
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> core::result::Result<(), Self::Error> {
+        (**self).write_fmt(args)
+    }
+}
+
+impl WriteBytes for Vec<u8> {
+    type Error = Infallible;
+
+    fn write_all(&mut self, buf: &[u8]) -> core::result::Result<(), Self::Error> {
+        self.extend_from_slice(buf);
+        Ok(())
+    }
+
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> core::result::Result<(), Self::Error> {
+        struct Adapter<'a>(&'a mut Vec<u8>);
+
+        impl fmt::Write for Adapter<'_> {
+            fn write_str(&mut self, s: &str) -> Result {
+                self.0.extend_from_slice(s.as_bytes());
+                Ok(())
+            }
+        }
+
+        // Writing into a `Vec` can't fail
+        let _ = fmt::Write::write_fmt(&mut Adapter(self), args);
+        Ok(())
+    }
+}
+
+// Counts the bytes written to it rather than storing them
+struct ByteCounter(usize);
+
+impl WriteBytes for ByteCounter {
+    type Error = Infallible;
+
+    fn write_all(&mut self, buf: &[u8]) -> core::result::Result<(), Self::Error> {
+        self.0 += buf.len();
+        Ok(())
+    }
+
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> core::result::Result<(), Self::Error> {
+        // Counting bytes can't fail
+        let _ = fmt::Write::write_fmt(self, args);
+        Ok(())
+    }
+}
+
+impl fmt::Write for ByteCounter {
+    fn write_str(&mut self, s: &str) -> Result {
+        self.0 += s.len();
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+pub(super) struct IoWrite<W>(pub(super) W);
+
+#[cfg(feature = "std")]
+impl<W: io::Write> WriteBytes for IoWrite<W> {
+    type Error = io::Error;
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.0.write_all(buf)
+    }
+
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> io::Result<()> {
+        self.0.write_fmt(args)
+    }
+}
@@ -0,0 +1,120 @@
+use super::{Hunk, Patch};
+use alloc::{borrow::ToOwned, vec::Vec};
+use core::ops;
+
+/// A change between two versions of a patch, see [`diff_patches`]
+///
+/// Hunks are the ones of the canonical form of each patch, see [`Patch::canonicalize`]. They
+/// carry no context lines and are identified by their ranges: a hunk's old range locates it in
+/// the file both patches apply to.
+///
+/// [`diff_patches`]: fn.diff_patches.html
+/// [`Patch::canonicalize`]: struct.Patch.html#method.canonicalize
+#[derive(Debug, PartialEq, Eq)]
+pub enum HunkDiff<'a, T: ?Sized> {
+    /// A hunk which is only part of the new patch
+    Added(Hunk<'a, T>),
+    /// A hunk which is only part of the old patch
+    Removed(Hunk<'a, T>),
+    /// Hunks of both patches which change the same lines of the file, but differently
+    ///
+    /// This is usually one hunk of each patch, but a hunk which overlaps several hunks of the
+    /// other patch is grouped with all of them.
+    Changed {
+        /// The hunks of the old patch
+        old: Vec<Hunk<'a, T>>,
+        /// The hunks of the new patch
+        new: Vec<Hunk<'a, T>>,
+    },
+}
+
+/// Compares two versions of a patch to the same file, returning the hunks which were added,
+/// removed or changed in order of their position in the file
+///
+/// This shows how a patch evolved, e.g. how a pull request's diff changed since it was last
+/// reviewed. Both patches are compared in their canonical form, so the way their hunks are laid
+/// out and the context around them don't matter. Hunks of both patches making the same change
+/// aren't returned, so two patches making the same changes have no differences.
+///
+/// ```
+/// use diffy::{create_patch, diff_patches, HunkDiff};
+///
+/// let original = "a\nb\nc\nd\ne\nf\n";
+/// let old = create_patch(original, "A\nb\nc\nd\ne\nf\n");
+/// let new = create_patch(original, "A\nb\nc\nD\ne\nf\n");
+///
+/// let diff = diff_patches(&old, &new);
+/// assert_eq!(diff.len(), 1);
+/// match &diff[0] {
+///     HunkDiff::Added(hunk) => assert_eq!(hunk.to_string(), "@@ -4 +4 @@\n-d\n+D\n"),
+///     _ => unreachable!(),
+/// }
+///
+/// let interleaved = "--- a\n+++ b\n@@ -1 +1 @@\n-a\n+A\n";
+/// let interleaved = diffy::Patch::from_str(interleaved).unwrap();
+/// assert!(diff_patches(&old, &interleaved).is_empty());
+/// ```
+pub fn diff_patches<'p, T: ToOwned + PartialEq + ?Sized>(
+    old: &'p Patch<'_, T>,
+    new: &'p Patch<'_, T>,
+) -> Vec<HunkDiff<'p, T>> {
+    let mut old_hunks = old.canonicalize().hunks.into_iter().peekable();
+    let mut new_hunks = new.canonicalize().hunks.into_iter().peekable();
+
+    let mut diffs = Vec::new();
+    loop {
+        // Start a group with whichever hunk comes first, then keep adding the hunks of either
+        // patch which overlap the lines covered so far
+        let take_old = match (old_hunks.peek(), new_hunks.peek()) {
+            (Some(o), Some(n)) => old_range(o).start <= old_range(n).start,
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => break,
+        };
+        let (mut group_old, mut group_new) = (Vec::new(), Vec::new());
+        let mut range = if take_old {
+            let hunk = old_hunks.next().unwrap();
+            let range = old_range(&hunk);
+            group_old.push(hunk);
+            range
+        } else {
+            let hunk = new_hunks.next().unwrap();
+            let range = old_range(&hunk);
+            group_new.push(hunk);
+            range
+        };
+        loop {
+            if let Some(hunk) = old_hunks.next_if(|hunk| overlaps(&range, &old_range(hunk))) {
+                range.end = range.end.max(old_range(&hunk).end);
+                group_old.push(hunk);
+            } else if let Some(hunk) = new_hunks.next_if(|hunk| overlaps(&range, &old_range(hunk)))
+            {
+                range.end = range.end.max(old_range(&hunk).end);
+                group_new.push(hunk);
+            } else {
+                break;
+            }
+        }
+
+        match (&group_old[..], &group_new[..]) {
+            ([], _) => diffs.extend(group_new.into_iter().map(HunkDiff::Added)),
+            (_, []) => diffs.extend(group_old.into_iter().map(HunkDiff::Removed)),
+            ([o], [n]) if o.old_range == n.old_range && o.lines == n.lines => {}
+            _ => diffs.push(HunkDiff::Changed {
+                old: group_old,
+                new: group_new,
+            }),
+        }
+    }
+    diffs
+}
+
+fn old_range<T: ?Sized>(hunk: &Hunk<'_, T>) -> ops::Range<usize> {
+    hunk.old_range.to_std_range()
+}
+
+// Returns `true` if two ranges of the old file share a line, or if they start at the same line
+// so that one inserts lines where the other changes them
+fn overlaps(a: &ops::Range<usize>, b: &ops::Range<usize>) -> bool {
+    a.start == b.start || (a.start < b.end && b.start < a.end)
+}
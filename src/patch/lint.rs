@@ -0,0 +1,298 @@
+//! Check a Patch for common hygiene problems
+
+use super::{Hunk, Line, Patch};
+use alloc::{borrow::ToOwned, vec::Vec};
+use core::fmt;
+
+/// The kind of problem reported by a [`LintWarning`]
+///
+/// [`LintWarning`]: struct.LintWarning.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum LintCategory {
+    /// An inserted line ends with a space or a tab
+    TrailingWhitespace,
+    /// The indentation of an inserted line has a space directly followed by a tab
+    SpaceBeforeTab,
+    /// An inserted line is longer than the configured maximum length
+    LineTooLong,
+    /// An inserted line contains a carriage return
+    CarriageReturn,
+    /// The patch removes the newline at the end of the file
+    MissingFinalNewline,
+    /// A hunk doesn't contain any lines
+    EmptyHunk,
+    /// A hunk only contains context lines and doesn't change anything
+    ContextOnlyHunk,
+}
+
+impl fmt::Display for LintCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            LintCategory::TrailingWhitespace => "trailing whitespace",
+            LintCategory::SpaceBeforeTab => "space before tab in indent",
+            LintCategory::LineTooLong => "line too long",
+            LintCategory::CarriageReturn => "carriage return",
+            LintCategory::MissingFinalNewline => "no newline at end of file",
+            LintCategory::EmptyHunk => "empty hunk",
+            LintCategory::ContextOnlyHunk => "hunk has no changes",
+        };
+        f.write_str(s)
+    }
+}
+
+/// A problem found in a patch by [`Patch::lint`]
+///
+/// [`Patch::lint`]: struct.Patch.html#method.lint
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LintWarning {
+    /// The 0-based index of the hunk the problem was found in
+    pub hunk_index: usize,
+    /// The 1-based line number in the new file of the offending line. For problems with a whole
+    /// hunk this is the start of the hunk's range in the new file.
+    pub new_lineno: usize,
+    /// The kind of problem
+    pub category: LintCategory,
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "hunk #{} line {}: {}",
+            self.hunk_index + 1,
+            self.new_lineno,
+            self.category
+        )
+    }
+}
+
+/// A collection of options for modifying the checks done when linting a patch
+#[derive(Debug)]
+pub struct LintOptions {
+    max_line_len: Option<usize>,
+}
+
+impl LintOptions {
+    /// Constructs a new `LintOptions` with default settings
+    ///
+    /// ## Defaults
+    /// * max_line_len = None
+    pub fn new() -> Self {
+        Self { max_line_len: None }
+    }
+
+    /// Set the maximum length, in characters, of an inserted line
+    ///
+    /// Longer inserted lines are reported as [`LintCategory::LineTooLong`]. The line terminator
+    /// doesn't count towards the length. `None` disables the check.
+    ///
+    /// [`LintCategory::LineTooLong`]: enum.LintCategory.html#variant.LineTooLong
+    pub fn set_max_line_len(&mut self, max_line_len: Option<usize>) -> &mut Self {
+        self.max_line_len = max_line_len;
+        self
+    }
+
+    /// Check a `Patch` for common hygiene problems based on the configured options
+    ///
+    /// See [`Patch::lint`](struct.Patch.html#method.lint) for the checks performed.
+    pub fn lint<T: AsRef<[u8]> + ToOwned + ?Sized>(
+        &self,
+        patch: &Patch<'_, T>,
+    ) -> Vec<LintWarning> {
+        let mut warnings = Vec::new();
+        for (hunk_index, hunk) in patch.hunks().iter().enumerate() {
+            self.lint_hunk(hunk_index, hunk, &mut warnings);
+        }
+        warnings
+    }
+
+    fn lint_hunk<T: AsRef<[u8]> + ?Sized>(
+        &self,
+        hunk_index: usize,
+        hunk: &Hunk<'_, T>,
+        warnings: &mut Vec<LintWarning>,
+    ) {
+        let mut warn = |new_lineno, category| {
+            warnings.push(LintWarning {
+                hunk_index,
+                new_lineno,
+                category,
+            })
+        };
+
+        if hunk.lines().is_empty() {
+            warn(hunk.new_range().start(), LintCategory::EmptyHunk);
+            return;
+        }
+        if hunk.lines().iter().all(Line::is_context) {
+            warn(hunk.new_range().start(), LintCategory::ContextOnlyHunk);
+        }
+
+        // The final newline is only removed by the patch if the old side of the hunk had one
+        let old_has_final_newline = hunk
+            .lines()
+            .iter()
+            .filter(|line| line.is_delete())
+            .all(|line| line.value().as_ref().ends_with(b"\n"));
+
+        let mut new_lineno = hunk.new_range().start();
+        for line in hunk.lines() {
+            if line.is_delete() {
+                continue;
+            }
+            if line.is_insert() {
+                let line = line.value().as_ref();
+                let (content, has_newline) = match line.strip_suffix(b"\n") {
+                    Some(content) => (content, true),
+                    None => (line, false),
+                };
+                let content = content.strip_suffix(b"\r").unwrap_or(content);
+
+                if content.ends_with(b" ") || content.ends_with(b"\t") {
+                    warn(new_lineno, LintCategory::TrailingWhitespace);
+                }
+                let indent_len = content
+                    .iter()
+                    .position(|&b| b != b' ' && b != b'\t')
+                    .unwrap_or(content.len());
+                if content[..indent_len].windows(2).any(|w| w == b" \t") {
+                    warn(new_lineno, LintCategory::SpaceBeforeTab);
+                }
+                if let Some(max_line_len) = self.max_line_len {
+                    if char_count(content) > max_line_len {
+                        warn(new_lineno, LintCategory::LineTooLong);
+                    }
+                }
+                if line.contains(&b'\r') {
+                    warn(new_lineno, LintCategory::CarriageReturn);
+                }
+                if !has_newline && old_has_final_newline {
+                    warn(new_lineno, LintCategory::MissingFinalNewline);
+                }
+            }
+            new_lineno += 1;
+        }
+    }
+}
+
+impl Default for LintOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Count the characters in potentially non-utf8 text by counting the bytes which don't continue a
+// utf8 sequence, so that every invalid byte counts as a character of its own
+fn char_count(text: &[u8]) -> usize {
+    text.iter().filter(|&&b| b & 0xC0 != 0x80).count()
+}
+
+impl<T: AsRef<[u8]> + ToOwned + ?Sized> Patch<'_, T> {
+    /// Check the patch for common hygiene problems
+    ///
+    /// Every inserted line is checked for trailing whitespace, a space followed by a tab in its
+    /// indentation, carriage returns, and whether it removes the newline at the end of the file.
+    /// Each hunk is also checked for being empty or only containing context lines. The warnings
+    /// are returned in the order they appear in the patch.
+    ///
+    /// Use [`LintOptions`] to also check the length of inserted lines.
+    ///
+    /// ```
+    /// use diffy::{create_patch, LintCategory, LintWarning};
+    ///
+    /// let patch = create_patch("a\nb\n", "a\nb \n");
+    /// assert_eq!(
+    ///     patch.lint(),
+    ///     [LintWarning {
+    ///         hunk_index: 0,
+    ///         new_lineno: 2,
+    ///         category: LintCategory::TrailingWhitespace,
+    ///     }]
+    /// );
+    /// ```
+    ///
+    /// [`LintOptions`]: struct.LintOptions.html
+    pub fn lint(&self) -> Vec<LintWarning> {
+        LintOptions::new().lint(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LintCategory, LintOptions, LintWarning};
+    use crate::Patch;
+    use alloc::{string::ToString, vec::Vec};
+
+    #[test]
+    fn lint_categories() {
+        let s = "\
+--- a/file
++++ b/file
+@@ -1,3 +1,6 @@
+ context
+-old
++trailing space\x20
++ \tindented
++a line which is too long
++carriage\r
+ context
+@@ -10,3 +13,3 @@
+ x
+ y
+ z
+@@ -30 +33 @@
+-end
++end
+\\ No newline at end of file
+";
+        let patch = Patch::from_str(s).unwrap();
+        let mut options = LintOptions::new();
+        options.set_max_line_len(Some(20));
+
+        let warnings: Vec<_> = options
+            .lint(&patch)
+            .into_iter()
+            .map(|w| (w.hunk_index, w.new_lineno, w.category))
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                (0, 2, LintCategory::TrailingWhitespace),
+                (0, 3, LintCategory::SpaceBeforeTab),
+                (0, 4, LintCategory::LineTooLong),
+                (0, 5, LintCategory::CarriageReturn),
+                (1, 13, LintCategory::ContextOnlyHunk),
+                (2, 33, LintCategory::MissingFinalNewline),
+            ]
+        );
+
+        // Without a maximum length only the line length check is skipped
+        assert_eq!(patch.lint().len(), 5);
+        let bytes = Patch::from_bytes(s.as_bytes()).unwrap();
+        assert_eq!(options.lint(&bytes), options.lint(&patch));
+
+        let warning = LintWarning {
+            hunk_index: 1,
+            new_lineno: 13,
+            category: LintCategory::ContextOnlyHunk,
+        };
+        assert_eq!(warning.to_string(), "hunk #2 line 13: hunk has no changes");
+    }
+
+    #[test]
+    fn lint_empty_hunk() {
+        let s = "\
+@@ -1,0 +1,0 @@
+@@ -3 +3 @@
+-a
+\\ No newline at end of file
++b
+\\ No newline at end of file
+";
+        // A file which already lacked a final newline doesn't lose it
+        let patch = Patch::from_str(s).unwrap();
+        let warnings: Vec<_> = patch.lint().into_iter().map(|w| w.category).collect();
+        assert_eq!(warnings, [LintCategory::EmptyHunk]);
+    }
+}
@@ -1,12 +1,44 @@
+mod coalesce;
+mod convert;
+mod ed;
 mod format;
+mod interdiff;
+mod lint;
 mod parse;
+mod paths;
+mod porcelain;
+mod styled;
+mod theme;
 
+pub use coalesce::MergeHunksError;
+pub use convert::PatchUtf8Error;
+pub use ed::EdScriptFormatter;
 pub use format::PatchFormatter;
+pub use interdiff::{diff_patches, HunkDiff};
+pub use lint::{LintCategory, LintOptions, LintWarning};
 pub use parse::ParsePatchError;
+pub use paths::PrefixPolicy;
+pub use porcelain::{ParsePorcelainError, PorcelainFormatter, PorcelainPatch};
+pub use styled::{SpanStyle, SplitRow, StyledRow, StyledSpan};
+pub use theme::{Attributes, ColorChoice, StyleConfig, Theme};
 
-use std::{borrow::Cow, fmt, ops};
+use alloc::{
+    borrow::{Cow, ToOwned},
+    string::String,
+    vec::Vec,
+};
+use core::{cmp, fmt, ops};
 
-const NO_NEWLINE_AT_EOF: &str = "\\ No newline at end of file";
+#[cfg(feature = "std")]
+pub(crate) use paths::{file_name, Side};
+
+pub(crate) const NO_NEWLINE_AT_EOF: &str = "\\ No newline at end of file";
+
+// The name used in place of a file which doesn't exist, as used by GNU diff and git
+pub(crate) const DEV_NULL: &str = "/dev/null";
+
+// The mode of a regular, non-executable file, as written in git mode headers
+pub(crate) const DEFAULT_MODE: u32 = 0o100644;
 
 /// Representation of all the differences between two files
 #[derive(PartialEq, Eq)]
@@ -17,6 +49,38 @@ pub struct Patch<'a, T: ToOwned + ?Sized> {
     original: Option<Filename<'a, T>>,
     modified: Option<Filename<'a, T>>,
     hunks: Vec<Hunk<'a, T>>,
+    status: FileStatus,
+    mode: u32,
+}
+
+/// Whether a [`Patch`] modifies a file or adds or removes it entirely
+///
+/// This controls how the patch header is formatted. Patches for added or removed files use
+/// `/dev/null` in place of the missing file's name and are preceded by a git style
+/// `new file mode` or `deleted file mode` line.
+///
+/// [`Patch`]: struct.Patch.html
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum FileStatus {
+    /// The file didn't exist before the patch
+    Added,
+    /// The file doesn't exist after the patch
+    Removed,
+    /// The file exists on both sides of the patch
+    #[default]
+    Modified,
+}
+
+/// The order of the hunks of a patch sorted with [`Patch::sorted_hunks`]
+///
+/// [`Patch::sorted_hunks`]: struct.Patch.html#method.sorted_hunks
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum SortKey {
+    /// By position in the old file, the order a patch is applied in
+    #[default]
+    Position,
+    /// By the number of lines inserted and deleted, largest first
+    Size,
 }
 
 impl<'a, T: ToOwned + ?Sized> Patch<'a, T> {
@@ -35,9 +99,65 @@ impl<'a, T: ToOwned + ?Sized> Patch<'a, T> {
             original,
             modified,
             hunks,
+            status: FileStatus::Modified,
+            mode: DEFAULT_MODE,
         }
     }
 
+    /// Set whether the patch adds, removes or modifies its file
+    ///
+    /// Patches are created as [`FileStatus::Modified`]. When formatted, a patch for an added
+    /// file shows `/dev/null` as its original file and a patch for a removed file shows
+    /// `/dev/null` as its modified file, each preceded by the corresponding git mode header.
+    ///
+    /// ```
+    /// use diffy::{create_patch, FileStatus};
+    ///
+    /// let patch = create_patch("", "a\n").with_status(FileStatus::Added);
+    /// let expected = "\
+    /// new file mode 100644
+    /// --- /dev/null
+    /// +++ modified
+    /// @@ -0,0 +1 @@
+    /// +a
+    /// ";
+    /// assert_eq!(patch.to_string(), expected);
+    /// ```
+    ///
+    /// [`FileStatus::Modified`]: enum.FileStatus.html#variant.Modified
+    pub fn with_status(mut self, status: FileStatus) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Returns whether the patch adds, removes or modifies its file
+    pub fn status(&self) -> FileStatus {
+        self.status
+    }
+
+    /// Set the mode of the file the patch adds or removes, as written in its git mode header
+    ///
+    /// Patches are created with mode `0o100644`, a regular file. Parsing a patch keeps the mode
+    /// of its `new file mode` or `deleted file mode` line.
+    ///
+    /// ```
+    /// use diffy::{create_patch, FileStatus};
+    ///
+    /// let patch = create_patch("", "#!/bin/sh\n")
+    ///     .with_status(FileStatus::Added)
+    ///     .with_mode(0o100755);
+    /// assert!(patch.to_string().starts_with("new file mode 100755\n"));
+    /// ```
+    pub fn with_mode(mut self, mode: u32) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Returns the mode of the file the patch adds or removes
+    pub fn mode(&self) -> u32 {
+        self.mode
+    }
+
     /// Return the name of the old file
     pub fn original(&self) -> Option<&T> {
         self.original.as_ref().map(AsRef::as_ref)
@@ -53,13 +173,306 @@ impl<'a, T: ToOwned + ?Sized> Patch<'a, T> {
         &self.hunks
     }
 
+    /// Creates a patch which undoes this one
+    ///
+    /// The old and new sides of every hunk are swapped, as are the file names, and added files
+    /// become removed files and vice versa. If the patch [`is_reversible`], applying the
+    /// reversed patch to the result of applying this one gives back exactly the text this one
+    /// was applied to.
+    ///
+    /// ```
+    /// use diffy::{apply, create_patch};
+    ///
+    /// let original = "a\nb\nc\n";
+    /// let modified = "a\nB\nc\nd\n";
+    ///
+    /// let patch = create_patch(original, modified);
+    /// assert_eq!(apply(original, &patch).unwrap(), modified);
+    /// assert_eq!(apply(modified, &patch.reverse()).unwrap(), original);
+    /// ```
+    ///
+    /// [`is_reversible`]: #method.is_reversible
     pub fn reverse(&self) -> Patch<'_, T> {
         let hunks = self.hunks.iter().map(Hunk::reverse).collect();
+        let status = match self.status {
+            FileStatus::Added => FileStatus::Removed,
+            FileStatus::Removed => FileStatus::Added,
+            FileStatus::Modified => FileStatus::Modified,
+        };
         Patch {
             original: self.modified.clone(),
             modified: self.original.clone(),
             hunks,
+            status,
+            mode: self.mode,
+        }
+    }
+
+    /// Returns `true` if both this patch and its [`reverse`] apply exactly, so that the patch
+    /// can be used to both undo and redo a change
+    ///
+    /// Every hunk already carries both the old and the new content of the lines it changes, so
+    /// what's left to check is that the hunks can be located in either direction without
+    /// searching: they have to be in order, mustn't overlap, and each hunk's new range has to
+    /// start where its old range ends up after the preceding hunks were applied. When that holds,
+    /// applying the patch to the text it was created from and then applying its reverse to the
+    /// result round-trips exactly, whether or not the hunks have any context lines.
+    ///
+    /// Patches created by diffy are reversible, unless some changes were left out of them with
+    /// [`DiffOptions::set_squeeze_blank_lines`] or [`DiffOptions::set_ignore_comment_changes`],
+    /// as the line numbers of the hunks after such a change don't account for it. Parsed patches
+    /// may not be either, e.g. when they've been edited by hand without updating their line
+    /// numbers, in which case applying them relies on searching for where their hunks match,
+    /// which may find a different position in the other direction.
+    ///
+    /// ```
+    /// use diffy::{create_patch, Patch};
+    ///
+    /// let patch = create_patch("a\nb\nc\n", "a\nB\nc\nd\n");
+    /// assert!(patch.is_reversible());
+    ///
+    /// let s = "\
+    /// --- original
+    /// +++ modified
+    /// @@ -1 +1,2 @@
+    /// -a
+    /// +A
+    /// +x
+    /// @@ -3 +3 @@
+    /// -c
+    /// +C
+    /// ";
+    /// // The second hunk should start at line 4 of the new file
+    /// assert!(!Patch::from_str(s).unwrap().is_reversible());
+    /// ```
+    ///
+    /// [`reverse`]: #method.reverse
+    /// [`DiffOptions::set_squeeze_blank_lines`]: struct.DiffOptions.html#method.set_squeeze_blank_lines
+    /// [`DiffOptions::set_ignore_comment_changes`]: struct.DiffOptions.html#method.set_ignore_comment_changes
+    pub fn is_reversible(&self) -> bool {
+        // How many lines the new file has gained over the old one before the next hunk
+        let mut delta = 0isize;
+        let mut old_end = 0;
+        for hunk in &self.hunks {
+            let old = hunk.old_range.to_std_range();
+            let new = hunk.new_range.to_std_range();
+            if old.start < old_end || new.start as isize != old.start as isize + delta {
+                return false;
+            }
+            old_end = old.end;
+            delta += new.len() as isize - old.len() as isize;
+        }
+        true
+    }
+
+    /// Creates a copy of the patch with all context lines removed
+    ///
+    /// Each hunk is reduced to just its deleted and inserted lines, with its ranges adjusted to
+    /// cover only those lines. Hunks containing several groups of changes separated by context
+    /// are split into one hunk per group. The result is equivalent to creating the patch with a
+    /// context length of `0`.
+    ///
+    /// ```
+    /// use diffy::{apply, create_patch, DiffOptions};
+    ///
+    /// let original = "a\nb\nc\nd\n";
+    /// let modified = "a\nB\nc\nD\n";
+    ///
+    /// let patch = create_patch(original, modified);
+    /// let stripped = patch.strip_context();
+    /// assert_eq!(stripped.hunks().len(), 2);
+    /// assert_eq!(apply(original, &stripped).unwrap(), modified);
+    ///
+    /// let expected = DiffOptions::new().set_context_len(0).create_patch(original, modified);
+    /// assert_eq!(stripped, expected);
+    /// ```
+    pub fn strip_context(&self) -> Patch<'_, T> {
+        let hunks = self.hunks.iter().flat_map(Hunk::strip_context).collect();
+        Patch {
+            original: self.original.clone(),
+            modified: self.modified.clone(),
+            hunks,
+            status: self.status,
+            mode: self.mode,
+        }
+    }
+
+    /// Creates a canonical copy of the patch, so that patches making the same change compare
+    /// equal regardless of how they were laid out
+    ///
+    /// All context lines are removed, the hunks are sorted by their position in the old file
+    /// and hunks which touch are merged. Within each hunk the deleted lines come before the
+    /// inserted lines, hunks without any lines are dropped, the new file's line numbers are
+    /// recomputed from the old file's and function context is removed. The file names are kept
+    /// as they are.
+    ///
+    /// Unlike [`normalize`](#method.normalize) this doesn't need the original text, but it
+    /// also can't tell that a deleted line and an inserted line are the same.
+    ///
+    /// ```
+    /// use diffy::{create_patch, Patch};
+    ///
+    /// let original = "a\nb\nc\nd\n";
+    /// let modified = "A\nB\nc\nD\n";
+    /// let patch = create_patch(original, modified);
+    ///
+    /// let s = "\
+    /// --- original
+    /// +++ modified
+    /// @@ -1,2 +1,2 @@
+    /// -a
+    /// +A
+    /// -b
+    /// +B
+    /// @@ -4 +4 @@
+    /// -d
+    /// +D
+    /// ";
+    /// let interleaved = Patch::from_str(s).unwrap();
+    /// assert_ne!(patch, interleaved);
+    /// assert_eq!(patch.canonicalize(), interleaved.canonicalize());
+    /// ```
+    pub fn canonicalize(&self) -> Patch<'_, T> {
+        let mut changes: Vec<_> = self
+            .hunks
+            .iter()
+            .flat_map(Hunk::strip_context)
+            .filter(|hunk| !hunk.lines.is_empty())
+            .collect();
+        changes.sort_by_key(|hunk| hunk.old_range.to_std_range().start);
+
+        // Merge touching changes, collecting each one's deleted and inserted lines
+        #[allow(clippy::type_complexity)]
+        let mut groups: Vec<(ops::Range<usize>, Vec<Line<'_, T>>, Vec<Line<'_, T>>)> = Vec::new();
+        for hunk in &changes {
+            let old = hunk.old_range.to_std_range();
+            let (deleted, inserted) = hunk.lines.iter().partition(|line| line.is_delete());
+            match groups.last_mut() {
+                Some((range, d, i)) if old.start <= range.end => {
+                    range.end = cmp::max(range.end, old.end);
+                    d.extend(deleted);
+                    i.extend(inserted);
+                }
+                _ => groups.push((old, deleted, inserted)),
+            }
+        }
+
+        let mut delta = 0isize;
+        let hunks = groups
+            .into_iter()
+            .map(|(old, deleted, inserted)| {
+                // Overlapping changes can't come from a valid patch, but still mustn't produce a
+                // hunk whose range disagrees with its lines
+                let old = old.start..old.start + deleted.len();
+                let new_start = (old.start as isize + delta) as usize;
+                let new = new_start..new_start + inserted.len();
+                delta += inserted.len() as isize - deleted.len() as isize;
+
+                let mut lines = deleted;
+                lines.extend(inserted);
+                Hunk::new(HunkRange::from(old), HunkRange::from(new), None, lines)
+            })
+            .collect();
+
+        Patch {
+            original: self.original.clone(),
+            modified: self.modified.clone(),
+            hunks,
+            status: self.status,
+            mode: self.mode,
+        }
+    }
+
+    /// Returns a copy of the patch with its hunks reordered, leaving the hunks themselves as
+    /// they are
+    ///
+    /// With [`SortKey::Position`] the hunks are ordered by their position in the old file, which
+    /// is the order a patch is applied in. With [`SortKey::Size`] the hunks changing the most
+    /// lines come first, e.g. so that reviewers can tackle the biggest changes first. Hunks
+    /// which compare equal keep their relative order.
+    ///
+    /// A patch sorted by size is meant for display only: its hunks are no longer in the order
+    /// of the file, so it may fail to apply or even apply to the wrong lines, and its rendered
+    /// form is rejected by [`Patch::from_str`]. Sort it by position again before applying it.
+    ///
+    /// ```
+    /// use diffy::{DiffOptions, SortKey};
+    ///
+    /// let original = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+    /// let modified = "A\nb\nc\nd\ne\nf\ng\nH\nI\nJ\n";
+    /// let patch = DiffOptions::new()
+    ///     .set_context_len(1)
+    ///     .create_patch(original, modified);
+    ///
+    /// let sorted = patch.sorted_hunks(SortKey::Size);
+    /// assert_eq!(sorted.hunks()[0], patch.hunks()[1]);
+    /// assert_eq!(sorted.hunks()[1], patch.hunks()[0]);
+    /// assert_eq!(sorted.sorted_hunks(SortKey::Position), patch);
+    /// ```
+    ///
+    /// [`SortKey::Position`]: enum.SortKey.html#variant.Position
+    /// [`SortKey::Size`]: enum.SortKey.html#variant.Size
+    /// [`Patch::from_str`]: #method.from_str
+    pub fn sorted_hunks(&self, by: SortKey) -> Patch<'a, T> {
+        let mut hunks = self.hunks.clone();
+        match by {
+            SortKey::Position => hunks.sort_by_key(|hunk| hunk.old_range.to_std_range().start),
+            SortKey::Size => hunks.sort_by_key(|hunk| {
+                let changed = hunk.lines.iter().filter(|line| !line.is_context());
+                cmp::Reverse(changed.count())
+            }),
+        }
+
+        Patch {
+            original: self.original.clone(),
+            modified: self.modified.clone(),
+            hunks,
+            status: self.status,
+            mode: self.mode,
+        }
+    }
+
+    /// Returns every line of every hunk along with its line numbers in the old and new file
+    ///
+    /// Context lines have both line numbers, deleted lines only have an old line number and
+    /// inserted lines only have a new line number. Line numbers are 1-based.
+    ///
+    /// ```
+    /// use diffy::create_patch;
+    ///
+    /// let patch = create_patch("a\nb\nc\n", "a\nB\nc\n");
+    /// let numbers: Vec<_> = patch
+    ///     .annotated_lines()
+    ///     .map(|line| (line.old_lineno, line.new_lineno))
+    ///     .collect();
+    /// assert_eq!(
+    ///     numbers,
+    ///     [
+    ///         (Some(1), Some(1)),
+    ///         (Some(2), None),
+    ///         (None, Some(2)),
+    ///         (Some(3), Some(3)),
+    ///     ]
+    /// );
+    /// ```
+    pub fn annotated_lines(&self) -> impl Iterator<Item = AnnotatedLine<'_, 'a, T>> + '_ {
+        self.hunks
+            .iter()
+            .enumerate()
+            .flat_map(|(hunk_index, hunk)| hunk.annotated_lines(hunk_index))
+    }
+
+    // Whether the selected ranges of the hunks are contiguous and start at the first line
+    fn covers_whole_file(&self, range: fn(&Hunk<'a, T>) -> HunkRange) -> bool {
+        let mut next = 0;
+        for hunk in &self.hunks {
+            let range = range(hunk).to_std_range();
+            if range.start != next {
+                return false;
+            }
+            next = range.end;
         }
+        !self.hunks.is_empty()
     }
 }
 
@@ -70,11 +483,34 @@ impl<T: AsRef<[u8]> + ToOwned + ?Sized> Patch<'_, T> {
     /// potentially non-utf8 patches.
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut bytes = Vec::new();
-        PatchFormatter::new()
-            .write_patch_into(self, &mut bytes)
-            .unwrap();
+        PatchFormatter::new().write_patch_into_vec(self, &mut bytes);
         bytes
     }
+
+    /// Returns the number of bytes the patch takes up when rendered with `formatter`, without
+    /// rendering it
+    ///
+    /// The size is exact: it's the length of the output of [`PatchFormatter::patch_bytes`], and
+    /// of [`PatchFormatter::fmt_patch`] for patches between `str`s. That includes the escape
+    /// codes for colors and text attributes if the formatter uses color, and anything a custom
+    /// line renderer produces.
+    ///
+    /// ```
+    /// use diffy::{create_patch, PatchFormatter};
+    ///
+    /// let patch = create_patch("a\nb\n", "a\nc\n");
+    /// let f = PatchFormatter::new();
+    /// assert_eq!(patch.rendered_size(&f), patch.to_string().len());
+    ///
+    /// let colored = PatchFormatter::new().with_color();
+    /// assert!(patch.rendered_size(&colored) > patch.rendered_size(&f));
+    /// ```
+    ///
+    /// [`PatchFormatter::patch_bytes`]: struct.PatchFormatter.html#method.patch_bytes
+    /// [`PatchFormatter::fmt_patch`]: struct.PatchFormatter.html#method.fmt_patch
+    pub fn rendered_size(&self, formatter: &PatchFormatter) -> usize {
+        formatter.rendered_size(self)
+    }
 }
 
 impl<'a> Patch<'a, str> {
@@ -101,6 +537,81 @@ impl<'a> Patch<'a, str> {
     pub fn from_str(s: &'a str) -> Result<Patch<'a, str>, ParsePatchError> {
         parse::parse(s)
     }
+
+    /// Returns the text each hunk expects to find in the original file, along with the range
+    /// of the original file it covers
+    ///
+    /// Each fragment is made up of a hunk's context and deleted lines. This allows inspecting
+    /// what a patch expects without having access to the file it applies to.
+    ///
+    /// ```
+    /// use diffy::create_patch;
+    ///
+    /// let original = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+    /// let modified = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n";
+    ///
+    /// let patch = create_patch(original, modified);
+    /// let pre_image = patch.pre_image();
+    /// assert_eq!(pre_image.len(), 1);
+    /// assert_eq!(pre_image[0].0.range(), 2..9);
+    /// assert_eq!(pre_image[0].1, "2\n3\n4\n5\n6\n7\n8\n");
+    /// ```
+    pub fn pre_image(&self) -> Vec<(HunkRange, String)> {
+        self.hunks
+            .iter()
+            .map(|hunk| (hunk.old_range(), hunk.old_lines().collect()))
+            .collect()
+    }
+
+    /// Returns the text each hunk produces in the modified file, along with the range of the
+    /// modified file it covers
+    ///
+    /// Each fragment is made up of a hunk's context and inserted lines. See [`pre_image`] for
+    /// more details.
+    ///
+    /// [`pre_image`]: #method.pre_image
+    pub fn post_image(&self) -> Vec<(HunkRange, String)> {
+        self.hunks
+            .iter()
+            .map(|hunk| (hunk.new_range(), hunk.new_lines().collect()))
+            .collect()
+    }
+
+    /// Returns the entire original file if the patch's hunks cover all of it
+    ///
+    /// This is the case for patches which create a file, or which were created with enough
+    /// context to include every line of the file. `None` is returned if the hunks don't start
+    /// at the first line of the file or leave gaps between them.
+    ///
+    /// A patch doesn't record whether any lines follow its last hunk, so it's up to the caller
+    /// to know that the patch was created with enough context to reach the end of the file.
+    ///
+    /// ```
+    /// use diffy::DiffOptions;
+    ///
+    /// let original = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+    /// let modified = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n";
+    ///
+    /// let patch = DiffOptions::new()
+    ///     .set_context_len(usize::MAX)
+    ///     .create_patch(original, modified);
+    /// assert_eq!(patch.whole_pre_image().as_deref(), Some(original));
+    /// assert_eq!(patch.whole_post_image().as_deref(), Some(modified));
+    /// ```
+    pub fn whole_pre_image(&self) -> Option<String> {
+        self.covers_whole_file(Hunk::old_range)
+            .then(|| self.hunks.iter().flat_map(Hunk::old_lines).collect())
+    }
+
+    /// Returns the entire modified file if the patch's hunks cover all of it
+    ///
+    /// See [`whole_pre_image`] for when this is the case.
+    ///
+    /// [`whole_pre_image`]: #method.whole_pre_image
+    pub fn whole_post_image(&self) -> Option<String> {
+        self.covers_whole_file(Hunk::new_range)
+            .then(|| self.hunks.iter().flat_map(Hunk::new_lines).collect())
+    }
 }
 
 impl<'a> Patch<'a, [u8]> {
@@ -108,6 +619,68 @@ impl<'a> Patch<'a, [u8]> {
     pub fn from_bytes(s: &'a [u8]) -> Result<Patch<'a, [u8]>, ParsePatchError> {
         parse::parse_bytes(s)
     }
+
+    /// Returns the potentially non-utf8 text each hunk expects to find in the original file,
+    /// along with the range of the original file it covers
+    ///
+    /// See [`Patch::pre_image`](struct.Patch.html#method.pre_image) for more details.
+    pub fn pre_image(&self) -> Vec<(HunkRange, Vec<u8>)> {
+        self.hunks
+            .iter()
+            .map(|hunk| {
+                (
+                    hunk.old_range(),
+                    hunk.old_lines().flatten().copied().collect(),
+                )
+            })
+            .collect()
+    }
+
+    /// Returns the potentially non-utf8 text each hunk produces in the modified file, along
+    /// with the range of the modified file it covers
+    ///
+    /// See [`Patch::post_image`](struct.Patch.html#method.post_image) for more details.
+    pub fn post_image(&self) -> Vec<(HunkRange, Vec<u8>)> {
+        self.hunks
+            .iter()
+            .map(|hunk| {
+                (
+                    hunk.new_range(),
+                    hunk.new_lines().flatten().copied().collect(),
+                )
+            })
+            .collect()
+    }
+
+    /// Returns the entire original file if the patch's hunks cover all of it
+    ///
+    /// See [`Patch::whole_pre_image`](struct.Patch.html#method.whole_pre_image) for more
+    /// details.
+    pub fn whole_pre_image(&self) -> Option<Vec<u8>> {
+        self.covers_whole_file(Hunk::old_range).then(|| {
+            self.hunks
+                .iter()
+                .flat_map(Hunk::old_lines)
+                .flatten()
+                .copied()
+                .collect()
+        })
+    }
+
+    /// Returns the entire modified file if the patch's hunks cover all of it
+    ///
+    /// See [`Patch::whole_pre_image`](struct.Patch.html#method.whole_pre_image) for more
+    /// details.
+    pub fn whole_post_image(&self) -> Option<Vec<u8>> {
+        self.covers_whole_file(Hunk::new_range).then(|| {
+            self.hunks
+                .iter()
+                .flat_map(Hunk::new_lines)
+                .flatten()
+                .copied()
+                .collect()
+        })
+    }
 }
 
 impl<T: ToOwned + ?Sized> Clone for Patch<'_, T> {
@@ -116,6 +689,8 @@ impl<T: ToOwned + ?Sized> Clone for Patch<'_, T> {
             original: self.original.clone(),
             modified: self.modified.clone(),
             hunks: self.hunks.clone(),
+            status: self.status,
+            mode: self.mode,
         }
     }
 }
@@ -129,13 +704,15 @@ impl fmt::Display for Patch<'_, str> {
 impl<T: ?Sized, O> fmt::Debug for Patch<'_, T>
 where
     T: ToOwned<Owned = O> + fmt::Debug,
-    O: std::borrow::Borrow<T> + fmt::Debug,
+    O: core::borrow::Borrow<T> + fmt::Debug,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Patch")
             .field("original", &self.original)
             .field("modified", &self.modified)
             .field("hunks", &self.hunks)
+            .field("status", &self.status)
+            .field("mode", &self.mode)
             .finish()
     }
 }
@@ -144,7 +721,7 @@ where
 struct Filename<'a, T: ToOwned + ?Sized>(Cow<'a, T>);
 
 const ESCAPED_CHARS: &[char] = &['\n', '\t', '\0', '\r', '\"', '\\'];
-const ESCAPED_CHARS_BYTES: &[u8] = &[b'\n', b'\t', b'\0', b'\r', b'\"', b'\\'];
+const ESCAPED_CHARS_BYTES: &[u8] = b"\n\t\0\r\"\\";
 
 impl Filename<'_, str> {
     fn needs_to_be_escaped(&self) -> bool {
@@ -161,7 +738,7 @@ impl<T: ToOwned + AsRef<[u8]> + ?Sized> Filename<'_, T> {
             .any(|b| ESCAPED_CHARS_BYTES.contains(b))
     }
 
-    fn write_into<W: std::io::Write>(&self, mut w: W) -> std::io::Result<()> {
+    fn write_into<W: format::WriteBytes>(&self, mut w: W) -> Result<(), W::Error> {
         if self.needs_to_be_escaped_bytes() {
             w.write_all(b"\"")?;
             for b in self.0.as_ref().as_ref() {
@@ -200,8 +777,8 @@ impl<T: ToOwned + ?Sized> Clone for Filename<'_, T> {
 }
 
 impl fmt::Display for Filename<'_, str> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        use std::fmt::Write;
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use core::fmt::Write;
         if self.needs_to_be_escaped() {
             f.write_char('\"')?;
             for c in self.0.chars() {
@@ -222,7 +799,7 @@ impl fmt::Display for Filename<'_, str> {
 impl<T: ?Sized, O> fmt::Debug for Filename<'_, T>
 where
     T: ToOwned<Owned = O> + fmt::Debug,
-    O: std::borrow::Borrow<T> + fmt::Debug,
+    O: core::borrow::Borrow<T> + fmt::Debug,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_tuple("Filename").field(&self.0).finish()
@@ -241,10 +818,11 @@ pub struct Hunk<'a, T: ?Sized> {
 }
 
 fn hunk_lines_count<T: ?Sized>(lines: &[Line<'_, T>]) -> (usize, usize) {
-    lines.iter().fold((0, 0), |count, line| match line {
-        Line::Context(_) => (count.0 + 1, count.1 + 1),
-        Line::Delete(_) => (count.0 + 1, count.1),
-        Line::Insert(_) => (count.0, count.1 + 1),
+    lines.iter().fold((0, 0), |(old, new), line| {
+        (
+            old + usize::from(!line.is_insert()),
+            new + usize::from(!line.is_delete()),
+        )
     })
 }
 
@@ -288,6 +866,90 @@ impl<'a, T: ?Sized> Hunk<'a, T> {
         &self.lines
     }
 
+    /// Returns the lines of the old file covered by this hunk, i.e. its context and deleted
+    /// lines
+    ///
+    /// This always yields exactly `old_range().len()` lines.
+    pub fn old_lines(&self) -> impl Iterator<Item = &'a T> + '_ {
+        self.lines
+            .iter()
+            .filter(|line| !line.is_insert())
+            .map(Line::value)
+    }
+
+    /// Returns the lines of the new file covered by this hunk, i.e. its context and inserted
+    /// lines
+    ///
+    /// This always yields exactly `new_range().len()` lines.
+    pub fn new_lines(&self) -> impl Iterator<Item = &'a T> + '_ {
+        self.lines
+            .iter()
+            .filter(|line| !line.is_delete())
+            .map(Line::value)
+    }
+
+    // Splits the hunk into one hunk per group of changes, dropping all context lines
+    fn strip_context(&self) -> Vec<Self> {
+        let to_hunk = |old_start: usize, new_start: usize, lines: Vec<Line<'a, T>>| {
+            let (old_len, new_len) = hunk_lines_count(&lines);
+            Self::new(
+                HunkRange::from(old_start..old_start + old_len),
+                HunkRange::from(new_start..new_start + new_len),
+                self.function_context,
+                lines,
+            )
+        };
+
+        // 0-based indices of the next old and new line
+        let mut hunks = Vec::new();
+        let mut old = self.old_range.to_std_range().start;
+        let mut new = self.new_range.to_std_range().start;
+        let mut group: Option<(usize, usize, Vec<Line<'a, T>>)> = None;
+
+        for &line in &self.lines {
+            if line.is_context() {
+                if let Some((old_start, new_start, lines)) = group.take() {
+                    hunks.push(to_hunk(old_start, new_start, lines));
+                }
+            } else {
+                group
+                    .get_or_insert_with(|| (old, new, Vec::new()))
+                    .2
+                    .push(line);
+            }
+            old += usize::from(!line.is_insert());
+            new += usize::from(!line.is_delete());
+        }
+        if let Some((old_start, new_start, lines)) = group {
+            hunks.push(to_hunk(old_start, new_start, lines));
+        }
+
+        hunks
+    }
+
+    // Annotates each line of the hunk with its line numbers, see `Patch::annotated_lines`
+    fn annotated_lines<'p>(
+        &'p self,
+        hunk_index: usize,
+    ) -> impl Iterator<Item = AnnotatedLine<'p, 'a, T>> + 'p {
+        let mut old = self.old_range.to_std_range().start + 1;
+        let mut new = self.new_range.to_std_range().start + 1;
+
+        self.lines.iter().map(move |line| {
+            let old_lineno = (!line.is_insert()).then_some(old);
+            let new_lineno = (!line.is_delete()).then_some(new);
+            old += usize::from(old_lineno.is_some());
+            new += usize::from(new_lineno.is_some());
+
+            AnnotatedLine {
+                hunk_index,
+                old_lineno,
+                new_lineno,
+                line,
+            }
+        })
+    }
+
     /// Creates a reverse patch for the hunk.  This is equivalent to what
     /// XDL_PATCH_REVERSE would apply in libxdiff.
     pub fn reverse(&self) -> Self {
@@ -301,6 +963,37 @@ impl<'a, T: ?Sized> Hunk<'a, T> {
     }
 }
 
+impl<T: AsRef<[u8]> + ?Sized> Hunk<'_, T> {
+    /// Convert a `Hunk`, including its `@@ ... @@` header, into bytes
+    ///
+    /// This is the equivalent of the `to_string` function but for potentially non-utf8 hunks.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        PatchFormatter::new().write_hunk_into_vec(self, &mut bytes);
+        bytes
+    }
+
+    /// Returns a `Display` impl which prints the hunk, replacing any invalid utf8 sequences
+    /// with `U+FFFD REPLACEMENT CHARACTER`
+    pub fn display_lossy(&self) -> impl fmt::Display {
+        HunkDisplayLossy(self.to_bytes())
+    }
+}
+
+struct HunkDisplayLossy(Vec<u8>);
+
+impl fmt::Display for HunkDisplayLossy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&alloc::string::String::from_utf8_lossy(&self.0))
+    }
+}
+
+impl fmt::Display for Hunk<'_, str> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", PatchFormatter::new().fmt_hunk(self))
+    }
+}
+
 impl<T: ?Sized> Clone for Hunk<'_, T> {
     fn clone(&self) -> Self {
         Self {
@@ -313,6 +1006,13 @@ impl<T: ?Sized> Clone for Hunk<'_, T> {
 }
 
 /// The range of lines in a file for a particular `Hunk`.
+///
+/// Line numbers are 1-based, following the unified diff format. An empty range doesn't cover
+/// any lines, so by convention its start is the line *preceding* the position it refers to,
+/// e.g. a hunk which only inserts lines at the top of a file has an old range of `0,0`. Use
+/// [`to_std_range`] to convert to 0-based indices without having to handle this case.
+///
+/// [`to_std_range`]: #method.to_std_range
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct HunkRange {
     /// The starting line number of a hunk
@@ -350,6 +1050,90 @@ impl HunkRange {
     pub fn is_empty(&self) -> bool {
         self.len == 0
     }
+
+    /// Returns `true` if the 1-based `line` is one of the lines covered by the range
+    ///
+    /// An empty range doesn't contain any lines.
+    pub fn contains(&self, line: usize) -> bool {
+        self.range().contains(&line)
+    }
+
+    /// Returns `true` if the two ranges have at least one line in common
+    ///
+    /// An empty range doesn't intersect any other range.
+    pub fn intersects(&self, other: &HunkRange) -> bool {
+        !self.is_empty()
+            && !other.is_empty()
+            && self.start < other.end()
+            && other.start < self.end()
+    }
+
+    /// Returns a copy of the range moved by `delta` lines
+    ///
+    /// The start saturates at the beginning of the file: line `1` for a non-empty range and
+    /// line `0` for an empty one, so both end up at index `0` of [`to_std_range`]. It also
+    /// saturates at `usize::MAX` when moving forward.
+    ///
+    /// ```
+    /// use diffy::HunkRange;
+    ///
+    /// let range = HunkRange::from(4..6);
+    /// assert_eq!(range.shift(2), HunkRange::from(6..8));
+    /// assert_eq!(range.shift(-10), HunkRange::from(0..2));
+    /// ```
+    ///
+    /// [`to_std_range`]: #method.to_std_range
+    pub fn shift(&self, delta: isize) -> HunkRange {
+        let start = if delta < 0 {
+            self.start.saturating_sub(delta.unsigned_abs())
+        } else {
+            self.start.saturating_add(delta.unsigned_abs())
+        };
+        let min = if self.is_empty() { 0 } else { 1 };
+        HunkRange::new(cmp::max(start, min), self.len)
+    }
+
+    /// Returns the 0-based indices of the lines covered by the range
+    ///
+    /// For an empty range this is the empty range positioned where lines would be inserted,
+    /// i.e. right after the line its start refers to.
+    ///
+    /// ```
+    /// use diffy::HunkRange;
+    ///
+    /// let patch = diffy::create_patch("a\nb\n", "a\nb\nc\n");
+    /// let hunk = &patch.hunks()[0];
+    /// assert_eq!(hunk.old_range().to_std_range(), 0..2);
+    /// assert_eq!(hunk.new_range().to_std_range(), 0..3);
+    ///
+    /// let insertion = diffy::DiffOptions::new()
+    ///     .set_context_len(0)
+    ///     .create_patch("a\nb\n", "a\nb\nc\n");
+    /// assert_eq!(insertion.hunks()[0].old_range().to_std_range(), 2..2);
+    /// ```
+    pub fn to_std_range(&self) -> ops::Range<usize> {
+        let start = if self.is_empty() {
+            self.start
+        } else {
+            self.start.saturating_sub(1)
+        };
+        start..start + self.len
+    }
+}
+
+/// Converts 0-based line indices into a `HunkRange`, the inverse of [`HunkRange::to_std_range`]
+///
+/// [`HunkRange::to_std_range`]: struct.HunkRange.html#method.to_std_range
+impl From<ops::Range<usize>> for HunkRange {
+    fn from(range: ops::Range<usize>) -> Self {
+        let len = range.end.saturating_sub(range.start);
+        let start = if len > 0 {
+            range.start + 1
+        } else {
+            range.start
+        };
+        HunkRange::new(start, len)
+    }
 }
 
 impl fmt::Display for HunkRange {
@@ -384,12 +1168,1327 @@ impl<T: ?Sized> Clone for Line<'_, T> {
     }
 }
 
-impl<T: ?Sized> Line<'_, T> {
+impl<'a, T: ?Sized> Line<'a, T> {
+    /// Construct a `Line` of the given kind
+    pub fn new(kind: LineKind, value: &'a T) -> Self {
+        match kind {
+            LineKind::Context => Line::Context(value),
+            LineKind::Delete => Line::Delete(value),
+            LineKind::Insert => Line::Insert(value),
+        }
+    }
+
+    /// Returns the contents of the line, regardless of its kind
+    pub fn value(&self) -> &'a T {
+        match self {
+            Line::Context(value) | Line::Delete(value) | Line::Insert(value) => value,
+        }
+    }
+
+    /// Returns the kind of the line
+    pub fn kind(&self) -> LineKind {
+        match self {
+            Line::Context(_) => LineKind::Context,
+            Line::Delete(_) => LineKind::Delete,
+            Line::Insert(_) => LineKind::Insert,
+        }
+    }
+
+    /// Returns `true` if this is a context line
+    pub fn is_context(&self) -> bool {
+        self.kind() == LineKind::Context
+    }
+
+    /// Returns `true` if this is a deleted line
+    pub fn is_delete(&self) -> bool {
+        self.kind() == LineKind::Delete
+    }
+
+    /// Returns `true` if this is an inserted line
+    pub fn is_insert(&self) -> bool {
+        self.kind() == LineKind::Insert
+    }
+
+    /// Returns the character the line is prefixed with in a unified diff
+    pub fn prefix_char(&self) -> char {
+        self.kind().prefix_char()
+    }
+
+    /// Converts the contents of the line while keeping its kind
+    ///
+    /// ```
+    /// use diffy::Line;
+    ///
+    /// let line = Line::Insert("foo\n");
+    /// assert_eq!(line.map(str::as_bytes), Line::Insert(&b"foo\n"[..]));
+    /// ```
+    pub fn map<'b, U: ?Sized>(&self, f: impl FnOnce(&'a T) -> &'b U) -> Line<'b, U> {
+        Line::new(self.kind(), f(self.value()))
+    }
+
     pub fn reverse(&self) -> Self {
+        let kind = match self.kind() {
+            LineKind::Context => LineKind::Context,
+            LineKind::Delete => LineKind::Insert,
+            LineKind::Insert => LineKind::Delete,
+        };
+        Line::new(kind, self.value())
+    }
+}
+
+/// The kind of a [`Line`], without its contents
+///
+/// [`Line`]: enum.Line.html
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LineKind {
+    /// A line present in both the old and new file
+    Context,
+    /// A line deleted from the old file
+    Delete,
+    /// A line inserted to the new file
+    Insert,
+}
+
+impl LineKind {
+    /// Returns the character lines of this kind are prefixed with in a unified diff
+    pub fn prefix_char(self) -> char {
         match self {
-            Line::Context(s) => Line::Context(s),
-            Line::Delete(s) => Line::Insert(s),
-            Line::Insert(s) => Line::Delete(s),
+            LineKind::Context => ' ',
+            LineKind::Delete => '-',
+            LineKind::Insert => '+',
+        }
+    }
+
+    /// Returns the kind of line prefixed by `c` in a unified diff, if any
+    pub fn from_prefix_char(c: char) -> Option<Self> {
+        match c {
+            ' ' => Some(LineKind::Context),
+            '-' => Some(LineKind::Delete),
+            '+' => Some(LineKind::Insert),
+            _ => None,
         }
     }
 }
+
+/// A [`Line`] of a patch along with its position, as returned by [`Patch::annotated_lines`]
+///
+/// [`Line`]: enum.Line.html
+/// [`Patch::annotated_lines`]: struct.Patch.html#method.annotated_lines
+#[derive(Debug, PartialEq, Eq)]
+pub struct AnnotatedLine<'p, 'a, T: ?Sized> {
+    /// The 0-based index of the hunk containing the line
+    pub hunk_index: usize,
+    /// The 1-based line number in the old file, if the line is present in it
+    pub old_lineno: Option<usize>,
+    /// The 1-based line number in the new file, if the line is present in it
+    pub new_lineno: Option<usize>,
+    /// The line itself
+    pub line: &'p Line<'a, T>,
+}
+
+impl<T: ?Sized> Copy for AnnotatedLine<'_, '_, T> {}
+
+impl<T: ?Sized> Clone for AnnotatedLine<'_, '_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        FileStatus, Hunk, HunkDiff, HunkRange, Line, LineKind, MergeHunksError, Patch,
+        PatchFormatter, PatchUtf8Error, PrefixPolicy, SortKey,
+    };
+    use crate::{apply, apply_bytes, create_patch, create_patch_bytes, DiffOptions};
+    use alloc::{string::ToString, vec::Vec};
+
+    #[test]
+    fn hunk_separator() {
+        let original = "a\nb\nc\nd\ne\nf\ng\nh\n";
+        let modified = "A\nb\nC\nd\ne\nf\nG\nh\n";
+        let mut opts = DiffOptions::new();
+        opts.set_context_len(1).set_merge_hunks(false);
+        let patch = opts.create_patch(original, modified);
+        assert_eq!(patch.hunks().len(), 3);
+
+        // Only the last two hunks have a line between them
+        let expected = "\
+--- original
++++ modified
+@@ -1,2 +1,2 @@
+-a
++A
+ b
+@@ -3,2 +3,2 @@
+-c
++C
+ d
+~~~
+@@ -6,3 +6,3 @@
+ f
+-g
++G
+ h
+";
+        let f = PatchFormatter::new().hunk_separator("~~~\n");
+        assert_eq!(f.fmt_patch(&patch).to_string(), expected);
+        let mut bytes = Vec::new();
+        f.write_patch_into_vec(&patch, &mut bytes);
+        assert_eq!(bytes, expected.as_bytes());
+
+        // A single hunk is never separated
+        let patch = create_patch("a\n", "b\n");
+        assert_eq!(f.fmt_patch(&patch).to_string(), patch.to_string());
+
+        // Hunks are separated by the lines between them even when one of them is empty
+        opts.set_context_len(0);
+        let patch = opts.create_patch("a\nb\nc\nd\n", "a\nc\nX\nd\n");
+        let expected = "\
+--- original
++++ modified
+@@ -2 +1,0 @@
+-b
+~~~
+@@ -3,0 +3 @@
++X
+";
+        assert_eq!(f.fmt_patch(&patch).to_string(), expected);
+    }
+
+    #[test]
+    fn collapse_inner_context() {
+        let original = "c1\nc2\nc3\nx\nd1\nd2\nd3\ny\ne1\ne2\ne3\ne4\ne5\ne6\nz\nf1\nf2\nf3\n";
+        let modified = original
+            .replace('x', "X")
+            .replace('y', "Y")
+            .replace('z', "Z");
+        let patch = create_patch(original, &modified);
+        assert_eq!(patch.hunks().len(), 1);
+
+        // The context around the changes is kept, as are runs which would only hide one line
+        let expected = "\
+--- original
++++ modified
+@@ -1,18 +1,18 @@
+ c1
+ c2
+ c3
+-x
++X
+ d1
+ d2
+ d3
+-y
++Y
+ e1
+…
+ e6
+-z
++Z
+ f1
+ f2
+ f3
+";
+        let f = PatchFormatter::new().collapse_inner_context(1);
+        assert_eq!(f.fmt_patch(&patch).to_string(), expected);
+        let mut bytes = Vec::new();
+        f.write_patch_into_vec(&patch, &mut bytes);
+        assert_eq!(bytes, expected.as_bytes());
+
+        let expected = "\
+--- original
++++ modified
+@@ -1,18 +1,18 @@
+ c1
+ c2
+ c3
+-x
++X
+…
+-y
++Y
+…
+-z
++Z
+ f1
+ f2
+ f3
+";
+        let f = PatchFormatter::new().collapse_inner_context(0);
+        assert_eq!(f.fmt_patch(&patch).to_string(), expected);
+
+        let f = PatchFormatter::new().collapse_inner_context(3);
+        assert_eq!(f.fmt_patch(&patch).to_string(), patch.to_string());
+        let f = PatchFormatter::new().collapse_inner_context(usize::MAX);
+        assert_eq!(f.fmt_patch(&patch).to_string(), patch.to_string());
+    }
+
+    #[test]
+    fn line_kinds_match_formatter() {
+        for kind in [LineKind::Context, LineKind::Delete, LineKind::Insert] {
+            assert_eq!(LineKind::from_prefix_char(kind.prefix_char()), Some(kind));
+
+            let line = Line::new(kind, "a\n");
+            assert_eq!(line.kind(), kind);
+            assert_eq!(line.value(), "a\n");
+            assert_eq!(line.prefix_char(), kind.prefix_char());
+            assert_eq!(line.map(str::as_bytes).kind(), kind);
+            assert_eq!(line.map(str::as_bytes).value(), b"a\n");
+            assert_eq!(
+                [line.is_context(), line.is_delete(), line.is_insert()]
+                    .iter()
+                    .filter(|&&b| b)
+                    .count(),
+                1
+            );
+        }
+        assert_eq!(LineKind::from_prefix_char('@'), None);
+        assert!(Line::Context("").is_context());
+        assert!(Line::Delete("").is_delete());
+        assert!(Line::Insert("").is_insert());
+        assert_eq!(Line::Delete("a").reverse(), Line::Insert("a"));
+
+        let patch = create_patch("a\n\nb\nc\nd\n", "a\n\nB\nc\nd\ne");
+        let lines: Vec<_> = patch.hunks().iter().flat_map(|h| h.lines()).collect();
+        let rendered = patch.to_string();
+        let rendered: Vec<_> = rendered
+            .lines()
+            .filter(|l| !l.starts_with("---") && !l.starts_with("+++") && !l.starts_with("@@"))
+            .filter(|l| !l.starts_with('\\'))
+            .collect();
+        assert_eq!(lines.len(), rendered.len());
+
+        for (line, rendered) in lines.iter().zip(rendered) {
+            match rendered.chars().next() {
+                Some(c) => {
+                    assert_eq!(c, line.prefix_char());
+                    assert_eq!(&rendered[1..], line.value().trim_end_matches('\n'));
+                }
+                // Empty context lines are rendered without their prefix
+                None => assert_eq!(**line, Line::Context("\n")),
+            }
+        }
+    }
+
+    #[test]
+    fn hunk_range_conversions() {
+        // Non-empty ranges are 1-based
+        let range = HunkRange::new(3, 2);
+        assert_eq!(range.to_std_range(), 2..4);
+        assert_eq!(HunkRange::from(2..4), range);
+        assert_eq!(range.range(), 3..5);
+        assert_eq!(range.end(), 5);
+
+        // Empty ranges start at the line preceding them
+        let range = HunkRange::new(3, 0);
+        assert_eq!(range.to_std_range(), 3..3);
+        assert_eq!(HunkRange::from(3..3), range);
+        let range = HunkRange::new(0, 0);
+        assert_eq!(range.to_std_range(), 0..0);
+        assert_eq!(HunkRange::from(0..0), range);
+
+        // The first line of the file
+        let range = HunkRange::new(1, 1);
+        assert_eq!(range.to_std_range(), 0..1);
+        assert_eq!(HunkRange::from(0..1), range);
+
+        #[allow(clippy::reversed_empty_ranges)]
+        let reversed = HunkRange::from(4..2);
+        assert_eq!(reversed, HunkRange::new(4, 0));
+
+        for start in 0..4 {
+            for end in start..6 {
+                assert_eq!(HunkRange::from(start..end).to_std_range(), start..end);
+            }
+        }
+    }
+
+    #[test]
+    fn hunk_range_contains_and_intersects() {
+        let range = HunkRange::new(3, 2);
+        assert!(!range.contains(2));
+        assert!(range.contains(3));
+        assert!(range.contains(4));
+        assert!(!range.contains(5));
+        assert!(!HunkRange::new(3, 0).contains(3));
+        assert!(!HunkRange::new(0, 0).contains(0));
+
+        assert!(range.intersects(&range));
+        assert!(range.intersects(&HunkRange::new(4, 5)));
+        assert!(range.intersects(&HunkRange::new(1, 3)));
+        assert!(HunkRange::new(1, 10).intersects(&range));
+        assert!(!range.intersects(&HunkRange::new(5, 1)));
+        assert!(!range.intersects(&HunkRange::new(1, 2)));
+
+        // Empty ranges don't have any lines to share
+        assert!(!range.intersects(&HunkRange::new(3, 0)));
+        assert!(!HunkRange::new(3, 0).intersects(&range));
+        assert!(!HunkRange::new(3, 0).intersects(&HunkRange::new(3, 0)));
+    }
+
+    #[test]
+    fn hunk_range_shift() {
+        let range = HunkRange::new(5, 2);
+        assert_eq!(range.shift(0), range);
+        assert_eq!(range.shift(3), HunkRange::new(8, 2));
+        assert_eq!(range.shift(-4), HunkRange::new(1, 2));
+        assert_eq!(range.shift(-5), HunkRange::new(1, 2));
+        assert_eq!(range.shift(isize::MIN), HunkRange::new(1, 2));
+        assert_eq!(
+            HunkRange::new(usize::MAX - 1, 0).shift(isize::MAX),
+            HunkRange::new(usize::MAX, 0)
+        );
+
+        let empty = HunkRange::new(5, 0);
+        assert_eq!(empty.shift(-5), HunkRange::new(0, 0));
+        assert_eq!(empty.shift(-6), HunkRange::new(0, 0));
+        assert_eq!(empty.shift(-6).to_std_range(), 0..0);
+        assert_eq!(range.shift(-6).to_std_range(), 0..2);
+    }
+
+    #[test]
+    fn added_and_removed_files() {
+        let text = "a\nb\n";
+
+        let patch = create_patch("", text);
+        let added = Patch::new(Some("a/file"), Some("b/file"), patch.hunks.clone())
+            .with_status(FileStatus::Added);
+        let expected = "\
+new file mode 100644
+--- /dev/null
++++ b/file
+@@ -0,0 +1,2 @@
++a
++b
+";
+        assert_eq!(added.to_string(), expected);
+        assert_eq!(added.status(), FileStatus::Added);
+
+        let parsed = Patch::from_str(expected).unwrap();
+        assert_eq!(parsed.status(), FileStatus::Added);
+        assert_eq!(parsed.original(), Some("/dev/null"));
+        assert_eq!(parsed.to_string(), expected);
+        assert_eq!(apply("", &parsed).unwrap(), text);
+
+        let removed = added.reverse();
+        let expected = "\
+deleted file mode 100644
+--- b/file
++++ /dev/null
+@@ -1,2 +0,0 @@
+-a
+-b
+";
+        assert_eq!(removed.status(), FileStatus::Removed);
+        assert_eq!(removed.to_string(), expected);
+
+        let parsed = Patch::from_str(expected).unwrap();
+        assert_eq!(parsed.status(), FileStatus::Removed);
+        assert_eq!(parsed.modified(), Some("/dev/null"));
+        assert_eq!(apply(text, &parsed).unwrap(), "");
+
+        let bytes = create_patch_bytes(text.as_bytes(), b"").with_status(FileStatus::Removed);
+        let expected = "\
+deleted file mode 100644
+--- original
++++ /dev/null
+@@ -1,2 +0,0 @@
+-a
+-b
+";
+        assert_eq!(bytes.to_bytes(), expected.as_bytes());
+        let parsed = Patch::from_bytes(expected.as_bytes()).unwrap();
+        assert_eq!(parsed.status(), FileStatus::Removed);
+
+        // The mode of an added or removed file is kept through parsing and formatting
+        let expected = "\
+diff --git a/run.sh b/run.sh
+new file mode 100755
+index 0000000..e69de29
+--- /dev/null
++++ b/run.sh
+@@ -0,0 +1 @@
++a
+";
+        let parsed = Patch::from_str(expected).unwrap();
+        assert_eq!(parsed.status(), FileStatus::Added);
+        assert_eq!(parsed.mode(), 0o100755);
+        assert!(parsed.to_string().starts_with("new file mode 100755\n"));
+        let reversed = parsed.reverse();
+        assert_eq!(reversed.mode(), 0o100755);
+        assert!(reversed
+            .to_string()
+            .starts_with("deleted file mode 100755\n"));
+        let parsed = Patch::from_bytes(expected.as_bytes()).unwrap();
+        assert_eq!(parsed.mode(), 0o100755);
+        assert!(parsed.to_bytes().starts_with(b"new file mode 100755\n"));
+        assert_eq!(
+            Patch::from_str("deleted file mode 12x\n--- a\n+++ /dev/null\n")
+                .unwrap()
+                .mode(),
+            0o100644
+        );
+
+        // Modified files keep their names and have no mode header
+        let modified = create_patch("a\n", "b\n");
+        assert_eq!(modified.status(), FileStatus::Modified);
+        assert!(modified
+            .to_string()
+            .starts_with("--- original\n+++ modified\n"));
+    }
+
+    #[test]
+    fn wrap_long_lines() {
+        let original = "ctx\nold\n";
+        let modified = "ctx\nnäïve ünïcödé\n";
+        let patch = create_patch(original, modified);
+
+        let f = PatchFormatter::new().wrap_width(4);
+        let expected = "\
+--- original
++++ modified
+@@ -1,2 +1,2 @@
+ ctx
+-old
++näïv
++e ün
++ïcöd
++é
+";
+        assert_eq!(f.fmt_patch(&patch).to_string(), expected);
+
+        let bytes = create_patch_bytes(original.as_bytes(), modified.as_bytes());
+        let mut out = Vec::new();
+        f.write_patch_into_vec(&bytes, &mut out);
+        assert_eq!(out, expected.as_bytes());
+
+        // The last wrapped line keeps the missing newline of the line it was split from
+        let patch = create_patch("", "abcde");
+        let expected = "\
+--- original
++++ modified
+@@ -0,0 +1 @@
++abc
++de
+\\ No newline at end of file
+";
+        let f = PatchFormatter::new().wrap_width(3);
+        assert_eq!(f.fmt_patch(&patch).to_string(), expected);
+
+        // Lines which fit, including ones exactly as long as the width, aren't wrapped
+        let f = PatchFormatter::new().wrap_width(5);
+        assert_eq!(f.fmt_patch(&patch).to_string(), patch.to_string());
+        let f = PatchFormatter::new().wrap_width(0);
+        assert_eq!(f.fmt_patch(&patch).to_string(), patch.to_string());
+    }
+
+    #[test]
+    fn patch_bytes() {
+        let original = b"ctx\n\xff\xfeold\nend\n";
+        let modified = b"ctx\n\xff\xfenew\nend";
+        let patch = create_patch_bytes(original, modified);
+        let expected = b"\
+--- original
++++ modified
+@@ -1,3 +1,3 @@
+ ctx
+-\xff\xfeold
+-end
++\xff\xfenew
++end
+\\ No newline at end of file
+";
+        assert_eq!(PatchFormatter::new().patch_bytes(&patch), expected);
+        assert_eq!(patch.to_bytes(), expected);
+
+        // Colored output only wraps the lines in escape codes
+        let colored = PatchFormatter::new().with_color().patch_bytes(&patch);
+        assert!(colored.windows(10).any(|w| w == b"-\xff\xfeold\n\x1b[0"));
+
+        // Patches between strs produce the same output as their `Display` impl
+        let patch = create_patch("a\nb\n", "a\nc\n");
+        for f in [PatchFormatter::new(), PatchFormatter::new().with_color()] {
+            assert_eq!(
+                f.patch_bytes(&patch),
+                f.fmt_patch(&patch).to_string().as_bytes()
+            );
+        }
+    }
+
+    #[test]
+    fn rendered_size() {
+        let patch = create_patch("a\nb\nc\nd\ne\nf\ng\nh\n", "a\nB\nc\nd\ne\nf\ng\nH");
+        let formatters = [
+            PatchFormatter::new(),
+            PatchFormatter::new().with_color(),
+            PatchFormatter::new()
+                .with_color()
+                .with_intraline_highlight(true),
+            PatchFormatter::new().hunk_separator("~~~\n").wrap_width(1),
+            PatchFormatter::new().with_line_renderer(|_, text, w| write!(w, "<{}>", text)),
+        ];
+        for f in &formatters {
+            assert_eq!(
+                patch.rendered_size(f),
+                f.fmt_patch(&patch).to_string().len()
+            );
+        }
+
+        let patch = create_patch_bytes(b"caf\xe9\n", b"cafe\n");
+        for f in &formatters {
+            assert_eq!(patch.rendered_size(f), f.patch_bytes(&patch).len());
+        }
+        assert_eq!(
+            Patch::from_str("").unwrap().rendered_size(&formatters[0]),
+            0
+        );
+    }
+
+    #[test]
+    fn intraline_highlight() {
+        let original =
+            "The quick brown fox jumps over the lazy dog and keeps running far away\nend\n";
+        let modified =
+            "The quick brown cat jumps over the lazy dog and keeps running far away\nend\n";
+        let patch = create_patch(original, modified);
+
+        let f = PatchFormatter::new()
+            .with_color()
+            .with_intraline_highlight(true);
+        let expected = concat!(
+            "\u{1b}[1m--- original\n+++ modified\n\u{1b}[0m",
+            "\u{1b}[36m@@ -1,2 +1,2 @@\u{1b}[0m\n",
+            "\u{1b}[31m-The quick brown \u{1b}[0m\u{1b}[7;31mfox\u{1b}[0m",
+            "\u{1b}[31m jumps over the lazy dog and keeps running far away\n\u{1b}[0m",
+            "\u{1b}[32m+The quick brown \u{1b}[0m\u{1b}[7;32mcat\u{1b}[0m",
+            "\u{1b}[32m jumps over the lazy dog and keeps running far away\n\u{1b}[0m",
+            " end\n",
+        );
+        assert_eq!(f.fmt_patch(&patch).to_string(), expected);
+
+        let bytes = create_patch_bytes(original.as_bytes(), modified.as_bytes());
+        let mut out = Vec::new();
+        f.write_patch_into_vec(&bytes, &mut out);
+        assert_eq!(out, expected.as_bytes());
+
+        // Output without color is unchanged
+        let plain = PatchFormatter::new().with_intraline_highlight(true);
+        assert_eq!(plain.fmt_patch(&patch).to_string(), patch.to_string());
+
+        // Lines without a pair, or with nothing in common with theirs, render as usual
+        let patch = create_patch("abc\n", "xyz\nnew\n");
+        let colored = PatchFormatter::new().with_color();
+        assert_eq!(
+            f.fmt_patch(&patch).to_string(),
+            colored.fmt_patch(&patch).to_string()
+        );
+    }
+
+    #[test]
+    fn indentation_split() {
+        let f = PatchFormatter::new()
+            .with_color()
+            .with_indentation_split(true);
+
+        // A reindented and edited line highlights both changes
+        let patch = create_patch("\tlet x = 1;\nend\n", "        let x = 2;\nend\n");
+        let expected = concat!(
+            "\u{1b}[1m--- original\n+++ modified\n\u{1b}[0m",
+            "\u{1b}[36m@@ -1,2 +1,2 @@\u{1b}[0m\n",
+            "\u{1b}[31m-\u{1b}[0m\u{1b}[7;33m\t\u{1b}[0m\u{1b}[31mlet x = \u{1b}[0m",
+            "\u{1b}[7;31m1\u{1b}[0m\u{1b}[31m;\n\u{1b}[0m",
+            "\u{1b}[32m+\u{1b}[0m\u{1b}[7;33m        \u{1b}[0m\u{1b}[32mlet x = \u{1b}[0m",
+            "\u{1b}[7;32m2\u{1b}[0m\u{1b}[32m;\n\u{1b}[0m",
+            " end\n",
+        );
+        assert_eq!(f.fmt_patch(&patch).to_string(), expected);
+
+        let bytes = create_patch_bytes(
+            b"\tlet x = 1;\nend\n".as_slice(),
+            b"        let x = 2;\nend\n".as_slice(),
+        );
+        let mut out = Vec::new();
+        f.write_patch_into_vec(&bytes, &mut out);
+        assert_eq!(out, expected.as_bytes());
+
+        // Plain intraline highlighting mixes the whitespace into the content diff
+        let intraline = PatchFormatter::new()
+            .with_color()
+            .with_intraline_highlight(true);
+        assert_ne!(intraline.fmt_patch(&patch).to_string(), expected);
+
+        // Only reindented, or only edited lines highlight just that part
+        let patch = create_patch("  foo();\n", "    foo();\n");
+        let expected = concat!(
+            "\u{1b}[1m--- original\n+++ modified\n\u{1b}[0m",
+            "\u{1b}[36m@@ -1 +1 @@\u{1b}[0m\n",
+            "\u{1b}[31m-\u{1b}[0m\u{1b}[7;33m  \u{1b}[0m\u{1b}[31mfoo();\n\u{1b}[0m",
+            "\u{1b}[32m+\u{1b}[0m\u{1b}[7;33m    \u{1b}[0m\u{1b}[32mfoo();\n\u{1b}[0m",
+        );
+        assert_eq!(f.fmt_patch(&patch).to_string(), expected);
+
+        let patch = create_patch("  foo(1);\n", "  foo(2);\n");
+        assert_eq!(
+            f.fmt_patch(&patch).to_string(),
+            intraline.fmt_patch(&patch).to_string()
+        );
+
+        // Marking indentation-only lines takes precedence
+        let marked = PatchFormatter::new()
+            .with_color()
+            .mark_indentation_changes();
+        let patch = create_patch("foo();\n", "    foo();\n");
+        assert_eq!(
+            PatchFormatter::new()
+                .with_color()
+                .mark_indentation_changes()
+                .with_indentation_split(true)
+                .fmt_patch(&patch)
+                .to_string(),
+            marked.fmt_patch(&patch).to_string()
+        );
+
+        // Pairs whose content has nothing in common, and output without color, are unaffected
+        let patch = create_patch("abc\n", "  xyz\n");
+        let colored = PatchFormatter::new().with_color();
+        assert_eq!(
+            f.fmt_patch(&patch).to_string(),
+            colored.fmt_patch(&patch).to_string()
+        );
+        let plain = PatchFormatter::new().with_indentation_split(true);
+        assert_eq!(plain.fmt_patch(&patch).to_string(), patch.to_string());
+    }
+
+    #[test]
+    fn canonicalize() {
+        let original = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let modified = "A\nB\nc\nd\ne\nf\ng\nx\nh\ni\n";
+
+        let patch = create_patch(original, modified);
+        let canonical = patch.canonicalize();
+        let expected = "\
+--- original
++++ modified
+@@ -1,2 +1,2 @@
+-a
+-b
++A
++B
+@@ -7,0 +8 @@
++x
+@@ -10 +10,0 @@
+-j
+";
+        assert_eq!(canonical.to_string(), expected);
+        assert_eq!(apply(original, &canonical).unwrap(), modified);
+        assert_eq!(canonical.canonicalize(), canonical);
+
+        // The same change with different amounts of context and without merged hunks
+        let mut opts = DiffOptions::new();
+        opts.set_context_len(0).set_merge_hunks(false);
+        assert_eq!(
+            opts.create_patch(original, modified).canonicalize(),
+            canonical
+        );
+        opts.set_context_len(1).set_merge_hunks(true);
+        assert_eq!(
+            opts.create_patch(original, modified).canonicalize(),
+            canonical
+        );
+
+        // A hand-written patch with interleaved lines, split hunks, wrong new line numbers and
+        // function context, with its hunks put out of order
+        let s = "\
+@@ -1 +1 @@
+-a
++A
+@@ -2 +2 @@
+-b
++B
+@@ -7,0 +9 @@
++x
+@@ -9,2 +20,1 @@ fn foo()
+ i
+-j
+";
+        let parsed = Patch::from_str(s).unwrap();
+        let mut hunks = parsed.hunks().to_vec();
+        hunks.rotate_left(2);
+        let patch = Patch::new(Some("original"), Some("modified"), hunks);
+        assert_eq!(patch.canonicalize(), canonical);
+
+        // A patch without any changes has no hunks
+        let s = "\
+--- original
++++ modified
+@@ -1,2 +1,2 @@
+ a
+ b
+";
+        assert!(Patch::from_str(s)
+            .unwrap()
+            .canonicalize()
+            .hunks()
+            .is_empty());
+    }
+
+    #[test]
+    fn diff_patches() {
+        let original = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+        let old = create_patch(original, "1\ntwo\n3\n4\n5\n6\n7\n8\nnine\n10\n11\n12\n");
+        let new = create_patch(original, "1\ntwo\n3\n4\n5\nsix\n7\n8\n9\n10\n11\nTWELVE\n");
+
+        let hunks = |s: &[Hunk<'_, str>]| s.iter().map(Hunk::to_string).collect::<Vec<_>>();
+        let strings = |diffs: &[HunkDiff<'_, str>]| {
+            diffs
+                .iter()
+                .map(|diff| match diff {
+                    HunkDiff::Added(hunk) => ("+", hunks(core::slice::from_ref(hunk)), Vec::new()),
+                    HunkDiff::Removed(hunk) => {
+                        ("-", hunks(core::slice::from_ref(hunk)), Vec::new())
+                    }
+                    HunkDiff::Changed { old, new } => ("~", hunks(old), hunks(new)),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // The change to line 2 is the same, 6 and 12 were added and 9 was dropped
+        let diffs = super::diff_patches(&old, &new);
+        assert_eq!(
+            strings(&diffs),
+            [
+                ("+", vec!["@@ -6 +6 @@\n-6\n+six\n".to_string()], vec![]),
+                ("-", vec!["@@ -9 +9 @@\n-9\n+nine\n".to_string()], vec![]),
+                (
+                    "+",
+                    vec!["@@ -12 +12 @@\n-12\n+TWELVE\n".to_string()],
+                    vec![]
+                ),
+            ]
+        );
+        let reversed = super::diff_patches(&new, &old);
+        assert_eq!(
+            strings(&reversed)
+                .into_iter()
+                .map(|(kind, hunks, _)| (kind, hunks))
+                .collect::<Vec<_>>(),
+            [
+                ("-", vec!["@@ -6 +6 @@\n-6\n+six\n".to_string()]),
+                ("+", vec!["@@ -9 +9 @@\n-9\n+nine\n".to_string()]),
+                ("-", vec!["@@ -12 +12 @@\n-12\n+TWELVE\n".to_string()]),
+            ]
+        );
+
+        // The same change laid out differently has no differences
+        assert!(super::diff_patches(&old, &old).is_empty());
+        let mut options = DiffOptions::new();
+        options.set_context_len(0);
+        let modified = apply(original, &old).unwrap();
+        assert!(super::diff_patches(&old, &options.create_patch(original, &modified)).is_empty());
+
+        // Hunks changing the same lines differently are paired up, including insertions at the
+        // same position and hunks overlapping several hunks of the other patch
+        let old = create_patch(
+            original,
+            "1\n2\nthree\n4\n5\nfive and a half\n6\n7\n8\n9\n10\n11\n12\n",
+        );
+        let new = create_patch(original, "1\n2\nTHREE\n4\n5\n5.5\n6\n7\n8\n9\n10\n11\n12\n");
+        assert_eq!(
+            strings(&super::diff_patches(&old, &new)),
+            [
+                (
+                    "~",
+                    vec!["@@ -3 +3 @@\n-3\n+three\n".to_string()],
+                    vec!["@@ -3 +3 @@\n-3\n+THREE\n".to_string()]
+                ),
+                (
+                    "~",
+                    vec!["@@ -5,0 +6 @@\n+five and a half\n".to_string()],
+                    vec!["@@ -5,0 +6 @@\n+5.5\n".to_string()]
+                ),
+            ]
+        );
+        let old = create_patch(original, "1\nTWO\n3\nFOUR\n5\n6\n7\n8\n9\n10\n11\n12\n");
+        let new = create_patch(original, "1\nnew\n5\n6\n7\n8\n9\n10\n11\n12\n");
+        let diffs = super::diff_patches(&old, &new);
+        assert_eq!(diffs.len(), 1);
+        match &diffs[0] {
+            HunkDiff::Changed { old, new } => {
+                assert_eq!(old.len(), 2);
+                assert_eq!(new.len(), 1);
+                assert_eq!(new[0].old_range(), HunkRange::new(2, 3));
+            }
+            diff => panic!("{:?}", diff),
+        }
+
+        // Potentially non-utf8 patches work the same way
+        let old = create_patch_bytes(b"a\n\xff\n", b"a\n\xfe\n");
+        let new = create_patch_bytes(b"a\n\xff\n", b"b\n\xff\n");
+        let diffs = super::diff_patches(&old, &new);
+        assert_eq!(diffs.len(), 2);
+        match (&diffs[0], &diffs[1]) {
+            (HunkDiff::Added(added), HunkDiff::Removed(removed)) => {
+                assert_eq!(added.old_range(), HunkRange::new(1, 1));
+                assert_eq!(removed.old_range(), HunkRange::new(2, 1));
+            }
+            diffs => panic!("{:?}", diffs),
+        }
+    }
+
+    #[test]
+    fn sorted_hunks() {
+        let original = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n";
+        let modified = "a\nB\nc\nd\nE\nF\ng\nh\ni\nJ\nK\nL\n";
+        let patch = DiffOptions::new()
+            .set_context_len(0)
+            .create_patch(original, modified);
+        assert_eq!(patch.hunks().len(), 3);
+
+        let by_size = patch.sorted_hunks(SortKey::Size);
+        let expected = "\
+--- original
++++ modified
+@@ -10,3 +10,3 @@
+-j
+-k
+-l
++J
++K
++L
+@@ -5,2 +5,2 @@
+-e
+-f
++E
++F
+@@ -2 +2 @@
+-b
++B
+";
+        assert_eq!(by_size.to_string(), expected);
+
+        // Sorting by position restores the order the patch applies in
+        let by_position = by_size.sorted_hunks(SortKey::Position);
+        assert_eq!(by_position, patch);
+        assert_eq!(apply(original, &by_position).unwrap(), modified);
+        assert_eq!(SortKey::default(), SortKey::Position);
+
+        // A patch sorted by size can't be parsed back either
+        assert!(Patch::from_str(expected).is_err());
+    }
+
+    #[test]
+    fn merge_hunks() {
+        let original = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let modified = "A\nb\nC\nd\ne\nF\ng\nh\ni\nJ\n";
+        let mut opts = DiffOptions::new();
+        opts.set_context_len(0);
+        let patch = opts.create_patch(original, modified);
+        assert_eq!(patch.hunks().len(), 4);
+
+        let merged = patch.merge_hunks(1, Some(original)).unwrap();
+        let expected = "\
+--- original
++++ modified
+@@ -1,3 +1,3 @@
+-a
++A
+ b
+-c
++C
+@@ -6 +6 @@
+-f
++F
+@@ -10 +10 @@
+-j
++J
+";
+        assert_eq!(merged.to_string(), expected);
+        assert_eq!(apply(original, &merged).unwrap(), modified);
+
+        let merged = patch.merge_hunks(3, Some(original)).unwrap();
+        assert_eq!(merged.hunks().len(), 1);
+        assert_eq!(merged.hunks()[0].old_range(), HunkRange::new(1, 10));
+        assert_eq!(apply(original, &merged).unwrap(), modified);
+
+        // Gaps wider than `max_gap` don't need the original text
+        assert_eq!(patch.merge_hunks(0, None).unwrap(), patch);
+        assert_eq!(
+            patch.merge_hunks(1, None),
+            Err(MergeHunksError::MissingContext { hunk: 2 })
+        );
+        assert_eq!(
+            patch.merge_hunks(1, Some("a\nb\nc\nd\ne\nf\ng\nh\n")),
+            Ok(patch.merge_hunks(1, Some(original)).unwrap())
+        );
+        assert_eq!(
+            patch.merge_hunks(3, Some("a\nb\nc\nd\ne\nf\ng\nh\n")),
+            Err(MergeHunksError::MissingContext { hunk: 4 })
+        );
+
+        // Touching hunks and hunks overlapping by their shared context are merged without the
+        // original text. The parser rejects overlapping hunks, so each is parsed on its own
+        fn join<'a>(patches: &'a [Patch<'a, str>]) -> Patch<'a, str> {
+            let hunks = patches.iter().flat_map(|p| p.hunks().to_vec()).collect();
+            Patch::new(None::<&str>, None::<&str>, hunks)
+        }
+        let patches = [
+            "@@ -1,2 +1,2 @@\n-a\n+A\n b\n",
+            "@@ -2,2 +2,2 @@\n b\n-c\n+C\n",
+            "@@ -4 +4 @@\n-d\n+D\n",
+            "@@ -4,0 +5 @@\n+x\n",
+        ]
+        .map(|s| Patch::from_str(s).unwrap());
+        let patch = join(&patches);
+        let merged = patch.merge_hunks(0, None).unwrap();
+        let expected = "\
+@@ -1,4 +1,5 @@
+-a
++A
+ b
+-c
++C
+-d
++D
++x
+";
+        assert_eq!(merged.to_string(), expected);
+        assert_eq!(
+            apply(original, &merged).unwrap(),
+            "A\nb\nC\nD\nx\ne\nf\ng\nh\ni\nj\n"
+        );
+
+        // Hunks overlapping by changed lines or by differing context can't be merged
+        let patches = [
+            "@@ -1,2 +1,2 @@\n-a\n+A\n-b\n+B\n",
+            "@@ -2,2 +2,2 @@\n b\n-c\n+C\n",
+        ]
+        .map(|s| Patch::from_str(s).unwrap());
+        assert_eq!(
+            join(&patches).merge_hunks(0, None),
+            Err(MergeHunksError::Overlap { hunk: 2 })
+        );
+        let patches = [
+            "@@ -1,2 +1,2 @@\n-a\n+A\n b\n",
+            "@@ -2,2 +2,2 @@\n x\n-c\n+C\n",
+        ]
+        .map(|s| Patch::from_str(s).unwrap());
+        assert_eq!(
+            join(&patches).merge_hunks(0, None),
+            Err(MergeHunksError::Overlap { hunk: 2 })
+        );
+    }
+
+    #[test]
+    fn str_and_byte_patches() {
+        let original = "fn a() {\n    1\n}\n\nfn b() {\n    2\n}\n";
+        let modified = "fn a() {\n    1\n}\n\nfn b() {\n    3\n}";
+        let s = "\
+--- \"a/with\\ttab\"
++++ b/plain
+@@ -4,4 +4,4 @@ fn a() {
+ 
+ fn b() {
+-    2
+-}
++    3
++}
+\\ No newline at end of file
+";
+        let patch = Patch::from_str(s)
+            .unwrap()
+            .with_status(FileStatus::Modified);
+        let bytes = Patch::from_bytes(s.as_bytes()).unwrap();
+
+        // Both directions keep everything, and format and apply identically
+        let converted = bytes.to_str_patch().unwrap();
+        assert_eq!(converted, patch);
+        assert_eq!(Patch::<[u8]>::from(converted), bytes);
+        let from_str = Patch::<[u8]>::from(patch.clone());
+        assert_eq!(from_str, bytes);
+        assert_eq!(from_str.to_bytes(), patch.to_string().as_bytes());
+        assert_eq!(bytes.to_str_patch().unwrap().to_string(), patch.to_string());
+        assert_eq!(
+            apply_bytes(original.as_bytes(), &from_str).unwrap(),
+            modified.as_bytes()
+        );
+        assert_eq!(
+            apply(original, &bytes.to_str_patch().unwrap()).unwrap(),
+            modified
+        );
+        let f = PatchFormatter::new()
+            .with_color()
+            .with_intraline_highlight(true);
+        let mut out = Vec::new();
+        f.write_patch_into_vec(&from_str, &mut out);
+        assert_eq!(out, f.fmt_patch(&patch).to_string().as_bytes());
+
+        // Owned file names and statuses survive as well
+        let created = create_patch("", "new\n").with_status(FileStatus::Added);
+        let bytes = Patch::<[u8]>::from(created.clone());
+        assert_eq!(bytes.status(), FileStatus::Added);
+        assert_eq!(bytes.to_str_patch().unwrap(), created);
+
+        let hunk = &patch.hunks()[0];
+        let bytes_hunk = Hunk::<[u8]>::from(hunk.clone());
+        assert_eq!(bytes_hunk.to_str_hunk().unwrap(), *hunk);
+        assert_eq!(bytes_hunk.function_context(), Some(&b"fn a() {\n"[..]));
+        assert_eq!(
+            Line::<[u8]>::from(Line::Delete("x\n")),
+            Line::Delete(&b"x\n"[..])
+        );
+        assert_eq!(
+            Line::Insert(&b"x\n"[..]).to_str_line(),
+            Ok(Line::Insert("x\n"))
+        );
+        assert!(Line::Context(&b"\xff\n"[..]).to_str_line().is_err());
+
+        // Invalid utf8 is reported where it occurs
+        let invalid = |s: &[u8]| Patch::from_bytes(s).unwrap().to_str_patch().unwrap_err();
+        let error =
+            invalid(b"--- a\n+++ b\n@@ -1 +1 @@\n-a\n+a\n@@ -5,2 +5,2 @@\n x\n-\xc3(\n+y\n");
+        assert_eq!(error, PatchUtf8Error::Line { hunk: 2, line: 2 });
+        assert_eq!((error.hunk(), error.line()), (Some(2), Some(2)));
+        assert_eq!(error.to_string(), "invalid utf8 in line 2 of hunk #2");
+
+        let error = invalid(b"--- a\n+++ b\n@@ -1 +1 @@ f\xff\n-a\n+b\n");
+        assert_eq!(error, PatchUtf8Error::FunctionContext { hunk: 1 });
+        assert_eq!(error.line(), None);
+
+        let error = invalid(b"--- a\xff\n+++ b\n@@ -1 +1 @@\n-a\n+b\n");
+        assert_eq!(error, PatchUtf8Error::OriginalFilename);
+        assert_eq!(error.hunk(), None);
+        let error = invalid(b"--- a\n+++ b\xff\n@@ -1 +1 @@\n-a\n+b\n");
+        assert_eq!(error, PatchUtf8Error::ModifiedFilename);
+    }
+
+    #[test]
+    fn file_paths() {
+        let header = |original: &str, modified: &str| {
+            std::format!("--- {}\n+++ {}\n@@ -1 +1 @@\n-a\n+b\n", original, modified)
+        };
+
+        // Git prefixes are stripped from their own side only
+        let s = header("a/src/lib.rs", "b/a/lib.rs");
+        let patch = Patch::from_str(&s).unwrap();
+        let bytes = Patch::from_bytes(s.as_bytes()).unwrap();
+        assert_eq!(
+            bytes.old_path(PrefixPolicy::Git).unwrap(),
+            &b"src/lib.rs"[..]
+        );
+        assert_eq!(bytes.new_path(PrefixPolicy::Git).unwrap(), &b"a/lib.rs"[..]);
+        assert_eq!(
+            bytes.old_path(PrefixPolicy::None).unwrap(),
+            &b"a/src/lib.rs"[..]
+        );
+        assert_eq!(
+            bytes.old_path(PrefixPolicy::Prefix("b/")).unwrap(),
+            &b"a/src/lib.rs"[..]
+        );
+        assert_eq!(
+            bytes.new_path(PrefixPolicy::Prefix("b/a")).unwrap(),
+            &b"lib.rs"[..]
+        );
+        // Prefixes only match whole path components
+        assert_eq!(
+            bytes.old_path(PrefixPolicy::Prefix("a/sr")).unwrap(),
+            &b"a/src/lib.rs"[..]
+        );
+        assert_eq!(
+            bytes.old_path(PrefixPolicy::Prefix("a\\src")).unwrap(),
+            &b"lib.rs"[..]
+        );
+        // Nothing is stripped if it would leave nothing of the name
+        assert_eq!(
+            bytes
+                .old_path(PrefixPolicy::Prefix("a/src/lib.rs"))
+                .unwrap(),
+            &b"a/src/lib.rs"[..]
+        );
+        let s = header("srcfoo/x", "src/x");
+        let srcfoo = Patch::from_bytes(s.as_bytes()).unwrap();
+        let src = PrefixPolicy::Prefix("src");
+        assert_eq!(srcfoo.old_path(src).unwrap(), &b"srcfoo/x"[..]);
+        assert_eq!(srcfoo.new_path(src).unwrap(), &b"x"[..]);
+        let s = header("a//x", "b/");
+        let doubled = Patch::from_bytes(s.as_bytes()).unwrap();
+        assert_eq!(doubled.old_path(PrefixPolicy::Git).unwrap(), &b"x"[..]);
+        assert_eq!(doubled.new_path(PrefixPolicy::Git).unwrap(), &b"b/"[..]);
+        #[cfg(feature = "std")]
+        {
+            use std::path::Path;
+            let old = patch.old_path(PrefixPolicy::Git).unwrap();
+            assert_eq!(old, Path::new("src").join("lib.rs"));
+            assert_eq!(
+                patch.new_path(PrefixPolicy::Git).unwrap(),
+                Path::new("a").join("lib.rs")
+            );
+        }
+        #[cfg(not(feature = "std"))]
+        let _ = patch;
+
+        // Quoted names are unquoted
+        let s = header("\"a/with space\\ttab\"", "\"b/quote\\\"d\"");
+        let bytes = Patch::from_bytes(s.as_bytes()).unwrap();
+        let old = bytes.old_path(PrefixPolicy::Git).unwrap();
+        assert_eq!(old, &b"with space\ttab"[..]);
+        assert_eq!(bytes.new_path(PrefixPolicy::Git).unwrap(), &b"quote\"d"[..]);
+
+        // Windows separators, which have to be quoted, are normalized, including in the prefix
+        let s = header("\"a\\\\src\\\\lib.rs\"", "\"b/src\\\\lib.rs\"");
+        let bytes = Patch::from_bytes(s.as_bytes()).unwrap();
+        assert_eq!(
+            bytes.old_path(PrefixPolicy::Git).unwrap(),
+            &b"src/lib.rs"[..]
+        );
+        assert_eq!(
+            bytes.new_path(PrefixPolicy::Git).unwrap(),
+            &b"src/lib.rs"[..]
+        );
+        #[cfg(feature = "std")]
+        {
+            let patch = Patch::from_str(&s).unwrap();
+            let expected = std::path::Path::new("src").join("lib.rs");
+            assert_eq!(patch.old_path(PrefixPolicy::Git).unwrap(), expected);
+            assert_eq!(patch.new_path(PrefixPolicy::Git).unwrap(), expected);
+        }
+
+        // `/dev/null`, missing names and the missing side of added or removed files have no path
+        let s = header("/dev/null", "b/new.rs");
+        let bytes = Patch::from_bytes(s.as_bytes()).unwrap();
+        assert_eq!(bytes.old_path(PrefixPolicy::Git), None);
+        assert_eq!(bytes.new_path(PrefixPolicy::Git).unwrap(), &b"new.rs"[..]);
+
+        let patch = create_patch_bytes(b"a\n", b"");
+        assert_eq!(
+            patch.old_path(PrefixPolicy::None).unwrap(),
+            &b"original"[..]
+        );
+        let removed = patch.with_status(FileStatus::Removed);
+        assert_eq!(
+            removed.old_path(PrefixPolicy::None).unwrap(),
+            &b"original"[..]
+        );
+        assert_eq!(removed.new_path(PrefixPolicy::None), None);
+        let added = create_patch_bytes(b"", b"a\n").with_status(FileStatus::Added);
+        assert_eq!(added.old_path(PrefixPolicy::None), None);
+
+        let patch = Patch::<[u8]>::new(None::<&[u8]>, Some(&b"b/x"[..]), Vec::new());
+        assert_eq!(patch.old_path(PrefixPolicy::Git), None);
+        assert_eq!(patch.new_path(PrefixPolicy::Git).unwrap(), &b"x"[..]);
+    }
+
+    #[test]
+    fn reverse_round_trips() {
+        let cases = [
+            (
+                "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n",
+                "A\nb\nc\nx\nd\ne\nf\ng\ni\nJ\n",
+            ),
+            ("a\nb\nc\nd\ne\nf\ng\nh\n", "x\na\nb\nd\ne\nf\ng\nh\ny\n"),
+            ("a\na\na\nb\na\na\na\n", "a\na\nb\nb\na\na\n"),
+            ("a\nb\nc\nd\ne\nf\ng", "a\nB\nc\nd\ne\nF\ng\n"),
+            ("", "a\nb\n"),
+            ("a\nb\n", ""),
+        ];
+        for (original, modified) in cases {
+            for context_len in 0..4 {
+                let mut opts = DiffOptions::new();
+                opts.set_context_len(context_len);
+                let patch = opts.create_patch(original, modified);
+                let stripped = patch.strip_context();
+                let merged = stripped.merge_hunks(usize::MAX, Some(original)).unwrap();
+
+                for patch in [&patch, &stripped, &merged, &patch.canonicalize()] {
+                    assert!(patch.is_reversible(), "{}", patch);
+                    let reversed = patch.reverse();
+                    assert!(reversed.is_reversible());
+                    assert_eq!(apply(original, patch).unwrap(), modified);
+                    assert_eq!(apply(modified, &reversed).unwrap(), original);
+                    assert_eq!(&reversed.reverse(), patch);
+                }
+            }
+        }
+
+        // Hunks whose new ranges don't follow from their old ranges, which overlap or which are
+        // out of order can't be located in both directions without searching
+        let s = "\
+@@ -2 +2 @@
+-b
++B
+@@ -5 +6 @@
+-e
++E
+";
+        assert!(!Patch::from_str(s).unwrap().is_reversible());
+        let patch = create_patch("a\nb\nc\nd\ne\n", "a\nB\nc\nd\nE\n");
+        assert!(patch.is_reversible());
+        let stripped = patch.strip_context();
+        let mut hunks = stripped.hunks().to_vec();
+        hunks.reverse();
+        assert!(!Patch::new(Some("a"), Some("b"), hunks).is_reversible());
+        let hunks = [
+            "@@ -1,2 +1,2 @@\n-a\n+A\n b\n",
+            "@@ -2,2 +2,2 @@\n b\n-c\n+C\n",
+        ];
+        let patches = hunks.map(|s| Patch::from_str(s).unwrap());
+        let hunks = patches.iter().flat_map(|p| p.hunks().to_vec()).collect();
+        assert!(!Patch::new(Some("a"), Some("b"), hunks).is_reversible());
+
+        // Nor can patches leaving out some of the changes
+        let mut opts = DiffOptions::new();
+        opts.set_squeeze_blank_lines(true).set_context_len(1);
+        let patch = opts.create_patch("a\n\n\nb\nc\nd\ne\n", "a\n\nb\nc\nd\nE\n");
+        assert!(!patch.is_reversible());
+        let mut opts = DiffOptions::new();
+        opts.set_ignore_comment_changes(true).set_context_len(1);
+        let patch = opts.create_patch("a\nb\nc\nd\ne\n", "# x\na\nb\nc\nd\nE\n");
+        assert!(!patch.is_reversible());
+    }
+
+    #[test]
+    fn line_renderer() {
+        let original = "fn main() {\n    old();\n}";
+        let modified = "fn main() {\n    new();\n}";
+        let patch = create_patch(original, modified);
+
+        let f = PatchFormatter::new()
+            .with_line_renderer(|_, text, w| w.write_str(&text.to_uppercase()));
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+        assert_send_sync(&f);
+        let expected = "\
+--- original
++++ modified
+@@ -1,3 +1,3 @@
+ FN MAIN() {
+-    OLD();
++    NEW();
+ }
+\\ No newline at end of file
+";
+        assert_eq!(f.fmt_patch(&patch).to_string(), expected);
+
+        let bytes = create_patch_bytes(original.as_bytes(), modified.as_bytes());
+        let mut out = Vec::new();
+        f.write_patch_into_vec(&bytes, &mut out);
+        assert_eq!(out, expected.as_bytes());
+
+        // The renderer is told which kind of line it renders and runs inside the line's color
+        let f = PatchFormatter::new()
+            .with_color()
+            .with_line_renderer(|kind, text, w| write!(w, "{}{}", kind.prefix_char(), text));
+        let output = f.fmt_patch(&patch).to_string();
+        assert!(output.contains("\n  fn main() {\n"));
+        assert!(output.contains("\u{1b}[31m--    old();\n\u{1b}[0m"));
+        assert!(output.contains("\u{1b}[32m++    new();\n\u{1b}[0m"));
+
+        // With intraline highlighting the changed and unchanged runs are rendered separately
+        let f = PatchFormatter::new()
+            .with_color()
+            .with_intraline_highlight(true)
+            .with_line_renderer(|_, text, w| write!(w, "<{}>", text));
+        let output = f.fmt_patch(&patch).to_string();
+        assert!(output.contains("-<    >\u{1b}[0m\u{1b}[7;31m<old>\u{1b}[0m\u{1b}[31m<();>\n"));
+        assert!(output.contains("+<    >\u{1b}[0m\u{1b}[7;32m<new>\u{1b}[0m\u{1b}[32m<();>\n"));
+    }
+}
@@ -1,37 +1,115 @@
 //! Parse a Patch
 
-use super::{Hunk, HunkRange, Line, ESCAPED_CHARS_BYTES, NO_NEWLINE_AT_EOF};
+use super::{
+    FileStatus, Hunk, HunkRange, Line, LineKind, DEFAULT_MODE, ESCAPED_CHARS_BYTES,
+    NO_NEWLINE_AT_EOF,
+};
 use crate::{
     patch::Patch,
     utils::{LineIter, Text},
 };
-use std::{borrow::Cow, fmt};
+use alloc::{
+    borrow::{Cow, ToOwned},
+    string::String,
+    vec::Vec,
+};
+use core::{fmt, num::ParseIntError};
 
-type Result<T, E = ParsePatchError> = std::result::Result<T, E>;
+type Result<T, E = ParsePatchError> = core::result::Result<T, E>;
 
 /// An error returned when parsing a `Patch` using [`Patch::from_str`] fails
 ///
 /// [`Patch::from_str`]: struct.Patch.html#method.from_str
-// TODO use a custom error type instead of a Cow
-#[derive(Debug)]
-pub struct ParsePatchError(Cow<'static, str>);
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParsePatchError {
+    /// The input ended in the middle of a patch
+    UnexpectedEof,
+    /// The patch header contains more than one `---` or `+++` line
+    DuplicateFilename,
+    /// A filename in the patch header is malformed
+    InvalidFilename {
+        /// A description of what is wrong with the filename
+        reason: &'static str,
+    },
+    /// A hunk header (`@@ -a,b +c,d @@`) is malformed
+    InvalidHunkHeader,
+    /// A line number or length in a hunk header isn't a valid number
+    InvalidRange(ParseIntError),
+    /// The number of lines in a hunk doesn't match the lengths given in its header
+    HunkHeaderMismatch,
+    /// The hunks of the patch aren't in order or overlap one another
+    HunksOutOfOrder,
+    /// A line in the body of a hunk doesn't start with ` `, `-`, `+` or `\`
+    UnexpectedHunkLine,
+    /// A "No newline at end of file" marker is misplaced or is followed by more lines of the
+    /// same kind
+    MisplacedNoNewline,
+}
 
 impl ParsePatchError {
-    fn new<E: Into<Cow<'static, str>>>(e: E) -> Self {
-        Self(e.into())
+    /// Returns `true` if the input ended in the middle of a patch
+    pub fn is_unexpected_eof(&self) -> bool {
+        matches!(self, ParsePatchError::UnexpectedEof)
+    }
+
+    /// Returns `true` if the patch header is malformed
+    pub fn is_invalid_header(&self) -> bool {
+        matches!(
+            self,
+            ParsePatchError::DuplicateFilename | ParsePatchError::InvalidFilename { .. }
+        )
+    }
+
+    /// Returns `true` if one of the hunks, or the way they're ordered, is malformed
+    pub fn is_invalid_hunk(&self) -> bool {
+        matches!(
+            self,
+            ParsePatchError::InvalidHunkHeader
+                | ParsePatchError::InvalidRange(_)
+                | ParsePatchError::HunkHeaderMismatch
+                | ParsePatchError::HunksOutOfOrder
+                | ParsePatchError::UnexpectedHunkLine
+                | ParsePatchError::MisplacedNoNewline
+        )
+    }
+
+    fn invalid_filename(reason: &'static str) -> Self {
+        ParsePatchError::InvalidFilename { reason }
     }
 }
 
 impl fmt::Display for ParsePatchError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "error parsing patch: {}", self.0)
+        write!(f, "error parsing patch: ")?;
+        match self {
+            ParsePatchError::UnexpectedEof => write!(f, "unexpected EOF"),
+            ParsePatchError::DuplicateFilename => write!(f, "multiple '---' or '+++' lines"),
+            ParsePatchError::InvalidFilename { reason } => write!(f, "{}", reason),
+            ParsePatchError::InvalidHunkHeader => write!(f, "unable to parse hunk header"),
+            ParsePatchError::InvalidRange(_) => write!(f, "can't parse range"),
+            ParsePatchError::HunkHeaderMismatch => write!(f, "hunk header does not match hunk"),
+            ParsePatchError::HunksOutOfOrder => write!(f, "hunks not in order or overlap"),
+            ParsePatchError::UnexpectedHunkLine => write!(f, "unexpected line in hunk body"),
+            ParsePatchError::MisplacedNoNewline => {
+                write!(f, "unexpected 'No newline at end of file' line")
+            }
+        }
     }
 }
 
-impl std::error::Error for ParsePatchError {}
+#[cfg(feature = "std")]
+impl std::error::Error for ParsePatchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParsePatchError::InvalidRange(e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
 struct Parser<'a, T: Text + ?Sized> {
-    lines: std::iter::Peekable<LineIter<'a, T>>,
+    lines: core::iter::Peekable<LineIter<'a, T>>,
 }
 
 impl<'a, T: Text + ?Sized> Parser<'a, T> {
@@ -46,16 +124,14 @@ impl<'a, T: Text + ?Sized> Parser<'a, T> {
     }
 
     fn next(&mut self) -> Result<&'a T> {
-        let line = self
-            .lines
-            .next()
-            .ok_or_else(|| ParsePatchError::new("unexpected EOF"))?;
+        let line = self.lines.next().ok_or(ParsePatchError::UnexpectedEof)?;
         Ok(line)
     }
 }
 
 pub fn parse(input: &str) -> Result<Patch<'_, str>> {
     let mut parser = Parser::new(input);
+    let (status, mode) = skip_header_preamble(&mut parser)?;
     let header = patch_header(&mut parser)?;
     let hunks = hunks(&mut parser)?;
 
@@ -63,21 +139,26 @@ pub fn parse(input: &str) -> Result<Patch<'_, str>> {
         header.0.map(convert_cow_to_str),
         header.1.map(convert_cow_to_str),
         hunks,
-    ))
+    )
+    .with_status(status)
+    .with_mode(mode))
 }
 
 pub fn parse_bytes(input: &[u8]) -> Result<Patch<'_, [u8]>> {
     let mut parser = Parser::new(input);
+    let (status, mode) = skip_header_preamble(&mut parser)?;
     let header = patch_header(&mut parser)?;
     let hunks = hunks(&mut parser)?;
 
-    Ok(Patch::new(header.0, header.1, hunks))
+    Ok(Patch::new(header.0, header.1, hunks)
+        .with_status(status)
+        .with_mode(mode))
 }
 
 // This is only used when the type originated as a utf8 string
 fn convert_cow_to_str(cow: Cow<'_, [u8]>) -> Cow<'_, str> {
     match cow {
-        Cow::Borrowed(b) => std::str::from_utf8(b).unwrap().into(),
+        Cow::Borrowed(b) => core::str::from_utf8(b).unwrap().into(),
         Cow::Owned(o) => String::from_utf8(o).unwrap().into(),
     }
 }
@@ -86,20 +167,18 @@ fn convert_cow_to_str(cow: Cow<'_, [u8]>) -> Cow<'_, str> {
 fn patch_header<'a, T: Text + ToOwned + ?Sized>(
     parser: &mut Parser<'a, T>,
 ) -> Result<(Option<Cow<'a, [u8]>>, Option<Cow<'a, [u8]>>)> {
-    skip_header_preamble(parser)?;
-
     let mut filename1 = None;
     let mut filename2 = None;
 
     while let Some(line) = parser.peek() {
         if line.starts_with("--- ") {
             if filename1.is_some() {
-                return Err(ParsePatchError::new("multiple '---' lines"));
+                return Err(ParsePatchError::DuplicateFilename);
             }
             filename1 = Some(parse_filename("--- ", parser.next()?)?);
         } else if line.starts_with("+++ ") {
             if filename2.is_some() {
-                return Err(ParsePatchError::new("multiple '+++' lines"));
+                return Err(ParsePatchError::DuplicateFilename);
             }
             filename2 = Some(parse_filename("+++ ", parser.next()?)?);
         } else {
@@ -111,16 +190,38 @@ fn patch_header<'a, T: Text + ToOwned + ?Sized>(
 }
 
 // Skip to the first filename header ("--- " or "+++ ") or hunk line,
-// skipping any preamble lines like "diff --git", etc.
-fn skip_header_preamble<T: Text + ?Sized>(parser: &mut Parser<'_, T>) -> Result<()> {
+// skipping any preamble lines like "diff --git", etc. A git "new file mode" or
+// "deleted file mode" line in the preamble determines the status of the patch and the mode of
+// its file, which is left at the default if it isn't a valid octal number.
+fn skip_header_preamble<T: Text + ?Sized>(parser: &mut Parser<'_, T>) -> Result<(FileStatus, u32)> {
+    let mut status = FileStatus::Modified;
+    let mut mode = DEFAULT_MODE;
     while let Some(line) = parser.peek() {
         if line.starts_with("--- ") | line.starts_with("+++ ") | line.starts_with("@@ ") {
             break;
         }
+        let mode_line = if let Some(rest) = line.strip_prefix("new file mode ") {
+            status = FileStatus::Added;
+            Some(rest)
+        } else if let Some(rest) = line.strip_prefix("deleted file mode ") {
+            status = FileStatus::Removed;
+            Some(rest)
+        } else {
+            None
+        };
+        if let Some(parsed) = mode_line.and_then(parse_mode) {
+            mode = parsed;
+        }
         parser.next()?;
     }
 
-    Ok(())
+    Ok((status, mode))
+}
+
+fn parse_mode<T: Text + ?Sized>(mode: &T) -> Option<u32> {
+    let mode = core::str::from_utf8(mode.as_bytes()).ok()?;
+    let mode = mode.strip_suffix('\n').unwrap_or(mode);
+    u32::from_str_radix(mode.strip_suffix('\r').unwrap_or(mode), 8).ok()
 }
 
 fn parse_filename<'a, T: Text + ToOwned + ?Sized>(
@@ -129,14 +230,14 @@ fn parse_filename<'a, T: Text + ToOwned + ?Sized>(
 ) -> Result<Cow<'a, [u8]>> {
     let line = line
         .strip_prefix(prefix)
-        .ok_or_else(|| ParsePatchError::new("unable to parse filename"))?;
+        .ok_or_else(|| ParsePatchError::invalid_filename("unable to parse filename"))?;
 
     let filename = if let Some((filename, _)) = line.split_at_exclusive("\t") {
         filename
     } else if let Some((filename, _)) = line.split_at_exclusive("\n") {
         filename
     } else {
-        return Err(ParsePatchError::new("filename unterminated"));
+        return Err(ParsePatchError::invalid_filename("filename unterminated"));
     };
 
     let filename = if let Some(quoted) = is_quoted(filename) {
@@ -156,7 +257,9 @@ fn unescaped_filename<T: Text + ToOwned + ?Sized>(filename: &T) -> Result<Cow<'_
     let bytes = filename.as_bytes();
 
     if bytes.iter().any(|b| ESCAPED_CHARS_BYTES.contains(b)) {
-        return Err(ParsePatchError::new("invalid char in unquoted filename"));
+        return Err(ParsePatchError::invalid_filename(
+            "invalid char in unquoted filename",
+        ));
     }
 
     Ok(bytes.into())
@@ -170,7 +273,7 @@ fn escaped_filename<T: Text + ToOwned + ?Sized>(escaped: &T) -> Result<Cow<'_, [
         if c == b'\\' {
             let ch = match chars
                 .next()
-                .ok_or_else(|| ParsePatchError::new("expected escaped character"))?
+                .ok_or_else(|| ParsePatchError::invalid_filename("expected escaped character"))?
             {
                 b'n' => b'\n',
                 b't' => b'\t',
@@ -178,11 +281,17 @@ fn escaped_filename<T: Text + ToOwned + ?Sized>(escaped: &T) -> Result<Cow<'_, [
                 b'r' => b'\r',
                 b'\"' => b'\"',
                 b'\\' => b'\\',
-                _ => return Err(ParsePatchError::new("invalid escaped character")),
+                _ => {
+                    return Err(ParsePatchError::invalid_filename(
+                        "invalid escaped character",
+                    ))
+                }
             };
             filename.push(ch);
         } else if ESCAPED_CHARS_BYTES.contains(&c) {
-            return Err(ParsePatchError::new("invalid unescaped character"));
+            return Err(ParsePatchError::invalid_filename(
+                "invalid unescaped character",
+            ));
         } else {
             filename.push(c);
         }
@@ -193,8 +302,8 @@ fn escaped_filename<T: Text + ToOwned + ?Sized>(escaped: &T) -> Result<Cow<'_, [
 
 fn verify_hunks_in_order<T: ?Sized>(hunks: &[Hunk<'_, T>]) -> bool {
     for hunk in hunks.windows(2) {
-        if hunk[0].old_range.end() > hunk[1].old_range.start()
-            || hunk[0].new_range.end() > hunk[1].new_range.start()
+        if hunk[0].old_range.to_std_range().end > hunk[1].old_range.to_std_range().start
+            || hunk[0].new_range.to_std_range().end > hunk[1].new_range.to_std_range().start
         {
             return false;
         }
@@ -210,7 +319,7 @@ fn hunks<'a, T: Text + ?Sized>(parser: &mut Parser<'a, T>) -> Result<Vec<Hunk<'a
 
     // check and verify that the Hunks are in sorted order and don't overlap
     if !verify_hunks_in_order(&hunks) {
-        return Err(ParsePatchError::new("Hunks not in order or overlap"));
+        return Err(ParsePatchError::HunksOutOfOrder);
     }
 
     Ok(hunks)
@@ -223,7 +332,7 @@ fn hunk<'a, T: Text + ?Sized>(parser: &mut Parser<'a, T>) -> Result<Hunk<'a, T>>
     // check counts of lines to see if they match the ranges in the hunk header
     let (len1, len2) = super::hunk_lines_count(&lines);
     if len1 != range1.len || len2 != range2.len {
-        return Err(ParsePatchError::new("Hunk header does not match hunk"));
+        return Err(ParsePatchError::HunkHeaderMismatch);
     }
 
     Ok(Hunk::new(range1, range2, function_context, lines))
@@ -232,44 +341,38 @@ fn hunk<'a, T: Text + ?Sized>(parser: &mut Parser<'a, T>) -> Result<Hunk<'a, T>>
 fn hunk_header<T: Text + ?Sized>(input: &T) -> Result<(HunkRange, HunkRange, Option<&T>)> {
     let input = input
         .strip_prefix("@@ ")
-        .ok_or_else(|| ParsePatchError::new("unable to parse hunk header"))?;
+        .ok_or(ParsePatchError::InvalidHunkHeader)?;
 
     let (ranges, function_context) = input
         .split_at_exclusive(" @@")
-        .ok_or_else(|| ParsePatchError::new("hunk header unterminated"))?;
+        .ok_or(ParsePatchError::InvalidHunkHeader)?;
     let function_context = function_context.strip_prefix(" ");
 
     let (range1, range2) = ranges
         .split_at_exclusive(" ")
-        .ok_or_else(|| ParsePatchError::new("unable to parse hunk header"))?;
+        .ok_or(ParsePatchError::InvalidHunkHeader)?;
     let range1 = range(
         range1
             .strip_prefix("-")
-            .ok_or_else(|| ParsePatchError::new("unable to parse hunk header"))?,
+            .ok_or(ParsePatchError::InvalidHunkHeader)?,
     )?;
     let range2 = range(
         range2
             .strip_prefix("+")
-            .ok_or_else(|| ParsePatchError::new("unable to parse hunk header"))?,
+            .ok_or(ParsePatchError::InvalidHunkHeader)?,
     )?;
     Ok((range1, range2, function_context))
 }
 
 fn range<T: Text + ?Sized>(s: &T) -> Result<HunkRange> {
-    let (start, len) = if let Some((start, len)) = s.split_at_exclusive(",") {
+    let s = s.as_str().ok_or(ParsePatchError::InvalidHunkHeader)?;
+    let (start, len) = if let Some((start, len)) = s.split_once(',') {
         (
-            start
-                .parse()
-                .ok_or_else(|| ParsePatchError::new("can't parse range"))?,
-            len.parse()
-                .ok_or_else(|| ParsePatchError::new("can't parse range"))?,
+            start.parse().map_err(ParsePatchError::InvalidRange)?,
+            len.parse().map_err(ParsePatchError::InvalidRange)?,
         )
     } else {
-        (
-            s.parse()
-                .ok_or_else(|| ParsePatchError::new("can't parse range"))?,
-            1,
-        )
+        (s.parse().map_err(ParsePatchError::InvalidRange)?, 1)
     };
 
     Ok(HunkRange::new(start, len))
@@ -285,41 +388,30 @@ fn hunk_lines<'a, T: Text + ?Sized>(parser: &mut Parser<'a, T>) -> Result<Vec<Li
         let line = if line.starts_with("@") {
             break;
         } else if no_newline_context {
-            return Err(ParsePatchError::new("expected end of hunk"));
-        } else if let Some(line) = line.strip_prefix(" ") {
-            Line::Context(line)
+            return Err(ParsePatchError::MisplacedNoNewline);
         } else if line.starts_with("\n") {
+            // Some tools drop the trailing space of empty context lines
             Line::Context(*line)
-        } else if let Some(line) = line.strip_prefix("-") {
-            if no_newline_delete {
-                return Err(ParsePatchError::new("expected no more deleted lines"));
-            }
-            Line::Delete(line)
-        } else if let Some(line) = line.strip_prefix("+") {
-            if no_newline_insert {
-                return Err(ParsePatchError::new("expected no more inserted lines"));
+        } else if let Some((kind, line)) = split_line_prefix(*line) {
+            let misplaced = match kind {
+                LineKind::Context => false,
+                LineKind::Delete => no_newline_delete,
+                LineKind::Insert => no_newline_insert,
+            };
+            if misplaced {
+                return Err(ParsePatchError::MisplacedNoNewline);
             }
-            Line::Insert(line)
+            Line::new(kind, line)
         } else if line.starts_with(NO_NEWLINE_AT_EOF) {
-            let last_line = lines.pop().ok_or_else(|| {
-                ParsePatchError::new("unexpected 'No newline at end of file' line")
-            })?;
-            match last_line {
-                Line::Context(line) => {
-                    no_newline_context = true;
-                    Line::Context(strip_newline(line)?)
-                }
-                Line::Delete(line) => {
-                    no_newline_delete = true;
-                    Line::Delete(strip_newline(line)?)
-                }
-                Line::Insert(line) => {
-                    no_newline_insert = true;
-                    Line::Insert(strip_newline(line)?)
-                }
+            let last_line = lines.pop().ok_or(ParsePatchError::MisplacedNoNewline)?;
+            match last_line.kind() {
+                LineKind::Context => no_newline_context = true,
+                LineKind::Delete => no_newline_delete = true,
+                LineKind::Insert => no_newline_insert = true,
             }
+            Line::new(last_line.kind(), strip_newline(last_line.value())?)
         } else {
-            return Err(ParsePatchError::new("unexpected line in hunk body"));
+            return Err(ParsePatchError::UnexpectedHunkLine);
         };
 
         lines.push(line);
@@ -329,17 +421,25 @@ fn hunk_lines<'a, T: Text + ?Sized>(parser: &mut Parser<'a, T>) -> Result<Vec<Li
     Ok(lines)
 }
 
+// Splits a hunk line into its kind and its contents
+fn split_line_prefix<T: Text + ?Sized>(line: &T) -> Option<(LineKind, &T)> {
+    let prefix = *line.as_bytes().first()?;
+    let kind = LineKind::from_prefix_char(char::from(prefix))?;
+    // Every prefix is a single ASCII byte
+    Some((kind, line.split_at(1).1))
+}
+
 fn strip_newline<T: Text + ?Sized>(s: &T) -> Result<&T> {
     if let Some(stripped) = s.strip_suffix("\n") {
         Ok(stripped)
     } else {
-        Err(ParsePatchError::new("missing newline"))
+        Err(ParsePatchError::MisplacedNoNewline)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{parse, parse_bytes};
+    use super::{parse, parse_bytes, ParsePatchError};
 
     #[test]
     fn test_escaped_filenames() {
@@ -472,5 +572,62 @@ mod tests {
 
 ";
         parse(s).unwrap();
+
+        // An insertion directly after the lines covered by the previous hunk
+        let s = "\
+--- original
++++ modified
+@@ -2,2 +2,2 @@
+-b
++B
+ c
+@@ -3,0 +4 @@
++d
+";
+        parse(s).unwrap();
+
+        // An insertion after line 3 can't follow a hunk which starts at line 4
+        let s = "\
+--- original
++++ modified
+@@ -4 +4 @@
+-d
++D
+@@ -3,0 +4 @@
++c
+";
+        assert_eq!(parse(s).unwrap_err(), ParsePatchError::HunksOutOfOrder);
+    }
+
+    #[test]
+    fn error_variants() {
+        fn assert_send_sync<T: Send + Sync + 'static>() {}
+        assert_send_sync::<ParsePatchError>();
+
+        let err = parse("--- a\n+++ b\n@@ -1 +1\n").unwrap_err();
+        assert_eq!(err, ParsePatchError::InvalidHunkHeader);
+        assert!(err.is_invalid_hunk());
+
+        let err = parse("--- a\n+++ b\n@@ -x +1 @@\n").unwrap_err();
+        assert!(matches!(err, ParsePatchError::InvalidRange(_)));
+        #[cfg(feature = "std")]
+        assert!(std::error::Error::source(&err).is_some());
+
+        let err = parse("--- a\n+++ b\n@@ -1,2 +1,2 @@\n a\n").unwrap_err();
+        assert_eq!(err, ParsePatchError::HunkHeaderMismatch);
+
+        let err = parse("--- a\n+++ b\n@@ -1 +1 @@\n-a\n+b\n*c\n").unwrap_err();
+        assert_eq!(err, ParsePatchError::UnexpectedHunkLine);
+
+        let err = parse("--- a\n--- b\n").unwrap_err();
+        assert_eq!(err, ParsePatchError::DuplicateFilename);
+        assert!(err.is_invalid_header());
+
+        let err = parse("--- \"a\n").unwrap_err();
+        assert!(matches!(err, ParsePatchError::InvalidFilename { .. }));
+
+        let err =
+            parse("--- a\n+++ b\n@@ -1 +1 @@\n a\n\\ No newline at end of file\n b\n").unwrap_err();
+        assert_eq!(err, ParsePatchError::MisplacedNoNewline);
     }
 }
@@ -0,0 +1,193 @@
+use super::{FileStatus, Patch, DEV_NULL};
+use crate::utils::Text;
+use alloc::{
+    borrow::{Cow, ToOwned},
+    vec::Vec,
+};
+#[cfg(feature = "std")]
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+
+/// Which prefix [`Patch::old_path`] and [`Patch::new_path`] strip from the file names in a
+/// patch's header
+///
+/// A prefix is only stripped from names which start with it, other names are kept whole. A
+/// prefix which doesn't end in a separator has to be followed by one or by the end of the name,
+/// so `src` strips the start of `src/lib.rs` but not of `srcfoo/lib.rs`. When matching a prefix
+/// `\` and `/` are treated as the same separator, so `a/` also strips the `a\` of a patch
+/// written on Windows.
+///
+/// The separators right after a prefix are stripped along with it, so `src` turns `src/lib.rs`
+/// into `lib.rs`. A name made up of nothing but the prefix is kept whole.
+///
+/// [`Patch::old_path`]: struct.Patch.html#method.old_path
+/// [`Patch::new_path`]: struct.Patch.html#method.new_path
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PrefixPolicy<'a> {
+    /// Strip git's `a/` prefix from the old file's name and its `b/` prefix from the new one's
+    Git,
+    /// Strip the given prefix from the names of both files
+    Prefix(&'a str),
+    /// Keep the names as they are
+    None,
+}
+
+// The side of a patch a file name belongs to
+#[derive(Clone, Copy)]
+pub(crate) enum Side {
+    Old,
+    New,
+}
+
+impl PrefixPolicy<'_> {
+    // Strips the prefix from the name of a file on the given side of a patch
+    pub(crate) fn strip<T: Text + ?Sized>(self, name: &T, side: Side) -> &T {
+        let prefix = match (self, side) {
+            (PrefixPolicy::Git, Side::Old) => "a/",
+            (PrefixPolicy::Git, Side::New) => "b/",
+            (PrefixPolicy::Prefix(prefix), _) => prefix,
+            (PrefixPolicy::None, _) => "",
+        };
+
+        let matches = name.len() >= prefix.len()
+            && name.is_char_boundary(prefix.len())
+            && name
+                .as_bytes()
+                .iter()
+                .zip(prefix.as_bytes())
+                .all(|(&a, &b)| a == b || is_separator(a) && is_separator(b))
+            && (prefix.bytes().last().map_or(true, is_separator)
+                || name
+                    .as_bytes()
+                    .get(prefix.len())
+                    .map_or(true, |&b| is_separator(b)));
+        if !matches {
+            return name;
+        }
+        let separators = name.as_bytes()[prefix.len()..]
+            .iter()
+            .take_while(|&&b| is_separator(b))
+            .count();
+        match name.split_at(prefix.len() + separators).1 {
+            stripped if stripped.is_empty() => name,
+            stripped => stripped,
+        }
+    }
+}
+
+fn is_separator(b: u8) -> bool {
+    b == b'/' || b == b'\\'
+}
+
+impl<'a> Patch<'a, str> {
+    /// Returns the path of the old file, or `None` if it doesn't exist
+    ///
+    /// The path is the file's name from the patch header with the prefix chosen by `strip`
+    /// removed and its separators converted to the platform's own. Quoted names are unquoted
+    /// when the patch is parsed. There's no old file if the patch adds its file, or if the
+    /// name is missing or `/dev/null`.
+    ///
+    /// ```
+    /// use diffy::{Patch, PrefixPolicy};
+    /// use std::path::Path;
+    ///
+    /// let s = "\
+    /// --- \"a/src/main\\tfile.rs\"
+    /// +++ /dev/null
+    /// @@ -1 +0,0 @@
+    /// -fn main() {}
+    /// ";
+    /// let patch = Patch::from_str(s).unwrap();
+    ///
+    /// let path = patch.old_path(PrefixPolicy::Git).unwrap();
+    /// assert_eq!(path, Path::new("src").join("main\tfile.rs"));
+    /// assert_eq!(patch.new_path(PrefixPolicy::Git), None);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn old_path(&self, strip: PrefixPolicy<'_>) -> Option<Cow<'_, Path>> {
+        old_name(self, strip).map(native_path)
+    }
+
+    /// Returns the path of the new file, or `None` if it doesn't exist
+    ///
+    /// See [`Patch::old_path`](struct.Patch.html#method.old_path) for how the path is derived
+    /// from the file's name.
+    #[cfg(feature = "std")]
+    pub fn new_path(&self, strip: PrefixPolicy<'_>) -> Option<Cow<'_, Path>> {
+        new_name(self, strip).map(native_path)
+    }
+}
+
+impl<'a> Patch<'a, [u8]> {
+    /// Returns the path of the old file of a potentially non-utf8 patch, or `None` if it
+    /// doesn't exist
+    ///
+    /// This works like [`Patch::old_path`](struct.Patch.html#method.old_path), except that the
+    /// path is returned as bytes with every separator converted to `/`.
+    pub fn old_path(&self, strip: PrefixPolicy<'_>) -> Option<Cow<'_, [u8]>> {
+        old_name(self, strip).map(slash_path)
+    }
+
+    /// Returns the path of the new file of a potentially non-utf8 patch, or `None` if it
+    /// doesn't exist
+    ///
+    /// This works like [`Patch::new_path`](struct.Patch.html#method.new_path), except that the
+    /// path is returned as bytes with every separator converted to `/`.
+    pub fn new_path(&self, strip: PrefixPolicy<'_>) -> Option<Cow<'_, [u8]>> {
+        new_name(self, strip).map(slash_path)
+    }
+}
+
+fn old_name<'a, T: Text + ToOwned + ?Sized>(
+    patch: &'a Patch<'_, T>,
+    strip: PrefixPolicy<'_>,
+) -> Option<&'a T> {
+    if patch.status == FileStatus::Added {
+        return None;
+    }
+    file_name(patch.original(), strip, Side::Old)
+}
+
+fn new_name<'a, T: Text + ToOwned + ?Sized>(
+    patch: &'a Patch<'_, T>,
+    strip: PrefixPolicy<'_>,
+) -> Option<&'a T> {
+    if patch.status == FileStatus::Removed {
+        return None;
+    }
+    file_name(patch.modified(), strip, Side::New)
+}
+
+// Strips the prefix from the name of a file in a patch header, or returns `None` if it's
+// missing or `/dev/null`
+pub(crate) fn file_name<'a, T: Text + ?Sized>(
+    name: Option<&'a T>,
+    strip: PrefixPolicy<'_>,
+    side: Side,
+) -> Option<&'a T> {
+    let name = name.filter(|name| name.as_bytes() != DEV_NULL.as_bytes())?;
+    Some(strip.strip(name, side))
+}
+
+#[cfg(feature = "std")]
+fn native_path(name: &str) -> Cow<'_, Path> {
+    let foreign = if MAIN_SEPARATOR == '/' { '\\' } else { '/' };
+    if name.contains(foreign) {
+        let mut separator = [0; 4];
+        let separator = MAIN_SEPARATOR.encode_utf8(&mut separator);
+        Cow::Owned(PathBuf::from(name.replace(foreign, separator)))
+    } else {
+        Cow::Borrowed(Path::new(name))
+    }
+}
+
+fn slash_path(name: &[u8]) -> Cow<'_, [u8]> {
+    if name.contains(&b'\\') {
+        let path: Vec<u8> = name
+            .iter()
+            .map(|&b| if b == b'\\' { b'/' } else { b })
+            .collect();
+        Cow::Owned(path)
+    } else {
+        Cow::Borrowed(name)
+    }
+}
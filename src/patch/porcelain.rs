@@ -0,0 +1,540 @@
+//! A line oriented, machine readable representation of a Patch
+
+use super::{FileStatus, Hunk, HunkRange, Line, LineKind, Patch, DEFAULT_MODE};
+use alloc::{borrow::ToOwned, vec::Vec};
+use core::fmt::{self, Display, Formatter, Write};
+
+/// Struct used to output a `Patch` in the porcelain format, which is meant to be read by other
+/// programs rather than people
+///
+/// The porcelain format is stable: records will keep their meaning across releases of this
+/// crate, so scripts can rely on it. Each record is a single line ending in `\n`, made of
+/// fields separated by tabs, the first of which says what kind of record it is:
+///
+/// | Record | Meaning |
+/// |--------|---------|
+/// | `O` `<name>` | The name of the original file, if the patch has one |
+/// | `M` `<name>` | The name of the modified file, if the patch has one |
+/// | `S` `added` `<mode>` or `S` `removed` `<mode>` | The patch adds or removes its file, see [`FileStatus`], which has the given mode in octal. The mode may be left out, in which case it's `100644`. |
+/// | `H` `<old_start>` `<old_len>` `<new_start>` `<new_len>` \[`<function context>`\] | The start of a hunk, with the numbers of its `@@` header, followed by its function context if it has one |
+/// | `C` `<old_lineno>` `<new_lineno>` `<content>` | A context line |
+/// | `D` `<old_lineno>` `-` `<content>` | A deleted line |
+/// | `I` `-` `<new_lineno>` `<content>` | An inserted line |
+///
+/// The `O`, `M` and `S` records come before the first hunk. Line numbers are 1-based, and a
+/// line which isn't present in one of the files has a `-` in place of that line number.
+///
+/// Names, function contexts and line contents are escaped so that they never contain a tab or
+/// a newline. A backslash is written as `\\`, a tab as `\t`, a newline as `\n` and a carriage
+/// return as `\r`. Any other control character, as well as every byte which isn't part of
+/// valid UTF-8, is written as `\x` followed by two lowercase hex digits. The content of a line
+/// includes its line ending, so the last line of a file which doesn't end in a newline is
+/// simply the one whose content doesn't end with `\n`.
+///
+/// Porcelain output is read back with [`PorcelainPatch::parse`].
+///
+/// ```
+/// use diffy::{create_patch, PorcelainFormatter};
+///
+/// let patch = create_patch("a\nb\n", "a\nb\tc");
+/// let expected = "\
+/// O\toriginal
+/// M\tmodified
+/// H\t1\t2\t1\t2
+/// C\t1\t1\ta\\n
+/// D\t2\t-\tb\\n
+/// I\t-\t2\tb\\tc
+/// ";
+/// let f = PorcelainFormatter::new();
+/// assert_eq!(f.fmt_patch(&patch).to_string(), expected);
+/// ```
+///
+/// [`FileStatus`]: enum.FileStatus.html
+/// [`PorcelainPatch::parse`]: struct.PorcelainPatch.html#method.parse
+#[derive(Clone, Debug, Default)]
+pub struct PorcelainFormatter {
+    _private: (),
+}
+
+impl PorcelainFormatter {
+    /// Construct a new formatter
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a `Display` impl which can be used to print a Patch in the porcelain format
+    ///
+    /// As all non-UTF-8 bytes are escaped this works for patches of both text and bytes.
+    pub fn fmt_patch<'a, T: ToOwned + AsRef<[u8]> + ?Sized>(
+        &'a self,
+        patch: &'a Patch<'a, T>,
+    ) -> impl Display + 'a {
+        PorcelainDisplay { patch }
+    }
+}
+
+struct PorcelainDisplay<'a, T: ToOwned + ?Sized> {
+    patch: &'a Patch<'a, T>,
+}
+
+impl<T: ToOwned + AsRef<[u8]> + ?Sized> Display for PorcelainDisplay<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(original) = self.patch.original() {
+            writeln!(f, "O\t{}", Escaped(original.as_ref()))?;
+        }
+        if let Some(modified) = self.patch.modified() {
+            writeln!(f, "M\t{}", Escaped(modified.as_ref()))?;
+        }
+        match self.patch.status() {
+            FileStatus::Added => writeln!(f, "S\tadded\t{:o}", self.patch.mode())?,
+            FileStatus::Removed => writeln!(f, "S\tremoved\t{:o}", self.patch.mode())?,
+            FileStatus::Modified => {}
+        }
+
+        for (index, hunk) in self.patch.hunks().iter().enumerate() {
+            let (old, new) = (hunk.old_range(), hunk.new_range());
+            write!(
+                f,
+                "H\t{}\t{}\t{}\t{}",
+                old.start(),
+                old.len(),
+                new.start(),
+                new.len()
+            )?;
+            if let Some(function_context) = hunk.function_context() {
+                write!(f, "\t{}", Escaped(function_context.as_ref()))?;
+            }
+            writeln!(f)?;
+
+            for line in hunk.annotated_lines(index) {
+                let kind = match line.line.kind() {
+                    LineKind::Context => 'C',
+                    LineKind::Delete => 'D',
+                    LineKind::Insert => 'I',
+                };
+                writeln!(
+                    f,
+                    "{}\t{}\t{}\t{}",
+                    kind,
+                    Lineno(line.old_lineno),
+                    Lineno(line.new_lineno),
+                    Escaped(line.line.value().as_ref())
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+struct Lineno(Option<usize>);
+
+impl Display for Lineno {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(lineno) => write!(f, "{}", lineno),
+            None => f.write_char('-'),
+        }
+    }
+}
+
+// Writes bytes using the escaping scheme described on `PorcelainFormatter`
+struct Escaped<'a>(&'a [u8]);
+
+impl Display for Escaped<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut bytes = self.0;
+        while !bytes.is_empty() {
+            let (valid, invalid) = match core::str::from_utf8(bytes) {
+                Ok(s) => (s, &[][..]),
+                Err(e) => {
+                    let (valid, rest) = bytes.split_at(e.valid_up_to());
+                    let invalid_len = e.error_len().unwrap_or(rest.len());
+                    let valid = core::str::from_utf8(valid).map_err(|_| fmt::Error)?;
+                    (valid, &rest[..invalid_len])
+                }
+            };
+
+            for c in valid.chars() {
+                match c {
+                    '\\' => f.write_str("\\\\")?,
+                    '\t' => f.write_str("\\t")?,
+                    '\n' => f.write_str("\\n")?,
+                    '\r' => f.write_str("\\r")?,
+                    c if c.is_ascii_control() => write!(f, "\\x{:02x}", c as u8)?,
+                    c => f.write_char(c)?,
+                }
+            }
+            for byte in invalid {
+                write!(f, "\\x{:02x}", byte)?;
+            }
+
+            bytes = &bytes[valid.len() + invalid.len()..];
+        }
+        Ok(())
+    }
+}
+
+/// An error returned when parsing porcelain output with [`PorcelainPatch::parse`] fails
+///
+/// [`PorcelainPatch::parse`]: struct.PorcelainPatch.html#method.parse
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParsePorcelainError {
+    line: usize,
+    reason: &'static str,
+}
+
+impl ParsePorcelainError {
+    fn new(line: usize, reason: &'static str) -> Self {
+        Self { line, reason }
+    }
+
+    /// Returns the 1-based number of the line containing the malformed record
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl fmt::Display for ParsePorcelainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid porcelain record on line {}: {}",
+            self.line, self.reason
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParsePorcelainError {}
+
+/// A patch read back from the output of a [`PorcelainFormatter`]
+///
+/// As the contents of the lines have to be unescaped they can't be borrowed from the input, so
+/// a `PorcelainPatch` owns them and hands out a [`Patch`] borrowing from it with
+/// [`patch`](#method.patch).
+///
+/// ```
+/// use diffy::{create_patch, PorcelainFormatter, PorcelainPatch};
+///
+/// let patch = create_patch("a\nb\n", "a\nc\n");
+/// let porcelain = PorcelainFormatter::new().fmt_patch(&patch).to_string();
+///
+/// let parsed = PorcelainPatch::parse(&porcelain).unwrap();
+/// assert_eq!(parsed.patch().to_bytes(), patch.to_bytes());
+/// ```
+///
+/// [`PorcelainFormatter`]: struct.PorcelainFormatter.html
+/// [`Patch`]: struct.Patch.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PorcelainPatch {
+    original: Option<Vec<u8>>,
+    modified: Option<Vec<u8>>,
+    status: FileStatus,
+    mode: u32,
+    hunks: Vec<PorcelainHunk>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct PorcelainHunk {
+    old_range: HunkRange,
+    new_range: HunkRange,
+    function_context: Option<Vec<u8>>,
+    lines: Vec<(LineKind, Vec<u8>)>,
+}
+
+impl PorcelainPatch {
+    /// Parse the output of a [`PorcelainFormatter`]
+    ///
+    /// Besides the syntax of every record, this checks that the line numbers of each line
+    /// follow from its hunk's header and that every hunk has as many lines as its header says.
+    ///
+    /// [`PorcelainFormatter`]: struct.PorcelainFormatter.html
+    pub fn parse(input: &str) -> Result<Self, ParsePorcelainError> {
+        let mut patch = Self {
+            original: None,
+            modified: None,
+            status: FileStatus::Modified,
+            mode: DEFAULT_MODE,
+            hunks: Vec::new(),
+        };
+        // The next old and new line numbers of the current hunk
+        let mut next = (0, 0);
+
+        let mut records = 0;
+        for (index, record) in input.split_terminator('\n').enumerate() {
+            records += 1;
+            let lineno = index + 1;
+            let error = |reason| ParsePorcelainError::new(lineno, reason);
+            let fields: Vec<&str> = record.split('\t').collect();
+
+            match fields[..] {
+                [kind @ ("O" | "M"), name] => {
+                    if !patch.hunks.is_empty() {
+                        return Err(error("file name after the first hunk"));
+                    }
+                    let slot = match kind {
+                        "O" => &mut patch.original,
+                        _ => &mut patch.modified,
+                    };
+                    if slot.is_some() {
+                        return Err(error("duplicate file name"));
+                    }
+                    *slot = Some(unescape(name).ok_or_else(|| error("invalid escape"))?);
+                }
+                ["S", status, ref mode @ ..] if mode.len() <= 1 => {
+                    if !patch.hunks.is_empty() {
+                        return Err(error("file status after the first hunk"));
+                    }
+                    patch.status = match status {
+                        "added" => FileStatus::Added,
+                        "removed" => FileStatus::Removed,
+                        _ => return Err(error("unknown file status")),
+                    };
+                    if let Some(mode) = mode.first() {
+                        patch.mode =
+                            u32::from_str_radix(mode, 8).map_err(|_| error("invalid mode"))?;
+                    }
+                }
+                ["H", old_start, old_len, new_start, new_len, ref function_context @ ..]
+                    if function_context.len() <= 1 =>
+                {
+                    check_hunk_complete(patch.hunks.last(), lineno)?;
+
+                    let number = |s: &str| s.parse().map_err(|_| error("invalid number"));
+                    let old_range = HunkRange::new(number(old_start)?, number(old_len)?);
+                    let new_range = HunkRange::new(number(new_start)?, number(new_len)?);
+                    let function_context = match function_context.first() {
+                        Some(s) => Some(unescape(s).ok_or_else(|| error("invalid escape"))?),
+                        None => None,
+                    };
+                    next = (
+                        first_lineno(old_range).ok_or_else(|| error("line number overflow"))?,
+                        first_lineno(new_range).ok_or_else(|| error("line number overflow"))?,
+                    );
+                    patch.hunks.push(PorcelainHunk {
+                        old_range,
+                        new_range,
+                        function_context,
+                        lines: Vec::new(),
+                    });
+                }
+                [kind @ ("C" | "D" | "I"), old_lineno, new_lineno, content] => {
+                    let hunk = patch
+                        .hunks
+                        .last_mut()
+                        .ok_or_else(|| error("line outside of a hunk"))?;
+                    let kind = match kind {
+                        "C" => LineKind::Context,
+                        "D" => LineKind::Delete,
+                        _ => LineKind::Insert,
+                    };
+
+                    let expected =
+                        |present: bool, next: usize| Some(Some(next).filter(|_| present));
+                    if parse_lineno(old_lineno) != expected(kind != LineKind::Insert, next.0)
+                        || parse_lineno(new_lineno) != expected(kind != LineKind::Delete, next.1)
+                    {
+                        return Err(error("unexpected line number"));
+                    }
+                    next = match (
+                        next.0.checked_add(usize::from(kind != LineKind::Insert)),
+                        next.1.checked_add(usize::from(kind != LineKind::Delete)),
+                    ) {
+                        (Some(old), Some(new)) => (old, new),
+                        _ => return Err(error("line number overflow")),
+                    };
+
+                    let content = unescape(content).ok_or_else(|| error("invalid escape"))?;
+                    hunk.lines.push((kind, content));
+                }
+                _ => return Err(error("unknown record")),
+            }
+        }
+        check_hunk_complete(patch.hunks.last(), records + 1)?;
+
+        Ok(patch)
+    }
+
+    /// Returns the parsed patch, borrowing the contents of its lines from `self`
+    pub fn patch(&self) -> Patch<'_, [u8]> {
+        let hunks = self
+            .hunks
+            .iter()
+            .map(|hunk| {
+                Hunk::new(
+                    hunk.old_range,
+                    hunk.new_range,
+                    hunk.function_context.as_deref(),
+                    hunk.lines
+                        .iter()
+                        .map(|(kind, content)| Line::new(*kind, &content[..]))
+                        .collect(),
+                )
+            })
+            .collect();
+        Patch::new(self.original.as_deref(), self.modified.as_deref(), hunks)
+            .with_status(self.status)
+            .with_mode(self.mode)
+    }
+}
+
+// Returns the line number of the first line of a hunk, if the line numbers of all of its lines
+// fit in a `usize`
+fn first_lineno(range: HunkRange) -> Option<usize> {
+    range.start().checked_add(range.len())?.checked_add(1)?;
+    Some(range.to_std_range().start + 1)
+}
+
+// Parses the line number of a line record, where `-` stands for a missing one
+fn parse_lineno(s: &str) -> Option<Option<usize>> {
+    match s {
+        "-" => Some(None),
+        s => s.parse().ok().map(Some),
+    }
+}
+
+// A new hunk, or the end of the input, may only follow a hunk once it has all of its lines
+fn check_hunk_complete(
+    hunk: Option<&PorcelainHunk>,
+    lineno: usize,
+) -> Result<(), ParsePorcelainError> {
+    if let Some(hunk) = hunk {
+        let old_len = hunk
+            .lines
+            .iter()
+            .filter(|(k, _)| *k != LineKind::Insert)
+            .count();
+        let new_len = hunk
+            .lines
+            .iter()
+            .filter(|(k, _)| *k != LineKind::Delete)
+            .count();
+        if old_len != hunk.old_range.len() || new_len != hunk.new_range.len() {
+            return Err(ParsePorcelainError::new(
+                lineno,
+                "hunk doesn't match the lengths in its header",
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Reverses the escaping done by `Escaped`, returning `None` for an invalid escape sequence
+fn unescape(s: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+
+        let (&escape, tail) = rest.split_first()?;
+        rest = tail;
+        match escape {
+            b'\\' => bytes.push(b'\\'),
+            b't' => bytes.push(b'\t'),
+            b'n' => bytes.push(b'\n'),
+            b'r' => bytes.push(b'\r'),
+            b'x' if rest.len() >= 2 => {
+                let hex = core::str::from_utf8(&rest[..2]).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+                rest = &rest[2..];
+            }
+            _ => return None,
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PorcelainFormatter, PorcelainPatch};
+    use crate::{create_patch, create_patch_bytes, DiffOptions, FileStatus, Patch};
+    use alloc::{format, string::ToString};
+
+    fn round_trip<T: ToOwned + AsRef<[u8]> + ?Sized>(
+        patch: &Patch<'_, T>,
+    ) -> alloc::string::String {
+        let porcelain = PorcelainFormatter::new().fmt_patch(patch).to_string();
+        let parsed = PorcelainPatch::parse(&porcelain).unwrap();
+        assert_eq!(parsed.patch().to_bytes(), patch.to_bytes());
+        assert_eq!(
+            PorcelainFormatter::new()
+                .fmt_patch(&parsed.patch())
+                .to_string(),
+            porcelain
+        );
+        porcelain
+    }
+
+    #[test]
+    fn round_trips() {
+        let original = "fn a() {\n\tx\n}\n\nfn b() {\n    1\n    2\n    3\n    4\n}\n";
+        let modified = "fn a() {\n\tx\\y\n}\n\nfn b() {\n    1\n    2\n    3\n    four\r\n}";
+        let patch = DiffOptions::new()
+            .set_context_len(1)
+            .create_patch(original, modified);
+        round_trip(&patch);
+        let text = patch.to_string();
+        round_trip(&Patch::from_str(&text).unwrap());
+
+        let patch = create_patch("", "new\n").with_status(FileStatus::Added);
+        assert_eq!(
+            round_trip(&patch),
+            "O\toriginal\nM\tmodified\nS\tadded\t100644\nH\t0\t0\t1\t1\nI\t-\t1\tnew\\n\n"
+        );
+        let patch = create_patch("", "#!/bin/sh\n")
+            .with_status(FileStatus::Added)
+            .with_mode(0o100755);
+        assert!(round_trip(&patch).contains("S\tadded\t100755\n"));
+        let parsed = PorcelainPatch::parse(&round_trip(&patch.reverse())).unwrap();
+        assert_eq!(parsed.patch().mode(), 0o100755);
+        assert_eq!(parsed.patch().status(), FileStatus::Removed);
+        // The mode may be left out
+        let parsed = PorcelainPatch::parse("S\tadded\n").unwrap();
+        assert_eq!(parsed.patch().mode(), 0o100644);
+        round_trip(&create_patch("gone\n", "").with_status(FileStatus::Removed));
+        round_trip(&create_patch("same\n", "same\n"));
+
+        let patch = create_patch_bytes(b"\xff\x00a\tb\n\xe2\x82\xac\n", b"\xe2\x82\xac\n\xe2\x82");
+        let porcelain = round_trip(&patch);
+        assert!(porcelain.contains("D\t1\t-\t\\xff\\x00a\\tb\\n\n"));
+        assert!(porcelain.contains("C\t2\t1\t\u{20ac}\\n\n"));
+        assert!(porcelain.contains("I\t-\t2\t\\xe2\\x82\n"));
+
+        let patch =
+            Patch::from_str("--- \"a\\tb\"\n+++ \"c\\\\d\"\n@@ -1 +1 @@ f\tn\n-x\n+y\n").unwrap();
+        assert_eq!(
+            round_trip(&patch),
+            "O\ta\\tb\nM\tc\\\\d\nH\t1\t1\t1\t1\tf\\tn\\n\nD\t1\t-\tx\\n\nI\t-\t1\ty\\n\n"
+        );
+    }
+
+    #[test]
+    fn invalid_records() {
+        let error = |input: &str| PorcelainPatch::parse(input).unwrap_err().line();
+
+        assert_eq!(error("X\t1\n"), 1);
+        assert_eq!(error("O\ta\nO\tb\n"), 2);
+        assert_eq!(error("S\tchanged\n"), 1);
+        assert_eq!(error("S\tadded\t100855\n"), 1);
+        assert_eq!(error("S\tadded\t100644\t1\n"), 1);
+        assert_eq!(error("C\t1\t1\ta\\n\n"), 1);
+        assert_eq!(error("H\t1\t1\t1\t1\nC\t2\t1\ta\\n\n"), 2);
+        assert_eq!(error("H\t1\t1\t1\t1\nC\t1\t1\ta\\q\n"), 2);
+        assert_eq!(error("H\t1\t2\t1\t2\nC\t1\t1\ta\\n\nH\t5\t1\t5\t1\n"), 3);
+        assert_eq!(error("H\t1\t2\t1\t2\nC\t1\t1\ta\\n\n"), 3);
+        assert_eq!(error("H\t1\tx\t1\t1\n"), 1);
+        assert_eq!(error(&format!("H\t{}\t5\t1\t1\n", usize::MAX)), 1);
+        let input = format!("H\t1\t1\t{}\t0\nI\t-\t{}\tx\n", usize::MAX - 1, usize::MAX);
+        assert_eq!(error(&input), 2);
+        assert!(PorcelainPatch::parse("")
+            .unwrap()
+            .patch()
+            .hunks()
+            .is_empty());
+    }
+}
@@ -0,0 +1,444 @@
+//! Renderer agnostic representation of a formatted patch
+
+use super::{Hunk, Line, Patch};
+use crate::{
+    range::SliceLike,
+    utils::{is_indentation_only, split_line_ending},
+};
+use alloc::{borrow::Cow, format, vec, vec::Vec};
+
+/// The semantic style of a [`StyledSpan`]
+///
+/// Unlike the ANSI styles used by [`PatchFormatter`](struct.PatchFormatter.html) these are meant
+/// to be mapped by the caller onto whatever styling their renderer supports.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SpanStyle {
+    /// Text of a line present in both the old and new file
+    Context,
+    /// Text of an inserted line
+    Add,
+    /// Text of a deleted line
+    Delete,
+    /// Patch and hunk headers
+    Header,
+    /// The part of an inserted line which differs from the line it replaced
+    InlineAdd,
+    /// The part of a deleted line which differs from the line that replaced it
+    InlineDelete,
+    /// Text of a deleted or inserted line which only differs from the line it's paired with in
+    /// its indentation, see [`PatchFormatter::mark_indentation_changes`]
+    ///
+    /// [`PatchFormatter::mark_indentation_changes`]: struct.PatchFormatter.html#method.mark_indentation_changes
+    Indentation,
+}
+
+/// A run of text sharing a single [`SpanStyle`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StyledSpan<'a> {
+    /// The text of the span, without any line ending
+    pub text: Cow<'a, str>,
+    /// How the text should be styled
+    pub style: SpanStyle,
+}
+
+/// A single rendered row made up of [`StyledSpan`]s
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StyledRow<'a> {
+    /// The 1-based line number in the old file, if this row shows a line from it
+    pub old_line: Option<usize>,
+    /// The 1-based line number in the new file, if this row shows a line from it
+    pub new_line: Option<usize>,
+    /// The spans making up this row
+    pub spans: Vec<StyledSpan<'a>>,
+}
+
+/// A row in a side-by-side rendering of a patch
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SplitRow<'a> {
+    /// A patch or hunk header spanning both sides
+    Header(StyledRow<'a>),
+    /// A line from the old file on the left and/or a line from the new file on the right
+    Lines {
+        old: Option<StyledRow<'a>>,
+        new: Option<StyledRow<'a>>,
+    },
+}
+
+pub(super) fn unified_rows<'a>(
+    patch: &'a Patch<'a, str>,
+    mark_indentation: bool,
+) -> Vec<StyledRow<'a>> {
+    let mut rows = Vec::new();
+    patch_header_rows(patch, |row| rows.push(row));
+
+    for hunk in patch.hunks() {
+        rows.push(hunk_header_row(hunk));
+
+        for block in blocks(hunk) {
+            match block {
+                Block::Context { old, new, text } => rows.push(StyledRow {
+                    old_line: Some(old),
+                    new_line: Some(new),
+                    spans: signed_spans(
+                        " ",
+                        SpanStyle::Context,
+                        line_spans(text, None, SpanStyle::Context),
+                    ),
+                }),
+                Block::Change { deleted, inserted } => {
+                    for (i, &(lineno, text)) in deleted.iter().enumerate() {
+                        let other = inserted.get(i).map(|&(_, other)| other);
+                        let style = change_style(text, other, SpanStyle::Delete, mark_indentation);
+                        rows.push(StyledRow {
+                            old_line: Some(lineno),
+                            new_line: None,
+                            spans: signed_spans("-", style, line_spans(text, other, style)),
+                        });
+                    }
+                    for (i, &(lineno, text)) in inserted.iter().enumerate() {
+                        let other = deleted.get(i).map(|&(_, other)| other);
+                        let style = change_style(text, other, SpanStyle::Add, mark_indentation);
+                        rows.push(StyledRow {
+                            old_line: None,
+                            new_line: Some(lineno),
+                            spans: signed_spans("+", style, line_spans(text, other, style)),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    rows
+}
+
+pub(super) fn split_rows<'a>(
+    patch: &'a Patch<'a, str>,
+    mark_indentation: bool,
+) -> Vec<SplitRow<'a>> {
+    let mut rows = Vec::new();
+    patch_header_rows(patch, |row| rows.push(SplitRow::Header(row)));
+
+    for hunk in patch.hunks() {
+        rows.push(SplitRow::Header(hunk_header_row(hunk)));
+
+        for block in blocks(hunk) {
+            match block {
+                Block::Context { old, new, text } => rows.push(SplitRow::Lines {
+                    old: Some(StyledRow {
+                        old_line: Some(old),
+                        new_line: None,
+                        spans: line_spans(text, None, SpanStyle::Context),
+                    }),
+                    new: Some(StyledRow {
+                        old_line: None,
+                        new_line: Some(new),
+                        spans: line_spans(text, None, SpanStyle::Context),
+                    }),
+                }),
+                Block::Change { deleted, inserted } => {
+                    for i in 0..deleted.len().max(inserted.len()) {
+                        let d = deleted.get(i).copied();
+                        let n = inserted.get(i).copied();
+                        rows.push(SplitRow::Lines {
+                            old: d.map(|(lineno, text)| {
+                                let other = n.map(|n| n.1);
+                                let style =
+                                    change_style(text, other, SpanStyle::Delete, mark_indentation);
+                                StyledRow {
+                                    old_line: Some(lineno),
+                                    new_line: None,
+                                    spans: line_spans(text, other, style),
+                                }
+                            }),
+                            new: n.map(|(lineno, text)| {
+                                let other = d.map(|d| d.1);
+                                let style =
+                                    change_style(text, other, SpanStyle::Add, mark_indentation);
+                                StyledRow {
+                                    old_line: None,
+                                    new_line: Some(lineno),
+                                    spans: line_spans(text, other, style),
+                                }
+                            }),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    rows
+}
+
+fn patch_header_rows<'a>(patch: &'a Patch<'a, str>, mut push: impl FnMut(StyledRow<'a>)) {
+    for (prefix, filename) in [("--- ", &patch.original), ("+++ ", &patch.modified)] {
+        if let Some(filename) = filename {
+            push(header_row(format!("{}{}", prefix, filename).into()));
+        }
+    }
+}
+
+fn hunk_header_row<'a>(hunk: &Hunk<'a, str>) -> StyledRow<'a> {
+    let mut header = format!("@@ -{} +{} @@", hunk.old_range(), hunk.new_range());
+    if let Some(ctx) = hunk.function_context() {
+        header.push(' ');
+        header.push_str(split_line_ending(ctx).0);
+    }
+    header_row(header.into())
+}
+
+fn header_row(text: Cow<'_, str>) -> StyledRow<'_> {
+    StyledRow {
+        old_line: None,
+        new_line: None,
+        spans: vec![StyledSpan {
+            text,
+            style: SpanStyle::Header,
+        }],
+    }
+}
+
+fn signed_spans<'a>(
+    sign: &'static str,
+    style: SpanStyle,
+    mut spans: Vec<StyledSpan<'a>>,
+) -> Vec<StyledSpan<'a>> {
+    spans.insert(
+        0,
+        StyledSpan {
+            text: sign.into(),
+            style,
+        },
+    );
+    spans
+}
+
+// The style of a changed line, which is only marked as an indentation change when requested
+fn change_style(line: &str, other: Option<&str>, style: SpanStyle, mark: bool) -> SpanStyle {
+    match other {
+        Some(other) if mark && is_indentation_only(line, other) => SpanStyle::Indentation,
+        _ => style,
+    }
+}
+
+// Splits a line into spans, highlighting the section which differs from `other` when the two
+// lines share a common prefix or suffix
+fn line_spans<'a>(line: &'a str, other: Option<&str>, style: SpanStyle) -> Vec<StyledSpan<'a>> {
+    let line = split_line_ending(line).0;
+    let inline = match style {
+        SpanStyle::Add => SpanStyle::InlineAdd,
+        SpanStyle::Delete => SpanStyle::InlineDelete,
+        style => style,
+    };
+
+    let (prefix, suffix) = match other.map(|other| split_line_ending(other).0) {
+        Some(other) if style != SpanStyle::Indentation => {
+            let prefix = line.common_prefix_len(other);
+            let suffix = line[prefix..].common_suffix_len(&other[prefix..]);
+            (prefix, suffix)
+        }
+        _ => (0, 0),
+    };
+
+    if prefix + suffix == 0 {
+        return vec![StyledSpan {
+            text: line.into(),
+            style,
+        }];
+    }
+
+    let middle_end = line.len() - suffix;
+    [
+        (&line[..prefix], style),
+        (&line[prefix..middle_end], inline),
+        (&line[middle_end..], style),
+    ]
+    .into_iter()
+    .filter(|(text, _)| !text.is_empty())
+    .map(|(text, style)| StyledSpan {
+        text: text.into(),
+        style,
+    })
+    .collect()
+}
+
+enum Block<'a> {
+    Context {
+        old: usize,
+        new: usize,
+        text: &'a str,
+    },
+    Change {
+        deleted: Vec<(usize, &'a str)>,
+        inserted: Vec<(usize, &'a str)>,
+    },
+}
+
+// Groups the lines of a hunk into context lines and runs of deletions followed by insertions,
+// annotating each line with its line number
+fn blocks<'a>(hunk: &Hunk<'a, str>) -> Vec<Block<'a>> {
+    let mut blocks = Vec::new();
+
+    for annotated in hunk.annotated_lines(0) {
+        match (*annotated.line, annotated.old_lineno, annotated.new_lineno) {
+            (Line::Context(text), Some(old), Some(new)) => {
+                blocks.push(Block::Context { old, new, text })
+            }
+            (Line::Delete(text), Some(old), _) => match blocks.last_mut() {
+                Some(Block::Change { deleted, inserted }) if inserted.is_empty() => {
+                    deleted.push((old, text))
+                }
+                _ => blocks.push(Block::Change {
+                    deleted: vec![(old, text)],
+                    inserted: Vec::new(),
+                }),
+            },
+            (Line::Insert(text), _, Some(new)) => match blocks.last_mut() {
+                Some(Block::Change { inserted, .. }) => inserted.push((new, text)),
+                _ => blocks.push(Block::Change {
+                    deleted: Vec::new(),
+                    inserted: vec![(new, text)],
+                }),
+            },
+            _ => unreachable!("line numbers always match the kind of line"),
+        }
+    }
+
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_patch;
+
+    fn span(text: &str, style: SpanStyle) -> StyledSpan<'_> {
+        StyledSpan {
+            text: text.into(),
+            style,
+        }
+    }
+
+    #[test]
+    fn unified() {
+        let patch = create_patch("a\nfoo bar\nc\n", "a\nfoo baz\nc\nd\n");
+        let rows = unified_rows(&patch, false);
+
+        let expected = vec![
+            header_row("--- original".into()),
+            header_row("+++ modified".into()),
+            header_row("@@ -1,3 +1,4 @@".into()),
+            StyledRow {
+                old_line: Some(1),
+                new_line: Some(1),
+                spans: vec![span(" ", SpanStyle::Context), span("a", SpanStyle::Context)],
+            },
+            StyledRow {
+                old_line: Some(2),
+                new_line: None,
+                spans: vec![
+                    span("-", SpanStyle::Delete),
+                    span("foo ba", SpanStyle::Delete),
+                    span("r", SpanStyle::InlineDelete),
+                ],
+            },
+            StyledRow {
+                old_line: None,
+                new_line: Some(2),
+                spans: vec![
+                    span("+", SpanStyle::Add),
+                    span("foo ba", SpanStyle::Add),
+                    span("z", SpanStyle::InlineAdd),
+                ],
+            },
+            StyledRow {
+                old_line: Some(3),
+                new_line: Some(3),
+                spans: vec![span(" ", SpanStyle::Context), span("c", SpanStyle::Context)],
+            },
+            StyledRow {
+                old_line: None,
+                new_line: Some(4),
+                spans: vec![span("+", SpanStyle::Add), span("d", SpanStyle::Add)],
+            },
+        ];
+        assert_eq!(rows, expected);
+    }
+
+    #[test]
+    fn split() {
+        let patch = create_patch("one\ntwo\n", "uno\ntwo\nthree\n");
+        let rows = split_rows(&patch, false);
+
+        let row = |old_line, new_line, spans| StyledRow {
+            old_line,
+            new_line,
+            spans,
+        };
+        let expected = vec![
+            SplitRow::Header(header_row("--- original".into())),
+            SplitRow::Header(header_row("+++ modified".into())),
+            SplitRow::Header(header_row("@@ -1,2 +1,3 @@".into())),
+            SplitRow::Lines {
+                old: Some(row(Some(1), None, vec![span("one", SpanStyle::Delete)])),
+                new: Some(row(None, Some(1), vec![span("uno", SpanStyle::Add)])),
+            },
+            SplitRow::Lines {
+                old: Some(row(Some(2), None, vec![span("two", SpanStyle::Context)])),
+                new: Some(row(None, Some(2), vec![span("two", SpanStyle::Context)])),
+            },
+            SplitRow::Lines {
+                old: None,
+                new: Some(row(None, Some(3), vec![span("three", SpanStyle::Add)])),
+            },
+        ];
+        assert_eq!(rows, expected);
+    }
+
+    #[test]
+    fn indentation() {
+        let patch = create_patch("{\nfoo();\nbar();\n}\n", "{\n    foo();\n\tbaz();\n}\n");
+
+        let changed = |rows: Vec<StyledRow<'_>>| -> Vec<Vec<SpanStyle>> {
+            rows.into_iter()
+                .filter(|row| row.old_line.is_none() != row.new_line.is_none())
+                .map(|row| row.spans.into_iter().map(|s| s.style).collect())
+                .collect()
+        };
+
+        let rows = changed(unified_rows(&patch, true));
+        assert_eq!(
+            rows,
+            vec![
+                vec![SpanStyle::Indentation; 2],
+                vec![
+                    SpanStyle::Delete,
+                    SpanStyle::InlineDelete,
+                    SpanStyle::Delete
+                ],
+                vec![SpanStyle::Indentation; 2],
+                vec![SpanStyle::Add, SpanStyle::InlineAdd, SpanStyle::Add],
+            ]
+        );
+
+        // Without marking, the added indentation is highlighted like any other inline change
+        let rows = changed(unified_rows(&patch, false));
+        assert_eq!(
+            rows[2],
+            vec![SpanStyle::Add, SpanStyle::InlineAdd, SpanStyle::Add]
+        );
+
+        let rows = split_rows(&patch, true);
+        match &rows[4] {
+            SplitRow::Lines {
+                old: Some(old),
+                new: Some(new),
+            } => {
+                assert_eq!(old.spans, vec![span("foo();", SpanStyle::Indentation)]);
+                assert_eq!(new.spans, vec![span("    foo();", SpanStyle::Indentation)]);
+            }
+            row => panic!("unexpected row {:?}", row),
+        }
+    }
+}
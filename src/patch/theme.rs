@@ -0,0 +1,516 @@
+//! Color themes and color selection for formatting patches
+
+use super::LineKind;
+use nu_ansi_term::{Color, Style};
+
+/// The styles used for each element of a patch when formatting it with color
+///
+/// A theme is chosen from one of the presets and set with [`PatchFormatter::with_theme`].
+///
+/// ```
+/// use diffy::{create_patch, PatchFormatter, Theme};
+///
+/// let patch = create_patch("a\n", "b\n");
+/// let f = PatchFormatter::new()
+///     .with_color()
+///     .with_theme(Theme::colorblind());
+/// print!("{}", f.fmt_patch(&patch));
+/// ```
+///
+/// [`PatchFormatter::with_theme`]: struct.PatchFormatter.html#method.with_theme
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Theme {
+    pub(super) context: Style,
+    pub(super) delete: Style,
+    pub(super) insert: Style,
+    pub(super) inline_delete: Style,
+    pub(super) inline_insert: Style,
+    pub(super) indentation: Style,
+    pub(super) inline_indentation: Style,
+    pub(super) hunk_header: Style,
+    pub(super) patch_header: Style,
+    pub(super) function_context: Style,
+}
+
+impl Theme {
+    /// The default theme, with red deletions and green insertions for a dark background
+    pub fn dark() -> Self {
+        Self {
+            context: Style::new(),
+            delete: Color::Red.normal(),
+            insert: Color::Green.normal(),
+            inline_delete: Color::Red.reverse(),
+            inline_insert: Color::Green.reverse(),
+            indentation: Color::Yellow.normal(),
+            inline_indentation: Color::Yellow.reverse(),
+            hunk_header: Color::Cyan.normal(),
+            patch_header: Style::new().bold(),
+            function_context: Style::new(),
+        }
+    }
+
+    /// A theme using darker colors which remain readable on a light background
+    pub fn light() -> Self {
+        Self {
+            context: Style::new(),
+            delete: Color::Fixed(124).normal(),
+            insert: Color::Fixed(28).normal(),
+            inline_delete: Color::Fixed(124).reverse(),
+            inline_insert: Color::Fixed(28).reverse(),
+            indentation: Color::Fixed(130).normal(),
+            inline_indentation: Color::Fixed(130).reverse(),
+            hunk_header: Color::Blue.normal(),
+            patch_header: Style::new().bold(),
+            function_context: Style::new(),
+        }
+    }
+
+    /// A theme with orange deletions and blue insertions, which can be told apart with the
+    /// common forms of color blindness
+    pub fn colorblind() -> Self {
+        Self {
+            context: Style::new(),
+            delete: Color::Fixed(208).normal(),
+            insert: Color::Fixed(33).normal(),
+            inline_delete: Color::Fixed(208).reverse(),
+            inline_insert: Color::Fixed(33).reverse(),
+            indentation: Color::Purple.normal(),
+            inline_indentation: Color::Purple.reverse(),
+            hunk_header: Color::Cyan.normal(),
+            patch_header: Style::new().bold(),
+            function_context: Style::new(),
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+/// Text attributes, like bold or dimmed, added on top of the colors of a [`Theme`]
+///
+/// ```
+/// use diffy::Attributes;
+///
+/// let attributes = Attributes::new().bold().underline();
+/// assert!(attributes.is_bold());
+/// assert!(!attributes.is_dimmed());
+/// ```
+///
+/// [`Theme`]: struct.Theme.html
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Attributes {
+    bold: bool,
+    dimmed: bool,
+    italic: bool,
+    underline: bool,
+}
+
+impl Attributes {
+    /// Construct attributes which don't change the text at all
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Render the text bold
+    pub fn bold(mut self) -> Self {
+        self.bold = true;
+        self
+    }
+
+    /// Render the text dimmed, also known as faint
+    pub fn dimmed(mut self) -> Self {
+        self.dimmed = true;
+        self
+    }
+
+    /// Render the text in italics
+    pub fn italic(mut self) -> Self {
+        self.italic = true;
+        self
+    }
+
+    /// Render the text underlined
+    pub fn underline(mut self) -> Self {
+        self.underline = true;
+        self
+    }
+
+    /// Returns `true` if the text is rendered bold
+    pub fn is_bold(&self) -> bool {
+        self.bold
+    }
+
+    /// Returns `true` if the text is rendered dimmed
+    pub fn is_dimmed(&self) -> bool {
+        self.dimmed
+    }
+
+    /// Returns `true` if the text is rendered in italics
+    pub fn is_italic(&self) -> bool {
+        self.italic
+    }
+
+    /// Returns `true` if the text is rendered underlined
+    pub fn is_underline(&self) -> bool {
+        self.underline
+    }
+
+    // Adds the attributes to a style, keeping the ones it already has
+    pub(super) fn apply(self, mut style: Style) -> Style {
+        style.is_bold |= self.bold;
+        style.is_dimmed |= self.dimmed;
+        style.is_italic |= self.italic;
+        style.is_underline |= self.underline;
+        style
+    }
+}
+
+/// The text attributes of each kind of line when formatting a patch with color, set with
+/// [`PatchFormatter::style`]
+///
+/// Attributes are added on top of the colors of the formatter's [`Theme`]. Lines which are
+/// highlighted or marked as indentation changes keep the attributes of their kind of line.
+///
+/// ```
+/// use diffy::{create_patch, Attributes, PatchFormatter, StyleConfig};
+///
+/// let patch = create_patch("a\nb\nc\n", "a\nB\nc\n");
+/// let style = StyleConfig::new()
+///     .context(Attributes::new().dimmed())
+///     .delete(Attributes::new().bold())
+///     .insert(Attributes::new().bold());
+/// let f = PatchFormatter::new().with_color().style(style);
+/// let formatted = f.fmt_patch(&patch).to_string();
+///
+/// // Context lines are dimmed, and changed lines are bold on top of their theme's colors
+/// assert!(formatted.contains("\u{1b}[2m a\n"));
+/// assert!(formatted.contains("\u{1b}[1;31m-b\n"));
+/// assert!(formatted.contains("\u{1b}[1;32m+B\n"));
+/// ```
+///
+/// [`PatchFormatter::style`]: struct.PatchFormatter.html#method.style
+/// [`Theme`]: struct.Theme.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StyleConfig {
+    context: Attributes,
+    delete: Attributes,
+    insert: Attributes,
+    hunk_header: Attributes,
+    color_hunk_header: bool,
+}
+
+impl StyleConfig {
+    /// Construct a new `StyleConfig` with default settings, which match how `git diff` renders
+    /// a patch
+    ///
+    /// ## Defaults
+    /// * context = Attributes::new()
+    /// * delete = Attributes::new()
+    /// * insert = Attributes::new()
+    /// * hunk_header = Attributes::new()
+    /// * color_hunk_header = true
+    pub fn new() -> Self {
+        Self {
+            context: Attributes::new(),
+            delete: Attributes::new(),
+            insert: Attributes::new(),
+            hunk_header: Attributes::new(),
+            color_hunk_header: true,
+        }
+    }
+
+    /// A style rendering context lines dimmed and changed lines bold, drawing attention to
+    /// the changes
+    pub fn emphasize_changes() -> Self {
+        Self::new()
+            .context(Attributes::new().dimmed())
+            .delete(Attributes::new().bold())
+            .insert(Attributes::new().bold())
+    }
+
+    /// Set the attributes of context lines
+    pub fn context(mut self, attributes: Attributes) -> Self {
+        self.context = attributes;
+        self
+    }
+
+    /// Set the attributes of deleted lines
+    pub fn delete(mut self, attributes: Attributes) -> Self {
+        self.delete = attributes;
+        self
+    }
+
+    /// Set the attributes of inserted lines
+    pub fn insert(mut self, attributes: Attributes) -> Self {
+        self.insert = attributes;
+        self
+    }
+
+    /// Set the attributes of the `@@ -a,b +c,d @@` line starting each hunk
+    pub fn hunk_header(mut self, attributes: Attributes) -> Self {
+        self.hunk_header = attributes;
+        self
+    }
+
+    /// Choose whether the `@@ -a,b +c,d @@` line starting each hunk is rendered in the hunk
+    /// header color of the theme, or in the terminal's default color
+    pub fn color_hunk_header(mut self, enable: bool) -> Self {
+        self.color_hunk_header = enable;
+        self
+    }
+
+    // Returns the attributes of a kind of line
+    pub(super) fn line_attributes(&self, kind: LineKind) -> Attributes {
+        match kind {
+            LineKind::Context => self.context,
+            LineKind::Delete => self.delete,
+            LineKind::Insert => self.insert,
+        }
+    }
+
+    // Returns the style of hunk headers given the theme's one
+    pub(super) fn hunk_header_style(&self, theme: Style) -> Style {
+        let style = if self.color_hunk_header {
+            theme
+        } else {
+            Style::new()
+        };
+        self.hunk_header.apply(style)
+    }
+}
+
+impl Default for StyleConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// When a [`PatchFormatter`] should use color, as set with
+/// [`PatchFormatter::color_choice`]
+///
+/// [`PatchFormatter`]: struct.PatchFormatter.html
+/// [`PatchFormatter::color_choice`]: struct.PatchFormatter.html#method.color_choice
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ColorChoice {
+    /// Always use color
+    Always,
+    /// Use color unless the `NO_COLOR` environment variable is set to a non-empty value or
+    /// stdout isn't a terminal. Without the `std` feature this never uses color.
+    Auto,
+    /// Never use color
+    #[default]
+    Never,
+}
+
+impl ColorChoice {
+    // Decides whether to use color, looking at the environment for `Auto`
+    pub(super) fn use_color(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            #[cfg(feature = "std")]
+            ColorChoice::Auto => auto_color(
+                std::env::var_os("NO_COLOR").as_deref(),
+                terminal::stdout_is_terminal(),
+            ),
+            #[cfg(not(feature = "std"))]
+            ColorChoice::Auto => false,
+        }
+    }
+}
+
+// See https://no-color.org, only a non-empty `NO_COLOR` disables color
+#[cfg(feature = "std")]
+fn auto_color(no_color: Option<&std::ffi::OsStr>, is_terminal: bool) -> bool {
+    no_color.map_or(true, |v| v.is_empty()) && is_terminal
+}
+
+#[cfg(feature = "std")]
+mod terminal {
+    #[cfg(unix)]
+    pub fn stdout_is_terminal() -> bool {
+        use std::os::raw::c_int;
+
+        extern "C" {
+            fn isatty(fd: c_int) -> c_int;
+        }
+
+        // SAFETY: `isatty` only inspects the file descriptor and is fine to call on any value
+        unsafe { isatty(1) == 1 }
+    }
+
+    #[cfg(windows)]
+    pub fn stdout_is_terminal() -> bool {
+        use std::os::raw::c_void;
+
+        const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn GetStdHandle(std_handle: u32) -> *mut c_void;
+            fn GetConsoleMode(console_handle: *mut c_void, mode: *mut u32) -> i32;
+        }
+
+        let mut mode = 0;
+        // SAFETY: `GetConsoleMode` fails gracefully for handles which aren't a console, including
+        // a null or invalid handle returned by `GetStdHandle`
+        unsafe { GetConsoleMode(GetStdHandle(STD_OUTPUT_HANDLE), &mut mode) != 0 }
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    pub fn stdout_is_terminal() -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Attributes, ColorChoice, StyleConfig, Theme};
+    use crate::{create_patch, PatchFormatter};
+    use alloc::string::ToString;
+
+    #[test]
+    fn theme_presets() {
+        let patch = create_patch("a\nb\nc\n", "a\nB\nc\n");
+        let render = |theme| {
+            PatchFormatter::new()
+                .with_color()
+                .with_theme(theme)
+                .fmt_patch(&patch)
+                .to_string()
+        };
+
+        let dark = render(Theme::dark());
+        assert_eq!(dark, render(Theme::default()));
+        assert_eq!(
+            dark,
+            PatchFormatter::new()
+                .with_color()
+                .fmt_patch(&patch)
+                .to_string()
+        );
+        assert_eq!(
+            dark,
+            concat!(
+                "\u{1b}[1m--- original\n+++ modified\n\u{1b}[0m",
+                "\u{1b}[36m@@ -1,3 +1,3 @@\u{1b}[0m\n",
+                " a\n",
+                "\u{1b}[31m-b\n\u{1b}[0m",
+                "\u{1b}[32m+B\n\u{1b}[0m",
+                " c\n",
+            )
+        );
+
+        assert_eq!(
+            render(Theme::light()),
+            concat!(
+                "\u{1b}[1m--- original\n+++ modified\n\u{1b}[0m",
+                "\u{1b}[34m@@ -1,3 +1,3 @@\u{1b}[0m\n",
+                " a\n",
+                "\u{1b}[38;5;124m-b\n\u{1b}[0m",
+                "\u{1b}[38;5;28m+B\n\u{1b}[0m",
+                " c\n",
+            )
+        );
+        assert_eq!(
+            render(Theme::colorblind()),
+            concat!(
+                "\u{1b}[1m--- original\n+++ modified\n\u{1b}[0m",
+                "\u{1b}[36m@@ -1,3 +1,3 @@\u{1b}[0m\n",
+                " a\n",
+                "\u{1b}[38;5;208m-b\n\u{1b}[0m",
+                "\u{1b}[38;5;33m+B\n\u{1b}[0m",
+                " c\n",
+            )
+        );
+    }
+
+    #[test]
+    fn style_config() {
+        let patch = create_patch("a\nb\nc\n", "a\nB\nc\n");
+        let render = |style| {
+            PatchFormatter::new()
+                .with_color()
+                .style(style)
+                .fmt_patch(&patch)
+                .to_string()
+        };
+
+        assert_eq!(
+            render(StyleConfig::default()),
+            PatchFormatter::new()
+                .with_color()
+                .fmt_patch(&patch)
+                .to_string()
+        );
+        assert_eq!(
+            render(StyleConfig::emphasize_changes()),
+            concat!(
+                "\u{1b}[1m--- original\n+++ modified\n\u{1b}[0m",
+                "\u{1b}[36m@@ -1,3 +1,3 @@\u{1b}[0m\n",
+                "\u{1b}[2m a\n\u{1b}[0m",
+                "\u{1b}[1;31m-b\n\u{1b}[0m",
+                "\u{1b}[1;32m+B\n\u{1b}[0m",
+                "\u{1b}[2m c\n\u{1b}[0m",
+            )
+        );
+        assert_eq!(
+            render(
+                StyleConfig::new()
+                    .insert(Attributes::new().underline().italic())
+                    .hunk_header(Attributes::new().bold())
+                    .color_hunk_header(false)
+            ),
+            concat!(
+                "\u{1b}[1m--- original\n+++ modified\n\u{1b}[0m",
+                "\u{1b}[1m@@ -1,3 +1,3 @@\u{1b}[0m\n",
+                " a\n",
+                "\u{1b}[31m-b\n\u{1b}[0m",
+                "\u{1b}[3;4;32m+B\n\u{1b}[0m",
+                " c\n",
+            )
+        );
+
+        // Attributes carry over to highlighted parts of a line
+        let patch = create_patch("let x = 1;\n", "let x = 2;\n");
+        let f = PatchFormatter::new()
+            .with_color()
+            .with_intraline_highlight(true)
+            .style(StyleConfig::emphasize_changes());
+        assert!(f
+            .fmt_patch(&patch)
+            .to_string()
+            .contains("\u{1b}[1;7;32m2\u{1b}[0m"));
+
+        // Without color the style has no effect
+        assert_eq!(
+            PatchFormatter::new()
+                .style(StyleConfig::emphasize_changes())
+                .fmt_patch(&patch)
+                .to_string(),
+            patch.to_string()
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn auto_color() {
+        use super::auto_color;
+        use std::ffi::OsStr;
+
+        assert!(auto_color(None, true));
+        assert!(auto_color(Some(OsStr::new("")), true));
+        assert!(!auto_color(None, false));
+        assert!(!auto_color(Some(OsStr::new("1")), true));
+        assert!(!auto_color(Some(OsStr::new("1")), false));
+    }
+
+    #[test]
+    fn forced_color_choices() {
+        assert!(ColorChoice::Always.use_color());
+        assert!(!ColorChoice::Never.use_color());
+    }
+}
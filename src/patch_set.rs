@@ -0,0 +1,1296 @@
+use crate::{
+    diff::DiffOptions,
+    patch::{file_name, Patch, Side},
+    ApplyError, ApplyOptions, FilePatch, FileStatus, PatchFormatter, Pattern, PrefixPolicy,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    string::String,
+    vec::Vec,
+};
+
+/// An ordered set of patches between the files of two trees
+///
+/// A file which only exists in the old tree is deleted by its patch and one which only exists in
+/// the new tree is created, with `/dev/null` standing in for the missing side. Paths are given
+/// git's `a/` and `b/` prefixes in the file headers, and each file is introduced by a
+/// `diff --git` line, so rendered sets can be applied with `git apply` or `patch -p1`. See
+/// [`PatchSetFormatter`] for rendering them in color or without the `diff --git` lines.
+///
+/// ```
+/// use diffy::{DiffOptions, PatchSet};
+/// use std::{collections::{BTreeMap, BTreeSet}, path::PathBuf};
+///
+/// let mut old = BTreeMap::new();
+/// old.insert(PathBuf::from("README"), b"hello\n".to_vec());
+/// let mut new = BTreeMap::new();
+/// new.insert(PathBuf::from("README"), b"hello world\n".to_vec());
+///
+/// let patches = PatchSet::from_trees(old, new, &DiffOptions::new());
+/// let expected = "\
+/// diff --git a/README b/README
+/// --- a/README
+/// +++ b/README
+/// @@ -1 +1 @@
+/// -hello
+/// +hello world
+/// ";
+/// assert_eq!(patches.to_bytes(), expected.as_bytes());
+/// assert_eq!(patches.to_string(), expected);
+/// ```
+///
+/// [`PatchSetFormatter`]: struct.PatchSetFormatter.html
+#[derive(Clone, Debug, Default)]
+pub struct PatchSet {
+    patches: Vec<FilePatch>,
+}
+
+impl PatchSet {
+    /// Diff two in-memory trees which map paths to file contents
+    ///
+    /// Files with identical contents in both trees are skipped. The patches are ordered by
+    /// path. With the `rayon` feature enabled the files are diffed in parallel, which gives the
+    /// same patches in the same order.
+    pub fn from_trees(
+        old: BTreeMap<PathBuf, Vec<u8>>,
+        new: BTreeMap<PathBuf, Vec<u8>>,
+        options: &DiffOptions,
+    ) -> Self {
+        let files = changed_files(old, new);
+        Self {
+            patches: diff_files(files, options),
+        }
+    }
+
+    /// Returns the patches in the set
+    pub fn patches(&self) -> &[FilePatch] {
+        &self.patches
+    }
+
+    /// Returns the number of patches in the set
+    pub fn len(&self) -> usize {
+        self.patches.len()
+    }
+
+    /// Returns `true` if the set doesn't contain any patches
+    pub fn is_empty(&self) -> bool {
+        self.patches.is_empty()
+    }
+
+    /// Returns a set of the patches whose paths match one of the `include` patterns and none of
+    /// the `exclude` patterns, in their original order
+    ///
+    /// An empty `include` matches every path. Paths are matched without the `a/` or `b/` prefix
+    /// of the file headers. A renamed file is included if either its old or new path matches
+    /// an `include` pattern, and excluded if either of them matches an `exclude` pattern.
+    pub fn filter_paths(&self, include: &[Pattern], exclude: &[Pattern]) -> PatchSet {
+        let included = |path: &str| include.is_empty() || include.iter().any(|p| p.matches(path));
+        let excluded = |path: &str| exclude.iter().any(|p| p.matches(path));
+        let patches = self
+            .patches
+            .iter()
+            .filter(|patch| paths(patch).any(included) && !paths(patch).any(excluded))
+            .cloned()
+            .collect();
+        PatchSet { patches }
+    }
+
+    /// Returns a set of the patches for which `predicate` returns `true`, in their original order
+    ///
+    /// The predicate is called with the old and new path of each patch, without their `a/` or
+    /// `b/` prefix, and the patch is kept if it returns `true` for either of them. This way a
+    /// file renamed into or out of a directory is kept when filtering on that directory. The
+    /// `/dev/null` name of a created or deleted file is never passed to the predicate.
+    pub fn filter<F>(&self, mut predicate: F) -> PatchSet
+    where
+        F: FnMut(&str) -> bool,
+    {
+        let patches = self
+            .patches
+            .iter()
+            .filter(|patch| paths(patch).any(&mut predicate))
+            .cloned()
+            .collect();
+        PatchSet { patches }
+    }
+
+    /// Keep only the patches for which `predicate` returns `true`, like [`filter`] but in place
+    ///
+    /// [`filter`]: #method.filter
+    pub fn retain<F>(&mut self, mut predicate: F)
+    where
+        F: FnMut(&str) -> bool,
+    {
+        self.patches
+            .retain(|patch| paths(patch).any(&mut predicate));
+    }
+
+    /// Apply every patch in the set, in order, to an in-memory tree which maps paths to file
+    /// contents
+    ///
+    /// Paths are the keys of `files`, matched against the names in the patches without their
+    /// `a/` or `b/` prefix. A patch which modifies a file is applied to its contents in place, a
+    /// created file is inserted, a deleted file is removed and a file whose old and new paths
+    /// differ is moved to its new path. A deleted file has to match the contents the patch
+    /// expects it to have, unless [`ApplyOptions::set_force_deletions`] is enabled. Binary files
+    /// have no hunks, so their contents have to match the old file of the patch exactly.
+    ///
+    /// The tree is only changed if every patch applies, so on error `files` is left untouched.
+    ///
+    /// ```
+    /// use diffy::{ApplyOptions, DiffOptions, FileOutcome, PatchSet};
+    /// use std::{collections::BTreeMap, path::PathBuf};
+    ///
+    /// let old = BTreeMap::from([(PathBuf::from("a.txt"), b"a\n".to_vec())]);
+    /// let new = BTreeMap::from([(PathBuf::from("a.txt"), b"A\n".to_vec())]);
+    /// let patches = PatchSet::from_trees(old, new, &DiffOptions::new());
+    ///
+    /// let mut files = BTreeMap::from([("a.txt".to_owned(), "a\n".to_owned())]);
+    /// let report = patches.apply_to_map(&mut files, &ApplyOptions::new()).unwrap();
+    /// assert_eq!(files["a.txt"], "A\n");
+    /// assert_eq!(report.files(), [FileOutcome::Modified("a.txt".to_owned())]);
+    /// ```
+    ///
+    /// [`ApplyOptions::set_force_deletions`]: struct.ApplyOptions.html#method.set_force_deletions
+    pub fn apply_to_map(
+        &self,
+        files: &mut BTreeMap<String, String>,
+        options: &ApplyOptions,
+    ) -> Result<PatchSetReport, PatchSetError> {
+        // Changes are staged and only written to `files` once every patch applied, with `None`
+        // marking a removed file
+        let mut staged: BTreeMap<String, Option<String>> = BTreeMap::new();
+        let mut outcomes = Vec::with_capacity(self.patches.len());
+
+        for (index, patch) in self.patches.iter().enumerate() {
+            let old_path = file_name(Some(patch.original_name()), PrefixPolicy::Git, Side::Old);
+            let new_path = file_name(Some(patch.modified_name()), PrefixPolicy::Git, Side::New);
+            let current = |path: &str| match staged.get(path) {
+                Some(contents) => contents.clone(),
+                None => files.get(path).cloned(),
+            };
+
+            let contents = match old_path {
+                Some(path) => current(path).ok_or_else(|| PatchSetError::MissingFile {
+                    patch: index,
+                    path: path.to_owned(),
+                })?,
+                None => String::new(),
+            };
+            let outcome = match (old_path, new_path) {
+                (Some(path), None) => {
+                    let deleted = apply_file(patch, index, path, &contents, options);
+                    if !deleted.map_or(false, |deleted| deleted.is_empty())
+                        && !options.force_deletions
+                    {
+                        return Err(PatchSetError::ContentMismatch {
+                            patch: index,
+                            path: path.to_owned(),
+                        });
+                    }
+                    staged.insert(path.to_owned(), None);
+                    FileOutcome::Deleted(path.to_owned())
+                }
+                (old_path, Some(path)) => {
+                    if old_path != Some(path) && current(path).is_some() {
+                        return Err(PatchSetError::FileExists {
+                            patch: index,
+                            path: path.to_owned(),
+                        });
+                    }
+                    let patched =
+                        apply_file(patch, index, old_path.unwrap_or(path), &contents, options)?;
+                    staged.insert(path.to_owned(), Some(patched));
+                    match old_path {
+                        None => FileOutcome::Created(path.to_owned()),
+                        Some(old_path) if old_path != path => {
+                            staged.insert(old_path.to_owned(), None);
+                            FileOutcome::Renamed {
+                                from: old_path.to_owned(),
+                                to: path.to_owned(),
+                            }
+                        }
+                        Some(_) => FileOutcome::Modified(path.to_owned()),
+                    }
+                }
+                // Neither side exists, so there is nothing to apply
+                (None, None) => continue,
+            };
+            outcomes.push(outcome);
+        }
+
+        for (path, contents) in staged {
+            match contents {
+                Some(contents) => files.insert(path, contents),
+                None => files.remove(&path),
+            };
+        }
+        Ok(PatchSetReport { files: outcomes })
+    }
+
+    /// Render every patch in the set, one after the other, each introduced by a `diff --git`
+    /// line
+    ///
+    /// This is the byte-level counterpart of the `Display` impl, which replaces invalid utf8
+    /// sequences with `U+FFFD REPLACEMENT CHARACTER`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        PatchSetFormatter::new().patch_set_bytes(self)
+    }
+}
+
+impl From<Vec<FilePatch>> for PatchSet {
+    fn from(patches: Vec<FilePatch>) -> Self {
+        Self { patches }
+    }
+}
+
+impl fmt::Display for PatchSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", PatchSetFormatter::new().fmt_patch_set(self))
+    }
+}
+
+/// The files [`PatchSet::apply_to_map`] changed
+///
+/// [`PatchSet::apply_to_map`]: struct.PatchSet.html#method.apply_to_map
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PatchSetReport {
+    files: Vec<FileOutcome>,
+}
+
+impl PatchSetReport {
+    /// Returns what happened to each file, in the order of the patches in the set
+    pub fn files(&self) -> &[FileOutcome] {
+        &self.files
+    }
+}
+
+/// What applying one patch of a [`PatchSet`] did to its file, identified by its path
+///
+/// [`PatchSet`]: struct.PatchSet.html
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FileOutcome {
+    /// The file was created
+    Created(String),
+    /// The file was removed
+    Deleted(String),
+    /// The file's contents were patched in place
+    Modified(String),
+    /// The file was moved to a new path, and its contents patched
+    Renamed {
+        /// The path the file was moved from
+        from: String,
+        /// The path the file was moved to
+        to: String,
+    },
+}
+
+/// An error returned when [`PatchSet::apply_to_map`] fails
+///
+/// Patches are identified by their 0-based index in the set, and files by their path without
+/// the `a/` or `b/` prefix.
+///
+/// [`PatchSet::apply_to_map`]: struct.PatchSet.html#method.apply_to_map
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PatchSetError {
+    /// The hunks of a patch couldn't be applied to its file
+    Apply {
+        /// The index of the patch which failed to apply
+        patch: usize,
+        /// The path of the file
+        path: String,
+        /// Why the patch failed to apply
+        error: ApplyError,
+    },
+    /// A patch modifies, deletes or renames a file which doesn't exist
+    MissingFile {
+        /// The index of the patch which failed to apply
+        patch: usize,
+        /// The path of the missing file
+        path: String,
+    },
+    /// A patch creates a file, or renames one to a path, which already exists
+    FileExists {
+        /// The index of the patch which failed to apply
+        patch: usize,
+        /// The path of the existing file
+        path: String,
+    },
+    /// A deleted or binary file doesn't have the contents its patch expects
+    ContentMismatch {
+        /// The index of the patch which failed to apply
+        patch: usize,
+        /// The path of the file
+        path: String,
+    },
+    /// The patched contents of a file aren't valid utf8
+    NotUtf8 {
+        /// The index of the patch which failed to apply
+        patch: usize,
+        /// The path of the file
+        path: String,
+    },
+}
+
+impl PatchSetError {
+    /// Returns the index of the patch which failed to apply
+    pub fn patch(&self) -> usize {
+        match self {
+            PatchSetError::Apply { patch, .. }
+            | PatchSetError::MissingFile { patch, .. }
+            | PatchSetError::FileExists { patch, .. }
+            | PatchSetError::ContentMismatch { patch, .. }
+            | PatchSetError::NotUtf8 { patch, .. } => *patch,
+        }
+    }
+
+    /// Returns the path of the file the patch failed to apply to
+    pub fn path(&self) -> &str {
+        match self {
+            PatchSetError::Apply { path, .. }
+            | PatchSetError::MissingFile { path, .. }
+            | PatchSetError::FileExists { path, .. }
+            | PatchSetError::ContentMismatch { path, .. }
+            | PatchSetError::NotUtf8 { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for PatchSetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "patch #{} ({}): ", self.patch(), self.path())?;
+        match self {
+            PatchSetError::Apply { error, .. } => write!(f, "{}", error),
+            PatchSetError::MissingFile { .. } => write!(f, "file doesn't exist"),
+            PatchSetError::FileExists { .. } => write!(f, "file already exists"),
+            PatchSetError::ContentMismatch { .. } => {
+                write!(f, "file doesn't have the contents the patch expects")
+            }
+            PatchSetError::NotUtf8 { .. } => write!(f, "patched file isn't valid utf8"),
+        }
+    }
+}
+
+impl std::error::Error for PatchSetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PatchSetError::Apply { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Struct used to adjust the formatting of a [`PatchSet`]
+///
+/// By default each file's patch is preceded by the extended headers `git apply` expects: a
+/// `diff --git a/<path> b/<path>` line, followed by `new file mode` or `deleted file mode`
+/// for created and deleted files, or by `rename from` and `rename to` for a file whose old and
+/// new paths differ. Binary files are rendered as a `Binary files ... differ` line in place of
+/// their file headers and hunks, which `git apply` can't apply.
+///
+/// Without the `diff --git` lines, see [`without_git_header`], the output is a plain
+/// concatenation of unified diffs for tools like GNU patch, the same as rendering each
+/// [`FilePatch`] on its own.
+///
+/// ```
+/// use diffy::{DiffOptions, PatchSet, PatchSetFormatter};
+/// use std::{collections::BTreeMap, path::PathBuf};
+///
+/// let old = BTreeMap::from([(PathBuf::from("a.txt"), b"a\n".to_vec())]);
+/// let new = BTreeMap::from([(PathBuf::from("b.txt"), b"b\n".to_vec())]);
+/// let patches = PatchSet::from_trees(old, new, &DiffOptions::new());
+///
+/// let expected = "\
+/// diff --git a/a.txt b/a.txt
+/// deleted file mode 100644
+/// --- a/a.txt
+/// +++ /dev/null
+/// @@ -1 +0,0 @@
+/// -a
+/// diff --git a/b.txt b/b.txt
+/// new file mode 100644
+/// --- /dev/null
+/// +++ b/b.txt
+/// @@ -0,0 +1 @@
+/// +b
+/// ";
+/// assert_eq!(patches.to_string(), expected);
+///
+/// let f = PatchSetFormatter::new().without_git_header();
+/// let expected = "\
+/// --- a/a.txt
+/// +++ /dev/null
+/// @@ -1 +0,0 @@
+/// -a
+/// --- /dev/null
+/// +++ b/b.txt
+/// @@ -0,0 +1 @@
+/// +b
+/// ";
+/// assert_eq!(f.fmt_patch_set(&patches).to_string(), expected);
+/// ```
+///
+/// [`PatchSet`]: struct.PatchSet.html
+/// [`FilePatch`]: struct.FilePatch.html
+/// [`without_git_header`]: #method.without_git_header
+#[derive(Debug, Default)]
+pub struct PatchSetFormatter {
+    formatter: PatchFormatter,
+    without_git_header: bool,
+}
+
+impl PatchSetFormatter {
+    /// Construct a new formatter
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enable formatting the patches with color
+    ///
+    /// The `diff --git` lines and other extended headers are styled like the file headers.
+    pub fn with_color(mut self) -> Self {
+        self.formatter = self.formatter.with_color();
+        self
+    }
+
+    /// Use `formatter` to format each file's patch, e.g. to choose a [`Theme`] or wrap long
+    /// lines
+    ///
+    /// [`Theme`]: struct.Theme.html
+    pub fn with_patch_formatter(mut self, formatter: PatchFormatter) -> Self {
+        self.formatter = formatter;
+        self
+    }
+
+    /// Leave out the `diff --git` lines and other git extended headers, producing plain unified
+    /// diffs one after the other
+    pub fn without_git_header(mut self) -> Self {
+        self.without_git_header = true;
+        self
+    }
+
+    /// Returns a `Display` impl which can be used to print a `PatchSet`
+    ///
+    /// Invalid utf8 sequences are replaced with `U+FFFD REPLACEMENT CHARACTER`, use
+    /// [`patch_set_bytes`] to get the exact bytes.
+    ///
+    /// [`patch_set_bytes`]: #method.patch_set_bytes
+    pub fn fmt_patch_set<'a>(&'a self, patches: &'a PatchSet) -> impl fmt::Display + 'a {
+        PatchSetDisplay { f: self, patches }
+    }
+
+    /// Write a `PatchSet` into an `io::Write`r
+    pub fn write_patch_set_into<W: io::Write>(
+        &self,
+        patches: &PatchSet,
+        mut w: W,
+    ) -> io::Result<()> {
+        w.write_all(&self.patch_set_bytes(patches))
+    }
+
+    /// Render a `PatchSet` into a `Vec<u8>`
+    pub fn patch_set_bytes(&self, patches: &PatchSet) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for patch in &patches.patches {
+            self.write_file_patch(patch, &mut bytes);
+        }
+        bytes
+    }
+
+    fn write_file_patch(&self, patch: &FilePatch, w: &mut Vec<u8>) {
+        if self.without_git_header {
+            if patch.is_binary() {
+                w.extend(patch.to_bytes());
+            } else {
+                w.extend(self.formatter.patch_bytes(&patch.patch()));
+            }
+            return;
+        }
+        // Like `FilePatch::to_bytes`, binary files without any changes aren't rendered at all
+        if patch.is_binary() && patch.original() == patch.modified() {
+            return;
+        }
+
+        // The `diff --git` line names the file on both sides, even when it's created or deleted
+        let mut names = paths(patch);
+        let original = names.next().unwrap_or_default();
+        let modified = names.next().unwrap_or(original);
+        let status = if patch.is_creation() {
+            FileStatus::Added
+        } else if patch.is_deletion() {
+            FileStatus::Removed
+        } else {
+            FileStatus::Modified
+        };
+        let git_patch = Patch::new(
+            Some(std::format!("a/{}", original).into_bytes()),
+            Some(std::format!("b/{}", modified).into_bytes()),
+            patch.hunks(),
+        )
+        .with_status(status);
+        self.formatter
+            .write_git_patch_into_vec(&git_patch, patch.is_binary(), w);
+    }
+}
+
+struct PatchSetDisplay<'a> {
+    f: &'a PatchSetFormatter,
+    patches: &'a PatchSet,
+}
+
+impl fmt::Display for PatchSetDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes = self.f.patch_set_bytes(self.patches);
+        f.write_str(&String::from_utf8_lossy(&bytes))
+    }
+}
+
+/// Create a set of patches between the files in two directories
+///
+/// Both directories are walked recursively and every regular file is read into memory before
+/// being diffed as by [`PatchSet::from_trees`]. Symbolic links and other special files are
+/// skipped. Files and directories whose path relative to the directory being walked matches
+/// one of the `ignore` patterns are skipped as well, see [`Pattern`] for how paths are matched.
+///
+/// If one of the directories doesn't exist an error with [`io::ErrorKind::NotFound`] is
+/// returned, unless [`DiffOptions::set_missing_files_as_empty`] is enabled in which case it's
+/// treated as empty.
+///
+/// [`PatchSet::from_trees`]: struct.PatchSet.html#method.from_trees
+/// [`Pattern`]: struct.Pattern.html
+/// [`io::ErrorKind::NotFound`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.NotFound
+/// [`DiffOptions::set_missing_files_as_empty`]: struct.DiffOptions.html#method.set_missing_files_as_empty
+pub fn create_patchset_from_dirs(
+    old_dir: &Path,
+    new_dir: &Path,
+    options: &DiffOptions,
+    ignore: &[Pattern],
+) -> io::Result<PatchSet> {
+    let old = read_tree(old_dir, options.missing_files_as_empty, ignore)?;
+    let new = read_tree(new_dir, options.missing_files_as_empty, ignore)?;
+    Ok(PatchSet::from_trees(old, new, options))
+}
+
+fn read_tree(
+    root: &Path,
+    allow_missing: bool,
+    ignore: &[Pattern],
+) -> io::Result<BTreeMap<PathBuf, Vec<u8>>> {
+    let mut tree = BTreeMap::new();
+    match fs::metadata(root) {
+        Err(e) if allow_missing && e.kind() == io::ErrorKind::NotFound => return Ok(tree),
+        Err(e) => return Err(e),
+        Ok(_) => {}
+    }
+    walk(root, PathBuf::new(), ignore, &mut tree)?;
+    Ok(tree)
+}
+
+fn walk(
+    root: &Path,
+    dir: PathBuf,
+    ignore: &[Pattern],
+    tree: &mut BTreeMap<PathBuf, Vec<u8>>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(root.join(&dir))? {
+        let entry = entry?;
+        let path = dir.join(entry.file_name());
+        let slashed = slash_path(&path);
+        if ignore.iter().any(|pattern| pattern.matches(&slashed)) {
+            continue;
+        }
+
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            walk(root, path, ignore, tree)?;
+        } else if file_type.is_file() {
+            let contents = fs::read(entry.path())?;
+            tree.insert(path, contents);
+        }
+    }
+    Ok(())
+}
+
+// A file which differs between two trees, along with its contents in each of them
+type ChangedFile = (PathBuf, Option<Vec<u8>>, Option<Vec<u8>>);
+
+// Pairs up the files of two trees by path, in order, skipping the ones which didn't change
+fn changed_files(
+    mut old: BTreeMap<PathBuf, Vec<u8>>,
+    mut new: BTreeMap<PathBuf, Vec<u8>>,
+) -> Vec<ChangedFile> {
+    let paths: BTreeSet<PathBuf> = old.keys().chain(new.keys()).cloned().collect();
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let original = old.remove(&path);
+            let modified = new.remove(&path);
+            (original != modified).then_some((path, original, modified))
+        })
+        .collect()
+}
+
+// Diffs every changed file, keeping their order
+fn diff_files(files: Vec<ChangedFile>, options: &DiffOptions) -> Vec<FilePatch> {
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::iter::{IntoParallelIterator, ParallelIterator};
+        files
+            .into_par_iter()
+            .map(|file| diff_file(file, options))
+            .collect()
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        files
+            .into_iter()
+            .map(|file| diff_file(file, options))
+            .collect()
+    }
+}
+
+fn diff_file((path, original, modified): ChangedFile, options: &DiffOptions) -> FilePatch {
+    let path = slash_path(&path);
+    FilePatch::new(
+        std::format!("a/{}", path),
+        original,
+        std::format!("b/{}", path),
+        modified,
+        options,
+    )
+}
+
+// Applies the patch of a single file to its contents
+fn apply_file(
+    patch: &FilePatch,
+    index: usize,
+    path: &str,
+    contents: &str,
+    options: &ApplyOptions,
+) -> Result<String, PatchSetError> {
+    let patched = if patch.is_binary() {
+        if contents.as_bytes() != patch.original() {
+            return Err(PatchSetError::ContentMismatch {
+                patch: index,
+                path: path.to_owned(),
+            });
+        }
+        patch.modified().to_vec()
+    } else {
+        options
+            .apply_bytes(contents.as_bytes(), &patch.patch())
+            .map_err(|error| PatchSetError::Apply {
+                patch: index,
+                path: path.to_owned(),
+                error,
+            })?
+    };
+    String::from_utf8(patched).map_err(|_| PatchSetError::NotUtf8 {
+        patch: index,
+        path: path.to_owned(),
+    })
+}
+
+// Returns the old and new path of a patch, without their `a/` and `b/` prefixes, skipping the
+// side which doesn't exist
+fn paths(patch: &FilePatch) -> impl Iterator<Item = &str> {
+    let original = file_name(Some(patch.original_name()), PrefixPolicy::Git, Side::Old);
+    let modified = file_name(Some(patch.modified_name()), PrefixPolicy::Git, Side::New);
+    original.into_iter().chain(modified)
+}
+
+// Joins the components of a relative path with `/`, regardless of the platform's separator
+fn slash_path(path: &Path) -> String {
+    let components: Vec<_> = path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+    components.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{apply_bytes, RecordSep};
+
+    fn tree(files: &[(&str, &str)]) -> BTreeMap<PathBuf, Vec<u8>> {
+        files
+            .iter()
+            .map(|(path, contents)| (PathBuf::from(path), contents.as_bytes().to_vec()))
+            .collect()
+    }
+
+    // A scratch directory which is removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(std::format!("diffy-{}-{}", name, std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn file(&self, name: &str, contents: &[u8]) -> PathBuf {
+            let path = self.0.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, contents).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn patch_set_from_trees() {
+        let old = tree(&[
+            ("src/lib.rs", "mod a;\nmod b;\n"),
+            ("README", "readme\n"),
+            ("deleted.txt", "gone\n"),
+        ]);
+        let new = tree(&[
+            ("src/lib.rs", "mod a;\nmod c;\n"),
+            ("README", "readme\n"),
+            ("src/added.rs", "fn added() {}\n"),
+        ]);
+
+        let patches = PatchSet::from_trees(old.clone(), new.clone(), &DiffOptions::new());
+        let expected = "\
+diff --git a/deleted.txt b/deleted.txt
+deleted file mode 100644
+--- a/deleted.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-gone
+diff --git a/src/added.rs b/src/added.rs
+new file mode 100644
+--- /dev/null
++++ b/src/added.rs
+@@ -0,0 +1 @@
++fn added() {}
+diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,2 +1,2 @@
+ mod a;
+-mod b;
++mod c;
+";
+        assert_eq!(patches.len(), 3);
+        assert_eq!(core::str::from_utf8(&patches.to_bytes()).unwrap(), expected);
+        assert!(patches.patches()[0].is_deletion());
+        assert!(patches.patches()[1].is_creation());
+
+        // Applying every patch to its old file reproduces the new tree
+        for patch in patches.patches() {
+            let path = patch.modified_name().strip_prefix("b/");
+            let result = apply_bytes(patch.original(), &patch.patch()).unwrap();
+            match path {
+                Some(path) => assert_eq!(new[Path::new(path)], result),
+                None => assert!(result.is_empty()),
+            }
+        }
+
+        // Binary content is detected per file
+        let mut binary = new;
+        binary.insert(PathBuf::from("README"), b"\x00\x01".to_vec());
+        let patches = PatchSet::from_trees(old, binary, &DiffOptions::new());
+        let readme = &patches.patches()[0];
+        assert_eq!(readme.original_name(), "a/README");
+        assert!(readme.is_binary());
+        assert!(!patches.patches()[1].is_binary());
+
+        // Files are split into the same records they were diffed by
+        let mut options = DiffOptions::new();
+        options.set_record_separator(RecordSep::Str(";"));
+        let patches = PatchSet::from_trees(
+            tree(&[("list", "a;b;c;d;e")]),
+            tree(&[("list", "a;B;c;d;e")]),
+            &options,
+        );
+        let expected = options.create_patch_bytes(b"a;b;c;d;e", b"a;B;c;d;e");
+        assert_eq!(patches.patches()[0].patch().hunks(), expected.hunks());
+        let rendered = patches.to_bytes();
+        assert!(core::str::from_utf8(&rendered)
+            .unwrap()
+            .contains("@@ -1,5 +1,5 @@\n a;\n"));
+    }
+
+    #[test]
+    fn patch_set_formatting() {
+        let old = tree(&[("image.png", "\0old"), ("keep.txt", "a\nb\n")]);
+        let new = tree(&[("image.png", "\0new"), ("new.bin", "\0")]);
+        let mut patches = PatchSet::from_trees(old, new, &DiffOptions::new()).patches;
+        patches.push(FilePatch::new(
+            "a/src/old.rs".into(),
+            Some(b"a\nb\n".to_vec()),
+            "b/lib/new.rs".into(),
+            Some(b"a\nc\n".to_vec()),
+            &DiffOptions::new(),
+        ));
+        let patches = PatchSet::from(patches);
+
+        let expected = "\
+diff --git a/image.png b/image.png
+Binary files a/image.png and b/image.png differ
+diff --git a/keep.txt b/keep.txt
+deleted file mode 100644
+--- a/keep.txt
++++ /dev/null
+@@ -1,2 +0,0 @@
+-a
+-b
+diff --git a/new.bin b/new.bin
+new file mode 100644
+Binary files /dev/null and b/new.bin differ
+diff --git a/src/old.rs b/lib/new.rs
+rename from src/old.rs
+rename to lib/new.rs
+--- a/src/old.rs
++++ b/lib/new.rs
+@@ -1,2 +1,2 @@
+ a
+-b
++c
+";
+        assert_eq!(patches.to_string(), expected);
+        assert_eq!(patches.to_bytes(), expected.as_bytes());
+        let mut written = Vec::new();
+        let f = PatchSetFormatter::new();
+        f.write_patch_set_into(&patches, &mut written).unwrap();
+        assert_eq!(written, expected.as_bytes());
+
+        // Without the git headers each patch is rendered on its own
+        let f = PatchSetFormatter::new().without_git_header();
+        let plain: Vec<u8> = patches
+            .patches()
+            .iter()
+            .flat_map(FilePatch::to_bytes)
+            .collect();
+        assert_eq!(f.patch_set_bytes(&patches), plain);
+        assert!(!f.fmt_patch_set(&patches).to_string().contains("diff --git"));
+
+        // The extended headers are styled like the file headers
+        let f = PatchSetFormatter::new().with_color();
+        let colored = f.fmt_patch_set(&patches).to_string();
+        assert!(colored.starts_with("\u{1b}[1mdiff --git a/image.png b/image.png\n\u{1b}[0m"));
+        assert!(colored.contains("\u{1b}[1mdiff --git a/keep.txt b/keep.txt\n\u{1b}[0m"));
+
+        // Identical binary files aren't rendered at all, and invalid utf8 is replaced when
+        // displaying
+        let same = FilePatch::new(
+            "a/same".into(),
+            Some(b"\0".to_vec()),
+            "b/same".into(),
+            Some(b"\0".to_vec()),
+            &DiffOptions::new(),
+        );
+        let latin1 = FilePatch::new(
+            "a/latin1".into(),
+            Some(b"caf\xe9\n".to_vec()),
+            "b/latin1".into(),
+            Some(b"cafe\n".to_vec()),
+            &DiffOptions::new(),
+        );
+        let patches = PatchSet::from(std::vec![same, latin1]);
+        assert!(patches.to_bytes().ends_with(b"-caf\xe9\n+cafe\n"));
+        assert!(patches.to_string().ends_with("-caf\u{fffd}\n+cafe\n"));
+        assert!(patches
+            .to_string()
+            .starts_with("diff --git a/latin1 b/latin1\n"));
+    }
+
+    #[cfg(feature = "git-apply-tests")]
+    #[test]
+    fn git_apply_accepts_patch_sets() {
+        use std::process::Command;
+
+        let root = TempDir::new("git");
+        let old = tree(&[
+            ("README", "readme\n"),
+            ("src/lib.rs", "mod a;\nmod b;\nfn main() {}\n"),
+            ("src/deleted.rs", "gone\n"),
+            ("src/old_name.rs", "one\ntwo\nthree\n"),
+            ("no-newline.txt", "a\nb"),
+            ("emptied.txt", "content\n"),
+        ]);
+        let new = tree(&[
+            ("README", "readme\nmore\n"),
+            ("src/lib.rs", "mod a;\nmod c;\nfn main() {}\n"),
+            ("src/added.rs", "fn added() {}\n"),
+            ("src/empty.rs", ""),
+            ("no-newline.txt", "a\nB"),
+            ("emptied.txt", ""),
+        ]);
+        let options = DiffOptions::new();
+        let mut patches = PatchSet::from_trees(old.clone(), new.clone(), &options).patches;
+        patches.retain(|patch| patch.original_name() != "a/src/old_name.rs");
+        patches.push(FilePatch::new(
+            "a/src/old_name.rs".into(),
+            Some(b"one\ntwo\nthree\n".to_vec()),
+            "b/src/new_name.rs".into(),
+            Some(b"one\n2\nthree\n".to_vec()),
+            &options,
+        ));
+        let patches = PatchSet::from(patches);
+
+        for (path, contents) in &old {
+            root.file(path.to_str().unwrap(), contents);
+        }
+        root.file("changes.patch", &patches.to_bytes());
+        let git = |args: &[&str]| {
+            let output = Command::new("git")
+                .args(args)
+                .current_dir(&root.0)
+                .output()
+                .expect("git is required");
+            assert!(
+                output.status.success(),
+                "git {:?} failed: {}",
+                args,
+                std::string::String::from_utf8_lossy(&output.stderr)
+            );
+        };
+        git(&["init", "--quiet"]);
+        git(&["apply", "--check", "changes.patch"]);
+        git(&["apply", "changes.patch"]);
+
+        let mut expected = new;
+        expected.insert(
+            PathBuf::from("src/new_name.rs"),
+            b"one\n2\nthree\n".to_vec(),
+        );
+        for (path, contents) in &expected {
+            assert_eq!(
+                &fs::read(root.0.join(path)).unwrap(),
+                contents,
+                "{:?}",
+                path
+            );
+        }
+        assert!(!root.0.join("src/deleted.rs").exists());
+        assert!(!root.0.join("src/old_name.rs").exists());
+    }
+
+    #[test]
+    fn patch_set_filtering() {
+        let old = tree(&[
+            ("Cargo.toml", "[package]\n"),
+            ("src/lib.rs", "mod diff;\n"),
+            ("src/diff/mod.rs", "fn diff() {}\n"),
+            ("src/diff/tests.rs", "fn test() {}\n"),
+            ("tests/it.rs", "fn it() {}\n"),
+        ]);
+        let new = tree(&[
+            ("Cargo.toml", "[package]\nname = \"diffy\"\n"),
+            ("src/lib.rs", "mod diff;\nmod merge;\n"),
+            ("src/diff/mod.rs", "fn diff() { todo!() }\n"),
+            ("src/diff/myers.rs", "fn myers() {}\n"),
+            ("tests/it.rs", "fn it() { todo!() }\n"),
+        ]);
+        let patches = PatchSet::from_trees(old.clone(), new.clone(), &DiffOptions::new());
+        assert_eq!(patches.len(), 6);
+
+        let diff = patches.filter_paths(&[Pattern::new("src/diff/**")], &[]);
+        let names: Vec<_> = diff
+            .patches()
+            .iter()
+            .map(FilePatch::modified_name)
+            .collect();
+        assert_eq!(
+            names,
+            ["b/src/diff/mod.rs", "b/src/diff/myers.rs", "/dev/null"]
+        );
+
+        // The filtered set is a valid patch on its own
+        let text = std::string::String::from_utf8(diff.to_bytes()).unwrap();
+        let parsed: Vec<_> = text
+            .split("diff --git ")
+            .skip(1)
+            .map(|patch| std::format!("diff --git {}", patch))
+            .collect();
+        assert_eq!(parsed.len(), 3);
+        for (patch, text) in diff.patches().iter().zip(&parsed) {
+            let parsed = crate::Patch::from_bytes(text.as_bytes()).unwrap();
+            let result = apply_bytes(patch.original(), &parsed).unwrap();
+            assert_eq!(result, patch.modified());
+        }
+
+        let diff = patches.filter_paths(&[Pattern::new("src/**")], &[Pattern::new("tests.rs")]);
+        assert_eq!(diff.len(), 3);
+        assert!(diff
+            .patches()
+            .iter()
+            .all(|p| !p.original_name().ends_with("tests.rs")));
+
+        let mut retained = patches.clone();
+        retained.retain(|path| !path.starts_with("src/"));
+        let names: Vec<_> = retained
+            .patches()
+            .iter()
+            .map(FilePatch::modified_name)
+            .collect();
+        assert_eq!(names, ["b/Cargo.toml", "b/tests/it.rs"]);
+        assert_eq!(patches.filter(|path| !path.starts_with("src/")).len(), 2);
+
+        // A rename matches on either of its paths
+        let options = DiffOptions::new();
+        let rename = FilePatch::new(
+            "a/src/old.rs".into(),
+            Some(b"a\n".to_vec()),
+            "b/lib/new.rs".into(),
+            Some(b"b\n".to_vec()),
+            &options,
+        );
+        let renames = PatchSet::from(std::vec![rename]);
+        assert_eq!(renames.filter_paths(&[Pattern::new("src/*")], &[]).len(), 1);
+        assert_eq!(renames.filter_paths(&[Pattern::new("lib/*")], &[]).len(), 1);
+        assert!(renames
+            .filter_paths(&[Pattern::new("src/*")], &[Pattern::new("lib/*")])
+            .is_empty());
+    }
+
+    #[test]
+    fn apply_patch_set_to_map() {
+        fn map(files: &[(&str, &str)]) -> BTreeMap<String, String> {
+            files
+                .iter()
+                .map(|(path, contents)| (path.to_string(), contents.to_string()))
+                .collect()
+        }
+
+        let options = DiffOptions::new();
+        let file_patch = |old: &str, original: Option<&str>, new: &str, modified: Option<&str>| {
+            FilePatch::new(
+                std::format!("a/{}", old),
+                original.map(|o| o.as_bytes().to_vec()),
+                std::format!("b/{}", new),
+                modified.map(|m| m.as_bytes().to_vec()),
+                &options,
+            )
+        };
+        let apply_options = ApplyOptions::new();
+
+        // Modifications, creations, deletions and renames
+        let old = tree(&[
+            ("src/lib.rs", "mod a;\nmod b;\n"),
+            ("README", "readme\n"),
+            ("deleted.txt", "gone\n"),
+        ]);
+        let new = tree(&[
+            ("src/lib.rs", "mod a;\nmod c;\n"),
+            ("README", "readme\n"),
+            ("src/added.rs", "fn added() {}\n"),
+        ]);
+        let mut patches = PatchSet::from_trees(old, new, &options);
+        patches.patches.push(file_patch(
+            "README",
+            Some("readme\n"),
+            "docs/README",
+            Some("read me\n"),
+        ));
+        let mut files = map(&[
+            ("src/lib.rs", "mod a;\nmod b;\n"),
+            ("README", "readme\n"),
+            ("deleted.txt", "gone\n"),
+            ("untouched", "untouched\n"),
+        ]);
+        let report = patches.apply_to_map(&mut files, &apply_options).unwrap();
+        assert_eq!(
+            files,
+            map(&[
+                ("src/lib.rs", "mod a;\nmod c;\n"),
+                ("docs/README", "read me\n"),
+                ("src/added.rs", "fn added() {}\n"),
+                ("untouched", "untouched\n"),
+            ])
+        );
+        assert_eq!(
+            report.files(),
+            [
+                FileOutcome::Deleted("deleted.txt".to_string()),
+                FileOutcome::Created("src/added.rs".to_string()),
+                FileOutcome::Modified("src/lib.rs".to_string()),
+                FileOutcome::Renamed {
+                    from: "README".to_string(),
+                    to: "docs/README".to_string(),
+                },
+            ]
+        );
+
+        // Later patches apply to the result of earlier ones
+        let series = PatchSet::from(std::vec![
+            file_patch("a.txt", Some("a\n"), "a.txt", Some("b\n")),
+            file_patch("a.txt", Some("b\n"), "b.txt", Some("c\n")),
+            file_patch("b.txt", Some("c\n"), "b.txt", None),
+        ]);
+        let mut files = map(&[("a.txt", "a\n")]);
+        series.apply_to_map(&mut files, &apply_options).unwrap();
+        assert!(files.is_empty());
+
+        // A failing patch leaves the map untouched
+        let failing = PatchSet::from(std::vec![
+            file_patch("a.txt", Some("a\n"), "a.txt", Some("b\n")),
+            file_patch("new.txt", None, "new.txt", Some("new\n")),
+            file_patch("c.txt", Some("c\n"), "c.txt", Some("C\n")),
+        ]);
+        let mut files = map(&[("a.txt", "a\n"), ("c.txt", "x\n")]);
+        let original = files.clone();
+        let error = failing
+            .apply_to_map(&mut files, &apply_options)
+            .unwrap_err();
+        assert_eq!(
+            error,
+            PatchSetError::Apply {
+                patch: 2,
+                path: "c.txt".to_string(),
+                error: crate::ApplyError::HunkMismatch { hunk: 1 },
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "patch #2 (c.txt): error applying hunk #1"
+        );
+        assert_eq!(files, original);
+
+        // Missing and existing files
+        let mut files = map(&[("new.txt", "exists\n")]);
+        let error = failing
+            .apply_to_map(&mut files, &apply_options)
+            .unwrap_err();
+        assert_eq!(
+            error,
+            PatchSetError::MissingFile {
+                patch: 0,
+                path: "a.txt".to_string(),
+            }
+        );
+        files.insert("a.txt".to_string(), "a\n".to_string());
+        let error = failing
+            .apply_to_map(&mut files, &apply_options)
+            .unwrap_err();
+        assert_eq!(
+            error,
+            PatchSetError::FileExists {
+                patch: 1,
+                path: "new.txt".to_string(),
+            }
+        );
+        let rename = PatchSet::from(std::vec![file_patch(
+            "a.txt",
+            Some("a\n"),
+            "new.txt",
+            Some("a\n")
+        )]);
+        assert_eq!(
+            rename.apply_to_map(&mut files, &apply_options),
+            Err(PatchSetError::FileExists {
+                patch: 0,
+                path: "new.txt".to_string(),
+            })
+        );
+
+        // Deleting a file which doesn't match the patch requires forcing it
+        let deletion = PatchSet::from(std::vec![file_patch("a.txt", Some("a\n"), "a.txt", None)]);
+        for contents in ["A\n", "a\nb\n"] {
+            let mut files = map(&[("a.txt", contents)]);
+            assert_eq!(
+                deletion.apply_to_map(&mut files, &apply_options),
+                Err(PatchSetError::ContentMismatch {
+                    patch: 0,
+                    path: "a.txt".to_string(),
+                })
+            );
+            assert_eq!(files.len(), 1);
+
+            let mut forced = ApplyOptions::new();
+            forced.set_force_deletions(true);
+            deletion.apply_to_map(&mut files, &forced).unwrap();
+            assert!(files.is_empty());
+        }
+    }
+
+    #[test]
+    fn patch_set_from_dirs() {
+        let root = TempDir::new("dirs");
+        for (path, contents) in [
+            ("old/a.txt", "a\n"),
+            ("old/target/out", "old\n"),
+            ("new/a.txt", "A\n"),
+            ("new/target/out", "new\n"),
+            ("new/sub/b.txt", "b\n"),
+        ] {
+            root.file(path, contents.as_bytes());
+        }
+        let old_dir = root.0.join("old");
+        let new_dir = root.0.join("new");
+
+        let options = DiffOptions::new();
+        let patches =
+            create_patchset_from_dirs(&old_dir, &new_dir, &options, &[Pattern::new("target")])
+                .unwrap();
+        let names: Vec<_> = patches
+            .patches()
+            .iter()
+            .map(|patch| patch.modified_name())
+            .collect();
+        assert_eq!(names, ["b/a.txt", "b/sub/b.txt"]);
+
+        let missing = root.0.join("missing");
+        let err = create_patchset_from_dirs(&missing, &new_dir, &options, &[]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        let mut options = DiffOptions::new();
+        options.set_missing_files_as_empty(true);
+        let patches = create_patchset_from_dirs(&missing, &new_dir, &options, &[]).unwrap();
+        assert!(patches.patches().iter().all(FilePatch::is_creation));
+        assert_eq!(patches.len(), 3);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_from_trees() {
+        let contents = |i: usize, changed: bool| {
+            let mut text: std::string::String = (0..50)
+                .map(|line| std::format!("file {} line {}\n", i, line))
+                .collect();
+            if changed {
+                text = text.replace(&std::format!("line {}\n", i % 50), "changed\n");
+            }
+            text
+        };
+        let mut old = BTreeMap::new();
+        let mut new = BTreeMap::new();
+        for i in 0..3000 {
+            let path = PathBuf::from(std::format!("dir{}/file{}.txt", i % 7, i));
+            match i % 5 {
+                0 => old.insert(path, contents(i, false).into_bytes()),
+                1 => new.insert(path, contents(i, false).into_bytes()),
+                _ => {
+                    old.insert(path.clone(), contents(i, false).into_bytes());
+                    new.insert(path, contents(i, i % 5 != 2).into_bytes())
+                }
+            };
+        }
+
+        let options = DiffOptions::new();
+        let serial: Vec<_> = changed_files(old.clone(), new.clone())
+            .into_iter()
+            .map(|file| diff_file(file, &options))
+            .collect();
+        let serial = PatchSet::from(serial).to_bytes();
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+        let patches = pool.install(|| PatchSet::from_trees(old, new, &options));
+        assert_eq!(patches.len(), 2400);
+        assert_eq!(patches.to_bytes(), serial);
+    }
+}
@@ -0,0 +1,508 @@
+//! [`proptest`] strategies for generating related texts
+//!
+//! These strategies produce texts which share most of their lines, which exercises far more
+//! interesting code paths in a diff than two independently generated strings would.
+//!
+//! ```
+//! use diffy::{apply, create_patch, proptest::text_pair};
+//! use proptest::{strategy::Strategy, test_runner::TestRunner};
+//!
+//! let mut runner = TestRunner::default();
+//! runner
+//!     .run(&text_pair(20, 0.3), |(old, new)| {
+//!         let patch = create_patch(&old, &new);
+//!         assert_eq!(apply(&old, &patch).unwrap(), new);
+//!         Ok(())
+//!     })
+//!     .unwrap();
+//! ```
+//!
+//! [`proptest`]: https://docs.rs/proptest
+
+use ::proptest::{collection, prelude::*, sample};
+
+// A small vocabulary so that generated texts contain plenty of repeated lines
+const LINES: &[&str] = &[
+    "\n",
+    "{\n",
+    "}\n",
+    "foo\n",
+    "bar\n",
+    "baz\n",
+    "fn main() {\n",
+    "    return;\n",
+];
+
+/// A single edit to the lines of a text
+///
+/// Line indexes are taken modulo the number of lines in the text at the time the mutation is
+/// applied, so any sequence of mutations can be applied to any text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Mutation {
+    /// Insert a line before the line at the given index
+    Insert(usize, String),
+    /// Delete the line at the given index
+    Delete(usize),
+    /// Replace the line at the given index
+    Replace(usize, String),
+}
+
+/// Generates a single line, including its terminating newline
+pub fn line() -> impl Strategy<Value = String> {
+    prop_oneof![
+        3 => sample::select(LINES).prop_map(String::from),
+        1 => "[a-c ]{1,4}\n",
+    ]
+}
+
+/// Generates a text of at most `max_lines` lines which may be missing its final newline
+pub fn text(max_lines: usize) -> impl Strategy<Value = String> {
+    (collection::vec(line(), 0..=max_lines), any::<bool>()).prop_map(|(lines, trailing_newline)| {
+        let mut text = lines.concat();
+        if !trailing_newline {
+            text.pop();
+        }
+        text
+    })
+}
+
+/// Generates a single `Mutation`
+pub fn mutation() -> impl Strategy<Value = Mutation> {
+    prop_oneof![
+        (any::<usize>(), line()).prop_map(|(idx, line)| Mutation::Insert(idx, line)),
+        any::<usize>().prop_map(Mutation::Delete),
+        (any::<usize>(), line()).prop_map(|(idx, line)| Mutation::Replace(idx, line)),
+    ]
+}
+
+/// Generates a sequence of mutations for a text with `line_count` lines
+///
+/// `density` is the maximum number of mutations per line, e.g. a density of `0.1` produces at
+/// most one mutation for every ten lines (and always allows at least one).
+pub fn mutations(line_count: usize, density: f64) -> impl Strategy<Value = Vec<Mutation>> {
+    let max = ((line_count as f64 * density).ceil() as usize).max(1);
+    collection::vec(mutation(), 0..=max)
+}
+
+/// Applies a sequence of mutations to `text`
+pub fn apply_mutations(text: &str, mutations: &[Mutation]) -> String {
+    let mut lines: Vec<String> = text.split_inclusive('\n').map(String::from).collect();
+
+    for mutation in mutations {
+        match mutation {
+            Mutation::Insert(idx, line) => {
+                let idx = idx % (lines.len() + 1);
+                lines.insert(idx, line.clone());
+            }
+            Mutation::Delete(idx) if !lines.is_empty() => {
+                let idx = idx % lines.len();
+                lines.remove(idx);
+            }
+            Mutation::Replace(idx, line) if !lines.is_empty() => {
+                let idx = idx % lines.len();
+                lines[idx] = line.clone();
+            }
+            Mutation::Delete(_) | Mutation::Replace(_, _) => {}
+        }
+    }
+
+    // Keep every line but the last terminated so that the result is still a valid text
+    let len = lines.len();
+    for line in lines.iter_mut().take(len.saturating_sub(1)) {
+        if !line.ends_with('\n') {
+            line.push('\n');
+        }
+    }
+
+    lines.concat()
+}
+
+/// Generates a base text of at most `max_lines` lines along with a mutated copy of it
+pub fn text_pair(max_lines: usize, density: f64) -> impl Strategy<Value = (String, String)> {
+    text(max_lines).prop_flat_map(move |old| {
+        let line_count = old.lines().count();
+        mutations(line_count, density).prop_map(move |mutations| {
+            let new = apply_mutations(&old, &mutations);
+            (old.clone(), new)
+        })
+    })
+}
+
+/// Generates an `(ancestor, ours, theirs)` triple where `ours` and `theirs` are independently
+/// mutated copies of `ancestor`
+pub fn text_triple(
+    max_lines: usize,
+    density: f64,
+) -> impl Strategy<Value = (String, String, String)> {
+    text(max_lines).prop_flat_map(move |ancestor| {
+        let line_count = ancestor.lines().count();
+        (
+            mutations(line_count, density),
+            mutations(line_count, density),
+        )
+            .prop_map(move |(a, b)| {
+                let ours = apply_mutations(&ancestor, &a);
+                let theirs = apply_mutations(&ancestor, &b);
+                (ancestor.clone(), ours, theirs)
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "binary-delta")]
+    use crate::binary_delta;
+    use crate::fuzzy::FuzzyPatch;
+    use crate::{
+        apply, apply_delta, cleanup, create_patch, diff, diff_lines, encode_delta, merge,
+        merge_conflict_count, merge_is_clean, AppendDiff, ApplyOptions, Diff, DiffOptions,
+        MergeOptions, Patch, Resolution,
+    };
+    use alloc::borrow::Cow;
+    #[cfg(feature = "binary-delta")]
+    use core::cmp;
+
+    fn reconstruct(diffs: &[Diff<'_, str>]) -> (String, String) {
+        let mut old = String::new();
+        let mut new = String::new();
+        for d in diffs {
+            match *d {
+                Diff::Equal(s) => {
+                    old.push_str(s);
+                    new.push_str(s);
+                }
+                Diff::Delete(s) => old.push_str(s),
+                Diff::Insert(s) => new.push_str(s),
+            }
+        }
+        (old, new)
+    }
+
+    // Segments of a hand built diff, as `(kind, text)` where kind 0 is equal, 1 a deletion and
+    // 2 an insertion
+    fn segments() -> impl Strategy<Value = Vec<(u8, String)>> {
+        collection::vec((0u8..3, "[ab\n]{0,4}"), 0..20)
+    }
+
+    proptest! {
+        #[test]
+        fn diff_reconstructs_inputs((old, new) in text_pair(30, 0.2)) {
+            let solution = diff(&old, &new);
+            let (reconstructed_old, reconstructed_new) = reconstruct(&solution);
+            prop_assert_eq!(reconstructed_old, old);
+            prop_assert_eq!(reconstructed_new, new);
+        }
+
+        #[test]
+        fn diff_reconstructs_unicode_inputs(
+            old in "[aé\u{2603}\u{1f600}]{0,16}",
+            new in "[aé\u{2603}\u{1f600}]{0,16}",
+        ) {
+            let solution = diff(&old, &new);
+            let (reconstructed_old, reconstructed_new) = reconstruct(&solution);
+            prop_assert_eq!(reconstructed_old, old);
+            prop_assert_eq!(reconstructed_new, new);
+        }
+
+        #[test]
+        fn fuzzy_patch_round_trips(old in "\\PC{0,40}", new in "\\PC{0,40}") {
+            let patch = FuzzyPatch::make(&old, &new);
+            let (patched, applied) = patch.apply(&old);
+            prop_assert!(applied.iter().all(|&applied| applied));
+            prop_assert_eq!(patched, new);
+        }
+
+        #[test]
+        fn delta_round_trips((old, new) in text_pair(30, 0.2), suffix in "\\PC{0,20}") {
+            let new = new + &suffix;
+            prop_assert_eq!(apply_delta(&old, &encode_delta(&old, &new)).unwrap(), new);
+        }
+
+        #[test]
+        fn compact_preserves_reconstruction(segments in segments()) {
+            let diffs: Vec<_> = segments
+                .iter()
+                .map(|(kind, s)| match kind {
+                    0 => Diff::Equal(s.as_str()),
+                    1 => Diff::Delete(s.as_str()),
+                    _ => Diff::Insert(s.as_str()),
+                })
+                .collect();
+            let (old, new) = reconstruct(&diffs);
+
+            // Re-borrow every segment from the reconstructed texts
+            let mut compacted = Vec::with_capacity(diffs.len());
+            let (mut old_pos, mut new_pos) = (0, 0);
+            for d in diffs {
+                compacted.push(match d {
+                    Diff::Equal(s) => {
+                        old_pos += s.len();
+                        new_pos += s.len();
+                        Diff::Equal(&old[old_pos - s.len()..old_pos])
+                    }
+                    Diff::Delete(s) => {
+                        old_pos += s.len();
+                        Diff::Delete(&old[old_pos - s.len()..old_pos])
+                    }
+                    Diff::Insert(s) => {
+                        new_pos += s.len();
+                        Diff::Insert(&new[new_pos - s.len()..new_pos])
+                    }
+                });
+            }
+
+            cleanup::compact(&old, &new, &mut compacted).unwrap();
+            let (compacted_old, compacted_new) = reconstruct(&compacted);
+            prop_assert_eq!(&compacted_old, &old);
+            prop_assert_eq!(&compacted_new, &new);
+            for pair in compacted.windows(2) {
+                prop_assert!(core::mem::discriminant(&pair[0]) != core::mem::discriminant(&pair[1]));
+            }
+        }
+
+        #[test]
+        fn create_apply_round_trips((old, new) in text_pair(30, 0.2)) {
+            let patch = create_patch(&old, &new);
+            prop_assert_eq!(apply(&old, &patch).unwrap(), new.as_str());
+            prop_assert_eq!(apply(&new, &patch.reverse()).unwrap(), old.as_str());
+
+            let patch_str = patch.to_string();
+            prop_assert_eq!(Patch::from_str(&patch_str).unwrap(), patch);
+        }
+
+        #[test]
+        fn squeezed_patches_apply_to_original(
+            (old, new) in text_pair(30, 0.3),
+            // Without context lines a hunk is placed by its new line numbers, which count the
+            // blank lines of the runs left out of the patch
+            context_len in 1..4usize,
+        ) {
+            let mut options = DiffOptions::new();
+            options.set_squeeze_blank_lines(true).set_context_len(context_len);
+            let patch = options.create_patch(&old, &new);
+            let patched = apply(&old, &patch).unwrap();
+
+            // Only the length of runs of blank lines outside the hunks may differ
+            let squeeze = |text: &str| -> String {
+                let mut prev_blank = false;
+                text.split_inclusive('\n')
+                    .filter(|&line| {
+                        let blank = line == "\n" || line == "\r\n";
+                        let squeezed = blank && prev_blank;
+                        prev_blank = blank;
+                        !squeezed
+                    })
+                    .collect()
+            };
+            prop_assert_eq!(squeeze(&patched), squeeze(&new));
+            prop_assert!(options.create_patch(&patched, &new).hunks().is_empty());
+        }
+
+        #[test]
+        fn apply_cow_matches_apply(
+            (old, new) in text_pair(30, 0.2),
+            context_len in 0..4usize,
+            strip_newline: bool,
+        ) {
+            let mut options = DiffOptions::new();
+            options.set_context_len(context_len);
+            let patch = options.create_patch(&old, &new);
+
+            // Also apply to a base which lost its trailing newline
+            let base = if strip_newline { old.trim_end_matches('\n') } else { &old };
+            let mut apply_options = ApplyOptions::new();
+            apply_options.set_ignore_trailing_newline(strip_newline);
+            prop_assert_eq!(
+                apply_options.apply_cow(base, &patch).map(Cow::into_owned),
+                apply_options.apply(base, &patch)
+            );
+            let bpatch = Patch::from(patch.clone());
+            prop_assert_eq!(
+                apply_options.apply_bytes_cow(base.as_bytes(), &bpatch).map(Cow::into_owned),
+                apply_options.apply_bytes(base.as_bytes(), &bpatch)
+            );
+        }
+
+        #[test]
+        fn minimal_never_grows_diff((old, new) in text_pair(30, 0.2), junk: bool) {
+            let mut options = DiffOptions::new();
+            if junk {
+                options.set_is_junk(Some(|line| line.len() < 3));
+            }
+            let counts = options.diff_counts(&old, &new);
+
+            options.set_minimal(true);
+            let minimal = options.diff_counts(&old, &new);
+            prop_assert!(minimal.1 <= counts.1 && minimal.2 <= counts.2);
+            if !junk {
+                prop_assert_eq!(minimal, counts);
+            }
+            let patch = options.create_patch(&old, &new);
+            prop_assert_eq!(apply(&old, &patch).unwrap(), new.as_str());
+        }
+
+        #[test]
+        fn append_diff_round_trips(
+            (old, new) in text_pair(30, 0.2),
+            splits in collection::vec(any::<sample::Index>(), 0..4),
+        ) {
+            // Append the modified text in pieces, which may split its lines
+            let mut splits: Vec<_> = splits.iter().map(|split| split.index(new.len() + 1)).collect();
+            splits.sort_unstable();
+            let mut diff = AppendDiff::new(&old, "");
+            let mut start = 0;
+            for end in splits.into_iter().chain(Some(new.len())) {
+                diff.append_new(&new[start..end]);
+                start = end;
+            }
+
+            prop_assert_eq!(diff.modified(), new.as_str());
+            let patch = diff.to_patch(3);
+            prop_assert_eq!(apply(&old, &patch).unwrap(), new.as_str());
+            prop_assert_eq!(apply(&new, &patch.reverse()).unwrap(), old.as_str());
+        }
+
+        #[test]
+        fn reverse_round_trips((old, new) in text_pair(30, 0.2), context_len in 0..4usize) {
+            let mut options = DiffOptions::new();
+            options.set_context_len(context_len);
+            let patch = options.create_patch(&old, &new);
+            prop_assert!(patch.is_reversible());
+
+            let redone = apply(&old, &patch).unwrap();
+            prop_assert_eq!(&redone, &new);
+            prop_assert_eq!(apply(&redone, &patch.reverse()).unwrap(), old);
+        }
+
+        #[test]
+        fn hunk_count_matches_patch((old, new) in text_pair(30, 0.2), context_len in 0..5usize) {
+            let diff = diff_lines(&old, &new);
+            prop_assert_eq!(diff.hunk_count(context_len), diff.to_patch(context_len).hunks().len());
+        }
+
+        #[test]
+        fn normalize_is_idempotent((old, new) in text_pair(30, 0.2), context_len in 0..5usize) {
+            let mut options = DiffOptions::new();
+            options.set_context_len(context_len);
+            let patch = options.create_patch(&old, &new);
+
+            let normalized = patch.normalize(&old, &DiffOptions::new()).unwrap();
+            prop_assert_eq!(apply(&old, &normalized).unwrap(), new.as_str());
+            prop_assert_eq!(&normalized, &create_patch(&old, &new));
+            prop_assert_eq!(normalized.normalize(&old, &DiffOptions::new()).unwrap(), normalized.clone());
+        }
+
+        #[test]
+        fn merge_hunks_applies_identically(
+            (old, new) in text_pair(30, 0.2),
+            context_len in 0..3usize,
+            max_gap in 0..8usize,
+        ) {
+            let mut options = DiffOptions::new();
+            options.set_context_len(context_len);
+            let patch = options.create_patch(&old, &new);
+
+            for patch in [patch.clone(), patch.strip_context()] {
+                let merged = patch.merge_hunks(max_gap, Some(&old)).unwrap();
+                prop_assert_eq!(apply(&old, &merged).unwrap(), new.as_str());
+                prop_assert!(merged.hunks().len() <= patch.hunks().len());
+                let merged_str = merged.to_string();
+                prop_assert_eq!(Patch::from_str(&merged_str).unwrap(), merged.clone());
+            }
+
+            let merged = patch.merge_hunks(usize::MAX, Some(&old)).unwrap();
+            prop_assert!(merged.hunks().len() <= 1);
+            prop_assert_eq!(apply(&old, &merged).unwrap(), new.as_str());
+        }
+
+        #[test]
+        fn merge_identical_sides_is_clean((ancestor, ours, theirs) in text_triple(30, 0.2)) {
+            prop_assert_eq!(merge(&ancestor, &ours, &ours), Ok(ours.clone()));
+            prop_assert_eq!(merge(&ancestor, &ancestor, &theirs), Ok(theirs.clone()));
+            prop_assert_eq!(merge(&ancestor, &ours, &ancestor), Ok(ours));
+        }
+
+        #[test]
+        fn merge_is_clean_agrees_with_merge(
+            (ancestor, ours, theirs) in text_triple(30, 0.3),
+            resolve: bool,
+        ) {
+            let mut options = MergeOptions::new();
+            if resolve {
+                // Resolve only some of the conflicts
+                options.set_resolver(|region| match region.ancestor() {
+                    [] => Resolution::Theirs,
+                    _ => Resolution::Unresolved,
+                });
+            }
+
+            let report = options.merge_report(&ancestor, &ours, &theirs);
+            prop_assert_eq!(
+                merge_is_clean(&ancestor, &ours, &theirs, &options),
+                options.merge(&ancestor, &ours, &theirs).is_ok()
+            );
+            prop_assert_eq!(
+                merge_conflict_count(&ancestor, &ours, &theirs, &options),
+                report.conflicts().len()
+            );
+        }
+    }
+
+    // A binary file along with a copy of it where ranges have been replaced, duplicated or moved,
+    // as `(start, len, kind, bytes)` edits applied in order
+    #[cfg(feature = "binary-delta")]
+    fn binary_pair() -> impl Strategy<Value = (Vec<u8>, Vec<u8>)> {
+        let edit = (
+            any::<usize>(),
+            0..200usize,
+            0u8..3,
+            collection::vec(any::<u8>(), 0..50),
+        );
+        (
+            collection::vec(any::<u8>(), 0..3000),
+            collection::vec(edit, 0..10),
+        )
+            .prop_map(|(old, edits)| {
+                let mut new = old.clone();
+                for (start, len, kind, bytes) in edits {
+                    let start = start % (new.len() + 1);
+                    let end = cmp::min(start + len, new.len());
+                    match kind {
+                        0 => {
+                            new.splice(start..end, bytes);
+                        }
+                        1 => {
+                            let copy = new[start..end].to_vec();
+                            new.splice(start..start, copy);
+                        }
+                        _ => {
+                            let block: Vec<u8> = new.drain(start..end).collect();
+                            new.extend(block);
+                        }
+                    }
+                }
+                (old, new)
+            })
+    }
+
+    #[cfg(feature = "binary-delta")]
+    proptest! {
+        #[test]
+        fn binary_delta_round_trips_random(
+            old in collection::vec(any::<u8>(), 0..500),
+            new in collection::vec(any::<u8>(), 0..500),
+        ) {
+            let delta = binary_delta::encode_delta(&old, &new);
+            prop_assert_eq!(binary_delta::apply_delta(&old, &delta).unwrap(), new);
+        }
+
+        #[test]
+        fn binary_delta_round_trips_edits((old, new) in binary_pair()) {
+            let delta = binary_delta::encode_delta(&old, &new);
+            prop_assert_eq!(binary_delta::apply_delta(&old, &delta).unwrap(), new.clone());
+            // Most of the new file is copied rather than inserted
+            prop_assert!(delta.len() <= new.len() / 2 + 600);
+        }
+    }
+}
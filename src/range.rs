@@ -1,4 +1,4 @@
-use std::{cmp, fmt::Debug, ops};
+use core::{cmp, fmt::Debug, ops};
 
 // Range type inspired by the Range type used in [dissimilar](https://docs.rs/dissimilar)
 #[derive(Debug)]
@@ -415,69 +415,34 @@ where
     }
 }
 
-impl<'a, 'b> DiffRange<'a, 'b, [u8]> {
-    pub fn to_str(self, text1: &'a str, text2: &'b str) -> DiffRange<'a, 'b, str> {
-        fn boundary_down(text: &str, pos: usize) -> usize {
-            let mut adjust = 0;
-            while !text.is_char_boundary(pos - adjust) {
-                adjust += 1;
-            }
-            adjust
-        }
-
-        fn boundary_up(text: &str, pos: usize) -> usize {
-            let mut adjust = 0;
-            while !text.is_char_boundary(pos + adjust) {
-                adjust += 1;
-            }
-            adjust
+impl<T> DiffRange<'_, '_, [T]> {
+    // Maps a diff of the chars of two texts back onto the texts. `offset1` and `offset2` return
+    // the byte offset of the char at an index, or the length of the text for the index past its
+    // last char.
+    pub fn to_str<'a, 'b>(
+        self,
+        text1: &'a str,
+        offset1: impl Fn(usize) -> usize,
+        text2: &'b str,
+        offset2: impl Fn(usize) -> usize,
+    ) -> DiffRange<'a, 'b, str> {
+        fn map<'t, T>(
+            range: Range<'_, [T]>,
+            text: &'t str,
+            offset: impl Fn(usize) -> usize,
+        ) -> Range<'t, str> {
+            Range::new(
+                text,
+                offset(range.offset())..offset(range.offset() + range.len()),
+            )
         }
 
         match self {
             DiffRange::Equal(range1, range2) => {
-                debug_assert_eq!(range1.inner().as_ptr(), text1.as_ptr());
-                debug_assert_eq!(range2.inner().as_ptr(), text2.as_ptr());
-                let mut offset1 = range1.offset();
-                let mut len1 = range1.len();
-                let mut offset2 = range2.offset();
-                let mut len2 = range2.len();
-
-                let adjust = boundary_up(text1, offset1);
-                offset1 += adjust;
-                len1 -= adjust;
-                offset2 += adjust;
-                len2 -= adjust;
-                let adjust = boundary_down(text1, offset1 + len1);
-                len1 -= adjust;
-                len2 -= adjust;
-
-                DiffRange::Equal(
-                    Range::new(text1, offset1..offset1 + len1),
-                    Range::new(text2, offset2..offset2 + len2),
-                )
-            }
-            DiffRange::Delete(range) => {
-                debug_assert_eq!(range.inner().as_ptr(), text1.as_ptr());
-                let mut offset = range.offset();
-                let mut len = range.len();
-                let adjust = boundary_down(text1, offset);
-                offset -= adjust;
-                len += adjust;
-                let adjust = boundary_up(text1, offset + len);
-                len += adjust;
-                DiffRange::Delete(Range::new(text1, offset..offset + len))
-            }
-            DiffRange::Insert(range) => {
-                debug_assert_eq!(range.inner().as_ptr(), text2.as_ptr());
-                let mut offset = range.offset();
-                let mut len = range.len();
-                let adjust = boundary_down(text2, offset);
-                offset -= adjust;
-                len += adjust;
-                let adjust = boundary_up(text2, offset + len);
-                len += adjust;
-                DiffRange::Insert(Range::new(text2, offset..offset + len))
+                DiffRange::Equal(map(range1, text1, offset1), map(range2, text2, offset2))
             }
+            DiffRange::Delete(range) => DiffRange::Delete(map(range, text1, offset1)),
+            DiffRange::Insert(range) => DiffRange::Insert(map(range, text2, offset2)),
         }
     }
 }
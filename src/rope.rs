@@ -0,0 +1,284 @@
+use crate::{
+    apply::{ApplyError, ApplyOptions, Image},
+    patch::Patch,
+};
+use alloc::{borrow::Cow, string::String, vec::Vec};
+use core::{mem, ops};
+use ropey::Rope;
+
+/// The edits [`apply_to_rope`] made to a `Rope`
+///
+/// [`apply_to_rope`]: fn.apply_to_rope.html
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ApplyReport {
+    edits: Vec<RopeEdit>,
+}
+
+impl ApplyReport {
+    /// Returns the edits which were made, in order of their position in the rope
+    ///
+    /// Edits don't overlap, and only cover lines which changed. Hunks next to each other may be
+    /// reported as a single edit.
+    pub fn edits(&self) -> &[RopeEdit] {
+        &self.edits
+    }
+}
+
+/// A replacement of a range of chars in a `Rope`
+///
+/// Positions are char indexes, as used by `Rope`'s own editing methods.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RopeEdit {
+    /// The range of chars which were replaced, in the rope from before the patch was applied
+    pub old: ops::Range<usize>,
+    /// The range of chars which replaced them, in the rope from after the patch was applied
+    pub new: ops::Range<usize>,
+}
+
+/// Apply a `Patch` to a [`Rope`] in place
+///
+/// This gives the same result as [`ApplyOptions::apply`] on the rope's contents, without
+/// turning the rope into a `String` and back. Instead, the lines matched by each hunk are
+/// replaced with `Rope::remove` and `Rope::insert`, and the edits made are returned so that
+/// positions in the rope, like cursors, can be mapped over the patch. The rope is left unchanged
+/// if the patch doesn't apply.
+///
+/// Lines end with `\n`, like everywhere else in this crate, regardless of which line breaks
+/// `Rope` itself recognizes.
+///
+/// ```
+/// use diffy::{apply_to_rope, create_patch, ApplyOptions};
+/// use ropey::Rope;
+///
+/// let patch = create_patch("über\nthe\nlazy dog\n", "über\nthe\nquick fox\n");
+///
+/// let mut rope = Rope::from_str("über\nthe\nlazy dog\n");
+/// let report = apply_to_rope(&mut rope, &patch, &ApplyOptions::new()).unwrap();
+/// assert_eq!(rope, "über\nthe\nquick fox\n");
+///
+/// let edit = &report.edits()[0];
+/// assert_eq!((edit.old.clone(), edit.new.clone()), (9..18, 9..19));
+/// ```
+///
+/// [`Rope`]: https://docs.rs/ropey/1/ropey/struct.Rope.html
+/// [`ApplyOptions::apply`]: struct.ApplyOptions.html#method.apply
+pub fn apply_to_rope(
+    rope: &mut Rope,
+    patch: &Patch<'_, str>,
+    options: &ApplyOptions,
+) -> Result<ApplyReport, ApplyError> {
+    let bounds = line_bounds(rope);
+    let lines: Vec<Cow<'_, str>> = bounds
+        .windows(2)
+        .map(|w| rope.byte_slice(w[0]..w[1]).into())
+        .collect();
+
+    let mut image = Image::from_lines(lines.iter().map(|line| &**line));
+    image.apply(patch, options)?;
+
+    // Collect the ranges of lines which were replaced along with the lines replacing them
+    let mut replaced = Vec::new();
+    let mut next_line = 0;
+    let mut new_lines = Vec::new();
+    for (line, origin) in image.lines().zip(image.origins()) {
+        match origin {
+            Some(idx) => {
+                if idx != next_line || !new_lines.is_empty() {
+                    replaced.push((next_line..idx, mem::take(&mut new_lines)));
+                }
+                next_line = idx + 1;
+            }
+            None => new_lines.push(line),
+        }
+    }
+    if next_line != lines.len() || !new_lines.is_empty() {
+        replaced.push((next_line..lines.len(), new_lines));
+    }
+
+    // Hunks rewrite their context lines too, which don't need to be edited unless they changed
+    let mut replacements: Vec<(ops::Range<usize>, String)> = replaced
+        .into_iter()
+        .filter_map(|(mut old, new)| {
+            let same = |&(old, new): &(&Cow<'_, str>, &&str)| **old == **new;
+            let prefix_len = lines[old.clone()].iter().zip(&new).take_while(same).count();
+            old.start += prefix_len;
+            let new = &new[prefix_len..];
+            let suffix_len = lines[old.clone()]
+                .iter()
+                .rev()
+                .zip(new.iter().rev())
+                .take_while(same)
+                .count();
+            old.end -= suffix_len;
+            let new = &new[..new.len() - suffix_len];
+            if old.is_empty() && new.is_empty() {
+                None
+            } else {
+                Some((old, new.concat()))
+            }
+        })
+        .collect();
+
+    // The newline at the end of the patched text can differ from the one its last line has
+    if let Some(eof_newline) = image.eof_newline() {
+        let last_line = image.lines().last().unwrap_or("");
+        if last_line.ends_with('\n') != eof_newline {
+            let end = lines.len();
+            match replacements.last() {
+                Some((old, text)) if old.end == end && !text.is_empty() => {}
+                // The last line is an unchanged one, so it needs to be replaced as well
+                _ => {
+                    let start = match replacements.last() {
+                        Some((old, _)) if old.end == end => old.start,
+                        _ => end,
+                    };
+                    if start != end {
+                        replacements.pop();
+                    }
+                    let start = start.saturating_sub(1);
+                    let line = lines.get(start).map_or("", |line| &**line);
+                    replacements.push((start..end, String::from(line)));
+                }
+            }
+            let text = &mut replacements.last_mut().unwrap().1;
+            if eof_newline {
+                text.push('\n');
+            } else {
+                text.pop();
+            }
+        }
+    }
+
+    let mut edits = Vec::with_capacity(replacements.len());
+    let mut new_pos = 0;
+    let mut old_pos = 0;
+    for (old_lines, text) in &replacements {
+        let old =
+            rope.byte_to_char(bounds[old_lines.start])..rope.byte_to_char(bounds[old_lines.end]);
+        let new_start = new_pos + (old.start - old_pos);
+        let new = new_start..new_start + text.chars().count();
+        old_pos = old.end;
+        new_pos = new.end;
+        edits.push(RopeEdit { old, new });
+    }
+
+    // Editing from the back keeps the positions of the edits before each one valid
+    for (edit, (_, text)) in edits.iter().zip(&replacements).rev() {
+        rope.remove(edit.old.clone());
+        rope.insert(edit.old.start, text);
+    }
+
+    Ok(ApplyReport { edits })
+}
+
+// Returns the byte offsets the rope's lines start at, followed by the length of the rope
+fn line_bounds(rope: &Rope) -> Vec<usize> {
+    let mut bounds = Vec::with_capacity(rope.len_lines() + 1);
+    bounds.push(0);
+    let mut offset = 0;
+    for chunk in rope.chunks() {
+        bounds.extend(
+            chunk
+                .bytes()
+                .enumerate()
+                .filter(|&(_, byte)| byte == b'\n')
+                .map(|(idx, _)| offset + idx + 1),
+        );
+        offset += chunk.len();
+    }
+    if *bounds.last().unwrap() != offset {
+        bounds.push(offset);
+    }
+    bounds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{create_patch, Patch};
+    use alloc::format;
+
+    // Applies `patch` to a rope of `base`, checking the result and the reported edits against
+    // applying it to the string
+    fn check(base: &str, patch: &Patch<'_, str>, options: &ApplyOptions) {
+        let mut rope = Rope::from_str(base);
+        let result = apply_to_rope(&mut rope, patch, options);
+        let expected = options.apply(base, patch);
+        assert_eq!(result.as_ref().err(), expected.as_ref().err());
+        let (report, expected) = match (result, expected) {
+            (Ok(report), Ok(expected)) => (report, expected),
+            _ => {
+                assert_eq!(rope, base);
+                return;
+            }
+        };
+        assert_eq!(rope, expected.as_str());
+
+        // Replaying the edits from the front on the base gives the result too
+        let base: Vec<char> = base.chars().collect();
+        let expected: Vec<char> = expected.chars().collect();
+        let mut replayed = Vec::new();
+        let mut old_pos = 0;
+        for edit in report.edits() {
+            assert!(edit.old.start >= old_pos, "{:?}", report);
+            replayed.extend_from_slice(&base[old_pos..edit.old.start]);
+            assert_eq!(replayed.len(), edit.new.start);
+            replayed.extend_from_slice(&expected[edit.new.clone()]);
+            old_pos = edit.old.end;
+        }
+        replayed.extend_from_slice(&base[old_pos..]);
+        assert_eq!(replayed, expected);
+    }
+
+    #[test]
+    fn matches_string_apply() {
+        let options = ApplyOptions::new();
+        let cases = [
+            ("", "a\n"),
+            ("a\n", ""),
+            ("a\nb\nc\n", "a\nB\nc\n"),
+            ("a\nb\nc", "a\nb\nc\n"),
+            ("a\nb\nc\n", "a\nb\nc"),
+            ("a\r\nb\r\n", "a\r\nB\r\n"),
+            ("héllo\nwörld\n", "héllo\n🌍\nwörld\n"),
+            ("日本\n語\nテキスト\n", "日本\nテキスト\n終\n"),
+        ];
+        for (original, modified) in cases {
+            check(original, &create_patch(original, modified), &options);
+        }
+
+        // Long texts span several chunks, with multi-byte chars on either side of edits
+        let original: String = (0..2000).map(|i| format!("ligne {} – ü\n", i)).collect();
+        let modified = original
+            .replace("ligne 10 ", "LIGNE 10 ")
+            .replace("ligne 999 – ü\n", "")
+            .replace("ligne 1500 ", "ligne 1500 ✓\nnouvelle ")
+            + "fin";
+        let patch = create_patch(&original, &modified);
+        check(&original, &patch, &options);
+
+        // Hunks apply at an offset, and patches which don't apply leave the rope unchanged
+        let shifted = format!("extra\nextra\n{}", original);
+        check(&shifted, &patch, &options);
+        check(&modified, &patch, &options);
+    }
+
+    #[test]
+    fn ignore_trailing_newline() {
+        let mut options = ApplyOptions::new();
+        options.set_ignore_trailing_newline(true);
+
+        for (patched, base) in [
+            (("a\nb\n", "a\nB\n"), "a\nb"),
+            (("a\nb", "a\nB"), "a\nb\n"),
+            (("a\nb\nc\n", "a\nb\n"), "a\nb\nc"),
+            (("a\nb\nc", "a\nb"), "a\nb\nc\n"),
+            (("a\nb\n", ""), "a\nb"),
+            (("a\nb\n", "a\nb\nc\n"), "a\nb"),
+        ] {
+            check(base, &create_patch(patched.0, patched.1), &options);
+        }
+        assert!(options.apply("x\n", &create_patch("a\n", "b\n")).is_err());
+        check("x\n", &create_patch("a\n", "b\n"), &options);
+    }
+}
@@ -0,0 +1,522 @@
+use crate::{
+    apply::{ApplyError, ApplyOptions, Image},
+    patch::Patch,
+    utils::Text,
+};
+use alloc::{borrow::ToOwned, string::String, vec, vec::Vec};
+use core::{cmp, fmt};
+
+/// An error returned when working with a [`PatchSeries`] fails
+///
+/// Patches are identified by their 0-based index in the series.
+///
+/// [`PatchSeries`]: struct.PatchSeries.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SeriesError {
+    /// A patch in the series couldn't be applied to the result of the patches preceding it
+    Apply {
+        /// The index of the patch which failed to apply
+        patch: usize,
+        /// Why the patch failed to apply
+        error: ApplyError,
+        /// The text the patch was applied to, i.e. the result of the patches preceding it
+        image: Vec<u8>,
+        /// The index of the last preceding patch which touched the lines the failing hunk was
+        /// expected to apply to, which is the most likely cause of the conflict
+        culprit: Option<usize>,
+    },
+    /// The requested order isn't a permutation of the patches in the series
+    InvalidOrder,
+    /// Every patch applied in the requested order, but the final result differs from applying
+    /// them in their current order
+    DifferentResult,
+}
+
+impl SeriesError {
+    /// Returns the index of the patch which failed to apply, if any
+    pub fn patch(&self) -> Option<usize> {
+        match self {
+            SeriesError::Apply { patch, .. } => Some(*patch),
+            SeriesError::InvalidOrder | SeriesError::DifferentResult => None,
+        }
+    }
+
+    /// Returns the text the failing patch was applied to, if any
+    ///
+    /// This is the base text with every patch preceding the failing one applied. It's valid
+    /// utf8 when applying `str` patches.
+    pub fn image(&self) -> Option<&[u8]> {
+        match self {
+            SeriesError::Apply { image, .. } => Some(image),
+            SeriesError::InvalidOrder | SeriesError::DifferentResult => None,
+        }
+    }
+
+    /// Returns the index of the preceding patch which most likely caused a patch to fail to
+    /// apply, if any
+    pub fn culprit(&self) -> Option<usize> {
+        match self {
+            SeriesError::Apply { culprit, .. } => *culprit,
+            SeriesError::InvalidOrder | SeriesError::DifferentResult => None,
+        }
+    }
+
+    // Maps the indices of patches in the applied order back to their indices in the series
+    fn reordered(self, order: &[usize]) -> Self {
+        match self {
+            SeriesError::Apply {
+                patch,
+                error,
+                image,
+                culprit,
+            } => SeriesError::Apply {
+                patch: order[patch],
+                error,
+                image,
+                culprit: culprit.map(|culprit| order[culprit]),
+            },
+            err => err,
+        }
+    }
+}
+
+impl fmt::Display for SeriesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SeriesError::Apply {
+                patch,
+                error,
+                culprit,
+                ..
+            } => {
+                write!(f, "patch #{}: {}", patch, error)?;
+                if let Some(culprit) = culprit {
+                    write!(f, " (conflicts with patch #{})", culprit)?;
+                }
+                Ok(())
+            }
+            SeriesError::InvalidOrder => write!(f, "order isn't a permutation of the series"),
+            SeriesError::DifferentResult => {
+                write!(f, "reordered series produces a different result")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SeriesError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SeriesError::Apply { error, .. } => Some(error),
+            SeriesError::InvalidOrder | SeriesError::DifferentResult => None,
+        }
+    }
+}
+
+/// An ordered stack of patches where each patch applies to the result of the ones before it
+///
+/// This is the model used by patch management tools like quilt: patches are pushed onto and
+/// popped off the top of the series and the whole series is applied to a base text in order.
+///
+/// ```
+/// use diffy::{create_patch, PatchSeries};
+///
+/// let base = "a\nb\nc\n";
+/// let first = "a\nB\nc\n";
+/// let second = "a\nB\nc\nd\n";
+///
+/// let mut series = PatchSeries::new();
+/// series.push(create_patch(base, first));
+/// series.push(create_patch(first, second));
+/// assert_eq!(series.apply_all(base).unwrap(), second);
+///
+/// series.pop();
+/// assert_eq!(series.apply_all(base).unwrap(), first);
+/// ```
+pub struct PatchSeries<'a, T: ToOwned + ?Sized> {
+    patches: Vec<Patch<'a, T>>,
+}
+
+impl<'a, T: ToOwned + ?Sized> PatchSeries<'a, T> {
+    /// Construct an empty series
+    pub fn new() -> Self {
+        Self {
+            patches: Vec::new(),
+        }
+    }
+
+    /// Returns the patches in the series, in the order they're applied
+    pub fn patches(&self) -> &[Patch<'a, T>] {
+        &self.patches
+    }
+
+    /// Returns the number of patches in the series
+    pub fn len(&self) -> usize {
+        self.patches.len()
+    }
+
+    /// Returns `true` if the series doesn't contain any patches
+    pub fn is_empty(&self) -> bool {
+        self.patches.is_empty()
+    }
+
+    /// Push a patch onto the top of the series
+    pub fn push(&mut self, patch: Patch<'a, T>) {
+        self.patches.push(patch);
+    }
+
+    /// Remove the patch at the top of the series and return it
+    pub fn pop(&mut self) -> Option<Patch<'a, T>> {
+        self.patches.pop()
+    }
+
+    // Checks that `order` contains every index of the series exactly once
+    fn validate_order(&self, order: &[usize]) -> Result<(), SeriesError> {
+        let mut seen = vec![false; self.patches.len()];
+        if order.len() != seen.len() {
+            return Err(SeriesError::InvalidOrder);
+        }
+        for &idx in order {
+            match seen.get_mut(idx) {
+                Some(seen) if !*seen => *seen = true,
+                _ => return Err(SeriesError::InvalidOrder),
+            }
+        }
+        Ok(())
+    }
+
+    fn reorder_with(&mut self, base: &T, order: &[usize]) -> Result<(), SeriesError>
+    where
+        T: Text,
+    {
+        self.validate_order(order)?;
+        let options = ApplyOptions::new();
+        let expected = run_series(base, self.patches.iter(), &options)?.to_bytes();
+        let patches = order.iter().map(|&idx| &self.patches[idx]);
+        let reordered = run_series(base, patches, &options).map_err(|err| err.reordered(order))?;
+        if reordered.to_bytes() != expected {
+            return Err(SeriesError::DifferentResult);
+        }
+
+        let mut patches: Vec<_> = self.patches.drain(..).map(Some).collect();
+        self.patches = order
+            .iter()
+            .map(|&idx| patches[idx].take().expect("order was validated"))
+            .collect();
+        Ok(())
+    }
+}
+
+impl<'a> PatchSeries<'a, str> {
+    /// Apply every patch in the series to `base`, in order
+    pub fn apply_all(&self, base: &str) -> Result<String, SeriesError> {
+        apply_series(
+            base,
+            &self.patches.iter().collect::<Vec<_>>(),
+            &ApplyOptions::new(),
+        )
+    }
+
+    /// Reorder the series, where `order[i]` is the current index of the patch which should end
+    /// up at index `i`
+    ///
+    /// The series is only reordered if every patch still applies to `base` in the new order and
+    /// the final result is the same as before. Otherwise the series is left untouched and the
+    /// conflict is reported, identifying patches by their current index.
+    ///
+    /// ```
+    /// use diffy::{create_patch, PatchSeries};
+    ///
+    /// let base = "a\nb\nc\nd\ne\nf\ng\nh\n";
+    /// let first = "A\nb\nc\nd\ne\nf\ng\nh\n";
+    /// let second = "A\nb\nc\nd\ne\nf\ng\nH\n";
+    /// let third = "A\nb\nc\nd\ne\nf\ng\nH!\n";
+    ///
+    /// let mut series = PatchSeries::new();
+    /// series.push(create_patch(base, first));
+    /// series.push(create_patch(first, second));
+    /// series.push(create_patch(second, third));
+    ///
+    /// // The first patch doesn't touch the lines the others change
+    /// series.reorder(base, &[1, 2, 0]).unwrap();
+    /// assert_eq!(series.apply_all(base).unwrap(), third);
+    ///
+    /// // The patch adding "!" depends on the one capitalizing "h"
+    /// let err = series.reorder(base, &[1, 0, 2]).unwrap_err();
+    /// assert_eq!(err.patch(), Some(1));
+    /// ```
+    pub fn reorder(&mut self, base: &str, order: &[usize]) -> Result<(), SeriesError> {
+        self.reorder_with(base, order)
+    }
+}
+
+impl<'a> PatchSeries<'a, [u8]> {
+    /// Apply every patch in the series to a potentially non-utf8 `base`, in order
+    pub fn apply_all(&self, base: &[u8]) -> Result<Vec<u8>, SeriesError> {
+        let image = run_series(base, self.patches.iter(), &ApplyOptions::new())?;
+        Ok(image.to_bytes())
+    }
+
+    /// Reorder a series of potentially non-utf8 patches, see
+    /// [`PatchSeries::reorder`](struct.PatchSeries.html#method.reorder)
+    pub fn reorder(&mut self, base: &[u8], order: &[usize]) -> Result<(), SeriesError> {
+        self.reorder_with(base, order)
+    }
+}
+
+impl<'a, T: ToOwned + ?Sized> From<Vec<Patch<'a, T>>> for PatchSeries<'a, T> {
+    fn from(patches: Vec<Patch<'a, T>>) -> Self {
+        Self { patches }
+    }
+}
+
+impl<T: ToOwned + ?Sized> Default for PatchSeries<'_, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: ToOwned + ?Sized> Clone for PatchSeries<'_, T> {
+    fn clone(&self) -> Self {
+        Self {
+            patches: self.patches.clone(),
+        }
+    }
+}
+
+impl<T: ?Sized, O> fmt::Debug for PatchSeries<'_, T>
+where
+    T: ToOwned<Owned = O> + fmt::Debug,
+    O: core::borrow::Borrow<T> + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PatchSeries")
+            .field("patches", &self.patches)
+            .finish()
+    }
+}
+
+/// Apply a stack of patches to `base`, each to the result of the ones before it
+///
+/// If a patch fails to apply, the error identifies it by its index in `patches` along with the
+/// text it was applied to. The last preceding patch which touched the lines the failing hunk
+/// was expected to apply to is reported as the likely culprit.
+///
+/// ```
+/// use diffy::{apply_series, create_patch, ApplyOptions};
+///
+/// let base = "a\nb\nc\n";
+/// let first = create_patch(base, "a\nB\nc\n");
+/// let second = create_patch("a\nB\nc\n", "a\nB!\nc\n");
+///
+/// let options = ApplyOptions::new();
+/// let result = apply_series(base, &[&first, &second], &options).unwrap();
+/// assert_eq!(result, "a\nB!\nc\n");
+///
+/// let third = create_patch(base, "a\nb\nC\n");
+/// let err = apply_series(base, &[&first, &third], &options).unwrap_err();
+/// assert_eq!(err.patch(), Some(1));
+/// assert_eq!(err.culprit(), Some(0));
+/// assert_eq!(err.image(), Some(&b"a\nB\nc\n"[..]));
+/// ```
+pub fn apply_series(
+    base: &str,
+    patches: &[&Patch<'_, str>],
+    options: &ApplyOptions,
+) -> Result<String, SeriesError> {
+    let image = run_series(base, patches.iter().copied(), options)?;
+    Ok(image.render())
+}
+
+/// Check that a stack of patches applies to `base`, like [`apply_series`] but without
+/// producing the result
+///
+/// The lines of the intermediate results are only borrowed from `base` and the patches, so
+/// no text is built unless a patch fails to apply.
+///
+/// [`apply_series`]: fn.apply_series.html
+pub fn check_series(
+    base: &str,
+    patches: &[&Patch<'_, str>],
+    options: &ApplyOptions,
+) -> Result<(), SeriesError> {
+    run_series(base, patches.iter().copied(), options).map(|_| ())
+}
+
+// Applies the patches in order, tracking which patch last touched each line of the image so
+// that a failure can be blamed on one of them. The image is patched in place, and only rebuilt
+// from the preceding patches when a patch fails to apply.
+fn run_series<'a, T>(
+    base: &'a T,
+    patches: impl Iterator<Item = &'a Patch<'a, T>> + Clone,
+    options: &ApplyOptions,
+) -> Result<Image<'a, T>, SeriesError>
+where
+    T: Text + ToOwned + ?Sized,
+{
+    let mut image = Image::new(base);
+    let mut touched_by = vec![None; image.len()];
+    for (idx, patch) in patches.clone().enumerate() {
+        if let Err(error) = image.apply(patch, options) {
+            // Blame the lines where the failing hunk should have applied, accounting for the
+            // offset introduced by the hunks before it
+            let hunk = &patch.hunks()[error.hunk() - 1];
+            let start = cmp::min(hunk.new_range().to_std_range().start, image.len());
+            let end = cmp::min(start + hunk.old_range().len(), image.len());
+            let culprit = image
+                .origins()
+                .skip(start)
+                .take(end - start)
+                .filter_map(|origin| origin.and_then(|line| touched_by[line]))
+                .max();
+
+            // The hunks before the failing one are left applied, so the image the patch was
+            // applied to is rebuilt from the preceding patches
+            let mut before = Image::new(base);
+            for patch in patches.take(idx) {
+                before
+                    .apply(patch, options)
+                    .expect("the preceding patches applied before");
+                before.reset();
+            }
+            return Err(SeriesError::Apply {
+                patch: idx,
+                error,
+                image: before.to_bytes(),
+                culprit,
+            });
+        }
+
+        touched_by = image
+            .origins()
+            .map(|origin| match origin {
+                Some(line) => touched_by[line],
+                None => Some(idx),
+            })
+            .collect();
+        image.reset();
+    }
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::{create_patch, create_patch_bytes, DiffOptions};
+
+    #[test]
+    fn series_conflicts() {
+        let base = "a\nb\nc\n";
+        let first = "a\nB\nc\n";
+        let second = "a\nB!\nc\n";
+
+        let mut series = PatchSeries::new();
+        series.push(create_patch(base, first));
+        series.push(create_patch(first, second));
+        assert_eq!(series.len(), 2);
+
+        assert_eq!(series.reorder(base, &[0]), Err(SeriesError::InvalidOrder));
+        assert_eq!(
+            series.reorder(base, &[1, 1]),
+            Err(SeriesError::InvalidOrder)
+        );
+
+        let err = series.reorder(base, &[1, 0]).unwrap_err();
+        assert_eq!(err.patch(), Some(1));
+        assert_eq!(err.to_string(), "patch #1: error applying hunk #1");
+        assert_eq!(series.apply_all(base).unwrap(), second);
+        assert_eq!(series.apply_all("x\nb\nc\n").unwrap_err().patch(), Some(0));
+    }
+
+    #[test]
+    fn apply_series_blames_culprit() {
+        let base = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let v1 = "a\nb\nc\nd\ne\nf\ng\nh\ni\nJ\n";
+        let v2 = "a\nB\nc\nd\ne\nf\ng\nh\ni\nJ\n";
+        let v3 = "a\nB!\nc\nd\ne\nf\ng\nh\ni\nJ\n";
+        let first = create_patch(base, v1);
+        let second = create_patch(v1, v2);
+        let third = create_patch(v2, v3);
+        let options = ApplyOptions::new();
+
+        assert_eq!(
+            apply_series(base, &[&first, &second, &third], &options).unwrap(),
+            v3
+        );
+        check_series(base, &[&first, &second, &third], &options).unwrap();
+        check_series(base, &[&second, &first, &third], &options).unwrap();
+
+        // Swapping the last two patches makes the third one apply before the line it changes
+        // was capitalized by the second
+        let err = check_series(base, &[&first, &third, &second], &options).unwrap_err();
+        assert_eq!(err.patch(), Some(1));
+        assert_eq!(err.culprit(), None);
+        assert_eq!(err.image(), Some(v1.as_bytes()));
+
+        // The second patch conflicts with the third, which changed the line it expects
+        let v3_only = create_patch(base, "a\nb!\nc\nd\ne\nf\ng\nh\ni\nj\n");
+        let err = apply_series(base, &[&first, &v3_only, &second], &options).unwrap_err();
+        assert_eq!(err.patch(), Some(2));
+        assert_eq!(err.culprit(), Some(1));
+        assert_eq!(
+            err.to_string(),
+            "patch #2: error applying hunk #1 (conflicts with patch #1)"
+        );
+    }
+
+    #[test]
+    fn series_error_image_is_before_the_failing_patch() {
+        let base = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let v1 = "a\nb\nc\nd\ne\nf\ng\nh\ni\nJ\n";
+        let first = create_patch(base, v1);
+        // The first hunk of the second patch applies, the one changing "j" doesn't
+        let second = create_patch(base, "A\nb\nc\nd\ne\nf\ng\nh\ni\nj!\n");
+        assert_eq!(second.hunks().len(), 2);
+
+        let options = ApplyOptions::new();
+        let err = apply_series(base, &[&first, &second], &options).unwrap_err();
+        assert_eq!(err.patch(), Some(1));
+        assert_eq!(
+            err.to_string(),
+            "patch #1: error applying hunk #2 (conflicts with patch #0)"
+        );
+        assert_eq!(err.image(), Some(v1.as_bytes()));
+    }
+
+    #[test]
+    fn series_reordering_must_keep_result() {
+        // Without context the second patch still applies to the base, but inserts its line
+        // after "a" instead of after "b"
+        let base = "a\nb\nc\n";
+        let first = "b\nc\n";
+        let second = "b\nx\nc\n";
+        let mut opts = DiffOptions::new();
+        opts.set_context_len(0);
+
+        let mut series = PatchSeries::new();
+        series.push(opts.create_patch(base, first));
+        series.push(opts.create_patch(first, second));
+        assert_eq!(
+            series.reorder(base, &[1, 0]),
+            Err(SeriesError::DifferentResult)
+        );
+        assert_eq!(series.apply_all(base).unwrap(), second);
+    }
+
+    #[test]
+    fn series_bytes() {
+        let base = b"a\n\xff\n";
+        let first = b"a\n\xfe\n";
+        let second = b"b\n\xfe\n";
+        let mut series: PatchSeries<[u8]> = vec![
+            create_patch_bytes(base, first),
+            create_patch_bytes(first, second),
+        ]
+        .into();
+        assert_eq!(series.apply_all(base).unwrap(), second);
+        series.reorder(base, &[1, 0]).unwrap_err();
+        assert_eq!(series.pop().unwrap(), create_patch_bytes(first, second));
+        assert_eq!(series.apply_all(base).unwrap(), first);
+    }
+}
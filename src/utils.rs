@@ -1,18 +1,23 @@
 //! Common utilities
 
-use std::{
-    collections::{hash_map::Entry, HashMap},
-    hash::Hash,
-};
+use alloc::vec::Vec;
+use core::hash::Hash;
+
+#[cfg(feature = "std")]
+use std::collections::{hash_map::Entry, HashMap as Map};
+
+// Without `std` there's no randomly seeded hasher available so fall back to an ordered map
+#[cfg(not(feature = "std"))]
+use alloc::collections::{btree_map::Entry, BTreeMap as Map};
 
 /// Classifies lines, converting lines into unique `u64`s for quicker comparison
 pub struct Classifier<'a, T: ?Sized> {
     next_id: u64,
-    unique_ids: HashMap<&'a T, u64>,
+    unique_ids: Map<&'a T, u64>,
 }
 
-impl<'a, T: ?Sized + Eq + Hash> Classifier<'a, T> {
-    fn classify(&mut self, record: &'a T) -> u64 {
+impl<'a, T: ?Sized + Text> Classifier<'a, T> {
+    pub fn classify(&mut self, record: &'a T) -> u64 {
         match self.unique_ids.entry(record) {
             Entry::Occupied(o) => *o.get(),
             Entry::Vacant(v) => {
@@ -22,21 +27,34 @@ impl<'a, T: ?Sized + Eq + Hash> Classifier<'a, T> {
             }
         }
     }
-}
 
-impl<'a, T: ?Sized + Text> Classifier<'a, T> {
+    /// Returns the id of a previously classified record
+    pub fn get(&self, record: &T) -> Option<u64> {
+        self.unique_ids.get(record).copied()
+    }
+
     pub fn classify_lines(&mut self, text: &'a T) -> (Vec<&'a T>, Vec<u64>) {
+        self.classify_lines_by(text, |line| line)
+    }
+
+    /// Classify lines by the key extracted from each line rather than by the whole line, so
+    /// that lines with equal keys receive the same id
+    pub fn classify_lines_by(
+        &mut self,
+        text: &'a T,
+        key: impl Fn(&'a T) -> &'a T,
+    ) -> (Vec<&'a T>, Vec<u64>) {
         LineIter::new(text)
-            .map(|line| (line, self.classify(line)))
+            .map(|line| (line, self.classify(key(line))))
             .unzip()
     }
 }
 
-impl<T: Eq + Hash + ?Sized> Default for Classifier<'_, T> {
+impl<T: Text + ?Sized> Default for Classifier<'_, T> {
     fn default() -> Self {
         Self {
             next_id: 0,
-            unique_ids: HashMap::default(),
+            unique_ids: Map::default(),
         }
     }
 }
@@ -70,9 +88,38 @@ impl<'a, T: Text + ?Sized> Iterator for LineIter<'a, T> {
     }
 }
 
+/// Split a line into its content and its line terminator (`\n`, `\r\n`, or nothing at all when
+/// the line is the last line of a text that doesn't end with a newline)
+pub fn split_line_ending<T: Text + ?Sized>(line: &T) -> (&T, &T) {
+    let content_len = if let Some(content) = line.strip_suffix("\r\n") {
+        content.len()
+    } else if let Some(content) = line.strip_suffix("\n") {
+        content.len()
+    } else {
+        line.len()
+    };
+
+    line.split_at(content_len)
+}
+
+/// Strip the leading spaces and tabs from a line
+pub fn strip_indentation<T: Text + ?Sized>(line: &T) -> &T {
+    let indent = line
+        .as_bytes()
+        .iter()
+        .take_while(|&&b| b == b' ' || b == b'\t')
+        .count();
+    line.split_at(indent).1
+}
+
+/// Returns `true` if two lines differ, but only in their leading spaces and tabs
+pub fn is_indentation_only<T: Text + ?Sized>(a: &T, b: &T) -> bool {
+    a != b && strip_indentation(a) == strip_indentation(b)
+}
+
 /// A helper trait for processing text like `str` and `[u8]`
 /// Useful for abstracting over those types for parsing as well as breaking input into lines
-pub trait Text: Eq + Hash {
+pub trait Text: Eq + Ord + Hash {
     fn is_empty(&self) -> bool;
     fn len(&self) -> usize;
     fn starts_with(&self, prefix: &str) -> bool;
@@ -83,14 +130,11 @@ pub trait Text: Eq + Hash {
     fn split_at_exclusive(&self, needle: &str) -> Option<(&Self, &Self)>;
     fn find(&self, needle: &str) -> Option<usize>;
     fn split_at(&self, mid: usize) -> (&Self, &Self);
+    fn is_char_boundary(&self, idx: usize) -> bool;
     fn as_str(&self) -> Option<&str>;
     fn as_bytes(&self) -> &[u8];
     #[allow(unused)]
-    fn lines(&self) -> LineIter<Self>;
-
-    fn parse<T: std::str::FromStr>(&self) -> Option<T> {
-        self.as_str().and_then(|s| s.parse().ok())
-    }
+    fn lines(&self) -> LineIter<'_, Self>;
 }
 
 impl Text for str {
@@ -131,6 +175,10 @@ impl Text for str {
         self.split_at(mid)
     }
 
+    fn is_char_boundary(&self, idx: usize) -> bool {
+        self.is_char_boundary(idx)
+    }
+
     fn as_str(&self) -> Option<&str> {
         Some(self)
     }
@@ -139,7 +187,7 @@ impl Text for str {
         self.as_bytes()
     }
 
-    fn lines(&self) -> LineIter<Self> {
+    fn lines(&self) -> LineIter<'_, Self> {
         LineIter::new(self)
     }
 }
@@ -181,15 +229,19 @@ impl Text for [u8] {
         self.split_at(mid)
     }
 
+    fn is_char_boundary(&self, idx: usize) -> bool {
+        idx <= self.len()
+    }
+
     fn as_str(&self) -> Option<&str> {
-        std::str::from_utf8(self).ok()
+        core::str::from_utf8(self).ok()
     }
 
     fn as_bytes(&self) -> &[u8] {
         self
     }
 
-    fn lines(&self) -> LineIter<Self> {
+    fn lines(&self) -> LineIter<'_, Self> {
         LineIter::new(self)
     }
 }